@@ -67,6 +67,8 @@ impl HttpMetaExtractor<Metadata> for MetadataExtractor {
 		Metadata {
 			dapp_id: dapp_id,
 			origin: Origin::Dapps,
+			token: None,
+			remote: Some(request.remote_addr().ip()),
 		}
 	}
 }
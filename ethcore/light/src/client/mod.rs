@@ -139,6 +139,7 @@ impl Client {
 			ancient_block_number: if first_block.is_some() { Some(0) } else { None },
 			first_block_hash: first_block.as_ref().map(|first| first.hash),
 			first_block_number: first_block.as_ref().map(|first| first.number),
+			earliest_body_number: 0,
 		}
 	}
 
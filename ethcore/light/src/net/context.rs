@@ -16,7 +16,7 @@
 
 //! I/O and event context generalizations.
 
-use network::{NetworkContext, PeerId, NodeId};
+use network::{NetworkContext, PeerId, NodeId, PeerReport};
 
 use super::{Announcement, LightProtocol, ReqId};
 use super::error::Error;
@@ -39,6 +39,9 @@ pub trait IoContext {
 	/// Disable a peer -- this is a disconnect + a time-out.
 	fn disable_peer(&self, peer: PeerId);
 
+	/// Report the outcome of an exchange with a peer.
+	fn report_peer(&self, peer: PeerId, report: PeerReport);
+
 	/// Get a peer's protocol version.
 	fn protocol_version(&self, peer: PeerId) -> Option<u8>;
 
@@ -68,6 +71,10 @@ impl<'a> IoContext for NetworkContext<'a> {
 		NetworkContext::disable_peer(self, peer);
 	}
 
+	fn report_peer(&self, peer: PeerId, report: PeerReport) {
+		NetworkContext::report_peer(self, peer, report);
+	}
+
 	fn protocol_version(&self, peer: PeerId) -> Option<u8> {
 		self.protocol_version(self.subprotocol_name(), peer)
 	}
@@ -98,6 +105,9 @@ pub trait BasicContext {
 
 	/// Disable a peer.
 	fn disable_peer(&self, peer: PeerId);
+
+	/// Report the outcome of an exchange with a peer.
+	fn report_peer(&self, peer: PeerId, report: PeerReport);
 }
 
 /// Context for a protocol event which has a peer ID attached.
@@ -142,6 +152,10 @@ impl<'a> BasicContext for TickCtx<'a> {
 	fn disable_peer(&self, peer: PeerId) {
 		self.io.disable_peer(peer);
 	}
+
+	fn report_peer(&self, peer: PeerId, report: PeerReport) {
+		self.io.report_peer(peer, report);
+	}
 }
 
 /// Concrete implementation of `EventContext` over the light protocol struct and
@@ -179,6 +193,10 @@ impl<'a> BasicContext for Ctx<'a> {
 	fn disable_peer(&self, peer: PeerId) {
 		self.io.disable_peer(peer);
 	}
+
+	fn report_peer(&self, peer: PeerId, report: PeerReport) {
+		self.io.report_peer(peer, report);
+	}
 }
 
 impl<'a> EventContext for Ctx<'a> {
@@ -23,7 +23,7 @@ use ethcore::transaction::UnverifiedTransaction;
 use ethcore::receipt::Receipt;
 
 use io::TimerToken;
-use network::{NetworkProtocolHandler, NetworkContext, PeerId};
+use network::{NetworkProtocolHandler, NetworkContext, PeerId, PeerReport, ProtocolId};
 use rlp::{RlpStream, Stream, UntrustedRlp, View};
 use util::hash::H256;
 use util::{Bytes, Mutex, RwLock, U256};
@@ -536,8 +536,11 @@ impl LightProtocol {
 			}
 		};
 
-		if let Err(e) = res {
-			punish(*peer, io, e);
+		match res {
+			Ok(()) => if is_response(packet_id) {
+				io.report_peer(*peer, PeerReport::Success);
+			},
+			Err(e) => punish(*peer, io, e),
 		}
 	}
 
@@ -567,6 +570,7 @@ impl LightProtocol {
 			for (peer_id, peer) in self.peers.read().iter() {
 				if peer.lock().pending_requests.check_timeout(now) {
 					debug!(target: "les", "Peer {} request timeout", peer_id);
+					io.report_peer(*peer_id, PeerReport::Timeout);
 					io.disconnect_peer(*peer_id);
 				}
 			}
@@ -1205,12 +1209,35 @@ fn punish(peer: PeerId, io: &IoContext, e: Error) {
 	}
 }
 
+// whether the packet is a response to one of our requests.
+fn is_response(packet_id: u8) -> bool {
+	match packet_id {
+		packet::BLOCK_HEADERS | packet::BLOCK_BODIES | packet::RECEIPTS |
+		packet::PROOFS | packet::CONTRACT_CODES | packet::HEADER_PROOFS => true,
+		_ => false,
+	}
+}
+
+// discovery topic of the servers of a network, e.g. `les@1`.
+fn discovery_topic(protocol: ProtocolId, network_id: u64) -> Vec<u8> {
+	let mut topic = protocol.to_vec();
+	topic.extend(format!("@{}", network_id).into_bytes());
+	topic
+}
+
 impl NetworkProtocolHandler for LightProtocol {
 	fn initialize(&self, io: &NetworkContext) {
 		io.register_timer(TIMEOUT, TIMEOUT_INTERVAL_MS)
 			.expect("Error registering sync timer.");
 		io.register_timer(TICK_TIMEOUT, TICK_TIMEOUT_INTERVAL_MS)
 			.expect("Error registering sync timer.");
+
+		// servers advertise themselves in node discovery, light clients look them up.
+		let topic = discovery_topic(io.subprotocol_name(), self.network_id);
+		match self.capabilities.read().serve_headers {
+			true => io.register_topic(&topic),
+			false => io.search_topic(&topic),
+		}
 	}
 
 	fn read(&self, io: &NetworkContext, peer: &PeerId, packet_id: u8, data: &[u8]) {
@@ -22,7 +22,7 @@ use ethcore::client::{EachBlockWith, TestBlockChainClient};
 use ethcore::ids::BlockId;
 use ethcore::transaction::PendingTransaction;
 use ethcore::encoded;
-use network::{PeerId, NodeId};
+use network::{PeerId, NodeId, PeerReport};
 
 use net::buffer_flow::FlowParams;
 use net::context::IoContext;
@@ -66,6 +66,8 @@ impl IoContext for Expect {
 		assert_eq!(self, &Expect::Punish(peer));
 	}
 
+	fn report_peer(&self, _peer: PeerId, _report: PeerReport) {}
+
 	fn protocol_version(&self, _peer: PeerId) -> Option<u8> {
 		Some(super::MAX_PROTOCOL_VERSION)
 	}
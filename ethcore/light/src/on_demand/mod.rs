@@ -26,7 +26,7 @@ use ethcore::receipt::Receipt;
 
 use futures::{Async, Poll, Future};
 use futures::sync::oneshot;
-use network::PeerId;
+use network::{PeerId, PeerReport};
 
 use net::{Handler, Status, Capabilities, Announcement, EventContext, BasicContext, ReqId};
 use util::{Bytes, RwLock};
@@ -377,6 +377,8 @@ impl Handler for OnDemand {
 							ctx.disable_peer(peer);
 						}
 					}
+				} else {
+					ctx.report_peer(peer, PeerReport::Useless);
 				}
 
 				self.dispatch_header_by_number(ctx.as_basic(), req, sender);
@@ -405,6 +407,8 @@ impl Handler for OnDemand {
 							ctx.disable_peer(peer);
 						}
 					}
+				} else {
+					ctx.report_peer(peer, PeerReport::Useless);
 				}
 
 				self.dispatch_header_by_hash(ctx.as_basic(), req, sender);
@@ -433,6 +437,8 @@ impl Handler for OnDemand {
 							ctx.disable_peer(peer);
 						}
 					}
+				} else {
+					ctx.report_peer(peer, PeerReport::Useless);
 				}
 
 				self.dispatch_block(ctx.as_basic(), req, sender);
@@ -461,6 +467,8 @@ impl Handler for OnDemand {
 							ctx.disable_peer(peer);
 						}
 					}
+				} else {
+					ctx.report_peer(peer, PeerReport::Useless);
 				}
 
 				self.dispatch_block_receipts(ctx.as_basic(), req, sender);
@@ -489,6 +497,8 @@ impl Handler for OnDemand {
 							ctx.disable_peer(peer);
 						}
 					}
+				} else {
+					ctx.report_peer(peer, PeerReport::Useless);
 				}
 
 				self.dispatch_account(ctx.as_basic(), req, sender);
@@ -503,7 +513,7 @@ mod tests {
 	use super::*;
 	use net::{Announcement, BasicContext, ReqId, Error as LesError};
 	use request::{Request as LesRequest, Kind as LesRequestKind};
-	use network::{PeerId, NodeId};
+	use network::{PeerId, NodeId, PeerReport};
 	use futures::Future;
 	use util::H256;
 
@@ -518,6 +528,7 @@ mod tests {
 		fn max_requests(&self, _: PeerId, _: LesRequestKind) -> usize { 0 }
 		fn disconnect_peer(&self, _: PeerId) { }
 		fn disable_peer(&self, _: PeerId) { }
+		fn report_peer(&self, _: PeerId, _: PeerReport) { }
 	}
 
 	#[test]
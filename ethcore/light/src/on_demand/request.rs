@@ -21,10 +21,9 @@ use ethcore::encoded;
 use ethcore::receipt::Receipt;
 
 use rlp::{RlpStream, Stream, UntrustedRlp, View};
-use util::{Address, Bytes, HashDB, H256};
-use util::memorydb::MemoryDB;
+use util::{Address, Bytes, H256};
 use util::sha3::Hashable;
-use util::trie::{Trie, TrieDB, TrieError};
+use util::trie::{verify_proof, TrieError};
 
 /// Errors in verification.
 #[derive(Debug, PartialEq)]
@@ -67,15 +66,10 @@ pub struct HeaderByNumber {
 impl HeaderByNumber {
 	/// Check a response with a header and cht proof.
 	pub fn check_response(&self, header: &[u8], proof: &[Bytes]) -> Result<encoded::Header, Error> {
-		use util::trie::{Trie, TrieDB};
-
 		// check the proof
-		let mut db = MemoryDB::new();
-
-		for node in proof { db.insert(&node[..]); }
 		let key = ::rlp::encode(&self.num);
 
-		let expected_hash: H256 = match TrieDB::new(&db, &self.cht_root).and_then(|t| t.get(&*key))? {
+		let expected_hash: H256 = match verify_proof(&self.cht_root, &*key, proof)? {
 			Some(val) => ::rlp::decode(&val),
 			None => return Err(Error::BadProof)
 		};
@@ -169,10 +163,7 @@ impl Account {
 	pub fn check_response(&self, proof: &[Bytes]) -> Result<BasicAccount, Error> {
 		let state_root = self.header.state_root();
 
-		let mut db = MemoryDB::new();
-		for node in proof { db.insert(&node[..]); }
-
-		match TrieDB::new(&db, &state_root).and_then(|t| t.get(&self.address.sha3()))? {
+		match verify_proof(&state_root, &self.address.sha3(), proof)? {
 			Some(val) => {
 				let rlp = UntrustedRlp::new(&val);
 				Ok(BasicAccount {
@@ -191,8 +182,7 @@ impl Account {
 mod tests {
 	use super::*;
 	use util::{MemoryDB, Address, H256, FixedHash};
-	use util::trie::{Trie, TrieMut, TrieDB, SecTrieDB, TrieDBMut, SecTrieDBMut};
-	use util::trie::recorder::Recorder;
+	use util::trie::{TrieMut, TrieDB, SecTrieDB, TrieDBMut, SecTrieDBMut};
 
 	use ethcore::header::Header;
 	use ethcore::encoded;
@@ -221,11 +211,7 @@ mod tests {
 		let proof = {
 			let trie = TrieDB::new(&db, &root).unwrap();
 			let key = ::rlp::encode(&10_000u64);
-			let mut recorder = Recorder::new();
-
-			trie.get_with(&*key, &mut recorder).unwrap().unwrap();
-
-			recorder.drain().into_iter().map(|r| r.data).collect::<Vec<_>>()
+			trie.get_proof(&*key).unwrap()
 		};
 
 		let req = HeaderByNumber {
@@ -318,11 +304,7 @@ mod tests {
 
 		let proof = {
 			let trie = SecTrieDB::new(&db, &root).unwrap();
-			let mut recorder = Recorder::new();
-
-			trie.get_with(&*addr, &mut recorder).unwrap().unwrap();
-
-			recorder.drain().into_iter().map(|r| r.data).collect::<Vec<_>>()
+			trie.get_proof(&*addr).unwrap()
 		};
 
 		header.set_state_root(root.clone());
@@ -17,6 +17,7 @@
 //! Account management.
 
 mod stores;
+pub mod typed_data;
 
 use self::stores::{AddressBook, DappsSettingsStore, NewDappsPolicy};
 
@@ -0,0 +1,386 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Typed structured data hashing (EIP-712).
+
+use std::fmt;
+use std::str::FromStr;
+use std::collections::{BTreeMap, BTreeSet};
+use rustc_serialize::hex::FromHex;
+use serde_json::Value;
+use util::{Address, H256, U256, Uint, Hashable, clean_0x};
+
+/// Name of the struct type describing the signing domain.
+pub const DOMAIN_TYPE: &'static str = "EIP712Domain";
+
+/// Member of a struct type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+	/// Member name.
+	pub name: String,
+	/// Member type, e.g. `uint256`, `Person` or `address[]`.
+	pub field_type: String,
+}
+
+/// Struct type definitions keyed by type name.
+pub type Types = BTreeMap<String, Vec<Field>>;
+
+/// Typed structured data to be signed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedData {
+	/// Struct type definitions. Must include `EIP712Domain`.
+	pub types: Types,
+	/// Type of the message.
+	pub primary_type: String,
+	/// Instance of `EIP712Domain`.
+	pub domain: Value,
+	/// Instance of `primary_type`.
+	pub message: Value,
+}
+
+/// Typed data encoding error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+	/// Referenced struct type is not defined.
+	UnknownType(String),
+	/// Type name is malformed or not supported.
+	InvalidType(String),
+	/// Struct instance is missing a member.
+	MissingField(String),
+	/// Value cannot be encoded as the given type.
+	InvalidValue(String, String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+		match *self {
+			Error::UnknownType(ref name) => write!(f, "Unknown type: {}", name),
+			Error::InvalidType(ref name) => write!(f, "Invalid type: {}", name),
+			Error::MissingField(ref name) => write!(f, "Missing field: {}", name),
+			Error::InvalidValue(ref ty, ref value) => write!(f, "Invalid value for {}: {}", ty, value),
+		}
+	}
+}
+
+impl TypedData {
+	/// Returns `hashStruct` of the domain.
+	pub fn domain_separator(&self) -> Result<H256, Error> {
+		hash_struct(&self.types, DOMAIN_TYPE, &self.domain)
+	}
+
+	/// Returns `hashStruct` of the message.
+	pub fn message_hash(&self) -> Result<H256, Error> {
+		hash_struct(&self.types, &self.primary_type, &self.message)
+	}
+
+	/// Returns the hash to be signed: `sha3(0x19 ‖ 0x01 ‖ domainSeparator ‖ hashStruct(message))`.
+	pub fn sign_hash(&self) -> Result<H256, Error> {
+		let mut data = Vec::with_capacity(66);
+		data.extend_from_slice(&[0x19, 0x01]);
+		data.extend_from_slice(&*self.domain_separator()?);
+		data.extend_from_slice(&*self.message_hash()?);
+		Ok(data.sha3())
+	}
+}
+
+/// Encodes the type of `name`, followed by all struct types it references sorted by name,
+/// e.g. `Mail(Person from,Person to,string contents)Person(string name,address wallet)`.
+pub fn encode_type(types: &Types, name: &str) -> Result<String, Error> {
+	let mut deps = BTreeSet::new();
+	collect_dependencies(types, name, &mut deps)?;
+	deps.remove(name);
+
+	let mut encoded = type_signature(types, name)?;
+	for dep in &deps {
+		encoded.push_str(&type_signature(types, dep)?);
+	}
+	Ok(encoded)
+}
+
+/// Returns `sha3(encodeType(name))`.
+pub fn type_hash(types: &Types, name: &str) -> Result<H256, Error> {
+	Ok(encode_type(types, name)?.as_bytes().sha3())
+}
+
+/// Encodes struct instance `value` of type `name` as `typeHash ‖ enc(member_1) ‖ … ‖ enc(member_n)`.
+pub fn encode_data(types: &Types, name: &str, value: &Value) -> Result<Vec<u8>, Error> {
+	let fields = types.get(name).ok_or_else(|| Error::UnknownType(name.to_owned()))?;
+	let object = value.as_object().ok_or_else(|| invalid_value(name, value))?;
+
+	let mut encoded = type_hash(types, name)?.to_vec();
+	for field in fields {
+		let member = object.get(&field.name).ok_or_else(|| Error::MissingField(format!("{}.{}", name, field.name)))?;
+		encoded.extend_from_slice(&*encode_value(types, &field.field_type, member)?);
+	}
+	Ok(encoded)
+}
+
+/// Returns `sha3(encodeData(name, value))`.
+pub fn hash_struct(types: &Types, name: &str, value: &Value) -> Result<H256, Error> {
+	Ok(encode_data(types, name, value)?.sha3())
+}
+
+fn type_signature(types: &Types, name: &str) -> Result<String, Error> {
+	let fields = types.get(name).ok_or_else(|| Error::UnknownType(name.to_owned()))?;
+	let members = fields.iter()
+		.map(|field| format!("{} {}", field.field_type, field.name))
+		.collect::<Vec<_>>();
+	Ok(format!("{}({})", name, members.join(",")))
+}
+
+fn collect_dependencies(types: &Types, name: &str, deps: &mut BTreeSet<String>) -> Result<(), Error> {
+	if deps.contains(name) {
+		return Ok(());
+	}
+
+	let fields = types.get(name).ok_or_else(|| Error::UnknownType(name.to_owned()))?;
+	deps.insert(name.to_owned());
+	for field in fields {
+		let base = match field.field_type.find('[') {
+			Some(pos) => &field.field_type[..pos],
+			None => &field.field_type[..],
+		};
+		if types.contains_key(base) {
+			collect_dependencies(types, base, deps)?;
+		}
+	}
+	Ok(())
+}
+
+fn invalid_value(field_type: &str, value: &Value) -> Error {
+	Error::InvalidValue(field_type.to_owned(), format!("{:?}", value))
+}
+
+/// Encodes a single member value into a 32-byte word.
+fn encode_value(types: &Types, field_type: &str, value: &Value) -> Result<H256, Error> {
+	if field_type.ends_with(']') {
+		let open = field_type.rfind('[').ok_or_else(|| Error::InvalidType(field_type.to_owned()))?;
+		let inner = &field_type[..open];
+		let length = &field_type[open + 1..field_type.len() - 1];
+		let items = value.as_array().ok_or_else(|| invalid_value(field_type, value))?;
+
+		if !length.is_empty() {
+			let length = usize::from_str(length).map_err(|_| Error::InvalidType(field_type.to_owned()))?;
+			if length != items.len() {
+				return Err(invalid_value(field_type, value));
+			}
+		}
+
+		let mut encoded = Vec::with_capacity(items.len() * 32);
+		for item in items {
+			encoded.extend_from_slice(&*encode_value(types, inner, item)?);
+		}
+		return Ok(encoded.sha3());
+	}
+
+	if types.contains_key(field_type) {
+		return hash_struct(types, field_type, value);
+	}
+
+	match field_type {
+		"string" => value.as_str()
+			.map(|s| s.as_bytes().sha3())
+			.ok_or_else(|| invalid_value(field_type, value)),
+		"bytes" => parse_bytes(field_type, value).map(|bytes| bytes.sha3()),
+		"bool" => value.as_bool()
+			.map(|b| H256::from(b as u64))
+			.ok_or_else(|| invalid_value(field_type, value)),
+		"address" => value.as_str()
+			.and_then(|s| Address::from_str(clean_0x(s)).ok())
+			.map(H256::from)
+			.ok_or_else(|| invalid_value(field_type, value)),
+		_ if field_type.starts_with("bytes") => {
+			let size = type_size(field_type, "bytes", 1, 32, 1)?;
+			let bytes = parse_bytes(field_type, value)?;
+			if bytes.len() != size {
+				return Err(invalid_value(field_type, value));
+			}
+			let mut word = H256::default();
+			word[0..size].copy_from_slice(&bytes);
+			Ok(word)
+		},
+		_ if field_type.starts_with("uint") => {
+			let bits = type_size(field_type, "uint", 8, 256, 8)?;
+			let (negative, magnitude) = parse_number(field_type, value)?;
+			if negative || magnitude.bits() > bits {
+				return Err(invalid_value(field_type, value));
+			}
+			Ok(magnitude.into())
+		},
+		_ if field_type.starts_with("int") => {
+			let bits = type_size(field_type, "int", 8, 256, 8)?;
+			let (negative, magnitude) = parse_number(field_type, value)?;
+			let limit = U256::one() << (bits - 1);
+			match negative {
+				false if magnitude < limit => Ok(magnitude.into()),
+				true if magnitude.is_zero() => Ok(H256::default()),
+				// two's complement
+				true if magnitude <= limit => Ok((U256::max_value() - magnitude + U256::one()).into()),
+				_ => Err(invalid_value(field_type, value)),
+			}
+		},
+		_ => Err(Error::UnknownType(field_type.to_owned())),
+	}
+}
+
+/// Parses the size suffix of types like `uint64` or `bytes4`.
+fn type_size(field_type: &str, prefix: &str, min: usize, max: usize, step: usize) -> Result<usize, Error> {
+	let suffix = &field_type[prefix.len()..];
+	if suffix.is_empty() && prefix != "bytes" {
+		return Ok(max);
+	}
+
+	match usize::from_str(suffix) {
+		Ok(size) if size >= min && size <= max && size % step == 0 => Ok(size),
+		_ => Err(Error::InvalidType(field_type.to_owned())),
+	}
+}
+
+fn parse_bytes(field_type: &str, value: &Value) -> Result<Vec<u8>, Error> {
+	value.as_str()
+		.and_then(|s| clean_0x(s).from_hex().ok())
+		.ok_or_else(|| invalid_value(field_type, value))
+}
+
+/// Parses a JSON number, decimal string or `0x`-prefixed hex string into a sign and magnitude.
+fn parse_number(field_type: &str, value: &Value) -> Result<(bool, U256), Error> {
+	match *value {
+		Value::U64(v) => Ok((false, v.into())),
+		Value::I64(v) if v < 0 => Ok((true, U256::from((v as u64).wrapping_neg()))),
+		Value::I64(v) => Ok((false, U256::from(v as u64))),
+		Value::String(ref s) => {
+			let (negative, digits) = match s.starts_with('-') {
+				true => (true, &s[1..]),
+				false => (false, &s[..]),
+			};
+			let magnitude = match digits.starts_with("0x") {
+				true => U256::from_str(&digits[2..]).ok(),
+				false => U256::from_dec_str(digits).ok(),
+			};
+			magnitude.map(|m| (negative, m)).ok_or_else(|| invalid_value(field_type, value))
+		},
+		_ => Err(invalid_value(field_type, value)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+	use serde_json::{self, Value};
+	use util::H256;
+	use super::*;
+
+	fn field(name: &str, field_type: &str) -> Field {
+		Field { name: name.into(), field_type: field_type.into() }
+	}
+
+	// Example from the EIP-712 specification.
+	fn mail() -> TypedData {
+		let mut types = Types::new();
+		types.insert(DOMAIN_TYPE.into(), vec![
+			field("name", "string"),
+			field("version", "string"),
+			field("chainId", "uint256"),
+			field("verifyingContract", "address"),
+		]);
+		types.insert("Person".into(), vec![
+			field("name", "string"),
+			field("wallet", "address"),
+		]);
+		types.insert("Mail".into(), vec![
+			field("from", "Person"),
+			field("to", "Person"),
+			field("contents", "string"),
+		]);
+
+		let domain: Value = serde_json::from_str(r#"{
+			"name": "Ether Mail",
+			"version": "1",
+			"chainId": 1,
+			"verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+		}"#).unwrap();
+		let message: Value = serde_json::from_str(r#"{
+			"from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+			"to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+			"contents": "Hello, Bob!"
+		}"#).unwrap();
+
+		TypedData {
+			types: types,
+			primary_type: "Mail".into(),
+			domain: domain,
+			message: message,
+		}
+	}
+
+	#[test]
+	fn should_encode_type() {
+		let data = mail();
+		assert_eq!(
+			encode_type(&data.types, "Mail").unwrap(),
+			"Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+		);
+		assert_eq!(
+			type_hash(&data.types, "Mail").unwrap(),
+			H256::from_str("a0cedeb2dc280ba39b857546d74f5549c3a1d7bdc2dd96bf881f76108e23dac2").unwrap()
+		);
+	}
+
+	#[test]
+	fn should_hash_message_and_domain() {
+		let data = mail();
+		assert_eq!(
+			data.message_hash().unwrap(),
+			H256::from_str("c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e").unwrap()
+		);
+		assert_eq!(
+			data.domain_separator().unwrap(),
+			H256::from_str("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f").unwrap()
+		);
+		assert_eq!(
+			data.sign_hash().unwrap(),
+			H256::from_str("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2").unwrap()
+		);
+	}
+
+	#[test]
+	fn should_encode_atomic_values() {
+		let types = Types::new();
+		let encode = |ty: &str, json: &str| encode_value(&types, ty, &serde_json::from_str(json).unwrap());
+
+		assert_eq!(encode("bool", "true").unwrap(), H256::from(1));
+		assert_eq!(encode("uint8", "255").unwrap(), H256::from(255));
+		assert_eq!(encode("uint256", r#""0x10""#).unwrap(), H256::from(16));
+		assert_eq!(encode("int8", "-1").unwrap(), H256::from_str("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap());
+		assert_eq!(encode("bytes2", r#""0x0102""#).unwrap(), H256::from_str("0102000000000000000000000000000000000000000000000000000000000000").unwrap());
+
+		assert!(encode("uint8", "256").is_err());
+		assert!(encode("int8", "128").is_err());
+		assert!(encode("bytes2", r#""0x01""#).is_err());
+		assert!(encode("uint7", "1").is_err());
+		assert!(encode("address[2]", r#"["0x0000000000000000000000000000000000000001"]"#).is_err());
+	}
+
+	#[test]
+	fn should_report_missing_fields_and_types() {
+		let mut data = mail();
+		data.message.as_object_mut().unwrap().remove("contents");
+		assert_eq!(data.message_hash(), Err(Error::MissingField("Mail.contents".into())));
+
+		data.primary_type = "Letter".into();
+		assert_eq!(data.message_hash(), Err(Error::UnknownType("Letter".into())));
+	}
+}
@@ -32,6 +32,7 @@ use types::blockchain_info::BlockChainInfo;
 use types::tree_route::TreeRoute;
 use blockchain::update::ExtrasUpdate;
 use blockchain::{CacheSize, ImportRoute, Config};
+use blockchain::freezer::{Freezer, FROZEN_KEY};
 use db::{self, Writable, Readable, CacheUpdatePolicy};
 use cache_manager::CacheManager;
 use engines::Engine;
@@ -39,6 +40,12 @@ use encoded;
 
 const LOG_BLOOMS_LEVELS: usize = 3;
 const LOG_BLOOMS_ELEMENTS_PER_INDEX: usize = 16;
+// Maximal number of blocks moved to the freezer at once.
+const FREEZE_BATCH_SIZE: u64 = 1024;
+// Maximal number of blocks whose history is expired at once.
+const EXPIRE_BATCH_SIZE: u64 = 1024;
+// Key of the earliest block with body and receipts available in `COL_EXTRA`.
+const EXPIRED_KEY: &'static [u8] = b"expired";
 
 /// Interface for querying blocks by hash and by number.
 pub trait BlockProvider {
@@ -192,7 +199,15 @@ pub struct BlockChain {
 	blocks_blooms: RwLock<HashMap<LogGroupPosition, BloomGroup>>,
 	block_receipts: RwLock<HashMap<H256, BlockReceipts>>,
 
-	db: Arc<Database>,
+	db: Arc<KeyValueDB>,
+
+	// Storage of ancient canonical blocks and the number of blocks kept out of it.
+	freezer: Option<Freezer>,
+	freezer_depth: Option<u64>,
+
+	// Earliest non-genesis block with body and receipts available, and the number of blocks kept.
+	earliest_body: RwLock<BlockNumber>,
+	history_retention: Option<u64>,
 
 	cache_man: Mutex<CacheManager<CacheId>>,
 
@@ -259,13 +274,14 @@ impl BlockProvider for BlockChain {
 			}
 		}
 
-		// Read from DB and populate cache
+		// Read from DB or freezer and populate cache
 		let opt = self.db.get(db::COL_HEADERS, hash)
-			.expect("Low level database error. Some issue with disk?");
+			.expect("Low level database error. Some issue with disk?")
+			.map(|b| UntrustedRlp::new(&b).decompress(RlpType::Blocks).to_vec())
+			.or_else(|| self.read_frozen(hash, Freezer::header));
 
 		let result = match opt {
-			Some(b) => {
-				let bytes: Bytes = UntrustedRlp::new(&b).decompress(RlpType::Blocks).to_vec();
+			Some(bytes) => {
 				let mut write = self.block_headers.write();
 				write.insert(hash.clone(), bytes.clone());
 				Some(encoded::Header::new(bytes))
@@ -295,13 +311,14 @@ impl BlockProvider for BlockChain {
 			}
 		}
 
-		// Read from DB and populate cache
+		// Read from DB or freezer and populate cache
 		let opt = self.db.get(db::COL_BODIES, hash)
-			.expect("Low level database error. Some issue with disk?");
+			.expect("Low level database error. Some issue with disk?")
+			.map(|b| UntrustedRlp::new(&b).decompress(RlpType::Blocks).to_vec())
+			.or_else(|| self.read_frozen_history(hash, Freezer::body));
 
 		let result = match opt {
-			Some(b) => {
-				let bytes: Bytes = UntrustedRlp::new(&b).decompress(RlpType::Blocks).to_vec();
+			Some(bytes) => {
 				let mut write = self.block_bodies.write();
 				write.insert(hash.clone(), bytes.clone());
 				Some(encoded::Body::new(bytes))
@@ -337,7 +354,12 @@ impl BlockProvider for BlockChain {
 
 	/// Get receipts of block with given hash.
 	fn block_receipts(&self, hash: &H256) -> Option<BlockReceipts> {
-		let result = self.db.read_with_cache(db::COL_EXTRA, &self.block_receipts, hash);
+		let result = self.db.read_with_cache(db::COL_EXTRA, &self.block_receipts, hash)
+			.or_else(|| self.read_frozen_history(hash, Freezer::receipts).map(|bytes| {
+				let receipts: BlockReceipts = decode(&bytes);
+				self.block_receipts.write().insert(hash.clone(), receipts.clone());
+				receipts
+			}));
 		self.cache_man.lock().note_used(CacheId::BlockReceipts(hash.clone()));
 		result
 	}
@@ -425,10 +447,27 @@ impl<'a> Iterator for AncestryIter<'a> {
 
 impl BlockChain {
 	/// Create new instance of blockchain from given Genesis and block picking rules of Engine.
-	pub fn new(config: Config, genesis: &[u8], db: Arc<Database>, engine: Arc<Engine>) -> BlockChain {
+	pub fn new(config: Config, genesis: &[u8], db: Arc<KeyValueDB>, engine: Arc<Engine>) -> BlockChain {
 		// 400 is the avarage size of the key
 		let cache_man = CacheManager::new(config.pref_cache_size, config.max_cache_size, 400);
 
+		// the database is the source of truth about frozen blocks; anything
+		// appended to the freezer afterwards was never committed.
+		let frozen = db.get(db::COL_EXTRA, FROZEN_KEY).unwrap().map_or(0, |n| decode::<u64>(&n));
+		let freezer = match config.freezer {
+			Some(ref freezer_config) if freezer_config.depth.is_some() || frozen > 0 => {
+				let freezer = Freezer::open(&freezer_config.path).expect("Unable to open ancient block freezer. Some issue with disk?");
+				if freezer.len() < frozen {
+					panic!("Ancient block freezer is missing blocks #{}..#{}. Database corrupt?", freezer.len(), frozen);
+				}
+				freezer.truncate(frozen).expect("Low level freezer error. Some issue with disk?");
+				Some(freezer)
+			},
+			_ if frozen > 0 => panic!("Blocks below #{} are stored in the ancient block freezer, which is not configured.", frozen),
+			_ => None,
+		};
+		let earliest_body = db.get(db::COL_EXTRA, EXPIRED_KEY).unwrap().map_or(0, |n| decode::<u64>(&n));
+
 		let mut bc = BlockChain {
 			blooms_config: bc::Config {
 				levels: LOG_BLOOMS_LEVELS,
@@ -445,6 +484,10 @@ impl BlockChain {
 			blocks_blooms: RwLock::new(HashMap::new()),
 			block_receipts: RwLock::new(HashMap::new()),
 			db: db.clone(),
+			freezer: freezer,
+			freezer_depth: config.freezer.as_ref().and_then(|c| c.depth),
+			earliest_body: RwLock::new(earliest_body),
+			history_retention: config.history_retention,
 			cache_man: Mutex::new(cache_man),
 			pending_best_block: RwLock::new(None),
 			pending_block_hashes: RwLock::new(HashMap::new()),
@@ -472,7 +515,7 @@ impl BlockChain {
 					children: vec![]
 				};
 
-				let mut batch = DBTransaction::new(&db);
+				let mut batch = DBTransaction::new();
 				batch.put(db::COL_HEADERS, &hash, block.header_rlp().as_raw());
 				batch.put(db::COL_BODIES, &hash, &Self::block_to_body(genesis));
 
@@ -553,6 +596,223 @@ impl BlockChain {
 		bc
 	}
 
+	// Read data of a canonical block which was moved to the freezer.
+	fn read_frozen<F>(&self, hash: &H256, read: F) -> Option<Bytes> where F: FnOnce(&Freezer, BlockNumber) -> io::Result<Option<Bytes>> {
+		let freezer = match self.freezer {
+			Some(ref freezer) => freezer,
+			None => return None,
+		};
+
+		match self.block_number(hash) {
+			Some(number) if number < freezer.len() && self.block_hash(number).as_ref() == Some(hash) =>
+				read(freezer, number).expect("Low level freezer error. Some issue with disk?"),
+			_ => None,
+		}
+	}
+
+	// Read body or receipts of a frozen block. The freezer is append-only, so data of
+	// blocks below the expired history is still there, but is not served anymore.
+	fn read_frozen_history<F>(&self, hash: &H256, read: F) -> Option<Bytes> where F: FnOnce(&Freezer, BlockNumber) -> io::Result<Option<Bytes>> {
+		match self.block_number(hash) {
+			Some(number) if number > 0 && number < self.earliest_body_number() => None,
+			_ => self.read_frozen(hash, read),
+		}
+	}
+
+	/// Move canonical blocks which are at least freezer depth behind the best block
+	/// from the database to the freezer. Stops at the first block with missing data,
+	/// so gaps left by warp sync are never frozen. Returns the number of moved blocks.
+	pub fn freeze_ancient(&self, batch: &mut DBTransaction) -> usize {
+		use db::Key;
+		type ReceiptsKey = Key<BlockReceipts, Target=H264>;
+
+		let (freezer, depth) = match (self.freezer.as_ref(), self.freezer_depth) {
+			(Some(freezer), Some(depth)) => (freezer, depth),
+			_ => return 0,
+		};
+
+		let first = freezer.len();
+		let end = match self.best_block_number().checked_sub(depth) {
+			Some(last) if last >= first => cmp::min(last + 1, first + FREEZE_BATCH_SIZE),
+			_ => return 0,
+		};
+
+		let mut number = first;
+		while number < end {
+			let hash = match self.block_hash(number) {
+				Some(hash) => hash,
+				None => break,
+			};
+
+			let get = |col, key: &[u8]| self.db.get(col, key).expect("Low level database error. Some issue with disk?");
+			let receipts_key = ReceiptsKey::key(&hash);
+			let receipts = match get(db::COL_EXTRA, &receipts_key) {
+				Some(receipts) => receipts.to_vec(),
+				// genesis is inserted without receipts.
+				None if number == 0 => encode(&BlockReceipts::new(Vec::new())).to_vec(),
+				None => break,
+			};
+			let (header, body) = match (get(db::COL_HEADERS, &hash), get(db::COL_BODIES, &hash)) {
+				(Some(header), Some(body)) => (header, body),
+				_ => break,
+			};
+
+			freezer.append(
+				number,
+				&UntrustedRlp::new(&header).decompress(RlpType::Blocks),
+				&UntrustedRlp::new(&body).decompress(RlpType::Blocks),
+				&receipts,
+			).expect("Low level freezer error. Some issue with disk?");
+
+			batch.delete(db::COL_HEADERS, &hash);
+			batch.delete(db::COL_BODIES, &hash);
+			batch.delete(db::COL_EXTRA, &receipts_key);
+			number += 1;
+		}
+
+		if number == first {
+			return 0;
+		}
+
+		// blocks are removed from the database only once they are safely on disk.
+		freezer.flush().expect("Low level freezer error. Some issue with disk?");
+		batch.put(db::COL_EXTRA, FROZEN_KEY, &encode(&number));
+		trace!(target: "blockchain", "Moved blocks #{}..#{} to the freezer", first, number);
+		(number - first) as usize
+	}
+
+	/// Number of the earliest block (besides genesis) whose body and receipts were not expired.
+	pub fn earliest_body_number(&self) -> BlockNumber {
+		*self.earliest_body.read()
+	}
+
+	/// Remove bodies, receipts and transaction addresses of canonical blocks which are
+	/// more than history retention behind the best block. Headers and the genesis block
+	/// are always kept. Bodies and receipts of frozen blocks stay in the freezer, but
+	/// are not served anymore. Returns the number of processed blocks.
+	pub fn expire_history(&self, batch: &mut DBTransaction) -> usize {
+		use db::Key;
+		type ReceiptsKey = Key<BlockReceipts, Target=H264>;
+		type AddressKey = Key<TransactionAddress, Target=H264>;
+
+		let retention = match self.history_retention {
+			Some(retention) => retention,
+			None => return 0,
+		};
+
+		let first = cmp::max(self.earliest_body_number(), 1);
+		let end = match self.best_block_number().checked_sub(retention) {
+			Some(end) if end > first => cmp::min(end, first + EXPIRE_BATCH_SIZE),
+			_ => return 0,
+		};
+
+		let mut expired = Vec::new();
+		for number in first..end {
+			// blocks missing after warp sync have nothing to remove.
+			let hash = match self.block_hash(number) {
+				Some(hash) => hash,
+				None => continue,
+			};
+
+			let tx_hashes = self.db.get(db::COL_BODIES, &hash)
+				.expect("Low level database error. Some issue with disk?")
+				.map(|body| UntrustedRlp::new(&body).decompress(RlpType::Blocks).to_vec())
+				.or_else(|| self.read_frozen(&hash, Freezer::body))
+				.map_or_else(Vec::new, |body| BodyView::new(&body).transaction_hashes());
+
+			for tx_hash in &tx_hashes {
+				batch.delete(db::COL_EXTRA, &AddressKey::key(tx_hash));
+			}
+			batch.delete(db::COL_BODIES, &hash);
+			batch.delete(db::COL_EXTRA, &ReceiptsKey::key(&hash));
+			expired.push((hash, tx_hashes));
+		}
+		batch.put(db::COL_EXTRA, EXPIRED_KEY, &encode(&end));
+
+		{
+			let mut block_bodies = self.block_bodies.write();
+			let mut transaction_addresses = self.transaction_addresses.write();
+			let mut block_receipts = self.block_receipts.write();
+			for (hash, tx_hashes) in expired {
+				block_bodies.remove(&hash);
+				block_receipts.remove(&hash);
+				for tx_hash in tx_hashes {
+					transaction_addresses.remove(&tx_hash);
+				}
+			}
+		}
+
+		*self.earliest_body.write() = end;
+		trace!(target: "blockchain", "Expired history of blocks #{}..#{}", first, end);
+		(end - first) as usize
+	}
+
+	/// Make the canonical block with given number the best block. Canonical blocks above it
+	/// lose their number, details, transaction addresses and blooms, so they are treated as
+	/// unknown and can be imported again. `batch` is written together with the rewind, e.g.
+	/// to remove other records of the removed blocks, and dropped if nothing is rewound.
+	/// Returns the hash of the new best block.
+	pub fn rewind_to(&self, mut batch: DBTransaction, number: BlockNumber) -> Option<H256> {
+		use db::Key;
+		type DetailsKey = Key<BlockDetails, Target=H264>;
+		type AddressKey = Key<TransactionAddress, Target=H264>;
+
+		let best = self.best_block_number();
+		if number >= best {
+			return None;
+		}
+		let (hash, details, block) = match self.block_hash(number) {
+			Some(hash) => match (self.block_details(&hash), self.block(&hash)) {
+				(Some(details), Some(block)) => (hash, details, block),
+				_ => return None,
+			},
+			None => return None,
+		};
+
+		for n in number + 1..best + 1 {
+			batch.delete(db::COL_EXTRA, &n.key());
+			let removed = match self.block_hash(n) {
+				Some(removed) => removed,
+				None => continue,
+			};
+			batch.delete(db::COL_EXTRA, &DetailsKey::key(&removed));
+			if let Some(body) = self.block_body(&removed) {
+				for tx_hash in body.transaction_hashes() {
+					batch.delete(db::COL_EXTRA, &AddressKey::key(&tx_hash));
+				}
+			}
+		}
+
+		let range = (number + 1) as bc::Number..best as bc::Number;
+		let chain = bc::group::BloomGroupChain::new(self.blooms_config, self);
+		for (k, v) in chain.replace(&range, vec![]) {
+			batch.write(db::COL_EXTRA, &LogGroupPosition::from(k), &BloomGroup::from(v));
+		}
+
+		let mut new_details = details.clone();
+		new_details.children.clear();
+		batch.write(db::COL_EXTRA, &hash, &new_details);
+		batch.put(db::COL_EXTRA, b"best", &hash);
+		self.db.write(batch).expect("Low level database error. Some issue with disk?");
+
+		{
+			let mut best_block = self.best_block.write();
+			*best_block = BestBlock {
+				number: number,
+				total_difficulty: details.total_difficulty,
+				hash: hash,
+				block: block.into_inner(),
+			};
+		}
+
+		self.block_details.write().clear();
+		self.block_hashes.write().clear();
+		self.transaction_addresses.write().clear();
+		self.blocks_blooms.write().clear();
+		trace!(target: "blockchain", "Rewound best block from #{} to #{}", best, number);
+		Some(hash)
+	}
+
 	/// Returns true if the given parent block has given child
 	/// (though not necessarily a part of the canon chain).
 	fn is_known_child(&self, parent: &H256, hash: &H256) -> bool {
@@ -797,6 +1057,13 @@ impl BlockChain {
 		}
 	}
 
+	/// Forget the best ancient block, once the gap between the ancient blocks and the
+	/// best block has been filled by other means.
+	pub fn clear_best_ancient_block(&self, batch: &mut DBTransaction) {
+		batch.delete(db::COL_EXTRA, b"ancient");
+		*self.best_ancient_block.write() = None;
+	}
+
 	/// Add a child to a given block. Assumes that the block hash is in
 	/// the chain and the child's parent is this block.
 	///
@@ -1302,11 +1569,12 @@ impl BlockChain {
 			first_block_number: From::from(self.first_block_number()),
 			ancient_block_hash: best_ancient_block.as_ref().map(|b| b.hash.clone()),
 			ancient_block_number: best_ancient_block.as_ref().map(|b| b.number),
+			earliest_body_number: self.earliest_body_number(),
 		}
 	}
 
 	#[cfg(test)]
-	pub fn db(&self) -> &Arc<Database> {
+	pub fn db(&self) -> &Arc<KeyValueDB> {
 		&self.db
 	}
 }
@@ -1316,13 +1584,13 @@ mod tests {
 	#![cfg_attr(feature="dev", allow(similar_names))]
 	use std::sync::Arc;
 	use rustc_serialize::hex::FromHex;
-	use util::{Database, DatabaseConfig};
+	use util::kvdb::KeyValueDB;
 	use util::hash::*;
 	use util::sha3::Hashable;
 	use receipt::Receipt;
-	use blockchain::{BlockProvider, BlockChain, Config, ImportRoute};
+	use blockchain::{BlockProvider, BlockChain, Config, ImportRoute, FreezerConfig};
 	use tests::helpers::*;
-	use devtools::*;
+	use devtools::RandomTempPath;
 	use blockchain::generator::{ChainGenerator, ChainIterator, BlockFinalizer};
 	use blockchain::extras::TransactionAddress;
 	use views::BlockView;
@@ -1331,11 +1599,11 @@ mod tests {
 	use spec::Spec;
 	use ethkey::Secret;
 
-	fn new_db(path: &str) -> Arc<Database> {
-		Arc::new(Database::open(&DatabaseConfig::with_columns(::db::NUM_COLUMNS), path).unwrap())
+	fn new_db() -> Arc<KeyValueDB> {
+		Arc::new(::util::kvdb::in_memory(::db::NUM_COLUMNS.unwrap_or(0)))
 	}
 
-	fn new_chain(genesis: &[u8], db: Arc<Database>) -> BlockChain {
+	fn new_chain(genesis: &[u8], db: Arc<KeyValueDB>) -> BlockChain {
 		BlockChain::new(Config::default(), genesis, db, Spec::new_null().engine)
 	}
 
@@ -1347,8 +1615,7 @@ mod tests {
 		let genesis = canon_chain.generate(&mut finalizer).unwrap();
 		let first = canon_chain.generate(&mut finalizer).unwrap();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 		assert_eq!(bc.best_block_number(), 0);
 
@@ -1373,8 +1640,7 @@ mod tests {
 		let genesis_hash = BlockView::new(&genesis).header_view().sha3();
 		let first_hash = BlockView::new(&first).header_view().sha3();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 
 		assert_eq!(bc.genesis_hash(), genesis_hash.clone());
@@ -1404,8 +1670,7 @@ mod tests {
 		let genesis = canon_chain.generate(&mut finalizer).unwrap();
 		let genesis_hash = BlockView::new(&genesis).header_view().sha3();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 
 		let mut block_hashes = vec![genesis_hash.clone()];
@@ -1440,8 +1705,7 @@ mod tests {
 		let b5b = canon_chain.fork(1).generate(&mut finalizer.fork()).unwrap();
 		let b5a = canon_chain.generate(&mut finalizer).unwrap();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 
 		let mut batch =db.transaction();
@@ -1506,8 +1770,7 @@ mod tests {
 
 		let t1_hash = t1.hash();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 
 		let mut batch = db.transaction();
@@ -1594,8 +1857,7 @@ mod tests {
 		let t2_hash = t2.hash();
 		let t3_hash = t3.hash();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 
 		let mut batch = db.transaction();
@@ -1656,8 +1918,7 @@ mod tests {
 		// b3a is a part of canon chain, whereas b3b is part of sidechain
 		let best_block_hash = b3a_hash.clone();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 
 		let mut batch = db.transaction();
@@ -1771,9 +2032,8 @@ mod tests {
 		let genesis_hash = BlockView::new(&genesis).header_view().sha3();
 		let first_hash = BlockView::new(&first).header_view().sha3();
 
-		let temp = RandomTempPath::new();
+		let db = new_db();
 		{
-			let db = new_db(temp.as_str());
 			let bc = new_chain(&genesis, db.clone());
 			assert_eq!(bc.best_block_hash(), genesis_hash);
 			let mut batch =db.transaction();
@@ -1784,7 +2044,6 @@ mod tests {
 		}
 
 		{
-			let db = new_db(temp.as_str());
 			let bc = new_chain(&genesis, db.clone());
 
 			assert_eq!(bc.best_block_hash(), first_hash);
@@ -1838,8 +2097,7 @@ mod tests {
 		let b1 = "f904a8f901faa0ce1f26f798dd03c8782d63b3e42e79a64eaea5694ea686ac5d7ce3df5171d1aea01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347948888f1f195afa192cfee860698584c030f4c9db1a0a65c2364cd0f1542d761823dc0109c6b072f14c20459598c5455c274601438f4a070616ebd7ad2ed6fb7860cf7e9df00163842351c38a87cac2c1cb193895035a2a05c5b4fc43c2d45787f54e1ae7d27afdb4ad16dfc567c5692070d5c4556e0b1d7b9010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000830200000183023ec683021536845685109780a029f07836e4e59229b3a065913afc27702642c683bba689910b2b2fd45db310d3888957e6d004a31802f902a7f85f800a8255f094aaaf5374fce5edbc8e2a8697c15331677e6ebf0b0a801ca0575da4e21b66fa764be5f74da9389e67693d066fb0d1312e19e17e501da00ecda06baf5a5327595f6619dfc2fcb3f2e6fb410b5810af3cb52d0e7508038e91a188f85f010a82520894bbbf5374fce5edbc8e2a8697c15331677e6ebf0b0a801ba04fa966bf34b93abc1bcd665554b7f316b50f928477b50be0f3285ead29d18c5ba017bba0eeec1625ab433746955e125d46d80b7fdc97386c51266f842d8e02192ef85f020a82520894bbbf5374fce5edbc8e2a8697c15331677e6ebf0b0a801ca004377418ae981cc32b1312b4a427a1d69a821b28db8584f5f2bd8c6d42458adaa053a1dba1af177fac92f3b6af0a9fa46a22adf56e686c93794b6a012bf254abf5f85f030a82520894bbbf5374fce5edbc8e2a8697c15331677e6ebf0b0a801ca04fe13febd28a05f4fcb2f451d7ddc2dda56486d9f8c79a62b0ba4da775122615a0651b2382dd402df9ebc27f8cb4b2e0f3cea68dda2dca0ee9603608f0b6f51668f85f040a82520894bbbf5374fce5edbc8e2a8697c15331677e6ebf0b0a801ba078e6a0ba086a08f8450e208a399bb2f2d2a0d984acd2517c7c7df66ccfab567da013254002cd45a97fac049ae00afbc43ed0d9961d0c56a3b2382c80ce41c198ddf85f050a82520894bbbf5374fce5edbc8e2a8697c15331677e6ebf0b0a801ba0a7174d8f43ea71c8e3ca9477691add8d80ac8e0ed89d8d8b572041eef81f4a54a0534ea2e28ec4da3b5b944b18c51ec84a5cf35f5b3343c5fb86521fd2d388f506f85f060a82520894bbbf5374fce5edbc8e2a8697c15331677e6ebf0b0a801ba034bd04065833536a10c77ee2a43a5371bc6d34837088b861dd9d4b7f44074b59a078807715786a13876d3455716a6b9cb2186b7a4887a5c31160fc877454958616c0".from_hex().unwrap();
 		let b1_hash: H256 = "f53f268d23a71e85c7d6d83a9504298712b84c1a2ba220441c86eeda0bf0b6e3".into();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 		let mut batch =db.transaction();
 		bc.insert_block(&mut batch, &b1, vec![]);
@@ -1853,7 +2111,7 @@ mod tests {
 		}
 	}
 
-	fn insert_block(db: &Arc<Database>, bc: &BlockChain, bytes: &[u8], receipts: Vec<Receipt>) -> ImportRoute {
+	fn insert_block(db: &Arc<KeyValueDB>, bc: &BlockChain, bytes: &[u8], receipts: Vec<Receipt>) -> ImportRoute {
 		let mut batch = db.transaction();
 		let res = bc.insert_block(&mut batch, bytes, receipts);
 		db.write(batch).unwrap();
@@ -1898,8 +2156,7 @@ mod tests {
 		let b1 = canon_chain.with_transaction(t1).with_transaction(t2).generate(&mut finalizer).unwrap();
 		let b2 = canon_chain.with_transaction(t3).generate(&mut finalizer).unwrap();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 		insert_block(&db, &bc, &b1, vec![Receipt {
 			state_root: H256::default(),
@@ -2007,8 +2264,7 @@ mod tests {
 		let b1a = canon_chain.with_bloom(bloom_ba.clone()).generate(&mut finalizer).unwrap();
 		let b2a = canon_chain.with_bloom(bloom_ba.clone()).generate(&mut finalizer).unwrap();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 
 		let blocks_b1 = bc.blocks_with_bloom(&bloom_b1, 0, 5);
@@ -2062,10 +2318,9 @@ mod tests {
 		let mut finalizer = BlockFinalizer::default();
 		let genesis = canon_chain.generate(&mut finalizer).unwrap();
 
-		let temp = RandomTempPath::new();
+		let db = new_db();
 
 		{
-			let db = new_db(temp.as_str());
 			let bc = new_chain(&genesis, db.clone());
 			let uncle = canon_chain.fork(1).generate(&mut finalizer.fork()).unwrap();
 
@@ -2084,7 +2339,6 @@ mod tests {
 		}
 
 		// re-loading the blockchain should load the correct best block.
-		let db = new_db(temp.as_str());
 		let bc = new_chain(&genesis, db.clone());
 		assert_eq!(bc.best_block_number(), 5);
 	}
@@ -2100,8 +2354,7 @@ mod tests {
 		let first_hash = BlockView::new(&first).header_view().sha3();
 		let second_hash = BlockView::new(&second).header_view().sha3();
 
-		let temp = RandomTempPath::new();
-		let db = new_db(temp.as_str());
+		let db = new_db();
 		let bc = new_chain(&genesis, db.clone());
 
 		let mut batch =db.transaction();
@@ -2119,4 +2372,209 @@ mod tests {
 		assert_eq!(bc.rewind(), Some(genesis_hash.clone()));
 		assert_eq!(bc.rewind(), None);
 	}
+
+	#[test]
+	fn freezes_ancient_blocks() {
+		let mut canon_chain = ChainGenerator::default();
+		let mut finalizer = BlockFinalizer::default();
+		let genesis = canon_chain.generate(&mut finalizer).unwrap();
+		let blocks: Vec<_> = (0..5).map(|_| canon_chain.generate(&mut finalizer).unwrap()).collect();
+		let hashes: Vec<_> = blocks.iter().map(|b| BlockView::new(b).header_view().sha3()).collect();
+
+		let temp = RandomTempPath::create_dir();
+		let config = Config {
+			freezer: Some(FreezerConfig {
+				path: temp.as_path().clone(),
+				depth: Some(2),
+			}),
+			..Config::default()
+		};
+
+		let db = new_db();
+		{
+			let bc = BlockChain::new(config.clone(), &genesis, db.clone(), Spec::new_null().engine);
+			for block in &blocks {
+				insert_block(&db, &bc, block, vec![]);
+			}
+			assert_eq!(bc.best_block_number(), 5);
+
+			let mut batch = db.transaction();
+			assert_eq!(bc.freeze_ancient(&mut batch), 4);
+			db.write(batch).unwrap();
+
+			let mut batch = db.transaction();
+			assert_eq!(bc.freeze_ancient(&mut batch), 0);
+		}
+
+		// genesis and blocks 1..3 are served from the freezer only.
+		assert!(db.get(::db::COL_HEADERS, &hashes[2]).unwrap().is_none());
+		assert!(db.get(::db::COL_BODIES, &hashes[0]).unwrap().is_none());
+		assert!(db.get(::db::COL_HEADERS, &hashes[3]).unwrap().is_some());
+
+		let bc = BlockChain::new(config, &genesis, db.clone(), Spec::new_null().engine);
+		assert_eq!(bc.best_block_number(), 5);
+		assert_eq!(bc.genesis_header().number(), 0);
+		for (block, hash) in blocks.iter().zip(hashes.iter()) {
+			assert_eq!(&bc.block(hash).unwrap().into_inner(), block);
+			assert!(bc.block_receipts(hash).is_some());
+		}
+	}
+
+	#[test]
+	fn expires_history() {
+		let mut canon_chain = ChainGenerator::default();
+		let mut finalizer = BlockFinalizer::default();
+		let genesis = canon_chain.generate(&mut finalizer).unwrap();
+
+		let t1 = Transaction {
+			nonce: 0.into(),
+			gas_price: 0.into(),
+			gas: 100_000.into(),
+			action: Action::Create,
+			value: 100.into(),
+			data: "601080600c6000396000f3006000355415600957005b60203560003555".from_hex().unwrap(),
+		}.sign(&secret(), None);
+
+		let mut blocks = vec![canon_chain.with_transaction(t1.clone()).generate(&mut finalizer).unwrap()];
+		blocks.extend((0..5).map(|_| canon_chain.generate(&mut finalizer).unwrap()));
+		let hashes: Vec<_> = blocks.iter().map(|b| BlockView::new(b).header_view().sha3()).collect();
+
+		let config = Config {
+			history_retention: Some(2),
+			..Config::default()
+		};
+
+		let db = new_db();
+		{
+			let bc = BlockChain::new(config.clone(), &genesis, db.clone(), Spec::new_null().engine);
+			for block in &blocks {
+				insert_block(&db, &bc, block, vec![]);
+			}
+			assert_eq!(bc.best_block_number(), 6);
+			assert!(bc.transaction_address(&t1.hash()).is_some());
+
+			let mut batch = db.transaction();
+			assert_eq!(bc.expire_history(&mut batch), 3);
+			db.write(batch).unwrap();
+
+			let mut batch = db.transaction();
+			assert_eq!(bc.expire_history(&mut batch), 0);
+			assert_eq!(bc.chain_info().earliest_body_number, 4);
+		}
+
+		let bc = BlockChain::new(config, &genesis, db.clone(), Spec::new_null().engine);
+		assert_eq!(bc.earliest_body_number(), 4);
+		assert!(bc.block_body(&bc.genesis_hash()).is_some());
+		assert!(bc.transaction_address(&t1.hash()).is_none());
+		for hash in &hashes[..3] {
+			assert!(bc.block_header(hash).is_some());
+			assert!(bc.block_body(hash).is_none());
+			assert!(bc.block_receipts(hash).is_none());
+		}
+		for (block, hash) in blocks[3..].iter().zip(hashes[3..].iter()) {
+			assert_eq!(&bc.block(hash).unwrap().into_inner(), block);
+		}
+	}
+
+	#[test]
+	fn expires_frozen_history() {
+		let mut canon_chain = ChainGenerator::default();
+		let mut finalizer = BlockFinalizer::default();
+		let genesis = canon_chain.generate(&mut finalizer).unwrap();
+
+		let t1 = Transaction {
+			nonce: 0.into(),
+			gas_price: 0.into(),
+			gas: 100_000.into(),
+			action: Action::Create,
+			value: 100.into(),
+			data: "601080600c6000396000f3006000355415600957005b60203560003555".from_hex().unwrap(),
+		}.sign(&secret(), None);
+
+		let mut blocks = vec![canon_chain.with_transaction(t1.clone()).generate(&mut finalizer).unwrap()];
+		blocks.extend((0..5).map(|_| canon_chain.generate(&mut finalizer).unwrap()));
+		let hashes: Vec<_> = blocks.iter().map(|b| BlockView::new(b).header_view().sha3()).collect();
+
+		let temp = RandomTempPath::create_dir();
+		let config = Config {
+			history_retention: Some(2),
+			freezer: Some(FreezerConfig {
+				path: temp.as_path().clone(),
+				depth: Some(3),
+			}),
+			..Config::default()
+		};
+
+		let db = new_db();
+		let bc = BlockChain::new(config, &genesis, db.clone(), Spec::new_null().engine);
+		for block in &blocks {
+			insert_block(&db, &bc, block, vec![]);
+		}
+
+		// genesis and blocks 1..3 are frozen, blocks 1..3 are expired.
+		let mut batch = db.transaction();
+		assert_eq!(bc.freeze_ancient(&mut batch), 4);
+		db.write(batch).unwrap();
+		assert!(bc.block_body(&hashes[0]).is_some());
+
+		let mut batch = db.transaction();
+		assert_eq!(bc.expire_history(&mut batch), 3);
+		db.write(batch).unwrap();
+
+		assert!(bc.block_body(&bc.genesis_hash()).is_some());
+		assert!(bc.transaction_address(&t1.hash()).is_none());
+		for hash in &hashes[..3] {
+			assert!(bc.block_header(hash).is_some());
+			assert!(bc.block_body(hash).is_none());
+			assert!(bc.block_receipts(hash).is_none());
+		}
+		for (block, hash) in blocks[3..].iter().zip(hashes[3..].iter()) {
+			assert_eq!(&bc.block(hash).unwrap().into_inner(), block);
+		}
+	}
+
+	#[test]
+	fn rewinds_to_canonical_block() {
+		let mut canon_chain = ChainGenerator::default();
+		let mut finalizer = BlockFinalizer::default();
+		let genesis = canon_chain.generate(&mut finalizer).unwrap();
+
+		let t1 = Transaction {
+			nonce: 0.into(),
+			gas_price: 0.into(),
+			gas: 100_000.into(),
+			action: Action::Create,
+			value: 100.into(),
+			data: "601080600c6000396000f3006000355415600957005b60203560003555".from_hex().unwrap(),
+		}.sign(&secret(), None);
+
+		let b1 = canon_chain.generate(&mut finalizer).unwrap();
+		let b2 = canon_chain.with_transaction(t1.clone()).generate(&mut finalizer).unwrap();
+		let b3 = canon_chain.generate(&mut finalizer).unwrap();
+		let b1_hash = BlockView::new(&b1).header_view().sha3();
+		let b2_hash = BlockView::new(&b2).header_view().sha3();
+
+		let db = new_db();
+		{
+			let bc = new_chain(&genesis, db.clone());
+			insert_block(&db, &bc, &b1, vec![]);
+			insert_block(&db, &bc, &b2, vec![]);
+			insert_block(&db, &bc, &b3, vec![]);
+			assert_eq!(bc.best_block_number(), 3);
+
+			assert_eq!(bc.rewind_to(db.transaction(), 3), None);
+			assert_eq!(bc.rewind_to(db.transaction(), 1), Some(b1_hash));
+			assert_eq!(bc.best_block_number(), 1);
+			assert_eq!(bc.block_hash(2), None);
+			assert!(!bc.is_known(&b2_hash));
+			assert!(bc.transaction_address(&t1.hash()).is_none());
+			assert!(bc.block_details(&b1_hash).unwrap().children.is_empty());
+		}
+
+		let bc = new_chain(&genesis, db.clone());
+		assert_eq!(bc.best_block_hash(), b1_hash);
+		insert_block(&db, &bc, &b2, vec![]);
+		assert_eq!(bc.best_block_number(), 2);
+		assert_eq!(bc.transaction_address(&t1.hash()), Some(TransactionAddress { block_hash: b2_hash, index: 0 }));
+	}
 }
@@ -16,6 +16,8 @@
 
 //! Blockchain configuration.
 
+use super::freezer::FreezerConfig;
+
 /// Blockchain configuration.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Config {
@@ -25,6 +27,11 @@ pub struct Config {
 	pub max_cache_size: usize,
 	/// Backing db cache_size
 	pub db_cache_size: Option<usize>,
+	/// Ancient block freezer.
+	pub freezer: Option<FreezerConfig>,
+	/// Number of most recent blocks whose bodies and receipts are kept.
+	/// `None` keeps the whole history.
+	pub history_retention: Option<u64>,
 }
 
 impl Default for Config {
@@ -33,6 +40,8 @@ impl Default for Config {
 			pref_cache_size: 1 << 14,
 			max_cache_size: 1 << 20,
 			db_cache_size: None,
+			freezer: None,
+			history_retention: None,
 		}
 	}
 }
@@ -0,0 +1,276 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Append-only flat-file storage for ancient blocks.
+//!
+//! Canonical blocks deep enough to never be reorganised are moved out of
+//! the key-value database into a "freezer". Each kind of data (headers,
+//! bodies and receipts) lives in its own table, which consists of two files:
+//!
+//! 	`<name>.dat`: concatenated snappy-compressed entries.
+//! 	`<name>.idx`: end offset of every entry in the data file (8 bytes little-endian).
+//!
+//! Entries are indexed by block number, starting at the genesis block.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{LittleEndian, ByteOrder};
+use header::BlockNumber;
+use util::{Bytes, Mutex, snappy};
+
+const INDEX_ENTRY_SIZE: u64 = 8;
+
+/// Key of the number of frozen blocks in `COL_EXTRA`.
+/// Block data below this number is removed from the database.
+pub const FROZEN_KEY: &'static [u8] = b"frozen";
+
+/// Freezer configuration.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FreezerConfig {
+	/// Directory holding the freezer files.
+	pub path: PathBuf,
+	/// Number of most recent blocks which are never moved to the freezer.
+	/// `None` disables freezing of new blocks, but already frozen ones are still served.
+	pub depth: Option<u64>,
+}
+
+// A single append-only table.
+struct Table {
+	index: File,
+	data: File,
+	len: u64,
+	data_len: u64,
+}
+
+impl Table {
+	fn open(dir: &Path, name: &str) -> io::Result<Self> {
+		let open = |ext: &str| OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.open(dir.join(format!("{}.{}", name, ext)));
+
+		let mut table = Table {
+			index: open("idx")?,
+			data: open("dat")?,
+			len: 0,
+			data_len: 0,
+		};
+
+		// discard partially written entries, if any.
+		let mut len = table.index.metadata()?.len() / INDEX_ENTRY_SIZE;
+		let data_len = table.data.metadata()?.len();
+		while len > 0 && table.end_offset(len - 1)? > data_len {
+			len -= 1;
+		}
+		table.truncate(len)?;
+		Ok(table)
+	}
+
+	fn end_offset(&mut self, number: u64) -> io::Result<u64> {
+		let mut buf = [0u8; INDEX_ENTRY_SIZE as usize];
+		self.index.seek(SeekFrom::Start(number * INDEX_ENTRY_SIZE))?;
+		self.index.read_exact(&mut buf)?;
+		Ok(LittleEndian::read_u64(&buf))
+	}
+
+	fn start_offset(&mut self, number: u64) -> io::Result<u64> {
+		match number {
+			0 => Ok(0),
+			n => self.end_offset(n - 1),
+		}
+	}
+
+	fn truncate(&mut self, len: u64) -> io::Result<()> {
+		let data_len = self.start_offset(len)?;
+		self.index.set_len(len * INDEX_ENTRY_SIZE)?;
+		self.data.set_len(data_len)?;
+		self.len = len;
+		self.data_len = data_len;
+		Ok(())
+	}
+
+	fn append(&mut self, value: &[u8]) -> io::Result<()> {
+		let compressed = snappy::compress(value);
+		self.data.seek(SeekFrom::Start(self.data_len))?;
+		self.data.write_all(&compressed)?;
+
+		let mut buf = [0u8; INDEX_ENTRY_SIZE as usize];
+		LittleEndian::write_u64(&mut buf, self.data_len + compressed.len() as u64);
+		self.index.seek(SeekFrom::Start(self.len * INDEX_ENTRY_SIZE))?;
+		self.index.write_all(&buf)?;
+
+		self.data_len += compressed.len() as u64;
+		self.len += 1;
+		Ok(())
+	}
+
+	fn get(&mut self, number: u64) -> io::Result<Option<Bytes>> {
+		if number >= self.len {
+			return Ok(None);
+		}
+
+		let start = self.start_offset(number)?;
+		let end = self.end_offset(number)?;
+		let mut compressed = vec![0u8; (end - start) as usize];
+		self.data.seek(SeekFrom::Start(start))?;
+		self.data.read_exact(&mut compressed)?;
+
+		snappy::decompress(&compressed)
+			.map(Some)
+			.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Corrupted freezer entry"))
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.data.sync_data()?;
+		self.index.sync_data()
+	}
+}
+
+struct Tables {
+	headers: Table,
+	bodies: Table,
+	receipts: Table,
+}
+
+/// Ancient block storage.
+pub struct Freezer {
+	tables: Mutex<Tables>,
+}
+
+impl Freezer {
+	/// Open the freezer at the given directory, creating it if necessary.
+	pub fn open(path: &Path) -> io::Result<Self> {
+		fs::create_dir_all(path)?;
+
+		let mut tables = Tables {
+			headers: Table::open(path, "headers")?,
+			bodies: Table::open(path, "bodies")?,
+			receipts: Table::open(path, "receipts")?,
+		};
+
+		// tables could have been left with different lengths by an interrupted append.
+		let len = ::std::cmp::min(tables.headers.len, ::std::cmp::min(tables.bodies.len, tables.receipts.len));
+		tables.headers.truncate(len)?;
+		tables.bodies.truncate(len)?;
+		tables.receipts.truncate(len)?;
+
+		Ok(Freezer {
+			tables: Mutex::new(tables),
+		})
+	}
+
+	/// Number of frozen blocks. All blocks below this number are in the freezer.
+	pub fn len(&self) -> u64 {
+		self.tables.lock().headers.len
+	}
+
+	/// Append the next block. Its number must be equal to the current `len`.
+	pub fn append(&self, number: BlockNumber, header: &[u8], body: &[u8], receipts: &[u8]) -> io::Result<()> {
+		let mut tables = self.tables.lock();
+		if number != tables.headers.len {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Expected block #{}, got #{}", tables.headers.len, number)));
+		}
+
+		tables.headers.append(header)?;
+		tables.bodies.append(body)?;
+		tables.receipts.append(receipts)
+	}
+
+	/// Drop all blocks starting from the given number.
+	pub fn truncate(&self, len: u64) -> io::Result<()> {
+		let mut tables = self.tables.lock();
+		if len >= tables.headers.len {
+			return Ok(());
+		}
+
+		tables.headers.truncate(len)?;
+		tables.bodies.truncate(len)?;
+		tables.receipts.truncate(len)
+	}
+
+	/// Get header RLP of the frozen block.
+	pub fn header(&self, number: BlockNumber) -> io::Result<Option<Bytes>> {
+		self.tables.lock().headers.get(number)
+	}
+
+	/// Get body RLP of the frozen block.
+	pub fn body(&self, number: BlockNumber) -> io::Result<Option<Bytes>> {
+		self.tables.lock().bodies.get(number)
+	}
+
+	/// Get receipts RLP of the frozen block.
+	pub fn receipts(&self, number: BlockNumber) -> io::Result<Option<Bytes>> {
+		self.tables.lock().receipts.get(number)
+	}
+
+	/// Make sure that all appended blocks reached the disk.
+	pub fn flush(&self) -> io::Result<()> {
+		let mut tables = self.tables.lock();
+		tables.headers.flush()?;
+		tables.bodies.flush()?;
+		tables.receipts.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use devtools::RandomTempPath;
+	use super::Freezer;
+
+	#[test]
+	fn append_and_read() {
+		let path = RandomTempPath::create_dir();
+		let freezer = Freezer::open(path.as_path()).unwrap();
+		assert_eq!(freezer.len(), 0);
+
+		freezer.append(0, b"header0", b"body0", b"receipts0").unwrap();
+		freezer.append(1, b"header1", b"", b"receipts1").unwrap();
+		assert!(freezer.append(3, b"header3", b"body3", b"receipts3").is_err());
+
+		assert_eq!(freezer.len(), 2);
+		assert_eq!(freezer.header(0).unwrap().unwrap(), b"header0".to_vec());
+		assert_eq!(freezer.body(1).unwrap().unwrap(), Vec::<u8>::new());
+		assert_eq!(freezer.receipts(1).unwrap().unwrap(), b"receipts1".to_vec());
+		assert!(freezer.header(2).unwrap().is_none());
+	}
+
+	#[test]
+	fn reopen_and_truncate() {
+		let path = RandomTempPath::create_dir();
+		{
+			let freezer = Freezer::open(path.as_path()).unwrap();
+			for i in 0..10u8 {
+				freezer.append(i as u64, &[i; 100], &[i; 10], &[i]).unwrap();
+			}
+			freezer.flush().unwrap();
+		}
+
+		let freezer = Freezer::open(path.as_path()).unwrap();
+		assert_eq!(freezer.len(), 10);
+		assert_eq!(freezer.header(9).unwrap().unwrap(), vec![9u8; 100]);
+
+		freezer.truncate(5).unwrap();
+		assert_eq!(freezer.len(), 5);
+		assert!(freezer.body(5).unwrap().is_none());
+
+		freezer.append(5, b"header5", b"body5", b"receipts5").unwrap();
+		assert_eq!(freezer.body(5).unwrap().unwrap(), b"body5".to_vec());
+		assert_eq!(freezer.body(4).unwrap().unwrap(), vec![4u8; 10]);
+	}
+}
@@ -21,6 +21,7 @@ mod block_info;
 pub mod blockchain;
 mod cache;
 mod config;
+mod freezer;
 pub mod extras;
 mod import_route;
 mod update;
@@ -31,5 +32,6 @@ pub mod generator;
 pub use self::blockchain::{BlockProvider, BlockChain};
 pub use self::cache::CacheSize;
 pub use self::config::Config;
+pub use self::freezer::{Freezer, FreezerConfig, FROZEN_KEY};
 pub use types::tree_route::TreeRoute;
 pub use self::import_route::ImportRoute;
@@ -0,0 +1,303 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Index of canonical transactions by the addresses taking part in them.
+//!
+//! Every imported block gets a record of its entries, keyed by the block hash, so that
+//! the index can follow reorganizations without re-executing blocks. Index keys are
+//! `address ++ block number ++ transaction index`, so transactions of an address
+//! are iterated in chain order.
+
+use std::collections::BTreeMap;
+
+use byteorder::{BigEndian, ByteOrder};
+use db::COL_ADDRESS_INDEX;
+use executive::contract_address;
+use header::BlockNumber;
+use rlp::{self, Decodable, Decoder, DecoderError, Encodable, RlpStream, Stream, View};
+use trace::FlatTrace;
+use trace::trace::{Action as TraceAction, Res};
+use transaction::{Action, SignedTransaction};
+use util::{Address, H256, DBTransaction, KeyValueDB};
+
+const ENTRY_PREFIX: u8 = b'a';
+const BLOCK_PREFIX: u8 = b'b';
+
+/// The address sent the transaction.
+pub const SENDER: u8 = 1;
+/// The address received the transaction or is the contract created by it.
+pub const RECIPIENT: u8 = 2;
+/// The address was called, created or destroyed during the execution.
+pub const INTERNAL: u8 = 4;
+
+/// Address taking part in a transaction of a block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+	/// The address.
+	pub address: Address,
+	/// Index of the transaction in the block.
+	pub index: usize,
+	/// Combination of `SENDER`, `RECIPIENT` and `INTERNAL`.
+	pub flags: u8,
+}
+
+impl Encodable for Entry {
+	fn rlp_append(&self, s: &mut RlpStream) {
+		s.begin_list(3);
+		s.append(&self.address);
+		s.append(&self.index);
+		s.append(&self.flags);
+	}
+}
+
+impl Decodable for Entry {
+	fn decode<D>(decoder: &D) -> Result<Self, DecoderError> where D: Decoder {
+		let d = decoder.as_rlp();
+		Ok(Entry {
+			address: d.val_at(0)?,
+			index: d.val_at(1)?,
+			flags: d.val_at(2)?,
+		})
+	}
+}
+
+// entries of a single block, stored under its hash.
+struct BlockRecord {
+	number: BlockNumber,
+	entries: Vec<Entry>,
+}
+
+impl Encodable for BlockRecord {
+	fn rlp_append(&self, s: &mut RlpStream) {
+		s.begin_list(2);
+		s.append(&self.number);
+		s.append(&self.entries);
+	}
+}
+
+impl Decodable for BlockRecord {
+	fn decode<D>(decoder: &D) -> Result<Self, DecoderError> where D: Decoder {
+		let d = decoder.as_rlp();
+		Ok(BlockRecord {
+			number: d.val_at(0)?,
+			entries: d.val_at(1)?,
+		})
+	}
+}
+
+fn address_prefix(address: &Address) -> [u8; 21] {
+	let mut key = [0u8; 21];
+	key[0] = ENTRY_PREFIX;
+	key[1..].copy_from_slice(&**address);
+	key
+}
+
+fn entry_key(address: &Address, number: BlockNumber, index: usize) -> [u8; 33] {
+	let mut key = [0u8; 33];
+	key[..21].copy_from_slice(&address_prefix(address));
+	BigEndian::write_u64(&mut key[21..29], number);
+	BigEndian::write_u32(&mut key[29..], index as u32);
+	key
+}
+
+fn block_key(hash: &H256) -> [u8; 33] {
+	let mut key = [0u8; 33];
+	key[0] = BLOCK_PREFIX;
+	key[1..].copy_from_slice(&**hash);
+	key
+}
+
+/// Collect the addresses taking part in the transactions of a block. Addresses found
+/// in nested calls of `traces` are marked as internal participants.
+pub fn block_entries(transactions: &[SignedTransaction], traces: Option<&[Vec<FlatTrace>]>) -> Vec<Entry> {
+	let mut flags: BTreeMap<(usize, Address), u8> = BTreeMap::new();
+	{
+		let mut note = |index: usize, address: Address, flag: u8| {
+			*flags.entry((index, address)).or_insert(0) |= flag;
+		};
+
+		for (index, tx) in transactions.iter().enumerate() {
+			let sender = tx.sender();
+			note(index, sender, SENDER);
+			match tx.action {
+				Action::Call(ref to) => note(index, *to, RECIPIENT),
+				Action::Create => note(index, contract_address(&sender, &tx.nonce), RECIPIENT),
+			}
+		}
+
+		for (index, tx_traces) in traces.into_iter().flat_map(|traces| traces.iter()).enumerate() {
+			for trace in tx_traces.iter().filter(|trace| !trace.trace_address.is_empty()) {
+				match trace.action {
+					TraceAction::Call(ref call) => {
+						note(index, call.from, INTERNAL);
+						note(index, call.to, INTERNAL);
+					},
+					TraceAction::Create(ref create) => {
+						note(index, create.from, INTERNAL);
+						if let Res::Create(ref result) = trace.result {
+							note(index, result.address, INTERNAL);
+						}
+					},
+					TraceAction::Suicide(ref suicide) => {
+						note(index, suicide.address, INTERNAL);
+						note(index, suicide.refund_address, INTERNAL);
+					},
+				}
+			}
+		}
+	}
+
+	flags.into_iter().map(|((index, address), flags)| Entry {
+		address: address,
+		index: index,
+		flags: flags,
+	}).collect()
+}
+
+fn read_record(db: &KeyValueDB, hash: &H256) -> Option<BlockRecord> {
+	db.get(COL_ADDRESS_INDEX, &block_key(hash)).expect("Low level database error. Some issue with disk?")
+		.map(|record| rlp::decode(&record))
+}
+
+/// Record the entries of an imported block and update the index along the route
+/// of the import. Blocks imported before the index was enabled are not indexed.
+pub fn import(
+	batch: &mut DBTransaction,
+	db: &KeyValueDB,
+	hash: &H256,
+	number: BlockNumber,
+	entries: Vec<Entry>,
+	enacted: &[H256],
+	retracted: &[H256],
+) {
+	let record = BlockRecord {
+		number: number,
+		entries: entries,
+	};
+
+	for retracted_hash in retracted {
+		retract(batch, db, retracted_hash);
+	}
+
+	for enacted_hash in enacted {
+		let stored;
+		let enacted = match enacted_hash == hash {
+			true => &record,
+			false => match read_record(db, enacted_hash) {
+				Some(record) => {
+					stored = record;
+					&stored
+				},
+				None => {
+					trace!(target: "client", "Block {} is not in the address index", enacted_hash);
+					continue;
+				}
+			},
+		};
+		for entry in &enacted.entries {
+			batch.put(COL_ADDRESS_INDEX, &entry_key(&entry.address, enacted.number, entry.index), &[entry.flags]);
+		}
+	}
+
+	batch.put(COL_ADDRESS_INDEX, &block_key(hash), &rlp::encode(&record));
+}
+
+/// Remove the index entries of a block which is no longer canonical. Its record is kept,
+/// so the entries are restored if the block is enacted again.
+pub fn retract(batch: &mut DBTransaction, db: &KeyValueDB, hash: &H256) {
+	if let Some(record) = read_record(db, hash) {
+		for entry in &record.entries {
+			batch.delete(COL_ADDRESS_INDEX, &entry_key(&entry.address, record.number, entry.index));
+		}
+	}
+}
+
+/// Canonical transactions of an address in chain order as `(block number, transaction index, flags)`,
+/// starting after the given position.
+pub fn transactions(db: &KeyValueDB, address: &Address, after: Option<(BlockNumber, usize)>, count: usize) -> Vec<(BlockNumber, usize, u8)> {
+	let prefix = address_prefix(address);
+	let after = after.map(|(number, index)| entry_key(address, number, index));
+
+	db.iter_from_prefix(COL_ADDRESS_INDEX, &prefix)
+		.skip_while(|&(ref key, _)| after.as_ref().map_or(false, |after| &**key <= &after[..]))
+		.take(count)
+		.map(|(key, value)| (
+			BigEndian::read_u64(&key[21..29]),
+			BigEndian::read_u32(&key[29..]) as usize,
+			value.get(0).cloned().unwrap_or(0),
+		))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use db::NUM_COLUMNS;
+	use util::{Address, H256, KeyValueDB};
+	use util::kvdb::in_memory;
+	use super::*;
+
+	fn entry(address: u64, index: usize, flags: u8) -> Entry {
+		Entry {
+			address: Address::from(address),
+			index: index,
+			flags: flags,
+		}
+	}
+
+	fn import_block(db: &KeyValueDB, hash: H256, number: BlockNumber, entries: Vec<Entry>, enacted: &[H256], retracted: &[H256]) {
+		let mut batch = db.transaction();
+		import(&mut batch, db, &hash, number, entries, enacted, retracted);
+		db.write(batch).unwrap();
+	}
+
+	#[test]
+	fn follows_reorganizations() {
+		let db: Arc<KeyValueDB> = Arc::new(in_memory(NUM_COLUMNS.unwrap_or(0)));
+		let (a, b) = (Address::from(1), Address::from(2));
+		let (h1, h2, h2b) = (H256::from(11), H256::from(12), H256::from(22));
+
+		import_block(&*db, h1, 1, vec![entry(1, 0, SENDER), entry(2, 0, RECIPIENT)], &[h1], &[]);
+		import_block(&*db, h2, 2, vec![entry(1, 0, SENDER | INTERNAL), entry(1, 1, RECIPIENT)], &[h2], &[]);
+		assert_eq!(transactions(&*db, &a, None, 10), vec![(1, 0, SENDER), (2, 0, SENDER | INTERNAL), (2, 1, RECIPIENT)]);
+		assert_eq!(transactions(&*db, &a, Some((2, 0)), 10), vec![(2, 1, RECIPIENT)]);
+		assert_eq!(transactions(&*db, &a, None, 1), vec![(1, 0, SENDER)]);
+
+		// a side block is recorded but not indexed.
+		import_block(&*db, h2b, 2, vec![entry(2, 0, SENDER)], &[], &[]);
+		assert_eq!(transactions(&*db, &b, None, 10), vec![(1, 0, RECIPIENT)]);
+
+		// it becomes canonical later.
+		import_block(&*db, H256::from(23), 3, vec![], &[h2b, H256::from(23)], &[h2]);
+		assert_eq!(transactions(&*db, &a, None, 10), vec![(1, 0, SENDER)]);
+		assert_eq!(transactions(&*db, &b, None, 10), vec![(1, 0, RECIPIENT), (2, 0, SENDER)]);
+	}
+
+	#[test]
+	fn retracted_block_is_restored_when_enacted() {
+		let db: Arc<KeyValueDB> = Arc::new(in_memory(NUM_COLUMNS.unwrap_or(0)));
+		let (h1, h2) = (H256::from(11), H256::from(12));
+
+		import_block(&*db, h1, 1, vec![entry(1, 0, SENDER)], &[h1], &[]);
+		let mut batch = db.transaction();
+		retract(&mut batch, &*db, &h1);
+		db.write(batch).unwrap();
+		assert_eq!(transactions(&*db, &Address::from(1), None, 10), vec![]);
+
+		import_block(&*db, h2, 2, vec![], &[h1, h2], &[]);
+		assert_eq!(transactions(&*db, &Address::from(1), None, 10), vec![(1, 0, SENDER)]);
+	}
+}
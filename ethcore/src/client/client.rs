@@ -25,7 +25,7 @@ use time::precise_time_ns;
 
 // util
 use util::{Bytes, PerfTimer, Itertools, Mutex, RwLock, MutexGuard, Hashable};
-use util::{journaldb, TrieFactory, Trie};
+use util::{journaldb, TrieFactory, Trie, HashDB};
 use util::{U256, H256, Address, H2048, Uint, FixedHash};
 use util::trie::TrieSpec;
 use util::kvdb::*;
@@ -34,7 +34,7 @@ use util::kvdb::*;
 use io::*;
 use views::BlockView;
 use error::{ImportError, ExecutionError, CallError, BlockError, ImportResult, Error as EthcoreError};
-use header::BlockNumber;
+use header::{BlockNumber, Header};
 use state::{State, CleanupMode};
 use spec::Spec;
 use basic_types::Seal;
@@ -54,7 +54,7 @@ use blockchain::{BlockChain, BlockProvider, TreeRoute, ImportRoute};
 use client::{
 	BlockId, TransactionId, UncleId, TraceId, ClientConfig, BlockChainClient,
 	MiningBlockChainClient, EngineClient, TraceFilter, CallAnalytics, BlockImportError, Mode,
-	ChainNotify, PruningInfo,
+	ChainNotify, PruningInfo, AddressTransaction, DatabaseStats, ColumnStats,
 };
 use client::Error as ClientError;
 use env_info::EnvInfo;
@@ -69,9 +69,13 @@ use snapshot::{self, io as snapshot_io};
 use factory::Factories;
 use rlp::{View, UntrustedRlp};
 use state_db::StateDB;
+use account_db::AccountDB;
 use rand::OsRng;
 use client::registry::Registry;
+use client::integrity::{self, Issue, IntegrityReport};
+use client::{address_index, log_index};
 use encoded;
+use types::state_node::StateNode;
 
 // re-export
 pub use types::blockchain_info::BlockChainInfo;
@@ -82,6 +86,13 @@ pub use verification::queue::QueueInfo as BlockQueueInfo;
 const MAX_TX_QUEUE_SIZE: usize = 4096;
 const MAX_QUEUE_SIZE_TO_SLEEP_ON: usize = 2;
 const MIN_HISTORY_SIZE: u64 = 8;
+// Number of blocks added to the log index in each backfill step.
+const LOG_INDEX_BACKFILL_BLOCKS: u64 = 100;
+// Number of flat state entries generated in each step.
+const FLAT_STATE_GENERATE_ENTRIES: usize = 10_000;
+
+// Key of the pending fast sync pivot header in the extras column.
+const FAST_SYNC_PIVOT_KEY: &'static [u8] = b"fast_pivot";
 
 impl fmt::Display for BlockChainInfo {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -135,7 +146,7 @@ pub struct Client {
 	engine: Arc<Engine>,
 	config: ClientConfig,
 	pruning: journaldb::Algorithm,
-	db: RwLock<Arc<Database>>,
+	db: RwLock<Arc<KeyValueDB>>,
 	state_db: Mutex<StateDB>,
 	block_queue: BlockQueue,
 	report: RwLock<ClientReport>,
@@ -154,6 +165,8 @@ pub struct Client {
 	rng: Mutex<OsRng>,
 	on_mode_change: Mutex<Option<Box<FnMut(&Mode) + 'static + Send>>>,
 	registrar: Mutex<Option<Registry>>,
+	log_index_backfill: AtomicBool,
+	flat_state_generation: AtomicBool,
 }
 
 impl Client {
@@ -166,9 +179,20 @@ impl Client {
 		message_channel: IoChannel<ClientIoMessage>,
 		db_config: &DatabaseConfig,
 	) -> Result<Arc<Client>, ClientError> {
-
 		let path = path.to_path_buf();
-		let db = Arc::new(Database::open(&db_config, &path.to_str().expect("DB path could not be converted to string.")).map_err(ClientError::Database)?);
+		let db = Database::open(&db_config, &path.to_str().expect("DB path could not be converted to string.")).map_err(ClientError::Database)?;
+		Client::with_db(config, spec, Arc::new(db), miner, message_channel)
+	}
+
+	/// Create a new client with given spec on top of an already opened database.
+	/// Use `util::kvdb::in_memory` for ephemeral chains.
+	pub fn with_db(
+		config: ClientConfig,
+		spec: &Spec,
+		db: Arc<KeyValueDB>,
+		miner: Arc<Miner>,
+		message_channel: IoChannel<ClientIoMessage>,
+	) -> Result<Arc<Client>, ClientError> {
 		let trie_spec = match config.fat_db {
 			true => TrieSpec::Fat,
 			false => TrieSpec::Secure,
@@ -186,7 +210,7 @@ impl Client {
 		if state_db.journal_db().is_empty() {
 			// Sets the correct state root.
 			state_db = spec.ensure_db_good(state_db, &factories)?;
-			let mut batch = DBTransaction::new(&db);
+			let mut batch = DBTransaction::new();
 			state_db.journal_under(&mut batch, 0, &spec.genesis_header().hash())?;
 			db.write(batch).map_err(ClientError::Database)?;
 		}
@@ -210,6 +234,20 @@ impl Client {
 			warn!("State root not found for block #{} ({})", chain.best_block_number(), chain.best_block_hash().hex());
 		}
 
+		if config.flat_state {
+			enable_flat_state(&mut state_db, &chain);
+		}
+
+		if config.log_index {
+			log_index::init(&*db, chain.best_block_number());
+		} else {
+			log_index::reset(&*db);
+		}
+
+		if config.address_index_traces && !config.tracing.enabled {
+			warn!(target: "client", "Tracing is disabled, internal calls won't be indexed by address");
+		}
+
 		let engine = spec.engine.clone();
 
 		let block_queue = BlockQueue::new(config.queue.clone(), engine.clone(), message_channel.clone(), config.verifier_type.verifying_seal());
@@ -245,6 +283,8 @@ impl Client {
 			rng: Mutex::new(OsRng::new().map_err(::util::UtilError::StdIo)?),
 			on_mode_change: Mutex::new(None),
 			registrar: Mutex::new(None),
+			log_index_backfill: AtomicBool::new(false),
+			flat_state_generation: AtomicBool::new(false),
 		});
 
 		{
@@ -518,7 +558,7 @@ impl Client {
 
 			// Commit results
 			let receipts = ::rlp::decode(&receipts_bytes);
-			let mut batch = DBTransaction::new(&self.db.read());
+			let mut batch = DBTransaction::new();
 			chain.insert_unordered_block(&mut batch, &block_bytes, receipts, None, false, true);
 			// Final commit to the DB
 			self.db.read().write_buffered(batch);
@@ -542,7 +582,22 @@ impl Client {
 
 		//let traces = From::from(block.traces().clone().unwrap_or_else(Vec::new));
 
-		let mut batch = DBTransaction::new(&self.db.read());
+		let address_entries = match self.config.address_index {
+			true => {
+				let traces = match self.config.address_index_traces {
+					true => block.traces().as_ref().map(|traces| &traces[..]),
+					false => None,
+				};
+				Some(address_index::block_entries(block.transactions(), traces))
+			},
+			false => None,
+		};
+		let log_keys = match self.config.log_index {
+			true => Some(log_index::block_keys(&receipts)),
+			false => None,
+		};
+
+		let mut batch = DBTransaction::new();
 		// CHECK! I *think* this is fine, even if the state_root is equal to another
 		// already-imported block of the same number.
 		// TODO: Prove it with a test.
@@ -557,6 +612,12 @@ impl Client {
 			enacted: route.enacted.clone(),
 			retracted: route.retracted.len()
 		});
+		if let Some(entries) = address_entries {
+			address_index::import(&mut batch, &**self.db.read(), hash, number, entries, &route.enacted, &route.retracted);
+		}
+		if let Some(keys) = log_keys {
+			log_index::import(&mut batch, &chain, hash, number, keys, &route.enacted, &route.retracted);
+		}
 
 		let is_canon = route.enacted.last().map_or(false, |h| h == hash);
 		state.sync_cache(&route.enacted, &route.retracted, is_canon);
@@ -565,6 +626,19 @@ impl Client {
 		chain.commit();
 		self.update_last_hashes(&parent, hash);
 
+		// blocks older than the pruning history are not expected to be reorganized.
+		if is_canon && state.has_flat_state() && number > self.history {
+			let finalized = chain.block_hash(number - self.history).and_then(|h| chain.block_header(&h));
+			if let Some(header) = finalized {
+				state.flatten_state(header.state_root());
+			}
+		}
+
+		let mut batch = DBTransaction::new();
+		if chain.freeze_ancient(&mut batch) + chain.expire_history(&mut batch) > 0 {
+			self.db.read().write_buffered(batch);
+		}
+
 		if let Err(e) = self.prune_ancient(state, &chain) {
 			warn!("Failed to prune ancient state data: {}", e);
 		}
@@ -591,7 +665,7 @@ impl Client {
 					trace!(target: "client", "Pruning state for ancient era {}", era);
 					match chain.block_hash(era) {
 						Some(ancient_hash) => {
-							let mut batch = DBTransaction::new(&self.db.read());
+							let mut batch = DBTransaction::new();
 							state_db.mark_canonical(&mut batch, era, &ancient_hash)?;
 							self.db.read().write_buffered(batch);
 							state_db.journal_db().flush();
@@ -716,6 +790,8 @@ impl Client {
 	pub fn tick(&self) {
 		self.check_garbage();
 		self.check_snooze();
+		self.check_log_index();
+		self.check_flat_state();
 	}
 
 	fn check_garbage(&self) {
@@ -724,6 +800,104 @@ impl Client {
 		self.tracedb.read().collect_garbage();
 	}
 
+	fn check_log_index(&self) {
+		if !self.config.log_index || self.log_index_backfill.load(AtomicOrdering::SeqCst) {
+			return;
+		}
+
+		let pending = log_index::pending(&**self.db.read(), &self.chain.read());
+		if pending {
+			if let Err(e) = self.io_channel.lock().send(ClientIoMessage::BackfillLogIndex) {
+				debug!(target: "client", "Failed to start log index backfill: {:?}", e);
+			}
+		}
+	}
+
+	/// Backfill the log index towards the genesis in small steps. Blocks are read without
+	/// locks; the import lock is only held to commit each step. Meant to be run on a
+	/// background thread; returns immediately if another backfill is running.
+	pub fn backfill_log_index(&self) {
+		if self.log_index_backfill.swap(true, AtomicOrdering::SeqCst) {
+			return;
+		}
+
+		let mut indexed = 0;
+		loop {
+			let chain = self.chain.read().clone();
+			let db = self.db.read().clone();
+			let step = match log_index::backfill(&*db, &chain, LOG_INDEX_BACKFILL_BLOCKS) {
+				Some(step) => step,
+				None => break,
+			};
+			let blocks = step.blocks();
+
+			// reorganizations must not interleave with the commit.
+			let _import_lock = self.import_lock.lock();
+			if !step.commit(&**self.db.read(), &self.chain.read()) {
+				trace!(target: "client", "Chain changed during log index backfill, retrying");
+				continue;
+			}
+			indexed += blocks;
+		}
+
+		if indexed > 0 {
+			debug!(target: "client", "Backfilled log index with {} blocks", indexed);
+		}
+		self.log_index_backfill.store(false, AtomicOrdering::SeqCst);
+	}
+
+	fn check_flat_state(&self) {
+		if self.flat_state_generation.load(AtomicOrdering::SeqCst) {
+			return;
+		}
+
+		let generating = self.state_db.lock().flat_state().map_or(false, |flat_state| flat_state.is_generating());
+		if generating {
+			if let Err(e) = self.io_channel.lock().send(ClientIoMessage::GenerateFlatState) {
+				debug!(target: "client", "Failed to start flat state generation: {:?}", e);
+			}
+		}
+	}
+
+	/// Generate the flat state from the trie in small steps, holding the state lock
+	/// only for each step. Meant to be run on a background thread; returns immediately
+	/// if another generation is running.
+	pub fn generate_flat_state(&self) {
+		if self.flat_state_generation.swap(true, AtomicOrdering::SeqCst) {
+			return;
+		}
+
+		loop {
+			// the disk root is kept from being pruned while the lock is held.
+			let state_db = self.state_db.lock();
+			let flat_state = match state_db.flat_state() {
+				Some(flat_state) => flat_state,
+				None => break,
+			};
+			match flat_state.generate_step(state_db.as_hashdb(), FLAT_STATE_GENERATE_ENTRIES) {
+				Ok(true) => {
+					info!(target: "client", "Flat state generated");
+					break;
+				},
+				Ok(false) => {},
+				Err(e) => {
+					warn!(target: "client", "Flat state generation failed: {}. Reading state from the trie.", e);
+					break;
+				},
+			}
+		}
+
+		self.flat_state_generation.store(false, AtomicOrdering::SeqCst);
+	}
+
+	// numbers of blocks in the range whose blooms may match the filter.
+	fn blocks_with_blooms(&self, filter: &Filter, from_block: BlockId, to_block: BlockId) -> HashSet<BlockNumber> {
+		filter.bloom_possibilities().iter()
+			.filter_map(|bloom| self.blocks_with_bloom(bloom, from_block.clone(), to_block.clone()))
+			.flat_map(|m| m)
+			.collect()
+	}
+
 	fn check_snooze(&self) {
 		let mode = self.mode.lock().clone();
 		match mode {
@@ -789,7 +963,8 @@ impl Client {
 			},
 		};
 
-		snapshot::take_snapshot(&self.chain.read(), start_hash, db.as_hashdb(), writer, p)?;
+		let flat_state = self.state_db.lock().flat_state();
+		snapshot::take_snapshot(&self.chain.read(), start_hash, db.as_hashdb(), flat_state.as_ref().map(|f| &**f), writer, p)?;
 
 		Ok(())
 	}
@@ -799,6 +974,101 @@ impl Client {
 		self.history
 	}
 
+	/// Check the canonical chain and the state at the best block for missing or
+	/// inconsistent entries. Also records which recent states have their root available.
+	pub fn check_integrity(&self) -> IntegrityReport {
+		let chain = self.chain.read();
+		let db = self.state_db.lock().journal_db().boxed_clone();
+		let mut report = IntegrityReport::default();
+
+		integrity::check_chain(&chain, &mut report);
+
+		let best = chain.best_block_number();
+		let earliest = db.earliest_era().map_or(best, |era| ::std::cmp::max(era, best.saturating_sub(self.history)));
+		for number in earliest..best + 1 {
+			let root = chain.block_hash(number)
+				.and_then(|hash| chain.block_header(&hash))
+				.map(|header| header.state_root().clone());
+			match root {
+				Some(ref root) if db.contains(root) => report.state_roots.push(number),
+				Some(root) => report.issues.push(Issue::MissingStateNode(number, root)),
+				None => {},
+			}
+		}
+
+		if let Some(header) = chain.block_header(&chain.best_block_hash()) {
+			info!(target: "integrity", "Checking state at block #{}", best);
+			match integrity::check_state(db.as_hashdb(), best, header.state_root()) {
+				Ok(accounts) => report.accounts = accounts,
+				Err(issues) => report.issues.extend(issues),
+			}
+		}
+
+		report
+	}
+
+	/// Rewind the best block to the most recent block below every chain inconsistency
+	/// in the report whose state is fully available. Returns the new best block number
+	/// or `None` if no such block is left.
+	pub fn repair(&self, report: &IntegrityReport) -> Option<BlockNumber> {
+		let _import_lock = self.import_lock.lock();
+		let mut state_db = self.state_db.lock();
+		let chain = self.chain.read();
+		let db = state_db.journal_db().boxed_clone();
+		let best = chain.best_block_number();
+
+		let first_broken = report.issues.iter()
+			.filter(|issue| match **issue {
+				Issue::MissingStateNode(..) | Issue::MissingCode(..) => false,
+				_ => true,
+			})
+			.map(Issue::block_number)
+			.min();
+
+		let candidates = report.state_roots.iter()
+			.rev()
+			.cloned()
+			.filter(|number| first_broken.map_or(true, |broken| *number < broken));
+
+		for number in candidates {
+			let root = match chain.block_hash(number).and_then(|hash| chain.block_header(&hash)) {
+				Some(header) => header.state_root().clone(),
+				None => continue,
+			};
+			let complete = (number == best && report.is_ok()) ||
+				integrity::check_state(db.as_hashdb(), number, &root).is_ok();
+			if !complete {
+				continue;
+			}
+
+			if number < best {
+				info!(target: "integrity", "Rewinding best block from #{} to #{}", best, number);
+				// index entries of the removed blocks are deleted along with them.
+				let mut batch = DBTransaction::new();
+				for removed in (number + 1..best + 1).filter_map(|n| chain.block_hash(n)) {
+					if self.config.address_index {
+						address_index::retract(&mut batch, &**self.db.read(), &removed);
+					}
+					if self.config.log_index {
+						log_index::retract(&mut batch, &chain, &removed);
+					}
+				}
+				chain.rewind_to(batch, number);
+				// cached accounts may come from the retracted blocks.
+				let cache_size = state_db.cache_size();
+				*state_db = StateDB::new(journaldb::new(self.db.read().clone(), self.pruning, ::db::COL_STATE), cache_size);
+				if self.config.flat_state {
+					enable_flat_state(&mut state_db, &chain);
+				}
+				self.last_hashes.write().clear();
+				self.miner.clear();
+			}
+			return Some(number);
+		}
+
+		None
+	}
+
 	fn block_hash(chain: &BlockChain, id: BlockId) -> Option<H256> {
 		match id {
 			BlockId::Hash(hash) => Some(hash),
@@ -858,6 +1128,14 @@ impl snapshot::DatabaseRestore for Client {
 		let cache_size = state_db.cache_size();
 		*state_db = StateDB::new(journaldb::new(db.clone(), self.pruning, ::db::COL_STATE), cache_size);
 		*chain = Arc::new(BlockChain::new(self.config.blockchain.clone(), &[], db.clone(), self.engine.clone()));
+		if self.config.flat_state {
+			enable_flat_state(&mut state_db, &chain);
+		}
+		if self.config.log_index {
+			log_index::init(&**db, chain.best_block_number());
+		} else {
+			log_index::reset(&**db);
+		}
 		*tracedb = TraceDB::new(self.config.tracing.clone(), db.clone(), chain.clone());
 		Ok(())
 	}
@@ -1220,6 +1498,46 @@ impl BlockChainClient for Client {
 		self.transaction_address(id).and_then(|address| self.chain.read().transaction(&address))
 	}
 
+	fn address_transactions(&self, address: &Address, after: Option<&H256>, count: u64) -> Option<Vec<AddressTransaction>> {
+		if !self.config.address_index {
+			trace!(target: "client", "address_transactions: Address index disabled");
+			return None;
+		}
+
+		let after = match after {
+			Some(hash) => {
+				let chain = self.chain.read();
+				let position = chain.transaction_address(hash)
+					.and_then(|tx| chain.block_number(&tx.block_hash).map(|number| (number, tx.index)));
+				match position {
+					Some(position) => Some(position),
+					// unknown or retracted transaction, nothing follows it.
+					None => return Some(Vec::new()),
+				}
+			},
+			None => None,
+		};
+
+		let entries = address_index::transactions(&**self.db.read(), address, after, count as usize);
+		let mut transactions = Vec::with_capacity(entries.len());
+		for (number, index, flags) in entries {
+			let transaction = match self.transaction(TransactionId::Location(BlockId::Number(number), index)) {
+				Some(transaction) => transaction,
+				None => {
+					debug!(target: "client", "address_transactions: Transaction #{}:{} is not available", number, index);
+					break;
+				}
+			};
+			transactions.push(AddressTransaction {
+				transaction: transaction,
+				sender: flags & address_index::SENDER != 0,
+				recipient: flags & address_index::RECIPIENT != 0,
+				internal: flags & address_index::INTERNAL != 0,
+			});
+		}
+		Some(transactions)
+	}
+
 	fn transaction_block(&self, id: TransactionId) -> Option<H256> {
 		self.transaction_address(id).map(|addr| addr.block_hash)
 	}
@@ -1253,6 +1571,22 @@ impl BlockChainClient for Client {
 			}))
 	}
 
+	fn localized_block_receipts(&self, id: BlockId) -> Option<Vec<LocalizedReceipt>> {
+		let chain = self.chain.read();
+		Self::block_hash(&chain, id).and_then(|hash| {
+			let number = chain.block_number(&hash);
+			let body = chain.block_body(&hash);
+			let receipts = chain.block_receipts(&hash);
+			match (number, body, receipts) {
+				(Some(number), Some(body), Some(receipts)) => {
+					let transactions = body.view().localized_transactions(&hash, number);
+					Some(block_receipts(transactions, receipts.receipts))
+				},
+				_ => None,
+			}
+		})
+	}
+
 	fn tree_route(&self, from: &H256, to: &H256) -> Option<TreeRoute> {
 		let chain = self.chain.read();
 		match chain.is_known(from) && chain.is_known(to) {
@@ -1305,6 +1639,95 @@ impl BlockChainClient for Client {
 		self.import_old_block(block_bytes, receipts_bytes).map_err(Into::into)
 	}
 
+	fn state_node_known(&self, owner: Option<H256>, hash: &H256) -> bool {
+		let state_db = self.state_db.lock();
+		match owner {
+			Some(owner) => AccountDB::from_hash(state_db.as_hashdb(), owner).contains(hash),
+			None => state_db.as_hashdb().contains(hash),
+		}
+	}
+
+	fn import_state_nodes(&self, nodes: Vec<StateNode>, accounts: Vec<H256>) {
+		let mut batch = DBTransaction::new();
+		{
+			let mut state_db = self.state_db.lock();
+			if let Err(e) = state_db.import_nodes(&mut batch, nodes, &accounts) {
+				warn!(target: "client", "Failed to import state nodes: {}", e);
+				return;
+			}
+		}
+		self.db.read().write_buffered(batch);
+	}
+
+	fn import_fast_sync_pivot(&self, block_bytes: Bytes, receipts_bytes: Bytes) -> Result<H256, BlockImportError> {
+		let block = BlockView::new(&block_bytes);
+		let header = block.header();
+		let hash = header.hash();
+
+		let _import_lock = self.import_lock.lock();
+		let mut state_db = self.state_db.lock();
+		{
+			let chain = self.chain.read();
+			if chain.is_known(&hash) {
+				return Err(BlockImportError::Import(ImportError::AlreadyInChain));
+			}
+			if !chain.is_known(header.parent_hash()) {
+				return Err(BlockImportError::Block(BlockError::UnknownParent(header.parent_hash().clone())));
+			}
+			if !state_db.as_hashdb().contains(header.state_root()) {
+				return Err(BlockImportError::Other(format!("Missing state for pivot block {}", hash)));
+			}
+			::snapshot::verify_old_block(
+				&mut *self.rng.lock(),
+				&header,
+				&*self.engine,
+				&*chain,
+				Some(&block_bytes),
+				true,
+			)?;
+
+			let receipts = ::rlp::decode(&receipts_bytes);
+			let mut batch = DBTransaction::new();
+			state_db.journal_under(&mut batch, header.number(), &hash).map_err(EthcoreError::from)?;
+			batch.delete(::db::COL_EXTRA, FAST_SYNC_PIVOT_KEY);
+			chain.insert_unordered_block(&mut batch, &block_bytes, receipts, None, true, false);
+			chain.clear_best_ancient_block(&mut batch);
+			self.db.read().write_buffered(batch);
+			chain.commit();
+		}
+		self.db.read().flush().expect("DB flush failed.");
+
+		let db = self.db.read();
+		let cache_size = state_db.cache_size();
+		*state_db = StateDB::new(journaldb::new(db.clone(), self.pruning, ::db::COL_STATE), cache_size);
+		if self.config.flat_state {
+			enable_flat_state(&mut state_db, &self.chain.read());
+		}
+		self.last_hashes.write().clear();
+		self.miner.clear();
+		info!(target: "client", "Fast sync complete at block #{} ({})", header.number(), hash);
+		Ok(hash)
+	}
+
+	fn fast_sync_pivot(&self) -> Option<encoded::Header> {
+		self.db.read().get(::db::COL_EXTRA, FAST_SYNC_PIVOT_KEY)
+			.expect("Low level database error. Some issue with disk?")
+			.map(|bytes| encoded::Header::new(bytes.to_vec()))
+	}
+
+	fn set_fast_sync_pivot(&self, header: Option<encoded::Header>) {
+		let mut batch = DBTransaction::new();
+		match header {
+			Some(header) => batch.put(::db::COL_EXTRA, FAST_SYNC_PIVOT_KEY, &header.into_inner()),
+			None => batch.delete(::db::COL_EXTRA, FAST_SYNC_PIVOT_KEY),
+		}
+		self.db.read().write(batch).expect("Low level database error. Some issue with disk?");
+	}
+
+	fn verify_header_seal(&self, header: &Header) -> bool {
+		self.engine.verify_block_seal(header).is_ok()
+	}
+
 	fn queue_info(&self) -> BlockQueueInfo {
 		self.block_queue.queue_info()
 	}
@@ -1331,15 +1754,35 @@ impl BlockChainClient for Client {
 	}
 
 	fn logs(&self, filter: Filter) -> Vec<LocalizedLogEntry> {
-		let blocks = filter.bloom_possibilities().iter()
-			.filter_map(|bloom| self.blocks_with_bloom(bloom, filter.from_block.clone(), filter.to_block.clone()))
-			.flat_map(|m| m)
-			// remove duplicate elements
-			.collect::<HashSet<u64>>()
-			.into_iter()
-			.collect::<Vec<u64>>();
+		let range = match (self.block_number(filter.from_block.clone()), self.block_number(filter.to_block.clone())) {
+			(Some(from), Some(to)) => Some((from, to)),
+			_ => None,
+		};
+
+		// the log index covers recent blocks, blooms are used below it.
+		let indexed = match range {
+			Some((from, to)) if self.config.log_index => {
+				let db = self.db.read();
+				log_index::indexed_from(&**db).and_then(|indexed_from| {
+					let start = ::std::cmp::max(from, indexed_from);
+					log_index::blocks(&**db, &filter, start, to).map(|blocks| (start, blocks))
+				})
+			},
+			_ => None,
+		};
 
-		self.chain.read().logs(blocks, |entry| filter.matches(entry), filter.limit)
+		let blocks = match (indexed, range) {
+			(Some((start, mut blocks)), Some((from, to))) => {
+				if start > from {
+					let below = BlockId::Number(::std::cmp::min(start - 1, to));
+					blocks.extend(self.blocks_with_blooms(&filter, filter.from_block.clone(), below));
+				}
+				blocks
+			},
+			_ => self.blocks_with_blooms(&filter, filter.from_block.clone(), filter.to_block.clone()),
+		};
+
+		self.chain.read().logs(blocks.into_iter().collect(), |entry| filter.matches(entry), filter.limit)
 	}
 
 	fn filter_traces(&self, filter: TraceFilter) -> Option<Vec<LocalizedTrace>> {
@@ -1434,6 +1877,28 @@ impl BlockChainClient for Client {
 		PruningInfo {
 			earliest_chain: self.chain.read().first_block_number().unwrap_or(1),
 			earliest_state: self.state_db.lock().journal_db().earliest_era().unwrap_or(0),
+			earliest_body: self.chain.read().earliest_body_number(),
+		}
+	}
+
+	fn database_stats(&self) -> DatabaseStats {
+		let stats = self.db.read().stats();
+		let columns = stats.columns.into_iter().map(|(col, column)| ColumnStats {
+			name: match col {
+				Some(c) => ::db::COLUMN_NAMES.get(c as usize).map_or_else(|| format!("col{}", c), |name| name.to_string()),
+				None => "default".into(),
+			},
+			keys: column.keys,
+			disk_size: column.disk_size,
+			memtable_size: column.memtable_size,
+			pending_writes: column.pending_writes,
+		}).collect();
+
+		DatabaseStats {
+			columns: columns,
+			journal_memory: self.state_db.lock().journal_db().mem_used() as u64,
+			running_compactions: stats.running_compactions,
+			pending_compaction_bytes: stats.pending_compaction_bytes,
 		}
 	}
 
@@ -1602,21 +2067,57 @@ impl ::client::ProvingBlockChainClient for Client {
 impl Drop for Client {
 	fn drop(&mut self) {
 		self.engine.stop();
+
+		// store the best state so that the flat state needn't be regenerated on restart.
+		let chain = self.chain.read();
+		if let Some(header) = chain.block_header(&chain.best_block_hash()) {
+			self.state_db.lock().flatten_state(header.state_root());
+		}
+	}
+}
+
+/// Enable the flat state of `state_db` at the best block of `chain`.
+/// Reads go through the trie until it is generated by `Client::generate_flat_state`.
+fn enable_flat_state(state_db: &mut StateDB, chain: &BlockChain) {
+	if let Some(header) = chain.block_header(&chain.best_block_hash()) {
+		state_db.enable_flat_state(header.state_root());
 	}
 }
 
 /// Returns `LocalizedReceipt` given `LocalizedTransaction`
 /// and a vector of receipts from given block up to transaction index.
-fn transaction_receipt(mut tx: LocalizedTransaction, mut receipts: Vec<Receipt>) -> LocalizedReceipt {
+fn transaction_receipt(tx: LocalizedTransaction, mut receipts: Vec<Receipt>) -> LocalizedReceipt {
 	assert_eq!(receipts.len(), tx.transaction_index + 1, "All previous receipts are provided.");
 
-	let sender = tx.sender();
 	let receipt = receipts.pop().expect("Current receipt is provided; qed");
 	let prior_gas_used = match tx.transaction_index {
 		0 => 0.into(),
 		i => receipts.get(i - 1).expect("All previous receipts are provided; qed").gas_used,
 	};
 	let no_of_logs = receipts.into_iter().map(|receipt| receipt.logs.len()).sum::<usize>();
+
+	localize_receipt(tx, receipt, prior_gas_used, no_of_logs)
+}
+
+/// Returns `LocalizedReceipt`s of all transactions in a block.
+/// Transactions and receipts are expected to be in block order.
+fn block_receipts(transactions: Vec<LocalizedTransaction>, receipts: Vec<Receipt>) -> Vec<LocalizedReceipt> {
+	assert_eq!(transactions.len(), receipts.len(), "Every transaction has a receipt.");
+
+	let mut prior_gas_used = U256::zero();
+	let mut no_of_logs = 0;
+	transactions.into_iter().zip(receipts).map(|(tx, receipt)| {
+		let gas_used = receipt.gas_used;
+		let logs = receipt.logs.len();
+		let localized = localize_receipt(tx, receipt, prior_gas_used, no_of_logs);
+		prior_gas_used = gas_used;
+		no_of_logs += logs;
+		localized
+	}).collect()
+}
+
+fn localize_receipt(mut tx: LocalizedTransaction, receipt: Receipt, prior_gas_used: U256, no_of_logs: usize) -> LocalizedReceipt {
+	let sender = tx.sender();
 	let transaction_hash = tx.hash();
 	let block_hash = tx.block_hash;
 	let block_number = tx.block_number;
@@ -1671,7 +2172,7 @@ mod tests {
 			let go_thread = go.clone();
 			let another_client = client.reference().clone();
 			thread::spawn(move || {
-				let mut batch = DBTransaction::new(&*another_client.chain.read().db().clone());
+				let mut batch = DBTransaction::new();
 				another_client.chain.read().insert_block(&mut batch, &new_block, Vec::new());
 				go_thread.store(true, Ordering::SeqCst);
 			});
@@ -1770,4 +2271,62 @@ mod tests {
 			state_root: state_root,
 		});
 	}
+
+	#[test]
+	fn should_localize_all_receipts_of_a_block() {
+		use super::{transaction_receipt, block_receipts};
+		use ethkey::KeyPair;
+		use log_entry::LogEntry;
+		use receipt::Receipt;
+		use transaction::{Transaction, LocalizedTransaction, Action};
+		use util::Hashable;
+
+		// given
+		let key = KeyPair::from_secret_slice(&"test".sha3()).unwrap();
+		let secret = key.secret();
+		let localized = |nonce: u64, index: usize| {
+			let tx = Transaction {
+				nonce: nonce.into(),
+				gas_price: 0.into(),
+				gas: 21000.into(),
+				action: Action::Create,
+				value: 0.into(),
+				data: vec![],
+			}.sign(secret, None);
+			LocalizedTransaction {
+				cached_sender: Some(tx.sender()),
+				signed: tx.into(),
+				block_number: 1,
+				block_hash: 5.into(),
+				transaction_index: index,
+			}
+		};
+		let log = LogEntry {
+			address: 5.into(),
+			topics: vec![],
+			data: vec![],
+		};
+		let receipts = vec![Receipt {
+			state_root: 99.into(),
+			gas_used: 5.into(),
+			log_bloom: Default::default(),
+			logs: vec![log.clone(), log.clone()],
+		}, Receipt {
+			state_root: 99.into(),
+			gas_used: 12.into(),
+			log_bloom: Default::default(),
+			logs: vec![log],
+		}];
+
+		// when
+		let localized_receipts = block_receipts(vec![localized(0, 0), localized(1, 1)], receipts.clone());
+
+		// then
+		assert_eq!(localized_receipts, vec![
+			transaction_receipt(localized(0, 0), receipts[..1].to_vec()),
+			transaction_receipt(localized(1, 1), receipts),
+		]);
+		assert_eq!(localized_receipts[1].gas_used, 7.into());
+		assert_eq!(localized_receipts[1].logs[0].log_index, 2);
+	}
 }
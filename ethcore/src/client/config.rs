@@ -18,7 +18,7 @@ use std::str::FromStr;
 use std::path::Path;
 use std::fmt::{Display, Formatter, Error as FmtError};
 pub use std::time::Duration;
-pub use blockchain::Config as BlockChainConfig;
+pub use blockchain::{Config as BlockChainConfig, FreezerConfig};
 pub use trace::Config as TraceConfig;
 pub use evm::VMType;
 
@@ -135,6 +135,14 @@ pub struct ClientConfig {
 	pub history_mem: usize,
 	/// Check seal valididity on block import
 	pub check_seal: bool,
+	/// Maintain a flat copy of the state for fast reads and snapshots.
+	pub flat_state: bool,
+	/// Index transactions by their senders and recipients.
+	pub address_index: bool,
+	/// Index internal call participants too. Requires tracing.
+	pub address_index_traces: bool,
+	/// Index blocks by log addresses and topics for fast log filtering.
+	pub log_index: bool,
 }
 
 #[cfg(test)]
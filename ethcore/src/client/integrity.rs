@@ -0,0 +1,297 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Database integrity checks.
+
+use std::fmt;
+
+use account_db::AccountDB;
+use basic_account::BasicAccount;
+use blockchain::{BlockChain, BlockProvider};
+use blockchain::extras::TransactionAddress;
+use header::BlockNumber;
+use rlp;
+use util::{H256, HashDB};
+use util::sha3::SHA3_EMPTY;
+use util::trie::{TrieDB, Trie, TrieError};
+
+// Number of blocks between progress messages.
+const PROGRESS_INTERVAL: BlockNumber = 10_000;
+
+/// Inconsistency found in the database.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+	/// Canonical block number has no hash assigned.
+	MissingHash(BlockNumber),
+	/// Canonical block has no header.
+	MissingHeader(BlockNumber, H256),
+	/// Stored header does not hash to the expected value.
+	CorruptHeader(BlockNumber, H256),
+	/// Canonical block has no details.
+	MissingDetails(BlockNumber, H256),
+	/// Block details or header do not point to the previous canonical block.
+	BrokenLink(BlockNumber, H256),
+	/// Canonical block has no body.
+	MissingBody(BlockNumber, H256),
+	/// Canonical block has no receipts or their number differs from the number of transactions.
+	MissingReceipts(BlockNumber, H256),
+	/// Transaction of a canonical block is not indexed or points elsewhere.
+	WrongTransactionAddress(BlockNumber, H256),
+	/// Node of the state trie is missing.
+	MissingStateNode(BlockNumber, H256),
+	/// Contract code is missing.
+	MissingCode(BlockNumber, H256),
+}
+
+impl Issue {
+	/// Number of the affected block.
+	pub fn block_number(&self) -> BlockNumber {
+		match *self {
+			Issue::MissingHash(number) => number,
+			Issue::MissingHeader(number, _) | Issue::CorruptHeader(number, _) |
+			Issue::MissingDetails(number, _) | Issue::BrokenLink(number, _) |
+			Issue::MissingBody(number, _) | Issue::MissingReceipts(number, _) |
+			Issue::WrongTransactionAddress(number, _) | Issue::MissingStateNode(number, _) |
+			Issue::MissingCode(number, _) => number,
+		}
+	}
+}
+
+impl fmt::Display for Issue {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Issue::MissingHash(number) => write!(f, "#{}: canonical hash missing", number),
+			Issue::MissingHeader(number, ref hash) => write!(f, "#{} ({}): header missing", number, hash),
+			Issue::CorruptHeader(number, ref hash) => write!(f, "#{} ({}): header corrupt", number, hash),
+			Issue::MissingDetails(number, ref hash) => write!(f, "#{} ({}): block details missing", number, hash),
+			Issue::BrokenLink(number, ref hash) => write!(f, "#{} ({}): parent is not the previous canonical block", number, hash),
+			Issue::MissingBody(number, ref hash) => write!(f, "#{} ({}): body missing", number, hash),
+			Issue::MissingReceipts(number, ref hash) => write!(f, "#{} ({}): receipts missing or incomplete", number, hash),
+			Issue::WrongTransactionAddress(number, ref tx) => write!(f, "#{}: transaction {} not indexed", number, tx),
+			Issue::MissingStateNode(number, ref node) => write!(f, "#{}: state node {} missing", number, node),
+			Issue::MissingCode(number, ref code) => write!(f, "#{}: code {} missing", number, code),
+		}
+	}
+}
+
+/// Result of the database integrity check.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IntegrityReport {
+	/// Number of checked canonical blocks.
+	pub blocks: u64,
+	/// Number of accounts in the state at the best block.
+	pub accounts: u64,
+	/// Recent blocks which have the state root node available.
+	pub state_roots: Vec<BlockNumber>,
+	/// Found inconsistencies.
+	pub issues: Vec<Issue>,
+}
+
+impl IntegrityReport {
+	/// Whether no inconsistencies were found.
+	pub fn is_ok(&self) -> bool {
+		self.issues.is_empty()
+	}
+}
+
+/// Walk the canonical chain from the genesis to the best block, skipping the gap left
+/// by warp sync, and check headers, details, bodies, receipts and transaction addresses.
+pub fn check_chain(chain: &BlockChain, report: &mut IntegrityReport) {
+	let best = chain.best_block_number();
+	let gap = match (chain.best_ancient_number(), chain.first_block_number()) {
+		(Some(ancient), Some(first)) => Some((ancient, first)),
+		_ => None,
+	};
+	let earliest_body = chain.earliest_body_number();
+
+	let mut parent_hash = None;
+	for number in 0..best + 1 {
+		if let Some((ancient, first)) = gap {
+			if number > ancient && number < first {
+				parent_hash = None;
+				continue;
+			}
+		}
+		if number % PROGRESS_INTERVAL == 0 {
+			info!(target: "integrity", "Checking block #{}", number);
+		}
+		report.blocks += 1;
+
+		let hash = match chain.block_hash(number) {
+			Some(hash) => hash,
+			None => {
+				report.issues.push(Issue::MissingHash(number));
+				parent_hash = None;
+				continue;
+			}
+		};
+
+		match chain.block_header_data(&hash) {
+			Some(ref header) if header.hash() != hash => report.issues.push(Issue::CorruptHeader(number, hash)),
+			Some(ref header) if parent_hash.map_or(false, |parent| header.parent_hash() != parent) =>
+				report.issues.push(Issue::BrokenLink(number, hash)),
+			Some(_) => {},
+			None => report.issues.push(Issue::MissingHeader(number, hash)),
+		}
+
+		match chain.block_details(&hash) {
+			Some(ref details) if details.number != number || parent_hash.map_or(false, |parent| details.parent != parent) =>
+				report.issues.push(Issue::BrokenLink(number, hash)),
+			Some(_) => {},
+			None => report.issues.push(Issue::MissingDetails(number, hash)),
+		}
+		parent_hash = Some(hash);
+
+		// bodies and receipts of expired blocks are gone on purpose.
+		if number > 0 && number < earliest_body {
+			continue;
+		}
+
+		let tx_hashes = match chain.block_body(&hash) {
+			Some(body) => body.transaction_hashes(),
+			None => {
+				report.issues.push(Issue::MissingBody(number, hash));
+				continue;
+			}
+		};
+
+		// genesis is inserted without receipts.
+		if number > 0 {
+			match chain.block_receipts(&hash) {
+				Some(ref receipts) if receipts.receipts.len() == tx_hashes.len() => {},
+				_ => report.issues.push(Issue::MissingReceipts(number, hash)),
+			}
+		}
+
+		for (index, tx_hash) in tx_hashes.into_iter().enumerate() {
+			let expected = TransactionAddress { block_hash: hash, index: index };
+			if chain.transaction_address(&tx_hash).as_ref() != Some(&expected) {
+				report.issues.push(Issue::WrongTransactionAddress(number, tx_hash));
+			}
+		}
+	}
+}
+
+fn missing_node(number: BlockNumber, err: Box<TrieError>) -> Issue {
+	match *err {
+		TrieError::InvalidStateRoot(hash) | TrieError::IncompleteDatabase(hash) => Issue::MissingStateNode(number, hash),
+	}
+}
+
+fn check_storage(db: &HashDB, root: &H256) -> Result<(), Box<TrieError>> {
+	let trie = TrieDB::new(db, root)?;
+	for item in trie.iter()? {
+		item?;
+	}
+	Ok(())
+}
+
+/// Walk the whole state with given root: the account trie, every storage trie and contract code.
+/// Returns the number of accounts, or the issues found.
+pub fn check_state(db: &HashDB, number: BlockNumber, root: &H256) -> Result<u64, Vec<Issue>> {
+	let mut issues = Vec::new();
+	let mut accounts = 0;
+
+	let account_trie = TrieDB::new(db, root).map_err(|e| vec![missing_node(number, e)])?;
+	let iter = account_trie.iter().map_err(|e| vec![missing_node(number, e)])?;
+	for item in iter {
+		let (key, value) = match item {
+			Ok(item) => item,
+			Err(e) => {
+				// the rest of the trie can't be reached reliably.
+				issues.push(missing_node(number, e));
+				break;
+			}
+		};
+		accounts += 1;
+
+		let account: BasicAccount = rlp::decode(&*value);
+		let account_db = AccountDB::from_hash(db, H256::from_slice(&key));
+
+		if account.code_hash != SHA3_EMPTY && !account_db.contains(&account.code_hash) {
+			issues.push(Issue::MissingCode(number, account.code_hash));
+		}
+
+		if let Err(e) = check_storage(&account_db, &account.storage_root) {
+			issues.push(missing_node(number, e));
+		}
+	}
+
+	match issues.is_empty() {
+		true => Ok(accounts),
+		false => Err(issues),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use blockchain::{BlockChain, Config};
+	use blockchain::extras::BlockDetails;
+	use blockchain::generator::{ChainGenerator, ChainIterator, BlockFinalizer};
+	use db;
+	use spec::Spec;
+	use util::{H256, MemoryDB};
+	use util::kvdb::KeyValueDB;
+	use util::sha3::Hashable;
+	use views::BlockView;
+	use super::*;
+
+	#[test]
+	fn reports_missing_body_and_details() {
+		let mut canon_chain = ChainGenerator::default();
+		let mut finalizer = BlockFinalizer::default();
+		let genesis = canon_chain.generate(&mut finalizer).unwrap();
+		let blocks: Vec<_> = (0..3).map(|_| canon_chain.generate(&mut finalizer).unwrap()).collect();
+		let hashes: Vec<H256> = blocks.iter().map(|b| BlockView::new(b).header_view().sha3()).collect();
+
+		let db: Arc<KeyValueDB> = Arc::new(::util::kvdb::in_memory(db::NUM_COLUMNS.unwrap_or(0)));
+		{
+			let bc = BlockChain::new(Config::default(), &genesis, db.clone(), Spec::new_null().engine);
+			for block in &blocks {
+				let mut batch = db.transaction();
+				bc.insert_block(&mut batch, block, vec![]);
+				db.write(batch).unwrap();
+				bc.commit();
+			}
+
+			let mut report = IntegrityReport::default();
+			check_chain(&bc, &mut report);
+			assert!(report.is_ok());
+			assert_eq!(report.blocks, 4);
+		}
+
+		let mut batch = db.transaction();
+		batch.delete(db::COL_BODIES, &hashes[0]);
+		batch.delete(db::COL_EXTRA, &<H256 as db::Key<BlockDetails>>::key(&hashes[1]));
+		db.write(batch).unwrap();
+
+		let bc = BlockChain::new(Config::default(), &genesis, db.clone(), Spec::new_null().engine);
+		let mut report = IntegrityReport::default();
+		check_chain(&bc, &mut report);
+		assert_eq!(report.issues, vec![
+			Issue::MissingBody(1, hashes[0]),
+			Issue::MissingDetails(2, hashes[1]),
+		]);
+	}
+
+	#[test]
+	fn reports_missing_state_root() {
+		let db = MemoryDB::new();
+		let root = b"missing".sha3();
+		assert_eq!(check_state(&db, 5, &root), Err(vec![Issue::MissingStateNode(5, root)]));
+	}
+}
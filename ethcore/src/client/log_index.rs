@@ -0,0 +1,321 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Inverted index of logs of canonical blocks.
+//!
+//! Keys are `address ++ topic ++ block number` for every topic of every log, regardless of
+//! the topic position. Every log is also indexed under its address with a zero topic and
+//! under its topics with a zero address, so filters naming only addresses or only topics
+//! can be served. Candidate blocks are a superset of the matching ones; receipts are
+//! still checked against the filter.
+//!
+//! New blocks are indexed on import; older blocks are backfilled towards the genesis and
+//! the lowest block from which the index is complete is stored under `INDEXED_FROM_KEY`.
+//! Blocks imported while indexing is disabled are not indexed, so the key is removed on
+//! such runs and the index restarts above the best block once it is enabled again.
+
+use std::cmp;
+use std::collections::HashSet;
+
+use blockchain::{BlockChain, BlockProvider};
+use byteorder::{BigEndian, ByteOrder};
+use db::COL_LOG_INDEX;
+use header::BlockNumber;
+use receipt::Receipt;
+use types::filter::Filter;
+use util::{Address, H256, FixedHash, DBTransaction, KeyValueDB};
+
+const INDEXED_FROM_KEY: &'static [u8] = b"indexed_from";
+
+fn prefix(address: &Address, topic: &H256) -> [u8; 52] {
+	let mut key = [0u8; 52];
+	key[..20].copy_from_slice(&**address);
+	key[20..].copy_from_slice(&**topic);
+	key
+}
+
+fn key(address: &Address, topic: &H256, number: BlockNumber) -> [u8; 60] {
+	let mut key = [0u8; 60];
+	key[..52].copy_from_slice(&prefix(address, topic));
+	BigEndian::write_u64(&mut key[52..], number);
+	key
+}
+
+/// Index keys of the logs in the given receipts, without the block number.
+pub fn block_keys(receipts: &[Receipt]) -> HashSet<(Address, H256)> {
+	let mut keys = HashSet::new();
+	for log in receipts.iter().flat_map(|receipt| receipt.logs.iter()) {
+		keys.insert((log.address, H256::zero()));
+		for topic in &log.topics {
+			keys.insert((log.address, *topic));
+			keys.insert((Address::zero(), *topic));
+		}
+	}
+	keys
+}
+
+/// Lowest block from which all canonical blocks are indexed, or `None` if the index
+/// was never started.
+pub fn indexed_from(db: &KeyValueDB) -> Option<BlockNumber> {
+	db.get(COL_LOG_INDEX, INDEXED_FROM_KEY).expect("Low level database error. Some issue with disk?")
+		.map(|number| BigEndian::read_u64(&number))
+}
+
+fn set_indexed_from(batch: &mut DBTransaction, number: BlockNumber) {
+	let mut value = [0u8; 8];
+	BigEndian::write_u64(&mut value, number);
+	batch.put(COL_LOG_INDEX, INDEXED_FROM_KEY, &value);
+}
+
+/// Start the index above the given best block if it was not started yet.
+pub fn init(db: &KeyValueDB, best: BlockNumber) {
+	if indexed_from(db).is_none() {
+		let mut batch = DBTransaction::new();
+		set_indexed_from(&mut batch, best + 1);
+		db.write(batch).expect("Low level database error. Some issue with disk?");
+	}
+}
+
+/// Forget the indexed range, as blocks imported from now on are not indexed.
+pub fn reset(db: &KeyValueDB) {
+	if indexed_from(db).is_some() {
+		let mut batch = DBTransaction::new();
+		batch.delete(COL_LOG_INDEX, INDEXED_FROM_KEY);
+		db.write(batch).expect("Low level database error. Some issue with disk?");
+	}
+}
+
+/// Update the index along the route of a block import. `keys` are the index keys of the
+/// imported block; receipts of other blocks on the route are read from `chain`.
+pub fn import(
+	batch: &mut DBTransaction,
+	chain: &BlockChain,
+	hash: &H256,
+	number: BlockNumber,
+	keys: HashSet<(Address, H256)>,
+	enacted: &[H256],
+	retracted: &[H256],
+) {
+	for retracted_hash in retracted {
+		retract(batch, chain, retracted_hash);
+	}
+
+	for enacted_hash in enacted.iter().filter(|enacted_hash| *enacted_hash != hash) {
+		if let (Some(number), Some(receipts)) = (chain.block_number(enacted_hash), chain.block_receipts(enacted_hash)) {
+			for (address, topic) in block_keys(&receipts.receipts) {
+				batch.put(COL_LOG_INDEX, &key(&address, &topic, number), &[]);
+			}
+		}
+	}
+
+	if enacted.contains(hash) {
+		for (address, topic) in keys {
+			batch.put(COL_LOG_INDEX, &key(&address, &topic, number), &[]);
+		}
+	}
+}
+
+/// Remove the index entries of a block which is no longer canonical.
+/// Must be called while the block still has its number in `chain`.
+pub fn retract(batch: &mut DBTransaction, chain: &BlockChain, hash: &H256) {
+	if let (Some(number), Some(receipts)) = (chain.block_number(hash), chain.block_receipts(hash)) {
+		for (address, topic) in block_keys(&receipts.receipts) {
+			batch.delete(COL_LOG_INDEX, &key(&address, &topic, number));
+		}
+	}
+}
+
+/// Index entries of canonical blocks right below the indexed range, read without
+/// holding the import lock. Committed by `commit` unless the chain changed meanwhile.
+pub struct Backfill {
+	batch: DBTransaction,
+	from: BlockNumber,
+	end: BlockNumber,
+	top: H256,
+}
+
+impl Backfill {
+	/// Number of blocks in the step.
+	pub fn blocks(&self) -> u64 {
+		self.end - self.from
+	}
+
+	/// Write the step if the indexed range and the canonical block at its top are unchanged.
+	/// Must be called with the import lock held. Returns whether the step was written.
+	pub fn commit(mut self, db: &KeyValueDB, chain: &BlockChain) -> bool {
+		if indexed_from(db) != Some(self.end) || chain.block_hash(self.end - 1) != Some(self.top) {
+			return false;
+		}
+		set_indexed_from(&mut self.batch, self.from);
+		db.write(self.batch).expect("Low level database error. Some issue with disk?");
+		true
+	}
+}
+
+/// Checks if there are imported blocks below the indexed range.
+pub fn pending(db: &KeyValueDB, chain: &BlockChain) -> bool {
+	match indexed_from(db) {
+		None | Some(0) => false,
+		// the genesis has no receipts.
+		Some(1) => true,
+		Some(number) => chain.block_hash(number - 1).map_or(false, |hash| chain.block_receipts(&hash).is_some()),
+	}
+}
+
+/// Read up to `count` canonical blocks below the indexed range, moving towards the genesis.
+/// Stops at blocks which are not imported yet, like the ones below a restored snapshot.
+/// Returns `None` if there is nothing to index.
+pub fn backfill(db: &KeyValueDB, chain: &BlockChain, count: u64) -> Option<Backfill> {
+	let end = match indexed_from(db) {
+		Some(number) if number > 0 => number,
+		_ => return None,
+	};
+	let top = match chain.block_hash(end - 1) {
+		Some(hash) => hash,
+		None => return None,
+	};
+	let start = cmp::max(end.saturating_sub(count), chain.earliest_body_number());
+
+	let mut batch = DBTransaction::new();
+	let mut next = end;
+	while next > start {
+		let number = next - 1;
+		let keys = match chain.block_hash(number).map(|hash| chain.block_receipts(&hash)) {
+			Some(Some(receipts)) => block_keys(&receipts.receipts),
+			// the genesis has no receipts.
+			Some(None) if number == 0 => HashSet::new(),
+			_ => break,
+		};
+		for (address, topic) in keys {
+			batch.put(COL_LOG_INDEX, &key(&address, &topic, number), &[]);
+		}
+		next = number;
+	}
+
+	match next == end {
+		true => None,
+		false => Some(Backfill { batch: batch, from: next, end: end, top: top }),
+	}
+}
+
+/// Numbers of blocks in `from..to + 1` which may contain logs matching the filter.
+/// Returns `None` if the filter names neither addresses nor topics.
+pub fn blocks(db: &KeyValueDB, filter: &Filter, from: BlockNumber, to: BlockNumber) -> Option<HashSet<BlockNumber>> {
+	let addresses = match filter.address {
+		Some(ref addresses) if !addresses.is_empty() => addresses.clone(),
+		_ => vec![Address::zero()],
+	};
+	// any single topic position narrows down the candidates enough.
+	let topics = filter.topics.iter()
+		.filter_map(|topics| topics.as_ref())
+		.find(|topics| !topics.is_empty())
+		.cloned()
+		.unwrap_or_else(|| vec![H256::zero()]);
+
+	if addresses == vec![Address::zero()] && topics == vec![H256::zero()] {
+		return None;
+	}
+
+	let mut blocks = HashSet::new();
+	for address in &addresses {
+		for topic in &topics {
+			let prefix = prefix(address, topic);
+			let start = key(address, topic, from);
+			let numbers = db.iter_from(COL_LOG_INDEX, &start)
+				.take_while(|&(ref key, _)| key.starts_with(&prefix))
+				.map(|(key, _)| BigEndian::read_u64(&key[52..]))
+				.take_while(|number| *number <= to);
+			blocks.extend(numbers);
+		}
+	}
+	Some(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+	use db::NUM_COLUMNS;
+	use log_entry::LogEntry;
+	use receipt::Receipt;
+	use types::filter::Filter;
+	use ids::BlockId;
+	use util::{Address, H256, KeyValueDB, DBTransaction};
+	use util::kvdb::in_memory;
+	use super::*;
+
+	fn receipt(address: u64, topics: Vec<H256>) -> Receipt {
+		Receipt::new(H256::default(), 0.into(), vec![LogEntry {
+			address: Address::from(address),
+			topics: topics,
+			data: vec![],
+		}])
+	}
+
+	fn filter(address: Option<Vec<Address>>, topics: Vec<Option<Vec<H256>>>) -> Filter {
+		Filter {
+			from_block: BlockId::Earliest,
+			to_block: BlockId::Latest,
+			address: address,
+			topics: topics,
+			limit: None,
+		}
+	}
+
+	fn index(db: &KeyValueDB, number: BlockNumber, receipts: &[Receipt]) {
+		let mut batch = DBTransaction::new();
+		for (address, topic) in block_keys(receipts) {
+			batch.put(COL_LOG_INDEX, &key(&address, &topic, number), &[]);
+		}
+		db.write(batch).unwrap();
+	}
+
+	#[test]
+	fn finds_candidate_blocks() {
+		let db = in_memory(NUM_COLUMNS.unwrap_or(0));
+		let (t1, t2) = (H256::from(1), H256::from(2));
+		index(&db, 1, &[receipt(10, vec![t1])]);
+		index(&db, 2, &[receipt(10, vec![t2]), receipt(20, vec![t1])]);
+		index(&db, 3, &[receipt(20, vec![])]);
+
+		let numbers = |f: Filter, from, to| blocks(&db, &f, from, to).map(|b| {
+			let mut b: Vec<_> = b.into_iter().collect();
+			b.sort();
+			b
+		});
+
+		assert_eq!(numbers(filter(None, vec![]), 0, 10), None);
+		assert_eq!(numbers(filter(Some(vec![Address::from(10)]), vec![]), 0, 10), Some(vec![1, 2]));
+		assert_eq!(numbers(filter(Some(vec![Address::from(20)]), vec![]), 0, 10), Some(vec![2, 3]));
+		assert_eq!(numbers(filter(Some(vec![Address::from(10)]), vec![Some(vec![t1])]), 0, 10), Some(vec![1]));
+		assert_eq!(numbers(filter(None, vec![None, Some(vec![t1])]), 0, 10), Some(vec![1, 2]));
+		assert_eq!(numbers(filter(None, vec![Some(vec![t1, t2])]), 2, 10), Some(vec![2]));
+		assert_eq!(numbers(filter(Some(vec![Address::from(30)]), vec![]), 0, 10), Some(vec![]));
+		assert_eq!(block_keys(&[]), HashSet::new());
+	}
+
+	#[test]
+	fn restarts_above_best_block_after_reset() {
+		let db = in_memory(NUM_COLUMNS.unwrap_or(0));
+		init(&db, 10);
+		init(&db, 20);
+		assert_eq!(indexed_from(&db), Some(11));
+
+		reset(&db);
+		assert_eq!(indexed_from(&db), None);
+		init(&db, 20);
+		assert_eq!(indexed_from(&db), Some(21));
+	}
+}
@@ -22,10 +22,14 @@ mod error;
 mod test_client;
 mod trace;
 mod client;
+mod integrity;
+mod address_index;
+mod log_index;
 
 pub use self::client::*;
-pub use self::config::{Mode, ClientConfig, DatabaseCompactionProfile, BlockChainConfig, VMType};
+pub use self::config::{Mode, ClientConfig, DatabaseCompactionProfile, BlockChainConfig, FreezerConfig, VMType};
 pub use self::error::Error;
+pub use self::integrity::{Issue as IntegrityIssue, IntegrityReport};
 pub use self::test_client::{TestBlockChainClient, EachBlockWith};
 pub use self::chain_notify::ChainNotify;
 pub use self::traits::{BlockChainClient, MiningBlockChainClient, EngineClient};
@@ -35,6 +39,10 @@ pub use self::traits::ProvingBlockChainClient;
 pub use types::ids::*;
 pub use types::trace_filter::Filter as TraceFilter;
 pub use types::pruning_info::PruningInfo;
+pub use types::fee_history::FeeHistory;
+pub use types::address_transaction::AddressTransaction;
+pub use types::database_stats::{DatabaseStats, ColumnStats};
+pub use types::state_node::StateNode;
 pub use types::call_analytics::CallAnalytics;
 
 pub use executive::{Executed, Executive, TransactOptions};
@@ -40,6 +40,8 @@ use miner::{Miner, MinerService, TransactionImportResult};
 use spec::Spec;
 use types::mode::Mode;
 use types::pruning_info::PruningInfo;
+use types::address_transaction::AddressTransaction;
+use types::database_stats::DatabaseStats;
 
 use verification::queue::QueueInfo;
 use block::{OpenBlock, SealedBlock};
@@ -48,6 +50,7 @@ use error::CallError;
 use trace::LocalizedTrace;
 use state_db::StateDB;
 use encoded;
+use types::state_node::StateNode;
 
 /// Test client.
 pub struct TestBlockChainClient {
@@ -95,6 +98,14 @@ pub struct TestBlockChainClient {
 	pub traces: RwLock<Option<Vec<LocalizedTrace>>>,
 	/// Pruning history size to report.
 	pub history: RwLock<Option<u64>>,
+	/// Earliest block with body and receipts to serve. Earlier ones are treated as expired.
+	pub earliest_body: RwLock<BlockNumber>,
+	/// State trie nodes and code, by hash.
+	pub state_nodes: RwLock<HashMap<H256, Bytes>>,
+	/// State root set for each new block.
+	pub state_root: RwLock<H256>,
+	/// Pending fast sync pivot.
+	pub fast_sync_pivot: RwLock<Option<encoded::Header>>,
 }
 
 /// Used for generating test client blocks.
@@ -161,6 +172,10 @@ impl TestBlockChainClient {
 			first_block: RwLock::new(None),
 			traces: RwLock::new(None),
 			history: RwLock::new(None),
+			earliest_body: RwLock::new(0),
+			state_nodes: RwLock::new(HashMap::new()),
+			state_root: RwLock::new(SHA3_NULL_RLP),
+			fast_sync_pivot: RwLock::new(None),
 		};
 
 		// insert genesis hash.
@@ -181,6 +196,12 @@ impl TestBlockChainClient {
 		*self.execution_result.write() = Some(result);
 	}
 
+	// Returns true if the block body and receipts are older than `earliest_body`.
+	fn is_expired(&self, hash: &H256) -> bool {
+		let earliest_body = *self.earliest_body.read();
+		self.block_number(BlockId::Hash(hash.clone())).map_or(false, |n| n > 0 && n < earliest_body)
+	}
+
 	/// Set the balance of account `address` to `balance`.
 	pub fn set_balance(&self, address: Address, balance: U256) {
 		self.balances.write().insert(address, balance);
@@ -226,6 +247,7 @@ impl TestBlockChainClient {
 			header.set_number(n as BlockNumber);
 			header.set_gas_limit(U256::from(1_000_000));
 			header.set_extra_data(self.extra_data.clone());
+			header.set_state_root(self.state_root.read().clone());
 			let uncles = match with {
 				EachBlockWith::Uncle | EachBlockWith::UncleAndTransaction => {
 					let mut uncles = RlpStream::new_list(1);
@@ -456,6 +478,10 @@ impl BlockChainClient for TestBlockChainClient {
 		None	// Simple default.
 	}
 
+	fn address_transactions(&self, _address: &Address, _after: Option<&H256>, _count: u64) -> Option<Vec<AddressTransaction>> {
+		None
+	}
+
 	fn transaction_block(&self, _id: TransactionId) -> Option<H256> {
 		None	// Simple default.
 	}
@@ -472,6 +498,17 @@ impl BlockChainClient for TestBlockChainClient {
 		self.receipts.read().get(&id).cloned()
 	}
 
+	fn localized_block_receipts(&self, id: BlockId) -> Option<Vec<LocalizedReceipt>> {
+		self.block_hash(id).map(|hash| {
+			let mut receipts: Vec<_> = self.receipts.read().values()
+				.filter(|r| r.block_hash == hash)
+				.cloned()
+				.collect();
+			receipts.sort_by_key(|r| r.transaction_index);
+			receipts
+		})
+	}
+
 	fn blocks_with_bloom(&self, _bloom: &H2048, _from_block: BlockId, _to_block: BlockId) -> Option<Vec<BlockNumber>> {
 		unimplemented!();
 	}
@@ -500,12 +537,17 @@ impl BlockChainClient for TestBlockChainClient {
 			.map(encoded::Header::new)
 	}
 
-	fn block_number(&self, _id: BlockId) -> Option<BlockNumber> {
-		unimplemented!()
+	fn block_number(&self, id: BlockId) -> Option<BlockNumber> {
+		match id {
+			BlockId::Number(number) => Some(number),
+			BlockId::Earliest => Some(0),
+			BlockId::Latest | BlockId::Pending => Some(self.numbers.read().len() as BlockNumber - 1),
+			BlockId::Hash(hash) => self.numbers.read().iter().find(|&(_, h)| *h == hash).map(|(n, _)| *n as BlockNumber),
+		}.and_then(|number| self.block_hash(BlockId::Number(number)).map(|_| number))
 	}
 
 	fn block_body(&self, id: BlockId) -> Option<encoded::Body> {
-		self.block_hash(id).and_then(|hash| self.blocks.read().get(&hash).map(|r| {
+		self.block_hash(id).and_then(|hash| if self.is_expired(&hash) { None } else { Some(hash) }).and_then(|hash| self.blocks.read().get(&hash).map(|r| {
 			let mut stream = RlpStream::new_list(2);
 			stream.append_raw(Rlp::new(r).at(1).as_raw(), 1);
 			stream.append_raw(Rlp::new(r).at(2).as_raw(), 1);
@@ -515,6 +557,7 @@ impl BlockChainClient for TestBlockChainClient {
 
 	fn block(&self, id: BlockId) -> Option<encoded::Block> {
 		self.block_hash(id)
+			.and_then(|hash| if self.is_expired(&hash) { None } else { Some(hash) })
 			.and_then(|hash| self.blocks.read().get(&hash).cloned())
 			.map(encoded::Block::new)
 	}
@@ -571,6 +614,9 @@ impl BlockChainClient for TestBlockChainClient {
 
 	// TODO: returns just hashes instead of node state rlp(?)
 	fn state_data(&self, hash: &H256) -> Option<Bytes> {
+		if let Some(data) = self.state_nodes.read().get(hash) {
+			return Some(data.clone());
+		}
 		// starts with 'f' ?
 		if *hash > H256::from("f000000000000000000000000000000000000000000000000000000000000000") {
 			let mut rlp = RlpStream::new();
@@ -581,6 +627,9 @@ impl BlockChainClient for TestBlockChainClient {
 	}
 
 	fn block_receipts(&self, hash: &H256) -> Option<Bytes> {
+		if self.is_expired(hash) {
+			return None;
+		}
 		// starts with 'f' ?
 		if *hash > H256::from("f000000000000000000000000000000000000000000000000000000000000000") {
 			let receipt = BlockReceipts::new(vec![Receipt::new(
@@ -643,6 +692,34 @@ impl BlockChainClient for TestBlockChainClient {
 		self.import_block(b)
 	}
 
+	fn state_node_known(&self, _owner: Option<H256>, hash: &H256) -> bool {
+		*hash == SHA3_NULL_RLP || self.state_nodes.read().contains_key(hash)
+	}
+
+	fn import_state_nodes(&self, nodes: Vec<StateNode>, _accounts: Vec<H256>) {
+		let mut state_nodes = self.state_nodes.write();
+		for node in nodes {
+			state_nodes.insert(node.data.sha3(), node.data);
+		}
+	}
+
+	fn import_fast_sync_pivot(&self, b: Bytes, _r: Bytes) -> Result<H256, BlockImportError> {
+		*self.fast_sync_pivot.write() = None;
+		self.import_block(b)
+	}
+
+	fn fast_sync_pivot(&self) -> Option<encoded::Header> {
+		self.fast_sync_pivot.read().clone()
+	}
+
+	fn set_fast_sync_pivot(&self, header: Option<encoded::Header>) {
+		*self.fast_sync_pivot.write() = header;
+	}
+
+	fn verify_header_seal(&self, _header: &BlockHeader) -> bool {
+		true
+	}
+
 	fn queue_info(&self) -> QueueInfo {
 		QueueInfo {
 			verified_queue_size: self.queue_size.load(AtomicOrder::Relaxed),
@@ -671,7 +748,8 @@ impl BlockChainClient for TestBlockChainClient {
 			first_block_hash: self.first_block.read().as_ref().map(|x| x.0),
 			first_block_number: self.first_block.read().as_ref().map(|x| x.1),
 			ancient_block_hash: self.ancient_block.read().as_ref().map(|x| x.0),
-			ancient_block_number: self.ancient_block.read().as_ref().map(|x| x.1)
+			ancient_block_number: self.ancient_block.read().as_ref().map(|x| x.1),
+			earliest_body_number: *self.earliest_body.read(),
 		}
 	}
 
@@ -718,9 +796,14 @@ impl BlockChainClient for TestBlockChainClient {
 		PruningInfo {
 			earliest_chain: 1,
 			earliest_state: self.history.read().as_ref().map(|x| best_num - x).unwrap_or(0),
+			earliest_body: *self.earliest_body.read(),
 		}
 	}
 
+	fn database_stats(&self) -> DatabaseStats {
+		DatabaseStats::default()
+	}
+
 	fn call_contract(&self, _address: Address, _data: Bytes) -> Result<Bytes, String> { Ok(vec![]) }
 
 	fn registrar_address(&self) -> Option<Address> { None }
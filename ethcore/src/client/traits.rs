@@ -16,16 +16,16 @@
 
 use std::collections::BTreeMap;
 use util::{U256, Address, H256, H2048, Bytes, Itertools};
-use util::stats::Histogram;
+use util::stats::{Histogram, weighted_percentiles};
 use blockchain::TreeRoute;
 use verification::queue::QueueInfo as BlockQueueInfo;
 use block::{OpenBlock, SealedBlock};
-use header::{BlockNumber};
+use header::{BlockNumber, Header};
 use transaction::{LocalizedTransaction, PendingTransaction, SignedTransaction};
 use log_entry::LocalizedLogEntry;
 use filter::Filter;
 use error::{ImportResult, CallError};
-use receipt::LocalizedReceipt;
+use receipt::{Receipt, LocalizedReceipt};
 use trace::LocalizedTrace;
 use evm::{Factory as EvmFactory, Schedule};
 use executive::Executed;
@@ -39,7 +39,12 @@ use types::blockchain_info::BlockChainInfo;
 use types::block_status::BlockStatus;
 use types::mode::Mode;
 use types::pruning_info::PruningInfo;
+use types::fee_history::FeeHistory;
+use types::address_transaction::AddressTransaction;
+use types::database_stats::DatabaseStats;
+use types::state_node::StateNode;
 use encoded;
+use rlp::UntrustedRlp;
 
 #[ipc(client_ident="RemoteClient")]
 /// Blockchain database client. Owns and manages a blockchain and a block queue.
@@ -132,6 +137,12 @@ pub trait BlockChainClient : Sync + Send {
 	/// Get transaction with given hash.
 	fn transaction(&self, id: TransactionId) -> Option<LocalizedTransaction>;
 
+	/// Get canonical transactions in which `address` takes part in chain order, if the address
+	/// index is enabled, otherwise `None`. If `after` is set the list starts with the following transaction.
+	/// The list ends before the first transaction whose block body is not available, so that listing
+	/// after the last returned transaction never skips any.
+	fn address_transactions(&self, address: &Address, after: Option<&H256>, count: u64) -> Option<Vec<AddressTransaction>>;
+
 	/// Get the hash of block that contains the transaction, if any.
 	fn transaction_block(&self, id: TransactionId) -> Option<H256>;
 
@@ -141,6 +152,9 @@ pub trait BlockChainClient : Sync + Send {
 	/// Get transaction receipt with given hash.
 	fn transaction_receipt(&self, id: TransactionId) -> Option<LocalizedReceipt>;
 
+	/// Get receipts of all transactions in given block.
+	fn localized_block_receipts(&self, id: BlockId) -> Option<Vec<LocalizedReceipt>>;
+
 	/// Get a tree route between `from` and `to`.
 	/// See `BlockChain::tree_route`.
 	fn tree_route(&self, from: &H256, to: &H256) -> Option<TreeRoute>;
@@ -160,6 +174,27 @@ pub trait BlockChainClient : Sync + Send {
 	/// Import a block with transaction receipts. Does no sealing and transaction validation.
 	fn import_block_with_receipts(&self, block_bytes: Bytes, receipts_bytes: Bytes) -> Result<H256, BlockImportError>;
 
+	/// Check if a state node is in the state database. Nodes of storage tries and code
+	/// are looked up in the storage of the account with address hash `owner`.
+	fn state_node_known(&self, owner: Option<H256>, hash: &H256) -> bool;
+
+	/// Write state nodes downloaded by fast sync to the state database. `accounts` are the
+	/// address hashes of non-empty accounts in the imported nodes.
+	fn import_state_nodes(&self, nodes: Vec<StateNode>, accounts: Vec<H256>);
+
+	/// Import the fast sync pivot block with receipts without executing it and make it the
+	/// best block. The state of the block must be in the state database.
+	fn import_fast_sync_pivot(&self, block_bytes: Bytes, receipts_bytes: Bytes) -> Result<H256, BlockImportError>;
+
+	/// Get the header of the pivot block of an unfinished fast sync.
+	fn fast_sync_pivot(&self) -> Option<encoded::Header>;
+
+	/// Store the header of the fast sync pivot block, or clear it.
+	fn set_fast_sync_pivot(&self, header: Option<encoded::Header>);
+
+	/// Check the seal of a block header received from the network, e.g. its proof of work.
+	fn verify_header_seal(&self, header: &Header) -> bool;
+
 	/// Get block queue information.
 	fn queue_info(&self) -> BlockQueueInfo;
 
@@ -248,6 +283,53 @@ pub trait BlockChainClient : Sync + Send {
 		Histogram::new(corpus, bucket_number)
 	}
 
+	/// Gas used ratio and gas price percentiles of `block_count` blocks ending with `newest`.
+	fn fee_history(&self, block_count: u64, newest: BlockId, percentiles: &[f64]) -> Option<FeeHistory> {
+		let newest = match self.block_number(newest) {
+			Some(number) => number,
+			None => return None,
+		};
+		let oldest = (newest + 1).saturating_sub(block_count);
+		let mut history = FeeHistory {
+			oldest_block: oldest,
+			gas_used_ratio: Vec::new(),
+			gas_price: Vec::new(),
+		};
+
+		for number in oldest..(newest + 1) {
+			let block = match self.block(BlockId::Number(number)) {
+				Some(block) => block,
+				None => return None,
+			};
+			let header = block.header_view();
+			let gas_limit = header.gas_limit();
+			history.gas_used_ratio.push(match gas_limit.is_zero() {
+				true => 0.0,
+				false => header.gas_used().low_u64() as f64 / gas_limit.low_u64() as f64,
+			});
+
+			// receipts hold cumulative gas used, fall back to gas limits of transactions if they are missing.
+			let receipts: Vec<Receipt> = self.block_receipts(&header.hash())
+				.and_then(|receipts| UntrustedRlp::new(&receipts).as_val().ok())
+				.unwrap_or_else(Vec::new);
+			let mut cumulative = U256::zero();
+			let corpus = block.transaction_views().iter().enumerate().map(|(i, t)| {
+				let gas_used = match receipts.get(i) {
+					Some(receipt) => {
+						let used = receipt.gas_used - cumulative;
+						cumulative = receipt.gas_used;
+						used
+					},
+					None => t.gas(),
+				};
+				(t.gas_price(), gas_used)
+			}).collect();
+			history.gas_price.push(weighted_percentiles(corpus, percentiles));
+		}
+
+		Some(history)
+	}
+
 	/// Get the preferred network ID to sign on
 	fn signing_network_id(&self) -> Option<u64>;
 
@@ -270,6 +352,9 @@ pub trait BlockChainClient : Sync + Send {
 	/// Returns information about pruning/data availability.
 	fn pruning_info(&self) -> PruningInfo;
 
+	/// Returns approximate sizes of the database columns and database activity counters.
+	fn database_stats(&self) -> DatabaseStats;
+
 	/// Like `call`, but with various defaults. Designed to be used for calling contracts.
 	fn call_contract(&self, address: Address, data: Bytes) -> Result<Bytes, String>;
 
@@ -19,7 +19,7 @@
 use std::ops::Deref;
 use std::hash::Hash;
 use std::collections::HashMap;
-use util::{DBTransaction, Database, RwLock};
+use util::{DBTransaction, KeyValueDB, RwLock};
 
 use rlp;
 
@@ -36,8 +36,18 @@ pub const COL_EXTRA: Option<u32> = Some(3);
 pub const COL_TRACE: Option<u32> = Some(4);
 /// Column for Traces
 pub const COL_ACCOUNT_BLOOM: Option<u32> = Some(5);
+/// Column for flat accounts and storage
+pub const COL_FLAT_STATE: Option<u32> = Some(6);
+/// Column for transactions indexed by address
+pub const COL_ADDRESS_INDEX: Option<u32> = Some(7);
+/// Column for blocks indexed by log address and topics
+pub const COL_LOG_INDEX: Option<u32> = Some(8);
 /// Number of columns in DB
-pub const NUM_COLUMNS: Option<u32> = Some(6);
+pub const NUM_COLUMNS: Option<u32> = Some(9);
+/// Names of the columns in DB, for reporting
+pub const COLUMN_NAMES: [&'static str; 9] = [
+	"state", "headers", "bodies", "extra", "trace", "account_bloom", "flat_state", "address_index", "log_index",
+];
 
 /// Modes for updating caches.
 #[derive(Clone, Copy)]
@@ -212,7 +222,7 @@ impl Writable for DBTransaction {
 	}
 }
 
-impl Readable for Database {
+impl<KVDB: KeyValueDB + ?Sized> Readable for KVDB {
 	fn read<T, R>(&self, col: Option<u32>, key: &Key<T, Target = R>) -> Option<T> where T: rlp::Decodable, R: Deref<Target = [u8]> {
 		let result = self.get(col, &key.key());
 
@@ -0,0 +1,716 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Flat account and storage state.
+//!
+//! Accounts and storage values of a single finalized state are kept in `COL_FLAT_STATE`,
+//! keyed by the address hash and by the address hash followed by the storage key hash.
+//! Changes made by recent blocks are kept in memory as diff layers on top of it, keyed by
+//! the state root they produce. Lookups for a root which is neither the disk layer nor a
+//! known diff layer return `None`; the caller has to fall back to the trie then.
+//!
+//! The disk layer is generated from the trie in steps, in address hash order. The lowest
+//! address hash not generated yet is stored under `GENERATOR_KEY`, so generation resumes
+//! where it stopped. Until it completes, lookups of accounts above it return `None` and
+//! flattening leaves them alone; they are read from the trie of the current disk root later.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::iter::Peekable;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use account_db::AccountDB;
+use basic_account::BasicAccount;
+use db::COL_FLAT_STATE;
+use rlp;
+use util::{Bytes, H256, HashDB, FixedHash, KeyValueDB, DBTransaction, RwLock};
+use util::sha3::SHA3_NULL_RLP;
+use util::trie::{TrieDB, Trie, TrieError};
+
+const ACCOUNT_PREFIX: &'static [u8] = b"a";
+const STORAGE_PREFIX: &'static [u8] = b"s";
+const ROOT_KEY: &'static [u8] = b"root";
+const GENERATOR_KEY: &'static [u8] = b"generator";
+
+fn account_key(addr_hash: &H256) -> [u8; 33] {
+	let mut key = [0u8; 33];
+	key[0] = ACCOUNT_PREFIX[0];
+	key[1..].copy_from_slice(&**addr_hash);
+	key
+}
+
+fn storage_prefix(addr_hash: &H256) -> [u8; 33] {
+	let mut key = [0u8; 33];
+	key[0] = STORAGE_PREFIX[0];
+	key[1..].copy_from_slice(&**addr_hash);
+	key
+}
+
+fn storage_key(addr_hash: &H256, key_hash: &H256) -> [u8; 65] {
+	let mut key = [0u8; 65];
+	key[0] = STORAGE_PREFIX[0];
+	key[1..33].copy_from_slice(&**addr_hash);
+	key[33..].copy_from_slice(&**key_hash);
+	key
+}
+
+// the address hash following the given one, `None` for the highest one.
+fn next_hash(hash: &H256) -> Option<H256> {
+	let mut next = *hash;
+	for byte in next.iter_mut().rev() {
+		if *byte == 0xff {
+			*byte = 0;
+		} else {
+			*byte += 1;
+			return Some(next);
+		}
+	}
+	None
+}
+
+/// State changes made on top of some state root.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FlatDiff {
+	/// Account RLPs by address hash. `None` if the account was removed.
+	accounts: HashMap<H256, Option<Bytes>>,
+	/// Storage value RLPs by address hash and storage key hash. `None` if the value was cleared.
+	storage: HashMap<H256, HashMap<H256, Option<Bytes>>>,
+	/// Accounts whose previous storage was wiped before the changes in `storage`.
+	wiped: HashSet<H256>,
+}
+
+impl FlatDiff {
+	/// Whether the diff contains no changes.
+	pub fn is_empty(&self) -> bool {
+		self.accounts.is_empty() && self.storage.is_empty() && self.wiped.is_empty()
+	}
+
+	/// Note a new value of an account.
+	pub fn note_account(&mut self, addr_hash: H256, account: Option<Bytes>) {
+		self.accounts.insert(addr_hash, account);
+	}
+
+	/// Note that all storage of an account was removed.
+	pub fn note_wiped(&mut self, addr_hash: H256) {
+		self.storage.remove(&addr_hash);
+		self.wiped.insert(addr_hash);
+	}
+
+	/// Note a new storage value of an account.
+	pub fn note_storage(&mut self, addr_hash: H256, key_hash: H256, value: Option<Bytes>) {
+		self.storage.entry(addr_hash).or_insert_with(HashMap::new).insert(key_hash, value);
+	}
+
+	/// Apply changes made on top of this diff.
+	pub fn merge(&mut self, newer: FlatDiff) {
+		for addr_hash in newer.wiped {
+			self.note_wiped(addr_hash);
+		}
+		for (addr_hash, storage) in newer.storage {
+			self.storage.entry(addr_hash).or_insert_with(HashMap::new).extend(storage);
+		}
+		self.accounts.extend(newer.accounts);
+	}
+
+	/// Look up an account. `None` if the account was not changed.
+	pub fn account(&self, addr_hash: &H256) -> Option<Option<Bytes>> {
+		self.accounts.get(addr_hash).cloned()
+	}
+
+	/// Look up a storage value. `None` if the value was not changed.
+	pub fn storage(&self, addr_hash: &H256, key_hash: &H256) -> Option<Option<Bytes>> {
+		if let Some(value) = self.storage.get(addr_hash).and_then(|storage| storage.get(key_hash)) {
+			return Some(value.clone());
+		}
+		match self.wiped.contains(addr_hash) {
+			true => Some(None),
+			false => None,
+		}
+	}
+}
+
+struct DiffLayer {
+	parent: H256,
+	diff: FlatDiff,
+}
+
+struct Layers {
+	// root of the state stored on disk or `None` if it was never generated.
+	disk_root: Option<H256>,
+	// lowest address hash not generated on disk yet or `None` if generation is complete.
+	generating: Option<H256>,
+	diffs: HashMap<H256, DiffLayer>,
+}
+
+impl Layers {
+	// whether the account with given address hash and its storage are generated on disk.
+	fn is_generated(&self, addr_hash: &H256) -> bool {
+		self.generating.map_or(true, |next| *addr_hash < next)
+	}
+
+	// diff layers from `root` down to the disk layer, newest first.
+	// `None` if `root` does not build on the disk layer.
+	fn path(&self, root: &H256) -> Option<Vec<H256>> {
+		let mut path = Vec::new();
+		let mut current = *root;
+		loop {
+			if self.disk_root == Some(current) {
+				return Some(path);
+			}
+			match self.diffs.get(&current) {
+				Some(layer) => {
+					path.push(current);
+					current = layer.parent;
+				}
+				None => return None,
+			}
+		}
+	}
+}
+
+/// Flat state: a disk layer with diff layers of recent blocks on top.
+pub struct FlatState {
+	db: Arc<KeyValueDB>,
+	layers: RwLock<Layers>,
+	// number of live views; the disk layer is not modified while there are any.
+	views: AtomicUsize,
+}
+
+impl FlatState {
+	/// Open the flat state stored in the given database.
+	pub fn open(db: Arc<KeyValueDB>) -> Self {
+		let disk_root = db.get(COL_FLAT_STATE, ROOT_KEY).expect("Low level database error. Some issue with disk?")
+			.map(|root| H256::from_slice(&root));
+		let generating = db.get(COL_FLAT_STATE, GENERATOR_KEY).expect("Low level database error. Some issue with disk?")
+			.map(|next| H256::from_slice(&next));
+
+		FlatState {
+			db: db,
+			layers: RwLock::new(Layers {
+				disk_root: disk_root,
+				generating: generating,
+				diffs: HashMap::new(),
+			}),
+			views: AtomicUsize::new(0),
+		}
+	}
+
+	/// Root of the state stored on disk.
+	pub fn disk_root(&self) -> Option<H256> {
+		self.layers.read().disk_root
+	}
+
+	/// Number of diff layers kept in memory.
+	pub fn diff_layers(&self) -> usize {
+		self.layers.read().diffs.len()
+	}
+
+	/// Whether the disk layer is being generated.
+	pub fn is_generating(&self) -> bool {
+		self.layers.read().generating.is_some()
+	}
+
+	/// Start generating the disk layer for the state with given root. Entries left from
+	/// a previous disk layer are removed as generation passes over them.
+	pub fn start_generation(&self, root: &H256) {
+		let mut layers = self.layers.write();
+
+		let mut batch = DBTransaction::new();
+		batch.put(COL_FLAT_STATE, ROOT_KEY, &**root);
+		batch.put(COL_FLAT_STATE, GENERATOR_KEY, &*H256::zero());
+		self.db.write(batch).expect("Low level database error. Some issue with disk?");
+
+		layers.disk_root = Some(*root);
+		layers.generating = Some(H256::zero());
+		layers.diffs.clear();
+	}
+
+	/// Generate accounts of the disk layer from the trie of the disk root, stopping after
+	/// the account at which at least `max` entries were written. Returns `true` once
+	/// generation is complete.
+	pub fn generate_step(&self, db: &HashDB, max: usize) -> Result<bool, Box<TrieError>> {
+		let mut layers = self.layers.write();
+		let (root, from) = match (layers.disk_root, layers.generating) {
+			(Some(root), Some(from)) => (root, from),
+			_ => return Ok(true),
+		};
+
+		let mut batch = DBTransaction::new();
+		let mut count = 0;
+		let mut last = None;
+		{
+			let account_trie = TrieDB::new(db, &root)?;
+			let mut iter = account_trie.iter()?;
+			iter.seek(&*from)?;
+			for item in iter {
+				let (key, value) = item?;
+				let addr_hash = H256::from_slice(&key);
+				batch.put(COL_FLAT_STATE, &account_key(&addr_hash), &value);
+				count += 1;
+
+				let account: BasicAccount = rlp::decode(&*value);
+				if account.storage_root != SHA3_NULL_RLP {
+					let account_db = AccountDB::from_hash(db, addr_hash);
+					let storage_trie = TrieDB::new(&account_db, &account.storage_root)?;
+					for item in storage_trie.iter()? {
+						let (key, value) = item?;
+						batch.put(COL_FLAT_STATE, &storage_key(&addr_hash, &H256::from_slice(&key)), &value);
+						count += 1;
+					}
+				}
+
+				last = Some(addr_hash);
+				if count >= max {
+					break;
+				}
+			}
+		}
+
+		// `None` once the trie is exhausted.
+		let next = match last {
+			Some(ref last) if count >= max => next_hash(last),
+			_ => None,
+		};
+
+		// remove stale entries of the generated range before writing it.
+		let mut clear = DBTransaction::new();
+		for prefix in &[ACCOUNT_PREFIX, STORAGE_PREFIX] {
+			let mut start = prefix.to_vec();
+			start.extend_from_slice(&*from);
+			for (key, _) in self.db.iter_from(COL_FLAT_STATE, &start) {
+				if !key.starts_with(prefix) || next.map_or(false, |next| &key[1..33] >= &next[..]) {
+					break;
+				}
+				clear.delete(COL_FLAT_STATE, &key);
+			}
+		}
+		self.db.write(clear).expect("Low level database error. Some issue with disk?");
+
+		match next {
+			Some(ref next) => batch.put(COL_FLAT_STATE, GENERATOR_KEY, &**next),
+			None => batch.delete(COL_FLAT_STATE, GENERATOR_KEY),
+		}
+		self.db.write(batch).expect("Low level database error. Some issue with disk?");
+		layers.generating = next;
+
+		trace!(target: "flat_state", "Generated {} entries of {} from {}", count, root, from);
+		Ok(next.is_none())
+	}
+
+	/// Add changes producing the state `root` on top of the state `parent`.
+	/// Ignored if the parent state is not known.
+	pub fn add_layer(&self, parent: H256, root: H256, diff: FlatDiff) {
+		if parent == root {
+			return;
+		}
+
+		let mut layers = self.layers.write();
+		if layers.disk_root == Some(root) || layers.diffs.contains_key(&root) {
+			return;
+		}
+		if layers.disk_root != Some(parent) && !layers.diffs.contains_key(&parent) {
+			trace!(target: "flat_state", "Unknown parent state {} of {}", parent, root);
+			return;
+		}
+
+		layers.diffs.insert(root, DiffLayer {
+			parent: parent,
+			diff: diff,
+		});
+	}
+
+	/// Look up an account by its address hash in the state with given root.
+	/// Returns `None` if the state is not available.
+	pub fn account(&self, root: &H256, addr_hash: &H256) -> Option<Option<Bytes>> {
+		self.lookup(root, addr_hash, |diff| diff.account(addr_hash), || account_key(addr_hash).to_vec())
+	}
+
+	/// Look up a storage value by address hash and storage key hash in the state with given root.
+	/// Returns `None` if the state is not available.
+	pub fn storage(&self, root: &H256, addr_hash: &H256, key_hash: &H256) -> Option<Option<Bytes>> {
+		self.lookup(root, addr_hash, |diff| diff.storage(addr_hash, key_hash), || storage_key(addr_hash, key_hash).to_vec())
+	}
+
+	fn lookup<F, K>(&self, root: &H256, addr_hash: &H256, in_diff: F, disk_key: K) -> Option<Option<Bytes>>
+		where F: Fn(&FlatDiff) -> Option<Option<Bytes>>, K: Fn() -> Vec<u8>
+	{
+		// the lock is held while reading the disk so that it is not modified underneath.
+		let layers = self.layers.read();
+		let mut current = *root;
+		loop {
+			if layers.disk_root == Some(current) {
+				if !layers.is_generated(addr_hash) {
+					return None;
+				}
+				let value = self.db.get(COL_FLAT_STATE, &disk_key()).expect("Low level database error. Some issue with disk?");
+				return Some(value.map(|value| value.to_vec()));
+			}
+			let layer = match layers.diffs.get(&current) {
+				Some(layer) => layer,
+				None => return None,
+			};
+			if let Some(value) = in_diff(&layer.diff) {
+				return Some(value);
+			}
+			current = layer.parent;
+		}
+	}
+
+	/// Write all diff layers from the disk layer up to the state with given root to disk
+	/// and drop diff layers which do not build on it. Postponed while there are live views.
+	pub fn flatten(&self, root: &H256) {
+		if self.views.load(Ordering::SeqCst) > 0 {
+			return;
+		}
+
+		let mut layers = self.layers.write();
+		let path = match layers.path(root) {
+			Some(ref path) if path.is_empty() => return,
+			Some(path) => path,
+			None => return,
+		};
+
+		// the disk layer is inconsistent until the last write.
+		let mut batch = DBTransaction::new();
+		batch.delete(COL_FLAT_STATE, ROOT_KEY);
+		// accounts changed by the layers applied so far.
+		let mut written: HashMap<H256, Option<Bytes>> = HashMap::new();
+		for hash in path.iter().rev() {
+			let layer = layers.diffs.remove(hash).expect("path consists of known layers; qed");
+
+			// accounts not generated yet are read from the trie of the new root later.
+			for addr_hash in layer.diff.wiped.iter().filter(|addr_hash| layers.is_generated(addr_hash)) {
+				// only accounts which had any storage need to be cleared.
+				let previous = match written.get(addr_hash) {
+					Some(account) => account.clone(),
+					None => self.db.get(COL_FLAT_STATE, &account_key(addr_hash))
+						.expect("Low level database error. Some issue with disk?")
+						.map(|account| account.to_vec()),
+				};
+				let had_storage = previous.map_or(false, |account| rlp::decode::<BasicAccount>(&account).storage_root != SHA3_NULL_RLP);
+				if !had_storage {
+					continue;
+				}
+
+				// iteration only sees flushed data; write what was applied so far.
+				self.db.write(batch).expect("Low level database error. Some issue with disk?");
+				batch = DBTransaction::new();
+				let prefix = storage_prefix(addr_hash);
+				for (key, _) in self.db.iter_from_prefix(COL_FLAT_STATE, &prefix) {
+					batch.delete(COL_FLAT_STATE, &key);
+				}
+			}
+
+			for (addr_hash, storage) in layer.diff.storage.into_iter().filter(|&(ref addr_hash, _)| layers.is_generated(addr_hash)) {
+				for (key_hash, value) in storage {
+					let key = storage_key(&addr_hash, &key_hash);
+					match value {
+						Some(value) => batch.put(COL_FLAT_STATE, &key, &value),
+						None => batch.delete(COL_FLAT_STATE, &key),
+					}
+				}
+			}
+
+			for (addr_hash, account) in layer.diff.accounts.into_iter().filter(|&(ref addr_hash, _)| layers.is_generated(addr_hash)) {
+				match account {
+					Some(ref account) => batch.put(COL_FLAT_STATE, &account_key(&addr_hash), account),
+					None => batch.delete(COL_FLAT_STATE, &account_key(&addr_hash)),
+				}
+				written.insert(addr_hash, account);
+			}
+		}
+
+		batch.put(COL_FLAT_STATE, ROOT_KEY, &**root);
+		self.db.write(batch).expect("Low level database error. Some issue with disk?");
+		layers.disk_root = Some(*root);
+
+		// drop layers of blocks which are no longer reachable.
+		let stale: Vec<H256> = layers.diffs.keys().filter(|hash| layers.path(hash).is_none()).cloned().collect();
+		for hash in stale {
+			layers.diffs.remove(&hash);
+		}
+		trace!(target: "flat_state", "Flattened {} layers up to {}, {} layers left", path.len(), root, layers.diffs.len());
+	}
+
+	/// Get a consistent view of the state with given root which can be iterated in
+	/// key order. The disk layer is not modified while the view is alive.
+	/// Returns `None` while the disk layer is being generated.
+	pub fn view(&self, root: &H256) -> Option<FlatStateView> {
+		let layers = self.layers.read();
+		if layers.generating.is_some() {
+			return None;
+		}
+		let path = match layers.path(root) {
+			Some(path) => path,
+			None => return None,
+		};
+		self.views.fetch_add(1, Ordering::SeqCst);
+
+		let mut accounts = BTreeMap::new();
+		let mut storage: HashMap<H256, BTreeMap<H256, Option<Bytes>>> = HashMap::new();
+		let mut wiped = HashSet::new();
+		for hash in &path {
+			let diff = &layers.diffs[hash].diff;
+			for (addr_hash, account) in &diff.accounts {
+				accounts.entry(*addr_hash).or_insert_with(|| account.clone());
+			}
+			for (addr_hash, values) in diff.storage.iter().filter(|&(addr_hash, _)| !wiped.contains(addr_hash)) {
+				let merged = storage.entry(*addr_hash).or_insert_with(BTreeMap::new);
+				for (key_hash, value) in values {
+					merged.entry(*key_hash).or_insert_with(|| value.clone());
+				}
+			}
+			wiped.extend(diff.wiped.iter().cloned());
+		}
+
+		Some(FlatStateView {
+			flat: self,
+			accounts: accounts,
+			storage: storage,
+			wiped: wiped,
+		})
+	}
+}
+
+/// Consistent view of a state kept in the flat state.
+pub struct FlatStateView<'a> {
+	flat: &'a FlatState,
+	accounts: BTreeMap<H256, Option<Bytes>>,
+	storage: HashMap<H256, BTreeMap<H256, Option<Bytes>>>,
+	wiped: HashSet<H256>,
+}
+
+impl<'a> FlatStateView<'a> {
+	/// Iterate over address hashes and account RLPs ordered by address hash.
+	pub fn accounts<'b>(&'b self) -> Box<Iterator<Item=(H256, Bytes)> + 'b> {
+		let disk = self.flat.db.iter_from_prefix(COL_FLAT_STATE, ACCOUNT_PREFIX)
+			.map(|(key, value)| (H256::from_slice(&key[1..]), value.into_vec()));
+		let overlay = self.accounts.iter().map(|(key, value)| (*key, value.clone()));
+
+		Box::new(Merge { disk: disk.peekable(), overlay: overlay.peekable() })
+	}
+
+	/// Iterate over storage key hashes and value RLPs of an account ordered by key hash.
+	pub fn storage<'b>(&'b self, addr_hash: &H256) -> Box<Iterator<Item=(H256, Bytes)> + 'b> {
+		let overlay = self.storage.get(addr_hash).into_iter()
+			.flat_map(|values| values.iter())
+			.map(|(key, value)| (*key, value.clone()));
+
+		if self.wiped.contains(addr_hash) {
+			return Box::new(overlay.filter_map(|(key, value)| value.map(|value| (key, value))));
+		}
+
+		let prefix = storage_prefix(addr_hash);
+		let disk: Vec<_> = self.flat.db.iter_from_prefix(COL_FLAT_STATE, &prefix)
+			.map(|(key, value)| (H256::from_slice(&key[33..]), value.into_vec()))
+			.collect();
+
+		Box::new(Merge { disk: disk.into_iter().peekable(), overlay: overlay.peekable() })
+	}
+}
+
+impl<'a> Drop for FlatStateView<'a> {
+	fn drop(&mut self) {
+		self.flat.views.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+// Merges two iterators ordered by key. Entries of `overlay` replace entries of `disk`
+// and are skipped if they have no value.
+struct Merge<D: Iterator<Item=(H256, Bytes)>, O: Iterator<Item=(H256, Option<Bytes>)>> {
+	disk: Peekable<D>,
+	overlay: Peekable<O>,
+}
+
+impl<D, O> Iterator for Merge<D, O> where D: Iterator<Item=(H256, Bytes)>, O: Iterator<Item=(H256, Option<Bytes>)> {
+	type Item = (H256, Bytes);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let disk_key = self.disk.peek().map(|&(key, _)| key);
+			let overlay_key = self.overlay.peek().map(|&(key, _)| key);
+			let take_overlay = match (disk_key, overlay_key) {
+				(None, None) => return None,
+				(Some(_), None) => false,
+				(None, Some(_)) => true,
+				(Some(disk_key), Some(overlay_key)) => {
+					if disk_key == overlay_key {
+						self.disk.next();
+					}
+					overlay_key <= disk_key
+				}
+			};
+
+			if !take_overlay {
+				return self.disk.next();
+			}
+			match self.overlay.next() {
+				Some((key, Some(value))) => return Some((key, value)),
+				_ => continue,
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use basic_account::BasicAccount;
+	use rlp;
+	use util::{Bytes, H256, KeyValueDB, DBTransaction};
+	use util::kvdb::in_memory;
+	use util::memorydb::MemoryDB;
+	use util::sha3::{SHA3_EMPTY, SHA3_NULL_RLP};
+	use util::trie::{TrieDBMut, TrieMut};
+	use db::COL_FLAT_STATE;
+	use super::{FlatState, FlatDiff, account_key};
+
+	fn new_db() -> Arc<KeyValueDB> {
+		Arc::new(in_memory(::db::NUM_COLUMNS.unwrap_or(0)))
+	}
+
+	fn account(nonce: u64, storage_root: H256) -> Bytes {
+		rlp::encode(&BasicAccount {
+			nonce: nonce.into(),
+			balance: 0.into(),
+			storage_root: storage_root,
+			code_hash: SHA3_EMPTY,
+		}).to_vec()
+	}
+
+	#[test]
+	fn looks_up_through_diff_layers() {
+		let db = new_db();
+		let flat = FlatState::open(db);
+		let (r0, r1, r2, r2b) = (H256::from(1), H256::from(2), H256::from(3), H256::from(4));
+		let (a, b) = (H256::from(10), H256::from(11));
+		let key = H256::from(20);
+
+		{
+			let mut layers = flat.layers.write();
+			layers.disk_root = Some(r0);
+		}
+		assert_eq!(flat.account(&r0, &a), Some(None));
+		assert_eq!(flat.account(&r1, &a), None);
+
+		let mut diff = FlatDiff::default();
+		diff.note_account(a, Some(account(1, H256::from(100))));
+		diff.note_storage(a, key, Some(vec![2]));
+		flat.add_layer(r0, r1, diff);
+
+		let mut diff = FlatDiff::default();
+		diff.note_account(b, Some(account(3, SHA3_NULL_RLP)));
+		diff.note_wiped(a);
+		flat.add_layer(r1, r2, diff);
+
+		let mut diff = FlatDiff::default();
+		diff.note_account(a, None);
+		flat.add_layer(r1, r2b, diff);
+
+		assert_eq!(flat.account(&r2, &a), Some(Some(account(1, H256::from(100)))));
+		assert_eq!(flat.account(&r2, &b), Some(Some(account(3, SHA3_NULL_RLP))));
+		assert_eq!(flat.account(&r2b, &a), Some(None));
+		assert_eq!(flat.storage(&r1, &a, &key), Some(Some(vec![2])));
+		assert_eq!(flat.storage(&r2, &a, &key), Some(None));
+
+		flat.flatten(&r1);
+		assert_eq!(flat.disk_root(), Some(r1));
+		assert_eq!(flat.diff_layers(), 2);
+		assert_eq!(flat.account(&r1, &a), Some(Some(account(1, H256::from(100)))));
+		assert_eq!(flat.storage(&r1, &a, &key), Some(Some(vec![2])));
+
+		flat.flatten(&r2);
+		assert_eq!(flat.disk_root(), Some(r2));
+		assert_eq!(flat.diff_layers(), 0);
+		assert_eq!(flat.account(&r2b, &a), None);
+		assert_eq!(flat.account(&r2, &b), Some(Some(account(3, SHA3_NULL_RLP))));
+		assert_eq!(flat.storage(&r2, &a, &key), Some(None));
+	}
+
+	#[test]
+	fn view_merges_layers_in_key_order() {
+		let db = new_db();
+		let flat = FlatState::open(db);
+		let (r0, r1, r2) = (H256::from(1), H256::from(2), H256::from(3));
+
+		{
+			let mut layers = flat.layers.write();
+			layers.disk_root = Some(r0);
+		}
+		let mut diff = FlatDiff::default();
+		diff.note_account(H256::from(10), Some(account(1, H256::from(100))));
+		diff.note_account(H256::from(30), Some(account(3, SHA3_NULL_RLP)));
+		flat.add_layer(r0, r1, diff);
+		flat.flatten(&r1);
+
+		let mut diff = FlatDiff::default();
+		diff.note_account(H256::from(20), Some(account(2, SHA3_NULL_RLP)));
+		diff.note_account(H256::from(30), None);
+		diff.note_account(H256::from(10), Some(account(4, SHA3_NULL_RLP)));
+		flat.add_layer(r1, r2, diff);
+
+		{
+			let view = flat.view(&r2).unwrap();
+			let accounts: Vec<_> = view.accounts().collect();
+			assert_eq!(accounts, vec![(H256::from(10), account(4, SHA3_NULL_RLP)), (H256::from(20), account(2, SHA3_NULL_RLP))]);
+
+			// the disk layer is left alone while the view is alive.
+			flat.flatten(&r2);
+			assert_eq!(flat.disk_root(), Some(r1));
+		}
+
+		flat.flatten(&r2);
+		assert_eq!(flat.disk_root(), Some(r2));
+	}
+
+	#[test]
+	fn generates_in_resumable_steps() {
+		let mut memdb = MemoryDB::new();
+		let mut root = H256::new();
+		{
+			let mut trie = TrieDBMut::new(&mut memdb, &mut root);
+			for i in 1..6 {
+				trie.insert(&*H256::from(i), &account(i, SHA3_NULL_RLP)).unwrap();
+			}
+		}
+
+		// an entry left from a previous disk layer.
+		let db = new_db();
+		let mut batch = DBTransaction::new();
+		batch.put(COL_FLAT_STATE, &account_key(&H256::from(9)), &account(9, SHA3_NULL_RLP));
+		db.write(batch).unwrap();
+
+		let flat = FlatState::open(db.clone());
+		flat.start_generation(&root);
+		assert!(flat.is_generating());
+		assert!(!flat.generate_step(&memdb, 2).unwrap());
+		assert_eq!(flat.account(&root, &H256::from(2)), Some(Some(account(2, SHA3_NULL_RLP))));
+		assert_eq!(flat.account(&root, &H256::from(3)), None);
+		assert!(flat.view(&root).is_none());
+
+		// generation resumes after reopening.
+		let flat = FlatState::open(db);
+		assert!(flat.is_generating());
+		assert!(!flat.generate_step(&memdb, 2).unwrap());
+		assert!(flat.generate_step(&memdb, 2).unwrap());
+		assert!(!flat.is_generating());
+		for i in 1..6 {
+			assert_eq!(flat.account(&root, &H256::from(i)), Some(Some(account(i, SHA3_NULL_RLP))));
+		}
+		assert_eq!(flat.account(&root, &H256::from(9)), Some(None));
+		assert!(flat.view(&root).is_some());
+	}
+}
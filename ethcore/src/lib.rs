@@ -102,6 +102,7 @@ extern crate ethcore_bloom_journal as bloom_journal;
 extern crate byteorder;
 extern crate transient_hashmap;
 extern crate linked_hash_map;
+extern crate serde_json;
 
 #[macro_use]
 extern crate log;
@@ -148,6 +149,7 @@ mod env_info;
 mod pod_account;
 mod state;
 mod state_db;
+pub mod flat_state;
 mod account_db;
 mod builtin;
 mod executive;
@@ -0,0 +1,63 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! New columns upgrade
+
+use std::sync::Arc;
+use util::migration::{Error, Migration, Progress, Batch, Config};
+use util::Database;
+
+/// Adds empty columns to the database. The migration manager creates them in place;
+/// `migrate` only copies existing columns unchanged.
+pub struct ColumnsAdded {
+	version: u32,
+	pre_columns: u32,
+	columns: u32,
+	progress: Progress,
+}
+
+impl ColumnsAdded {
+	/// New migration to `version` extending the database from `pre_columns` to `columns` columns.
+	pub fn new(version: u32, pre_columns: u32, columns: u32) -> ColumnsAdded {
+		ColumnsAdded {
+			version: version,
+			pre_columns: pre_columns,
+			columns: columns,
+			progress: Progress::default(),
+		}
+	}
+}
+
+impl Migration for ColumnsAdded {
+	fn version(&self) -> u32 {
+		self.version
+	}
+
+	fn pre_columns(&self) -> Option<u32> { Some(self.pre_columns) }
+
+	fn columns(&self) -> Option<u32> { Some(self.columns) }
+
+	fn alters_existing(&self) -> bool { false }
+
+	fn migrate(&mut self, source: Arc<Database>, config: &Config, dest: &mut Database, col: Option<u32>) -> Result<(), Error> {
+		let mut batch = Batch::new(config, col);
+		for (key, value) in source.iter(col) {
+			self.progress.tick();
+			batch.insert(key.to_vec(), value.to_vec(), dest)?;
+		}
+		batch.commit(dest)
+	}
+}
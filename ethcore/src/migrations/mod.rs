@@ -26,3 +26,9 @@ pub use self::v9::Extract;
 
 mod v10;
 pub use self::v10::ToV10;
+
+mod v11;
+pub use self::v11::ToV11;
+
+mod columns;
+pub use self::columns::ColumnsAdded;
@@ -0,0 +1,153 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Ancient block freezer upgrade
+
+use std::sync::Arc;
+use blockchain::{Freezer, FreezerConfig, FROZEN_KEY};
+use blockchain::extras::{BlockDetails, BlockReceipts, ExtrasIndex};
+use db::{Key, COL_EXTRA, COL_HEADERS, COL_BODIES};
+use header::BlockNumber;
+use rlp::{self, UntrustedRlp, RlpType, Compressible};
+use util::migration::{Error, Migration, Progress, Batch, Config};
+use util::{H256, FixedHash, Database};
+
+/// Moves canonical blocks which are at least `depth` blocks behind the best block
+/// to the freezer. Copies the database unchanged if the freezer is not configured.
+pub struct ToV11 {
+	freezer: Option<FreezerConfig>,
+	frozen: BlockNumber,
+	progress: Progress,
+}
+
+impl ToV11 {
+	/// New v11 migration. Blocks are frozen only if `freezer` has depth set.
+	pub fn new(freezer: Option<FreezerConfig>) -> ToV11 {
+		ToV11 {
+			freezer: freezer.and_then(|config| config.depth.map(|_| config)),
+			frozen: 0,
+			progress: Progress::default(),
+		}
+	}
+
+	fn block_number(source: &Database, hash: &H256) -> Result<Option<BlockNumber>, Error> {
+		Ok(source.get(COL_EXTRA, &<H256 as Key<BlockDetails>>::key(hash))?
+			.map(|details| rlp::decode::<BlockDetails>(&details).number))
+	}
+
+	fn block_hash(source: &Database, number: BlockNumber) -> Result<Option<H256>, Error> {
+		Ok(source.get(COL_EXTRA, &<BlockNumber as Key<H256>>::key(&number))?.map(|hash| rlp::decode(&hash)))
+	}
+
+	// Returns true if the block with given hash was moved to the freezer.
+	fn is_frozen(&self, source: &Database, hash: &H256) -> Result<bool, Error> {
+		match Self::block_number(source, hash)? {
+			Some(number) if number < self.frozen => Ok(Self::block_hash(source, number)?.as_ref() == Some(hash)),
+			_ => Ok(false),
+		}
+	}
+
+	fn freeze(&mut self, source: &Database, config: &FreezerConfig) -> Result<(), Error> {
+		let depth = config.depth.expect("Freezer without depth is never stored; qed");
+		let best_number = match source.get(COL_EXTRA, b"best")? {
+			Some(best) => Self::block_number(source, &H256::from_slice(&best))?.unwrap_or(0),
+			None => return Ok(()),
+		};
+
+		let end = match best_number.checked_sub(depth) {
+			Some(last) => last + 1,
+			None => return Ok(()),
+		};
+
+		let freezer = Freezer::open(&config.path)?;
+		// anything frozen before the database was upgraded is stale.
+		freezer.truncate(0)?;
+
+		trace!(target: "migration", "Moving blocks #0..#{} to the freezer", end);
+		while self.frozen < end {
+			let hash = match Self::block_hash(source, self.frozen)? {
+				Some(hash) => hash,
+				None => break,
+			};
+
+			let header = source.get(COL_HEADERS, &hash)?;
+			let body = source.get(COL_BODIES, &hash)?;
+			let receipts = match source.get(COL_EXTRA, &<H256 as Key<BlockReceipts>>::key(&hash))? {
+				Some(receipts) => Some(receipts.to_vec()),
+				// genesis is inserted without receipts.
+				None if self.frozen == 0 => Some(rlp::encode(&BlockReceipts::new(Vec::new())).to_vec()),
+				None => None,
+			};
+			match (header, body, receipts) {
+				(Some(header), Some(body), Some(receipts)) => freezer.append(
+					self.frozen,
+					&UntrustedRlp::new(&header).decompress(RlpType::Blocks),
+					&UntrustedRlp::new(&body).decompress(RlpType::Blocks),
+					&receipts,
+				)?,
+				_ => break,
+			}
+
+			self.progress.tick();
+			self.frozen += 1;
+		}
+
+		freezer.flush()?;
+		trace!(target: "migration", "Moved {} blocks to the freezer", self.frozen);
+		Ok(())
+	}
+}
+
+impl Migration for ToV11 {
+	fn version(&self) -> u32 {
+		11
+	}
+
+	fn columns(&self) -> Option<u32> { Some(6) }
+
+	fn migrate(&mut self, source: Arc<Database>, config: &Config, dest: &mut Database, col: Option<u32>) -> Result<(), Error> {
+		if col == COL_HEADERS {
+			if let Some(freezer_config) = self.freezer.clone() {
+				self.freeze(&source, &freezer_config)?;
+			}
+		}
+
+		let mut batch = Batch::new(config, col);
+		for (key, value) in source.iter(col) {
+			self.progress.tick();
+
+			let frozen_hash = match col {
+				COL_HEADERS | COL_BODIES => Some(H256::from_slice(&key)),
+				COL_EXTRA if key.len() == 33 && key[0] == ExtrasIndex::BlockReceipts as u8 => Some(H256::from_slice(&key[1..])),
+				_ => None,
+			};
+
+			if let Some(hash) = frozen_hash {
+				if self.frozen > 0 && self.is_frozen(&source, &hash)? {
+					continue;
+				}
+			}
+
+			batch.insert(key.to_vec(), value.to_vec(), dest)?;
+		}
+
+		if col == COL_EXTRA && self.frozen > 0 {
+			batch.insert(FROZEN_KEY.to_vec(), rlp::encode(&self.frozen).to_vec(), dest)?;
+		}
+
+		batch.commit(dest)
+	}
+}
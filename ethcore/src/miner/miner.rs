@@ -21,7 +21,7 @@ use util::using_queue::{UsingQueue, GetAction};
 use account_provider::{AccountProvider, Error as AccountError};
 use state::{State, CleanupMode};
 use client::{MiningBlockChainClient, Executive, Executed, EnvInfo, TransactOptions, BlockId, CallAnalytics, TransactionId};
-use client::TransactionImportResult;
+use client::{TransactionImportResult, FeeHistory};
 use executive::contract_address;
 use block::{ClosedBlock, IsBlock, Block};
 use error::*;
@@ -126,6 +126,44 @@ impl Default for MinerOptions {
 	}
 }
 
+/// Options for the gas price oracle variant of a `GasPricer`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GasPriceOracleOptions {
+	/// Number of recent blocks to sample. More recent blocks have bigger weight.
+	pub blocks: u64,
+	/// Percentile of gas prices (weighted by gas used) to take from every block.
+	pub percentile: f64,
+	/// Number of pending transactions in the local queue after which the suggested
+	/// price is raised to outbid the transaction at that position (0 = disabled).
+	pub backlog_threshold: usize,
+}
+
+impl Default for GasPriceOracleOptions {
+	fn default() -> Self {
+		GasPriceOracleOptions {
+			blocks: 20,
+			percentile: 60.0,
+			backlog_threshold: 200,
+		}
+	}
+}
+
+/// Average of block gas prices weighted linearly by recency. Blocks without transactions are skipped.
+fn recency_weighted_price(history: &FeeHistory) -> Option<U256> {
+	let (total, weights) = history.gas_price.iter()
+		.enumerate()
+		.filter_map(|(i, prices)| prices.first().and_then(|price| match price.is_zero() {
+			true => None,
+			false => Some((*price, U256::from(i + 1))),
+		}))
+		.fold((U256::zero(), U256::zero()), |(total, weights), (price, weight)| (total + price * weight, weights + weight));
+
+	match weights.is_zero() {
+		true => None,
+		false => Some(total / weights),
+	}
+}
+
 /// Options for the dynamic gas price recalibrator.
 #[derive(Debug, PartialEq)]
 pub struct GasPriceCalibratorOptions {
@@ -171,6 +209,9 @@ pub enum GasPricer {
 	Fixed(U256),
 	/// Gas price is calibrated according to a fixed amount of USD.
 	Calibrated(GasPriceCalibrator),
+	/// Minimal gas price is set by the inner pricer, while the gas price suggested to users
+	/// (e.g. `eth_gasPrice`) is based on recent blocks and the local queue backlog.
+	Oracle(Box<GasPricer>, GasPriceOracleOptions),
 }
 
 impl GasPricer {
@@ -187,10 +228,23 @@ impl GasPricer {
 		GasPricer::Fixed(gas_price)
 	}
 
+	/// Create a new Oracle `GasPricer` on top of given pricer.
+	pub fn new_oracle(minimal: GasPricer, options: GasPriceOracleOptions) -> GasPricer {
+		GasPricer::Oracle(Box::new(minimal), options)
+	}
+
 	fn recalibrate<F: Fn(U256) + Sync + Send + 'static>(&mut self, set_price: F) {
 		match *self {
 			GasPricer::Fixed(ref max) => set_price(max.clone()),
 			GasPricer::Calibrated(ref mut cal) => cal.recalibrate(set_price),
+			GasPricer::Oracle(ref mut minimal, _) => minimal.recalibrate(set_price),
+		}
+	}
+
+	fn oracle_options(&self) -> Option<GasPriceOracleOptions> {
+		match *self {
+			GasPricer::Oracle(_, ref options) => Some(options.clone()),
+			_ => None,
 		}
 	}
 }
@@ -800,6 +854,27 @@ impl MinerService for Miner {
 		*self.transaction_queue.lock().minimal_gas_price() * 110.into() / 100.into()
 	}
 
+	fn suggested_gas_price(&self, chain: &MiningBlockChainClient) -> U256 {
+		let options = self.gas_pricer.lock().oracle_options();
+		let options = match options {
+			Some(options) => options,
+			None => return chain.gas_price_median(100).unwrap_or_else(|| self.sensible_gas_price()),
+		};
+		let recent = chain.fee_history(options.blocks, BlockId::Latest, &[options.percentile])
+			.and_then(|history| recency_weighted_price(&history));
+		let backlog = match options.backlog_threshold {
+			0 => None,
+			threshold => self.transaction_queue.lock().gas_price_at(threshold - 1),
+		};
+
+		let price = match (recent, backlog) {
+			(Some(recent), Some(backlog)) => cmp::max(recent, backlog),
+			(Some(price), None) | (None, Some(price)) => price,
+			(None, None) => return self.sensible_gas_price(),
+		};
+		cmp::max(price, self.minimal_gas_price())
+	}
+
 	fn sensible_gas_limit(&self) -> U256 {
 		self.gas_range_target.read().0 / 5.into()
 	}
@@ -1168,7 +1243,7 @@ mod tests {
 	use block::IsBlock;
 	use util::{U256, Uint, FromHex};
 	use ethkey::{Generator, Random};
-	use client::{BlockChainClient, TestBlockChainClient, EachBlockWith, TransactionImportResult};
+	use client::{BlockChainClient, TestBlockChainClient, EachBlockWith, TransactionImportResult, FeeHistory};
 	use header::BlockNumber;
 	use types::transaction::{SignedTransaction, Transaction, PendingTransaction, Action};
 	use spec::Spec;
@@ -1223,7 +1298,7 @@ mod tests {
 				enable_resubmission: true,
 				tx_queue_banning: Banning::Disabled,
 			},
-			GasPricer::new_fixed(0u64.into()),
+			GasPricer::new_oracle(GasPricer::new_fixed(0u64.into()), GasPriceOracleOptions::default()),
 			&Spec::new_test(),
 			None, // accounts provider
 		)).ok().expect("Miner was just created.")
@@ -1333,4 +1408,27 @@ mod tests {
 		assert!(miner.pending_block().is_none());
 		assert_eq!(client.chain_info().best_block_number, 4 as BlockNumber);
 	}
+
+	#[test]
+	fn should_weight_recent_blocks_gas_prices() {
+		let history = |prices: Vec<u64>| FeeHistory {
+			oldest_block: 0,
+			gas_used_ratio: vec![0.5; prices.len()],
+			gas_price: prices.into_iter().map(|p| vec![p.into()]).collect(),
+		};
+
+		// empty block is skipped, newest block has weight 3.
+		assert_eq!(recency_weighted_price(&history(vec![10, 0, 40])), Some(32.into()));
+		assert_eq!(recency_weighted_price(&history(vec![0, 0])), None);
+	}
+
+	#[test]
+	fn should_suggest_gas_price_from_recent_blocks() {
+		let client = TestBlockChainClient::default();
+		let miner = miner();
+		assert_eq!(miner.suggested_gas_price(&client), miner.sensible_gas_price());
+
+		client.add_blocks(3, EachBlockWith::Transaction);
+		assert_eq!(miner.suggested_gas_price(&client), 200_000_000_000u64.into());
+	}
 }
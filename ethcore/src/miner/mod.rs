@@ -50,7 +50,7 @@ mod transaction_queue;
 mod work_notify;
 
 pub use self::external::{ExternalMiner, ExternalMinerService};
-pub use self::miner::{Miner, MinerOptions, Banning, PendingSet, GasPricer, GasPriceCalibratorOptions, GasLimit};
+pub use self::miner::{Miner, MinerOptions, Banning, PendingSet, GasPricer, GasPriceCalibratorOptions, GasPriceOracleOptions, GasLimit};
 pub use self::transaction_queue::{TransactionQueue, PrioritizationStrategy, AccountDetails, TransactionOrigin};
 pub use self::local_transactions::{Status as LocalTransactionStatus};
 pub use client::TransactionImportResult;
@@ -171,6 +171,11 @@ pub trait MinerService : Send + Sync {
 	/// Suggested gas price.
 	fn sensible_gas_price(&self) -> U256;
 
+	/// Gas price suggested to users, based on recent blocks and pending transactions.
+	fn suggested_gas_price(&self, chain: &MiningBlockChainClient) -> U256 {
+		chain.gas_price_median(100).unwrap_or_else(|| self.sensible_gas_price())
+	}
+
 	/// Suggested gas limit.
 	fn sensible_gas_limit(&self) -> U256 { 21000.into() }
 
@@ -1040,6 +1040,12 @@ impl TransactionQueue {
 		}
 	}
 
+	/// Returns gas price of the current transaction at given position in priority order.
+	/// Unlike `top_transactions` it only walks the queue up to that position.
+	pub fn gas_price_at(&self, position: usize) -> Option<U256> {
+		self.current.by_priority.iter().nth(position).map(|t| t.gas_price)
+	}
+
 	/// Returns top transactions from the queue ordered by priority.
 	pub fn top_transactions_at(&self, best_block: BlockNumber) -> Vec<SignedTransaction> {
 		let mut r = Vec::new();
@@ -2036,6 +2042,22 @@ mod test {
 		assert_eq!(top.len(), 2);
 	}
 
+	#[test]
+	fn should_return_gas_price_at_position() {
+		// given
+		let mut txq = TransactionQueue::default();
+		let (tx, tx2) = new_tx_pair_default(1.into(), 5.into());
+
+		// when
+		txq.add(tx.clone(), TransactionOrigin::External, 0, None, &default_account_details, &gas_estimator).unwrap();
+		txq.add(tx2.clone(), TransactionOrigin::External, 0, None, &default_account_details, &gas_estimator).unwrap();
+
+		// then
+		assert_eq!(txq.gas_price_at(0), Some(tx.gas_price));
+		assert_eq!(txq.gas_price_at(1), Some(tx2.gas_price));
+		assert_eq!(txq.gas_price_at(2), None);
+	}
+
 	#[test]
 	fn should_put_transaction_to_futures_if_gap_detected() {
 		// given
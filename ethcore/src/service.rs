@@ -47,7 +47,11 @@ pub enum ClientIoMessage {
 	/// Take a snapshot for the block with given number.
 	TakeSnapshot(u64),
 	/// New consensus message received.
-	NewMessage(Bytes)
+	NewMessage(Bytes),
+	/// Backfill the log index on a background thread.
+	BackfillLogIndex,
+	/// Generate the flat state on a background thread.
+	GenerateFlatState,
 }
 
 /// Client service setup. Creates and registers client and network services with the IO subsystem.
@@ -218,6 +222,20 @@ impl IoHandler<ClientIoMessage> for ClientIoHandler {
 			ClientIoMessage::NewMessage(ref message) => if let Err(e) = self.client.engine().handle_message(message) {
 				trace!(target: "poa", "Invalid message received: {}", e);
 			},
+			ClientIoMessage::BackfillLogIndex => {
+				let client = self.client.clone();
+				let res = thread::Builder::new().name("Log index backfill".into()).spawn(move || client.backfill_log_index());
+				if let Err(e) = res {
+					debug!(target: "client", "Failed to initialize log index backfill thread: {:?}", e);
+				}
+			},
+			ClientIoMessage::GenerateFlatState => {
+				let client = self.client.clone();
+				let res = thread::Builder::new().name("Flat state generation".into()).spawn(move || client.generate_flat_state());
+				if let Err(e) = res {
+					debug!(target: "client", "Failed to initialize flat state generation thread: {:?}", e);
+				}
+			},
 			_ => {} // ignore other messages
 		}
 	}
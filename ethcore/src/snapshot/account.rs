@@ -73,7 +73,17 @@ pub fn to_fat_rlp(acc: &BasicAccount, acct_db: &AccountDB, used_code: &mut HashS
 
 	for item in db.iter()? {
 		let (k, v) = item?;
-		pairs.push((k, v));
+		pairs.push((k, v.to_vec()));
+	}
+
+	Ok(to_fat_rlp_with_storage(acc, acct_db, pairs, used_code))
+}
+
+// create the account's RLP item from already known storage pairs of
+// hashed keys and values, ordered by key.
+pub fn to_fat_rlp_with_storage(acc: &BasicAccount, acct_db: &AccountDB, pairs: Vec<(Bytes, Bytes)>, used_code: &mut HashSet<H256>) -> Bytes {
+	if acc == &ACC_EMPTY {
+		return ::rlp::NULL_RLP.to_vec();
 	}
 
 	let mut stream = RlpStream::new_list(pairs.len());
@@ -108,7 +118,7 @@ pub fn to_fat_rlp(acc: &BasicAccount, acct_db: &AccountDB, used_code: &mut HashS
 
 	account_stream.append_raw(&pairs_rlp, 1);
 
-	Ok(account_stream.out())
+	account_stream.out()
 }
 
 // decode a fat rlp, and rebuild the storage trie as we go.
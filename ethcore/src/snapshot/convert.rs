@@ -0,0 +1,163 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Conversion of a database between state pruning algorithms.
+//!
+//! The state at the best block is streamed through the state chunker straight
+//! into a `StateRebuilder` writing into the target database, so only the live
+//! state survives the conversion. Blocks, extras, traces, the flat state and the
+//! address and log indexes are copied verbatim.
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use db::{COL_STATE, COL_HEADERS, COL_BODIES, COL_EXTRA, COL_TRACE, COL_FLAT_STATE, COL_ADDRESS_INDEX, COL_LOG_INDEX};
+use views::HeaderView;
+use util::{snappy, Mutex, UtilError};
+use util::hash::{FixedHash, H256};
+use util::journaldb::{self, Algorithm};
+use util::kvdb::KeyValueDB;
+use rlp::{UntrustedRlp, RlpType, Compressible};
+
+use super::{chunk_state, Progress, StateRebuilder, ManifestData, Error};
+use super::io::SnapshotWriter;
+
+// Number of entries copied in a single database transaction.
+const COPY_BATCH_SIZE: usize = 10_000;
+
+// Feeds produced state chunks directly into a rebuilder.
+struct RebuildingWriter {
+	rebuilder: StateRebuilder,
+	snappy_buffer: Vec<u8>,
+	error: Option<::error::Error>,
+}
+
+impl SnapshotWriter for RebuildingWriter {
+	fn write_state_chunk(&mut self, _hash: H256, chunk: &[u8]) -> io::Result<()> {
+		let len = snappy::decompress_into(chunk, &mut self.snappy_buffer)
+			.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid state chunk"))?;
+
+		let flag = AtomicBool::new(true);
+		if let Err(e) = self.rebuilder.feed(&self.snappy_buffer[..len], &flag) {
+			let message = format!("{}", e);
+			self.error = Some(e);
+			return Err(io::Error::new(io::ErrorKind::Other, message));
+		}
+		Ok(())
+	}
+
+	fn write_block_chunk(&mut self, _hash: H256, _chunk: &[u8]) -> io::Result<()> {
+		Ok(())
+	}
+
+	fn finish(self, _manifest: ManifestData) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+// Discards produced chunks; used to walk the converted state.
+struct NullWriter;
+
+impl SnapshotWriter for NullWriter {
+	fn write_state_chunk(&mut self, _hash: H256, _chunk: &[u8]) -> io::Result<()> { Ok(()) }
+	fn write_block_chunk(&mut self, _hash: H256, _chunk: &[u8]) -> io::Result<()> { Ok(()) }
+	fn finish(self, _manifest: ManifestData) -> io::Result<()> { Ok(()) }
+}
+
+/// Copy the state with given root from `source` into `dest`, laid out for the `pruning`
+/// algorithm, and journal it under the given block. The converted state is verified by
+/// chunking it again and comparing chunk hashes with the ones produced from `source`.
+pub fn convert_state(
+	source: Arc<KeyValueDB>,
+	source_pruning: Algorithm,
+	root: &H256,
+	dest: Arc<KeyValueDB>,
+	pruning: Algorithm,
+	era: (u64, H256),
+	progress: &Progress,
+) -> Result<(), ::error::Error> {
+	let source_db = journaldb::new(source, source_pruning, COL_STATE);
+
+	let writer = Mutex::new(RebuildingWriter {
+		rebuilder: StateRebuilder::new(dest.clone(), pruning),
+		snappy_buffer: Vec::new(),
+		error: None,
+	});
+
+	let chunk_hashes = chunk_state(source_db.as_hashdb(), root, &writer, progress);
+	let mut writer = writer.into_inner();
+	if let Some(e) = writer.error.take() {
+		return Err(e);
+	}
+	let chunk_hashes = chunk_hashes?;
+
+	if writer.rebuilder.state_root() != *root {
+		return Err(Error::WrongStateRoot(*root, writer.rebuilder.state_root()).into());
+	}
+	writer.rebuilder.journal_under(era.0, &era.1)?;
+	writer.rebuilder.check_missing()?;
+	dest.flush().map_err(UtilError::SimpleString)?;
+
+	info!(target: "snapshot", "Verifying converted state");
+	let dest_db = journaldb::new(dest, pruning, COL_STATE);
+	let verified_hashes = chunk_state(dest_db.as_hashdb(), root, &Mutex::new(NullWriter), &Progress::default())?;
+	if verified_hashes != chunk_hashes {
+		return Err(Error::ConversionMismatch.into());
+	}
+
+	progress.done.store(true, Ordering::SeqCst);
+	Ok(())
+}
+
+/// Convert the whole database between pruning algorithms. Blocks, extras, traces, the flat
+/// state and the indexes are copied as they are, while only the state at the best block is moved over.
+pub fn convert_database(
+	source: Arc<KeyValueDB>,
+	source_pruning: Algorithm,
+	dest: Arc<KeyValueDB>,
+	pruning: Algorithm,
+	progress: &Progress,
+) -> Result<(), ::error::Error> {
+	let best_hash = source.get(COL_EXTRA, b"best").map_err(UtilError::SimpleString)?
+		.map(|best| H256::from_slice(&best))
+		.ok_or(Error::IncompleteChain)?;
+	let best_header = source.get(COL_HEADERS, &best_hash).map_err(UtilError::SimpleString)?
+		.map(|header| UntrustedRlp::new(&header).decompress(RlpType::Blocks).to_vec())
+		.ok_or(Error::BlockNotFound(best_hash))?;
+	let (number, state_root) = {
+		let view = HeaderView::new(&best_header);
+		(view.number(), view.state_root())
+	};
+
+	for col in &[COL_HEADERS, COL_BODIES, COL_EXTRA, COL_TRACE, COL_FLAT_STATE, COL_ADDRESS_INDEX, COL_LOG_INDEX] {
+		let mut batch = dest.transaction();
+		let mut count = 0;
+		for (key, value) in source.iter(*col) {
+			batch.put_vec(*col, &key, value.into_vec());
+			count += 1;
+			if count % COPY_BATCH_SIZE == 0 {
+				dest.write(batch).map_err(UtilError::SimpleString)?;
+				batch = dest.transaction();
+			}
+		}
+		dest.write(batch).map_err(UtilError::SimpleString)?;
+		trace!(target: "snapshot", "Copied {} entries of column {:?}", count, col);
+	}
+
+	info!(target: "snapshot", "Converting state at block #{} from {} to {}", number, source_pruning, pruning);
+	convert_state(source, source_pruning, &state_root, dest, pruning, (number, best_hash), progress)
+}
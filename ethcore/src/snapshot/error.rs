@@ -47,6 +47,10 @@ pub enum Error {
 	UnrecognizedCodeState(u8),
 	/// Restoration aborted.
 	RestorationAborted,
+	/// State read back after conversion differs from the original.
+	ConversionMismatch,
+	/// Saved restoration progress doesn't match the restoration database.
+	InvalidProgress,
 	/// Trie error.
 	Trie(TrieError),
 	/// Decoder error.
@@ -70,6 +74,8 @@ impl fmt::Display for Error {
 			Error::MissingCode(ref missing) => write!(f, "Incomplete snapshot: {} contract codes not found.", missing.len()),
 			Error::UnrecognizedCodeState(state) => write!(f, "Unrecognized code encoding ({})", state),
 			Error::RestorationAborted => write!(f, "Snapshot restoration aborted."),
+			Error::ConversionMismatch => write!(f, "Converted state does not match the original."),
+			Error::InvalidProgress => write!(f, "Saved restoration progress does not match the restoration database."),
 			Error::Io(ref err) => err.fmt(f),
 			Error::Decoder(ref err) => err.fmt(f),
 			Error::Trie(ref err) => err.fmt(f),
@@ -34,7 +34,8 @@ use util::{Bytes, Hashable, HashDB, DBValue, snappy, U256, Uint};
 use util::Mutex;
 use util::hash::{FixedHash, H256};
 use util::journaldb::{self, Algorithm, JournalDB};
-use util::kvdb::Database;
+use util::kvdb::{KeyValueDB, DBTransaction};
+use util::UtilError;
 use util::trie::{TrieDB, TrieDBMut, Trie, TrieMut};
 use util::sha3::SHA3_NULL_RLP;
 use rlp::{RlpStream, Stream, UntrustedRlp, View};
@@ -44,6 +45,7 @@ use self::block::AbridgedBlock;
 use self::io::SnapshotWriter;
 
 use super::state_db::StateDB;
+use super::flat_state::{FlatState, FlatStateView};
 use super::state::Account as StateAccount;
 
 use crossbeam::scope;
@@ -51,6 +53,7 @@ use rand::{Rng, OsRng};
 
 pub use self::error::Error;
 
+pub use self::convert::{convert_database, convert_state};
 pub use self::service::{Service, DatabaseRestore};
 pub use self::traits::SnapshotService;
 pub use self::watcher::Watcher;
@@ -62,6 +65,7 @@ pub mod service;
 
 mod account;
 mod block;
+mod convert;
 mod error;
 mod watcher;
 
@@ -82,8 +86,8 @@ mod traits {
 // Try to have chunks be around 4MB (before compression)
 const PREFERRED_CHUNK_SIZE: usize = 4 * 1024 * 1024;
 
-// How many blocks to include in a snapshot, starting from the head of the chain.
-const SNAPSHOT_BLOCKS: u64 = 30000;
+/// How many blocks to include in a snapshot, starting from the head of the chain.
+pub const SNAPSHOT_BLOCKS: u64 = 30000;
 
 /// A progress indicator for snapshots.
 #[derive(Debug, Default)]
@@ -124,6 +128,7 @@ pub fn take_snapshot<W: SnapshotWriter + Send>(
 	chain: &BlockChain,
 	block_at: H256,
 	state_db: &HashDB,
+	flat_state: Option<&FlatState>,
 	writer: W,
 	p: &Progress
 ) -> Result<(), Error> {
@@ -134,10 +139,19 @@ pub fn take_snapshot<W: SnapshotWriter + Send>(
 
 	info!("Taking snapshot starting at block {}", number);
 
+	// the view keeps the flat state from being flattened past the snapshot block.
+	let flat_view = flat_state.and_then(|flat_state| flat_state.view(&state_root));
+	if flat_view.is_some() {
+		info!("Reading state from the flat state");
+	}
+
 	let writer = Mutex::new(writer);
 	let (state_hashes, block_hashes) = scope(|scope| {
 		let block_guard = scope.spawn(|| chunk_blocks(chain, block_at, &writer, p));
-		let state_res = chunk_state(state_db, state_root, &writer, p);
+		let state_res = match flat_view {
+			Some(ref view) => chunk_flat_state(view, state_db, &writer, p),
+			None => chunk_state(state_db, state_root, &writer, p),
+		};
 
 		state_res.and_then(|state_hashes| {
 			block_guard.join().map(|block_hashes| (state_hashes, block_hashes))
@@ -383,29 +397,166 @@ pub fn chunk_state<'a>(db: &HashDB, root: &H256, writer: &Mutex<SnapshotWriter +
 	Ok(chunker.hashes)
 }
 
+/// Walk the given flat state view, chunking accounts in the same order as `chunk_state`
+/// would, but without descending any tries. Code is still read from `db`.
+///
+/// Returns a list of hashes of chunks created, or any error it may
+/// have encountered.
+pub fn chunk_flat_state<'a>(view: &FlatStateView, db: &HashDB, writer: &Mutex<SnapshotWriter + 'a>, progress: &'a Progress) -> Result<Vec<H256>, Error> {
+	let mut chunker = StateChunker {
+		hashes: Vec::new(),
+		rlps: Vec::new(),
+		cur_size: 0,
+		snappy_buffer: vec![0; snappy::max_compressed_len(PREFERRED_CHUNK_SIZE)],
+		writer: writer,
+		progress: progress,
+	};
+
+	let mut used_code = HashSet::new();
+
+	for (account_key_hash, account_data) in view.accounts() {
+		let account = ::rlp::decode(&*account_data);
+		let account_db = AccountDB::from_hash(db, account_key_hash);
+
+		let storage = view.storage(&account_key_hash).map(|(k, v)| (k.to_vec(), v)).collect();
+		let fat_rlp = account::to_fat_rlp_with_storage(&account, &account_db, storage, &mut used_code);
+		chunker.push(account_key_hash.to_vec(), fat_rlp)?;
+	}
+
+	if chunker.cur_size != 0 {
+		chunker.write_chunk()?;
+	}
+
+	Ok(chunker.hashes)
+}
+
+// Progress of the rebuilders, kept in the extras column of the database being restored.
+// Entries are stored one per key, so saving progress only writes what the last chunk changed.
+const STATE_ROOT_KEY: &'static [u8] = b"restoration_state_root";
+const KNOWN_CODE_PREFIX: &'static [u8] = b"restoration_known_code";
+const MISSING_CODE_PREFIX: &'static [u8] = b"restoration_missing_code";
+const FED_BLOCKS_KEY: &'static [u8] = b"restoration_fed_blocks";
+const DISCONNECTED_PREFIX: &'static [u8] = b"restoration_disconnected";
+
+// key of a restoration progress entry.
+fn progress_key(prefix: &[u8], suffix: &[u8]) -> Vec<u8> {
+	let mut key = prefix.to_vec();
+	key.extend_from_slice(suffix);
+	key
+}
+
+// hash stored in the key of a restoration progress entry.
+fn progress_key_hash(prefix: &[u8], key: &[u8]) -> Result<H256, Error> {
+	match key.len() == prefix.len() + 32 {
+		true => Ok(H256::from_slice(&key[prefix.len()..])),
+		false => Err(Error::InvalidProgress),
+	}
+}
+
 /// Used to rebuild the state trie piece by piece.
 pub struct StateRebuilder {
 	db: Box<JournalDB>,
 	state_root: H256,
 	known_code: HashMap<H256, H256>, // code hashes mapped to first account with this code.
 	missing_code: HashMap<H256, Vec<H256>>, // maps code hashes to lists of accounts missing that code.
+	dirty_code: Option<HashSet<H256>>, // code hashes changed since the progress was last written, if it was.
 	bloom: Bloom,
 }
 
 impl StateRebuilder {
 	/// Create a new state rebuilder to write into the given backing DB.
-	pub fn new(db: Arc<Database>, pruning: Algorithm) -> Self {
+	pub fn new(db: Arc<KeyValueDB>, pruning: Algorithm) -> Self {
 		StateRebuilder {
 			db: journaldb::new(db.clone(), pruning, ::db::COL_STATE),
 			state_root: SHA3_NULL_RLP,
 			known_code: HashMap::new(),
 			missing_code: HashMap::new(),
+			dirty_code: None,
 			bloom: StateDB::load_bloom(&*db),
 		}
 	}
 
+	/// Resume a state rebuilder from progress saved by `write_progress`. The state
+	/// fed so far must already be in the backing DB.
+	pub fn resume(db: Arc<KeyValueDB>, pruning: Algorithm) -> Result<Self, ::error::Error> {
+		let mut rebuilder = StateRebuilder::new(db.clone(), pruning);
+
+		if let Some(root) = db.get(::db::COL_EXTRA, STATE_ROOT_KEY).map_err(UtilError::SimpleString)? {
+			rebuilder.state_root = UntrustedRlp::new(&root).as_val()?;
+		}
+
+		for (key, first_with) in db.iter_from_prefix(::db::COL_EXTRA, KNOWN_CODE_PREFIX) {
+			let code_hash = progress_key_hash(KNOWN_CODE_PREFIX, &key)?;
+			rebuilder.known_code.insert(code_hash, UntrustedRlp::new(&first_with).as_val()?);
+		}
+
+		for (key, accounts) in db.iter_from_prefix(::db::COL_EXTRA, MISSING_CODE_PREFIX) {
+			let code_hash = progress_key_hash(MISSING_CODE_PREFIX, &key)?;
+			rebuilder.missing_code.insert(code_hash, UntrustedRlp::new(&accounts).as_val()?);
+		}
+
+		rebuilder.dirty_code = Some(HashSet::new());
+
+		// trie nodes of each chunk are written in the same batch as the new root.
+		if rebuilder.state_root != SHA3_NULL_RLP && !rebuilder.db.as_hashdb().contains(&rebuilder.state_root) {
+			return Err(Error::InvalidProgress.into());
+		}
+
+		Ok(rebuilder)
+	}
+
+	/// Write the progress made since the last call into `batch`, for use with `resume`.
+	/// The first call writes all known and missing code.
+	pub fn write_progress(&mut self, batch: &mut DBTransaction) {
+		let dirty = match self.dirty_code.take() {
+			Some(dirty) => dirty,
+			None => self.known_code.keys().chain(self.missing_code.keys()).cloned().collect(),
+		};
+
+		for code_hash in &dirty {
+			let key = progress_key(KNOWN_CODE_PREFIX, code_hash);
+			match self.known_code.get(code_hash) {
+				Some(first_with) => batch.put(::db::COL_EXTRA, &key, &::rlp::encode(first_with)),
+				None => batch.delete(::db::COL_EXTRA, &key),
+			}
+
+			let key = progress_key(MISSING_CODE_PREFIX, code_hash);
+			match self.missing_code.get(code_hash) {
+				Some(accounts) => batch.put(::db::COL_EXTRA, &key, &::rlp::encode(accounts)),
+				None => batch.delete(::db::COL_EXTRA, &key),
+			}
+		}
+
+		batch.put(::db::COL_EXTRA, STATE_ROOT_KEY, &::rlp::encode(&self.state_root));
+		self.dirty_code = Some(HashSet::new());
+	}
+
+	/// Remove the progress written by `write_progress`.
+	pub fn clear_progress(&self, batch: &mut DBTransaction) {
+		for code_hash in self.known_code.keys().chain(self.missing_code.keys()) {
+			batch.delete(::db::COL_EXTRA, &progress_key(KNOWN_CODE_PREFIX, code_hash));
+			batch.delete(::db::COL_EXTRA, &progress_key(MISSING_CODE_PREFIX, code_hash));
+		}
+		batch.delete(::db::COL_EXTRA, STATE_ROOT_KEY);
+	}
+
+	// note a change of the code maps, if progress is being written.
+	fn note_dirty(&mut self, code_hash: H256) {
+		if let Some(ref mut dirty) = self.dirty_code {
+			dirty.insert(code_hash);
+		}
+	}
+
 	/// Feed an uncompressed state chunk into the rebuilder.
 	pub fn feed(&mut self, chunk: &[u8], flag: &AtomicBool) -> Result<(), ::error::Error> {
+		let mut batch = self.db.backing().transaction();
+		self.feed_into(chunk, flag, &mut batch)?;
+		self.db.backing().write_buffered(batch);
+		Ok(())
+	}
+
+	/// Feed an uncompressed state chunk into the rebuilder, writing the new state into `batch`.
+	pub fn feed_into(&mut self, chunk: &[u8], flag: &AtomicBool, batch: &mut DBTransaction) -> Result<(), ::error::Error> {
 		let rlp = UntrustedRlp::new(chunk);
 		let empty_rlp = StateAccount::new_basic(U256::zero(), U256::zero()).rlp();
 		let mut pairs = Vec::with_capacity(rlp.item_count());
@@ -423,6 +574,7 @@ impl StateRebuilder {
 
 		for (addr_hash, code_hash) in status.missing_code {
 			self.missing_code.entry(code_hash).or_insert_with(Vec::new).push(addr_hash);
+			self.note_dirty(code_hash);
 		}
 
 		// patch up all missing code. must be done after collecting all new missing code entries.
@@ -433,10 +585,9 @@ impl StateRebuilder {
 			}
 
 			self.known_code.insert(code_hash, first_with);
+			self.note_dirty(code_hash);
 		}
 
-		let backing = self.db.backing().clone();
-
 		// batch trie writes
 		{
 			let mut account_trie = if self.state_root != SHA3_NULL_RLP {
@@ -456,10 +607,8 @@ impl StateRebuilder {
 		}
 
 		let bloom_journal = self.bloom.drain_journal();
-		let mut batch = backing.transaction();
-		StateDB::commit_bloom(&mut batch, bloom_journal)?;
-		self.db.inject(&mut batch)?;
-		backing.write_buffered(batch);
+		StateDB::commit_bloom(batch, bloom_journal)?;
+		self.db.inject(batch)?;
 		trace!(target: "snapshot", "current state root: {:?}", self.state_root);
 		Ok(())
 	}
@@ -476,6 +625,16 @@ impl StateRebuilder {
 
 	/// Get the state root of the rebuilder.
 	pub fn state_root(&self) -> H256 { self.state_root }
+
+	/// Journal the rebuilt state under the given block, so that the database
+	/// is not considered empty when opened.
+	pub fn journal_under(&mut self, era: u64, id: &H256) -> Result<(), ::error::Error> {
+		let backing = self.db.backing().clone();
+		let mut batch = backing.transaction();
+		self.db.journal_under(&mut batch, era, id)?;
+		backing.write_buffered(batch);
+		Ok(())
+	}
 }
 
 #[derive(Default)]
@@ -569,18 +728,19 @@ pub fn verify_old_block(rng: &mut OsRng, header: &Header, engine: &Engine, chain
 /// After all chunks have been submitted, we "glue" the chunks together.
 pub struct BlockRebuilder {
 	chain: BlockChain,
-	db: Arc<Database>,
+	db: Arc<KeyValueDB>,
 	rng: OsRng,
 	disconnected: Vec<(u64, H256)>,
 	best_number: u64,
 	best_hash: H256,
 	best_root: H256,
 	fed_blocks: u64,
+	saved_disconnected: usize, // number of disconnected chunks written by `write_progress`.
 }
 
 impl BlockRebuilder {
 	/// Create a new BlockRebuilder.
-	pub fn new(chain: BlockChain, db: Arc<Database>, manifest: &ManifestData) -> Result<Self, ::error::Error> {
+	pub fn new(chain: BlockChain, db: Arc<KeyValueDB>, manifest: &ManifestData) -> Result<Self, ::error::Error> {
 		Ok(BlockRebuilder {
 			chain: chain,
 			db: db,
@@ -590,9 +750,49 @@ impl BlockRebuilder {
 			best_hash: manifest.block_hash,
 			best_root: manifest.state_root,
 			fed_blocks: 0,
+			saved_disconnected: 0,
 		})
 	}
 
+	/// Resume a block rebuilder from progress saved by `write_progress`. The blocks
+	/// fed so far must already be in the chain.
+	pub fn resume(chain: BlockChain, db: Arc<KeyValueDB>, manifest: &ManifestData) -> Result<Self, ::error::Error> {
+		let mut rebuilder = BlockRebuilder::new(chain, db.clone(), manifest)?;
+
+		if let Some(fed_blocks) = db.get(::db::COL_EXTRA, FED_BLOCKS_KEY).map_err(UtilError::SimpleString)? {
+			rebuilder.fed_blocks = UntrustedRlp::new(&fed_blocks).as_val()?;
+		}
+
+		for (key, number) in db.iter_from_prefix(::db::COL_EXTRA, DISCONNECTED_PREFIX) {
+			let hash = progress_key_hash(DISCONNECTED_PREFIX, &key)?;
+			rebuilder.disconnected.push((UntrustedRlp::new(&number).as_val()?, hash));
+		}
+		rebuilder.saved_disconnected = rebuilder.disconnected.len();
+
+		if rebuilder.fed_blocks > SNAPSHOT_BLOCKS || rebuilder.disconnected.iter().any(|&(_, ref hash)| !rebuilder.chain.is_known(hash)) {
+			return Err(Error::InvalidProgress.into());
+		}
+
+		Ok(rebuilder)
+	}
+
+	/// Write the progress made since the last call into `batch`, for use with `resume`.
+	pub fn write_progress(&mut self, batch: &mut DBTransaction) {
+		batch.put(::db::COL_EXTRA, FED_BLOCKS_KEY, &::rlp::encode(&self.fed_blocks));
+		for &(num, ref hash) in &self.disconnected[self.saved_disconnected..] {
+			batch.put(::db::COL_EXTRA, &progress_key(DISCONNECTED_PREFIX, hash), &::rlp::encode(&num));
+		}
+		self.saved_disconnected = self.disconnected.len();
+	}
+
+	/// Remove the progress written by `write_progress`.
+	pub fn clear_progress(&self, batch: &mut DBTransaction) {
+		for &(_, ref hash) in &self.disconnected {
+			batch.delete(::db::COL_EXTRA, &progress_key(DISCONNECTED_PREFIX, hash));
+		}
+		batch.delete(::db::COL_EXTRA, FED_BLOCKS_KEY);
+	}
+
 	/// Feed the rebuilder an uncompressed block chunk.
 	/// Returns the number of blocks fed or any errors.
 	pub fn feed(&mut self, chunk: &[u8], engine: &Engine, abort_flag: &AtomicBool) -> Result<u64, ::error::Error> {
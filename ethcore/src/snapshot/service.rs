@@ -35,11 +35,17 @@ use service::ClientIoMessage;
 
 use io::IoChannel;
 
-use util::{Bytes, H256, Mutex, RwLock, RwLockReadGuard, UtilError};
+use util::{Bytes, H256, Hashable, Mutex, RwLock, RwLockReadGuard, UtilError};
 use util::journaldb::Algorithm;
-use util::kvdb::{Database, DatabaseConfig};
+use util::kvdb::{Database, DatabaseConfig, DBTransaction, KeyValueDB};
 use util::snappy;
 
+/// Key of the manifest of the restoration in the restoration database.
+const MANIFEST_KEY: &'static [u8] = b"restoration_manifest";
+
+/// Prefix of the keys marking fed chunks in the restoration database.
+const CHUNK_PREFIX: &'static [u8] = b"restoration_chunk";
+
 /// Helper for removing directories in case of error.
 struct Guard(bool, PathBuf);
 
@@ -75,15 +81,14 @@ struct Restoration {
 	final_state_root: H256,
 	guard: Guard,
 	canonical_hashes: HashMap<u64, H256>,
-	db: Arc<Database>,
+	db: Arc<KeyValueDB>,
 }
 
 struct RestorationParams<'a> {
 	manifest: ManifestData, // manifest to base restoration on.
 	pruning: Algorithm, // pruning algorithm for the database.
 	engine: Arc<Engine>, // consensus engine of the chain.
-	db_path: PathBuf, // database path
-	db_config: &'a DatabaseConfig, // configuration for the database.
+	db: Arc<KeyValueDB>, // restoration database.
 	writer: Option<LooseWriter>, // writer for recovered snapshot.
 	genesis: &'a [u8], // genesis block of the chain.
 	guard: Guard, // guard for the restoration directory.
@@ -92,31 +97,58 @@ struct RestorationParams<'a> {
 impl Restoration {
 	// make a new restoration using the given parameters.
 	fn new(params: RestorationParams) -> Result<Self, Error> {
+		let chain = BlockChain::new(Default::default(), params.genesis, params.db.clone(), params.engine.clone());
+		let blocks = BlockRebuilder::new(chain, params.db.clone(), &params.manifest)?;
+		let state = StateRebuilder::new(params.db.clone(), params.pruning);
+
+		// the manifest is stored once; progress is saved per chunk from here on.
+		let mut batch = params.db.transaction();
+		batch.put(::db::COL_EXTRA, MANIFEST_KEY, &params.manifest.clone().into_rlp());
+		params.db.write_buffered(batch);
+
+		Ok(Restoration::with_rebuilders(params, state, blocks))
+	}
+
+	// resume a restoration from the progress saved in its database.
+	// chunks fed earlier are not replayed: they are only looked up by their manifest hashes,
+	// and the rebuilders check that their saved state is present in the database.
+	fn resume(params: RestorationParams) -> Result<Self, Error> {
+		let chain = BlockChain::new(Default::default(), params.genesis, params.db.clone(), params.engine.clone());
+		let blocks = BlockRebuilder::resume(chain, params.db.clone(), &params.manifest)?;
+		let state = StateRebuilder::resume(params.db.clone(), params.pruning)?;
+
+		let mut rest = Restoration::with_rebuilders(params, state, blocks);
+		let hashes: Vec<H256> = rest.manifest.state_hashes.iter().chain(rest.manifest.block_hashes.iter()).cloned().collect();
+		for hash in hashes {
+			if rest.db.get(::db::COL_EXTRA, &super::progress_key(CHUNK_PREFIX, &hash)).map_err(UtilError::SimpleString)?.is_some() {
+				rest.state_chunks_left.remove(&hash);
+				rest.block_chunks_left.remove(&hash);
+			}
+		}
+
+		Ok(rest)
+	}
+
+	fn with_rebuilders(params: RestorationParams, state: StateRebuilder, blocks: BlockRebuilder) -> Self {
 		let manifest = params.manifest;
 
 		let state_chunks = manifest.state_hashes.iter().cloned().collect();
 		let block_chunks = manifest.block_hashes.iter().cloned().collect();
 
-		let raw_db = Arc::new(Database::open(params.db_config, &*params.db_path.to_string_lossy())
-			.map_err(UtilError::SimpleString)?);
-
-		let chain = BlockChain::new(Default::default(), params.genesis, raw_db.clone(), params.engine);
-		let blocks = BlockRebuilder::new(chain, raw_db.clone(), &manifest)?;
-
 		let root = manifest.state_root.clone();
-		Ok(Restoration {
+		Restoration {
 			manifest: manifest,
 			state_chunks_left: state_chunks,
 			block_chunks_left: block_chunks,
-			state: StateRebuilder::new(raw_db.clone(), params.pruning),
+			state: state,
 			blocks: blocks,
 			writer: params.writer,
 			snappy_buffer: Vec::new(),
 			final_state_root: root,
 			guard: params.guard,
 			canonical_hashes: HashMap::new(),
-			db: raw_db,
-		})
+			db: params.db,
+		}
 	}
 
 	// feeds a state chunk, aborts early if `flag` becomes false.
@@ -124,11 +156,16 @@ impl Restoration {
 		if self.state_chunks_left.remove(&hash) {
 			let len = snappy::decompress_into(chunk, &mut self.snappy_buffer)?;
 
-			self.state.feed(&self.snappy_buffer[..len], flag)?;
+			// the chunk's state and the progress it makes go into the same batch.
+			let mut batch = self.db.transaction();
+			self.state.feed_into(&self.snappy_buffer[..len], flag, &mut batch)?;
 
 			if let Some(ref mut writer) = self.writer.as_mut() {
 				writer.write_state_chunk(hash, chunk)?;
 			}
+
+			self.write_progress(&mut batch, &hash);
+			self.db.write_buffered(batch);
 		}
 
 		Ok(())
@@ -143,11 +180,30 @@ impl Restoration {
 			if let Some(ref mut writer) = self.writer.as_mut() {
 				 writer.write_block_chunk(hash, chunk)?;
 			}
+
+			let mut batch = self.db.transaction();
+			self.write_progress(&mut batch, &hash);
+			self.db.write_buffered(batch);
 		}
 
 		Ok(())
 	}
 
+	// hashes of all chunks fed so far.
+	fn done_chunks(&self) -> Vec<H256> {
+		let state = self.manifest.state_hashes.iter().filter(|h| !self.state_chunks_left.contains(*h));
+		let blocks = self.manifest.block_hashes.iter().filter(|h| !self.block_chunks_left.contains(*h));
+		state.chain(blocks).cloned().collect()
+	}
+
+	// write the progress made by feeding the given chunk. it is flushed together with
+	// the data of the chunk, so the database never runs ahead of the saved progress.
+	fn write_progress(&mut self, batch: &mut DBTransaction, hash: &H256) {
+		batch.put(::db::COL_EXTRA, &super::progress_key(CHUNK_PREFIX, hash), &[]);
+		self.state.write_progress(batch);
+		self.blocks.write_progress(batch);
+	}
+
 	// note canonical hashes.
 	fn note_canonical(&mut self, hashes: &[(u64, H256)]) {
 		self.canonical_hashes.extend(hashes.iter().cloned());
@@ -159,6 +215,16 @@ impl Restoration {
 
 		if !self.is_done() { return Ok(()) }
 
+		// nothing is left to resume after this point.
+		let mut batch = self.db.transaction();
+		batch.delete(::db::COL_EXTRA, MANIFEST_KEY);
+		for hash in self.manifest.state_hashes.iter().chain(self.manifest.block_hashes.iter()) {
+			batch.delete(::db::COL_EXTRA, &super::progress_key(CHUNK_PREFIX, hash));
+		}
+		self.state.clear_progress(&mut batch);
+		self.blocks.clear_progress(&mut batch);
+		self.db.write_buffered(batch);
+
 		// verify final state root.
 		let root = self.state.state_root();
 		if root != self.final_state_root {
@@ -256,10 +322,26 @@ impl Service {
 			}
 		}
 
-		// delete the temporary restoration dir if it does exist.
-		if let Err(e) = fs::remove_dir_all(service.restoration_dir()) {
-			if e.kind() != ErrorKind::NotFound {
-				return Err(e.into())
+		let resumed = match service.resume_restoration() {
+			Ok(rest) => rest,
+			Err(e) => {
+				warn!("Discarding interrupted snapshot restoration: {}", e);
+				None
+			}
+		};
+
+		match resumed {
+			Some(rest) => {
+				info!("Found interrupted snapshot restoration at #{}", rest.manifest.block_number);
+				*service.restoration.get_mut() = Some(rest);
+			}
+			None => {
+				// delete the temporary restoration dir if it does exist.
+				if let Err(e) = fs::remove_dir_all(service.restoration_dir()) {
+					if e.kind() != ErrorKind::NotFound {
+						return Err(e.into())
+					}
+				}
 			}
 		}
 
@@ -311,6 +393,45 @@ impl Service {
 		dir
 	}
 
+	// open the restoration database, creating it if it doesn't exist.
+	fn open_restoration_db(&self) -> Result<Arc<KeyValueDB>, Error> {
+		let db = Database::open(&self.db_config, &*self.restoration_db().to_string_lossy())
+			.map_err(UtilError::SimpleString)?;
+
+		Ok(Arc::new(db))
+	}
+
+	// reopen a restoration interrupted by a previous shutdown, if there is one.
+	fn resume_restoration(&self) -> Result<Option<Restoration>, Error> {
+		if !self.restoration_db().exists() {
+			return Ok(None);
+		}
+
+		let guard = Guard::new(self.restoration_dir());
+		let db = self.open_restoration_db()?;
+		let manifest = match db.get(::db::COL_EXTRA, MANIFEST_KEY).map_err(UtilError::SimpleString)? {
+			Some(manifest) => ManifestData::from_rlp(&manifest)?,
+			None => return Ok(None),
+		};
+
+		let writer = match self.temp_recovery_dir().exists() {
+			true => Some(LooseWriter::new(self.temp_recovery_dir())?),
+			false => None,
+		};
+
+		let params = RestorationParams {
+			manifest: manifest,
+			pruning: self.pruning,
+			engine: self.engine.clone(),
+			db: db,
+			writer: writer,
+			genesis: &self.genesis_block,
+			guard: guard,
+		};
+
+		Restoration::resume(params).map(Some)
+	}
+
 	// replace one the client's database with our own.
 	fn replace_client_db(&self) -> Result<(), Error> {
 		let our_db = self.restoration_db();
@@ -391,48 +512,65 @@ impl Service {
 
 	/// Initialize the restoration synchronously.
 	/// The recover flag indicates whether to recover the restored snapshot.
+	/// A restoration of the same snapshot which is already in progress, e.g. one
+	/// interrupted by a restart, is continued rather than started over.
 	pub fn init_restore(&self, manifest: ManifestData, recover: bool) -> Result<(), Error> {
 		let rest_dir = self.restoration_dir();
 
 		let mut res = self.restoration.lock();
 
-		self.state_chunks.store(0, Ordering::SeqCst);
-		self.block_chunks.store(0, Ordering::SeqCst);
+		let state_chunks = manifest.state_hashes.len();
+		let block_chunks = manifest.block_hashes.len();
 
-		// tear down existing restoration.
-		*res = None;
+		// an existing restoration can only be continued if it has been recovering
+		// the snapshot all along, or if that isn't needed.
+		let resume = res.as_ref().map_or(false, |rest| rest.manifest == manifest && (rest.writer.is_some() || !recover));
 
-		// delete and restore the restoration dir.
-		if let Err(e) = fs::remove_dir_all(&rest_dir) {
-			match e.kind() {
-				ErrorKind::NotFound => {},
-				_ => return Err(e.into()),
-			}
-		}
+		if resume {
+			trace!(target: "snapshot", "resuming restoration of snapshot at #{}", manifest.block_number);
 
-		fs::create_dir_all(&rest_dir)?;
+			let rest = res.as_mut().expect("resume is only set for an existing restoration; qed");
+			if !recover {
+				rest.writer = None;
+			}
 
-		// make new restoration.
-		let writer = match recover {
-			true => Some(LooseWriter::new(self.temp_recovery_dir())?),
-			false => None
-		};
+			self.state_chunks.store(state_chunks - rest.state_chunks_left.len(), Ordering::SeqCst);
+			self.block_chunks.store(block_chunks - rest.block_chunks_left.len(), Ordering::SeqCst);
+		} else {
+			self.state_chunks.store(0, Ordering::SeqCst);
+			self.block_chunks.store(0, Ordering::SeqCst);
 
-		let params = RestorationParams {
-			manifest: manifest,
-			pruning: self.pruning,
-			engine: self.engine.clone(),
-			db_path: self.restoration_db(),
-			db_config: &self.db_config,
-			writer: writer,
-			genesis: &self.genesis_block,
-			guard: Guard::new(rest_dir),
-		};
+			// tear down existing restoration.
+			*res = None;
 
-		let state_chunks = params.manifest.state_hashes.len();
-		let block_chunks = params.manifest.block_hashes.len();
+			// delete and restore the restoration dir.
+			if let Err(e) = fs::remove_dir_all(&rest_dir) {
+				match e.kind() {
+					ErrorKind::NotFound => {},
+					_ => return Err(e.into()),
+				}
+			}
 
-		*res = Some(Restoration::new(params)?);
+			fs::create_dir_all(&rest_dir)?;
+
+			// make new restoration.
+			let writer = match recover {
+				true => Some(LooseWriter::new(self.temp_recovery_dir())?),
+				false => None
+			};
+
+			let params = RestorationParams {
+				manifest: manifest,
+				pruning: self.pruning,
+				engine: self.engine.clone(),
+				db: self.open_restoration_db()?,
+				writer: writer,
+				genesis: &self.genesis_block,
+				guard: Guard::new(rest_dir),
+			};
+
+			*res = Some(Restoration::new(params)?);
+		}
 
 		*self.status.lock() = RestorationStatus::Ongoing {
 			state_chunks: state_chunks as u32,
@@ -442,6 +580,12 @@ impl Service {
 		};
 
 		self.restoring_snapshot.store(true, Ordering::SeqCst);
+
+		// the previous run may have stopped after the last chunk, but before finalizing.
+		if resume && res.as_ref().map_or(false, |rest| rest.is_done()) {
+			return self.finalize_restoration(&mut *res);
+		}
+
 		Ok(())
 	}
 
@@ -578,6 +722,14 @@ impl SnapshotService for Service {
 		}
 	}
 
+	fn completed_chunks(&self, manifest_hash: H256) -> Option<Vec<H256>> {
+		self.restoration.lock().as_ref()
+			.and_then(|rest| match rest.manifest.clone().into_rlp().sha3() == manifest_hash {
+				true => Some(rest.done_chunks()),
+				false => None,
+			})
+	}
+
 	fn abort_restore(&self) {
 		self.restoring_snapshot.store(false, Ordering::SeqCst);
 		*self.restoration.lock() = None;
@@ -607,7 +759,12 @@ impl SnapshotService for Service {
 
 impl Drop for Service {
 	fn drop(&mut self) {
-		self.abort_restore();
+		self.restoring_snapshot.store(false, Ordering::SeqCst);
+
+		// leave the restoration on disk, so it can be resumed on the next start.
+		if let Some(rest) = self.restoration.get_mut().take() {
+			rest.guard.disarm();
+		}
 	}
 }
 
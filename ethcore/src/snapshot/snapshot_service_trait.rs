@@ -38,6 +38,10 @@ pub trait SnapshotService : Sync + Send {
 	/// From this point on, any previous snapshot may become unavailable.
 	fn begin_restore(&self, manifest: ManifestData);
 
+	/// Get the hashes of chunks already restored for the snapshot with the given
+	/// manifest hash, if its restoration is in progress or was interrupted by a restart.
+	fn completed_chunks(&self, manifest_hash: H256) -> Option<Vec<H256>>;
+
 	/// Abort an in-progress restoration if there is one.
 	fn abort_restore(&self);
 
@@ -27,6 +27,7 @@ use tests::helpers::generate_dummy_client_with_spec_and_data;
 
 use devtools::RandomTempPath;
 use io::IoChannel;
+use util::Hashable;
 use util::kvdb::DatabaseConfig;
 
 struct NoopDBRestore;
@@ -138,6 +139,71 @@ fn guards_delete_folders() {
 	service.init_restore(manifest.clone(), true).unwrap();
 	assert!(path.exists());
 
+	// an unfinished restoration is kept to be resumed later.
 	drop(service);
-	assert!(!path.exists());
+	assert!(path.exists());
+}
+
+#[test]
+fn resumes_interrupted_restoration() {
+	let client = generate_dummy_client_with_spec_and_data(Spec::new_null, 100, 5, &[1.into(), 2.into()]);
+
+	let path = RandomTempPath::create_dir();
+	let mut path = path.as_path().clone();
+	let mut client_db = path.clone();
+
+	client_db.push("client_db");
+	path.push("snapshot");
+
+	let spec = Spec::new_null();
+	let client2 = Client::new(
+		Default::default(),
+		&spec,
+		&client_db,
+		Arc::new(::miner::Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+		&DatabaseConfig::with_columns(::db::NUM_COLUMNS),
+	).unwrap();
+
+	let service_params = || ServiceParams {
+		engine: spec.engine.clone(),
+		genesis_block: spec.genesis_block(),
+		db_config: DatabaseConfig::with_columns(::db::NUM_COLUMNS),
+		pruning: ::util::journaldb::Algorithm::Archive,
+		channel: IoChannel::disconnected(),
+		snapshot_root: path.clone(),
+		db_restore: client2.clone(),
+	};
+
+	let service = Service::new(service_params()).unwrap();
+	service.take_snapshot(&client, 100).unwrap();
+
+	let manifest = service.manifest().unwrap();
+	let manifest_hash = manifest.clone().into_rlp().sha3();
+
+	service.init_restore(manifest.clone(), true).unwrap();
+	for hash in &manifest.state_hashes {
+		let chunk = service.chunk(*hash).unwrap();
+		service.feed_state_chunk(*hash, &chunk);
+	}
+	drop(service);
+
+	let service = Service::new(service_params()).unwrap();
+	assert_eq!(service.completed_chunks(manifest_hash), Some(manifest.state_hashes.clone()));
+
+	service.init_restore(manifest.clone(), true).unwrap();
+	assert_eq!(service.status(), ::snapshot::RestorationStatus::Ongoing {
+		state_chunks: manifest.state_hashes.len() as u32,
+		block_chunks: manifest.block_hashes.len() as u32,
+		state_chunks_done: manifest.state_hashes.len() as u32,
+		block_chunks_done: 0,
+	});
+
+	for hash in manifest.block_hashes {
+		let chunk = service.chunk(hash).unwrap();
+		service.feed_block_chunk(hash, &chunk);
+	}
+
+	assert_eq!(service.status(), ::snapshot::RestorationStatus::Inactive);
+	assert_eq!(client2.chain_info().best_block_hash, client.chain_info().best_block_hash);
 }
\ No newline at end of file
@@ -18,7 +18,8 @@
 
 use basic_account::BasicAccount;
 use snapshot::account;
-use snapshot::{chunk_state, Error as SnapshotError, Progress, StateRebuilder};
+use snapshot::{chunk_state, chunk_flat_state, Error as SnapshotError, Progress, StateRebuilder};
+use flat_state::FlatState;
 use snapshot::io::{PackedReader, PackedWriter, SnapshotReader, SnapshotWriter};
 use super::helpers::{compare_dbs, StateProducer};
 
@@ -27,7 +28,7 @@ use error::Error;
 use rand::{XorShiftRng, SeedableRng};
 use util::hash::H256;
 use util::journaldb::{self, Algorithm};
-use util::kvdb::{Database, DatabaseConfig};
+use util::kvdb::{self, Database, DatabaseConfig};
 use util::memorydb::MemoryDB;
 use util::Mutex;
 use devtools::RandomTempPath;
@@ -191,3 +192,70 @@ fn checks_flag() {
 		}
 	}
 }
+
+#[test]
+fn convert_between_pruning_algorithms() {
+	use snapshot::convert_state;
+	use util::kvdb::{self, KeyValueDB};
+	use util::{FixedHash, HashDB};
+	use util::trie::{TrieDB, Trie};
+
+	let mut producer = StateProducer::new();
+	let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+	let archive: Arc<KeyValueDB> = Arc::new(kvdb::in_memory(::db::NUM_COLUMNS.unwrap_or(0)));
+	{
+		let mut old_db = journaldb::new(archive.clone(), Algorithm::Archive, ::db::COL_STATE);
+		for _ in 0..150 {
+			producer.tick(&mut rng, old_db.as_hashdb_mut());
+		}
+		let mut batch = archive.transaction();
+		old_db.journal_under(&mut batch, 0, &H256::zero()).unwrap();
+		archive.write(batch).unwrap();
+	}
+
+	let state_root = producer.state_root();
+	let fast: Arc<KeyValueDB> = Arc::new(kvdb::in_memory(::db::NUM_COLUMNS.unwrap_or(0)));
+	let progress = Progress::default();
+	convert_state(archive.clone(), Algorithm::Archive, &state_root, fast.clone(), Algorithm::OverlayRecent, (150, H256::from(150)), &progress).unwrap();
+	assert!(progress.done());
+
+	// converting back yields the same live state.
+	let back: Arc<KeyValueDB> = Arc::new(kvdb::in_memory(::db::NUM_COLUMNS.unwrap_or(0)));
+	convert_state(fast.clone(), Algorithm::OverlayRecent, &state_root, back.clone(), Algorithm::Archive, (150, H256::from(150)), &Progress::default()).unwrap();
+
+	let accounts = |db: &HashDB| TrieDB::new(db, &state_root).unwrap().iter().unwrap().map(|item| item.unwrap()).collect::<Vec<_>>();
+	let old_db = journaldb::new(archive, Algorithm::Archive, ::db::COL_STATE);
+	let fast_db = journaldb::new(fast, Algorithm::OverlayRecent, ::db::COL_STATE);
+	let back_db = journaldb::new(back, Algorithm::Archive, ::db::COL_STATE);
+	assert_eq!(fast_db.latest_era(), Some(150));
+	assert_eq!(accounts(fast_db.as_hashdb()), accounts(old_db.as_hashdb()));
+	assert_eq!(accounts(back_db.as_hashdb()), accounts(old_db.as_hashdb()));
+}
+
+#[test]
+fn flat_state_chunks_match_trie_chunks() {
+	let mut producer = StateProducer::new();
+	let mut rng = XorShiftRng::from_seed([5, 6, 7, 8]);
+	let mut db = MemoryDB::new();
+
+	for _ in 0..150 {
+		producer.tick(&mut rng, &mut db);
+	}
+	let state_root = producer.state_root();
+
+	let flat = FlatState::open(Arc::new(kvdb::in_memory(::db::NUM_COLUMNS.unwrap_or(0))));
+	flat.start_generation(&state_root);
+	while !flat.generate_step(&db, 100).unwrap() {}
+	let view = flat.view(&state_root).unwrap();
+
+	let snap_dir = RandomTempPath::create_dir();
+	let trie_writer = Mutex::new(PackedWriter::new(&snap_dir.as_path().join("TRIE")).unwrap());
+	let flat_writer = Mutex::new(PackedWriter::new(&snap_dir.as_path().join("FLAT")).unwrap());
+
+	let trie_hashes = chunk_state(&db, &state_root, &trie_writer, &Progress::default()).unwrap();
+	let flat_hashes = chunk_flat_state(&view, &db, &flat_writer, &Progress::default()).unwrap();
+
+	assert!(!trie_hashes.is_empty());
+	assert_eq!(trie_hashes, flat_hashes);
+}
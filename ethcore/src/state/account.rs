@@ -187,6 +187,11 @@ impl Account {
 		value
 	}
 
+	/// Cache the contents of the trie's storage at `key`, read from elsewhere.
+	pub fn cache_storage(&self, key: H256, value: H256) {
+		self.storage_cache.borrow_mut().insert(key, value);
+	}
+
 	/// Get cached storage value if any. Returns `None` if the
 	/// key is not in the cache.
 	pub fn cached_storage_at(&self, key: &H256) -> Option<H256> {
@@ -324,6 +329,9 @@ impl Account {
 	/// Return the storage root associated with this account or None if it has been altered via the overlay.
 	pub fn storage_root(&self) -> Option<&H256> { if self.storage_is_clean() {Some(&self.storage_root)} else {None} }
 
+	/// Return the storage root of the last commit, ignoring the overlay.
+	pub fn base_storage_root(&self) -> &H256 { &self.storage_root }
+
 	/// Return the storage overlay.
 	pub fn storage_changes(&self) -> &HashMap<H256, H256> { &self.storage_changes }
 
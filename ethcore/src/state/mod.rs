@@ -29,6 +29,7 @@ use pod_state::{self, PodState};
 use types::state_diff::StateDiff;
 use transaction::SignedTransaction;
 use state_db::StateDB;
+use flat_state::FlatDiff;
 
 use util::*;
 
@@ -405,15 +406,13 @@ impl State {
 			// check the global cache and and cache storage key there if found,
 			// otherwise cache the account localy and cache storage key there.
 			if let Some(result) = self.db.get_cached(address, |acc| acc.map_or(H256::new(), |a| {
-					let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), a.address_hash(address));
-					a.storage_at(account_db.as_hashdb(), key)
+					self.storage_from_db(address, a, key)
 				})) {
 				return result;
 			}
 			if let Some(ref mut acc) = local_account {
 				if let Some(ref account) = acc.account {
-					let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), account.address_hash(address));
-					return account.storage_at(account_db.as_hashdb(), key)
+					return self.storage_from_db(address, account, key)
 				} else {
 					return H256::new()
 				}
@@ -424,15 +423,8 @@ impl State {
 		if !self.db.check_non_null_bloom(address) { return H256::zero() }
 
 		// account is not found in the global cache, get from the DB and insert into local
-		let db = self.factories.trie.readonly(self.db.as_hashdb(), &self.root).expect(SEC_TRIE_DB_UNWRAP_STR);
-		let maybe_acc = match db.get_with(address, Account::from_rlp) {
-			Ok(acc) => acc,
-			Err(e) => panic!("Potential DB corruption encountered: {}", e),
-		};
-		let r = maybe_acc.as_ref().map_or(H256::new(), |a| {
-			let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), a.address_hash(address));
-			a.storage_at(account_db.as_hashdb(), key)
-		});
+		let maybe_acc = self.load_account(address);
+		let r = maybe_acc.as_ref().map_or(H256::new(), |a| self.storage_from_db(address, a, key));
 		self.insert_cache(address, AccountEntry::new_clean(maybe_acc));
 		r
 	}
@@ -535,10 +527,29 @@ impl State {
 		root: &mut H256,
 		accounts: &mut HashMap<Address, AccountEntry>
 	) -> Result<(), Error> {
+		let parent = root.clone();
+		let mut flat_diff = match db.has_flat_state() {
+			true => Some(FlatDiff::default()),
+			false => None,
+		};
+
 		// first, commit the sub trees.
 		for (address, ref mut a) in accounts.iter_mut().filter(|&(_, ref a)| a.is_dirty()) {
 			if let Some(ref mut account) = a.account {
 				let addr_hash = account.address_hash(address);
+				if let Some(ref mut diff) = flat_diff {
+					// a fresh storage replaces whatever was stored under the address before.
+					if account.base_storage_root() == &SHA3_NULL_RLP {
+						diff.note_wiped(addr_hash.clone());
+					}
+					for (k, v) in account.storage_changes() {
+						let value = match v.is_zero() {
+							true => None,
+							false => Some(::rlp::encode(&U256::from(&**v)).to_vec()),
+						};
+						diff.note_storage(addr_hash.clone(), k.sha3(), value);
+					}
+				}
 				{
 					let mut account_db = factories.accountdb.create(db.as_hashdb_mut(), addr_hash);
 					account.commit_storage(&factories.trie, account_db.as_hashdb_mut());
@@ -556,15 +567,28 @@ impl State {
 				a.state = AccountState::Committed;
 				match a.account {
 					Some(ref mut account) => {
-						trie.insert(address, &account.rlp())?;
+						let rlp = account.rlp();
+						trie.insert(address, &rlp)?;
+						if let Some(ref mut diff) = flat_diff {
+							diff.note_account(account.address_hash(address), Some(rlp));
+						}
 					},
 					None => {
 						trie.remove(address)?;
+						if let Some(ref mut diff) = flat_diff {
+							let addr_hash = address.sha3();
+							diff.note_wiped(addr_hash.clone());
+							diff.note_account(addr_hash, None);
+						}
 					},
 				}
 			}
 		}
 
+		if let Some(diff) = flat_diff {
+			db.note_flat_diff(&parent, root, diff);
+		}
+
 		Ok(())
 	}
 
@@ -689,11 +713,7 @@ impl State {
 				if check_bloom && !self.db.check_non_null_bloom(a) { return f(None); }
 
 				// not found in the global cache, get from the DB and insert into local
-				let db = self.factories.trie.readonly(self.db.as_hashdb(), &self.root).expect(SEC_TRIE_DB_UNWRAP_STR);
-				let mut maybe_acc = match db.get_with(a, Account::from_rlp) {
-					Ok(acc) => acc,
-					Err(e) => panic!("Potential DB corruption encountered: {}", e),
-				};
+				let mut maybe_acc = self.load_account(a);
 				if let Some(ref mut account) = maybe_acc.as_mut() {
 					let accountdb = self.factories.accountdb.readonly(self.db.as_hashdb(), account.address_hash(a));
 					Self::update_account_cache(require, account, &self.db, accountdb.as_hashdb());
@@ -705,6 +725,37 @@ impl State {
 		}
 	}
 
+	/// Load account `a` from the flat state if it holds the current state, otherwise from the trie.
+	fn load_account(&self, a: &Address) -> Option<Account> {
+		if let Some(maybe_rlp) = self.db.flat_account(&self.root, &a.sha3()) {
+			return maybe_rlp.map(|rlp| Account::from_rlp(&rlp));
+		}
+		let db = self.factories.trie.readonly(self.db.as_hashdb(), &self.root).expect(SEC_TRIE_DB_UNWRAP_STR);
+		match db.get_with(a, Account::from_rlp) {
+			Ok(acc) => acc,
+			Err(e) => panic!("Potential DB corruption encountered: {}", e),
+		}
+	}
+
+	/// Get storage of account `address` at `key`, consulting the flat state before the storage trie.
+	fn storage_from_db(&self, address: &Address, account: &Account, key: &H256) -> H256 {
+		if let Some(value) = account.cached_storage_at(key) {
+			return value;
+		}
+		// nothing committed to the storage yet.
+		if account.base_storage_root() == &SHA3_NULL_RLP {
+			return H256::new();
+		}
+		let addr_hash = account.address_hash(address);
+		if let Some(maybe_rlp) = self.db.flat_storage(&self.root, &addr_hash, &key.sha3()) {
+			let value: H256 = maybe_rlp.map_or_else(H256::new, |rlp| ::rlp::decode::<U256>(&rlp).into());
+			account.cache_storage(key.clone(), value.clone());
+			return value;
+		}
+		let account_db = self.factories.accountdb.readonly(self.db.as_hashdb(), addr_hash);
+		account.storage_at(account_db.as_hashdb(), key)
+	}
+
 	/// Pull account `a` in our cache from the trie DB. `require_code` requires that the code be cached, too.
 	fn require<'a>(&'a self, a: &Address, require_code: bool) -> RefMut<'a, Account> {
 		self.require_or_from(a, require_code, || Account::new_basic(U256::from(0u8), self.account_start_nonce), |_|{})
@@ -721,11 +772,7 @@ impl State {
 				Some(acc) => self.insert_cache(a, AccountEntry::new_clean_cached(acc)),
 				None => {
 					let maybe_acc = if self.db.check_non_null_bloom(a) {
-						let db = self.factories.trie.readonly(self.db.as_hashdb(), &self.root).expect(SEC_TRIE_DB_UNWRAP_STR);
-						match db.get_with(a, Account::from_rlp) {
-							Ok(acc) => AccountEntry::new_clean(acc),
-							Err(e) => panic!("Potential DB corruption encountered: {}", e),
-						}
+						AccountEntry::new_clean(self.load_account(a))
 					} else {
 						AccountEntry::new_clean(None)
 					};
@@ -22,9 +22,12 @@ use util::hash::{H256};
 use util::hashdb::HashDB;
 use state::Account;
 use header::BlockNumber;
-use util::{Arc, Address, Database, DBTransaction, UtilError, Mutex, Hashable};
+use util::{Arc, Address, Bytes, KeyValueDB, DBTransaction, UtilError, Mutex, Hashable};
 use bloom_journal::{Bloom, BloomJournal};
 use db::COL_ACCOUNT_BLOOM;
+use flat_state::{FlatState, FlatDiff};
+use account_db::AccountDBMut;
+use types::state_node::StateNode;
 use byteorder::{LittleEndian, ByteOrder};
 
 pub const ACCOUNT_BLOOM_SPACE: usize = 1048576;
@@ -59,6 +62,15 @@ struct CacheQueueItem {
 	modified: bool,
 }
 
+/// Flat state changes of the committing block.
+struct PendingFlatDiff {
+	/// State root the changes were made on.
+	parent: H256,
+	/// State root after the changes.
+	root: H256,
+	diff: FlatDiff,
+}
+
 #[derive(Debug)]
 /// Accumulates a list of accounts changed in a block.
 struct BlockChanges {
@@ -107,6 +119,10 @@ pub struct StateDB {
 	commit_hash: Option<H256>,
 	/// Number of the committing block or `None` if not committed yet.
 	commit_number: Option<BlockNumber>,
+	/// Shared flat state or `None` if it is disabled.
+	flat_state: Option<Arc<FlatState>>,
+	/// Local flat state changes, added to the flat state in `journal_under`.
+	flat_pending: Option<PendingFlatDiff>,
 }
 
 impl StateDB {
@@ -134,12 +150,96 @@ impl StateDB {
 			parent_hash: None,
 			commit_hash: None,
 			commit_number: None,
+			flat_state: None,
+			flat_pending: None,
+		}
+	}
+
+	/// Enable the flat state. Generation from the trie is restarted unless the flat state
+	/// is stored for the given state root, which should be the state of the best block.
+	/// Generation itself is left to `FlatState::generate_step`.
+	pub fn enable_flat_state(&mut self, best_root: &H256) {
+		let flat_state = FlatState::open(self.db.backing().clone());
+		if flat_state.disk_root().as_ref() != Some(best_root) {
+			info!("Generating flat state at {}", best_root);
+			flat_state.start_generation(best_root);
+		}
+		self.flat_state = Some(Arc::new(flat_state));
+	}
+
+	/// Returns the shared flat state if it is enabled.
+	pub fn flat_state(&self) -> Option<Arc<FlatState>> {
+		self.flat_state.clone()
+	}
+
+	/// Whether the flat state is enabled.
+	pub fn has_flat_state(&self) -> bool {
+		self.flat_state.is_some()
+	}
+
+	/// Note flat state changes which turn the state `parent` into `root`.
+	/// Changes of consecutive commits of one block are merged.
+	pub fn note_flat_diff(&mut self, parent: &H256, root: &H256, diff: FlatDiff) {
+		if self.flat_state.is_none() {
+			return;
+		}
+		match self.flat_pending {
+			Some(ref mut pending) if pending.root == *parent => {
+				pending.diff.merge(diff);
+				pending.root = *root;
+				return;
+			}
+			_ => {},
+		}
+		self.flat_pending = Some(PendingFlatDiff {
+			parent: *parent,
+			root: *root,
+			diff: diff,
+		});
+	}
+
+	/// Look up an account RLP by address hash in the flat state with given root.
+	/// Returns `None` if the flat state is disabled or does not have the state.
+	pub fn flat_account(&self, root: &H256, addr_hash: &H256) -> Option<Option<Bytes>> {
+		let flat_state = match self.flat_state {
+			Some(ref flat_state) => flat_state,
+			None => return None,
+		};
+		match self.flat_pending {
+			Some(ref pending) if pending.root == *root => match pending.diff.account(addr_hash) {
+				Some(account) => Some(account),
+				None => flat_state.account(&pending.parent, addr_hash),
+			},
+			_ => flat_state.account(root, addr_hash),
+		}
+	}
+
+	/// Look up a storage value RLP by address hash and storage key hash in the flat
+	/// state with given root. Returns `None` if the flat state is disabled or does not have the state.
+	pub fn flat_storage(&self, root: &H256, addr_hash: &H256, key_hash: &H256) -> Option<Option<Bytes>> {
+		let flat_state = match self.flat_state {
+			Some(ref flat_state) => flat_state,
+			None => return None,
+		};
+		match self.flat_pending {
+			Some(ref pending) if pending.root == *root => match pending.diff.storage(addr_hash, key_hash) {
+				Some(value) => Some(value),
+				None => flat_state.storage(&pending.parent, addr_hash, key_hash),
+			},
+			_ => flat_state.storage(root, addr_hash, key_hash),
+		}
+	}
+
+	/// Write flat state diff layers up to the state with given root to disk.
+	pub fn flatten_state(&self, root: &H256) {
+		if let Some(ref flat_state) = self.flat_state {
+			flat_state.flatten(root);
 		}
 	}
 
 	/// Loads accounts bloom from the database
 	/// This bloom is used to handle request for the non-existant account fast
-	pub fn load_bloom(db: &Database) -> Bloom {
+	pub fn load_bloom(db: &KeyValueDB) -> Bloom {
 		let hash_count_entry = db.get(COL_ACCOUNT_BLOOM, ACCOUNT_BLOOM_HASHCOUNT_KEY)
 			.expect("Low-level database error");
 
@@ -191,6 +291,24 @@ impl StateDB {
 		Ok(())
 	}
 
+	/// Write state nodes downloaded by fast sync straight to the backing database
+	/// and note the given account address hashes in the account bloom.
+	pub fn import_nodes(&mut self, batch: &mut DBTransaction, nodes: Vec<StateNode>, accounts: &[H256]) -> Result<(), UtilError> {
+		for node in nodes {
+			match node.owner {
+				Some(owner) => { AccountDBMut::from_hash(self.db.as_hashdb_mut(), owner).insert(&node.data); },
+				None => { self.db.as_hashdb_mut().insert(&node.data); },
+			}
+		}
+		self.db.inject(batch)?;
+
+		let mut bloom = self.account_bloom.lock();
+		for address_hash in accounts {
+			bloom.set(&**address_hash);
+		}
+		Self::commit_bloom(batch, bloom.drain_journal())
+	}
+
 	/// Journal all recent operations under the given era and ID.
 	pub fn journal_under(&mut self, batch: &mut DBTransaction, now: u64, id: &H256) -> Result<u32, UtilError> {
 		{
@@ -198,6 +316,9 @@ impl StateDB {
  			Self::commit_bloom(batch, bloom_lock.drain_journal())?;
  		}
 		let records = self.db.journal_under(batch, now, id)?;
+		if let (Some(ref flat_state), Some(pending)) = (self.flat_state.as_ref(), self.flat_pending.take()) {
+			flat_state.add_layer(pending.parent, pending.root, pending.diff);
+		}
 		self.commit_hash = Some(id.clone());
 		self.commit_number = Some(now);
 		Ok(records)
@@ -327,6 +448,8 @@ impl StateDB {
 			parent_hash: None,
 			commit_hash: None,
 			commit_number: None,
+			flat_state: self.flat_state.clone(),
+			flat_pending: None,
 		}
 	}
 
@@ -342,6 +465,8 @@ impl StateDB {
 			parent_hash: Some(parent.clone()),
 			commit_hash: None,
 			commit_number: None,
+			flat_state: self.flat_state.clone(),
+			flat_pending: None,
 		}
 	}
 
@@ -477,7 +602,7 @@ mod tests {
 		let h2b = H256::random();
 		let h3a = H256::random();
 		let h3b = H256::random();
-		let mut batch = DBTransaction::new(state_db.journal_db().backing());
+		let mut batch = DBTransaction::new();
 
 		// blocks  [ 3a(c) 2a(c) 2b 1b 1a(c) 0 ]
 	    // balance [ 5     5     4  3  2     2 ]
@@ -195,6 +195,17 @@ fn imports_block_sequence() {
 	assert!(!block.into_inner().is_empty());
 }
 
+#[test]
+fn reports_database_stats() {
+	let client_result = generate_dummy_client(6);
+	let client = client_result.reference();
+	let stats = client.database_stats();
+
+	let names: Vec<_> = stats.columns.iter().map(|column| &*column.name).collect();
+	assert_eq!(&names[..4], &["default", "state", "headers", "bodies"]);
+	assert!(stats.columns[2].keys > 0);
+}
+
 #[test]
 fn can_collect_garbage() {
 	let client_result = generate_dummy_client(100);
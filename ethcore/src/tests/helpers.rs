@@ -284,16 +284,13 @@ pub fn get_test_client_with_blocks(blocks: Vec<Bytes>) -> GuardedTempResult<Arc<
 	}
 }
 
-fn new_db(path: &str) -> Arc<Database> {
-	Arc::new(
-		Database::open(&DatabaseConfig::with_columns(::db::NUM_COLUMNS), path)
-		.expect("Opening database for tests should always work.")
-	)
+fn new_db() -> Arc<KeyValueDB> {
+	Arc::new(::util::kvdb::in_memory(::db::NUM_COLUMNS.unwrap_or(0)))
 }
 
 pub fn generate_dummy_blockchain(block_number: u32) -> GuardedTempResult<BlockChain> {
 	let temp = RandomTempPath::new();
-	let db = new_db(temp.as_str());
+	let db = new_db();
 	let bc = BlockChain::new(BlockChainConfig::default(), &create_unverifiable_block(0, H256::zero()), db.clone(), Spec::new_null().engine);
 
 	let mut batch = db.transaction();
@@ -311,7 +308,7 @@ pub fn generate_dummy_blockchain(block_number: u32) -> GuardedTempResult<BlockCh
 
 pub fn generate_dummy_blockchain_with_extra(block_number: u32) -> GuardedTempResult<BlockChain> {
 	let temp = RandomTempPath::new();
-	let db = new_db(temp.as_str());
+	let db = new_db();
 	let bc = BlockChain::new(BlockChainConfig::default(), &create_unverifiable_block(0, H256::zero()), db.clone(), Spec::new_null().engine);
 
 
@@ -330,7 +327,7 @@ pub fn generate_dummy_blockchain_with_extra(block_number: u32) -> GuardedTempRes
 
 pub fn generate_dummy_empty_blockchain() -> GuardedTempResult<BlockChain> {
 	let temp = RandomTempPath::new();
-	let db = new_db(temp.as_str());
+	let db = new_db();
 	let bc = BlockChain::new(BlockChainConfig::default(), &create_unverifiable_block(0, H256::zero()), db.clone(), Spec::new_null().engine);
 
 	GuardedTempResult::<BlockChain> {
@@ -360,7 +357,10 @@ pub fn get_temp_state() -> GuardedTempResult<State> {
 }
 
 pub fn get_temp_state_db_in(path: &Path) -> StateDB {
-	let db = new_db(path.to_str().expect("Only valid utf8 paths for tests."));
+	let db = Arc::new(
+		Database::open(&DatabaseConfig::with_columns(::db::NUM_COLUMNS), path.to_str().expect("Only valid utf8 paths for tests."))
+		.expect("Opening database for tests should always work.")
+	);
 	let journal_db = journaldb::new(db.clone(), journaldb::Algorithm::EarlyMerge, ::db::COL_STATE);
 	StateDB::new(journal_db, 5 * 1024 * 1024)
 }
@@ -42,6 +42,8 @@ pub struct BlockChainInfo {
 	pub first_block_hash: Option<H256>,
 	/// Number of the first block on the best sequence.
 	pub first_block_number: Option<BlockNumber>,
+	/// Earliest block besides genesis with body and receipts available.
+	pub earliest_body_number: BlockNumber,
 }
 
 impl BlockChainInfo {
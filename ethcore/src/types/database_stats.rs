@@ -0,0 +1,54 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Database statistics.
+
+/// Approximate size of a database column.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "ipc", binary)]
+pub struct ColumnStats {
+	/// Name of the column.
+	pub name: String,
+	/// Estimated number of keys.
+	pub keys: u64,
+	/// Size of the table files on disk, in bytes.
+	pub disk_size: u64,
+	/// Size of the in-memory write buffers, in bytes.
+	pub memtable_size: u64,
+	/// Number of buffered writes which are not flushed yet.
+	pub pending_writes: u64,
+}
+
+/// Approximate database statistics.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "ipc", binary)]
+pub struct DatabaseStats {
+	/// Statistics of every column.
+	pub columns: Vec<ColumnStats>,
+	/// Memory used by the journal overlay of the state database, in bytes.
+	pub journal_memory: u64,
+	/// Number of compactions currently running.
+	pub running_compactions: u64,
+	/// Estimated number of bytes compactions have to rewrite.
+	pub pending_compaction_bytes: u64,
+}
+
+impl DatabaseStats {
+	/// Total size of the table files on disk, in bytes.
+	pub fn disk_size(&self) -> u64 {
+		self.columns.iter().map(|column| column.disk_size).sum()
+	}
+}
@@ -0,0 +1,31 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Gas usage and gas price statistics of recent blocks.
+
+use util::U256;
+use header::BlockNumber;
+
+/// Fee history of a range of blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeHistory {
+	/// Number of the first block in the range.
+	pub oldest_block: BlockNumber,
+	/// Ratio of gas used to the gas limit of every block.
+	pub gas_used_ratio: Vec<f64>,
+	/// Requested percentiles of gas prices in every block, weighted by gas used.
+	pub gas_price: Vec<Vec<U256>>,
+}
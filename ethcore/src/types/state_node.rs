@@ -0,0 +1,30 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! State data downloaded by fast sync.
+
+use util::{H256, Bytes};
+
+/// A node of the account trie or of a storage trie, or contract code.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ipc", binary)]
+pub struct StateNode {
+	/// Hash of the address of the account which owns the storage trie or code.
+	/// `None` for account trie nodes.
+	pub owner: Option<H256>,
+	/// Raw node data.
+	pub data: Bytes,
+}
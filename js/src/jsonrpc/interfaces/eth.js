@@ -912,6 +912,25 @@ export default {
     }
   },
 
+  signTypedData: {
+    desc: 'Signs typed structured data (EIP-712) with a given address.',
+    params: [
+      {
+        type: Address,
+        desc: '20 Bytes - address',
+        format: 'inputAddressFormatter'
+      },
+      {
+        type: Object,
+        desc: 'Typed data with `types`, `primaryType`, `domain` and `message` fields'
+      }
+    ],
+    returns: {
+      type: Data,
+      desc: 'Signed data'
+    }
+  },
+
   signTransaction: {
     desc: '?',
     params: [
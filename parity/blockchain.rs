@@ -20,19 +20,25 @@ use std::io::{BufReader, BufRead};
 use std::time::{Instant, Duration};
 use std::thread::sleep;
 use std::sync::Arc;
+use std::path::Path;
 use rustc_serialize::hex::FromHex;
 use io::{PanicHandler, ForwardPanic};
 use util::{ToPretty, Uint, U256, H256, Address, Hashable};
+use util::journaldb::Algorithm;
+use util::kvdb::{Database, DatabaseConfig};
 use rlp::PayloadInfo;
 use ethcore::service::ClientService;
-use ethcore::client::{Mode, DatabaseCompactionProfile, VMType, BlockImportError, BlockChainClient, BlockId};
+use ethcore::client::{Mode, DatabaseCompactionProfile, VMType, BlockImportError, BlockChainClient, BlockId, FreezerConfig};
 use ethcore::error::ImportError;
+use ethcore::db::NUM_COLUMNS;
+use ethcore::snapshot::{convert_database, Progress};
 use ethcore::miner::Miner;
 use ethcore::verification::queue::VerifierSettings;
 use cache::CacheConfig;
 use informant::{Informant, MillisecondDuration};
 use params::{SpecType, Pruning, Switch, tracing_switch_to_bool, fatdb_switch_to_bool};
 use helpers::{to_client_config, execute_upgrades};
+use migration::update_version;
 use dir::Directories;
 use user_defaults::UserDefaults;
 use fdlimit;
@@ -64,6 +70,9 @@ impl FromStr for DataFormat {
 #[derive(Debug, PartialEq)]
 pub enum BlockchainCmd {
 	Kill(KillBlockchain),
+	MigratePruning(MigratePruning),
+	Check(CheckBlockchain),
+	Stats(DatabaseStats),
 	Import(ImportBlockchain),
 	Export(ExportBlockchain),
 	ExportState(ExportState),
@@ -76,6 +85,45 @@ pub struct KillBlockchain {
 	pub pruning: Pruning,
 }
 
+#[derive(Debug, PartialEq)]
+pub struct MigratePruning {
+	pub spec: SpecType,
+	pub dirs: Directories,
+	pub pruning: Pruning,
+	pub to: Algorithm,
+	pub compaction: DatabaseCompactionProfile,
+	pub wal: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CheckBlockchain {
+	pub spec: SpecType,
+	pub cache_config: CacheConfig,
+	pub dirs: Directories,
+	pub pruning: Pruning,
+	pub pruning_history: u64,
+	pub pruning_memory: usize,
+	pub compaction: DatabaseCompactionProfile,
+	pub wal: bool,
+	pub tracing: Switch,
+	pub fat_db: Switch,
+	pub repair: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DatabaseStats {
+	pub spec: SpecType,
+	pub cache_config: CacheConfig,
+	pub dirs: Directories,
+	pub pruning: Pruning,
+	pub pruning_history: u64,
+	pub pruning_memory: usize,
+	pub compaction: DatabaseCompactionProfile,
+	pub wal: bool,
+	pub tracing: Switch,
+	pub fat_db: Switch,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ImportBlockchain {
 	pub spec: SpecType,
@@ -139,6 +187,9 @@ pub struct ExportState {
 pub fn execute(cmd: BlockchainCmd) -> Result<(), String> {
 	match cmd {
 		BlockchainCmd::Kill(kill_cmd) => kill_db(kill_cmd),
+		BlockchainCmd::MigratePruning(migrate_cmd) => migrate_pruning(migrate_cmd),
+		BlockchainCmd::Check(check_cmd) => execute_check(check_cmd),
+		BlockchainCmd::Stats(stats_cmd) => execute_stats(stats_cmd),
 		BlockchainCmd::Import(import_cmd) => execute_import(import_cmd),
 		BlockchainCmd::Export(export_cmd) => execute_export(export_cmd),
 		BlockchainCmd::ExportState(export_cmd) => execute_export_state(export_cmd),
@@ -182,7 +233,7 @@ fn execute_import(cmd: ImportBlockchain) -> Result<(), String> {
 	let snapshot_path = db_dirs.snapshot_path();
 
 	// execute upgrades
-	execute_upgrades(&cmd.dirs.base, &db_dirs, algorithm, cmd.compaction.compaction_profile(db_dirs.db_root_path().as_path()))?;
+	execute_upgrades(&cmd.dirs.base, &db_dirs, algorithm, cmd.compaction.compaction_profile(db_dirs.db_root_path().as_path()), None)?;
 
 	// create dirs used by parity
 	cmd.dirs.create_dirs(false, false)?;
@@ -200,7 +251,11 @@ fn execute_import(cmd: ImportBlockchain) -> Result<(), String> {
 		algorithm,
 		cmd.pruning_history,
 		cmd.pruning_memory,
-		cmd.check_seal
+		cmd.check_seal,
+		FreezerConfig {
+			path: db_dirs.freezer_path(algorithm),
+			depth: None,
+		},
 	);
 
 	client_config.queue.verifier_settings = cmd.verifier_settings;
@@ -353,7 +408,7 @@ fn start_client(
 	let snapshot_path = db_dirs.snapshot_path();
 
 	// execute upgrades
-	execute_upgrades(&dirs.base, &db_dirs, algorithm, compaction.compaction_profile(db_dirs.db_root_path().as_path()))?;
+	execute_upgrades(&dirs.base, &db_dirs, algorithm, compaction.compaction_profile(db_dirs.db_root_path().as_path()), None)?;
 
 	// create dirs used by parity
 	dirs.create_dirs(false, false)?;
@@ -372,6 +427,10 @@ fn start_client(
 		pruning_history,
 		pruning_memory,
 		true,
+		FreezerConfig {
+			path: db_dirs.freezer_path(algorithm),
+			depth: None,
+		},
 	);
 
 	let service = ClientService::start(
@@ -430,6 +489,79 @@ fn execute_export(cmd: ExportBlockchain) -> Result<(), String> {
 	Ok(())
 }
 
+fn execute_check(cmd: CheckBlockchain) -> Result<(), String> {
+	let service = start_client(
+		cmd.dirs,
+		cmd.spec,
+		cmd.pruning,
+		cmd.pruning_history,
+		cmd.pruning_memory,
+		cmd.tracing,
+		cmd.fat_db,
+		cmd.compaction,
+		cmd.wal,
+		cmd.cache_config
+	)?;
+	let panic_handler = PanicHandler::new_in_arc();
+
+	panic_handler.forward_from(&service);
+	let client = service.client();
+
+	info!("Checking database integrity");
+	let report = client.check_integrity();
+	for issue in &report.issues {
+		warn!("{}", issue);
+	}
+	info!("Checked {} blocks and {} accounts, found {} issues", report.blocks, report.accounts, report.issues.len());
+
+	if !cmd.repair {
+		if report.is_ok() {
+			return Ok(());
+		}
+		return Err("Database is inconsistent. Run `parity db check --repair` to rewind to the last consistent block.".into());
+	}
+
+	match client.repair(&report) {
+		Some(number) => {
+			info!("Best block is #{}; blocks above it will be synced again.", number);
+			Ok(())
+		},
+		None => Err("No block with complete chain data and state is left. Run `parity db kill` and sync from scratch.".into()),
+	}
+}
+
+fn execute_stats(cmd: DatabaseStats) -> Result<(), String> {
+	let service = start_client(
+		cmd.dirs,
+		cmd.spec,
+		cmd.pruning,
+		cmd.pruning_history,
+		cmd.pruning_memory,
+		cmd.tracing,
+		cmd.fat_db,
+		cmd.compaction,
+		cmd.wal,
+		cmd.cache_config
+	)?;
+	let panic_handler = PanicHandler::new_in_arc();
+
+	panic_handler.forward_from(&service);
+	let client = service.client();
+	let stats = client.database_stats();
+	let format_bytes = |bytes: u64| ::informant::format_bytes(bytes as usize);
+
+	println!("{:<16} {:>14} {:>12} {:>12} {:>8}", "Column", "Keys", "Disk", "Memtables", "Pending");
+	for column in &stats.columns {
+		println!("{:<16} {:>14} {:>12} {:>12} {:>8}", column.name, column.keys, format_bytes(column.disk_size), format_bytes(column.memtable_size), column.pending_writes);
+	}
+	println!("");
+	println!("Total on disk:           {}", format_bytes(stats.disk_size()));
+	println!("Journal overlay memory:  {}", format_bytes(stats.journal_memory));
+	println!("Running compactions:     {}", stats.running_compactions);
+	println!("Pending compaction:      {}", format_bytes(stats.pending_compaction_bytes));
+	Ok(())
+}
+
 fn execute_export_state(cmd: ExportState) -> Result<(), String> {
 	// Setup panic handler
 	let service = start_client(
@@ -535,6 +667,89 @@ pub fn kill_db(cmd: KillBlockchain) -> Result<(), String> {
 	Ok(())
 }
 
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+	fs::create_dir_all(to)?;
+	for entry in fs::read_dir(from)? {
+		let entry = entry?;
+		fs::copy(entry.path(), to.join(entry.file_name()))?;
+	}
+	Ok(())
+}
+
+pub fn migrate_pruning(cmd: MigratePruning) -> Result<(), String> {
+	let spec = cmd.spec.spec()?;
+	let genesis_hash = spec.genesis_header().hash();
+	let db_dirs = cmd.dirs.database(genesis_hash, None, spec.data_dir);
+	let user_defaults_path = db_dirs.user_defaults_path();
+	let mut user_defaults = UserDefaults::load(&user_defaults_path)?;
+	let from = cmd.pruning.to_algorithm(&user_defaults);
+	let to = cmd.to;
+
+	if from == to {
+		return Err(format!("Database already uses {} pruning.", to));
+	}
+	if user_defaults.fat_db {
+		return Err("Databases with --fat-db enabled cannot be migrated to another pruning method.".into());
+	}
+
+	fdlimit::raise_fd_limit();
+
+	let compaction = cmd.compaction.compaction_profile(db_dirs.db_root_path().as_path());
+	execute_upgrades(&cmd.dirs.base, &db_dirs, from, compaction, None)?;
+
+	let source_path = db_dirs.client_path(from);
+	let dest_path = db_dirs.client_path(to);
+	if !source_path.exists() {
+		return Err(format!("No database found at {}.", source_path.display()));
+	}
+	if db_dirs.db_path(to).exists() {
+		return Err(format!("A database for {} pruning already exists at {}. Remove it with `parity db kill --pruning {}` first.", to, db_dirs.db_path(to).display(), to));
+	}
+
+	let mut db_config = DatabaseConfig::with_columns(NUM_COLUMNS);
+	db_config.compaction = compaction;
+	db_config.wal = cmd.wal;
+
+	let source = Database::open(&db_config, &source_path.to_string_lossy()).map_err(|e| format!("Error opening database: {}", e))?;
+	let dest = Database::open(&db_config, &dest_path.to_string_lossy()).map_err(|e| format!("Error creating database: {}", e))?;
+
+	info!("Migrating database from {} to {} pruning", from, to);
+	let progress = Arc::new(Progress::default());
+	let p = progress.clone();
+	let informant_handle = ::std::thread::spawn(move || {
+		let mut last_accounts = 0;
+		while !p.done() {
+			::std::thread::sleep(Duration::from_secs(5));
+			if p.accounts() != last_accounts {
+				last_accounts = p.accounts();
+				info!("Migrating state: {} accounts {}", p.accounts(), ::informant::format_bytes(p.size()));
+			}
+		}
+	});
+
+	let result = convert_database(Arc::new(source), from, Arc::new(dest), to, &*progress)
+		.map_err(|e| format!("Error migrating database: {}", e))
+		.and_then(|_| {
+			let freezer_path = db_dirs.freezer_path(from);
+			match freezer_path.exists() {
+				true => copy_dir(&freezer_path, &db_dirs.freezer_path(to)).map_err(|e| format!("Error copying ancient blocks: {}", e)),
+				false => Ok(()),
+			}
+		})
+		.and_then(|_| update_version(&db_dirs.db_path(to)).map_err(|e| format!("{}", e)));
+
+	if let Err(e) = result {
+		let _ = fs::remove_dir_all(db_dirs.db_path(to));
+		return Err(e);
+	}
+	informant_handle.join().map_err(|_| "failed to join logger thread")?;
+
+	user_defaults.pruning = to;
+	user_defaults.save(&user_defaults_path)?;
+	info!("Database migrated. The {} database at {} can now be removed.", from, db_dirs.db_path(from).display());
+	Ok(())
+}
+
 #[cfg(test)]
 mod test {
 	use super::DataFormat;
@@ -16,6 +16,7 @@
 
 #[macro_use]
 mod usage;
+use std::collections::BTreeMap;
 use dir;
 
 usage! {
@@ -41,6 +42,9 @@ usage! {
 		cmd_tools: bool,
 		cmd_hash: bool,
 		cmd_kill: bool,
+		cmd_migrate_pruning: bool,
+		cmd_check: bool,
+		cmd_stats: bool,
 		cmd_db: bool,
 
 		// Arguments
@@ -112,6 +116,8 @@ usage! {
 			or |c: &Config| otry!(c.ui).interface.clone(),
 		flag_ui_path: String = "$BASE/signer",
 			or |c: &Config| otry!(c.ui).path.clone(),
+		flag_ui_allow: Option<String> = None,
+			or |c: &Config| otry!(c.rpc_access).ui.clone().map(|vec| Some(vec.join(","))),
 		// NOTE [todr] For security reasons don't put this to config files
 		flag_ui_no_validation: bool = false, or |_| None,
 
@@ -128,6 +134,10 @@ usage! {
 			or |c: &Config| otry!(c.network).max_pending_peers.clone(),
 		flag_snapshot_peers: u16 = 0u16,
 			or |c: &Config| otry!(c.network).snapshot_peers.clone(),
+		flag_max_upload_rate: Option<u32> = None,
+			or |c: &Config| otry!(c.network).max_upload_rate.clone().map(Some),
+		flag_max_download_rate: Option<u32> = None,
+			or |c: &Config| otry!(c.network).max_download_rate.clone().map(Some),
 		flag_nat: String = "any",
 			or |c: &Config| otry!(c.network).nat.clone(),
 		flag_allow_ips: String = "all",
@@ -144,7 +154,10 @@ usage! {
 			or |c: &Config| otry!(c.network).reserved_peers.clone().map(Some),
 		flag_reserved_only: bool = false,
 			or |c: &Config| otry!(c.network).reserved_only.clone(),
+		flag_peer_groups: Option<String> = None,
+			or |c: &Config| otry!(c.network).peer_groups.clone().map(Some),
 		flag_no_ancient_blocks: bool = false, or |_| None,
+		flag_no_fast_sync: bool = false, or |_| None,
 
 		// -- API and Console Options
 		// RPC
@@ -160,6 +173,20 @@ usage! {
 			or |c: &Config| otry!(c.rpc).apis.clone().map(|vec| vec.join(",")),
 		flag_jsonrpc_hosts: String = "none",
 			or |c: &Config| otry!(c.rpc).hosts.clone().map(|vec| vec.join(",")),
+		flag_jsonrpc_allow: Option<String> = None,
+			or |c: &Config| otry!(c.rpc_access).anonymous.clone().map(|vec| Some(vec.join(","))),
+		flag_jsonrpc_tokens: Option<String> = None,
+			or |c: &Config| otry!(c.rpc_access).tokens.as_ref().map(|tokens| Some(tokens.iter()
+				.map(|(token, methods)| format!("{}={}", token, methods.join(",")))
+				.collect::<Vec<_>>()
+				.join(";")
+			)),
+		flag_jsonrpc_rate_limit: u32 = 0u32,
+			or |c: &Config| otry!(c.rpc).rate_limit.clone(),
+		flag_jsonrpc_expensive_methods: String = "eth_getLogs,trace_filter,eth_estimateGas,parity_listAccounts",
+			or |c: &Config| otry!(c.rpc).expensive_methods.clone().map(|vec| vec.join(",")),
+		flag_jsonrpc_expensive_budget: u64 = 0u64,
+			or |c: &Config| otry!(c.rpc).expensive_budget.clone(),
 
 		// IPC
 		flag_no_ipc: bool = false,
@@ -168,6 +195,8 @@ usage! {
 			or |c: &Config| otry!(c.ipc).path.clone(),
 		flag_ipc_apis: String = "web3,eth,net,parity,parity_accounts,traces,rpc",
 			or |c: &Config| otry!(c.ipc).apis.clone().map(|vec| vec.join(",")),
+		flag_ipc_allow: Option<String> = None,
+			or |c: &Config| otry!(c.rpc_access).ipc.clone().map(|vec| Some(vec.join(","))),
 
 		// DAPPS
 		flag_no_dapps: bool = false,
@@ -210,6 +239,12 @@ usage! {
 			or |c: &Config| otry!(c.mining).usd_per_eth.clone(),
 		flag_price_update_period: String = "hourly",
 			or |c: &Config| otry!(c.mining).price_update_period.clone(),
+		flag_gas_price_blocks: u64 = 20u64,
+			or |c: &Config| otry!(c.mining).gas_price_blocks.clone(),
+		flag_gas_price_percentile: usize = 60usize,
+			or |c: &Config| otry!(c.mining).gas_price_percentile.clone(),
+		flag_gas_price_backlog: usize = 200usize,
+			or |c: &Config| otry!(c.mining).gas_price_backlog.clone(),
 		flag_gas_floor_target: String = "4700000",
 			or |c: &Config| otry!(c.mining).gas_floor_target.clone(),
 		flag_gas_cap: String = "6283184",
@@ -240,6 +275,18 @@ usage! {
 			or |c: &Config| otry!(c.footprint).pruning_history.clone(),
 		flag_pruning_memory: usize = 150usize,
 			or |c: &Config| otry!(c.footprint).pruning_memory.clone(),
+		flag_freezer_depth: Option<u64> = None,
+			or |c: &Config| otry!(c.footprint).freezer_depth.clone().map(Some),
+		flag_history_retention: Option<u64> = None,
+			or |c: &Config| otry!(c.footprint).history_retention.clone().map(Some),
+		flag_flat_state: bool = false,
+			or |c: &Config| otry!(c.footprint).flat_state.clone(),
+		flag_address_index: bool = false,
+			or |c: &Config| otry!(c.footprint).address_index.clone(),
+		flag_address_index_traces: bool = false,
+			or |c: &Config| otry!(c.footprint).address_index_traces.clone(),
+		flag_log_index: bool = false,
+			or |c: &Config| otry!(c.footprint).log_index.clone(),
 		flag_cache_size_db: u32 = 64u32,
 			or |c: &Config| otry!(c.footprint).cache_size_db.clone(),
 		flag_cache_size_blocks: u32 = 8u32,
@@ -271,6 +318,9 @@ usage! {
 		flag_min_balance: Option<String> = None, or |_| None,
 		flag_max_balance: Option<String> = None, or |_| None,
 
+		// -- Database Check Options
+		flag_repair: bool = false, or |_| None,
+
 		// -- Snapshot Optons
 		flag_at: String = "latest", or |_| None,
 		flag_no_periodic_snapshot: bool = false,
@@ -305,6 +355,7 @@ struct Config {
 	network: Option<Network>,
 	rpc: Option<Rpc>,
 	ipc: Option<Ipc>,
+	rpc_access: Option<RpcAccess>,
 	dapps: Option<Dapps>,
 	mining: Option<Mining>,
 	footprint: Option<Footprint>,
@@ -354,6 +405,8 @@ struct Network {
 	max_peers: Option<u16>,
 	snapshot_peers: Option<u16>,
 	max_pending_peers: Option<u16>,
+	max_upload_rate: Option<u32>,
+	max_download_rate: Option<u32>,
 	nat: Option<String>,
 	allow_ips: Option<String>,
 	id: Option<u64>,
@@ -362,6 +415,7 @@ struct Network {
 	node_key: Option<String>,
 	reserved_peers: Option<String>,
 	reserved_only: Option<bool>,
+	peer_groups: Option<String>,
 }
 
 #[derive(Default, Debug, PartialEq, RustcDecodable)]
@@ -372,6 +426,9 @@ struct Rpc {
 	cors: Option<String>,
 	apis: Option<Vec<String>>,
 	hosts: Option<Vec<String>>,
+	rate_limit: Option<u32>,
+	expensive_methods: Option<Vec<String>>,
+	expensive_budget: Option<u64>,
 }
 
 #[derive(Default, Debug, PartialEq, RustcDecodable)]
@@ -381,6 +438,14 @@ struct Ipc {
 	apis: Option<Vec<String>>,
 }
 
+#[derive(Default, Debug, PartialEq, RustcDecodable)]
+struct RpcAccess {
+	anonymous: Option<Vec<String>>,
+	ipc: Option<Vec<String>>,
+	ui: Option<Vec<String>>,
+	tokens: Option<BTreeMap<String, Vec<String>>>,
+}
+
 #[derive(Default, Debug, PartialEq, RustcDecodable)]
 struct Dapps {
 	disable: Option<bool>,
@@ -406,6 +471,9 @@ struct Mining {
 	usd_per_tx: Option<String>,
 	usd_per_eth: Option<String>,
 	price_update_period: Option<String>,
+	gas_price_blocks: Option<u64>,
+	gas_price_percentile: Option<usize>,
+	gas_price_backlog: Option<usize>,
 	gas_floor_target: Option<String>,
 	gas_cap: Option<String>,
 	extra_data: Option<String>,
@@ -424,6 +492,12 @@ struct Footprint {
 	pruning: Option<String>,
 	pruning_history: Option<u64>,
 	pruning_memory: Option<usize>,
+	freezer_depth: Option<u64>,
+	history_retention: Option<u64>,
+	flat_state: Option<bool>,
+	address_index: Option<bool>,
+	address_index_traces: Option<bool>,
+	log_index: Option<bool>,
 	fast_and_loose: Option<bool>,
 	cache_size: Option<u32>,
 	cache_size_db: Option<u32>,
@@ -537,6 +611,9 @@ mod tests {
 			cmd_hash: false,
 			cmd_db: false,
 			cmd_kill: false,
+			cmd_migrate_pruning: false,
+			cmd_check: false,
+			cmd_stats: false,
 
 			// Arguments
 			arg_pid_file: "".into(),
@@ -568,6 +645,7 @@ mod tests {
 			flag_ui_port: 8180u16,
 			flag_ui_interface: "127.0.0.1".into(),
 			flag_ui_path: "$HOME/.parity/signer".into(),
+			flag_ui_allow: Some("signer_*,parity_*".into()),
 			flag_ui_no_validation: false,
 
 			// -- Networking Options
@@ -577,6 +655,8 @@ mod tests {
 			flag_max_peers: 50u16,
 			flag_max_pending_peers: 64u16,
 			flag_snapshot_peers: 0u16,
+			flag_max_upload_rate: None,
+			flag_max_download_rate: None,
 			flag_allow_ips: "all".into(),
 			flag_nat: "any".into(),
 			flag_network_id: Some(1),
@@ -585,7 +665,9 @@ mod tests {
 			flag_node_key: None,
 			flag_reserved_peers: Some("./path_to_file".into()),
 			flag_reserved_only: false,
+			flag_peer_groups: None,
 			flag_no_ancient_blocks: false,
+			flag_no_fast_sync: false,
 
 			// -- API and Console Options
 			// RPC
@@ -595,11 +677,17 @@ mod tests {
 			flag_jsonrpc_cors: Some("null".into()),
 			flag_jsonrpc_apis: "web3,eth,net,parity,traces,rpc".into(),
 			flag_jsonrpc_hosts: "none".into(),
+			flag_jsonrpc_allow: Some("eth_*,net_*,web3_*".into()),
+			flag_jsonrpc_tokens: Some("secret_token=*".into()),
+			flag_jsonrpc_rate_limit: 600u32,
+			flag_jsonrpc_expensive_methods: "eth_getLogs,trace_filter".into(),
+			flag_jsonrpc_expensive_budget: 5000u64,
 
 			// IPC
 			flag_no_ipc: false,
 			flag_ipc_path: "$HOME/.parity/jsonrpc.ipc".into(),
 			flag_ipc_apis: "web3,eth,net,parity,parity_accounts,personal,traces,rpc".into(),
+			flag_ipc_allow: Some("*".into()),
 
 			// DAPPS
 			flag_no_dapps: false,
@@ -623,6 +711,9 @@ mod tests {
 			flag_usd_per_tx: "0.0025".into(),
 			flag_usd_per_eth: "auto".into(),
 			flag_price_update_period: "hourly".into(),
+			flag_gas_price_blocks: 20u64,
+			flag_gas_price_percentile: 60usize,
+			flag_gas_price_backlog: 200usize,
 			flag_gas_floor_target: "4700000".into(),
 			flag_gas_cap: "6283184".into(),
 			flag_extra_data: Some("Parity".into()),
@@ -639,6 +730,12 @@ mod tests {
 			flag_pruning: "auto".into(),
 			flag_pruning_history: 1200u64,
 			flag_pruning_memory: 500usize,
+			flag_freezer_depth: Some(90000u64),
+			flag_history_retention: None,
+			flag_flat_state: false,
+			flag_address_index: false,
+			flag_address_index_traces: false,
+			flag_log_index: false,
 			flag_cache_size_db: 64u32,
 			flag_cache_size_blocks: 8u32,
 			flag_cache_size_queue: 50u32,
@@ -660,6 +757,9 @@ mod tests {
 			flag_min_balance: None,
 			flag_max_balance: None,
 
+			// -- Database Check Options
+			flag_repair: false,
+
 			// -- Snapshot Optons
 			flag_at: "latest".into(),
 			flag_no_periodic_snapshot: false,
@@ -757,6 +857,8 @@ mod tests {
 				max_peers: Some(20),
 				max_pending_peers: Some(30),
 				snapshot_peers: Some(40),
+				max_upload_rate: Some(512),
+				max_download_rate: None,
 				allow_ips: Some("public".into()),
 				nat: Some("any".into()),
 				id: None,
@@ -765,6 +867,7 @@ mod tests {
 				node_key: None,
 				reserved_peers: Some("./path/to/reserved_peers".into()),
 				reserved_only: Some(true),
+				peer_groups: None,
 			}),
 			rpc: Some(Rpc {
 				disable: Some(true),
@@ -773,12 +876,21 @@ mod tests {
 				cors: None,
 				apis: None,
 				hosts: None,
+				rate_limit: Some(120),
+				expensive_methods: None,
+				expensive_budget: None,
 			}),
 			ipc: Some(Ipc {
 				disable: None,
 				path: None,
 				apis: Some(vec!["rpc".into(), "eth".into()]),
 			}),
+			rpc_access: Some(RpcAccess {
+				anonymous: Some(vec!["eth_*".into(), "net_version".into()]),
+				ipc: None,
+				ui: None,
+				tokens: Some(vec![("secret_token".to_owned(), vec!["*".to_owned()])].into_iter().collect()),
+			}),
 			dapps: Some(Dapps {
 				disable: None,
 				port: Some(8080),
@@ -799,6 +911,9 @@ mod tests {
 				usd_per_tx: None,
 				usd_per_eth: None,
 				price_update_period: Some("hourly".into()),
+				gas_price_blocks: None,
+				gas_price_percentile: Some(50),
+				gas_price_backlog: None,
 				gas_floor_target: None,
 				gas_cap: None,
 				tx_queue_size: Some(1024),
@@ -817,6 +932,12 @@ mod tests {
 				pruning: Some("fast".into()),
 				pruning_history: Some(64),
 				pruning_memory: None,
+				freezer_depth: None,
+				history_retention: None,
+				flat_state: None,
+				address_index: None,
+				address_index_traces: None,
+				log_index: None,
 				fast_and_loose: None,
 				cache_size: None,
 				cache_size_db: Some(128),
@@ -22,17 +22,17 @@ use std::cmp::max;
 use cli::{Args, ArgsError};
 use util::{Hashable, U256, Uint, Bytes, version_data, Address};
 use util::log::Colour;
-use ethsync::{NetworkConfiguration, is_valid_node_url, AllowIP};
+use ethsync::{NetworkConfiguration, is_valid_node_url, AllowIP, NatType, PeerGroup};
 use ethcore::ethstore::ethkey::Secret;
 use ethcore::client::{VMType};
-use ethcore::miner::{MinerOptions, Banning};
+use ethcore::miner::{MinerOptions, Banning, GasPriceOracleOptions};
 use ethcore::verification::queue::VerifierSettings;
 
 use rpc::{IpcConfiguration, HttpConfiguration};
-use ethcore_rpc::NetworkSettings;
+use ethcore_rpc::{NetworkSettings, AccessPolicy, Principal, RateLimits};
 use cache::CacheConfig;
 use helpers::{to_duration, to_mode, to_block_id, to_u256, to_pending_set, to_price, replace_home, replace_home_for_db,
-geth_ipc_path, parity_ipc_path, to_bootnodes, to_addresses, to_address, to_gas_limit, to_queue_strategy};
+geth_ipc_path, parity_ipc_path, to_bootnodes, to_peer_groups, to_addresses, to_address, to_gas_limit, to_queue_strategy};
 use params::{ResealPolicy, AccountsConfig, GasPricerConfig, MinerExtras};
 use ethcore_logger::Config as LogConfig;
 use dir::{self, Directories, default_hypervisor_path, default_local_path, default_data_path};
@@ -40,7 +40,7 @@ use dapps::Configuration as DappsConfiguration;
 use signer::{Configuration as SignerConfiguration};
 use updater::{UpdatePolicy, UpdateFilter, ReleaseTrack};
 use run::RunCmd;
-use blockchain::{BlockchainCmd, ImportBlockchain, ExportBlockchain, KillBlockchain, ExportState, DataFormat};
+use blockchain::{BlockchainCmd, ImportBlockchain, ExportBlockchain, KillBlockchain, MigratePruning, CheckBlockchain, DatabaseStats, ExportState, DataFormat};
 use presale::ImportWallet;
 use account::{AccountCmd, NewAccount, ListAccounts, ImportAccounts, ImportFromGethAccounts};
 use snapshot::{self, SnapshotCommand};
@@ -161,6 +161,44 @@ impl Configuration {
 				dirs: dirs,
 				pruning: pruning,
 			}))
+		} else if self.args.cmd_db && self.args.cmd_migrate_pruning {
+			let to = self.args.flag_to.parse()
+				.map_err(|_| format!("Invalid pruning method given with --to: {}. Expected archive, fast, light or basic.", self.args.flag_to))?;
+			Cmd::Blockchain(BlockchainCmd::MigratePruning(MigratePruning {
+				spec: spec,
+				dirs: dirs,
+				pruning: pruning,
+				to: to,
+				compaction: compaction,
+				wal: wal,
+			}))
+		} else if self.args.cmd_db && self.args.cmd_check {
+			Cmd::Blockchain(BlockchainCmd::Check(CheckBlockchain {
+				spec: spec,
+				cache_config: cache_config,
+				dirs: dirs,
+				pruning: pruning,
+				pruning_history: pruning_history,
+				pruning_memory: self.args.flag_pruning_memory,
+				compaction: compaction,
+				wal: wal,
+				tracing: tracing,
+				fat_db: fat_db,
+				repair: self.args.flag_repair,
+			}))
+		} else if self.args.cmd_db && self.args.cmd_stats {
+			Cmd::Blockchain(BlockchainCmd::Stats(DatabaseStats {
+				spec: spec,
+				cache_config: cache_config,
+				dirs: dirs,
+				pruning: pruning,
+				pruning_history: pruning_history,
+				pruning_memory: self.args.flag_pruning_memory,
+				compaction: compaction,
+				wal: wal,
+				tracing: tracing,
+				fat_db: fat_db,
+			}))
 		} else if self.args.cmd_account {
 			let account_cmd = if self.args.cmd_new {
 				let new_acc = NewAccount {
@@ -319,6 +357,12 @@ impl Configuration {
 				pruning: pruning,
 				pruning_history: pruning_history,
 				pruning_memory: self.args.flag_pruning_memory,
+				freezer_depth: self.args.flag_freezer_depth,
+				history_retention: self.history_retention()?,
+				flat_state: self.args.flag_flat_state,
+				address_index: self.args.flag_address_index,
+				address_index_traces: self.args.flag_address_index_traces,
+				log_index: self.args.flag_log_index,
 				daemon: daemon,
 				logger_config: logger_config.clone(),
 				miner_options: miner_options,
@@ -328,6 +372,7 @@ impl Configuration {
 				network_id: network_id,
 				acc_conf: self.accounts_config()?,
 				gas_pricer: self.gas_pricer_config()?,
+				gas_price_oracle: self.gas_price_oracle(),
 				miner_extras: self.miner_extras()?,
 				update_policy: update_policy,
 				mode: mode,
@@ -349,7 +394,10 @@ impl Configuration {
 				no_periodic_snapshot: self.args.flag_no_periodic_snapshot,
 				check_seal: !self.args.flag_no_seal_check,
 				download_old_blocks: !self.args.flag_no_ancient_blocks,
+				fast_sync: !self.args.flag_no_fast_sync,
 				verifier_settings: verifier_settings,
+				access_policy: self.access_policy()?,
+				rate_limits: self.rate_limits(),
 			};
 			Cmd::Run(run_cmd)
 		};
@@ -491,12 +539,20 @@ impl Configuration {
 					ban_duration: Duration::from_secs(self.args.flag_tx_queue_ban_time as u64),
 				},
 				None => Banning::Disabled,
-			}
+			},
 		};
 
 		Ok(options)
 	}
 
+	fn gas_price_oracle(&self) -> GasPriceOracleOptions {
+		GasPriceOracleOptions {
+			blocks: self.args.flag_gas_price_blocks,
+			percentile: self.args.flag_gas_price_percentile as f64,
+			backlog_threshold: self.args.flag_gas_price_backlog,
+		}
+	}
+
 	fn signer_config(&self) -> SignerConfiguration {
 		SignerConfiguration {
 			enabled: self.ui_enabled(),
@@ -574,6 +630,24 @@ impl Configuration {
 		Ok(GasPricerConfig::Fixed(wei_per_gas))
 	}
 
+	fn history_retention(&self) -> Result<Option<u64>, String> {
+		// reorganisations may reach the pruning history and periodic snapshots need all
+		// blocks they include, up to the time they are taken.
+		let snapshot_window = match self.args.flag_no_periodic_snapshot {
+			true => 0,
+			false => ::run::SNAPSHOT_PERIOD + ::run::SNAPSHOT_HISTORY + ::ethcore::snapshot::SNAPSHOT_BLOCKS,
+		};
+		let min_retention = self.args.flag_pruning_history + snapshot_window;
+
+		match (self.args.flag_history_retention, self.args.flag_freezer_depth) {
+			(Some(_), Some(_)) => Err("`--history-retention` cannot be used together with `--freezer-depth`.".into()),
+			(Some(retention), _) if retention < min_retention => Err(format!(
+				"`--history-retention` must be at least {} blocks to cover reorganisations and periodic snapshots.", min_retention
+			)),
+			(retention, _) => Ok(retention),
+		}
+	}
+
 	fn extra_data(&self) -> Result<Bytes, String> {
 		match self.args.flag_extradata.as_ref().or(self.args.flag_extra_data.as_ref()) {
 			Some(x) if x.len() <= 32 => Ok(x.as_bytes().to_owned()),
@@ -600,6 +674,20 @@ impl Configuration {
 		}
 	}
 
+	fn init_peer_groups(&self) -> Result<Vec<PeerGroup>, String> {
+		use std::fs::File;
+
+		match self.args.flag_peer_groups {
+			Some(ref path) => {
+				let mut buffer = String::new();
+				let mut groups_file = File::open(path).map_err(|e| format!("Error opening peer groups file: {}", e))?;
+				groups_file.read_to_string(&mut buffer).map_err(|_| "Error reading peer groups file")?;
+				to_peer_groups(&buffer)
+			},
+			None => Ok(Vec::new())
+		}
+	}
+
 	fn net_addresses(&self) -> Result<(Option<SocketAddr>, Option<SocketAddr>), String> {
 		let port = self.args.flag_port;
 		let listen_address = Some(SocketAddr::new("0.0.0.0".parse().unwrap(), port));
@@ -615,7 +703,8 @@ impl Configuration {
 
 	fn net_config(&self) -> Result<NetworkConfiguration, String> {
 		let mut ret = NetworkConfiguration::new();
-		ret.nat_enabled = self.args.flag_nat == "any" || self.args.flag_nat == "upnp";
+		ret.nat_enabled = NatType::parse(&self.args.flag_nat).is_some();
+		ret.nat_type = NatType::parse(&self.args.flag_nat).unwrap_or(NatType::Any);
 		ret.boot_nodes = to_bootnodes(&self.args.flag_bootnodes)?;
 		let (listen, public) = self.net_addresses()?;
 		ret.listen_address = listen.map(|l| format!("{}", l));
@@ -633,11 +722,14 @@ impl Configuration {
 		ret.snapshot_peers = self.snapshot_peers();
 		ret.allow_ips = self.allow_ips()?;
 		ret.max_pending_peers = self.max_pending_peers();
+		ret.max_upload_rate = self.args.flag_max_upload_rate.map(|kbps| kbps as u64 * 1024);
+		ret.max_download_rate = self.args.flag_max_download_rate.map(|kbps| kbps as u64 * 1024);
 		let mut net_path = PathBuf::from(self.directories().base);
 		net_path.push("network");
 		ret.config_path = Some(net_path.to_str().unwrap().to_owned());
 		ret.reserved_nodes = self.init_reserved_nodes()?;
 		ret.allow_non_reserved = !self.args.flag_reserved_only;
+		ret.peer_groups = self.init_peer_groups()?;
 		Ok(ret)
 	}
 
@@ -713,6 +805,49 @@ impl Configuration {
 		Ok(conf)
 	}
 
+	fn access_policy(&self) -> Result<AccessPolicy, String> {
+		fn methods(list: &str) -> Vec<&str> {
+			list.split(',').map(str::trim).filter(|m| !m.is_empty()).collect()
+		}
+
+		let mut policy = AccessPolicy::default();
+		if let Some(ref allow) = self.args.flag_jsonrpc_allow {
+			policy.allow(Principal::Anonymous, methods(allow));
+		}
+		if let Some(ref allow) = self.args.flag_ipc_allow {
+			policy.allow(Principal::Ipc, methods(allow));
+		}
+		if let Some(ref allow) = self.args.flag_ui_allow {
+			policy.allow(Principal::Ui, methods(allow));
+		}
+		if let Some(ref tokens) = self.args.flag_jsonrpc_tokens {
+			for entry in tokens.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+				let mut parts = entry.splitn(2, '=');
+				match (parts.next().map(str::trim), parts.next()) {
+					(Some(token), Some(allow)) if !token.is_empty() => {
+						policy.allow(Principal::Token(token.into()), methods(allow));
+					},
+					_ => return Err(format!("Invalid RPC token entry: {}. Expected TOKEN=METHODS.", entry)),
+				}
+			}
+		}
+
+		Ok(policy)
+	}
+
+	fn rate_limits(&self) -> RateLimits {
+		RateLimits {
+			requests: self.args.flag_jsonrpc_rate_limit,
+			expensive_methods: self.args.flag_jsonrpc_expensive_methods.split(',')
+				.map(str::trim)
+				.filter(|m| !m.is_empty())
+				.map(Into::into)
+				.collect(),
+			expensive_budget: Duration::from_millis(self.args.flag_jsonrpc_expensive_budget),
+			..Default::default()
+		}
+	}
+
 	fn network_settings(&self) -> NetworkSettings {
 		NetworkSettings {
 			name: self.args.flag_identity.clone(),
@@ -857,20 +992,23 @@ impl Configuration {
 mod tests {
 	use super::*;
 	use cli::Args;
-	use ethcore_rpc::NetworkSettings;
+	use ethcore_rpc::{NetworkSettings, AccessPolicy, Principal, RateLimits};
 	use ethcore::client::{VMType, BlockId};
 	use ethcore::miner::{MinerOptions, PrioritizationStrategy};
 	use helpers::{default_network_config};
+	use ethsync::NatType;
 	use run::RunCmd;
 	use dir::{Directories, default_hypervisor_path};
 	use signer::{Configuration as SignerConfiguration};
-	use blockchain::{BlockchainCmd, ImportBlockchain, ExportBlockchain, DataFormat, ExportState};
+	use blockchain::{BlockchainCmd, ImportBlockchain, ExportBlockchain, MigratePruning, CheckBlockchain, DatabaseStats, DataFormat, ExportState};
+	use util::journaldb::Algorithm;
 	use presale::ImportWallet;
 	use params::SpecType;
 	use account::{AccountCmd, NewAccount, ImportAccounts, ListAccounts};
 	use devtools::{RandomTempPath};
 	use updater::{UpdatePolicy, UpdateFilter, ReleaseTrack};
 	use std::io::Write;
+	use std::time::Duration;
 	use std::fs::{File, create_dir};
 
 	#[derive(Debug, PartialEq)]
@@ -961,6 +1099,60 @@ mod tests {
 		})));
 	}
 
+	#[test]
+	fn test_command_db_migrate_pruning() {
+		let args = vec!["parity", "db", "migrate-pruning", "--to", "archive"];
+		let conf = parse(&args);
+		assert_eq!(conf.into_command().unwrap().cmd, Cmd::Blockchain(BlockchainCmd::MigratePruning(MigratePruning {
+			spec: Default::default(),
+			dirs: Default::default(),
+			pruning: Default::default(),
+			to: Algorithm::Archive,
+			compaction: Default::default(),
+			wal: true,
+		})));
+
+		let args = vec!["parity", "db", "migrate-pruning", "--to", "latest"];
+		assert!(parse(&args).into_command().is_err());
+	}
+
+	#[test]
+	fn test_command_db_check() {
+		let args = vec!["parity", "db", "check", "--repair"];
+		let conf = parse(&args);
+		assert_eq!(conf.into_command().unwrap().cmd, Cmd::Blockchain(BlockchainCmd::Check(CheckBlockchain {
+			spec: Default::default(),
+			cache_config: Default::default(),
+			dirs: Default::default(),
+			pruning: Default::default(),
+			pruning_history: 1200,
+			pruning_memory: 150,
+			compaction: Default::default(),
+			wal: true,
+			tracing: Default::default(),
+			fat_db: Default::default(),
+			repair: true,
+		})));
+	}
+
+	#[test]
+	fn test_command_db_stats() {
+		let args = vec!["parity", "db", "stats"];
+		let conf = parse(&args);
+		assert_eq!(conf.into_command().unwrap().cmd, Cmd::Blockchain(BlockchainCmd::Stats(DatabaseStats {
+			spec: Default::default(),
+			cache_config: Default::default(),
+			dirs: Default::default(),
+			pruning: Default::default(),
+			pruning_history: 1200,
+			pruning_memory: 150,
+			compaction: Default::default(),
+			wal: true,
+			tracing: Default::default(),
+			fat_db: Default::default(),
+		})));
+	}
+
 	#[test]
 	fn test_command_blockchain_export() {
 		let args = vec!["parity", "export", "blocks", "blockchain.json"];
@@ -1057,6 +1249,12 @@ mod tests {
 			pruning: Default::default(),
 			pruning_history: 1200,
 			pruning_memory: 150,
+			freezer_depth: None,
+			history_retention: None,
+			flat_state: false,
+			address_index: false,
+			address_index_traces: false,
+			log_index: false,
 			daemon: None,
 			logger_config: Default::default(),
 			miner_options: Default::default(),
@@ -1067,6 +1265,7 @@ mod tests {
 			warp_sync: false,
 			acc_conf: Default::default(),
 			gas_pricer: Default::default(),
+			gas_price_oracle: Default::default(),
 			miner_extras: Default::default(),
 			update_policy: UpdatePolicy { enable_downloading: true, require_consensus: true, filter: UpdateFilter::Critical, track: ReleaseTrack::Unknown, path: default_hypervisor_path() },
 			mode: Default::default(),
@@ -1087,7 +1286,13 @@ mod tests {
 			no_periodic_snapshot: false,
 			check_seal: true,
 			download_old_blocks: true,
+			fast_sync: true,
 			verifier_settings: Default::default(),
+			access_policy: Default::default(),
+			rate_limits: RateLimits {
+				expensive_methods: vec!["eth_getLogs".into(), "trace_filter".into(), "eth_estimateGas".into(), "parity_listAccounts".into()],
+				..Default::default()
+			},
 		}));
 	}
 
@@ -1145,6 +1350,27 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn should_parse_nat_method() {
+		// given
+
+		// when
+		let conf0 = parse(&["parity"]);
+		let conf1 = parse(&["parity", "--nat", "natpmp"]);
+		let conf2 = parse(&["parity", "--nat", "extip:1.2.3.4"]);
+
+		// then
+		let net0 = conf0.net_config().unwrap();
+		assert!(net0.nat_enabled);
+		assert_eq!(net0.nat_type, NatType::Any);
+		let net1 = conf1.net_config().unwrap();
+		assert!(net1.nat_enabled);
+		assert_eq!(net1.nat_type, NatType::NatPmp);
+		let net2 = conf2.net_config().unwrap();
+		assert!(!net2.nat_enabled);
+		assert_eq!(net2.public_address, Some("1.2.3.4:30303".into()));
+	}
+
 	#[test]
 	fn should_parse_rpc_settings_with_geth_compatiblity() {
 		// given
@@ -1193,6 +1419,46 @@ mod tests {
 		assert_eq!(conf3.rpc_hosts(), Some(vec!["ethcore.io".into(), "something.io".into()]));
 	}
 
+	#[test]
+	fn should_parse_rpc_access_policy() {
+		// given
+		let mut expected = AccessPolicy::default();
+		expected.allow(Principal::Anonymous, vec!["eth_*", "net_version"]);
+		expected.allow(Principal::Ipc, vec!["*"]);
+		expected.allow(Principal::Token("admin".into()), vec!["*"]);
+		expected.allow(Principal::Token("reader".into()), vec!["eth_*", "parity_*"]);
+
+		// when
+		let conf0 = parse(&["parity"]);
+		let conf1 = parse(&["parity", "--jsonrpc-allow", "eth_*,net_version", "--ipc-allow", "*",
+			"--jsonrpc-tokens", "admin=*;reader=eth_*,parity_*"]);
+		let conf2 = parse(&["parity", "--jsonrpc-tokens", "admin"]);
+
+		// then
+		assert_eq!(conf0.access_policy(), Ok(AccessPolicy::default()));
+		assert_eq!(conf1.access_policy(), Ok(expected));
+		assert!(conf2.access_policy().is_err());
+	}
+
+	#[test]
+	fn should_parse_rate_limits() {
+		// given
+
+		// when
+		let conf0 = parse(&["parity"]);
+		let conf1 = parse(&["parity", "--jsonrpc-rate-limit", "100", "--jsonrpc-expensive-methods", "eth_getLogs, trace_*",
+			"--jsonrpc-expensive-budget", "2000"]);
+
+		// then
+		assert!(!conf0.rate_limits().is_enabled());
+		assert_eq!(conf1.rate_limits(), RateLimits {
+			requests: 100,
+			expensive_methods: vec!["eth_getLogs".into(), "trace_*".into()],
+			expensive_budget: Duration::from_millis(2000),
+			window: Duration::from_secs(60),
+		});
+	}
+
 	#[test]
 	fn should_parse_dapps_hosts() {
 		// given
@@ -1301,4 +1567,17 @@ mod tests {
 		let conf = Configuration::parse(&args).unwrap();
 		assert!(conf.init_reserved_nodes().is_ok());
 	}
+
+	#[test]
+	fn should_parse_history_retention() {
+		let conf0 = parse(&["parity", "--history-retention", "100000"]);
+		let conf1 = parse(&["parity", "--history-retention", "100000", "--freezer-depth", "90000"]);
+
+		let conf2 = parse(&["parity", "--history-retention", "1"]);
+		let conf3 = parse(&["parity", "--history-retention", "1", "--no-periodic-snapshot", "--pruning-history", "1"]);
+		assert_eq!(conf0.history_retention(), Ok(Some(100000)));
+		assert!(conf1.history_retention().is_err());
+		assert!(conf2.history_retention().is_err());
+		assert_eq!(conf3.history_retention(), Ok(Some(1)));
+	}
 }
@@ -128,7 +128,7 @@ mod server {
 	use ethcore::client::{Client, BlockChainClient, BlockId};
 
 	use rpc_apis;
-	use ethcore_rpc::is_major_importing;
+	use ethcore_rpc::{is_major_importing, Principal};
 	use hash_fetch::urlhint::ContractClient;
 	use jsonrpc_core::reactor::RpcHandler;
 	use parity_reactor;
@@ -162,7 +162,7 @@ mod server {
 			.signer_address(deps.signer.address())
 			.allowed_hosts(allowed_hosts);
 
-		let apis = rpc_apis::setup_rpc(Default::default(), deps.apis.clone(), rpc_apis::ApiSet::UnsafeContext);
+		let apis = rpc_apis::setup_rpc(Default::default(), deps.apis.clone(), rpc_apis::ApiSet::UnsafeContext, Principal::Anonymous);
 		let handler = RpcHandler::new(Arc::new(apis), deps.remote);
 		let start_result = match auth {
 			None => {
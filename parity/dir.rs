@@ -155,6 +155,13 @@ impl DatabaseDirectories {
 		dir
 	}
 
+	/// Get the path of the ancient block freezer
+	pub fn freezer_path(&self, pruning: Algorithm) -> PathBuf {
+		let mut dir = self.db_path(pruning);
+		dir.push("ancient");
+		dir
+	}
+
 	/// Get the root path for database
 	// TODO: remove in 1.7
 	pub fn legacy_version_path(&self, pruning: Algorithm) -> PathBuf {
@@ -20,13 +20,13 @@ use std::time::Duration;
 use std::fs::File;
 use util::{clean_0x, U256, Uint, Address, path, CompactionProfile};
 use util::journaldb::Algorithm;
-use ethcore::client::{Mode, BlockId, VMType, DatabaseCompactionProfile, ClientConfig, VerifierType};
+use ethcore::client::{Mode, BlockId, VMType, DatabaseCompactionProfile, ClientConfig, VerifierType, FreezerConfig};
 use ethcore::miner::{PendingSet, GasLimit, PrioritizationStrategy};
 use cache::CacheConfig;
 use dir::DatabaseDirectories;
 use upgrade::{upgrade, upgrade_data_paths};
 use migration::migrate;
-use ethsync::is_valid_node_url;
+use ethsync::{is_valid_node_url, PeerGroup};
 
 pub fn to_duration(s: &str) -> Result<Duration, String> {
 	to_seconds(s).map(Duration::from_secs)
@@ -189,9 +189,47 @@ pub fn to_bootnodes(bootnodes: &Option<String>) -> Result<Vec<String>, String> {
 	}
 }
 
+/// Parses static peer groups. Each group starts with a `[NAME] MIN MAX [trusted]` line
+/// followed by its members, one per line.
+pub fn to_peer_groups(s: &str) -> Result<Vec<PeerGroup>, String> {
+	let mut groups: Vec<PeerGroup> = Vec::new();
+	for line in s.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+		if line.starts_with('[') {
+			let end = line.find(']').ok_or_else(|| format!("Invalid peer group header: {}", line))?;
+			let name = line[1..end].trim();
+			let params: Vec<&str> = line[end + 1..].split_whitespace().collect();
+			let trusted = match (params.len(), params.get(2)) {
+				(2, _) => false,
+				(3, Some(&"trusted")) => true,
+				_ => return Err(format!("Invalid peer group header: {}", line)),
+			};
+			let min_slots: u32 = params[0].parse().map_err(|_| format!("Invalid minimum slots of peer group {}: {}", name, params[0]))?;
+			let max_slots: u32 = params[1].parse().map_err(|_| format!("Invalid maximum slots of peer group {}: {}", name, params[1]))?;
+			if name.is_empty() || min_slots > max_slots {
+				return Err(format!("Invalid peer group header: {}", line));
+			}
+			groups.push(PeerGroup {
+				name: name.to_owned(),
+				nodes: Vec::new(),
+				min_slots: min_slots,
+				max_slots: max_slots,
+				trusted: trusted,
+			});
+		} else if !is_valid_node_url(line) {
+			return Err(format!("Invalid node address format given for a peer group: {}", line));
+		} else {
+			match groups.last_mut() {
+				Some(group) => group.nodes.push(line.to_owned()),
+				None => return Err(format!("Peer group member given before any group: {}", line)),
+			}
+		}
+	}
+	Ok(groups)
+}
+
 #[cfg(test)]
 pub fn default_network_config() -> ::ethsync::NetworkConfiguration {
-	use ethsync::{NetworkConfiguration, AllowIP};
+	use ethsync::{NetworkConfiguration, AllowIP, NatType};
 	NetworkConfiguration {
 		config_path: Some(replace_home(&::dir::default_data_path(), "$BASE/network")),
 		net_config_path: None,
@@ -199,6 +237,7 @@ pub fn default_network_config() -> ::ethsync::NetworkConfiguration {
 		public_address: None,
 		udp_port: None,
 		nat_enabled: true,
+		nat_type: NatType::Any,
 		discovery_enabled: true,
 		boot_nodes: Vec::new(),
 		use_secret: None,
@@ -209,6 +248,9 @@ pub fn default_network_config() -> ::ethsync::NetworkConfiguration {
 		allow_ips: AllowIP::All,
 		reserved_nodes: Vec::new(),
 		allow_non_reserved: true,
+		peer_groups: Vec::new(),
+		max_upload_rate: None,
+		max_download_rate: None,
 	}
 }
 
@@ -226,6 +268,7 @@ pub fn to_client_config(
 		pruning_history: u64,
 		pruning_memory: usize,
 		check_seal: bool,
+		freezer: FreezerConfig,
 	) -> ClientConfig {
 	let mut client_config = ClientConfig::default();
 
@@ -256,6 +299,7 @@ pub fn to_client_config(
 	client_config.fat_db = fat_db;
 	client_config.pruning = pruning;
 	client_config.history = pruning_history;
+	client_config.blockchain.freezer = Some(freezer);
 	client_config.db_compaction = compaction;
 	client_config.db_wal = wal;
 	client_config.vm_type = vm_type;
@@ -268,7 +312,8 @@ pub fn execute_upgrades(
 	base_path: &str,
 	dirs: &DatabaseDirectories,
 	pruning: Algorithm,
-	compaction_profile: CompactionProfile
+	compaction_profile: CompactionProfile,
+	freezer_depth: Option<u64>,
 ) -> Result<(), String> {
 
 	upgrade_data_paths(base_path, dirs, pruning);
@@ -284,7 +329,11 @@ pub fn execute_upgrades(
 	}
 
 	let client_path = dirs.db_path(pruning);
-	migrate(&client_path, pruning, compaction_profile).map_err(|e| format!("{}", e))
+	let freezer = FreezerConfig {
+		path: dirs.freezer_path(pruning),
+		depth: freezer_depth,
+	};
+	migrate(&client_path, pruning, compaction_profile, Some(freezer)).map_err(|e| format!("{}", e))
 }
 
 /// Prompts user asking for password.
@@ -341,7 +390,7 @@ mod tests {
 	use util::{U256};
 	use ethcore::client::{Mode, BlockId};
 	use ethcore::miner::PendingSet;
-	use super::{to_duration, to_mode, to_block_id, to_u256, to_pending_set, to_address, to_addresses, to_price, geth_ipc_path, to_bootnodes, password_from_file};
+	use super::{to_duration, to_mode, to_block_id, to_u256, to_pending_set, to_address, to_addresses, to_price, geth_ipc_path, to_bootnodes, to_peer_groups, password_from_file};
 
 	#[test]
 	fn test_to_duration() {
@@ -478,5 +527,20 @@ but the first password is trimmed
 		assert_eq!(to_bootnodes(&Some(one_bootnode.into())), Ok(vec![one_bootnode.into()]));
 		assert_eq!(to_bootnodes(&Some(two_bootnodes.into())), Ok(vec![one_bootnode.into(), one_bootnode.into()]));
 	}
+	#[test]
+	fn test_to_peer_groups() {
+		let node = "enode://e731347db0521f3476e6bbbb83375dcd7133a1601425ebd15fd10f3835fd4c304fba6282087ca5a0deeafadf0aa0d4fd56c3323331901c1f38bd181c283e3e35@128.199.55.137:30303";
+		let groups = to_peer_groups(&format!("# validators first\n[validators] 1 4 trusted\n{}\n\n[archive-backups] 0 2\n", node)).unwrap();
+		assert_eq!(groups.len(), 2);
+		assert_eq!(groups[0].name, "validators");
+		assert_eq!(groups[0].nodes, vec![node.to_owned()]);
+		assert_eq!((groups[0].min_slots, groups[0].max_slots, groups[0].trusted), (1, 4, true));
+		assert_eq!((groups[1].min_slots, groups[1].max_slots, groups[1].trusted), (0, 2, false));
+		assert!(groups[1].nodes.is_empty());
+
+		assert!(to_peer_groups(node).is_err());
+		assert!(to_peer_groups("[validators] 2 1").is_err());
+		assert!(to_peer_groups("[validators] 1 2 untrusted").is_err());
+		assert!(to_peer_groups("[validators] 1 2\nenode://invalid").is_err());
+	}
 }
-
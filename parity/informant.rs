@@ -112,6 +112,8 @@ impl Informant {
 			}
 		);
 
+		let fast_sync = sync_status.and_then(|s| s.fast_sync_pivot.map(|pivot| (pivot, s.state_nodes_done, s.state_nodes_done + s.state_nodes_pending)));
+
 		if !importing && !snapshot_sync && elapsed < Duration::from_secs(30) {
 			return;
 		}
@@ -128,8 +130,8 @@ impl Informant {
 
 		info!(target: "import", "{}   {}   {}",
 			match importing {
-				true => match snapshot_sync {
-					false => format!("Syncing {} {}   {}   {}+{} Qed",
+				true => match (snapshot_sync, fast_sync) {
+					(false, None) => format!("Syncing {} {}   {}   {}+{} Qed",
 						paint(White.bold(), format!("{:>8}", format!("#{}", chain_info.best_block_number))),
 						paint(White.bold(), format!("{}", chain_info.best_block_hash)),
 						{
@@ -143,7 +145,8 @@ impl Informant {
 						paint(Green.bold(), format!("{:5}", queue_info.unverified_queue_size)),
 						paint(Green.bold(), format!("{:5}", queue_info.verified_queue_size))
 					),
-					true => format!("Syncing snapshot {}/{}", snapshot_current, snapshot_total),
+					(true, _) => format!("Syncing snapshot {}/{}", snapshot_current, snapshot_total),
+					(false, Some((pivot, nodes_done, nodes_total))) => format!("Syncing state at #{} {}/{} nodes", pivot, nodes_done, nodes_total),
 				},
 				false => String::new(),
 			},
@@ -26,11 +26,12 @@ use util::kvdb::{CompactionProfile, Database, DatabaseConfig};
 use ethcore::migrations;
 use ethcore::db;
 use ethcore::migrations::Extract;
+use ethcore::client::FreezerConfig;
 
 /// Database is assumed to be at default version, when no version file is found.
 const DEFAULT_VERSION: u32 = 5;
 /// Current version of database models.
-const CURRENT_VERSION: u32 = 10;
+const CURRENT_VERSION: u32 = 14;
 /// First version of the consolidated database.
 const CONSOLIDATION_VERSION: u32 = 9;
 /// Defines how many items are migrated to the new version of database at once.
@@ -112,7 +113,7 @@ fn current_version(path: &Path) -> Result<u32, Error> {
 
 /// Writes current database version to the file.
 /// Creates a new file if the version file does not exist yet.
-fn update_version(path: &Path) -> Result<(), Error> {
+pub fn update_version(path: &Path) -> Result<(), Error> {
 	fs::create_dir_all(path)?;
 	let mut file = File::create(version_file_path(path))?;
 	file.write_all(format!("{}", CURRENT_VERSION).as_bytes())?;
@@ -143,9 +144,16 @@ pub fn default_migration_settings(compaction_profile: &CompactionProfile) -> Mig
 }
 
 /// Migrations on the consolidated database.
-fn consolidated_database_migrations(compaction_profile: &CompactionProfile) -> Result<MigrationManager, Error> {
+fn consolidated_database_migrations(compaction_profile: &CompactionProfile, freezer: Option<FreezerConfig>) -> Result<MigrationManager, Error> {
 	let mut manager = MigrationManager::new(default_migration_settings(compaction_profile));
 	manager.add_migration(migrations::ToV10::new()).map_err(|_| Error::MigrationImpossible)?;
+	manager.add_migration(migrations::ToV11::new(freezer)).map_err(|_| Error::MigrationImpossible)?;
+	// flat state column
+	manager.add_migration(migrations::ColumnsAdded::new(12, 6, 7)).map_err(|_| Error::MigrationImpossible)?;
+	// address index column
+	manager.add_migration(migrations::ColumnsAdded::new(13, 7, 8)).map_err(|_| Error::MigrationImpossible)?;
+	// log index column
+	manager.add_migration(migrations::ColumnsAdded::new(14, 8, 9)).map_err(|_| Error::MigrationImpossible)?;
 	Ok(manager)
 }
 
@@ -200,6 +208,11 @@ fn migrate_database(version: u32, db_path: PathBuf, mut migrations: MigrationMan
 	// migrate old database to the new one
 	let temp_path = migrations.execute(&db_path, version)?;
 
+	// migrations which only add columns are applied in place
+	if temp_path == db_path {
+		return Ok(())
+	}
+
 	// create backup
 	fs::rename(&db_path, &backup_path)?;
 
@@ -221,7 +234,7 @@ fn exists(path: &Path) -> bool {
 }
 
 /// Migrates the database.
-pub fn migrate(path: &Path, pruning: Algorithm, compaction_profile: CompactionProfile) -> Result<(), Error> {
+pub fn migrate(path: &Path, pruning: Algorithm, compaction_profile: CompactionProfile, freezer: Option<FreezerConfig>) -> Result<(), Error> {
 	// read version file.
 	let version = current_version(path)?;
 
@@ -265,7 +278,7 @@ pub fn migrate(path: &Path, pruning: Algorithm, compaction_profile: CompactionPr
 	// Further migrations
 	if version >= CONSOLIDATION_VERSION && version < CURRENT_VERSION && exists(&consolidated_database_path(path)) {
 		println!("Migrating database from version {} to {}", ::std::cmp::max(CONSOLIDATION_VERSION, version), CURRENT_VERSION);
-		migrate_database(version, consolidated_database_path(path), consolidated_database_migrations(&compaction_profile)?)?;
+		migrate_database(version, consolidated_database_path(path), consolidated_database_migrations(&compaction_profile, freezer)?)?;
 		println!("Migration finished");
 	}
 
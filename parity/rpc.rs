@@ -21,7 +21,7 @@ use std::io;
 use io::PanicHandler;
 
 use dir::default_data_path;
-use ethcore_rpc::{self as rpc, RpcServerError, IpcServerError, Metadata};
+use ethcore_rpc::{self as rpc, RpcServerError, IpcServerError, Metadata, Principal, RpcExtractor};
 use helpers::parity_ipc_path;
 use jsonrpc_core::MetaIoHandler;
 use jsonrpc_core::reactor::{RpcHandler, Remote};
@@ -97,8 +97,8 @@ pub fn new_http(conf: HttpConfiguration, deps: &Dependencies) -> Result<Option<H
 	Ok(Some(setup_http_rpc_server(deps, &addr, conf.cors, conf.hosts, conf.apis)?))
 }
 
-fn setup_apis(apis: ApiSet, deps: &Dependencies) -> MetaIoHandler<Metadata> {
-	rpc_apis::setup_rpc(MetaIoHandler::default(), deps.apis.clone(), apis)
+fn setup_apis(apis: ApiSet, deps: &Dependencies, transport: Principal) -> MetaIoHandler<Metadata> {
+	rpc_apis::setup_rpc(MetaIoHandler::default(), deps.apis.clone(), apis, transport)
 }
 
 pub fn setup_http_rpc_server(
@@ -108,10 +108,10 @@ pub fn setup_http_rpc_server(
 	allowed_hosts: Option<Vec<String>>,
 	apis: ApiSet
 ) -> Result<HttpServer, String> {
-	let apis = setup_apis(apis, dependencies);
+	let apis = setup_apis(apis, dependencies, Principal::Anonymous);
 	let handler = RpcHandler::new(Arc::new(apis), dependencies.remote.clone());
 	let ph = dependencies.panic_handler.clone();
-	let start_result = rpc::start_http(url, cors_domains, allowed_hosts, ph, handler);
+	let start_result = rpc::start_http(url, cors_domains, allowed_hosts, ph, handler, RpcExtractor);
 	match start_result {
 		Err(RpcServerError::IoError(err)) => match err.kind() {
 			io::ErrorKind::AddrInUse => Err(format!("RPC address {} is already in use, make sure that another instance of an Ethereum client is not running or change the address using the --jsonrpc-port and --jsonrpc-interface options.", url)),
@@ -128,7 +128,7 @@ pub fn new_ipc(conf: IpcConfiguration, deps: &Dependencies) -> Result<Option<Ipc
 }
 
 pub fn setup_ipc_rpc_server(dependencies: &Dependencies, addr: &str, apis: ApiSet) -> Result<IpcServer<Metadata>, String> {
-	let apis = setup_apis(apis, dependencies);
+	let apis = setup_apis(apis, dependencies, Principal::Ipc);
 	let handler = RpcHandler::new(Arc::new(apis), dependencies.remote.clone());
 	match rpc::start_ipc(addr, handler) {
 		Err(IpcServerError::Io(io_error)) => Err(format!("RPC io error: {}", io_error)),
@@ -26,7 +26,8 @@ use ethcore::client::Client;
 use ethcore::account_provider::AccountProvider;
 use ethcore::snapshot::SnapshotService;
 use ethsync::{ManageNetwork, SyncProvider};
-use ethcore_rpc::{Metadata, NetworkSettings};
+use jsonrpc_core::RemoteProcedure;
+use ethcore_rpc::{Metadata, NetworkSettings, AccessPolicy, AccessControl, Principal, RateLimiter};
 pub use ethcore_rpc::SignerService;
 use updater::Updater;
 use hash_fetch::fetch::Client as FetchClient;
@@ -125,6 +126,8 @@ pub struct Dependencies {
 	pub dapps_interface: Option<String>,
 	pub dapps_port: Option<u16>,
 	pub fetch: FetchClient,
+	pub access_policy: Arc<AccessPolicy>,
+	pub rate_limiter: Arc<RateLimiter>,
 }
 
 fn to_modules(apis: &[Api]) -> BTreeMap<String, String> {
@@ -169,31 +172,60 @@ impl ApiSet {
 }
 
 macro_rules! add_signing_methods {
-	($namespace:ident, $handler:expr, $deps:expr) => {
+	($namespace:ident, $handler:expr, $deps:expr, $guards:expr) => {
 		{
 			let handler = &mut $handler;
 			let deps = &$deps;
+			let guards = &$guards;
 			if deps.signer_service.is_enabled() {
-				handler.extend_with($namespace::to_delegate(SigningQueueClient::new(&deps.signer_service, &deps.client, &deps.miner, &deps.secret_store)))
+				handler.extend_with(guards.wrap($namespace::to_delegate(SigningQueueClient::new(&deps.signer_service, &deps.client, &deps.miner, &deps.secret_store))))
 			} else {
-				handler.extend_with($namespace::to_delegate(SigningUnsafeClient::new(&deps.client, &deps.secret_store, &deps.miner)))
+				handler.extend_with(guards.wrap($namespace::to_delegate(SigningUnsafeClient::new(&deps.client, &deps.secret_store, &deps.miner))))
 			}
 		}
 	}
 }
 
-pub fn setup_rpc(mut handler: MetaIoHandler<Metadata>, deps: Arc<Dependencies>, apis: ApiSet) -> MetaIoHandler<Metadata> {
+/// Access control and rate limiting applied to methods of a single transport.
+struct Guards {
+	access: AccessControl,
+	limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Guards {
+	fn wrap<I>(&self, delegate: I) -> Vec<(String, RemoteProcedure<Metadata>)> where
+		I: IntoIterator<Item=(String, RemoteProcedure<Metadata>)>,
+	{
+		let methods = self.access.guard(delegate);
+		match self.limiter {
+			Some(ref limiter) => RateLimiter::limit(limiter, methods),
+			None => methods,
+		}
+	}
+}
+
+pub fn setup_rpc(mut handler: MetaIoHandler<Metadata>, deps: Arc<Dependencies>, apis: ApiSet, transport: Principal) -> MetaIoHandler<Metadata> {
 	use ethcore_rpc::v1::*;
 
+	// only the public transports are rate limited
+	let limiter = match transport {
+		Principal::Anonymous if deps.rate_limiter.is_enabled() => Some(deps.rate_limiter.clone()),
+		_ => None,
+	};
+	let guards = Guards {
+		access: AccessControl::new(deps.access_policy.clone(), transport),
+		limiter: limiter,
+	};
+
 	// it's turned into vector, cause ont of the cases requires &[]
 	let apis = apis.list_apis().into_iter().collect::<Vec<_>>();
 	for api in &apis {
 		match *api {
 			Api::Web3 => {
-				handler.extend_with(Web3Client::new().to_delegate());
+				handler.extend_with(guards.wrap(Web3Client::new().to_delegate()));
 			},
 			Api::Net => {
-				handler.extend_with(NetClient::new(&deps.sync).to_delegate());
+				handler.extend_with(guards.wrap(NetClient::new(&deps.sync).to_delegate()));
 			},
 			Api::Eth => {
 				let client = EthClient::new(
@@ -208,25 +240,25 @@ pub fn setup_rpc(mut handler: MetaIoHandler<Metadata>, deps: Arc<Dependencies>,
 						send_block_number_in_get_work: !deps.geth_compatibility,
 					}
 				);
-				handler.extend_with(client.to_delegate());
+				handler.extend_with(guards.wrap(client.to_delegate()));
 
 				let filter_client = EthFilterClient::new(&deps.client, &deps.miner);
-				handler.extend_with(filter_client.to_delegate());
+				handler.extend_with(guards.wrap(filter_client.to_delegate()));
 
-				add_signing_methods!(EthSigning, handler, deps);
+				add_signing_methods!(EthSigning, handler, deps, guards);
 			},
 			Api::Personal => {
-				handler.extend_with(PersonalClient::new(&deps.secret_store, &deps.client, &deps.miner, deps.geth_compatibility).to_delegate());
+				handler.extend_with(guards.wrap(PersonalClient::new(&deps.secret_store, &deps.client, &deps.miner, deps.geth_compatibility).to_delegate()));
 			},
 			Api::Signer => {
-				handler.extend_with(SignerClient::new(&deps.secret_store, &deps.client, &deps.miner, &deps.signer_service).to_delegate());
+				handler.extend_with(guards.wrap(SignerClient::new(&deps.secret_store, &deps.client, &deps.miner, &deps.signer_service).to_delegate()));
 			},
 			Api::Parity => {
 				let signer = match deps.signer_service.is_enabled() {
 					true => Some(deps.signer_service.clone()),
 					false => None,
 				};
-				handler.extend_with(ParityClient::new(
+				handler.extend_with(guards.wrap(ParityClient::new(
 					&deps.client,
 					&deps.miner,
 					&deps.sync,
@@ -238,29 +270,29 @@ pub fn setup_rpc(mut handler: MetaIoHandler<Metadata>, deps: Arc<Dependencies>,
 					signer,
 					deps.dapps_interface.clone(),
 					deps.dapps_port,
-				).to_delegate());
+				).to_delegate()));
 
-				add_signing_methods!(EthSigning, handler, deps);
-				add_signing_methods!(ParitySigning, handler, deps);
+				add_signing_methods!(EthSigning, handler, deps, guards);
+				add_signing_methods!(ParitySigning, handler, deps, guards);
 			},
 			Api::ParityAccounts => {
-				handler.extend_with(ParityAccountsClient::new(&deps.secret_store, &deps.client).to_delegate());
+				handler.extend_with(guards.wrap(ParityAccountsClient::new(&deps.secret_store, &deps.client).to_delegate()));
 			},
 			Api::ParitySet => {
-				handler.extend_with(ParitySetClient::new(
+				handler.extend_with(guards.wrap(ParitySetClient::new(
 					&deps.client,
 					&deps.miner,
 					&deps.updater,
 					&deps.net_service,
 					deps.fetch.clone(),
-				).to_delegate())
+				).to_delegate()))
 			},
 			Api::Traces => {
-				handler.extend_with(TracesClient::new(&deps.client, &deps.miner).to_delegate())
+				handler.extend_with(guards.wrap(TracesClient::new(&deps.client, &deps.miner).to_delegate()))
 			},
 			Api::Rpc => {
 				let modules = to_modules(&apis);
-				handler.extend_with(RpcClient::new(modules).to_delegate());
+				handler.extend_with(guards.wrap(RpcClient::new(modules).to_delegate()));
 			}
 		}
 	}
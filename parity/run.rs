@@ -18,15 +18,15 @@ use std::sync::Arc;
 use std::net::{TcpListener};
 use ctrlc::CtrlC;
 use fdlimit::raise_fd_limit;
-use ethcore_rpc::{NetworkSettings, is_major_importing};
+use ethcore_rpc::{NetworkSettings, AccessPolicy, RateLimits, RateLimiter, is_major_importing};
 use ethsync::NetworkConfiguration;
 use util::{Colour, version, RotatingLogger, Mutex, Condvar};
 use io::{MayPanic, ForwardPanic, PanicHandler};
 use ethcore_logger::{Config as LogConfig};
-use ethcore::client::{Mode, DatabaseCompactionProfile, VMType, BlockChainClient};
+use ethcore::client::{Mode, DatabaseCompactionProfile, VMType, BlockChainClient, FreezerConfig};
 use ethcore::service::ClientService;
 use ethcore::account_provider::AccountProvider;
-use ethcore::miner::{Miner, MinerService, ExternalMiner, MinerOptions};
+use ethcore::miner::{Miner, MinerService, ExternalMiner, MinerOptions, GasPricer, GasPriceOracleOptions};
 use ethcore::snapshot;
 use ethcore::verification::queue::VerifierSettings;
 use ethsync::SyncConfig;
@@ -53,10 +53,10 @@ use rpc;
 use url;
 
 // how often to take periodic snapshots.
-const SNAPSHOT_PERIOD: u64 = 10000;
+pub const SNAPSHOT_PERIOD: u64 = 10000;
 
 // how many blocks to wait before starting a periodic snapshot.
-const SNAPSHOT_HISTORY: u64 = 100;
+pub const SNAPSHOT_HISTORY: u64 = 100;
 
 // Pops along with error messages when a password is missing or invalid.
 const VERIFY_PASSWORD_HINT: &'static str = "Make sure valid password is present in files passed using `--password` or in the configuration file.";
@@ -69,6 +69,12 @@ pub struct RunCmd {
 	pub pruning: Pruning,
 	pub pruning_history: u64,
 	pub pruning_memory: usize,
+	pub freezer_depth: Option<u64>,
+	pub history_retention: Option<u64>,
+	pub flat_state: bool,
+	pub address_index: bool,
+	pub address_index_traces: bool,
+	pub log_index: bool,
 	/// Some if execution should be daemonized. Contains pid_file path.
 	pub daemon: Option<String>,
 	pub logger_config: LogConfig,
@@ -80,6 +86,7 @@ pub struct RunCmd {
 	pub warp_sync: bool,
 	pub acc_conf: AccountsConfig,
 	pub gas_pricer: GasPricerConfig,
+	pub gas_price_oracle: GasPriceOracleOptions,
 	pub miner_extras: MinerExtras,
 	pub update_policy: UpdatePolicy,
 	pub mode: Option<Mode>,
@@ -100,7 +107,10 @@ pub struct RunCmd {
 	pub no_periodic_snapshot: bool,
 	pub check_seal: bool,
 	pub download_old_blocks: bool,
+	pub fast_sync: bool,
 	pub verifier_settings: VerifierSettings,
+	pub access_policy: AccessPolicy,
+	pub rate_limits: RateLimits,
 }
 
 pub fn open_ui(dapps_conf: &dapps::Configuration, signer_conf: &signer::Configuration) -> Result<(), String> {
@@ -183,7 +193,7 @@ pub fn execute(cmd: RunCmd, can_restart: bool, logger: Arc<RotatingLogger>) -> R
 	let snapshot_path = db_dirs.snapshot_path();
 
 	// execute upgrades
-	execute_upgrades(&cmd.dirs.base, &db_dirs, algorithm, cmd.compaction.compaction_profile(db_dirs.db_root_path().as_path()))?;
+	execute_upgrades(&cmd.dirs.base, &db_dirs, algorithm, cmd.compaction.compaction_profile(db_dirs.db_root_path().as_path()), cmd.freezer_depth)?;
 
 	// create dirs used by parity
 	cmd.dirs.create_dirs(cmd.dapps_conf.enabled, cmd.signer_conf.enabled)?;
@@ -227,6 +237,7 @@ pub fn execute(cmd: RunCmd, can_restart: bool, logger: Arc<RotatingLogger>) -> R
 	sync_config.fork_block = spec.fork_block();
 	sync_config.warp_sync = cmd.warp_sync;
 	sync_config.download_old_blocks = cmd.download_old_blocks;
+	sync_config.fast_sync = cmd.fast_sync;
 
 	let passwords = passwords_from_files(&cmd.acc_conf.password_files)?;
 
@@ -235,7 +246,8 @@ pub fn execute(cmd: RunCmd, can_restart: bool, logger: Arc<RotatingLogger>) -> R
 
 	// create miner
 	let initial_min_gas_price = cmd.gas_pricer.initial_min();
-	let miner = Miner::new(cmd.miner_options, cmd.gas_pricer.into(), &spec, Some(account_provider.clone()));
+	let gas_pricer = GasPricer::new_oracle(cmd.gas_pricer.into(), cmd.gas_price_oracle);
+	let miner = Miner::new(cmd.miner_options, gas_pricer, &spec, Some(account_provider.clone()));
 	miner.set_author(cmd.miner_extras.author);
 	miner.set_gas_floor_target(cmd.miner_extras.gas_floor_target);
 	miner.set_gas_ceil_target(cmd.miner_extras.gas_ceil_target);
@@ -276,9 +288,18 @@ pub fn execute(cmd: RunCmd, can_restart: bool, logger: Arc<RotatingLogger>) -> R
 		cmd.pruning_history,
 		cmd.pruning_memory,
 		cmd.check_seal,
+		FreezerConfig {
+			path: db_dirs.freezer_path(algorithm),
+			depth: cmd.freezer_depth,
+		},
 	);
 
 	client_config.queue.verifier_settings = cmd.verifier_settings;
+	client_config.blockchain.history_retention = cmd.history_retention;
+	client_config.flat_state = cmd.flat_state;
+	client_config.address_index = cmd.address_index;
+	client_config.address_index_traces = cmd.address_index_traces;
+	client_config.log_index = cmd.log_index;
 
 	// set up bootnodes
 	let mut net_conf = cmd.net_conf;
@@ -376,6 +397,8 @@ pub fn execute(cmd: RunCmd, can_restart: bool, logger: Arc<RotatingLogger>) -> R
 			false => None,
 		},
 		fetch: fetch.clone(),
+		rate_limiter: Arc::new(RateLimiter::new(cmd.rate_limits, cmd.access_policy.tokens())),
+		access_policy: Arc::new(cmd.access_policy),
 	});
 
 	let dependencies = rpc::Dependencies {
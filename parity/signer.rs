@@ -22,6 +22,7 @@ use io::{ForwardPanic, PanicHandler};
 use util::path::restrict_permissions_owner;
 use rpc_apis;
 use ethcore_signer as signer;
+use ethcore_rpc::Principal;
 use dir::default_data_path;
 use helpers::replace_home;
 use jsonrpc_core::reactor::{RpcHandler, Remote};
@@ -126,7 +127,7 @@ fn do_start(conf: Configuration, deps: Dependencies) -> Result<SignerServer, Str
 			info!("If you do not intend this, exit now.");
 		}
 		let server = server.skip_origin_validation(conf.skip_origin_validation);
-		let apis = rpc_apis::setup_rpc(Default::default(), deps.apis, rpc_apis::ApiSet::SafeContext);
+		let apis = rpc_apis::setup_rpc(Default::default(), deps.apis, rpc_apis::ApiSet::SafeContext, Principal::Ui);
 		let handler = RpcHandler::new(Arc::new(apis), deps.remote);
 		server.start(addr, handler)
 	};
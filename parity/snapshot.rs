@@ -24,7 +24,7 @@ use ethcore::snapshot::{Progress, RestorationStatus, SnapshotService as SS};
 use ethcore::snapshot::io::{SnapshotReader, PackedReader, PackedWriter};
 use ethcore::snapshot::service::Service as SnapshotService;
 use ethcore::service::ClientService;
-use ethcore::client::{Mode, DatabaseCompactionProfile, VMType};
+use ethcore::client::{Mode, DatabaseCompactionProfile, VMType, FreezerConfig};
 use ethcore::miner::Miner;
 use ethcore::ids::BlockId;
 
@@ -168,7 +168,7 @@ impl SnapshotCommand {
 		let snapshot_path = db_dirs.snapshot_path();
 
 		// execute upgrades
-		execute_upgrades(&self.dirs.base, &db_dirs, algorithm, self.compaction.compaction_profile(db_dirs.db_root_path().as_path()))?;
+		execute_upgrades(&self.dirs.base, &db_dirs, algorithm, self.compaction.compaction_profile(db_dirs.db_root_path().as_path()), None)?;
 
 		// prepare client config
 		let client_config = to_client_config(
@@ -183,7 +183,11 @@ impl SnapshotCommand {
 			algorithm,
 			self.pruning_history,
 			self.pruning_memory,
-			true
+			true,
+			FreezerConfig {
+				path: db_dirs.freezer_path(algorithm),
+				depth: None,
+			},
 		);
 
 		let service = ClientService::start(
@@ -23,6 +23,7 @@ extern crate semver;
 extern crate rustc_serialize;
 extern crate serde;
 extern crate serde_json;
+extern crate hyper;
 extern crate jsonrpc_core;
 extern crate jsonrpc_http_server;
 
@@ -61,19 +62,24 @@ use io::PanicHandler;
 use jsonrpc_core::reactor::RpcHandler;
 
 pub use ipc::{Server as IpcServer, Error as IpcServerError};
-pub use jsonrpc_http_server::{ServerBuilder, Server, RpcServerError};
+pub use jsonrpc_http_server::{ServerBuilder, Server, RpcServerError, HttpMetaExtractor};
 pub mod v1;
 pub use v1::{SigningQueue, SignerService, ConfirmationsQueue, NetworkSettings, Metadata, Origin};
+pub use v1::{AccessPolicy, AccessControl, Principal, RpcExtractor, RateLimiter, RateLimits};
 pub use v1::block_import::is_major_importing;
 
 /// Start http server asynchronously and returns result with `Server` handle on success or an error.
-pub fn start_http<M: jsonrpc_core::Metadata>(
+pub fn start_http<M, T>(
 	addr: &SocketAddr,
 	cors_domains: Option<Vec<String>>,
 	allowed_hosts: Option<Vec<String>>,
 	panic_handler: Arc<PanicHandler>,
 	handler: RpcHandler<M>,
-) -> Result<Server, RpcServerError> {
+	extractor: T,
+) -> Result<Server, RpcServerError> where
+	M: jsonrpc_core::Metadata,
+	T: HttpMetaExtractor<M> + 'static,
+{
 
 	let cors_domains = cors_domains.map(|domains| {
 		domains.into_iter()
@@ -88,6 +94,7 @@ pub fn start_http<M: jsonrpc_core::Metadata>(
 	ServerBuilder::with_rpc_handler(handler)
 		.cors(cors_domains.into())
 		.allowed_hosts(allowed_hosts.into())
+		.meta_extractor(extractor)
 		.panic_handler(move || {
 			panic_handler.notify_all("Panic in RPC thread.".to_owned());
 		})
@@ -0,0 +1,219 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-method access control for RPC transports.
+
+use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+
+use futures::{self, BoxFuture, Future};
+use jsonrpc_core::{Params, Value, Error, RemoteProcedure, RpcMethod};
+use v1::helpers::errors;
+use v1::metadata::Metadata;
+
+/// Identity of the RPC caller.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Principal {
+	/// Caller without credentials (HTTP and dapps)
+	Anonymous,
+	/// Caller connected through IPC socket
+	Ipc,
+	/// Caller connected through Trusted UI websocket
+	Ui,
+	/// Caller presenting a bearer token
+	Token(String),
+}
+
+/// Method name or a prefix pattern ending with `*` (e.g. `eth_*`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodPattern(String);
+
+impl MethodPattern {
+	/// Returns true if given method name matches the pattern.
+	pub fn matches(&self, method: &str) -> bool {
+		match self.0.ends_with('*') {
+			true => method.starts_with(&self.0[..self.0.len() - 1]),
+			false => method == self.0,
+		}
+	}
+}
+
+impl<'a> From<&'a str> for MethodPattern {
+	fn from(s: &'a str) -> Self {
+		MethodPattern(s.trim().to_owned())
+	}
+}
+
+impl From<String> for MethodPattern {
+	fn from(s: String) -> Self {
+		s.as_str().into()
+	}
+}
+
+/// Set of methods each principal is allowed to call.
+///
+/// An empty policy doesn't restrict anything. Otherwise principals without any rules
+/// are denied, except unknown tokens which fall back to the rules of `Principal::Anonymous`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessPolicy {
+	rules: HashMap<Principal, Vec<MethodPattern>>,
+}
+
+impl AccessPolicy {
+	/// Allows given principal to call methods matching any of the patterns.
+	pub fn allow<I, T>(&mut self, principal: Principal, patterns: I) where
+		I: IntoIterator<Item=T>,
+		T: Into<MethodPattern>,
+	{
+		self.rules.entry(principal).or_insert_with(Vec::new).extend(patterns.into_iter().map(Into::into));
+	}
+
+	/// Returns true if the policy doesn't restrict anything.
+	pub fn is_empty(&self) -> bool {
+		self.rules.is_empty()
+	}
+
+	/// Returns bearer tokens the policy has rules for.
+	pub fn tokens(&self) -> HashSet<String> {
+		self.rules.keys().filter_map(|principal| match *principal {
+			Principal::Token(ref token) => Some(token.clone()),
+			_ => None,
+		}).collect()
+	}
+
+	/// Returns true if principal is allowed to call given method.
+	pub fn is_allowed(&self, principal: &Principal, method: &str) -> bool {
+		let rules = match *principal {
+			Principal::Token(_) => self.rules.get(principal).or_else(|| self.rules.get(&Principal::Anonymous)),
+			_ => self.rules.get(principal),
+		};
+
+		match rules {
+			Some(patterns) => patterns.iter().any(|pattern| pattern.matches(method)),
+			None => self.is_empty(),
+		}
+	}
+}
+
+/// Enforces `AccessPolicy` on methods exposed through a single transport.
+#[derive(Debug, Clone)]
+pub struct AccessControl {
+	policy: Arc<AccessPolicy>,
+	transport: Principal,
+}
+
+impl AccessControl {
+	/// Creates new access control for transport identified by given principal.
+	pub fn new(policy: Arc<AccessPolicy>, transport: Principal) -> Self {
+		AccessControl {
+			policy: policy,
+			transport: transport,
+		}
+	}
+
+	/// Resolves principal of the request. Bearer tokens take precedence over the transport.
+	pub fn principal(&self, meta: &Metadata) -> Principal {
+		match meta.token {
+			Some(ref token) => Principal::Token(token.clone()),
+			None => self.transport.clone(),
+		}
+	}
+
+	/// Wraps methods of given delegate, so that every call is checked against the policy.
+	pub fn guard<I>(&self, delegate: I) -> Vec<(String, RemoteProcedure<Metadata>)> where
+		I: IntoIterator<Item=(String, RemoteProcedure<Metadata>)>,
+	{
+		delegate.into_iter().map(|(name, procedure)| {
+			let procedure = match procedure {
+				RemoteProcedure::Method(method) if !self.policy.is_empty() => RemoteProcedure::Method(Arc::new(GuardedMethod {
+					name: name.clone(),
+					control: self.clone(),
+					method: method,
+				})),
+				procedure => procedure,
+			};
+			(name, procedure)
+		}).collect()
+	}
+}
+
+struct GuardedMethod {
+	name: String,
+	control: AccessControl,
+	method: Arc<RpcMethod<Metadata>>,
+}
+
+impl RpcMethod<Metadata> for GuardedMethod {
+	fn call(&self, params: Params, meta: Metadata) -> BoxFuture<Value, Error> {
+		let principal = self.control.principal(&meta);
+		if !self.control.policy.is_allowed(&principal, &self.name) {
+			return futures::failed(errors::access_denied(&self.name)).boxed();
+		}
+
+		self.method.call(params, meta)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{AccessPolicy, MethodPattern, Principal};
+
+	#[test]
+	fn should_match_exact_and_prefix_patterns() {
+		assert!(MethodPattern::from("eth_call").matches("eth_call"));
+		assert!(!MethodPattern::from("eth_call").matches("eth_callMany"));
+		assert!(MethodPattern::from("eth_*").matches("eth_sendTransaction"));
+		assert!(!MethodPattern::from("eth_*").matches("personal_unlockAccount"));
+		assert!(MethodPattern::from("*").matches("parity_killAccount"));
+	}
+
+	#[test]
+	fn should_allow_everything_without_rules() {
+		let policy = AccessPolicy::default();
+
+		assert!(policy.is_allowed(&Principal::Anonymous, "personal_unlockAccount"));
+		assert!(policy.is_allowed(&Principal::Token("abc".into()), "personal_unlockAccount"));
+	}
+
+	#[test]
+	fn should_apply_rules_per_principal() {
+		let mut policy = AccessPolicy::default();
+		policy.allow(Principal::Anonymous, vec!["eth_*", "net_version"]);
+		policy.allow(Principal::Token("admin".into()), vec!["*"]);
+		policy.allow(Principal::Ipc, vec!["personal_*"]);
+
+		assert!(policy.is_allowed(&Principal::Anonymous, "eth_blockNumber"));
+		assert!(!policy.is_allowed(&Principal::Anonymous, "personal_unlockAccount"));
+		assert!(policy.is_allowed(&Principal::Token("admin".into()), "personal_unlockAccount"));
+		// unknown token falls back to anonymous rules
+		assert!(policy.is_allowed(&Principal::Token("other".into()), "net_version"));
+		assert!(!policy.is_allowed(&Principal::Token("other".into()), "parity_killAccount"));
+		assert!(!policy.is_allowed(&Principal::Ipc, "eth_blockNumber"));
+		// transport without rules is denied
+		assert!(!policy.is_allowed(&Principal::Ui, "signer_confirmRequest"));
+	}
+
+	#[test]
+	fn should_deny_callers_without_token_when_only_tokens_have_rules() {
+		let mut policy = AccessPolicy::default();
+		policy.allow(Principal::Token("admin".into()), vec!["*"]);
+
+		assert!(policy.is_allowed(&Principal::Token("admin".into()), "personal_unlockAccount"));
+		assert!(!policy.is_allowed(&Principal::Anonymous, "eth_blockNumber"));
+		assert!(!policy.is_allowed(&Principal::Token("other".into()), "eth_blockNumber"));
+		assert!(!policy.is_allowed(&Principal::Ipc, "eth_blockNumber"));
+	}
+}
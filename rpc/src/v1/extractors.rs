@@ -0,0 +1,65 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Parity-specific metadata extractors.
+
+use std::str;
+use hyper;
+use jsonrpc_http_server::HttpMetaExtractor;
+use v1::{Metadata, Origin};
+
+/// Extracts metadata of requests coming through HTTP RPC server.
+pub struct RpcExtractor;
+
+impl HttpMetaExtractor<Metadata> for RpcExtractor {
+	fn read_metadata(&self, request: &hyper::server::Request<hyper::net::HttpStream>) -> Metadata {
+		let token = request.headers().get_raw("authorization")
+			.and_then(|values| values.first())
+			.and_then(|value| str::from_utf8(value).ok())
+			.and_then(bearer_token);
+
+		Metadata {
+			dapp_id: None,
+			origin: Origin::Rpc,
+			token: token,
+			remote: Some(request.remote_addr().ip()),
+		}
+	}
+}
+
+/// Parses value of `Authorization: Bearer <token>` header.
+fn bearer_token(header: &str) -> Option<String> {
+	let mut parts = header.trim().splitn(2, ' ');
+	match (parts.next(), parts.next()) {
+		(Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() => {
+			Some(token.trim().to_owned())
+		},
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::bearer_token;
+
+	#[test]
+	fn should_parse_bearer_token() {
+		assert_eq!(bearer_token("Bearer abc123"), Some("abc123".into()));
+		assert_eq!(bearer_token("bearer  abc123 "), Some("abc123".into()));
+		assert_eq!(bearer_token("Basic dXNlcjpwYXNz"), None);
+		assert_eq!(bearer_token("Bearer"), None);
+	}
+}
@@ -0,0 +1,85 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Block representation helpers shared by RPC implementations.
+
+use std::cmp;
+use rlp;
+use jsonrpc_core::Error;
+use ethcore::client::{BlockChainClient, BlockId};
+use v1::helpers::errors;
+use v1::types::{RichBlock, Block, BlockTransactions, Bytes};
+
+/// Proof that extra info of a block or uncle is available.
+pub const EXTRA_INFO_PROOF: &'static str = "Object exists in in blockchain (fetched earlier), extra_info is always available if object exists; qed";
+
+/// Returns RPC representation of given block or `None` if the block is unknown.
+pub fn rich_block<C: BlockChainClient + ?Sized>(client: &C, id: BlockId, include_txs: bool) -> Option<RichBlock> {
+	match (client.block(id.clone()), client.block_total_difficulty(id.clone())) {
+		(Some(block), Some(total_difficulty)) => {
+			let view = block.header_view();
+			Some(RichBlock {
+				block: Block {
+					hash: Some(view.sha3().into()),
+					size: Some(block.rlp().as_raw().len().into()),
+					parent_hash: view.parent_hash().into(),
+					uncles_hash: view.uncles_hash().into(),
+					author: view.author().into(),
+					miner: view.author().into(),
+					state_root: view.state_root().into(),
+					transactions_root: view.transactions_root().into(),
+					receipts_root: view.receipts_root().into(),
+					number: Some(view.number().into()),
+					gas_used: view.gas_used().into(),
+					gas_limit: view.gas_limit().into(),
+					logs_bloom: view.log_bloom().into(),
+					timestamp: view.timestamp().into(),
+					difficulty: view.difficulty().into(),
+					total_difficulty: total_difficulty.into(),
+					seal_fields: view.seal().into_iter().map(|f| rlp::decode(&f)).map(Bytes::new).collect(),
+					uncles: block.uncle_hashes().into_iter().map(Into::into).collect(),
+					transactions: match include_txs {
+						true => BlockTransactions::Full(block.view().localized_transactions().into_iter().map(Into::into).collect()),
+						false => BlockTransactions::Hashes(block.transaction_hashes().into_iter().map(Into::into).collect()),
+					},
+					extra_data: Bytes::new(view.extra_data()),
+				},
+				extra_info: client.block_extra_info(id).expect(EXTRA_INFO_PROOF),
+			})
+		},
+		_ => None
+	}
+}
+
+/// Returns an error if the body and receipts of given block were removed by history expiry.
+pub fn check_history<C: BlockChainClient + ?Sized>(client: &C, id: BlockId) -> Result<(), Error> {
+	let earliest_body = client.pruning_info().earliest_body;
+	match client.block_number(id) {
+		Some(number) if number > 0 && number < earliest_body => Err(errors::history_pruned()),
+		_ => Ok(()),
+	}
+}
+
+/// Returns an error if the body and receipts of any block in the given range were removed
+/// by history expiry.
+pub fn check_history_range<C: BlockChainClient + ?Sized>(client: &C, from: BlockId, to: BlockId) -> Result<(), Error> {
+	let earliest_body = client.pruning_info().earliest_body;
+	match (client.block_number(from), client.block_number(to)) {
+		// the genesis is never expired.
+		(Some(from), Some(to)) if cmp::max(from, 1) < earliest_body && to >= cmp::max(from, 1) => Err(errors::history_pruned()),
+		_ => Ok(()),
+	}
+}
@@ -25,6 +25,7 @@ use ethcore::miner::MinerService;
 use ethcore::client::MiningBlockChainClient;
 use ethcore::transaction::{Action, SignedTransaction, PendingTransaction, Transaction};
 use ethcore::account_provider::AccountProvider;
+use ethcore::account_provider::typed_data::TypedData;
 
 use jsonrpc_core::Error;
 use v1::helpers::{errors, TransactionRequest, FilledTransactionRequest, ConfirmationPayload};
@@ -34,6 +35,7 @@ use v1::types::{
 	ConfirmationPayload as RpcConfirmationPayload,
 	ConfirmationResponse,
 	SignRequest as RpcSignRequest,
+	SignTypedDataRequest as RpcSignTypedDataRequest,
 	DecryptRequest as RpcDecryptRequest,
 };
 
@@ -115,6 +117,14 @@ pub fn execute<C, M>(client: &C, miner: &M, accounts: &AccountProvider, payload:
 					.map(ConfirmationResponse::Signature)
 				)
 		},
+		ConfirmationPayload::SignTypedData(address, data) => {
+			let hash = typed_data_hash(&data)?;
+			signature(accounts, address, hash, pass)
+				.map(|result| result
+					.map(RpcH520::from)
+					.map(ConfirmationResponse::Signature)
+				)
+		},
 		ConfirmationPayload::Decrypt(address, data) => {
 			decrypt(accounts, address, data, pass)
 				.map(|result| result
@@ -136,6 +146,11 @@ fn signature(accounts: &AccountProvider, address: Address, hash: H256, password:
 	})
 }
 
+/// Returns the EIP-712 hash to sign for given typed data.
+pub fn typed_data_hash(data: &TypedData) -> Result<H256, Error> {
+	data.sign_hash().map_err(|e| errors::invalid_params("Invalid typed data.", e))
+}
+
 fn decrypt(accounts: &AccountProvider, address: Address, msg: Bytes, password: SignWith) -> Result<WithToken<Bytes>, Error> {
 	match password.clone() {
 		SignWith::Nothing => accounts.decrypt(address, None, &DEFAULT_MAC, &msg).map(WithToken::No),
@@ -227,7 +242,7 @@ pub fn fill_optional_fields<C, M>(request: TransactionRequest, client: &C, miner
 pub fn default_gas_price<C, M>(client: &C, miner: &M) -> U256
 	where C: MiningBlockChainClient, M: MinerService
 {
-	client.gas_price_median(100).unwrap_or_else(|| miner.sensible_gas_price())
+	miner.suggested_gas_price(client)
 }
 
 pub fn from_rpc<C, M>(payload: RpcConfirmationPayload, client: &C, miner: &M) -> ConfirmationPayload
@@ -246,5 +261,8 @@ pub fn from_rpc<C, M>(payload: RpcConfirmationPayload, client: &C, miner: &M) ->
 		RpcConfirmationPayload::Signature(RpcSignRequest { address, hash }) => {
 			ConfirmationPayload::Signature(address.into(), hash.into())
 		},
+		RpcConfirmationPayload::SignTypedData(RpcSignTypedDataRequest { address, data }) => {
+			ConfirmationPayload::SignTypedData(address.into(), data.into())
+		},
 	}
 }
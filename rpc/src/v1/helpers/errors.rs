@@ -21,6 +21,7 @@ macro_rules! rpc_unimplemented {
 }
 
 use std::fmt;
+use std::time::Duration;
 use rlp::DecoderError;
 use ethcore::error::{Error as EthcoreError, CallError, TransactionError};
 use ethcore::account_provider::{Error as AccountError};
@@ -49,6 +50,8 @@ mod codes {
 	pub const COMPILATION_ERROR: i64 = -32050;
 	pub const ENCRYPTION_ERROR: i64 = -32055;
 	pub const FETCH_ERROR: i64 = -32060;
+	pub const ACCESS_DENIED: i64 = -32065;
+	pub const RATE_LIMITED: i64 = -32066;
 }
 
 pub fn unimplemented(details: Option<String>) -> Error {
@@ -131,6 +134,14 @@ pub fn state_pruned() -> Error {
 	}
 }
 
+pub fn history_pruned() -> Error {
+	Error {
+		code: ErrorCode::ServerError(codes::UNSUPPORTED_REQUEST),
+		message: "This request is not supported because your node has removed old block bodies and receipts. Run without --history-retention.".into(),
+		data: None
+	}
+}
+
 pub fn exceptional() -> Error {
 	Error {
 		code: ErrorCode::ServerError(codes::EXCEPTION_ERROR),
@@ -219,6 +230,23 @@ pub fn from_fetch_error<T: fmt::Debug>(error: T) -> Error {
 	}
 }
 
+pub fn access_denied(method: &str) -> Error {
+	Error {
+		code: ErrorCode::ServerError(codes::ACCESS_DENIED),
+		message: format!("Access to method {} denied.", method),
+		data: None
+	}
+}
+
+pub fn rate_limited(retry_after: Duration) -> Error {
+	let secs = retry_after.as_secs() + if retry_after.subsec_nanos() > 0 { 1 } else { 0 };
+	Error {
+		code: ErrorCode::ServerError(codes::RATE_LIMITED),
+		message: format!("Request limit exceeded. Retry after {} seconds.", secs),
+		data: Some(Value::U64(secs)),
+	}
+}
+
 pub fn from_signing_error(error: AccountError) -> Error {
 	Error {
 		code: ErrorCode::ServerError(codes::ACCOUNT_LOCKED),
@@ -19,6 +19,7 @@ pub mod errors;
 
 pub mod dispatch;
 pub mod block_import;
+pub mod blocks;
 
 mod poll_manager;
 mod poll_filter;
@@ -15,6 +15,7 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use util::{Address, U256, Bytes, H256};
+use ethcore::account_provider::typed_data::TypedData;
 
 /// Transaction request coming from RPC
 #[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
@@ -93,7 +94,7 @@ pub struct CallRequest {
 }
 
 /// Confirmation object
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ConfirmationRequest {
 	/// Id of this confirmation
 	pub id: U256,
@@ -102,7 +103,7 @@ pub struct ConfirmationRequest {
 }
 
 /// Payload to confirm in Trusted Signer
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ConfirmationPayload {
 	/// Transaction
 	SendTransaction(FilledTransactionRequest),
@@ -110,6 +111,8 @@ pub enum ConfirmationPayload {
 	SignTransaction(FilledTransactionRequest),
 	/// Sign request
 	Signature(Address, H256),
+	/// Sign typed structured data request
+	SignTypedData(Address, TypedData),
 	/// Decrypt request
 	Decrypt(Address, Bytes),
 }
@@ -120,6 +123,7 @@ impl ConfirmationPayload {
 			ConfirmationPayload::SendTransaction(ref request) => request.from,
 			ConfirmationPayload::SignTransaction(ref request) => request.from,
 			ConfirmationPayload::Signature(ref address, _) => *address,
+			ConfirmationPayload::SignTypedData(ref address, _) => *address,
 			ConfirmationPayload::Decrypt(ref address, _) => *address,
 		}
 	}
@@ -33,6 +33,7 @@ use ethash::SeedHashCompute;
 use ethcore::account_provider::AccountProvider;
 use ethcore::block::IsBlock;
 use ethcore::client::{MiningBlockChainClient, BlockId, TransactionId, UncleId};
+use ethcore::encoded;
 use ethcore::ethereum::Ethash;
 use ethcore::filter::Filter as EthcoreFilter;
 use ethcore::header::{Header as BlockHeader, BlockNumber as EthBlockNumber};
@@ -54,10 +55,9 @@ use v1::types::{
 use v1::helpers::{CallRequest as CRequest, errors, limit_logs};
 use v1::helpers::dispatch::{dispatch_transaction, default_gas_price};
 use v1::helpers::block_import::is_major_importing;
+use v1::helpers::blocks::{rich_block, check_history, check_history_range, EXTRA_INFO_PROOF};
 use v1::metadata::Metadata;
 
-const EXTRA_INFO_PROOF: &'static str = "Object exists in in blockchain (fetched earlier), extra_info is always available if object exists; qed";
-
 /// Eth RPC options
 pub struct EthClientOptions {
 	/// Returns receipt from pending blocks
@@ -124,46 +124,28 @@ impl<C, SN: ?Sized, S: ?Sized, M, EM> EthClient<C, SN, S, M, EM> where
 
 	fn block(&self, id: BlockId, include_txs: bool) -> Result<Option<RichBlock>, Error> {
 		let client = take_weak!(self.client);
-		match (client.block(id.clone()), client.block_total_difficulty(id)) {
-			(Some(block), Some(total_difficulty)) => {
-				let view = block.header_view();
-				Ok(Some(RichBlock {
-					block: Block {
-						hash: Some(view.sha3().into()),
-						size: Some(block.rlp().as_raw().len().into()),
-						parent_hash: view.parent_hash().into(),
-						uncles_hash: view.uncles_hash().into(),
-						author: view.author().into(),
-						miner: view.author().into(),
-						state_root: view.state_root().into(),
-						transactions_root: view.transactions_root().into(),
-						receipts_root: view.receipts_root().into(),
-						number: Some(view.number().into()),
-						gas_used: view.gas_used().into(),
-						gas_limit: view.gas_limit().into(),
-						logs_bloom: view.log_bloom().into(),
-						timestamp: view.timestamp().into(),
-						difficulty: view.difficulty().into(),
-						total_difficulty: total_difficulty.into(),
-						seal_fields: view.seal().into_iter().map(|f| rlp::decode(&f)).map(Bytes::new).collect(),
-						uncles: block.uncle_hashes().into_iter().map(Into::into).collect(),
-						transactions: match include_txs {
-							true => BlockTransactions::Full(block.view().localized_transactions().into_iter().map(Into::into).collect()),
-							false => BlockTransactions::Hashes(block.transaction_hashes().into_iter().map(Into::into).collect()),
-						},
-						extra_data: Bytes::new(view.extra_data()),
-					},
-					extra_info: client.block_extra_info(id.clone()).expect(EXTRA_INFO_PROOF),
-				}))
-			},
-			_ => Ok(None)
+		match rich_block(&*client, id.clone(), include_txs) {
+			Some(block) => Ok(Some(block)),
+			None => check_history(&*client, id).map(|_| None),
+		}
+	}
+
+	fn block_body_info<F, T>(&self, id: BlockId, f: F) -> Result<Option<T>, Error> where F: FnOnce(encoded::Block) -> T {
+		let client = take_weak!(self.client);
+		match client.block(id.clone()) {
+			Some(block) => Ok(Some(f(block))),
+			None => check_history(&*client, id).map(|_| None),
 		}
 	}
 
 	fn transaction(&self, id: TransactionId) -> Result<Option<Transaction>, Error> {
-		match take_weak!(self.client).transaction(id) {
+		let client = take_weak!(self.client);
+		match client.transaction(id.clone()) {
 			Some(t) => Ok(Some(Transaction::from(t))),
-			None => Ok(None),
+			None => match id {
+				TransactionId::Location(block, _) => check_history(&*client, block).map(|_| None),
+				_ => Ok(None),
+			},
 		}
 	}
 
@@ -423,10 +405,7 @@ impl<C, SN: ?Sized, S: ?Sized, M, EM> Eth for EthClient<C, SN, S, M, EM> where
 
 	fn block_transaction_count_by_hash(&self, hash: RpcH256) -> Result<Option<RpcU256>, Error> {
 		self.active()?;
-		Ok(
-			take_weak!(self.client).block(BlockId::Hash(hash.into()))
-				.map(|block| block.transactions_count().into())
-		)
+		self.block_body_info(BlockId::Hash(hash.into()), |block| block.transactions_count().into())
 	}
 
 	fn block_transaction_count_by_number(&self, num: BlockNumber) -> Result<Option<RpcU256>, Error> {
@@ -436,20 +415,14 @@ impl<C, SN: ?Sized, S: ?Sized, M, EM> Eth for EthClient<C, SN, S, M, EM> where
 			BlockNumber::Pending => Ok(Some(
 				take_weak!(self.miner).status().transactions_in_pending_block.into()
 			)),
-			_ => Ok(
-				take_weak!(self.client).block(num.into())
-					.map(|block| block.transactions_count().into())
-				)
+			_ => self.block_body_info(num.into(), |block| block.transactions_count().into()),
 		}
 	}
 
 	fn block_uncles_count_by_hash(&self, hash: RpcH256) -> Result<Option<RpcU256>, Error> {
 		self.active()?;
 
-		Ok(
-			take_weak!(self.client).block(BlockId::Hash(hash.into()))
-				.map(|block| block.uncles_count().into())
-		)
+		self.block_body_info(BlockId::Hash(hash.into()), |block| block.uncles_count().into())
 	}
 
 	fn block_uncles_count_by_number(&self, num: BlockNumber) -> Result<Option<RpcU256>, Error> {
@@ -457,10 +430,7 @@ impl<C, SN: ?Sized, S: ?Sized, M, EM> Eth for EthClient<C, SN, S, M, EM> where
 
 		match num {
 			BlockNumber::Pending => Ok(Some(0.into())),
-			_ => Ok(
-				take_weak!(self.client).block(num.into())
-					.map(|block| block.uncles_count().into())
-			),
+			_ => self.block_body_info(num.into(), |block| block.uncles_count().into()),
 		}
 	}
 
@@ -556,6 +526,7 @@ impl<C, SN: ?Sized, S: ?Sized, M, EM> Eth for EthClient<C, SN, S, M, EM> where
 	fn logs(&self, filter: Filter) -> Result<Vec<Log>, Error> {
 		let include_pending = filter.to_block == Some(BlockNumber::Pending);
 		let filter: EthcoreFilter = filter.into();
+		check_history_range(&*take_weak!(self.client), filter.from_block.clone(), filter.to_block.clone())?;
 		let mut logs = take_weak!(self.client).logs(filter.clone())
 			.into_iter()
 			.map(From::from)
@@ -26,6 +26,7 @@ use util::Mutex;
 use v1::traits::EthFilter;
 use v1::types::{BlockNumber, Index, Filter, FilterChanges, Log, H256 as RpcH256, U256 as RpcU256};
 use v1::helpers::{PollFilter, PollManager, limit_logs};
+use v1::helpers::blocks::check_history_range;
 use v1::impls::eth::pending_logs;
 
 /// Eth filter rpc implementation.
@@ -187,6 +188,7 @@ impl<C, M> EthFilter for EthFilterClient<C, M>
 			Some(&PollFilter::Logs(ref _block_number, ref _previous_log, ref filter)) => {
 				let include_pending = filter.to_block == Some(BlockNumber::Pending);
 				let filter: EthcoreFilter = filter.clone().into();
+				check_history_range(&*take_weak!(self.client), filter.from_block.clone(), filter.to_block.clone())?;
 				let mut logs = take_weak!(self.client).logs(filter.clone())
 					.into_iter()
 					.map(From::from)
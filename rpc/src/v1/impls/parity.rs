@@ -27,7 +27,7 @@ use ethkey::{Brain, Generator};
 use ethstore::random_phrase;
 use ethsync::{SyncProvider, ManageNetwork};
 use ethcore::miner::MinerService;
-use ethcore::client::{MiningBlockChainClient};
+use ethcore::client::{MiningBlockChainClient, BlockId};
 use ethcore::mode::Mode;
 use ethcore::account_provider::AccountProvider;
 use updater::{Service as UpdateService};
@@ -37,13 +37,23 @@ use jsonrpc_macros::Trailing;
 use v1::traits::Parity;
 use v1::types::{
 	Bytes, U256, H160, H256, H512,
-	Peers, Transaction, RpcSettings, Histogram,
-	TransactionStats, LocalTransactionStatus,
+	Peers, Transaction, RpcSettings, Histogram, FeeHistory, AddressTransaction,
+	Receipt, BlockWithReceipts, TransactionStats, LocalTransactionStatus,
 	BlockNumber, ConsensusCapability, VersionInfo,
-	OperationsInfo, DappId, ChainStatus,
+	OperationsInfo, DappId, ChainStatus, DatabaseStats,
 };
 use v1::helpers::{errors, SigningQueue, SignerService, NetworkSettings};
 use v1::helpers::dispatch::DEFAULT_MAC;
+use v1::helpers::blocks::{rich_block, check_history};
+
+/// Maximal number of blocks returned by `parity_feeHistory`.
+const MAX_FEE_HISTORY_BLOCKS: u64 = 1024;
+
+/// Maximal number of blocks returned by `parity_getBlocksWithReceipts`.
+const MAX_BLOCKS_WITH_RECEIPTS: u64 = 128;
+
+/// Maximal number of transactions returned by `parity_addressTransactions`.
+const MAX_ADDRESS_TRANSACTIONS: u64 = 1024;
 
 /// Parity implementation.
 pub struct ParityClient<C, M, S: ?Sized, U> where
@@ -203,7 +213,8 @@ impl<C, M, S: ?Sized, U> Parity for ParityClient<C, M, S, U> where
 			active: sync_status.num_active_peers,
 			connected: sync_status.num_peers,
 			max: sync_status.current_max_peers(net_config.min_peers, net_config.max_peers),
-			peers: peers
+			peers: peers,
+			compression: sync.compression_stats().into(),
 		})
 	}
 
@@ -251,6 +262,58 @@ impl<C, M, S: ?Sized, U> Parity for ParityClient<C, M, S, U> where
 		take_weak!(self.client).gas_price_histogram(100, 10).ok_or_else(errors::not_enough_data).map(Into::into)
 	}
 
+	fn fee_history(&self, block_count: u64, newest_block: BlockNumber, percentiles: Trailing<Vec<f64>>) -> Result<FeeHistory, Error> {
+		self.active()?;
+
+		let percentiles = percentiles.0;
+		if block_count == 0 || block_count > MAX_FEE_HISTORY_BLOCKS {
+			return Err(errors::invalid_params("blockCount", format!("Expected value between 1 and {}", MAX_FEE_HISTORY_BLOCKS)));
+		}
+		if percentiles.iter().any(|p| *p < 0.0 || *p > 100.0) || percentiles.windows(2).any(|w| w[0] > w[1]) {
+			return Err(errors::invalid_params("percentiles", "Expected ascending values between 0 and 100"));
+		}
+
+		take_weak!(self.client)
+			.fee_history(block_count, newest_block.into(), &percentiles)
+			.ok_or_else(errors::unknown_block)
+			.map(Into::into)
+	}
+
+	fn block_receipts(&self, block: BlockNumber) -> Result<Option<Vec<Receipt>>, Error> {
+		self.active()?;
+
+		let client = take_weak!(self.client);
+		match client.localized_block_receipts(block.clone().into()) {
+			Some(receipts) => Ok(Some(receipts.into_iter().map(Into::into).collect())),
+			None => check_history(&*client, block.into()).map(|_| None),
+		}
+	}
+
+	fn blocks_with_receipts(&self, from: BlockNumber, to: BlockNumber) -> Result<Vec<BlockWithReceipts>, Error> {
+		self.active()?;
+
+		let client = take_weak!(self.client);
+		let from = client.block_number(from.into()).ok_or_else(errors::unknown_block)?;
+		let to = client.block_number(to.into()).ok_or_else(errors::unknown_block)?;
+		if from > to {
+			return Err(errors::invalid_params("fromBlock", "Expected fromBlock not greater than toBlock"));
+		}
+		if to - from >= MAX_BLOCKS_WITH_RECEIPTS {
+			return Err(errors::invalid_params("toBlock", format!("Expected at most {} blocks in range", MAX_BLOCKS_WITH_RECEIPTS)));
+		}
+
+		(from..to + 1).map(|number| {
+			let id = BlockId::Number(number);
+			match (rich_block(&*client, id.clone(), true), client.localized_block_receipts(id.clone())) {
+				(Some(block), Some(receipts)) => Ok(BlockWithReceipts {
+					block: block,
+					receipts: receipts.into_iter().map(Into::into).collect(),
+				}),
+				_ => check_history(&*client, id).and_then(|_| Err(errors::unknown_block())),
+			}
+		}).collect()
+	}
+
 	fn unsigned_transactions_count(&self) -> Result<usize, Error> {
 		self.active()?;
 
@@ -288,6 +351,18 @@ impl<C, M, S: ?Sized, U> Parity for ParityClient<C, M, S, U> where
 			.map(|a| a.into_iter().map(Into::into).collect()))
 	}
 
+	fn address_transactions(&self, address: H160, count: u64, after: Option<H256>) -> Result<Option<Vec<AddressTransaction>>, Error> {
+		self.active()?;
+
+		if count > MAX_ADDRESS_TRANSACTIONS {
+			return Err(errors::invalid_params("count", format!("Expected at most {}", MAX_ADDRESS_TRANSACTIONS)));
+		}
+
+		Ok(take_weak!(self.client)
+			.address_transactions(&address.into(), after.map(Into::into).as_ref(), count)
+			.map(|t| t.into_iter().map(Into::into).collect()))
+	}
+
 	fn encrypt_message(&self, key: H512, phrase: Bytes) -> Result<Bytes, Error> {
 		self.active()?;
 
@@ -379,6 +454,10 @@ impl<C, M, S: ?Sized, U> Parity for ParityClient<C, M, S, U> where
 		take_weak!(self.sync).enode().ok_or_else(errors::network_disabled)
 	}
 
+	fn enr(&self) -> Result<String, Error> {
+		take_weak!(self.sync).enr().ok_or_else(errors::network_disabled)
+	}
+
 	fn consensus_capability(&self) -> Result<ConsensusCapability, Error> {
 		self.active()?;
 		let updater = take_weak!(self.updater);
@@ -409,4 +488,10 @@ impl<C, M, S: ?Sized, U> Parity for ParityClient<C, M, S, U> where
 			block_gap: gap.map(|(x, y)| (x.into(), y.into())),
 		})
 	}
+
+	fn db_stats(&self) -> Result<DatabaseStats, Error> {
+		self.active()?;
+
+		Ok(take_weak!(self.client).database_stats().into())
+	}
 }
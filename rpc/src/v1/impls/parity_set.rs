@@ -21,16 +21,17 @@ use std::sync::{Arc, Weak};
 use ethcore::miner::MinerService;
 use ethcore::client::MiningBlockChainClient;
 use ethcore::mode::Mode;
-use ethsync::ManageNetwork;
+use ethsync::{ManageNetwork, MAX_BAN_DURATION_SEC};
 use fetch::{self, Fetch};
 use futures::{self, BoxFuture, Future};
 use util::sha3;
 use updater::{Service as UpdateService};
 
 use jsonrpc_core::Error;
+use jsonrpc_macros::Trailing;
 use v1::helpers::errors;
 use v1::traits::ParitySet;
-use v1::types::{Bytes, H160, H256, U256, ReleaseInfo};
+use v1::types::{Bytes, H160, H256, U256, ReleaseInfo, PeerReputation, PeerGroup};
 
 /// Parity-specific rpc interface for operations altering the settings.
 pub struct ParitySetClient<C, M, U, F=fetch::Client> where
@@ -132,24 +133,79 @@ impl<C, M, U, F> ParitySet for ParitySetClient<C, M, U, F> where
 		Ok(true)
 	}
 
-	fn add_reserved_peer(&self, peer: String) -> Result<bool, Error> {
+	fn add_reserved_peer(&self, peer: String, group: Trailing<String>) -> Result<bool, Error> {
 		self.active()?;
 
-		match take_weak!(self.net).add_reserved_peer(peer) {
+		let net = take_weak!(self.net);
+		let result = if group.0.is_empty() {
+			net.add_reserved_peer(peer)
+		} else {
+			net.add_peer_group_node(group.0, peer)
+		};
+		match result {
 			Ok(()) => Ok(true),
 			Err(e) => Err(errors::invalid_params("Peer address", e)),
 		}
 	}
 
-	fn remove_reserved_peer(&self, peer: String) -> Result<bool, Error> {
+	fn remove_reserved_peer(&self, peer: String, group: Trailing<String>) -> Result<bool, Error> {
 		self.active()?;
 
-		match take_weak!(self.net).remove_reserved_peer(peer) {
+		let net = take_weak!(self.net);
+		let result = if group.0.is_empty() {
+			net.remove_reserved_peer(peer)
+		} else {
+			net.remove_peer_group_node(group.0, peer)
+		};
+		match result {
 			Ok(()) => Ok(true),
 			Err(e) => Err(errors::invalid_params("Peer address", e)),
 		}
 	}
 
+	fn set_peer_group(&self, name: String, min_slots: u32, max_slots: u32, trusted: bool) -> Result<bool, Error> {
+		self.active()?;
+
+		match take_weak!(self.net).set_peer_group(name, min_slots, max_slots, trusted) {
+			Ok(()) => Ok(true),
+			Err(e) => Err(errors::invalid_params("Peer group", e)),
+		}
+	}
+
+	fn remove_peer_group(&self, name: String) -> Result<bool, Error> {
+		self.active()?;
+
+		match take_weak!(self.net).remove_peer_group(name) {
+			Ok(()) => Ok(true),
+			Err(e) => Err(errors::invalid_params("Peer group", e)),
+		}
+	}
+
+	fn peer_groups(&self) -> Result<Vec<PeerGroup>, Error> {
+		self.active()?;
+
+		Ok(take_weak!(self.net).peer_groups().into_iter().map(Into::into).collect())
+	}
+
+	fn ban_peer(&self, peer: String, duration: u64) -> Result<bool, Error> {
+		self.active()?;
+
+		if duration > MAX_BAN_DURATION_SEC {
+			return Err(errors::invalid_params("Ban duration", format!("Longer than {} seconds", MAX_BAN_DURATION_SEC)));
+		}
+
+		match take_weak!(self.net).ban_peer(peer, duration) {
+			Ok(()) => Ok(true),
+			Err(e) => Err(errors::invalid_params("Peer address", e)),
+		}
+	}
+
+	fn peer_reputation(&self) -> Result<Vec<PeerReputation>, Error> {
+		self.active()?;
+
+		Ok(take_weak!(self.net).peer_reputations().into_iter().map(Into::into).collect())
+	}
+
 	fn drop_non_reserved_peers(&self) -> Result<bool, Error> {
 		self.active()?;
 
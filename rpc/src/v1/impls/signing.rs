@@ -36,6 +36,7 @@ use v1::types::{
 	Either as RpcEither,
 	RichRawTransaction as RpcRichRawTransaction,
 	TransactionRequest as RpcTransactionRequest,
+	TypedData as RpcTypedData,
 	ConfirmationPayload as RpcConfirmationPayload,
 	ConfirmationResponse as RpcConfirmationResponse
 };
@@ -202,6 +203,23 @@ impl<C: 'static, M: 'static> EthSigning for SigningQueueClient<C, M> where
 		p.then(|result| futures::done(result.expect("Ready is never dropped nor canceled."))).boxed()
 	}
 
+	fn sign_typed_data(&self, address: RpcH160, data: RpcTypedData) -> BoxFuture<RpcH520, Error> {
+		let res = self.active()
+			.and_then(|_| dispatch::typed_data_hash(&data.clone().into()))
+			.and_then(|_| self.dispatch(RpcConfirmationPayload::SignTypedData((address, data).into())));
+
+		let (ready, p) = futures::oneshot();
+		self.handle_dispatch(res, |response| {
+			match response {
+				Ok(RpcConfirmationResponse::Signature(signature)) => ready.complete(Ok(signature)),
+				Err(e) => ready.complete(Err(e)),
+				e => ready.complete(Err(errors::internal("Unexpected result.", e))),
+			}
+		});
+
+		p.then(|result| futures::done(result.expect("Ready is never dropped nor canceled."))).boxed()
+	}
+
 	fn send_transaction(&self, request: RpcTransactionRequest) -> BoxFuture<RpcH256, Error> {
 		let res = self.active().and_then(|_| self.dispatch(RpcConfirmationPayload::SendTransaction(request)));
 
@@ -34,6 +34,7 @@ use v1::types::{
 	Either as RpcEither,
 	RichRawTransaction as RpcRichRawTransaction,
 	TransactionRequest as RpcTransactionRequest,
+	TypedData as RpcTypedData,
 	ConfirmationPayload as RpcConfirmationPayload,
 	ConfirmationResponse as RpcConfirmationResponse,
 };
@@ -96,6 +97,16 @@ impl<C: 'static, M: 'static> EthSigning for SigningUnsafeClient<C, M> where
 		futures::done(result).boxed()
 	}
 
+	fn sign_typed_data(&self, address: RpcH160, data: RpcTypedData) -> BoxFuture<RpcH520, Error> {
+		let result = match self.handle(RpcConfirmationPayload::SignTypedData((address, data).into())) {
+			Ok(RpcConfirmationResponse::Signature(signature)) => Ok(signature),
+			Err(e) => Err(e),
+			e => Err(errors::internal("Unexpected result", e)),
+		};
+
+		futures::done(result).boxed()
+	}
+
 	fn send_transaction(&self, request: RpcTransactionRequest) -> BoxFuture<RpcH256, Error> {
 		let result = match self.handle(RpcConfirmationPayload::SendTransaction(request)) {
 			Ok(RpcConfirmationResponse::SendTransaction(hash)) => Ok(hash),
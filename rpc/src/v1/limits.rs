@@ -0,0 +1,351 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-client request rate limits and execution budgets for expensive methods.
+
+use std::sync::Arc;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
+
+use futures::{self, BoxFuture, Future};
+use jsonrpc_core::{Params, Value, Error, RemoteProcedure, RpcMethod};
+use util::Mutex;
+use v1::access::MethodPattern;
+use v1::helpers::errors;
+use v1::metadata::Metadata;
+
+/// Maximal number of clients tracked at once. Above it expired entries are dropped
+/// first and then the client with the oldest window is evicted.
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+/// Every expensive call reserves this fraction of the budget while it runs, so a client
+/// can't start more expensive calls at once than the budget allows.
+const EXPENSIVE_CALL_SHARE: u32 = 8;
+
+/// Rate limiting configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimits {
+	/// Maximal number of requests a client can make within the window (0 = unlimited)
+	pub requests: u32,
+	/// Methods which execution time is accounted against the budget
+	pub expensive_methods: Vec<MethodPattern>,
+	/// Execution time a client can spend in expensive methods within the window (zero = unlimited)
+	pub expensive_budget: Duration,
+	/// Accounting window
+	pub window: Duration,
+}
+
+impl Default for RateLimits {
+	fn default() -> Self {
+		RateLimits {
+			requests: 0,
+			expensive_methods: Vec::new(),
+			expensive_budget: Duration::from_secs(0),
+			window: Duration::from_secs(60),
+		}
+	}
+}
+
+impl RateLimits {
+	/// Returns true if any of the limits is set.
+	pub fn is_enabled(&self) -> bool {
+		self.requests > 0 || (self.expensive_budget > Duration::from_secs(0) && !self.expensive_methods.is_empty())
+	}
+
+	fn is_expensive(&self, method: &str) -> bool {
+		self.expensive_budget > Duration::from_secs(0) && self.expensive_methods.iter().any(|m| m.matches(method))
+	}
+}
+
+/// Client identity used for accounting.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+	Token(String),
+	Address(IpAddr),
+	Unknown,
+}
+
+#[derive(Debug)]
+struct Usage {
+	since: Instant,
+	requests: u32,
+	/// Time spent in finished expensive calls and reserved for running ones.
+	spent: Duration,
+}
+
+impl Usage {
+	fn new(now: Instant) -> Self {
+		Usage {
+			since: now,
+			requests: 0,
+			spent: Duration::from_secs(0),
+		}
+	}
+}
+
+/// Part of the expensive budget held by a running call.
+#[derive(Debug, PartialEq)]
+struct Reservation {
+	/// Start of the window the reservation was made in.
+	window: Instant,
+	amount: Duration,
+}
+
+/// Tracks requests of every client and rejects the ones exceeding configured limits.
+#[derive(Debug)]
+pub struct RateLimiter {
+	limits: RateLimits,
+	tokens: HashSet<String>,
+	usage: Mutex<HashMap<Client, Usage>>,
+}
+
+impl RateLimiter {
+	/// Creates new rate limiter. Callers presenting one of the `tokens` are accounted
+	/// per token, everyone else per remote address.
+	pub fn new(limits: RateLimits, tokens: HashSet<String>) -> Self {
+		RateLimiter {
+			limits: limits,
+			tokens: tokens,
+			usage: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Returns true if the limiter restricts anything.
+	pub fn is_enabled(&self) -> bool {
+		self.limits.is_enabled()
+	}
+
+	/// Wraps methods of given delegate, so that every call is accounted against the limits.
+	pub fn limit<I>(limiter: &Arc<Self>, delegate: I) -> Vec<(String, RemoteProcedure<Metadata>)> where
+		I: IntoIterator<Item=(String, RemoteProcedure<Metadata>)>,
+	{
+		delegate.into_iter().map(|(name, procedure)| {
+			let procedure = match procedure {
+				RemoteProcedure::Method(method) if limiter.is_enabled() => RemoteProcedure::Method(Arc::new(LimitedMethod {
+					expensive: limiter.limits.is_expensive(&name),
+					limiter: limiter.clone(),
+					method: method,
+				})),
+				procedure => procedure,
+			};
+			(name, procedure)
+		}).collect()
+	}
+
+	/// Resolves the identity a request is accounted against. Unknown tokens are ignored,
+	/// otherwise a fresh token on every request would get a fresh budget.
+	fn client(&self, meta: &Metadata) -> Client {
+		match (meta.token.as_ref(), meta.remote) {
+			(Some(token), _) if self.tokens.contains(token) => Client::Token(token.clone()),
+			(_, Some(address)) => Client::Address(address),
+			(_, None) => Client::Unknown,
+		}
+	}
+
+	/// Registers new request and reserves a share of the budget for an expensive one.
+	/// Returns time after which the client may retry if the request is over the limit.
+	fn check(&self, client: &Client, expensive: bool, now: Instant) -> Result<Option<Reservation>, Duration> {
+		let window = self.limits.window;
+		let mut usage = self.usage.lock();
+
+		if usage.len() >= MAX_TRACKED_CLIENTS && !usage.contains_key(client) {
+			usage.retain(|_, u| now.duration_since(u.since) < window);
+			if usage.len() >= MAX_TRACKED_CLIENTS {
+				let oldest = usage.iter().min_by_key(|&(_, u)| u.since).map(|(c, _)| c.clone());
+				if let Some(oldest) = oldest {
+					usage.remove(&oldest);
+				}
+			}
+		}
+
+		let entry = usage.entry(client.clone()).or_insert_with(|| Usage::new(now));
+		let elapsed = now.duration_since(entry.since);
+		if elapsed >= window {
+			*entry = Usage::new(now);
+		}
+		let retry_after = window - now.duration_since(entry.since);
+
+		if self.limits.requests > 0 && entry.requests >= self.limits.requests {
+			return Err(retry_after);
+		}
+		if expensive && entry.spent >= self.limits.expensive_budget {
+			return Err(retry_after);
+		}
+
+		entry.requests += 1;
+		if !expensive {
+			return Ok(None);
+		}
+
+		let budget = self.limits.expensive_budget;
+		let left = budget - entry.spent;
+		let amount = ::std::cmp::min(budget / EXPENSIVE_CALL_SHARE, left);
+		entry.spent = entry.spent + amount;
+		Ok(Some(Reservation {
+			window: entry.since,
+			amount: amount,
+		}))
+	}
+
+	/// Replaces the reservation of a finished expensive call with its execution time.
+	/// Reservations of a past window were dropped with it, so the time is charged in full.
+	fn spend(&self, client: &Client, reservation: Reservation, time: Duration) {
+		if let Some(entry) = self.usage.lock().get_mut(client) {
+			let spent = entry.spent + time;
+			entry.spent = match entry.since == reservation.window {
+				true if spent > reservation.amount => spent - reservation.amount,
+				true => Duration::from_secs(0),
+				false => spent,
+			};
+		}
+	}
+}
+
+struct LimitedMethod {
+	expensive: bool,
+	limiter: Arc<RateLimiter>,
+	method: Arc<RpcMethod<Metadata>>,
+}
+
+impl RpcMethod<Metadata> for LimitedMethod {
+	fn call(&self, params: Params, meta: Metadata) -> BoxFuture<Value, Error> {
+		let client = self.limiter.client(&meta);
+		let start = Instant::now();
+		let reservation = match self.limiter.check(&client, self.expensive, start) {
+			Ok(reservation) => reservation,
+			Err(retry_after) => return futures::failed(errors::rate_limited(retry_after)).boxed(),
+		};
+
+		let result = self.method.call(params, meta);
+		let reservation = match reservation {
+			Some(reservation) => reservation,
+			None => return result,
+		};
+
+		let limiter = self.limiter.clone();
+		result.then(move |result| {
+			limiter.spend(&client, reservation, start.elapsed());
+			result
+		}).boxed()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+	use v1::metadata::Metadata;
+	use super::{RateLimiter, RateLimits, Client, Reservation, MAX_TRACKED_CLIENTS};
+
+	fn limiter(requests: u32, budget: u64) -> RateLimiter {
+		RateLimiter::new(RateLimits {
+			requests: requests,
+			expensive_methods: vec!["eth_getLogs".into(), "trace_*".into()],
+			expensive_budget: Duration::from_millis(budget),
+			window: Duration::from_secs(60),
+		}, vec!["known".to_owned()].into_iter().collect())
+	}
+
+	#[test]
+	fn should_detect_expensive_methods() {
+		let limits = limiter(0, 100).limits;
+
+		assert!(limits.is_enabled());
+		assert!(limits.is_expensive("eth_getLogs"));
+		assert!(limits.is_expensive("trace_filter"));
+		assert!(!limits.is_expensive("eth_blockNumber"));
+		assert!(!limiter(0, 0).is_enabled());
+	}
+
+	#[test]
+	fn should_limit_requests_per_client() {
+		let limiter = limiter(2, 0);
+		let now = Instant::now();
+		let client = Client::Token("a".into());
+
+		assert_eq!(limiter.check(&client, false, now), Ok(None));
+		assert_eq!(limiter.check(&client, false, now), Ok(None));
+		assert_eq!(limiter.check(&client, false, now + Duration::from_secs(15)), Err(Duration::from_secs(45)));
+		// other clients are not affected
+		assert_eq!(limiter.check(&Client::Unknown, false, now), Ok(None));
+		// new window starts
+		assert_eq!(limiter.check(&client, false, now + Duration::from_secs(60)), Ok(None));
+	}
+
+	#[test]
+	fn should_account_unknown_tokens_by_address() {
+		let limiter = limiter(1, 0);
+		let meta = |token: &str| Metadata {
+			token: Some(token.into()),
+			remote: Some("127.0.0.1".parse().unwrap()),
+			..Default::default()
+		};
+
+		assert_eq!(limiter.client(&meta("known")), Client::Token("known".into()));
+		assert_eq!(limiter.client(&meta("random1")), Client::Address("127.0.0.1".parse().unwrap()));
+		assert_eq!(limiter.client(&meta("random2")), limiter.client(&meta("random1")));
+	}
+
+	#[test]
+	fn should_cap_tracked_clients() {
+		let limiter = limiter(1, 0);
+		let now = Instant::now();
+
+		for i in 0..MAX_TRACKED_CLIENTS + 10 {
+			let since = now + Duration::from_millis(i as u64);
+			assert_eq!(limiter.check(&Client::Token(i.to_string()), false, since), Ok(None));
+		}
+
+		let usage = limiter.usage.lock();
+		assert_eq!(usage.len(), MAX_TRACKED_CLIENTS);
+		// the oldest clients were evicted
+		assert!(!usage.contains_key(&Client::Token("0".into())));
+		assert!(usage.contains_key(&Client::Token((MAX_TRACKED_CLIENTS + 9).to_string())));
+	}
+
+	#[test]
+	fn should_enforce_expensive_budget() {
+		let limiter = limiter(0, 100);
+		let now = Instant::now();
+		let client = Client::Unknown;
+
+		let reservation = limiter.check(&client, true, now).unwrap().unwrap();
+		limiter.spend(&client, reservation, Duration::from_millis(150));
+		assert_eq!(limiter.check(&client, false, now), Ok(None));
+		assert_eq!(limiter.check(&client, true, now), Err(Duration::from_secs(60)));
+		assert!(limiter.check(&client, true, now + Duration::from_secs(61)).unwrap().is_some());
+	}
+
+	#[test]
+	fn should_reserve_budget_of_running_calls() {
+		let limiter = limiter(0, 80);
+		let now = Instant::now();
+		let client = Client::Unknown;
+
+		let reservations: Vec<_> = (0..8).map(|_| limiter.check(&client, true, now).unwrap().unwrap()).collect();
+		assert_eq!(reservations[0], Reservation { window: now, amount: Duration::from_millis(10) });
+		// the whole budget is held by running calls.
+		assert_eq!(limiter.check(&client, true, now), Err(Duration::from_secs(60)));
+
+		// a call finishing early returns the rest of its reservation.
+		let mut reservations = reservations.into_iter();
+		limiter.spend(&client, reservations.next().unwrap(), Duration::from_millis(4));
+		assert_eq!(limiter.check(&client, true, now), Ok(Some(Reservation { window: now, amount: Duration::from_millis(6) })));
+		// a call running longer is charged in full.
+		limiter.spend(&client, reservations.next().unwrap(), Duration::from_millis(20));
+		assert_eq!(limiter.check(&client, true, now), Err(Duration::from_secs(60)));
+	}
+}
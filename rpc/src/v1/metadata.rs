@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::net::IpAddr;
 use jsonrpc_core;
 
 /// RPC methods metadata.
@@ -23,6 +24,10 @@ pub struct Metadata {
 	pub dapp_id: Option<String>,
 	/// Request origin
 	pub origin: Origin,
+	/// Bearer token presented by the caller
+	pub token: Option<String>,
+	/// Remote address of the caller
+	pub remote: Option<IpAddr>,
 }
 
 /// RPC request origin
@@ -22,6 +22,9 @@
 mod helpers;
 mod impls;
 mod metadata;
+mod access;
+mod extractors;
+mod limits;
 
 pub mod traits;
 pub mod tests;
@@ -31,3 +34,6 @@ pub use self::traits::{Web3, Eth, EthFilter, EthSigning, Net, Parity, ParityAcco
 pub use self::impls::*;
 pub use self::helpers::{SigningQueue, SignerService, ConfirmationsQueue, NetworkSettings, block_import};
 pub use self::metadata::{Metadata, Origin};
+pub use self::access::{AccessPolicy, AccessControl, MethodPattern, Principal};
+pub use self::extractors::RpcExtractor;
+pub use self::limits::{RateLimiter, RateLimits};
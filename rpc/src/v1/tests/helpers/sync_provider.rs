@@ -18,7 +18,8 @@
 
 use std::collections::BTreeMap;
 use util::{H256, RwLock};
-use ethsync::{SyncProvider, EthProtocolInfo, SyncStatus, SyncState, PeerInfo, TransactionStats};
+use ethsync::{SyncProvider, EthProtocolInfo, SyncStatus, SyncState, PeerInfo, PeerTraffic, TransactionStats,
+	CompressionStats};
 
 /// TestSyncProvider config.
 pub struct Config {
@@ -53,6 +54,9 @@ impl TestSyncProvider {
 				num_snapshot_chunks: 0,
 				snapshot_chunks_done: 0,
 				last_imported_old_block_number: None,
+				fast_sync_pivot: None,
+				state_nodes_done: 0,
+				state_nodes_pending: 0,
 			}),
 		}
 	}
@@ -78,6 +82,10 @@ impl SyncProvider for TestSyncProvider {
 				capabilities: vec!["eth/62".to_owned(), "eth/63".to_owned()],
     			remote_address: "127.0.0.1:7777".to_owned(),
 				local_address: "127.0.0.1:8888".to_owned(),
+				traffic: PeerTraffic { sent: 1024, received: 4096 },
+				protocol_traffic: map![
+					"eth".to_owned() => PeerTraffic { sent: 800, received: 3900 }
+				],
 				eth_info: Some(EthProtocolInfo {
 					version: 62,
 					difficulty: Some(40.into()),
@@ -91,6 +99,8 @@ impl SyncProvider for TestSyncProvider {
 				capabilities: vec!["eth/63".to_owned(), "eth/64".to_owned()],
     			remote_address: "Handshake".to_owned(),
 				local_address: "127.0.0.1:3333".to_owned(),
+				traffic: PeerTraffic::default(),
+				protocol_traffic: BTreeMap::new(),
 				eth_info: Some(EthProtocolInfo {
 					version: 64,
 					difficulty: None,
@@ -105,6 +115,10 @@ impl SyncProvider for TestSyncProvider {
 		None
 	}
 
+	fn enr(&self) -> Option<String> {
+		None
+	}
+
 	fn transactions_stats(&self) -> BTreeMap<H256, TransactionStats> {
 		map![
 			1.into() => TransactionStats {
@@ -121,5 +135,15 @@ impl SyncProvider for TestSyncProvider {
 			}
 		]
 	}
+
+	fn compression_stats(&self) -> CompressionStats {
+		CompressionStats {
+			raw_sent: 2048,
+			sent: 1024,
+			raw_received: 8192,
+			received: 4096,
+			savings: 5120,
+		}
+	}
 }
 
@@ -0,0 +1,72 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use jsonrpc_core::IoHandler;
+use v1::{Web3, Web3Client, Metadata, AccessPolicy, AccessControl, Principal};
+
+fn io(transport: Principal) -> IoHandler<Metadata> {
+	let mut policy = AccessPolicy::default();
+	policy.allow(Principal::Anonymous, vec!["web3_sha3"]);
+	policy.allow(Principal::Token("secret".into()), vec!["web3_*"]);
+
+	let access = AccessControl::new(Arc::new(policy), transport);
+	let mut io = IoHandler::default();
+	io.extend_with(access.guard(Web3Client::new().to_delegate()));
+	io
+}
+
+#[test]
+fn should_allow_permitted_method() {
+	let io = io(Principal::Anonymous);
+
+	let request = r#"{"jsonrpc": "2.0", "method": "web3_sha3", "params": ["0x00"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":"0xbc36789e7a1e281436464229828f817d6612f7b477d66591ff96a9e064bcc98a","id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn should_deny_method_not_in_policy() {
+	let io = io(Principal::Anonymous);
+
+	let request = r#"{"jsonrpc": "2.0", "method": "web3_clientVersion", "params": [], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32065,"message":"Access to method web3_clientVersion denied.","data":null},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn should_allow_method_for_token() {
+	let io = io(Principal::Anonymous);
+	let mut meta = Metadata::default();
+	meta.token = Some("secret".into());
+
+	let request = r#"{"jsonrpc": "2.0", "method": "web3_clientVersion", "params": [], "id": 1}"#;
+	let response = (*io).handle_request_sync(request, meta).unwrap();
+
+	assert!(response.contains(r#""result":"Parity/"#));
+}
+
+#[test]
+fn should_deny_transport_without_rules() {
+	let io = io(Principal::Ipc);
+
+	let request = r#"{"jsonrpc": "2.0", "method": "web3_sha3", "params": ["0x00"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32065,"message":"Access to method web3_sha3 denied.","data":null},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
@@ -225,6 +225,22 @@ fn rpc_eth_logs() {
 	assert_eq!(tester.io.handle_request_sync(request3), Some(response3.to_owned()));
 }
 
+#[test]
+fn rpc_eth_logs_over_expired_history() {
+	let tester = EthTester::default();
+	tester.client.add_blocks(10, EachBlockWith::Nothing);
+	*tester.client.earliest_body.write() = 5;
+
+	let request1 = r#"{"jsonrpc": "2.0", "method": "eth_getLogs", "params": [{"fromBlock":"0x0","toBlock":"0x9"}], "id": 1}"#;
+	let request2 = r#"{"jsonrpc": "2.0", "method": "eth_getLogs", "params": [{"fromBlock":"0x5","toBlock":"0x9"}], "id": 1}"#;
+
+	let response1 = r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"This request is not supported because your node has removed old block bodies and receipts. Run without --history-retention.","data":null},"id":1}"#;
+	let response2 = r#"{"jsonrpc":"2.0","result":[],"id":1}"#;
+
+	assert_eq!(tester.io.handle_request_sync(request1), Some(response1.to_owned()));
+	assert_eq!(tester.io.handle_request_sync(request2), Some(response2.to_owned()));
+}
+
 #[test]
 fn rpc_logs_filter() {
 	let tester = EthTester::default();
@@ -0,0 +1,38 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::time::Duration;
+use jsonrpc_core::IoHandler;
+use v1::{Web3, Web3Client, Metadata, RateLimiter, RateLimits};
+
+#[test]
+fn should_reject_requests_over_the_limit() {
+	let limiter = Arc::new(RateLimiter::new(RateLimits {
+		requests: 1,
+		window: Duration::from_secs(60),
+		..Default::default()
+	}, Default::default()));
+	let mut io: IoHandler<Metadata> = IoHandler::default();
+	io.extend_with(RateLimiter::limit(&limiter, Web3Client::new().to_delegate()));
+
+	let request = r#"{"jsonrpc": "2.0", "method": "web3_sha3", "params": ["0x00"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":"0xbc36789e7a1e281436464229828f817d6612f7b477d66591ff96a9e064bcc98a","id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32066,"message":"Request limit exceeded. Retry after 60 seconds.","data":60},"id":1}"#;
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
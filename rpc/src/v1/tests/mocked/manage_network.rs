@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use ethsync::{ManageNetwork, NetworkConfiguration};
+use ethsync::{ManageNetwork, NetworkConfiguration, PeerReputation, PeerGroupInfo};
 
 pub struct TestManageNetwork;
 
@@ -24,6 +24,28 @@ impl ManageNetwork for TestManageNetwork {
 	fn deny_unreserved_peers(&self) { }
 	fn remove_reserved_peer(&self, _peer: String) -> Result<(), String> { Ok(()) }
 	fn add_reserved_peer(&self, _peer: String) -> Result<(), String> { Ok(()) }
+	fn ban_peer(&self, _peer: String, _duration: u64) -> Result<(), String> { Ok(()) }
+	fn peer_reputations(&self) -> Vec<PeerReputation> {
+		vec![PeerReputation {
+			id: "a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c".into(),
+			score: -50,
+			banned_until: Some(1500000000),
+		}]
+	}
+	fn set_peer_group(&self, _name: String, _min_slots: u32, _max_slots: u32, _trusted: bool) -> Result<(), String> { Ok(()) }
+	fn remove_peer_group(&self, _name: String) -> Result<(), String> { Ok(()) }
+	fn add_peer_group_node(&self, _group: String, _peer: String) -> Result<(), String> { Ok(()) }
+	fn remove_peer_group_node(&self, _group: String, _peer: String) -> Result<(), String> { Ok(()) }
+	fn peer_groups(&self) -> Vec<PeerGroupInfo> {
+		vec![PeerGroupInfo {
+			name: "validators".into(),
+			nodes: vec!["a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c".into()],
+			min_slots: 1,
+			max_slots: 4,
+			trusted: true,
+			connected: 1,
+		}]
+	}
 	fn start_network(&self) {}
 	fn stop_network(&self) {}
 	fn network_config(&self) -> NetworkConfiguration { NetworkConfiguration::new_local() }
@@ -17,7 +17,9 @@
 //! RPC mocked tests. Most of these test that the RPC server is serializing and forwarding
 //! method calls properly.
 
+mod access;
 mod eth;
+mod limits;
 mod manage_network;
 mod net;
 mod parity;
@@ -16,10 +16,11 @@
 
 use std::sync::Arc;
 use util::log::RotatingLogger;
-use util::Address;
+use util::{Address, H256, U256};
 use ethsync::ManageNetwork;
 use ethcore::account_provider::AccountProvider;
-use ethcore::client::{TestBlockChainClient};
+use ethcore::client::{TestBlockChainClient, EachBlockWith, BlockChainClient, BlockId, TransactionId};
+use ethcore::receipt::LocalizedReceipt;
 use ethcore::miner::LocalTransactionStatus;
 use ethstore::ethkey::{Generator, Random};
 
@@ -201,6 +202,133 @@ fn rpc_parity_gas_floor_target() {
 	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
 }
 
+#[test]
+fn rpc_parity_address_transactions_without_index() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_addressTransactions", "params": ["0x0000000000000000000000000000000000000001", 10, null], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_parity_address_transactions_over_limit() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_addressTransactions", "params": ["0x0000000000000000000000000000000000000001", 1025, null], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: count","data":"\"Expected at most 1024\""},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_parity_fee_history() {
+	let deps = Dependencies::new();
+	deps.client.add_blocks(3, EachBlockWith::Transaction);
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_feeHistory", "params": [2, "latest", [10.0, 90.0]], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":{"gasPrice":[["0x2e90edd000","0x2e90edd000"],["0x2e90edd000","0x2e90edd000"]],"gasUsedRatio":[0.0,0.0],"oldestBlock":"0x2"},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_parity_fee_history_invalid_percentiles() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_feeHistory", "params": [1, "latest", [90.0, 10.0]], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: percentiles","data":"\"Expected ascending values between 0 and 100\""},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_parity_block_receipts() {
+	let deps = Dependencies::new();
+	deps.client.add_blocks(1, EachBlockWith::Nothing);
+	let hash = deps.client.block_hash(BlockId::Number(1)).unwrap();
+	deps.client.set_transaction_receipt(TransactionId::Location(BlockId::Number(1), 0), LocalizedReceipt {
+		transaction_hash: H256::zero(),
+		transaction_index: 0,
+		block_hash: hash,
+		block_number: 1,
+		cumulative_gas_used: U256::from(0x10),
+		gas_used: U256::from(0x10),
+		contract_address: None,
+		logs: vec![],
+		log_bloom: 0.into(),
+		state_root: 0.into(),
+	});
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_getBlockReceipts", "params": ["0x1"], "id": 1}"#;
+	let response = format!(r#"{{"jsonrpc":"2.0","result":[{{"blockHash":"0x{:?}","blockNumber":"0x1","contractAddress":null,"cumulativeGasUsed":"0x10","gasUsed":"0x10","logs":[],"logsBloom":"0x{}","root":"0x{:?}","transactionHash":"0x{:?}","transactionIndex":"0x0"}}],"id":1}}"#,
+		hash, "0".repeat(512), H256::zero(), H256::zero());
+
+	assert_eq!(io.handle_request_sync(request), Some(response));
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_getBlockReceipts", "params": ["0x5"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_parity_blocks_with_receipts() {
+	use serde_json::{self, Value};
+
+	let deps = Dependencies::new();
+	deps.client.add_blocks(3, EachBlockWith::Nothing);
+	let hash = deps.client.block_hash(BlockId::Number(2)).unwrap();
+	deps.client.set_transaction_receipt(TransactionId::Location(BlockId::Number(2), 0), LocalizedReceipt {
+		transaction_hash: H256::zero(),
+		transaction_index: 0,
+		block_hash: hash,
+		block_number: 2,
+		cumulative_gas_used: U256::from(0x10),
+		gas_used: U256::from(0x10),
+		contract_address: None,
+		logs: vec![],
+		log_bloom: 0.into(),
+		state_root: 0.into(),
+	});
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_getBlocksWithReceipts", "params": ["0x1", "0x3"], "id": 1}"#;
+	let response: Value = serde_json::from_str(&io.handle_request_sync(request).unwrap()).unwrap();
+	let result = response.find("result").and_then(Value::as_array).unwrap();
+
+	assert_eq!(result.len(), 3);
+	for (i, entry) in result.iter().enumerate() {
+		assert_eq!(entry.find("block").and_then(|b| b.find("number")).and_then(Value::as_str), Some(format!("0x{}", i + 1).as_str()));
+	}
+	assert_eq!(result[0].find("receipts").and_then(Value::as_array).map(Vec::len), Some(0));
+	let receipt = result[1].find("receipts").and_then(Value::as_array).and_then(|r| r.first()).unwrap();
+	assert_eq!(receipt.find("blockHash").and_then(Value::as_str), Some(format!("0x{:?}", hash).as_str()));
+}
+
+#[test]
+fn rpc_parity_blocks_with_receipts_range_limit() {
+	let deps = Dependencies::new();
+	deps.client.add_blocks(200, EachBlockWith::Nothing);
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_getBlocksWithReceipts", "params": ["0x10", "0x90"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: toBlock","data":"\"Expected at most 128 blocks in range\""},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_getBlocksWithReceipts", "params": ["0x10", "0x8"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: fromBlock","data":"\"Expected fromBlock not greater than toBlock\""},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
 #[test]
 fn rpc_parity_min_gas_price() {
 	let deps = Dependencies::new();
@@ -265,7 +393,7 @@ fn rpc_parity_net_peers() {
 	let io = deps.default_client();
 
 	let request = r#"{"jsonrpc": "2.0", "method": "parity_netPeers", "params":[], "id": 1}"#;
-	let response = r#"{"jsonrpc":"2.0","result":{"active":0,"connected":120,"max":50,"peers":[{"caps":["eth/62","eth/63"],"id":"node1","name":"Parity/1","network":{"localAddress":"127.0.0.1:8888","remoteAddress":"127.0.0.1:7777"},"protocols":{"eth":{"difficulty":"0x28","head":"0000000000000000000000000000000000000000000000000000000000000032","version":62},"les":null}},{"caps":["eth/63","eth/64"],"id":null,"name":"Parity/2","network":{"localAddress":"127.0.0.1:3333","remoteAddress":"Handshake"},"protocols":{"eth":{"difficulty":null,"head":"000000000000000000000000000000000000000000000000000000000000003c","version":64},"les":null}}]},"id":1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":{"active":0,"connected":120,"max":50,"peers":[{"caps":["eth/62","eth/63"],"id":"node1","name":"Parity/1","network":{"localAddress":"127.0.0.1:8888","protocolTraffic":{"eth":{"received":3900,"sent":800}},"remoteAddress":"127.0.0.1:7777","traffic":{"received":4096,"sent":1024}},"protocols":{"eth":{"difficulty":"0x28","head":"0000000000000000000000000000000000000000000000000000000000000032","version":62},"les":null}},{"caps":["eth/63","eth/64"],"id":null,"name":"Parity/2","network":{"localAddress":"127.0.0.1:3333","protocolTraffic":{},"remoteAddress":"Handshake","traffic":{"received":0,"sent":0}},"protocols":{"eth":{"difficulty":null,"head":"000000000000000000000000000000000000000000000000000000000000003c","version":64},"les":null}}],"compression":{"rawSent":2048,"sent":1024,"rawReceived":8192,"received":4096,"savings":5120}},"id":1}"#;
 
 	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
 }
@@ -460,3 +588,25 @@ fn rpc_parity_chain_status() {
 
 	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
 }
+
+#[test]
+fn rpc_parity_db_stats() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_dbStats", "params":[], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":{"columns":[],"diskSize":0,"journalMemory":0,"runningCompactions":0,"pendingCompactionBytes":0},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
+
+#[test]
+fn rpc_parity_enr_when_network_disabled() {
+	let deps = Dependencies::new();
+	let io = deps.default_client();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "parity_enr", "params":[], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32035,"message":"Network is disabled or not yet up.","data":null},"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
@@ -30,7 +30,7 @@ use util::{Address, FixedHash, Uint, U256, ToPretty, Hashable};
 use ethcore::account_provider::AccountProvider;
 use ethcore::client::TestBlockChainClient;
 use ethcore::transaction::{Transaction, Action, SignedTransaction};
-use ethstore::ethkey::{Generator, Random};
+use ethstore::ethkey::{Generator, Random, Secret};
 use futures::Future;
 use serde_json;
 
@@ -205,6 +205,118 @@ fn should_sign_if_account_is_unlocked() {
 	assert_eq!(tester.signer.requests().len(), 0);
 }
 
+fn mail_typed_data(contents: &str) -> String {
+	r#"{
+		"types": {
+			"EIP712Domain": [
+				{ "name": "name", "type": "string" },
+				{ "name": "version", "type": "string" },
+				{ "name": "chainId", "type": "uint256" },
+				{ "name": "verifyingContract", "type": "address" }
+			],
+			"Person": [
+				{ "name": "name", "type": "string" },
+				{ "name": "wallet", "type": "address" }
+			],
+			"Mail": [
+				{ "name": "from", "type": "Person" },
+				{ "name": "to", "type": "Person" },
+				{ "name": "contents", "type": "string" }
+			]
+		},
+		"primaryType": "Mail",
+		"domain": {
+			"name": "Ether Mail",
+			"version": "1",
+			"chainId": 1,
+			"verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+		},
+		"message": {
+			"from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+			"to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" }"#.to_owned() + contents + r#"
+		}
+	}"#
+}
+
+#[test]
+fn should_add_sign_typed_data_to_queue() {
+	// given
+	let tester = eth_signing();
+	let address = Address::random();
+	assert_eq!(tester.signer.requests().len(), 0);
+
+	// when
+	let request = r#"{
+		"jsonrpc": "2.0",
+		"method": "eth_signTypedData",
+		"params": [
+			""#.to_owned() + format!("0x{:?}", address).as_ref() + r#"",
+			"# + &mail_typed_data(r#", "contents": "Hello, Bob!""#) + r#"
+		],
+		"id": 1
+	}"#;
+	let response = r#"{"jsonrpc":"2.0","result":"0x0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","id":1}"#;
+
+	// then
+	let promise = tester.io.handle_request(&request);
+	assert_eq!(tester.signer.requests().len(), 1);
+	// respond
+	tester.signer.request_confirmed(1.into(), Ok(ConfirmationResponse::Signature(0.into())));
+
+	let res = promise.wait().unwrap();
+	assert_eq!(res, Some(response.to_owned()));
+}
+
+#[test]
+fn should_sign_typed_data_if_account_is_unlocked() {
+	// given
+	let tester = eth_signing();
+	let secret = Secret::from_str("c85ef7d79691fe79573b1a7064c19c1a9819ebdbd1faaab1a8ec92344438aaf4").unwrap();
+	let acc = tester.accounts.insert_account(secret, "test").unwrap();
+	tester.accounts.unlock_account_permanently(acc, "test".into()).unwrap();
+	assert_eq!(acc, Address::from_str("cd2a3d9f938e13cd947ec05abc7fe734df8dd826").unwrap());
+
+	// when
+	let request = r#"{
+		"jsonrpc": "2.0",
+		"method": "eth_signTypedData",
+		"params": [
+			""#.to_owned() + format!("0x{:?}", acc).as_ref() + r#"",
+			"# + &mail_typed_data(r#", "contents": "Hello, Bob!""#) + r#"
+		],
+		"id": 1
+	}"#;
+	// signature from the EIP-712 example
+	let response = r#"{"jsonrpc":"2.0","result":"0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b9156201","id":1}"#;
+
+	// then
+	assert_eq!(tester.io.handle_request_sync(&request), Some(response.to_owned()));
+	assert_eq!(tester.signer.requests().len(), 0);
+}
+
+#[test]
+fn should_reject_invalid_typed_data() {
+	// given
+	let tester = eth_signing();
+	let address = Address::random();
+
+	// when
+	let request = r#"{
+		"jsonrpc": "2.0",
+		"method": "eth_signTypedData",
+		"params": [
+			""#.to_owned() + format!("0x{:?}", address).as_ref() + r#"",
+			"# + &mail_typed_data("") + r#"
+		],
+		"id": 1
+	}"#;
+	let response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Couldn't parse parameters: Invalid typed data.","data":"MissingField(\"Mail.contents\")"},"id":1}"#;
+
+	// then
+	assert_eq!(tester.io.handle_request_sync(&request), Some(response.to_owned()));
+	assert_eq!(tester.signer.requests().len(), 0);
+}
+
 #[test]
 fn should_add_transaction_to_queue() {
 	// given
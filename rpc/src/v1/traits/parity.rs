@@ -23,10 +23,10 @@ use jsonrpc_macros::Trailing;
 
 use v1::types::{
 	H160, H256, H512, U256, Bytes,
-	Peers, Transaction, RpcSettings, Histogram,
-	TransactionStats, LocalTransactionStatus,
+	Peers, Transaction, RpcSettings, Histogram, FeeHistory, AddressTransaction,
+	Receipt, BlockWithReceipts, TransactionStats, LocalTransactionStatus,
 	BlockNumber, ConsensusCapability, VersionInfo,
-	OperationsInfo, DappId, ChainStatus,
+	OperationsInfo, DappId, ChainStatus, DatabaseStats,
 };
 
 build_rpc_trait! {
@@ -92,6 +92,19 @@ build_rpc_trait! {
 		#[rpc(name = "parity_gasPriceHistogram")]
 		fn gas_price_histogram(&self) -> Result<Histogram, Error>;
 
+		/// Returns gas used ratio and gas price percentiles (weighted by gas used) of
+		/// `blockCount` blocks ending with `newestBlock`.
+		#[rpc(name = "parity_feeHistory")]
+		fn fee_history(&self, u64, BlockNumber, Trailing<Vec<f64>>) -> Result<FeeHistory, Error>;
+
+		/// Returns receipts of all transactions in given block.
+		#[rpc(name = "parity_getBlockReceipts")]
+		fn block_receipts(&self, BlockNumber) -> Result<Option<Vec<Receipt>>, Error>;
+
+		/// Returns blocks with full transactions and their receipts for given inclusive range.
+		#[rpc(name = "parity_getBlocksWithReceipts")]
+		fn blocks_with_receipts(&self, BlockNumber, BlockNumber) -> Result<Vec<BlockWithReceipts>, Error>;
+
 		/// Returns number of unsigned transactions waiting in the signer queue (if signer enabled)
 		/// Returns error when signer is disabled
 		#[rpc(name = "parity_unsignedTransactionsCount")]
@@ -118,6 +131,13 @@ build_rpc_trait! {
 		#[rpc(name = "parity_listStorageKeys")]
 		fn list_storage_keys(&self, H160, u64, Option<H256>, Trailing<BlockNumber>) -> Result<Option<Vec<H256>>, Error>;
 
+		/// Returns up to `count` (second parameter, at most 1024) canonical transactions sent from or to the
+		/// given address, oldest first, if the address index is enabled (`--address-index`), or null if not.
+		/// Listing continues after the transaction with the hash given as the third parameter. The list ends
+		/// early at a transaction whose block body is no longer available (`--history-retention`).
+		#[rpc(name = "parity_addressTransactions")]
+		fn address_transactions(&self, H160, u64, Option<H256>) -> Result<Option<Vec<AddressTransaction>>, Error>;
+
 		/// Encrypt some data with a public key under ECIES.
 		/// First parameter is the 512-byte destination public key, second is the message.
 		#[rpc(name = "parity_encryptMessage")]
@@ -163,6 +183,10 @@ build_rpc_trait! {
 		#[rpc(name = "parity_enode")]
 		fn enode(&self) -> Result<String, Error>;
 
+		/// Get the signed node record (ENR) of this node.
+		#[rpc(name = "parity_enr")]
+		fn enr(&self) -> Result<String, Error>;
+
 		/// Returns information on current consensus capability.
 		#[rpc(name = "parity_consensusCapability")]
 		fn consensus_capability(&self) -> Result<ConsensusCapability, Error>;
@@ -178,5 +202,9 @@ build_rpc_trait! {
 		/// Get the current chain status.
 		#[rpc(name = "parity_chainStatus")]
 		fn chain_status(&self) -> Result<ChainStatus, Error>;
+
+		/// Returns approximate key counts and sizes of the database columns and database activity counters.
+		#[rpc(name = "parity_dbStats")]
+		fn db_stats(&self) -> Result<DatabaseStats, Error>;
 	}
 }
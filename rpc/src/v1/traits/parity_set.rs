@@ -17,9 +17,10 @@
 //! Parity-specific rpc interface for operations altering the settings.
 
 use jsonrpc_core::Error;
+use jsonrpc_macros::Trailing;
 use futures::BoxFuture;
 
-use v1::types::{Bytes, H160, H256, U256, ReleaseInfo};
+use v1::types::{Bytes, H160, H256, U256, ReleaseInfo, PeerReputation, PeerGroup};
 
 build_rpc_trait! {
 	/// Parity-specific rpc interface for operations altering the settings.
@@ -56,13 +57,34 @@ build_rpc_trait! {
 		#[rpc(name = "parity_setMaxTransactionGas")]
 		fn set_tx_gas_limit(&self, U256) -> Result<bool, Error>;
 
-		/// Add a reserved peer.
+		/// Add a reserved peer, or a member of the peer group given by name.
 		#[rpc(name = "parity_addReservedPeer")]
-		fn add_reserved_peer(&self, String) -> Result<bool, Error>;
+		fn add_reserved_peer(&self, String, Trailing<String>) -> Result<bool, Error>;
 
-		/// Remove a reserved peer.
+		/// Remove a reserved peer, or a member of the peer group given by name.
 		#[rpc(name = "parity_removeReservedPeer")]
-		fn remove_reserved_peer(&self, String) -> Result<bool, Error>;
+		fn remove_reserved_peer(&self, String, Trailing<String>) -> Result<bool, Error>;
+
+		/// Create a peer group with the given name, minimum and maximum slots and trusted flag,
+		/// or update the settings of an existing one.
+		#[rpc(name = "parity_setPeerGroup")]
+		fn set_peer_group(&self, String, u32, u32, bool) -> Result<bool, Error>;
+
+		/// Remove a peer group.
+		#[rpc(name = "parity_removePeerGroup")]
+		fn remove_peer_group(&self, String) -> Result<bool, Error>;
+
+		/// Peer groups and their connected members.
+		#[rpc(name = "parity_peerGroups")]
+		fn peer_groups(&self) -> Result<Vec<PeerGroup>, Error>;
+
+		/// Ban a peer given by its node id or url for the given number of seconds, at most 30 days.
+		#[rpc(name = "parity_banPeer")]
+		fn ban_peer(&self, String, u64) -> Result<bool, Error>;
+
+		/// Reputations of the peers which have been reported or banned.
+		#[rpc(name = "parity_peerReputation")]
+		fn peer_reputation(&self) -> Result<Vec<PeerReputation>, Error>;
 
 		/// Drop all non-reserved peers.
 		#[rpc(name = "parity_dropNonReservedPeers")]
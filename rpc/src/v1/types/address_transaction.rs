@@ -0,0 +1,44 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity. If not, see <http://www.gnu.org/licenses/>.
+
+//! Transactions of an address.
+
+use v1::types::Transaction;
+use ethcore::client;
+
+/// Canonical transaction in which an address takes part.
+#[derive(Debug, Serialize)]
+pub struct AddressTransaction {
+	/// The transaction.
+	pub transaction: Transaction,
+	/// Whether the address sent the transaction.
+	pub sender: bool,
+	/// Whether the address is the recipient or the contract created by the transaction.
+	pub recipient: bool,
+	/// Whether the address was called, created or destroyed during the execution.
+	pub internal: bool,
+}
+
+impl From<client::AddressTransaction> for AddressTransaction {
+	fn from(t: client::AddressTransaction) -> Self {
+		AddressTransaction {
+			transaction: t.transaction.into(),
+			sender: t.sender,
+			recipient: t.recipient,
+			internal: t.internal,
+		}
+	}
+}
@@ -17,7 +17,7 @@
 use std::ops::Deref;
 use std::collections::BTreeMap;
 use serde::{Serialize, Serializer};
-use v1::types::{Bytes, Transaction, H160, H256, H2048, U256};
+use v1::types::{Bytes, Transaction, Receipt, H160, H256, H2048, U256};
 
 /// Block Transactions
 #[derive(Debug)]
@@ -128,6 +128,15 @@ impl Serialize for RichBlock {
 	}
 }
 
+/// Block together with receipts of all its transactions.
+#[derive(Debug, Serialize)]
+pub struct BlockWithReceipts {
+	/// Block with full transactions
+	pub block: RichBlock,
+	/// Receipts of block transactions
+	pub receipts: Vec<Receipt>,
+}
+
 #[cfg(test)]
 mod tests {
 	use std::collections::BTreeMap;
@@ -20,11 +20,11 @@ use std::fmt;
 use serde::{Serialize, Serializer};
 use util::log::Colour;
 
-use v1::types::{U256, TransactionRequest, RichRawTransaction, H160, H256, H520, Bytes, BlockNumber};
+use v1::types::{U256, TransactionRequest, RichRawTransaction, H160, H256, H520, Bytes, BlockNumber, TypedData};
 use v1::helpers;
 
 /// Confirmation waiting in a queue
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConfirmationRequest {
 	/// Id of this confirmation
 	pub id: U256,
@@ -53,6 +53,7 @@ impl fmt::Display for ConfirmationPayload {
 			ConfirmationPayload::SendTransaction(ref transaction) => write!(f, "{}", transaction),
 			ConfirmationPayload::SignTransaction(ref transaction) => write!(f, "(Sign only) {}", transaction),
 			ConfirmationPayload::Signature(ref sign) => write!(f, "{}", sign),
+			ConfirmationPayload::SignTypedData(ref sign) => write!(f, "{}", sign),
 			ConfirmationPayload::Decrypt(ref decrypt) => write!(f, "{}", decrypt),
 		}
 	}
@@ -87,6 +88,35 @@ impl fmt::Display for SignRequest {
 	}
 }
 
+/// Typed structured data sign request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignTypedDataRequest {
+	/// Address
+	pub address: H160,
+	/// Typed data to sign
+	pub data: TypedData,
+}
+
+impl From<(H160, TypedData)> for SignTypedDataRequest {
+	fn from(tuple: (H160, TypedData)) -> Self {
+		SignTypedDataRequest {
+			address: tuple.0,
+			data: tuple.1,
+		}
+	}
+}
+
+impl fmt::Display for SignTypedDataRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"sign typed data {} with {}",
+			self.data,
+			Colour::White.bold().paint(format!("0x{:?}", self.address)),
+		)
+	}
+}
+
 /// Decrypt request
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct DecryptRequest {
@@ -151,7 +181,7 @@ pub struct ConfirmationResponseWithToken {
 }
 
 /// Confirmation payload, i.e. the thing to be confirmed
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ConfirmationPayload {
 	/// Send Transaction
 	#[serde(rename="sendTransaction")]
@@ -162,6 +192,9 @@ pub enum ConfirmationPayload {
 	/// Signature
 	#[serde(rename="sign")]
 	Signature(SignRequest),
+	/// Typed structured data signature
+	#[serde(rename="signTypedData")]
+	SignTypedData(SignTypedDataRequest),
 	/// Decryption
 	#[serde(rename="decrypt")]
 	Decrypt(DecryptRequest),
@@ -176,6 +209,10 @@ impl From<helpers::ConfirmationPayload> for ConfirmationPayload {
 				address: address.into(),
 				hash: hash.into(),
 			}),
+			helpers::ConfirmationPayload::SignTypedData(address, data) => ConfirmationPayload::SignTypedData(SignTypedDataRequest {
+				address: address.into(),
+				data: data.into(),
+			}),
 			helpers::ConfirmationPayload::Decrypt(address, msg) => ConfirmationPayload::Decrypt(DecryptRequest {
 				address: address.into(),
 				msg: msg.into(),
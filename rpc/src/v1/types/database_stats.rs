@@ -0,0 +1,80 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Database statistics.
+
+use ethcore::client;
+
+/// Approximate size of a database column.
+#[derive(Debug, Serialize)]
+pub struct ColumnStats {
+	/// Name of the column.
+	pub name: String,
+	/// Estimated number of keys.
+	pub keys: u64,
+	/// Size of the table files on disk, in bytes.
+	#[serde(rename="diskSize")]
+	pub disk_size: u64,
+	/// Size of the in-memory write buffers, in bytes.
+	#[serde(rename="memtableSize")]
+	pub memtable_size: u64,
+	/// Number of buffered writes which are not flushed yet.
+	#[serde(rename="pendingWrites")]
+	pub pending_writes: u64,
+}
+
+/// Approximate database statistics.
+#[derive(Debug, Serialize)]
+pub struct DatabaseStats {
+	/// Statistics of every column.
+	pub columns: Vec<ColumnStats>,
+	/// Total size of the table files on disk, in bytes.
+	#[serde(rename="diskSize")]
+	pub disk_size: u64,
+	/// Memory used by the journal overlay of the state database, in bytes.
+	#[serde(rename="journalMemory")]
+	pub journal_memory: u64,
+	/// Number of compactions currently running.
+	#[serde(rename="runningCompactions")]
+	pub running_compactions: u64,
+	/// Estimated number of bytes compactions have to rewrite.
+	#[serde(rename="pendingCompactionBytes")]
+	pub pending_compaction_bytes: u64,
+}
+
+impl From<client::ColumnStats> for ColumnStats {
+	fn from(c: client::ColumnStats) -> Self {
+		ColumnStats {
+			name: c.name,
+			keys: c.keys,
+			disk_size: c.disk_size,
+			memtable_size: c.memtable_size,
+			pending_writes: c.pending_writes,
+		}
+	}
+}
+
+impl From<client::DatabaseStats> for DatabaseStats {
+	fn from(s: client::DatabaseStats) -> Self {
+		DatabaseStats {
+			disk_size: s.disk_size(),
+			columns: s.columns.into_iter().map(Into::into).collect(),
+			journal_memory: s.journal_memory,
+			running_compactions: s.running_compactions,
+			pending_compaction_bytes: s.pending_compaction_bytes,
+		}
+	}
+}
@@ -0,0 +1,45 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity. If not, see <http://www.gnu.org/licenses/>.
+
+//! Fee history of recent blocks.
+
+use v1::types::U256;
+use ethcore::client;
+
+/// Gas usage and gas price percentiles of a range of blocks.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeHistory {
+	/// Number of the first block in the range.
+	#[serde(rename="oldestBlock")]
+	pub oldest_block: U256,
+	/// Ratio of gas used to the gas limit of every block.
+	#[serde(rename="gasUsedRatio")]
+	pub gas_used_ratio: Vec<f64>,
+	/// Requested gas price percentiles of every block.
+	#[serde(rename="gasPrice")]
+	pub gas_price: Vec<Vec<U256>>,
+}
+
+impl From<client::FeeHistory> for FeeHistory {
+	fn from(h: client::FeeHistory) -> Self {
+		FeeHistory {
+			oldest_block: h.oldest_block.into(),
+			gas_used_ratio: h.gas_used_ratio,
+			gas_price: h.gas_price.into_iter().map(|prices| prices.into_iter().map(Into::into).collect()).collect(),
+		}
+	}
+}
@@ -15,7 +15,8 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::BTreeMap;
-use ethsync::{self, PeerInfo as SyncPeerInfo, TransactionStats as SyncTransactionStats};
+use ethsync::{self, PeerInfo as SyncPeerInfo, PeerReputation as SyncPeerReputation, PeerTraffic as SyncPeerTraffic,
+	PeerGroupInfo as SyncPeerGroupInfo, TransactionStats as SyncTransactionStats, CompressionStats as SyncCompressionStats};
 use serde::{Serialize, Serializer};
 use v1::types::{U256, H512};
 
@@ -50,6 +51,8 @@ pub struct Peers {
 	pub max: u32,
 	/// Detailed information on peers
 	pub peers: Vec<PeerInfo>,
+	/// Payload bytes exchanged compressed with all peers
+	pub compression: CompressionStats,
 }
 
 /// Peer connection information
@@ -76,6 +79,37 @@ pub struct PeerNetworkInfo {
 	/// Local endpoint address
 	#[serde(rename="localAddress")]
 	pub local_address: String,
+	/// Bytes sent and received over the connection
+	pub traffic: PeerTraffic,
+	/// Packet payload bytes sent and received per protocol
+	#[serde(rename="protocolTraffic")]
+	pub protocol_traffic: BTreeMap<String, PeerTraffic>,
+}
+
+/// Bytes exchanged with a peer
+#[derive(Default, Debug, Serialize)]
+pub struct PeerTraffic {
+	/// Bytes sent
+	pub sent: u64,
+	/// Bytes received
+	pub received: u64,
+}
+
+/// Payload bytes of packets exchanged compressed
+#[derive(Default, Debug, Serialize)]
+pub struct CompressionStats {
+	/// Bytes sent, before compression
+	#[serde(rename="rawSent")]
+	pub raw_sent: u64,
+	/// Bytes sent, after compression
+	pub sent: u64,
+	/// Bytes received, after decompression
+	#[serde(rename="rawReceived")]
+	pub raw_received: u64,
+	/// Bytes received, before decompression
+	pub received: u64,
+	/// Bytes saved by compression
+	pub savings: u64,
 }
 
 /// Peer protocols information
@@ -168,6 +202,8 @@ impl From<SyncPeerInfo> for PeerInfo {
 			network: PeerNetworkInfo {
 				remote_address: p.remote_address,
 				local_address: p.local_address,
+				traffic: p.traffic.into(),
+				protocol_traffic: p.protocol_traffic.into_iter().map(|(p, t)| (p, t.into())).collect(),
 			},
 			protocols: PeerProtocolsInfo {
 				eth: p.eth_info.map(Into::into),
@@ -177,6 +213,27 @@ impl From<SyncPeerInfo> for PeerInfo {
 	}
 }
 
+impl From<SyncCompressionStats> for CompressionStats {
+	fn from(s: SyncCompressionStats) -> Self {
+		CompressionStats {
+			raw_sent: s.raw_sent,
+			sent: s.sent,
+			raw_received: s.raw_received,
+			received: s.received,
+			savings: s.savings,
+		}
+	}
+}
+
+impl From<SyncPeerTraffic> for PeerTraffic {
+	fn from(t: SyncPeerTraffic) -> Self {
+		PeerTraffic {
+			sent: t.sent,
+			received: t.received,
+		}
+	}
+}
+
 impl From<SyncTransactionStats> for TransactionStats {
 	fn from(s: SyncTransactionStats) -> Self {
 		TransactionStats {
@@ -189,6 +246,60 @@ impl From<SyncTransactionStats> for TransactionStats {
 	}
 }
 
+/// Reputation of a node.
+#[derive(Default, Debug, Serialize)]
+pub struct PeerReputation {
+	/// Public node id.
+	pub id: String,
+	/// Score of the node.
+	pub score: i32,
+	/// Unix time until which the node is banned.
+	#[serde(rename="bannedUntil")]
+	pub banned_until: Option<u64>,
+}
+
+impl From<SyncPeerReputation> for PeerReputation {
+	fn from(r: SyncPeerReputation) -> Self {
+		PeerReputation {
+			id: r.id,
+			score: r.score,
+			banned_until: r.banned_until,
+		}
+	}
+}
+
+/// Static peer group.
+#[derive(Default, Debug, Serialize)]
+pub struct PeerGroup {
+	/// Group name.
+	pub name: String,
+	/// Public node ids of the group members.
+	pub nodes: Vec<String>,
+	/// Number of connections to group members kept open regardless of the peer limits.
+	#[serde(rename="minSlots")]
+	pub min_slots: u32,
+	/// Maximum number of connections to group members.
+	#[serde(rename="maxSlots")]
+	pub max_slots: u32,
+	/// Members bypass the peer limits, the reserved-only mode and bans.
+	pub trusted: bool,
+	/// Number of connected group members.
+	pub connected: u32,
+}
+
+impl From<SyncPeerGroupInfo> for PeerGroup {
+	fn from(g: SyncPeerGroupInfo) -> Self {
+		PeerGroup {
+			name: g.name,
+			nodes: g.nodes,
+			min_slots: g.min_slots,
+			max_slots: g.max_slots,
+			trusted: g.trusted,
+			connected: g.connected,
+		}
+	}
+}
+
 /// Chain status.
 #[derive(Default, Debug, Serialize)]
 pub struct ChainStatus {
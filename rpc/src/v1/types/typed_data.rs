@@ -0,0 +1,145 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! EIP-712 typed structured data.
+
+use std::fmt;
+use std::collections::BTreeMap;
+use serde_json::{self, Value};
+use ethcore::account_provider::typed_data;
+
+/// Member of a struct type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TypedDataField {
+	/// Member name
+	pub name: String,
+	/// Member type
+	#[serde(rename="type")]
+	pub field_type: String,
+}
+
+/// Typed structured data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TypedData {
+	/// Struct type definitions
+	pub types: BTreeMap<String, Vec<TypedDataField>>,
+	/// Type of the message
+	#[serde(rename="primaryType")]
+	pub primary_type: String,
+	/// Signing domain
+	pub domain: Value,
+	/// Message to sign
+	pub message: Value,
+}
+
+impl TypedData {
+	/// Formats members of the message in the order they are declared.
+	fn fields(&self) -> Vec<String> {
+		let object = match self.message.as_object() {
+			Some(object) => object,
+			None => return Vec::new(),
+		};
+
+		self.types.get(&self.primary_type)
+			.map(|fields| fields.iter()
+				.map(|field| {
+					let value = object.get(&field.name)
+						.and_then(|value| serde_json::to_string(value).ok())
+						.unwrap_or_else(|| "<missing>".into());
+					format!("{}: {}", field.name, value)
+				})
+				.collect()
+			)
+			.unwrap_or_else(Vec::new)
+	}
+}
+
+impl fmt::Display for TypedData {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let domain = self.domain.find("name").and_then(Value::as_str).unwrap_or("unknown domain");
+		write!(f, "{} {{ {} }} for {}", self.primary_type, self.fields().join(", "), domain)
+	}
+}
+
+impl From<TypedDataField> for typed_data::Field {
+	fn from(f: TypedDataField) -> Self {
+		typed_data::Field {
+			name: f.name,
+			field_type: f.field_type,
+		}
+	}
+}
+
+impl From<typed_data::Field> for TypedDataField {
+	fn from(f: typed_data::Field) -> Self {
+		TypedDataField {
+			name: f.name,
+			field_type: f.field_type,
+		}
+	}
+}
+
+impl From<TypedData> for typed_data::TypedData {
+	fn from(d: TypedData) -> Self {
+		typed_data::TypedData {
+			types: d.types.into_iter().map(|(name, fields)| (name, fields.into_iter().map(Into::into).collect())).collect(),
+			primary_type: d.primary_type,
+			domain: d.domain,
+			message: d.message,
+		}
+	}
+}
+
+impl From<typed_data::TypedData> for TypedData {
+	fn from(d: typed_data::TypedData) -> Self {
+		TypedData {
+			types: d.types.into_iter().map(|(name, fields)| (name, fields.into_iter().map(Into::into).collect())).collect(),
+			primary_type: d.primary_type,
+			domain: d.domain,
+			message: d.message,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::*;
+
+	#[test]
+	fn should_deserialize_and_display_typed_data() {
+		let s = r#"{
+			"types": {
+				"EIP712Domain": [{ "name": "name", "type": "string" }],
+				"Mail": [{ "name": "to", "type": "address" }, { "name": "contents", "type": "string" }]
+			},
+			"primaryType": "Mail",
+			"domain": { "name": "Ether Mail" },
+			"message": { "contents": "Hello, Bob!", "to": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" }
+		}"#;
+
+		let data: TypedData = serde_json::from_str(s).unwrap();
+
+		assert_eq!(data.primary_type, "Mail");
+		assert_eq!(data.types["Mail"][1], TypedDataField { name: "contents".into(), field_type: "string".into() });
+		assert_eq!(
+			format!("{}", data),
+			r#"Mail { to: "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB", contents: "Hello, Bob!" } for Ether Mail"#
+		);
+	}
+}
@@ -20,7 +20,7 @@ use std::io;
 use util::Bytes;
 use network::{NetworkProtocolHandler, NetworkService, NetworkContext, PeerId, ProtocolId,
 	NetworkConfiguration as BasicNetworkConfiguration, NonReservedPeerMode, NetworkError,
-	AllowIP as NetworkAllowIP};
+	AllowIP as NetworkAllowIP, NatType as NetworkNatType, TrafficStats, PeerGroupConfig, PeerGroupInfo as NetworkPeerGroupInfo};
 use util::{U256, H256, H512};
 use io::{TimerToken};
 use ethcore::ethstore::ethkey::Secret;
@@ -62,6 +62,8 @@ pub struct SyncConfig {
 	pub fork_block: Option<(BlockNumber, H256)>,
 	/// Enable snapshot sync
 	pub warp_sync: bool,
+	/// Fall back to state trie download when no snapshots are available.
+	pub fast_sync: bool,
 	/// Enable light client server.
 	pub serve_light: bool,
 }
@@ -76,6 +78,7 @@ impl Default for SyncConfig {
 			light_subprotocol_name: LES_PROTOCOL,
 			fork_block: None,
 			warp_sync: false,
+			fast_sync: true,
 			serve_light: false,
 		}
 	}
@@ -95,8 +98,14 @@ pub trait SyncProvider: Send + Sync {
 	/// Get the enode if available.
 	fn enode(&self) -> Option<String>;
 
+	/// Get the node record if available.
+	fn enr(&self) -> Option<String>;
+
 	/// Returns propagation count for pending transactions.
 	fn transactions_stats(&self) -> BTreeMap<H256, TransactionStats>;
+
+	/// Get payload bytes of packets exchanged compressed with all peers.
+	fn compression_stats(&self) -> CompressionStats;
 }
 
 /// Transaction stats
@@ -123,12 +132,134 @@ pub struct PeerInfo {
 	pub remote_address: String,
 	/// Local endpoint address
 	pub local_address: String,
+	/// Bytes sent and received over the connection
+	pub traffic: PeerTraffic,
+	/// Packet payload bytes sent and received per protocol
+	pub protocol_traffic: BTreeMap<String, PeerTraffic>,
 	/// Eth protocol info.
 	pub eth_info: Option<EthProtocolInfo>,
 	/// Light protocol info.
 	pub les_info: Option<LesProtocolInfo>,
 }
 
+/// Bytes exchanged with a peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ipc", derive(Binary))]
+pub struct PeerTraffic {
+	/// Bytes sent
+	pub sent: u64,
+	/// Bytes received
+	pub received: u64,
+}
+
+impl From<TrafficStats> for PeerTraffic {
+	fn from(t: TrafficStats) -> Self {
+		PeerTraffic {
+			sent: t.sent as u64,
+			received: t.received as u64,
+		}
+	}
+}
+
+/// Payload bytes of packets exchanged compressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ipc", derive(Binary))]
+pub struct CompressionStats {
+	/// Bytes sent, before compression
+	pub raw_sent: u64,
+	/// Bytes sent, after compression
+	pub sent: u64,
+	/// Bytes received, after decompression
+	pub raw_received: u64,
+	/// Bytes received, before decompression
+	pub received: u64,
+	/// Bytes saved by compression in both directions
+	pub savings: u64,
+}
+
+/// Reputation of a node.
+#[derive(Debug)]
+#[cfg_attr(feature = "ipc", derive(Binary))]
+pub struct PeerReputation {
+	/// Public node id
+	pub id: String,
+	/// Score of the node, negative for nodes misbehaving more often than being useful.
+	pub score: i32,
+	/// Unix time until which the node is banned.
+	pub banned_until: Option<u64>,
+}
+
+/// Static peer group with its own connection slots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ipc", derive(Binary))]
+pub struct PeerGroup {
+	/// Group name
+	pub name: String,
+	/// Node urls of the group members
+	pub nodes: Vec<String>,
+	/// Number of connections to group members kept open regardless of the peer limits
+	pub min_slots: u32,
+	/// Maximum number of connections to group members
+	pub max_slots: u32,
+	/// Members bypass the peer limits, the reserved-only mode and bans
+	pub trusted: bool,
+}
+
+impl From<PeerGroupConfig> for PeerGroup {
+	fn from(group: PeerGroupConfig) -> Self {
+		PeerGroup {
+			name: group.name,
+			nodes: group.nodes,
+			min_slots: group.min_slots,
+			max_slots: group.max_slots,
+			trusted: group.trusted,
+		}
+	}
+}
+
+impl From<PeerGroup> for PeerGroupConfig {
+	fn from(group: PeerGroup) -> Self {
+		PeerGroupConfig {
+			name: group.name,
+			nodes: group.nodes,
+			min_slots: group.min_slots,
+			max_slots: group.max_slots,
+			trusted: group.trusted,
+		}
+	}
+}
+
+/// Peer group status.
+#[derive(Debug)]
+#[cfg_attr(feature = "ipc", derive(Binary))]
+pub struct PeerGroupInfo {
+	/// Group name
+	pub name: String,
+	/// Public node ids of the group members
+	pub nodes: Vec<String>,
+	/// Number of connections to group members kept open regardless of the peer limits
+	pub min_slots: u32,
+	/// Maximum number of connections to group members
+	pub max_slots: u32,
+	/// Members bypass the peer limits, the reserved-only mode and bans
+	pub trusted: bool,
+	/// Number of connected group members
+	pub connected: u32,
+}
+
+impl From<NetworkPeerGroupInfo> for PeerGroupInfo {
+	fn from(info: NetworkPeerGroupInfo) -> Self {
+		PeerGroupInfo {
+			name: info.name,
+			nodes: info.nodes.iter().map(|id| id.hex()).collect(),
+			min_slots: info.min_slots,
+			max_slots: info.max_slots,
+			trusted: info.trusted,
+			connected: info.connected as u32,
+		}
+	}
+}
+
 /// Ethereum protocol info.
 #[derive(Debug)]
 #[cfg_attr(feature = "ipc", derive(Binary))]
@@ -204,7 +335,7 @@ impl EthSync {
 					flow_params: Default::default(),
 					capabilities: Capabilities {
 						serve_headers: true,
-						serve_chain_since: Some(pruning_info.earliest_chain),
+						serve_chain_since: Some(::std::cmp::max(pruning_info.earliest_chain, pruning_info.earliest_body)),
 						serve_state_since: Some(pruning_info.earliest_state),
 						tx_relay: true,
 					},
@@ -217,17 +348,13 @@ impl EthSync {
 			})
 		};
 
-		let chain_sync = ChainSync::new(params.config, &*params.chain);
-		let service = NetworkService::new(params.network_config.clone().into_basic()?)?;
+		let eth_handler = SyncProtocolHandler::new(params.config, params.chain, params.snapshot_service);
+		let network_config = with_chain_entry(params.network_config.clone().into_basic()?, params.config.network_id);
+		let service = NetworkService::new(network_config)?;
 
 		let sync = Arc::new(EthSync {
 			network: service,
-			eth_handler: Arc::new(SyncProtocolHandler {
-				sync: RwLock::new(chain_sync),
-				chain: params.chain,
-				snapshot_service: params.snapshot_service,
-				overlay: RwLock::new(HashMap::new()),
-			}),
+			eth_handler: Arc::new(eth_handler),
 			light_proto: light_proto,
 			subprotocol_name: params.config.subprotocol_name,
 			light_subprotocol_name: params.config.light_subprotocol_name,
@@ -263,6 +390,10 @@ impl SyncProvider for EthSync {
 					capabilities: session_info.peer_capabilities.into_iter().map(|c| c.to_string()).collect(),
 					remote_address: session_info.remote_address,
 					local_address: session_info.local_address,
+					traffic: session_info.traffic.into(),
+					protocol_traffic: session_info.protocol_traffic.into_iter()
+						.map(|(p, t)| (String::from_utf8_lossy(&p).into_owned(), t.into()))
+						.collect(),
 					eth_info: eth_sync.peer_info(&peer_id),
 					les_info: light_proto.as_ref().and_then(|lp| lp.peer_status(&peer_id)).map(Into::into),
 				})
@@ -274,6 +405,10 @@ impl SyncProvider for EthSync {
 		self.network.external_url()
 	}
 
+	fn enr(&self) -> Option<String> {
+		self.network.local_enr()
+	}
+
 	fn transactions_stats(&self) -> BTreeMap<H256, TransactionStats> {
 		let sync = self.eth_handler.sync.read();
 		sync.transactions_stats()
@@ -281,9 +416,23 @@ impl SyncProvider for EthSync {
 			.map(|(hash, stats)| (*hash, stats.into()))
 			.collect()
 	}
+
+	fn compression_stats(&self) -> CompressionStats {
+		let stats = self.network.stats();
+		let (raw_sent, sent) = stats.compressed_send();
+		let (raw_received, received) = stats.compressed_recv();
+		CompressionStats {
+			raw_sent: raw_sent as u64,
+			sent: sent as u64,
+			raw_received: raw_received as u64,
+			received: received as u64,
+			savings: stats.compression_savings() as u64,
+		}
+	}
 }
 
-struct SyncProtocolHandler {
+/// Network handler of the eth and warp sync protocols.
+pub struct SyncProtocolHandler {
 	/// Shared blockchain client.
 	chain: Arc<BlockChainClient>,
 	/// Shared snapshot service.
@@ -294,6 +443,18 @@ struct SyncProtocolHandler {
 	overlay: RwLock<HashMap<BlockNumber, Bytes>>,
 }
 
+impl SyncProtocolHandler {
+	/// Create a new handler.
+	pub fn new(config: SyncConfig, chain: Arc<BlockChainClient>, snapshot_service: Arc<SnapshotService>) -> SyncProtocolHandler {
+		SyncProtocolHandler {
+			sync: RwLock::new(ChainSync::new(config, &*chain)),
+			chain: chain,
+			snapshot_service: snapshot_service,
+			overlay: RwLock::new(HashMap::new()),
+		}
+	}
+}
+
 impl NetworkProtocolHandler for SyncProtocolHandler {
 	fn initialize(&self, io: &NetworkContext) {
 		if io.subprotocol_name() != WARP_SYNC_PROTOCOL_ID {
@@ -432,6 +593,20 @@ pub trait ManageNetwork : Send + Sync {
 	fn remove_reserved_peer(&self, peer: String) -> Result<(), String>;
 	/// Add reserved peer
 	fn add_reserved_peer(&self, peer: String) -> Result<(), String>;
+	/// Ban the peer given by its node id or url for `duration` seconds
+	fn ban_peer(&self, peer: String, duration: u64) -> Result<(), String>;
+	/// Query reputations of the peers which have been reported or banned
+	fn peer_reputations(&self) -> Vec<PeerReputation>;
+	/// Create a peer group or update the slots of an existing one
+	fn set_peer_group(&self, name: String, min_slots: u32, max_slots: u32, trusted: bool) -> Result<(), String>;
+	/// Remove a peer group
+	fn remove_peer_group(&self, name: String) -> Result<(), String>;
+	/// Add a peer to a peer group
+	fn add_peer_group_node(&self, group: String, peer: String) -> Result<(), String>;
+	/// Remove a peer from a peer group
+	fn remove_peer_group_node(&self, group: String, peer: String) -> Result<(), String>;
+	/// Query the peer groups
+	fn peer_groups(&self) -> Vec<PeerGroupInfo>;
 	/// Start network
 	fn start_network(&self);
 	/// Stop network
@@ -459,6 +634,34 @@ impl ManageNetwork for EthSync {
 		self.network.add_reserved_peer(&peer).map_err(|e| format!("{:?}", e))
 	}
 
+	fn ban_peer(&self, peer: String, duration: u64) -> Result<(), String> {
+		self.network.ban_peer(&peer, duration).map_err(|e| format!("{:?}", e))
+	}
+
+	fn peer_reputations(&self) -> Vec<PeerReputation> {
+		peer_reputations(&self.network)
+	}
+
+	fn set_peer_group(&self, name: String, min_slots: u32, max_slots: u32, trusted: bool) -> Result<(), String> {
+		self.network.set_peer_group(&name, min_slots, max_slots, trusted).map_err(|e| format!("{:?}", e))
+	}
+
+	fn remove_peer_group(&self, name: String) -> Result<(), String> {
+		self.network.remove_peer_group(&name).map_err(|e| format!("{:?}", e))
+	}
+
+	fn add_peer_group_node(&self, group: String, peer: String) -> Result<(), String> {
+		self.network.add_peer_group_node(&group, &peer).map_err(|e| format!("{:?}", e))
+	}
+
+	fn remove_peer_group_node(&self, group: String, peer: String) -> Result<(), String> {
+		self.network.remove_peer_group_node(&group, &peer).map_err(|e| format!("{:?}", e))
+	}
+
+	fn peer_groups(&self) -> Vec<PeerGroupInfo> {
+		self.network.peer_groups().into_iter().map(Into::into).collect()
+	}
+
 	fn start_network(&self) {
 		self.start();
 	}
@@ -481,6 +684,14 @@ impl ManageNetwork for EthSync {
 	}
 }
 
+fn peer_reputations(network: &NetworkService) -> Vec<PeerReputation> {
+	network.peer_reputations().into_iter().map(|(id, reputation)| PeerReputation {
+		id: id.hex(),
+		score: reputation.score,
+		banned_until: reputation.banned_until,
+	}).collect()
+}
+
 /// IP fiter
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "ipc", binary)]
@@ -505,6 +716,30 @@ impl AllowIP {
 	}
 }
 
+/// Port mapping method
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "ipc", binary)]
+pub enum NatType {
+	/// Try UPnP, then NAT-PMP and PCP
+	Any,
+	/// UPnP IGD only
+	Upnp,
+	/// NAT-PMP or PCP only
+	NatPmp,
+}
+
+impl NatType {
+	/// Attempt to parse the port mapping method from a string.
+	pub fn parse(s: &str) -> Option<Self> {
+		match s {
+			"any" => Some(NatType::Any),
+			"upnp" => Some(NatType::Upnp),
+			"natpmp" => Some(NatType::NatPmp),
+			_ => None,
+		}
+	}
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "ipc", binary)]
 /// Network service configuration
@@ -521,6 +756,8 @@ pub struct NetworkConfiguration {
 	pub udp_port: Option<u16>,
 	/// Enable NAT configuration
 	pub nat_enabled: bool,
+	/// Port mapping method used if NAT configuration is enabled
+	pub nat_type: NatType,
 	/// Enable discovery
 	pub discovery_enabled: bool,
 	/// List of initial node addresses
@@ -539,8 +776,14 @@ pub struct NetworkConfiguration {
 	pub reserved_nodes: Vec<String>,
 	/// The non-reserved peer mode.
 	pub allow_non_reserved: bool,
+	/// Static peer groups.
+	pub peer_groups: Vec<PeerGroup>,
 	/// IP Filtering
 	pub allow_ips: AllowIP,
+	/// Maximum upload rate in bytes per second. Unlimited if none.
+	pub max_upload_rate: Option<u64>,
+	/// Maximum download rate in bytes per second. Unlimited if none.
+	pub max_download_rate: Option<u64>,
 }
 
 impl NetworkConfiguration {
@@ -563,6 +806,11 @@ impl NetworkConfiguration {
 			public_address:  match self.public_address { None => None, Some(addr) => Some(SocketAddr::from_str(&addr)?) },
 			udp_port: self.udp_port,
 			nat_enabled: self.nat_enabled,
+			nat_type: match self.nat_type {
+				NatType::Any => NetworkNatType::Any,
+				NatType::Upnp => NetworkNatType::Upnp,
+				NatType::NatPmp => NetworkNatType::NatPmp,
+			},
 			discovery_enabled: self.discovery_enabled,
 			boot_nodes: self.boot_nodes,
 			use_secret: self.use_secret,
@@ -577,6 +825,10 @@ impl NetworkConfiguration {
 				AllowIP::Public => NetworkAllowIP::Public,
 			},
 			non_reserved_mode: if self.allow_non_reserved { NonReservedPeerMode::Accept } else { NonReservedPeerMode::Deny },
+			peer_groups: self.peer_groups.into_iter().map(Into::into).collect(),
+			enr_entries: BTreeMap::new(),
+			max_upload_rate: self.max_upload_rate.map(|r| r as usize),
+			max_download_rate: self.max_download_rate.map(|r| r as usize),
 		})
 	}
 }
@@ -590,6 +842,11 @@ impl From<BasicNetworkConfiguration> for NetworkConfiguration {
 			public_address: other.public_address.and_then(|addr| Some(format!("{}", addr))),
 			udp_port: other.udp_port,
 			nat_enabled: other.nat_enabled,
+			nat_type: match other.nat_type {
+				NetworkNatType::Any => NatType::Any,
+				NetworkNatType::Upnp => NatType::Upnp,
+				NetworkNatType::NatPmp => NatType::NatPmp,
+			},
 			discovery_enabled: other.discovery_enabled,
 			boot_nodes: other.boot_nodes,
 			use_secret: other.use_secret,
@@ -604,10 +861,19 @@ impl From<BasicNetworkConfiguration> for NetworkConfiguration {
 				NetworkAllowIP::Public => AllowIP::Public,
 			},
 			allow_non_reserved: match other.non_reserved_mode { NonReservedPeerMode::Accept => true, _ => false } ,
+			peer_groups: other.peer_groups.into_iter().map(Into::into).collect(),
+			max_upload_rate: other.max_upload_rate.map(|r| r as u64),
+			max_download_rate: other.max_download_rate.map(|r| r as u64),
 		}
 	}
 }
 
+// advertise the network id in the node record.
+fn with_chain_entry(mut config: BasicNetworkConfiguration, network_id: u64) -> BasicNetworkConfiguration {
+	config.enr_entries.insert("chain".into(), ::rlp::encode(&network_id).to_vec());
+	config
+}
+
 /// Configuration for IPC service.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "ipc", binary)]
@@ -666,7 +932,7 @@ impl LightSync {
 			Arc::new(light_proto)
 		};
 
-		let service = try!(NetworkService::new(params.network_config));
+		let service = try!(NetworkService::new(with_chain_entry(params.network_config, params.network_id)));
 
 		Ok(LightSync {
 			proto: light_proto,
@@ -693,6 +959,34 @@ impl ManageNetwork for LightSync {
 		self.network.add_reserved_peer(&peer).map_err(|e| format!("{:?}", e))
 	}
 
+	fn ban_peer(&self, peer: String, duration: u64) -> Result<(), String> {
+		self.network.ban_peer(&peer, duration).map_err(|e| format!("{:?}", e))
+	}
+
+	fn peer_reputations(&self) -> Vec<PeerReputation> {
+		peer_reputations(&self.network)
+	}
+
+	fn set_peer_group(&self, name: String, min_slots: u32, max_slots: u32, trusted: bool) -> Result<(), String> {
+		self.network.set_peer_group(&name, min_slots, max_slots, trusted).map_err(|e| format!("{:?}", e))
+	}
+
+	fn remove_peer_group(&self, name: String) -> Result<(), String> {
+		self.network.remove_peer_group(&name).map_err(|e| format!("{:?}", e))
+	}
+
+	fn add_peer_group_node(&self, group: String, peer: String) -> Result<(), String> {
+		self.network.add_peer_group_node(&group, &peer).map_err(|e| format!("{:?}", e))
+	}
+
+	fn remove_peer_group_node(&self, group: String, peer: String) -> Result<(), String> {
+		self.network.remove_peer_group_node(&group, &peer).map_err(|e| format!("{:?}", e))
+	}
+
+	fn peer_groups(&self) -> Vec<PeerGroupInfo> {
+		self.network.peer_groups().into_iter().map(Into::into).collect()
+	}
+
 	fn start_network(&self) {
 		match self.network.start() {
 			Err(NetworkError::StdIo(ref e)) if  e.kind() == io::ErrorKind::AddrInUse => warn!("Network port {:?} is already in use, make sure that another instance of an Ethereum client is not running or change the port using the --port option.", self.network.config().listen_address.expect("Listen address is not set.")),
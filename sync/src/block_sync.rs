@@ -103,6 +103,8 @@ pub struct BlockDownloader {
 	download_receipts: bool,
 	/// Sync up to the block with this hash.
 	target_hash: Option<H256>,
+	/// Target block and receipts, once downloaded.
+	target_block: Option<(Bytes, Option<Bytes>)>,
 	/// Probing range for seeking common best block.
 	retract_step: u64,
 	/// Whether reorg should be limited.
@@ -125,6 +127,7 @@ impl BlockDownloader {
 			round_parents: VecDeque::new(),
 			download_receipts: sync_receipts,
 			target_hash: None,
+			target_block: None,
 			retract_step: 1,
 			limit_reorg: true,
 		}
@@ -144,6 +147,7 @@ impl BlockDownloader {
 			round_parents: VecDeque::new(),
 			download_receipts: sync_receipts,
 			target_hash: None,
+			target_block: None,
 			retract_step: 1,
 			limit_reorg: false,
 		}
@@ -181,6 +185,12 @@ impl BlockDownloader {
 		self.target_hash = Some(hash.clone());
 	}
 
+	/// Take the target block and its receipts. The target block is downloaded
+	/// but not imported.
+	pub fn take_target_block(&mut self) -> Option<(Bytes, Option<Bytes>)> {
+		self.target_block.take()
+	}
+
 	/// Unmark header as being downloaded.
 	pub fn clear_header_download(&mut self, hash: &H256) {
 		self.blocks.clear_header_download(hash)
@@ -487,6 +497,7 @@ impl BlockDownloader {
 			}
 
 			if self.target_hash.as_ref().map_or(false, |t| t == &h) {
+				self.target_block = Some((block, receipts));
 				self.state = State::Complete;
 				trace!(target: "sync", "Sync target reached");
 				return Ok(());
@@ -94,6 +94,7 @@ use rlp::*;
 use network::*;
 use ethcore::header::{BlockNumber, Header as BlockHeader};
 use ethcore::client::{BlockChainClient, BlockStatus, BlockId, BlockChainInfo, BlockImportError, BlockQueueInfo};
+use ethcore::encoded;
 use ethcore::error::*;
 use ethcore::snapshot::{ManifestData, RestorationStatus};
 use sync_io::SyncIo;
@@ -102,6 +103,7 @@ use super::SyncConfig;
 use block_sync::{BlockDownloader, BlockRequest, BlockDownloaderImportError as DownloaderImportError, DownloadAction};
 use rand::Rng;
 use snapshot::{Snapshot, ChunkType};
+use state_sync::{StateSync, StateImportError};
 use api::{EthProtocolInfo as PeerInfoDigest, WARP_SYNC_PROTOCOL_ID};
 use transactions_stats::{TransactionsStats, Stats as TransactionStats};
 
@@ -116,6 +118,7 @@ const PROTOCOL_VERSION_2: u8 = 2;
 const MAX_BODIES_TO_SEND: usize = 256;
 const MAX_HEADERS_TO_SEND: usize = 512;
 const MAX_NODE_DATA_TO_SEND: usize = 1024;
+const MAX_NODE_DATA_TO_REQUEST: usize = 384;
 const MAX_RECEIPTS_TO_SEND: usize = 1024;
 const MAX_RECEIPTS_HEADERS_TO_SEND: usize = 256;
 const MIN_PEERS_PROPAGATION: usize = 4;
@@ -130,6 +133,16 @@ const MAX_TRANSACTIONS_TO_PROPAGATE: usize = 64;
 // Min number of blocks to be behind for a snapshot sync
 const SNAPSHOT_RESTORE_THRESHOLD: BlockNumber = 100000;
 const SNAPSHOT_MIN_PEERS: usize = 3;
+// Fast sync pivot distance from the best network block.
+const FAST_SYNC_PIVOT_DISTANCE: BlockNumber = 64;
+// Pivot age after which a new pivot is chosen.
+const FAST_SYNC_PIVOT_MAX_AGE: BlockNumber = 256;
+// Minimal number of blocks to download for fast sync to be used.
+const FAST_SYNC_THRESHOLD: BlockNumber = 1024;
+// Number of peers that have to agree on a fast sync pivot.
+const FAST_SYNC_PIVOT_MIN_PEERS: usize = 3;
+// Time after which a peer is asked for its best block again while fast syncing.
+const FAST_SYNC_HEAD_EXPIRY_SEC: u64 = 60;
 
 const STATUS_PACKET: u8 = 0x00;
 const NEW_BLOCK_HASHES_PACKET: u8 = 0x01;
@@ -165,6 +178,7 @@ const RECEIPTS_TIMEOUT_SEC: u64 = 10;
 const FORK_HEADER_TIMEOUT_SEC: u64 = 3;
 const SNAPSHOT_MANIFEST_TIMEOUT_SEC: u64 = 3;
 const SNAPSHOT_DATA_TIMEOUT_SEC: u64 = 60;
+const NODE_DATA_TIMEOUT_SEC: u64 = 10;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 /// Sync state
@@ -177,6 +191,8 @@ pub enum SyncState {
 	SnapshotData,
 	/// Waiting for snapshot restoration progress.
 	SnapshotWaiting,
+	/// Downloading blocks without execution and the state at the fast sync pivot block.
+	FastSync,
 	/// Downloading new blocks
 	Blocks,
 	/// Initial chain sync complete. Waiting for new packets
@@ -218,6 +234,12 @@ pub struct SyncStatus {
 	pub snapshot_chunks_done: usize,
 	/// Last fully downloaded and imported ancient block number (if any).
 	pub last_imported_old_block_number: Option<BlockNumber>,
+	/// Fast sync pivot block number (if any).
+	pub fast_sync_pivot: Option<BlockNumber>,
+	/// State trie nodes downloaded by fast sync.
+	pub state_nodes_done: usize,
+	/// State trie nodes known to be missing by fast sync.
+	pub state_nodes_pending: usize,
 }
 
 impl SyncStatus {
@@ -255,6 +277,8 @@ enum PeerAsking {
 	BlockReceipts,
 	SnapshotManifest,
 	SnapshotData,
+	PivotHeaders,
+	NodeData,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
@@ -296,6 +320,8 @@ struct PeerInfo {
 	asking_hash: Option<H256>,
 	/// Holds requested snapshot chunk hash if any.
 	asking_snapshot_data: Option<H256>,
+	/// Holds requested state node hashes.
+	asking_nodes: Vec<H256>,
 	/// Request timestamp
 	ask_time: u64,
 	/// Holds a set of transactions recently sent to this peer to avoid spamming.
@@ -310,6 +336,10 @@ struct PeerInfo {
 	snapshot_number: Option<BlockNumber>,
 	/// Block set requested
 	block_set: Option<BlockSet>,
+	/// Best block number reported for fast sync and the time it was reported.
+	pivot_head: Option<(BlockNumber, u64)>,
+	/// Holds requested fast sync pivot number if currently requesting the pivot by number.
+	asking_pivot: Option<BlockNumber>,
 }
 
 impl PeerInfo {
@@ -324,6 +354,7 @@ impl PeerInfo {
 	fn reset_asking(&mut self) {
 		self.asking_blocks.clear();
 		self.asking_hash = None;
+		self.asking_pivot = None;
 		// mark any pending requests as expired
 		if self.asking != PeerAsking::Nothing && self.is_allowed() {
 			self.expired = true;
@@ -376,6 +407,18 @@ pub struct ChainSync {
 	transactions_stats: TransactionsStats,
 	/// Enable ancient block downloading
 	download_old_blocks: bool,
+	/// Fall back to fast sync when no snapshots are found.
+	fast_sync: bool,
+	/// Fast sync pivot block header.
+	pivot: Option<encoded::Header>,
+	/// Fast sync pivot block and receipts, once downloaded.
+	pivot_block: Option<(Bytes, Bytes)>,
+	/// Fast sync pivot candidates with the peers that have them.
+	pivot_votes: HashMap<H256, (encoded::Header, HashSet<PeerId>)>,
+	/// Peers that have no state at the fast sync pivot.
+	pivot_unavailable: HashSet<PeerId>,
+	/// Fast sync state downloader.
+	state_sync: StateSync,
 }
 
 type RlpResponseResult = Result<Option<(PacketId, RlpStream)>, PacketDecodeError>;
@@ -397,11 +440,23 @@ impl ChainSync {
 			network_id: config.network_id,
 			fork_block: config.fork_block,
 			download_old_blocks: config.download_old_blocks,
+			fast_sync: config.fast_sync,
+			pivot: None,
+			pivot_block: None,
+			pivot_votes: HashMap::new(),
+			pivot_unavailable: HashSet::new(),
+			state_sync: StateSync::new(),
 			snapshot: Snapshot::new(),
 			sync_start_time: None,
 			transactions_stats: TransactionsStats::default(),
 		};
 		sync.update_targets(chain);
+		// an interrupted fast sync has to be finished, even if disabled now.
+		// the pivot is replaced once peers turn out not to have its state.
+		if let Some(pivot) = chain.fast_sync_pivot() {
+			debug!(target: "sync", "Resuming fast sync at block #{}", pivot.number());
+			sync.set_pivot(chain, pivot);
+		}
 		sync
 	}
 
@@ -422,6 +477,9 @@ impl ChainSync {
 			num_active_peers: self.peers.values().filter(|p| p.is_allowed() && p.asking != PeerAsking::Nothing).count(),
 			num_snapshot_chunks: self.snapshot.total_chunks(),
 			snapshot_chunks_done: self.snapshot.done_chunks(),
+			fast_sync_pivot: self.pivot.as_ref().map(|h| h.number()),
+			state_nodes_done: self.state_sync.done_nodes(),
+			state_nodes_pending: self.state_sync.pending_nodes(),
 			mem_used:
 				self.new_blocks.heap_size()
 				+ self.old_blocks.as_ref().map_or(0, |d| d.heap_size())
@@ -477,7 +535,9 @@ impl ChainSync {
 				}
 			}
 		}
-		self.state = SyncState::Idle;
+		if self.state != SyncState::FastSync {
+			self.state = SyncState::Idle;
+		}
 		// Reactivate peers only if some progress has been made
 		// since the last sync round of if starting fresh.
 		self.active_peers = self.peers.keys().cloned().collect();
@@ -541,8 +601,15 @@ impl ChainSync {
 				self.start_snapshot_sync(io, peers);
 			}
 		} else if timeout {
-			trace!(target: "sync", "No snapshots found, starting full sync");
-			self.state = SyncState::Idle;
+			let chain_info = io.chain().chain_info();
+			let ahead = self.peers.values().any(|p| p.is_allowed() && p.difficulty.map_or(false, |d| d > chain_info.total_difficulty));
+			if self.fast_sync && our_best_block == 0 && ahead {
+				trace!(target: "sync", "No snapshots found, starting fast sync");
+				self.state = SyncState::FastSync;
+			} else {
+				trace!(target: "sync", "No snapshots found, starting full sync");
+				self.state = SyncState::Idle;
+			}
 			self.continue_sync(io);
 		}
 	}
@@ -557,6 +624,45 @@ impl ChainSync {
 		self.state = SyncState::SnapshotManifest;
 	}
 
+	/// Set a new fast sync pivot and start downloading its state.
+	fn set_pivot(&mut self, chain: &BlockChainClient, pivot: encoded::Header) {
+		trace!(target: "sync", "Fast sync pivot set to #{} ({})", pivot.number(), pivot.hash());
+		chain.set_fast_sync_pivot(Some(pivot.clone()));
+		self.state_sync.reset_to(chain, &pivot.state_root());
+		self.pivot = Some(pivot);
+		self.pivot_block = None;
+		self.pivot_votes.clear();
+		self.pivot_unavailable.clear();
+		self.state = SyncState::FastSync;
+		self.update_targets(chain);
+	}
+
+	/// Import the pivot block once it and its state are downloaded.
+	fn maybe_complete_fast_sync(&mut self, io: &mut SyncIo) {
+		if self.state != SyncState::FastSync || !self.state_sync.is_complete() {
+			return;
+		}
+		let (block, receipts) = match self.pivot_block.take() {
+			Some(block) => block,
+			None => return,
+		};
+		match io.chain().import_fast_sync_pivot(block, receipts) {
+			Ok(_) | Err(BlockImportError::Import(ImportError::AlreadyInChain)) => {
+				debug!(target: "sync", "Fast sync complete");
+				self.pivot = None;
+				self.state_sync.clear();
+				self.state = SyncState::Idle;
+				self.restart(io);
+			},
+			Err(e) => {
+				debug!(target: "sync", "Error importing fast sync pivot: {:?}", e);
+				// choose a new pivot
+				self.pivot = None;
+				self.state_sync.clear();
+			},
+		}
+	}
+
 	/// Restart sync disregarding the block queue status. May end up re-downloading up to QUEUE_SIZE blocks
 	pub fn restart(&mut self, io: &mut SyncIo) {
 		self.update_targets(io.chain());
@@ -564,12 +670,22 @@ impl ChainSync {
 	}
 
 	/// Update sync after the blockchain has been changed externally.
-	pub fn update_targets(&mut self, chain: &BlockChainClient) {
+	pub fn update_targets(&mut self, client: &BlockChainClient) {
 		// Do not assume that the block queue/chain still has our last_imported_block
-		let chain = chain.chain_info();
+		let chain = client.chain_info();
 		self.new_blocks = BlockDownloader::new(false, &chain.best_block_hash, chain.best_block_number);
 		self.old_blocks = None;
-		if self.download_old_blocks {
+		if let Some(pivot) = client.fast_sync_pivot() {
+			// Blocks below the fast sync pivot are imported without execution.
+			let (start_hash, start_number) = match (chain.ancient_block_hash, chain.ancient_block_number) {
+				(Some(hash), Some(number)) => (hash, number),
+				_ => (chain.best_block_hash, chain.best_block_number),
+			};
+			trace!(target: "sync", "Downloading blocks from {:?} (#{}) till fast sync pivot #{}", start_hash, start_number, pivot.number());
+			let mut downloader = BlockDownloader::with_unlimited_reorg(true, &start_hash, start_number);
+			downloader.set_target(&pivot.hash());
+			self.old_blocks = Some(downloader);
+		} else if self.download_old_blocks {
 			if let (Some(ancient_block_hash), Some(ancient_block_number)) = (chain.ancient_block_hash, chain.ancient_block_number) {
 
 				trace!(target: "sync", "Downloading old blocks from {:?} (#{}) till {:?} (#{:?})", ancient_block_hash, ancient_block_number, chain.first_block_hash, chain.first_block_number);
@@ -602,9 +718,12 @@ impl ChainSync {
 			expired: false,
 			confirmation: if self.fork_block.is_none() { ForkConfirmation::Confirmed } else { ForkConfirmation::Unconfirmed },
 			asking_snapshot_data: None,
+			asking_nodes: Vec::new(),
 			snapshot_hash: if warp_protocol { Some(r.val_at(5)?) } else { None },
 			snapshot_number: if warp_protocol { Some(r.val_at(6)?) } else { None },
 			block_set: None,
+			pivot_head: None,
+			asking_pivot: None,
 		};
 
 		if self.sync_start_time.is_none() {
@@ -655,6 +774,9 @@ impl ChainSync {
 	#[cfg_attr(feature="dev", allow(cyclomatic_complexity, needless_borrow))]
 	/// Called by peer once it has new block headers during sync
 	fn on_peer_block_headers(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		if self.peers.get(&peer_id).map_or(false, |p| p.asking == PeerAsking::PivotHeaders) {
+			return self.on_peer_pivot_headers(io, peer_id, r);
+		}
 		let confirmed = match self.peers.get_mut(&peer_id) {
 			Some(ref mut peer) if peer.asking == PeerAsking::ForkHeader => {
 				peer.asking = PeerAsking::Nothing;
@@ -727,6 +849,7 @@ impl ChainSync {
 
 		match result {
 			Err(DownloaderImportError::Useless) => {
+				io.report_peer(peer_id, PeerReport::Useless);
 				self.deactivate_peer(io, peer_id);
 			},
 			Err(DownloaderImportError::Invalid) => {
@@ -736,6 +859,7 @@ impl ChainSync {
 				return Ok(());
 			},
 			Ok(DownloadAction::Reset) => {
+				io.report_peer(peer_id, PeerReport::Success);
 				// mark all outstanding requests as expired
 				trace!("Resetting downloads for {:?}", block_set);
 				for (_, ref mut p) in self.peers.iter_mut().filter(|&(_, ref p)| p.block_set == Some(block_set)) {
@@ -743,7 +867,7 @@ impl ChainSync {
 				}
 
 			}
-			Ok(DownloadAction::None) => {},
+			Ok(DownloadAction::None) => io.report_peer(peer_id, PeerReport::Success),
 		}
 
 		self.collect_blocks(io, block_set);
@@ -754,6 +878,119 @@ impl ChainSync {
 		Ok(())
 	}
 
+	/// Called by peer once it has the headers to choose the fast sync pivot from
+	fn on_peer_pivot_headers(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		let asked = self.peers.get_mut(&peer_id).and_then(|p| p.asking_pivot.take());
+		let item_count = r.item_count();
+		trace!(target: "sync", "{} -> PivotHeaders ({} entries), state = {:?}", peer_id, item_count, self.state);
+		if !self.reset_peer_asking(peer_id, PeerAsking::PivotHeaders) || self.state != SyncState::FastSync {
+			trace!(target: "sync", "Ignored unexpected pivot headers");
+			self.continue_sync(io);
+			return Ok(());
+		}
+		if item_count == 0 {
+			io.report_peer(peer_id, PeerReport::Useless);
+			self.deactivate_peer(io, peer_id);
+			self.continue_sync(io);
+			return Ok(());
+		}
+
+		let mut headers = Vec::with_capacity(item_count);
+		for header_rlp in r.iter() {
+			let header: BlockHeader = header_rlp.as_val()?;
+			if !io.chain().verify_header_seal(&header) {
+				trace!(target: "sync", "{}: Invalid pivot header seal", peer_id);
+				io.disable_peer(peer_id);
+				self.deactivate_peer(io, peer_id);
+				self.continue_sync(io);
+				return Ok(());
+			}
+			headers.push(encoded::Header::new(header_rlp.as_raw().to_vec()));
+		}
+		let valid = match asked {
+			// the pivot candidate at the requested number.
+			Some(number) => item_count == 1 && headers[0].number() == number,
+			// the best block and the pivot candidate below it, if the chain is long enough.
+			None => item_count == 1 || (item_count == 2 && headers[1].number() + FAST_SYNC_PIVOT_DISTANCE == headers[0].number()),
+		};
+		if !valid {
+			trace!(target: "sync", "{}: Invalid pivot headers", peer_id);
+			io.disable_peer(peer_id);
+			self.deactivate_peer(io, peer_id);
+			self.continue_sync(io);
+			return Ok(());
+		}
+
+		if asked.is_none() {
+			if let Some(ref mut peer) = self.peers.get_mut(&peer_id) {
+				peer.pivot_head = Some((headers[0].number(), time::precise_time_ns()));
+			}
+		}
+		let candidate = match asked {
+			Some(_) => headers.into_iter().next(),
+			None => headers.into_iter().nth(1),
+		};
+		if let Some(candidate) = candidate {
+			let hash = candidate.hash();
+			self.pivot_votes.entry(hash).or_insert_with(|| (candidate, HashSet::new())).1.insert(peer_id);
+		}
+
+		self.choose_pivot(io);
+		self.continue_sync(io);
+		Ok(())
+	}
+
+	/// Best block number reached by at least `FAST_SYNC_PIVOT_MIN_PEERS` peers, as reported for fast sync.
+	fn network_best_block(&self) -> Option<BlockNumber> {
+		let mut heads: Vec<BlockNumber> = self.peers.values().filter_map(|p| p.pivot_head.map(|(n, _)| n)).collect();
+		heads.sort_by(|a, b| b.cmp(a));
+		heads.get(FAST_SYNC_PIVOT_MIN_PEERS - 1).cloned()
+	}
+
+	/// Checks if a new fast sync pivot has to be chosen: the current one is too old
+	/// or enough peers have no state for it.
+	fn pivot_expired(&self) -> bool {
+		self.pivot.as_ref().map_or(true, |p| !self.state_sync.is_complete() && (
+			self.pivot_unavailable.len() >= FAST_SYNC_PIVOT_MIN_PEERS ||
+			self.network_best_block().map_or(false, |best| p.number() + FAST_SYNC_PIVOT_MAX_AGE < best)
+		))
+	}
+
+	/// Switch to the highest pivot candidate enough peers agree on if a new pivot is needed,
+	/// or to full sync if the chain is too short for fast sync.
+	fn choose_pivot(&mut self, io: &mut SyncIo) {
+		// once blocks are imported without execution there is no going back to full sync.
+		let resuming = self.pivot.is_some() || io.chain().fast_sync_pivot().is_some();
+		let highest_head = self.peers.values().filter_map(|p| p.pivot_head.map(|(n, _)| n)).max().unwrap_or(0);
+		if !resuming && highest_head < io.chain().chain_info().best_block_number + FAST_SYNC_THRESHOLD + FAST_SYNC_PIVOT_DISTANCE {
+			trace!(target: "sync", "Chain is too short for fast sync, starting full sync");
+			self.pivot_votes.clear();
+			self.state = SyncState::Idle;
+			return;
+		}
+
+		let best = match self.network_best_block() {
+			Some(best) => best,
+			None => return,
+		};
+		if best > self.highest_block.unwrap_or(0) {
+			self.highest_block = Some(best);
+		}
+		if !self.pivot_expired() {
+			return;
+		}
+
+		self.pivot_votes.retain(|_, &mut (ref header, _)| header.number() + FAST_SYNC_PIVOT_MAX_AGE >= best);
+		let current = self.pivot.as_ref().map(|p| p.number());
+		let pivot = self.pivot_votes.values()
+			.filter(|&&(ref header, ref peers)| peers.len() >= FAST_SYNC_PIVOT_MIN_PEERS && current.map_or(true, |n| n < header.number()))
+			.max_by_key(|&&(ref header, _)| header.number())
+			.map(|&(ref header, _)| header.clone());
+		if let Some(pivot) = pivot {
+			self.set_pivot(io.chain(), pivot);
+		}
+	}
+
 	/// Called by peer once it has new block bodies
 	fn on_peer_block_bodies(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
 		self.clear_peer_download(peer_id);
@@ -766,6 +1003,7 @@ impl ChainSync {
 		let item_count = r.item_count();
 		trace!(target: "sync", "{} -> BlockBodies ({} entries), set = {:?}", peer_id, item_count, block_set);
 		if item_count == 0 {
+			io.report_peer(peer_id, PeerReport::Useless);
 			self.deactivate_peer(io, peer_id);
 		}
 		else if self.state == SyncState::Waiting {
@@ -796,9 +1034,10 @@ impl ChainSync {
 					return Ok(());
 				},
 				Err(DownloaderImportError::Useless) => {
+					io.report_peer(peer_id, PeerReport::Useless);
 					self.deactivate_peer(io, peer_id);
 				},
-				Ok(()) => (),
+				Ok(()) => io.report_peer(peer_id, PeerReport::Success),
 			}
 
 			self.collect_blocks(io, block_set);
@@ -820,6 +1059,7 @@ impl ChainSync {
 		let item_count = r.item_count();
 		trace!(target: "sync", "{} -> BlockReceipts ({} entries)", peer_id, item_count);
 		if item_count == 0 {
+			io.report_peer(peer_id, PeerReport::Useless);
 			self.deactivate_peer(io, peer_id);
 		}
 		else if self.state == SyncState::Waiting {
@@ -850,9 +1090,10 @@ impl ChainSync {
 					return Ok(());
 				},
 				Err(DownloaderImportError::Useless) => {
+					io.report_peer(peer_id, PeerReport::Useless);
 					self.deactivate_peer(io, peer_id);
 				},
-				Ok(()) => (),
+				Ok(()) => io.report_peer(peer_id, PeerReport::Success),
 			}
 
 			self.collect_blocks(io, block_set);
@@ -862,6 +1103,50 @@ impl ChainSync {
 		Ok(())
 	}
 
+	/// Called by peer once it has state trie nodes or code
+	fn on_peer_node_data(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
+		let asked = self.peers.get_mut(&peer_id).map_or_else(Vec::new, |p| mem::replace(&mut p.asking_nodes, Vec::new()));
+		if !self.reset_peer_asking(peer_id, PeerAsking::NodeData) || self.state != SyncState::FastSync {
+			trace!(target: "sync", "{}: Ignored unexpected node data", peer_id);
+			self.state_sync.clear_node_download(&asked);
+			self.continue_sync(io);
+			return Ok(());
+		}
+		let item_count = r.item_count();
+		trace!(target: "sync", "{} -> NodeData ({} entries)", peer_id, item_count);
+		let mut imported = 0;
+		let mut invalid = false;
+		for item in r.iter() {
+			match self.state_sync.import_node(io.chain(), item.as_val()?) {
+				Ok(()) => imported += 1,
+				Err(StateImportError::Useless) => (),
+				Err(StateImportError::Invalid) => invalid = true,
+			}
+		}
+		// request anything not delivered again
+		self.state_sync.clear_node_download(&asked);
+		let (nodes, accounts) = self.state_sync.drain();
+		if !nodes.is_empty() {
+			io.chain().import_state_nodes(nodes, accounts);
+		}
+
+		if invalid {
+			debug!(target: "sync", "Invalid state trie at fast sync pivot, choosing a new pivot");
+			self.pivot = None;
+			self.state_sync.clear();
+		} else if item_count == 0 {
+			// the state may be pruned already. a new pivot is chosen once enough peers lack it.
+			trace!(target: "sync", "{}: No state at fast sync pivot", peer_id);
+			self.pivot_unavailable.insert(peer_id);
+		} else if imported > 0 {
+			io.report_peer(peer_id, PeerReport::Success);
+		}
+		self.maybe_complete_fast_sync(io);
+		self.sync_peer(io, peer_id, false);
+		self.continue_sync(io);
+		Ok(())
+	}
+
 	/// Called by peer once it has new block bodies
 	#[cfg_attr(feature="dev", allow(cyclomatic_complexity))]
 	fn on_peer_new_block(&mut self, io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
@@ -889,6 +1174,10 @@ impl ChainSync {
 				peer.latest_hash = header.hash();
 			}
 		}
+		if self.state == SyncState::FastSync {
+			trace!(target: "sync", "NewBlock ignored during fast sync");
+			return Ok(());
+		}
 		let last_imported_number = self.new_blocks.last_imported_block_number();
 		if last_imported_number > header.number() && last_imported_number - header.number() > MAX_NEW_BLOCK_AGE {
 			trace!(target: "sync", "Ignored ancient new block {:?}", h);
@@ -1028,9 +1317,18 @@ impl ChainSync {
 			}
 			Ok(manifest) => manifest,
 		};
-		self.snapshot.reset_to(&manifest, &manifest_rlp.as_raw().sha3());
+		let manifest_hash = manifest_rlp.as_raw().sha3();
+		self.snapshot.reset_to(&manifest, &manifest_hash);
+		if let Some(completed) = io.snapshot_service().completed_chunks(manifest_hash) {
+			trace!(target: "sync", "{}: Resuming snapshot restoration with {} chunks done", peer_id, completed.len());
+			self.snapshot.note_completed(&completed);
+		}
 		io.snapshot_service().begin_restore(manifest);
-		self.state = SyncState::SnapshotData;
+		self.state = match self.snapshot.is_complete() {
+			// only finalizing the restoration is left.
+			true => SyncState::SnapshotWaiting,
+			false => SyncState::SnapshotData,
+		};
 
 		// give a task to the same peer first.
 		self.sync_peer(io, peer_id, false);
@@ -1070,14 +1368,17 @@ impl ChainSync {
 		match self.snapshot.validate_chunk(&snapshot_data) {
 			Ok(ChunkType::Block(hash)) => {
 				trace!(target: "sync", "{}: Processing block chunk", peer_id);
+				io.report_peer(peer_id, PeerReport::Success);
 				io.snapshot_service().restore_block_chunk(hash, snapshot_data);
 			}
 			Ok(ChunkType::State(hash)) => {
 				trace!(target: "sync", "{}: Processing state chunk", peer_id);
+				io.report_peer(peer_id, PeerReport::Success);
 				io.snapshot_service().restore_state_chunk(hash, snapshot_data);
 			}
 			Err(()) => {
 				trace!(target: "sync", "{}: Got bad snapshot chunk", peer_id);
+				io.report_peer(peer_id, PeerReport::Useless);
 				io.disconnect_peer(peer_id);
 				self.continue_sync(io);
 				return Ok(());
@@ -1132,7 +1433,7 @@ impl ChainSync {
 				self.sync_peer(io, p, false);
 			}
 		}
-		if (self.state != SyncState::WaitingPeers && self.state != SyncState::SnapshotWaiting && self.state != SyncState::Waiting && self.state != SyncState::Idle)
+		if (self.state != SyncState::WaitingPeers && self.state != SyncState::SnapshotWaiting && self.state != SyncState::Waiting && self.state != SyncState::Idle && self.state != SyncState::FastSync)
 			&& !self.peers.values().any(|p| p.asking != PeerAsking::Nothing && p.block_set != Some(BlockSet::OldBlocks) && p.can_sync()) {
 
 			self.complete_sync(io);
@@ -1225,6 +1526,29 @@ impl ChainSync {
 						self.request_snapshot_data(io, peer_id);
 					}
 				},
+				SyncState::FastSync => {
+					let choosing = self.pivot.is_none() || !self.state_sync.is_complete();
+					if choosing && higher_difficulty && self.request_pivot(io, peer_id, &peer_latest) {
+						return;
+					}
+					if self.pivot.is_none() {
+						return;
+					}
+					// share peers between block and state downloads, skipping peers without the state
+					let has_state = !self.pivot_unavailable.contains(&peer_id);
+					let node_peers = self.peers.values().filter(|p| p.asking == PeerAsking::NodeData).count();
+					let block_peers = self.peers.values().filter(|p| p.asking != PeerAsking::Nothing && p.block_set == Some(BlockSet::OldBlocks)).count();
+					if has_state && node_peers <= block_peers && self.request_node_data(io, peer_id) {
+						return;
+					}
+					if let Some(request) = self.old_blocks.as_mut().and_then(|d| d.request_blocks(io, num_active_peers)) {
+						self.request_blocks(io, peer_id, request, BlockSet::OldBlocks);
+						return;
+					}
+					if has_state {
+						self.request_node_data(io, peer_id);
+					}
+				},
 				SyncState::SnapshotManifest | //already downloading from other peer
 					SyncState::Waiting | SyncState::SnapshotWaiting => ()
 			}
@@ -1289,6 +1613,9 @@ impl ChainSync {
 						self.snapshot.clear_chunk_download(&hash);
 					}
 				},
+				PeerAsking::NodeData => {
+					self.state_sync.clear_node_download(&peer.asking_nodes);
+				},
 				_ => (),
 			}
 		}
@@ -1308,7 +1635,12 @@ impl ChainSync {
 					self.restart(io);
 				} else if self.old_blocks.as_ref().map_or(false, |downloader| { downloader.is_complete() }) {
 					trace!(target: "sync", "Background block download is complete");
+					let target = self.old_blocks.as_mut().and_then(|downloader| downloader.take_target_block());
 					self.old_blocks = None;
+					if let (Some(_), Some((block, receipts))) = (self.pivot.as_ref(), target) {
+						self.pivot_block = Some((block, receipts.unwrap_or_else(|| ::rlp::EMPTY_LIST_RLP.to_vec())));
+						self.maybe_complete_fast_sync(io);
+					}
 				}
 			}
 		}
@@ -1341,6 +1673,75 @@ impl ChainSync {
 		self.send_request(sync, peer_id, PeerAsking::ForkHeader, GET_BLOCK_HEADERS_PACKET, rlp.out());
 	}
 
+	/// Request what a peer can tell about the fast sync pivot: its best block when the last report
+	/// is outdated, or the pivot candidate when a new pivot is needed. Returns false if there is nothing to request.
+	fn request_pivot(&mut self, sync: &mut SyncIo, peer_id: PeerId, latest: &H256) -> bool {
+		let now = time::precise_time_ns();
+		let head_expired = self.peers.get(&peer_id).map_or(false, |p| p.pivot_head.map_or(true, |(_, t)|
+			now.saturating_sub(t) > FAST_SYNC_HEAD_EXPIRY_SEC * 1_000_000_000));
+		if head_expired {
+			self.request_pivot_headers(sync, peer_id, latest);
+			return true;
+		}
+		if !self.pivot_expired() {
+			return false;
+		}
+		let number = match self.network_best_block() {
+			Some(best) if best > FAST_SYNC_PIVOT_DISTANCE => best - FAST_SYNC_PIVOT_DISTANCE,
+			_ => return false,
+		};
+		if self.pivot_votes.values().any(|&(ref header, ref peers)| header.number() == number && peers.contains(&peer_id)) {
+			return false;
+		}
+		self.request_pivot_header(sync, peer_id, number);
+		true
+	}
+
+	/// Request the best block header and the fast sync pivot candidate below it.
+	fn request_pivot_headers(&mut self, sync: &mut SyncIo, peer_id: PeerId, latest: &H256) {
+		trace!(target: "sync", "{} <- GetPivotHeaders: from {}", peer_id, latest);
+		let mut rlp = RlpStream::new_list(4);
+		rlp.append(latest);
+		rlp.append(&2u32);
+		rlp.append(&(FAST_SYNC_PIVOT_DISTANCE - 1));
+		rlp.append(&1u32);
+		self.send_request(sync, peer_id, PeerAsking::PivotHeaders, GET_BLOCK_HEADERS_PACKET, rlp.out());
+		if let Some(ref mut peer) = self.peers.get_mut(&peer_id) {
+			peer.asking_pivot = None;
+		}
+	}
+
+	/// Request the fast sync pivot candidate at the given number.
+	fn request_pivot_header(&mut self, sync: &mut SyncIo, peer_id: PeerId, n: BlockNumber) {
+		trace!(target: "sync", "{} <- GetPivotHeaders: at {}", peer_id, n);
+		let mut rlp = RlpStream::new_list(4);
+		rlp.append(&n);
+		rlp.append(&1u32);
+		rlp.append(&0u32);
+		rlp.append(&0u32);
+		self.send_request(sync, peer_id, PeerAsking::PivotHeaders, GET_BLOCK_HEADERS_PACKET, rlp.out());
+		if let Some(ref mut peer) = self.peers.get_mut(&peer_id) {
+			peer.asking_pivot = Some(n);
+		}
+	}
+
+	/// Request state trie nodes from a peer. Returns false if there is nothing to request.
+	fn request_node_data(&mut self, sync: &mut SyncIo, peer_id: PeerId) -> bool {
+		let hashes = self.state_sync.needed_nodes(MAX_NODE_DATA_TO_REQUEST);
+		if hashes.is_empty() {
+			return false;
+		}
+		trace!(target: "sync", "{} <- GetNodeData: {} entries starting from {:?}", peer_id, hashes.len(), hashes.first());
+		let mut rlp = RlpStream::new_list(hashes.len());
+		for h in &hashes {
+			rlp.append(h);
+		}
+		self.send_request(sync, peer_id, PeerAsking::NodeData, GET_NODE_DATA_PACKET, rlp.out());
+		let peer = self.peers.get_mut(&peer_id).expect("peer_id may originate either from on_packet, where it is already validated or from enumerating self.peers. qed");
+		peer.asking_nodes = hashes;
+		true
+	}
+
 	/// Request snapshot manifest from a peer.
 	fn request_snapshot_manifest(&mut self, sync: &mut SyncIo, peer_id: PeerId) {
 		trace!(target: "sync", "{} <- GetSnapshotManifest", peer_id);
@@ -1554,6 +1955,13 @@ impl ChainSync {
 		Ok(Some((BLOCK_HEADERS_PACKET, rlp)))
 	}
 
+	/// Returns true if the body and receipts of a known block were removed by history expiry.
+	/// Responses are cut short at such blocks, so that the returned items remain a prefix
+	/// of the request and the peer can fetch the rest elsewhere.
+	fn is_history_expired(io: &SyncIo, earliest_body: BlockNumber, hash: &H256) -> bool {
+		io.chain().block_number(BlockId::Hash(hash.clone())).map_or(false, |n| n > 0 && n < earliest_body)
+	}
+
 	/// Respond to GetBlockBodies request
 	fn return_block_bodies(io: &SyncIo, r: &UntrustedRlp, peer_id: PeerId) -> RlpResponseResult {
 		let mut count = r.item_count();
@@ -1562,12 +1970,21 @@ impl ChainSync {
 			return Ok(None);
 		}
 		count = min(count, MAX_BODIES_TO_SEND);
+		let earliest_body = io.chain().pruning_info().earliest_body;
 		let mut added = 0usize;
 		let mut data = Bytes::new();
 		for i in 0..count {
-			if let Some(body) = io.chain().block_body(BlockId::Hash(r.val_at::<H256>(i)?)) {
-				data.append(&mut body.into_inner());
-				added += 1;
+			let hash = r.val_at::<H256>(i)?;
+			match io.chain().block_body(BlockId::Hash(hash.clone())) {
+				Some(body) => {
+					data.append(&mut body.into_inner());
+					added += 1;
+				},
+				None if ChainSync::is_history_expired(io, earliest_body, &hash) => {
+					trace!(target: "sync", "{} -> GetBlockBodies: history of {} expired", peer_id, hash);
+					break;
+				},
+				None => {},
 			}
 		}
 		let mut rlp = RlpStream::new_list(added);
@@ -1609,15 +2026,24 @@ impl ChainSync {
 			return Ok(None);
 		}
 		count = min(count, MAX_RECEIPTS_HEADERS_TO_SEND);
+		let earliest_body = io.chain().pruning_info().earliest_body;
 		let mut added_headers = 0usize;
 		let mut added_receipts = 0usize;
 		let mut data = Bytes::new();
 		for i in 0..count {
-			if let Some(mut receipts_bytes) = io.chain().block_receipts(&rlp.val_at::<H256>(i)?) {
-				data.append(&mut receipts_bytes);
-				added_receipts += receipts_bytes.len();
-				added_headers += 1;
-				if added_receipts > MAX_RECEIPTS_TO_SEND { break; }
+			let hash = rlp.val_at::<H256>(i)?;
+			match io.chain().block_receipts(&hash) {
+				Some(mut receipts_bytes) => {
+					data.append(&mut receipts_bytes);
+					added_receipts += receipts_bytes.len();
+					added_headers += 1;
+					if added_receipts > MAX_RECEIPTS_TO_SEND { break; }
+				},
+				None if ChainSync::is_history_expired(io, earliest_body, &hash) => {
+					trace!(target: "sync", "{} -> GetReceipts: history of {} expired", peer_id, hash);
+					break;
+				},
+				None => {},
 			}
 		}
 		let mut rlp_result = RlpStream::new_list(added_headers);
@@ -1732,6 +2158,7 @@ impl ChainSync {
 			BLOCK_HEADERS_PACKET => self.on_peer_block_headers(io, peer, &rlp),
 			BLOCK_BODIES_PACKET => self.on_peer_block_bodies(io, peer, &rlp),
 			RECEIPTS_PACKET => self.on_peer_block_receipts(io, peer, &rlp),
+			NODE_DATA_PACKET => self.on_peer_node_data(io, peer, &rlp),
 			NEW_BLOCK_PACKET => self.on_peer_new_block(io, peer, &rlp),
 			NEW_BLOCK_HASHES_PACKET => self.on_peer_new_hashes(io, peer, &rlp),
 			SNAPSHOT_MANIFEST_PACKET => self.on_snapshot_manifest(io, peer, &rlp),
@@ -1760,9 +2187,12 @@ impl ChainSync {
 				PeerAsking::ForkHeader => elapsed > FORK_HEADER_TIMEOUT_SEC,
 				PeerAsking::SnapshotManifest => elapsed > SNAPSHOT_MANIFEST_TIMEOUT_SEC,
 				PeerAsking::SnapshotData => elapsed > SNAPSHOT_DATA_TIMEOUT_SEC,
+				PeerAsking::PivotHeaders => elapsed > HEADERS_TIMEOUT_SEC,
+				PeerAsking::NodeData => elapsed > NODE_DATA_TIMEOUT_SEC,
 			};
 			if timeout {
 				trace!(target:"sync", "Timeout {}", peer_id);
+				io.report_peer(*peer_id, PeerReport::Timeout);
 				io.disconnect_peer(*peer_id);
 				aborting.push(*peer_id);
 			}
@@ -2194,6 +2624,9 @@ mod tests {
 			num_snapshot_chunks: 0,
 			snapshot_chunks_done: 0,
 			last_imported_old_block_number: None,
+			fast_sync_pivot: None,
+			state_nodes_done: 0,
+			state_nodes_pending: 0,
 		}
 	}
 
@@ -2251,6 +2684,37 @@ mod tests {
 		assert_eq!(1, io.packets.len());
 	}
 
+	#[test]
+	fn return_bodies_and_receipts_stop_at_expired_history() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(10, EachBlockWith::Nothing);
+		*client.earliest_body.write() = 5;
+		let hashes: Vec<_> = (0..10).map(|i| client.block_hash(BlockId::Number(i)).unwrap()).collect();
+
+		let queue = RwLock::new(VecDeque::new());
+		let ss = TestSnapshotService::new();
+		let io = TestIo::new(&mut client, &ss, &queue, None);
+
+		let request = |numbers: &[usize]| {
+			let mut rlp = RlpStream::new_list(numbers.len());
+			for n in numbers {
+				rlp.append(&hashes[*n]);
+			}
+			rlp.out()
+		};
+		let count = |result: ::chain::RlpResponseResult| Rlp::new(&result.unwrap().unwrap().1.out()).item_count();
+
+		// genesis body is always kept; unknown hashes are skipped.
+		let mut unknown = RlpStream::new_list(3);
+		unknown.append(&hashes[0]).append(&H256::new()).append(&hashes[6]);
+		assert_eq!(count(ChainSync::return_block_bodies(&io, &UntrustedRlp::new(&unknown.out()), 0)), 2);
+
+		assert_eq!(count(ChainSync::return_block_bodies(&io, &UntrustedRlp::new(&request(&[5, 6, 7])), 0)), 3);
+		assert_eq!(count(ChainSync::return_block_bodies(&io, &UntrustedRlp::new(&request(&[6, 4, 7])), 0)), 1);
+		assert_eq!(count(ChainSync::return_block_bodies(&io, &UntrustedRlp::new(&request(&[3, 4, 5])), 0)), 0);
+		assert_eq!(count(ChainSync::return_receipts(&io, &UntrustedRlp::new(&request(&[4, 5])), 0)), 0);
+	}
+
 	#[test]
 	fn return_block_headers() {
 		use ethcore::views::HeaderView;
@@ -2349,27 +2813,89 @@ mod tests {
 		assert_eq!(1, io.packets.len());
 	}
 
+	fn dummy_peer(peer_latest_hash: H256) -> PeerInfo {
+		PeerInfo {
+			protocol_version: 0,
+			genesis: H256::zero(),
+			network_id: 0,
+			latest_hash: peer_latest_hash,
+			difficulty: None,
+			asking: PeerAsking::Nothing,
+			asking_blocks: Vec::new(),
+			asking_hash: None,
+			ask_time: 0,
+			last_sent_transactions: HashSet::new(),
+			expired: false,
+			confirmation: super::ForkConfirmation::Confirmed,
+			snapshot_number: None,
+			snapshot_hash: None,
+			asking_snapshot_data: None,
+			asking_nodes: Vec::new(),
+			block_set: None,
+			pivot_head: None,
+			asking_pivot: None,
+		}
+	}
+
+	#[test]
+	fn chooses_pivot_agreed_by_peers() {
+		let mut remote = TestBlockChainClient::new();
+		remote.add_blocks(2000, EachBlockWith::Nothing);
+		let headers = {
+			let mut rlp = RlpStream::new_list(2);
+			rlp.append_raw(&remote.block_header(BlockId::Number(2000)).unwrap().into_inner(), 1);
+			rlp.append_raw(&remote.block_header(BlockId::Number(1936)).unwrap().into_inner(), 1);
+			rlp.out()
+		};
+
+		let mut client = TestBlockChainClient::new();
+		let queue = RwLock::new(VecDeque::new());
+		let mut sync = dummy_sync_with_peer(remote.block_hash_delta_minus(1), &client);
+		sync.peers.insert(1, dummy_peer(remote.block_hash_delta_minus(1)));
+		sync.peers.insert(2, dummy_peer(remote.block_hash_delta_minus(1)));
+		sync.state = SyncState::FastSync;
+		let ss = TestSnapshotService::new();
+		let mut io = TestIo::new(&mut client, &ss, &queue, None);
+
+		for peer_id in 0..3 {
+			assert!(sync.pivot.is_none());
+			sync.peers.get_mut(&peer_id).unwrap().asking = PeerAsking::PivotHeaders;
+			sync.on_peer_pivot_headers(&mut io, peer_id, &UntrustedRlp::new(&headers)).unwrap();
+		}
+		assert_eq!(sync.pivot.as_ref().map(|p| p.number()), Some(1936));
+		assert_eq!(sync.highest_block, Some(2000));
+		assert!(io.to_disconnect.is_empty());
+	}
+
+	#[test]
+	fn chooses_new_pivot_when_peers_have_no_state() {
+		let mut client = TestBlockChainClient::new();
+		*client.state_root.write() = H256::from(1);
+		client.add_blocks(100, EachBlockWith::Nothing);
+		let queue = RwLock::new(VecDeque::new());
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(1), &client);
+		sync.peers.insert(1, dummy_peer(client.block_hash_delta_minus(1)));
+		sync.peers.insert(2, dummy_peer(client.block_hash_delta_minus(1)));
+		let pivot = client.block_header(BlockId::Number(50)).unwrap();
+		sync.set_pivot(&client, pivot);
+		let ss = TestSnapshotService::new();
+		let mut io = TestIo::new(&mut client, &ss, &queue, None);
+
+		let empty = RlpStream::new_list(0).out();
+		for peer_id in 0..3 {
+			assert!(!sync.pivot_expired());
+			sync.active_peers.insert(peer_id);
+			sync.peers.get_mut(&peer_id).unwrap().asking = PeerAsking::NodeData;
+			sync.on_peer_node_data(&mut io, peer_id, &UntrustedRlp::new(&empty)).unwrap();
+		}
+		assert!(sync.pivot_expired());
+		assert!(io.reports.is_empty());
+		assert!((0..3).all(|p| sync.active_peers.contains(&p)));
+	}
+
 	fn dummy_sync_with_peer(peer_latest_hash: H256, client: &BlockChainClient) -> ChainSync {
 		let mut sync = ChainSync::new(SyncConfig::default(), client);
-		sync.peers.insert(0,
-			PeerInfo {
-				protocol_version: 0,
-				genesis: H256::zero(),
-				network_id: 0,
-				latest_hash: peer_latest_hash,
-				difficulty: None,
-				asking: PeerAsking::Nothing,
-				asking_blocks: Vec::new(),
-				asking_hash: None,
-				ask_time: 0,
-				last_sent_transactions: HashSet::new(),
-				expired: false,
-				confirmation: super::ForkConfirmation::Confirmed,
-				snapshot_number: None,
-				snapshot_hash: None,
-				asking_snapshot_data: None,
-				block_set: None,
-			});
+		sync.peers.insert(0, dummy_peer(peer_latest_hash));
 		sync
 	}
 
@@ -2423,6 +2949,30 @@ mod tests {
 		assert_eq!(0x01, io.packets[0].packet_id);
 	}
 
+	#[test]
+	fn reports_useless_and_timed_out_peers() {
+		let mut client = TestBlockChainClient::new();
+		client.add_blocks(10, EachBlockWith::Uncle);
+		let queue = RwLock::new(VecDeque::new());
+		let mut sync = dummy_sync_with_peer(client.block_hash_delta_minus(5), &client);
+		let ss = TestSnapshotService::new();
+		let mut io = TestIo::new(&mut client, &ss, &queue, None);
+
+		sync.peers.get_mut(&0).unwrap().asking = PeerAsking::BlockBodies;
+		let empty = RlpStream::new_list(0).out();
+		sync.on_peer_block_bodies(&mut io, 0, &UntrustedRlp::new(&empty)).unwrap();
+		assert_eq!(io.reports, vec![(0, PeerReport::Useless)]);
+
+		{
+			let peer = sync.peers.get_mut(&0).unwrap();
+			peer.asking = PeerAsking::BlockHeaders;
+			peer.ask_time = 0;
+		}
+		sync.maintain_peers(&mut io);
+		assert_eq!(io.reports, vec![(0, PeerReport::Useless), (0, PeerReport::Timeout)]);
+		assert!(io.to_disconnect.contains(&0));
+	}
+
 	#[test]
 	fn sends_latest_block_to_lagging_peer() {
 		let mut client = TestBlockChainClient::new();
@@ -2489,7 +3039,10 @@ mod tests {
 				snapshot_number: None,
 				snapshot_hash: None,
 				asking_snapshot_data: None,
+				asking_nodes: Vec::new(),
 				block_set: None,
+				pivot_head: None,
+				asking_pivot: None,
 			});
 		let ss = TestSnapshotService::new();
 		let mut io = TestIo::new(&mut client, &ss, &queue, None);
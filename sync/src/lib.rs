@@ -56,6 +56,7 @@ mod blocks;
 mod block_sync;
 mod sync_io;
 mod snapshot;
+mod state_sync;
 mod transactions_stats;
 
 pub mod light_sync;
@@ -74,11 +75,12 @@ mod api;
 
 pub use api::{
 	EthSync, Params, SyncProvider, ManageNetwork, SyncConfig,
-	ServiceConfiguration, NetworkConfiguration, PeerInfo, AllowIP, TransactionStats,
-	LightSync, LightSyncParams, LesProtocolInfo, EthProtocolInfo,
+	ServiceConfiguration, NetworkConfiguration, PeerInfo, AllowIP, NatType, TransactionStats,
+	LightSync, LightSyncParams, LesProtocolInfo, EthProtocolInfo, PeerReputation, PeerTraffic, PeerGroup, PeerGroupInfo,
+	CompressionStats,
 };
 pub use chain::{SyncStatus, SyncState};
-pub use network::{is_valid_node_url, NonReservedPeerMode, NetworkError};
+pub use network::{is_valid_node_url, NonReservedPeerMode, NetworkError, MAX_BAN_DURATION_SEC};
 
 /// IPC interfaces
 #[cfg(feature="ipc")]
@@ -28,7 +28,7 @@ use io::IoChannel;
 use light::client::Client as LightClient;
 use light::net::{LightProtocol, IoContext, Capabilities, Params as LightParams};
 use light::net::buffer_flow::FlowParams;
-use network::{NodeId, PeerId};
+use network::{NodeId, PeerId, PeerReport};
 use util::RwLock;
 
 const NETWORK_ID: u64 = 0xcafebabe;
@@ -59,6 +59,7 @@ impl<'a> IoContext for TestIoContext<'a> {
 	}
 
 	fn disable_peer(&self, peer: PeerId) { self.disconnect_peer(peer) }
+	fn report_peer(&self, _peer: PeerId, _report: PeerReport) { }
 	fn protocol_version(&self, _peer: PeerId) -> Option<u8> { Some(::light::net::MAX_PROTOCOL_VERSION) }
 
 	fn persistent_peer_id(&self, _peer: PeerId) -> Option<NodeId> { unimplemented!() }
@@ -62,6 +62,16 @@ impl Snapshot {
 		self.snapshot_hash = Some(hash.clone());
 	}
 
+	/// Mark chunks restored before, e.g. by a restoration interrupted by a restart,
+	/// as completed so that they are not downloaded again.
+	pub fn note_completed(&mut self, hashes: &[H256]) {
+		for hash in hashes {
+			if self.pending_state_chunks.contains(hash) || self.pending_block_chunks.contains(hash) {
+				self.completed_chunks.insert(hash.clone());
+			}
+		}
+	}
+
 	/// Validate chunk and mark it as downloaded
 	pub fn validate_chunk(&mut self, chunk: &[u8]) -> Result<ChunkType, ()> {
 		let hash = chunk.sha3();
@@ -199,5 +209,22 @@ mod test {
 		assert_eq!(snapshot.done_chunks(), snapshot.total_chunks());
 		assert_eq!(snapshot.snapshot_hash(), Some(manifest.into_rlp().sha3()));
 	}
+
+	#[test]
+	fn skips_completed_chunks() {
+		let mut snapshot = Snapshot::new();
+		let (manifest, mhash, state_chunks, _) = test_manifest();
+		snapshot.reset_to(&manifest, &mhash);
+
+		let mut completed = manifest.state_hashes.clone();
+		completed.push(H256::random());
+		snapshot.note_completed(&completed);
+		assert_eq!(snapshot.done_chunks(), 20);
+
+		let requested: Vec<H256> = (0..20).map(|_| snapshot.needed_chunk().unwrap()).collect();
+		assert!(snapshot.needed_chunk().is_none());
+		assert_eq!(&requested[..], &manifest.block_hashes[..]);
+		assert!(snapshot.validate_chunk(&state_chunks[0]).is_err());
+	}
 }
 
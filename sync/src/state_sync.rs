@@ -0,0 +1,507 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! State trie download for fast sync.
+//!
+//! Nodes are fetched with `GetNodeData` starting from the pivot state root. A node
+//! is handed to the client only once all of its children are in the database, so
+//! a node found in the database always has a complete subtree. This lets the
+//! download skip known subtrees, survive restarts and move to a new pivot without
+//! any extra bookkeeping.
+//!
+//! The trie is walked depth-first, so subtrees are completed and leave memory as soon
+//! as possible and the number of outstanding nodes stays proportional to the trie depth.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use util::{H256, Bytes, Hashable, NibbleSlice, SHA3_NULL_RLP, SHA3_EMPTY};
+use rlp::{UntrustedRlp, View, DecoderError};
+use ethcore::client::{BlockChainClient, StateNode};
+
+/// Number of missing and pending nodes past which new nodes are only requested
+/// once the current downloads are done.
+const MAX_OUTSTANDING_NODES: usize = 65536;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum StateImportError {
+	/// Node data does not decode.
+	Invalid,
+	/// Node data was not requested.
+	Useless,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum NodeKind {
+	/// Node of the account trie or of a storage trie.
+	Trie,
+	/// Contract code.
+	Code,
+}
+
+/// Node location: the owning account address hash for storage nodes and code.
+type NodeKey = (Option<H256>, H256);
+
+/// Reference to a node that is yet to be downloaded.
+struct Request {
+	owner: Option<H256>,
+	kind: NodeKind,
+	/// Nibble path from the trie root. Only tracked for the account trie.
+	path: Vec<u8>,
+	parent: Option<NodeKey>,
+}
+
+/// Downloaded node waiting for its children.
+struct Pending {
+	data: Bytes,
+	children_left: usize,
+	parents: Vec<Option<NodeKey>>,
+}
+
+/// State trie downloader.
+pub struct StateSync {
+	/// State root being downloaded.
+	root: Option<H256>,
+	/// Nodes to download, by hash.
+	missing: HashMap<H256, Vec<Request>>,
+	/// Hashes not requested yet, deepest first.
+	queue: VecDeque<H256>,
+	/// Hashes being downloaded.
+	downloading: HashSet<H256>,
+	/// Downloaded nodes with incomplete subtrees.
+	pending: HashMap<NodeKey, Pending>,
+	/// Complete nodes to be written to the database.
+	ready: Vec<StateNode>,
+	/// Address hashes of the accounts found so far.
+	accounts: Vec<H256>,
+	/// Number of nodes written.
+	done: usize,
+	complete: bool,
+}
+
+impl StateSync {
+	/// Create a new instance.
+	pub fn new() -> StateSync {
+		StateSync {
+			root: None,
+			missing: HashMap::new(),
+			queue: VecDeque::new(),
+			downloading: HashSet::new(),
+			pending: HashMap::new(),
+			ready: Vec::new(),
+			accounts: Vec::new(),
+			done: 0,
+			complete: false,
+		}
+	}
+
+	/// Clear everything.
+	pub fn clear(&mut self) {
+		self.root = None;
+		self.missing.clear();
+		self.queue.clear();
+		self.downloading.clear();
+		self.pending.clear();
+		self.ready.clear();
+		self.accounts.clear();
+		self.complete = false;
+	}
+
+	/// Start downloading the state trie with the given root. Nodes downloaded for a
+	/// previous root are kept only if their subtrees were complete.
+	pub fn reset_to(&mut self, chain: &BlockChainClient, root: &H256) {
+		self.clear();
+		self.root = Some(root.clone());
+		if chain.state_node_known(None, root) {
+			self.complete = true;
+			return;
+		}
+		self.missing.insert(root.clone(), vec![Request { owner: None, kind: NodeKind::Trie, path: Vec::new(), parent: None }]);
+		self.queue.push_back(root.clone());
+	}
+
+	/// State root being downloaded.
+	pub fn root(&self) -> Option<H256> {
+		self.root
+	}
+
+	/// Find up to `max` nodes to download.
+	pub fn needed_nodes(&mut self, max: usize) -> Vec<H256> {
+		if self.pending_nodes() >= MAX_OUTSTANDING_NODES && !self.downloading.is_empty() {
+			trace!(target: "sync", "Too many outstanding state nodes, waiting for downloads");
+			return Vec::new();
+		}
+		let mut hashes = Vec::new();
+		while hashes.len() < max {
+			match self.queue.pop_front() {
+				Some(hash) => if self.missing.contains_key(&hash) && self.downloading.insert(hash.clone()) {
+					hashes.push(hash);
+				},
+				None => break,
+			}
+		}
+		hashes
+	}
+
+	/// Unmark nodes as being downloaded.
+	pub fn clear_node_download(&mut self, hashes: &[H256]) {
+		for hash in hashes.iter().rev() {
+			if self.downloading.remove(hash) {
+				self.queue.push_front(hash.clone());
+			}
+		}
+	}
+
+	/// Import downloaded node data.
+	pub fn import_node(&mut self, chain: &BlockChainClient, data: Bytes) -> Result<(), StateImportError> {
+		let hash = data.sha3();
+		let requests = match self.missing.remove(&hash) {
+			Some(requests) => requests,
+			None => {
+				trace!(target: "sync", "Ignored unexpected node {}", hash);
+				return Err(StateImportError::Useless);
+			}
+		};
+		self.downloading.remove(&hash);
+
+		let mut result = Ok(());
+		let mut queued = Vec::new();
+		for request in requests {
+			let key = (request.owner, hash.clone());
+			if let Some(pending) = self.pending.get_mut(&key) {
+				pending.parents.push(request.parent);
+				continue;
+			}
+
+			let children = match request.kind {
+				NodeKind::Code => Vec::new(),
+				NodeKind::Trie => {
+					let mut children = Vec::new();
+					let decoded = Self::decode_node(&data, &request, request.path.clone(), &mut children, &mut self.accounts);
+					if let Err(e) = decoded {
+						debug!(target: "sync", "Error decoding state node {}: {:?}", hash, e);
+						result = Err(StateImportError::Invalid);
+						continue;
+					}
+					children
+				}
+			};
+
+			let mut children_left = 0;
+			for (owner, child, kind, path) in children {
+				if chain.state_node_known(owner, &child) {
+					continue;
+				}
+				children_left += 1;
+				let request = Request { owner: owner, kind: kind, path: path, parent: Some(key.clone()) };
+				let known = self.missing.contains_key(&child);
+				self.missing.entry(child.clone()).or_insert_with(Vec::new).push(request);
+				if !known {
+					queued.push(child);
+				}
+			}
+			self.pending.insert(key.clone(), Pending { data: data.clone(), children_left: children_left, parents: vec![request.parent] });
+			if children_left == 0 {
+				self.complete_node(key);
+			}
+		}
+		// children go ahead of the nodes queued so far, in order.
+		for child in queued.into_iter().rev() {
+			self.queue.push_front(child);
+		}
+		result
+	}
+
+	/// Move a node with a complete subtree to the ready list, followed by any
+	/// parents completed with it.
+	fn complete_node(&mut self, key: NodeKey) {
+		let mut completed = vec![key];
+		while let Some(key) = completed.pop() {
+			let node = self.pending.remove(&key).expect("only pending nodes are completed; qed");
+			for parent in node.parents {
+				match parent {
+					Some(parent) => {
+						let pending = self.pending.get_mut(&parent).expect("parents are pending until all children are complete; qed");
+						pending.children_left -= 1;
+						if pending.children_left == 0 {
+							completed.push(parent);
+						}
+					},
+					None => self.complete = true,
+				}
+			}
+			self.ready.push(StateNode { owner: key.0, data: node.data });
+			self.done += 1;
+		}
+	}
+
+	/// Collect child node references from trie node RLP, including ones in inline nodes.
+	fn decode_node(data: &[u8], request: &Request, path: Vec<u8>, children: &mut Vec<(Option<H256>, H256, NodeKind, Vec<u8>)>, accounts: &mut Vec<H256>) -> Result<(), DecoderError> {
+		let rlp = UntrustedRlp::new(data);
+		if !rlp.is_list() {
+			// empty trie
+			return Ok(());
+		}
+		match rlp.item_count() {
+			2 => {
+				let encoded = rlp.at(0)?.data()?;
+				if encoded.is_empty() {
+					return Err(DecoderError::RlpExpectedToBeData);
+				}
+				let (partial, is_leaf) = NibbleSlice::from_encoded(encoded);
+				let mut path = path;
+				if request.owner.is_none() {
+					path.extend(partial.iter());
+				}
+				if is_leaf {
+					if request.owner.is_none() {
+						Self::decode_account(&path, rlp.at(1)?.data()?, children, accounts)?;
+					}
+					Ok(())
+				} else {
+					Self::decode_child(&rlp.at(1)?, request, path, children, accounts)
+				}
+			},
+			17 => {
+				for i in 0..16 {
+					let mut child_path = Vec::new();
+					if request.owner.is_none() {
+						child_path.extend_from_slice(&path);
+						child_path.push(i as u8);
+					}
+					Self::decode_child(&rlp.at(i)?, request, child_path, children, accounts)?;
+				}
+				Ok(())
+			},
+			_ => Err(DecoderError::RlpIncorrectListLen),
+		}
+	}
+
+	fn decode_child(rlp: &UntrustedRlp, request: &Request, path: Vec<u8>, children: &mut Vec<(Option<H256>, H256, NodeKind, Vec<u8>)>, accounts: &mut Vec<H256>) -> Result<(), DecoderError> {
+		if rlp.is_empty() {
+			Ok(())
+		} else if rlp.is_list() {
+			Self::decode_node(rlp.as_raw(), request, path, children, accounts)
+		} else {
+			children.push((request.owner, rlp.as_val()?, NodeKind::Trie, path));
+			Ok(())
+		}
+	}
+
+	fn decode_account(path: &[u8], value: &[u8], children: &mut Vec<(Option<H256>, H256, NodeKind, Vec<u8>)>, accounts: &mut Vec<H256>) -> Result<(), DecoderError> {
+		if path.len() != 64 {
+			return Err(DecoderError::Custom("Invalid account key length"));
+		}
+		let mut key = [0u8; 32];
+		for (i, nibbles) in path.chunks(2).enumerate() {
+			key[i] = (nibbles[0] << 4) | nibbles[1];
+		}
+		let address_hash = H256(key);
+		let account = UntrustedRlp::new(value);
+		let storage_root: H256 = account.val_at(2)?;
+		let code_hash: H256 = account.val_at(3)?;
+		if storage_root != SHA3_NULL_RLP {
+			children.push((Some(address_hash), storage_root, NodeKind::Trie, Vec::new()));
+		}
+		if code_hash != SHA3_EMPTY {
+			children.push((Some(address_hash), code_hash, NodeKind::Code, Vec::new()));
+		}
+		accounts.push(address_hash);
+		Ok(())
+	}
+
+	/// Take complete nodes and the address hashes of the accounts found since the last call.
+	pub fn drain(&mut self) -> (Vec<StateNode>, Vec<H256>) {
+		(::std::mem::replace(&mut self.ready, Vec::new()), ::std::mem::replace(&mut self.accounts, Vec::new()))
+	}
+
+	/// Number of nodes downloaded and complete.
+	pub fn done_nodes(&self) -> usize {
+		self.done
+	}
+
+	/// Number of nodes known to be missing or waiting for their children.
+	pub fn pending_nodes(&self) -> usize {
+		self.missing.len() + self.pending.len()
+	}
+
+	/// Check if the whole trie is downloaded.
+	pub fn is_complete(&self) -> bool {
+		self.complete
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use util::*;
+	use super::*;
+	use rlp::RlpStream;
+	use ethcore::client::{TestBlockChainClient, BlockChainClient};
+
+	// Returns the state root and the database holding all state nodes and code.
+	fn test_state() -> (H256, MemoryDB) {
+		let mut db = MemoryDB::new();
+		let code = vec![0x60u8, 0x00, 0x56];
+		let code_hash = db.insert(&code);
+
+		let mut storage_root = H256::new();
+		{
+			let mut storage = SecTrieDBMut::new(&mut db, &mut storage_root);
+			for i in 1..50u64 {
+				storage.insert(&H256::from(i), &::rlp::encode(&U256::from(i * 7))).unwrap();
+			}
+		}
+
+		let mut root = H256::new();
+		{
+			let mut trie = SecTrieDBMut::new(&mut db, &mut root);
+			for i in 1..100u64 {
+				let mut account = RlpStream::new_list(4);
+				account.append(&U256::zero());
+				account.append(&U256::from(i));
+				if i % 10 == 0 {
+					account.append(&storage_root);
+					account.append(&code_hash);
+				} else {
+					account.append(&SHA3_NULL_RLP);
+					account.append(&SHA3_EMPTY);
+				}
+				trie.insert(&Address::from(i), &account.out()).unwrap();
+			}
+		}
+		(root, db)
+	}
+
+	fn download(sync: &mut StateSync, chain: &TestBlockChainClient, db: &MemoryDB) {
+		loop {
+			let hashes = sync.needed_nodes(16);
+			if hashes.is_empty() {
+				break;
+			}
+			for hash in hashes {
+				let data = db.get(&hash).expect("only nodes of the trie are requested").to_vec();
+				assert_eq!(sync.import_node(chain, data), Ok(()));
+			}
+			let (nodes, accounts) = sync.drain();
+			chain.import_state_nodes(nodes, accounts);
+		}
+	}
+
+	#[test]
+	fn downloads_state_trie() {
+		let (root, db) = test_state();
+		let chain = TestBlockChainClient::new();
+		let mut sync = StateSync::new();
+		sync.reset_to(&chain, &root);
+		assert!(!sync.is_complete());
+
+		let mut accounts = Vec::new();
+		loop {
+			let hashes = sync.needed_nodes(16);
+			if hashes.is_empty() {
+				break;
+			}
+			for hash in hashes {
+				assert!(sync.import_node(&chain, db.get(&hash).unwrap().to_vec()).is_ok());
+				// parents are only written when their subtree is complete.
+				let (nodes, found) = sync.drain();
+				for node in &nodes {
+					assert!(node.data.sha3() != root || sync.is_complete());
+				}
+				accounts.extend(found);
+				chain.import_state_nodes(nodes, Vec::new());
+			}
+		}
+		assert!(sync.is_complete());
+		assert_eq!(sync.pending_nodes(), 0);
+		assert_eq!(accounts.len(), 99);
+		assert!(chain.state_node_known(None, &root));
+		// the code is shared by all contracts and is present once in the database.
+		assert_eq!(chain.state_nodes.read().len(), db.keys().values().filter(|rc| **rc > 0).count());
+	}
+
+	#[test]
+	fn skips_known_subtrees() {
+		let (root, db) = test_state();
+		let chain = TestBlockChainClient::new();
+		let mut sync = StateSync::new();
+		sync.reset_to(&chain, &root);
+		download(&mut sync, &chain, &db);
+		assert!(sync.is_complete());
+
+		sync.reset_to(&chain, &root);
+		assert!(sync.is_complete());
+		assert!(sync.needed_nodes(16).is_empty());
+	}
+
+	#[test]
+	fn resumes_after_partial_download() {
+		let (root, db) = test_state();
+		let chain = TestBlockChainClient::new();
+		let mut sync = StateSync::new();
+		sync.reset_to(&chain, &root);
+		for _ in 0..3 {
+			for hash in sync.needed_nodes(16) {
+				sync.import_node(&chain, db.get(&hash).unwrap().to_vec()).unwrap();
+			}
+			let (nodes, accounts) = sync.drain();
+			chain.import_state_nodes(nodes, accounts);
+		}
+		assert!(!sync.is_complete());
+
+		// start over, as after a restart.
+		let mut sync = StateSync::new();
+		sync.reset_to(&chain, &root);
+		download(&mut sync, &chain, &db);
+		assert!(sync.is_complete());
+		assert!(chain.state_node_known(None, &root));
+	}
+
+	#[test]
+	fn walks_depth_first() {
+		let (root, db) = test_state();
+		let chain = TestBlockChainClient::new();
+		let mut sync = StateSync::new();
+		sync.reset_to(&chain, &root);
+		assert_eq!(sync.needed_nodes(1), vec![root.clone()]);
+		sync.import_node(&chain, db.get(&root).unwrap().to_vec()).unwrap();
+
+		let siblings = sync.needed_nodes(16);
+		sync.clear_node_download(&siblings);
+		assert_eq!(sync.needed_nodes(1), vec![siblings[0].clone()]);
+		sync.import_node(&chain, db.get(&siblings[0]).unwrap().to_vec()).unwrap();
+
+		// children of the first node are requested before its siblings.
+		let next = sync.needed_nodes(1);
+		assert_eq!(next.len(), 1);
+		assert!(!siblings.contains(&next[0]));
+	}
+
+	#[test]
+	fn rejects_unexpected_nodes() {
+		let (root, db) = test_state();
+		let chain = TestBlockChainClient::new();
+		let mut sync = StateSync::new();
+		sync.reset_to(&chain, &root);
+		assert_eq!(sync.import_node(&chain, vec![0xc0]), Err(StateImportError::Useless));
+
+		let hashes = sync.needed_nodes(16);
+		assert_eq!(hashes, vec![root.clone()]);
+		assert!(sync.needed_nodes(16).is_empty());
+		sync.clear_node_download(&hashes);
+		assert_eq!(sync.needed_nodes(16), vec![root.clone()]);
+		assert!(sync.import_node(&chain, db.get(&root).unwrap().to_vec()).is_ok());
+		assert_eq!(sync.import_node(&chain, db.get(&root).unwrap().to_vec()), Err(StateImportError::Useless));
+	}
+}
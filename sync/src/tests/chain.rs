@@ -15,8 +15,9 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use util::*;
+use rlp::RlpStream;
 use ethcore::client::{TestBlockChainClient, BlockChainClient, BlockId, EachBlockWith};
-use chain::{SyncState};
+use chain::{SyncState, ChainSync};
 use super::helpers::*;
 use SyncConfig;
 
@@ -268,3 +269,55 @@ fn disconnect_on_unrelated_chain() {
 	assert_eq!(net.disconnect_events, vec![(0, 0)]);
 }
 
+
+// Sets up the same account trie on all `chains` and returns its root.
+fn add_state(chains: &[&TestBlockChainClient]) -> H256 {
+	let mut db = MemoryDB::new();
+	let mut root = H256::new();
+	{
+		let mut trie = SecTrieDBMut::new(&mut db, &mut root);
+		for i in 1..500u64 {
+			let mut account = RlpStream::new_list(4);
+			account.append(&U256::zero());
+			account.append(&U256::from(i));
+			account.append(&SHA3_NULL_RLP);
+			account.append(&SHA3_EMPTY);
+			trie.insert(&Address::from(i), &account.out()).unwrap();
+		}
+	}
+	for chain in chains {
+		let mut nodes = chain.state_nodes.write();
+		for hash in db.keys().keys() {
+			nodes.insert(hash.clone(), db.get(hash).unwrap().to_vec());
+		}
+		*chain.state_root.write() = root.clone();
+	}
+	root
+}
+
+#[test]
+fn fast_sync() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(3);
+	let root = add_state(&[&*net.peer(1).chain, &*net.peer(2).chain]);
+	net.peer(1).chain.add_blocks(1200, EachBlockWith::Uncle);
+	net.peer(2).chain.add_blocks(1200, EachBlockWith::Uncle);
+
+	// resume with a pivot left by an interrupted fast sync.
+	let pivot = net.peer(1).chain.block_header(BlockId::Number(1100)).unwrap();
+	{
+		let peer = net.peer_mut(0);
+		peer.chain.set_fast_sync_pivot(Some(pivot));
+		peer.sync = RwLock::new(ChainSync::new(SyncConfig::default(), &*peer.chain));
+	}
+	assert_eq!(net.peer(0).sync.read().status().state, SyncState::FastSync);
+	assert_eq!(net.peer(0).sync.read().status().fast_sync_pivot, Some(1100));
+
+	net.sync();
+	let chain = &net.peer(0).chain;
+	assert!(chain.state_node_known(None, &root));
+	assert_eq!(chain.state_nodes.read().len(), net.peer(1).chain.state_nodes.read().len());
+	assert!(chain.fast_sync_pivot().is_none());
+	assert_eq!(chain.chain_info().best_block_number, 1200);
+	assert_eq!(net.peer(0).sync.read().status().state, SyncState::Idle);
+}
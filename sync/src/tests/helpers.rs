@@ -49,6 +49,7 @@ pub struct TestIo<'p, C> where C: FlushingBlockChainClient, C: 'p {
 	pub queue: &'p RwLock<VecDeque<TestPacket>>,
 	pub sender: Option<PeerId>,
 	pub to_disconnect: HashSet<PeerId>,
+	pub reports: Vec<(PeerId, PeerReport)>,
 	pub packets: Vec<TestPacket>,
 	overlay: RwLock<HashMap<BlockNumber, Bytes>>,
 }
@@ -61,6 +62,7 @@ impl<'p, C> TestIo<'p, C> where C: FlushingBlockChainClient, C: 'p {
 			queue: queue,
 			sender: sender,
 			to_disconnect: HashSet::new(),
+			reports: Vec::new(),
 			overlay: RwLock::new(HashMap::new()),
 			packets: Vec::new(),
 		}
@@ -82,6 +84,10 @@ impl<'p, C> SyncIo for TestIo<'p, C> where C: FlushingBlockChainClient, C: 'p {
 		self.to_disconnect.insert(peer_id);
 	}
 
+	fn report_peer(&mut self, peer_id: PeerId, report: PeerReport) {
+		self.reports.push((peer_id, report));
+	}
+
 	fn is_expired(&self) -> bool {
 		false
 	}
@@ -18,6 +18,7 @@ pub mod helpers;
 pub mod snapshot;
 mod chain;
 mod consensus;
+mod simulation;
 
 #[cfg(feature = "ipc")]
 mod rpc;
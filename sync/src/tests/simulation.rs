@@ -0,0 +1,123 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sync over the simulated network stack, including discovery and `RLPx` sessions.
+
+use std::sync::Arc;
+use ethcore::client::{TestBlockChainClient, BlockChainClient, BlockId, EachBlockWith};
+use ethcore::spec::Spec;
+use io::IoChannel;
+use light::client::Client as LightClient;
+use light::net::{LightProtocol, Capabilities, Params as LightParams};
+use light::net::buffer_flow::FlowParams;
+use network::{Simulation, LinkConfig, NetworkConfiguration};
+use api::{SyncProtocolHandler, ETH_PROTOCOL, WARP_SYNC_PROTOCOL_ID, LES_PROTOCOL};
+use chain::{ETH_PACKET_COUNT, SNAPSHOT_SYNC_PACKET_COUNT};
+use light_sync::LightSync;
+use tests::snapshot::TestSnapshotService;
+use SyncConfig;
+
+fn config(sim: &Simulation) -> NetworkConfiguration {
+	let mut config = NetworkConfiguration::new();
+	if sim.node_count() > 0 {
+		config.boot_nodes = vec![sim.node_url(0)];
+	}
+	config
+}
+
+fn full_node(sim: &mut Simulation, blocks: usize, serve_light: bool) -> Arc<TestBlockChainClient> {
+	let chain = Arc::new(TestBlockChainClient::new());
+	chain.add_blocks(blocks, EachBlockWith::Uncle);
+	let config = config(sim);
+	let node = sim.add_node(config);
+	let handler = Arc::new(SyncProtocolHandler::new(SyncConfig::default(), chain.clone(), Arc::new(TestSnapshotService::new())));
+	sim.register_protocol(node, handler.clone(), ETH_PROTOCOL, ETH_PACKET_COUNT, &[62u8, 63u8]);
+	sim.register_protocol(node, handler, WARP_SYNC_PROTOCOL_ID, SNAPSHOT_SYNC_PACKET_COUNT, &[1u8, 2u8]);
+	if serve_light {
+		let params = LightParams {
+			network_id: SyncConfig::default().network_id,
+			flow_params: FlowParams::free(),
+			capabilities: Capabilities {
+				serve_headers: true,
+				serve_chain_since: None,
+				serve_state_since: None,
+				tx_relay: true,
+			},
+		};
+		let proto = Arc::new(LightProtocol::new(chain.clone(), params));
+		sim.register_protocol(node, proto, LES_PROTOCOL, ::light::net::PACKET_COUNT, ::light::net::PROTOCOL_VERSIONS);
+	}
+	chain
+}
+
+#[test]
+fn full_sync() {
+	let mut sim = Simulation::new(1);
+	sim.set_default_link(LinkConfig { latency: 50, loss: 0.01, bandwidth: 1 << 20 });
+	let source = full_node(&mut sim, 1000, false);
+	let peer = full_node(&mut sim, 1000, false);
+	let target = full_node(&mut sim, 0, false);
+
+	assert!(sim.run_until(600_000, |_| target.chain_info().best_block_number == 1000));
+	assert_eq!(*target.blocks.read(), *source.blocks.read());
+	assert_eq!(*peer.blocks.read(), *source.blocks.read());
+}
+
+#[test]
+fn sync_after_partition() {
+	let mut sim = Simulation::new(2);
+	sim.partition(&[0], &[1]);
+	full_node(&mut sim, 200, false);
+	let target = full_node(&mut sim, 0, false);
+	sim.run_for(30_000);
+	assert!(!sim.is_connected(0, 1));
+	assert_eq!(target.chain_info().best_block_number, 0);
+
+	sim.heal();
+	assert!(sim.run_until(120_000, |_| target.chain_info().best_block_number == 200));
+}
+
+#[test]
+fn light_sync() {
+	let mut sim = Simulation::new(3);
+	let full = full_node(&mut sim, 500, true);
+
+	let client = Arc::new(LightClient::new(Default::default(), &Spec::new_test(), IoChannel::disconnected()));
+	let sync = Arc::new(LightSync::new(client.clone()).unwrap());
+	let params = LightParams {
+		network_id: SyncConfig::default().network_id,
+		flow_params: FlowParams::default(),
+		capabilities: Capabilities {
+			serve_headers: false,
+			serve_chain_since: None,
+			serve_state_since: None,
+			tx_relay: false,
+		},
+	};
+	let mut proto = LightProtocol::new(client.clone(), params);
+	proto.add_handler(sync);
+	let config = config(&sim);
+	let node = sim.add_node(config);
+	sim.register_protocol(node, Arc::new(proto), LES_PROTOCOL, ::light::net::PACKET_COUNT, ::light::net::PROTOCOL_VERSIONS);
+
+	assert!(sim.run_until(120_000, |_| {
+		// the client has no IO service to import verified headers.
+		client.flush_queue();
+		client.import_verified();
+		client.get_header(BlockId::Number(500)).is_some()
+	}));
+	assert_eq!(client.get_header(BlockId::Number(500)), full.block_header(BlockId::Number(500)).map(|h| h.into_inner()));
+}
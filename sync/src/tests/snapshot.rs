@@ -92,11 +92,26 @@ impl SnapshotService for TestSnapshotService {
 	}
 
 	fn begin_restore(&self, manifest: ManifestData) {
-		*self.restoration_manifest.lock() = Some(manifest);
+		let mut restoration_manifest = self.restoration_manifest.lock();
+		if restoration_manifest.as_ref() == Some(&manifest) {
+			return;
+		}
+		*restoration_manifest = Some(manifest);
 		self.state_restoration_chunks.lock().clear();
 		self.block_restoration_chunks.lock().clear();
 	}
 
+	fn completed_chunks(&self, manifest_hash: H256) -> Option<Vec<H256>> {
+		match *self.restoration_manifest.lock() {
+			Some(ref manifest) if manifest.clone().into_rlp().sha3() == manifest_hash => {
+				let mut chunks: Vec<H256> = self.state_restoration_chunks.lock().keys().cloned().collect();
+				chunks.extend(self.block_restoration_chunks.lock().keys().cloned());
+				Some(chunks)
+			}
+			_ => None,
+		}
+	}
+
 	fn abort_restore(&self) {
 		*self.restoration_manifest.lock() = None;
 		self.state_restoration_chunks.lock().clear();
@@ -136,3 +151,29 @@ fn snapshot_sync() {
 	assert_eq!(net.peer(4).snapshot_service.block_restoration_chunks.lock().len(), net.peer(0).snapshot_service.manifest.as_ref().unwrap().block_hashes.len());
 }
 
+#[test]
+fn snapshot_sync_resumes_restoration() {
+	::env_logger::init().ok();
+	let mut config = SyncConfig::default();
+	config.warp_sync = true;
+	let mut net = TestNet::new_with_config(5, config);
+	let snapshot_service = Arc::new(TestSnapshotService::new_with_snapshot(16, H256::new(), 500000));
+	for i in 0..4 {
+		net.peer_mut(i).snapshot_service = snapshot_service.clone();
+		net.peer(i).chain.add_blocks(1, EachBlockWith::Nothing);
+	}
+
+	// peer 4 was restarted half way through restoring the same snapshot.
+	let manifest = snapshot_service.manifest.clone().unwrap();
+	*net.peer(4).snapshot_service.restoration_manifest.lock() = Some(manifest.clone());
+	for hash in &manifest.state_hashes {
+		net.peer(4).snapshot_service.state_restoration_chunks.lock().insert(*hash, Bytes::new());
+	}
+
+	net.sync_steps(50);
+	let state_chunks = net.peer(4).snapshot_service.state_restoration_chunks.lock();
+	assert_eq!(state_chunks.len(), manifest.state_hashes.len());
+	assert!(state_chunks.values().all(|chunk| chunk.is_empty()));
+	assert_eq!(net.peer(4).snapshot_service.block_restoration_chunks.lock().len(), manifest.block_hashes.len());
+}
+
@@ -133,6 +133,7 @@ pub use service::IoContext;
 pub use service::IoService;
 pub use service::IoChannel;
 pub use service::IoManager;
+pub use service::{IoMessage, IoMessageQueue};
 pub use service::TOKENS_PER_HANDLER;
 pub use panics::{PanicHandler, MayPanic, OnPanicListener, ForwardPanic};
 
@@ -169,4 +170,23 @@ mod tests {
 		service.register_handler(Arc::new(MyHandler)).unwrap();
 	}
 
+	#[test]
+	fn test_queued_channel() {
+		let queue = IoMessageQueue::<MyMessage>::default();
+		let io = IoContext::new(IoChannel::queued(queue.clone()), 0);
+		io.register_timer(1, 100).unwrap();
+		io.message(MyMessage { data: 7 }).unwrap();
+
+		let queue = queue.lock();
+		assert_eq!(queue.len(), 2);
+		match queue[0] {
+			IoMessage::AddTimer { handler_id: 0, token: 1, delay: 100, once: false } => {},
+			_ => panic!("Unexpected message"),
+		}
+		match queue[1] {
+			IoMessage::UserMessage(ref message) => assert_eq!(message.data, 7),
+			_ => panic!("Unexpected message"),
+		}
+	}
+
 }
@@ -16,7 +16,7 @@
 
 use std::sync::{Arc, Weak};
 use std::thread::{self, JoinHandle};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use mio::*;
 use mio::timer::{Timeout};
 use mio::deprecated::{EventLoop, Handler, Sender, EventLoopBuilder};
@@ -335,10 +335,14 @@ enum Handlers<Message> where Message: Send + Clone {
 	Single(Weak<IoHandler<Message>>),
 }
 
+/// Message queue shared with a custom event loop driver.
+pub type IoMessageQueue<Message> = Arc<Mutex<VecDeque<IoMessage<Message>>>>;
+
 /// Allows sending messages into the event loop. All the IO handlers will get the message
 /// in the `message` callback.
 pub struct IoChannel<Message> where Message: Send + Clone{
 	channel: Option<Sender<IoMessage<Message>>>,
+	queue: Option<IoMessageQueue<Message>>,
 	handlers: Handlers<Message>,
 
 }
@@ -347,6 +351,7 @@ impl<Message> Clone for IoChannel<Message> where Message: Send + Clone + Sync +
 	fn clone(&self) -> IoChannel<Message> {
 		IoChannel {
 			channel: self.channel.clone(),
+			queue: self.queue.clone(),
 			handlers: self.handlers.clone(),
 		}
 	}
@@ -355,9 +360,10 @@ impl<Message> Clone for IoChannel<Message> where Message: Send + Clone + Sync +
 impl<Message> IoChannel<Message> where Message: Send + Clone + Sync + 'static {
 	/// Send a message through the channel
 	pub fn send(&self, message: Message) -> Result<(), IoError> {
-		match self.channel {
-			Some(ref channel) => channel.send(IoMessage::UserMessage(message))?,
-			None => self.send_sync(message)?
+		match (&self.channel, &self.queue) {
+			(&Some(ref channel), _) => channel.send(IoMessage::UserMessage(message))?,
+			(&None, &Some(ref queue)) => queue.lock().push_back(IoMessage::UserMessage(message)),
+			(&None, &None) => self.send_sync(message)?
 		}
 		Ok(())
 	}
@@ -388,6 +394,8 @@ impl<Message> IoChannel<Message> where Message: Send + Clone + Sync + 'static {
 	pub fn send_io(&self, message: IoMessage<Message>) -> Result<(), IoError> {
 		if let Some(ref channel) = self.channel {
 			channel.send(message)?
+		} else if let Some(ref queue) = self.queue {
+			queue.lock().push_back(message);
 		}
 		Ok(())
 	}
@@ -395,6 +403,7 @@ impl<Message> IoChannel<Message> where Message: Send + Clone + Sync + 'static {
 	pub fn disconnected() -> IoChannel<Message> {
 		IoChannel {
 			channel: None,
+			queue: None,
 			handlers: Handlers::SharedCollection(Weak::default()),
 		}
 	}
@@ -403,12 +412,25 @@ impl<Message> IoChannel<Message> where Message: Send + Clone + Sync + 'static {
 	pub fn to_handler(handler: Weak<IoHandler<Message>>) -> IoChannel<Message> {
 		IoChannel {
 			channel: None,
+			queue: None,
 			handlers: Handlers::Single(handler),
 		}
 	}
+
+	/// Create a new channel which collects all messages in a queue. The queue has to be
+	/// drained by the caller, which takes the place of the event loop.
+	pub fn queued(queue: IoMessageQueue<Message>) -> IoChannel<Message> {
+		IoChannel {
+			channel: None,
+			queue: Some(queue),
+			handlers: Handlers::SharedCollection(Weak::default()),
+		}
+	}
+
 	fn new(channel: Sender<IoMessage<Message>>, handlers: Weak<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>) -> IoChannel<Message> {
 		IoChannel {
 			channel: Some(channel),
+			queue: None,
 			handlers: Handlers::SharedCollection(handlers),
 		}
 	}
@@ -0,0 +1,166 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bandwidth limiting.
+//!
+//! Upload and download rates are capped with token buckets shared by all connections.
+//! A bucket holds at most one second worth of traffic. Connections check the allowance
+//! before touching the socket and stay throttled until the host refills them.
+
+use std::cmp;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+
+/// Token bucket refilled at a constant rate of bytes per second.
+#[derive(Debug)]
+pub struct TokenBucket {
+	rate: usize,
+	tokens: usize,
+	updated: Instant,
+}
+
+impl TokenBucket {
+	/// Create a full bucket.
+	pub fn new(rate: usize, now: Instant) -> TokenBucket {
+		TokenBucket {
+			rate: rate,
+			tokens: rate,
+			updated: now,
+		}
+	}
+
+	fn refill(&mut self, now: Instant) {
+		if now <= self.updated {
+			return;
+		}
+		let elapsed = cmp::min(now - self.updated, Duration::from_secs(1));
+		let nanos = elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64;
+		let added = (self.rate as u64 * nanos / 1_000_000_000) as usize;
+		// keep the remainder for the next refill unless the bucket is full.
+		if added > 0 || self.tokens == self.rate {
+			self.tokens = cmp::min(self.rate, self.tokens + added);
+			self.updated = now;
+		}
+	}
+
+	/// Number of bytes which may be transferred now.
+	pub fn allowance(&mut self, now: Instant) -> usize {
+		self.refill(now);
+		self.tokens
+	}
+
+	/// Account for transferred bytes.
+	pub fn consume(&mut self, size: usize) {
+		self.tokens = self.tokens.saturating_sub(size);
+	}
+}
+
+/// Upload and download limits shared by all connections.
+#[derive(Debug, Default)]
+pub struct BandwidthLimiter {
+	upload: Option<Mutex<TokenBucket>>,
+	download: Option<Mutex<TokenBucket>>,
+}
+
+impl BandwidthLimiter {
+	/// Create a limiter with the given rates in bytes per second. `None` or zero means unlimited.
+	pub fn new(upload: Option<usize>, download: Option<usize>) -> BandwidthLimiter {
+		let now = Instant::now();
+		let bucket = |rate: Option<usize>| match rate {
+			Some(rate) if rate > 0 => Some(Mutex::new(TokenBucket::new(rate, now))),
+			_ => None,
+		};
+		BandwidthLimiter {
+			upload: bucket(upload),
+			download: bucket(download),
+		}
+	}
+
+	/// Check if any rate is limited.
+	pub fn is_limited(&self) -> bool {
+		self.upload.is_some() || self.download.is_some()
+	}
+
+	/// Number of bytes which may be sent now, up to `wanted`.
+	pub fn upload_allowance(&self, wanted: usize) -> usize {
+		allowance(&self.upload, wanted)
+	}
+
+	/// Number of bytes which may be received now, up to `wanted`.
+	pub fn download_allowance(&self, wanted: usize) -> usize {
+		allowance(&self.download, wanted)
+	}
+
+	/// Account for sent bytes.
+	pub fn consume_upload(&self, size: usize) {
+		if let Some(ref bucket) = self.upload {
+			bucket.lock().consume(size);
+		}
+	}
+
+	/// Account for received bytes.
+	pub fn consume_download(&self, size: usize) {
+		if let Some(ref bucket) = self.download {
+			bucket.lock().consume(size);
+		}
+	}
+}
+
+fn allowance(bucket: &Option<Mutex<TokenBucket>>, wanted: usize) -> usize {
+	match *bucket {
+		Some(ref bucket) => cmp::min(wanted, bucket.lock().allowance(Instant::now())),
+		None => wanted,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+	use super::*;
+
+	#[test]
+	fn bucket_refills_at_rate() {
+		let start = Instant::now();
+		let mut bucket = TokenBucket::new(1000, start);
+		assert_eq!(bucket.allowance(start), 1000);
+		bucket.consume(1500);
+		assert_eq!(bucket.allowance(start), 0);
+
+		assert_eq!(bucket.allowance(start + Duration::from_millis(100)), 100);
+		bucket.consume(100);
+		// fractions are not lost between refills.
+		assert_eq!(bucket.allowance(start + Duration::from_millis(100) + Duration::new(0, 500_000)), 0);
+		assert_eq!(bucket.allowance(start + Duration::from_millis(101)), 1);
+
+		// never more than a second worth of traffic.
+		assert_eq!(bucket.allowance(start + Duration::from_secs(10)), 1000);
+	}
+
+	#[test]
+	fn unlimited() {
+		let limiter = BandwidthLimiter::new(None, Some(0));
+		assert!(!limiter.is_limited());
+		assert_eq!(limiter.upload_allowance(1 << 20), 1 << 20);
+		assert_eq!(limiter.download_allowance(1 << 20), 1 << 20);
+
+		let limiter = BandwidthLimiter::new(Some(100), None);
+		assert!(limiter.is_limited());
+		assert_eq!(limiter.upload_allowance(1 << 20), 100);
+		limiter.consume_upload(100);
+		assert!(limiter.upload_allowance(1 << 20) < 100);
+		assert_eq!(limiter.download_allowance(1 << 20), 1 << 20);
+	}
+}
@@ -20,7 +20,6 @@ use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use mio::{Token, Ready, PollOpt};
 use mio::deprecated::{Handler, EventLoop, TryRead, TryWrite};
-use mio::tcp::*;
 use util::hash::*;
 use util::sha3::*;
 use util::bytes::*;
@@ -30,6 +29,8 @@ use error::*;
 use io::{IoContext, StreamToken};
 use handshake::Handshake;
 use stats::NetworkStats;
+use bandwidth::BandwidthLimiter;
+use transport::Stream;
 use rcrypto::blockmodes::*;
 use rcrypto::aessafe::*;
 use rcrypto::symmetriccipher::*;
@@ -44,7 +45,7 @@ const RECIEVE_PAYLOAD_TIMEOUT: u64 = 30000;
 pub trait GenericSocket : Read + Write {
 }
 
-impl GenericSocket for TcpStream {
+impl GenericSocket for Box<Stream> {
 }
 
 pub struct GenericConnection<Socket: GenericSocket> {
@@ -62,6 +63,14 @@ pub struct GenericConnection<Socket: GenericSocket> {
 	interest: Ready,
 	/// Shared network statistics
 	stats: Arc<NetworkStats>,
+	/// Shared bandwidth limits
+	limiter: Arc<BandwidthLimiter>,
+	/// Set when an IO handler stopped because the bandwidth allowance was exhausted
+	throttled: bool,
+	/// Bytes sent over this connection
+	sent: usize,
+	/// Bytes received over this connection
+	received: usize,
 	/// Registered flag
 	registered: AtomicBool,
 }
@@ -82,11 +91,18 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
 		}
 		let sock_ref = <Socket as Read>::by_ref(&mut self.socket);
 		loop {
-			let max = self.rec_size - self.rec_buf.len();
+			let max = self.limiter.download_allowance(self.rec_size - self.rec_buf.len());
+			if max == 0 {
+				trace!(target:"network", "{}: Read throttled", self.token);
+				self.throttled = true;
+				return Ok(None);
+			}
 			match sock_ref.take(max as u64).try_read(unsafe { self.rec_buf.mut_bytes() }) {
 				Ok(Some(size)) if size != 0  => {
 					unsafe { self.rec_buf.advance(size); }
 					self.stats.inc_recv(size);
+					self.limiter.consume_download(size);
+					self.received += size;
 					trace!(target:"network", "{}: Read {} of {} bytes", self.token, self.rec_buf.len(), self.rec_size);
 					if self.rec_size != 0 && self.rec_buf.len() == self.rec_size {
 						self.rec_size = 0;
@@ -123,6 +139,25 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
 		self.interest.is_writable()
 	}
 
+	/// Check if IO was stopped by the bandwidth limits.
+	pub fn is_throttled(&self) -> bool {
+		self.throttled
+	}
+
+	/// Resume IO stopped by the bandwidth limits. Pending reads have to be retried by the caller.
+	pub fn unthrottle<Message>(&mut self, io: &IoContext<Message>) where Message: Send + Clone + Sync + 'static {
+		self.throttled = false;
+		if !self.send_queue.is_empty() && !self.interest.is_writable() {
+			self.interest.insert(Ready::writable());
+			io.update_registration(self.token).ok();
+		}
+	}
+
+	/// Get the number of bytes sent and received over this connection.
+	pub fn traffic(&self) -> (usize, usize) {
+		(self.sent, self.received)
+	}
+
 	/// Writable IO handler. Called when the socket is ready to send.
 	pub fn writable<Message>(&mut self, io: &IoContext<Message>) -> Result<WriteStatus, NetworkError> where Message: Send + Clone + Sync + 'static {
 		{
@@ -136,15 +171,28 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
 				warn!(target:"net", "Unexpected connection data");
 				return Ok(WriteStatus::Complete)
 			}
+			let max = self.limiter.upload_allowance(send_size - pos);
+			if max == 0 {
+				// stop polling for writes until the host refills the allowance.
+				trace!(target:"network", "{}: Write throttled", self.token);
+				self.throttled = true;
+				self.interest.remove(Ready::writable());
+				io.update_registration(self.token)?;
+				return Ok(WriteStatus::Ongoing);
+			}
 			let buf = buf as &mut Buf;
-			match self.socket.try_write(buf.bytes()) {
+			match self.socket.try_write(&buf.bytes()[..max]) {
 				Ok(Some(size)) if (pos + size) < send_size => {
 					buf.advance(size);
 					self.stats.inc_send(size);
+					self.limiter.consume_upload(size);
+					self.sent += size;
 					Ok(WriteStatus::Ongoing)
 				},
 				Ok(Some(size)) if (pos + size) == send_size => {
 					self.stats.inc_send(size);
+					self.limiter.consume_upload(size);
+					self.sent += size;
 					trace!(target:"network", "{}: Wrote {} bytes", self.token, send_size);
 					Ok(WriteStatus::Complete)
 				},
@@ -166,11 +214,11 @@ impl<Socket: GenericSocket> GenericConnection<Socket> {
 }
 
 /// Low level tcp connection
-pub type Connection = GenericConnection<TcpStream>;
+pub type Connection = GenericConnection<Box<Stream>>;
 
 impl Connection {
 	/// Create a new connection with given id and socket.
-	pub fn new(token: StreamToken, socket: TcpStream, stats: Arc<NetworkStats>) -> Connection {
+	pub fn new(token: StreamToken, socket: Box<Stream>, stats: Arc<NetworkStats>, limiter: Arc<BandwidthLimiter>) -> Connection {
 		Connection {
 			token: token,
 			socket: socket,
@@ -179,6 +227,10 @@ impl Connection {
 			rec_size: 0,
 			interest: Ready::hup() | Ready::readable(),
 			stats: stats,
+			limiter: limiter,
+			throttled: false,
+			sent: 0,
+			received: 0,
 			registered: AtomicBool::new(false),
 		}
 	}
@@ -213,6 +265,10 @@ impl Connection {
 			send_queue: self.send_queue.clone(),
 			interest: Ready::hup(),
 			stats: self.stats.clone(),
+			limiter: self.limiter.clone(),
+			throttled: false,
+			sent: self.sent,
+			received: self.received,
 			registered: AtomicBool::new(false),
 		})
 	}
@@ -223,7 +279,7 @@ impl Connection {
 			return Ok(());
         }
 		trace!(target: "network", "connection register; token={:?}", reg);
-		if let Err(e) = event_loop.register(&self.socket, reg, self.interest, PollOpt::edge() /* | PollOpt::oneshot() */) { // TODO: oneshot is broken on windows
+		if let Err(e) = event_loop.register(&*self.socket, reg, self.interest, PollOpt::edge() /* | PollOpt::oneshot() */) { // TODO: oneshot is broken on windows
 			trace!(target: "network", "Failed to register {:?}, {:?}", reg, e);
 		}
 		self.registered.store(true, AtomicOrdering::SeqCst);
@@ -236,7 +292,7 @@ impl Connection {
 		if !self.registered.load(AtomicOrdering::SeqCst) {
 			self.register_socket(reg, event_loop)
         } else {
-			event_loop.reregister(&self.socket, reg, self.interest, PollOpt::edge() /* | PollOpt::oneshot() */ ).unwrap_or_else(|e| {  // TODO: oneshot is broken on windows
+			event_loop.reregister(&*self.socket, reg, self.interest, PollOpt::edge() /* | PollOpt::oneshot() */ ).unwrap_or_else(|e| {  // TODO: oneshot is broken on windows
 				trace!(target: "network", "Failed to reregister {:?}, {:?}", reg, e);
 			});
 			Ok(())
@@ -246,7 +302,7 @@ impl Connection {
 	/// Delete connection registration. Should be called at the end of the IO handler.
 	pub fn deregister_socket<Host: Handler>(&self, event_loop: &mut EventLoop<Host>) -> io::Result<()> {
 		trace!(target: "network", "connection deregister; token={:?}", self.token);
-		event_loop.deregister(&self.socket).ok(); // ignore errors here
+		event_loop.deregister(&*self.socket).ok(); // ignore errors here
 		Ok(())
 	}
 }
@@ -504,6 +560,7 @@ mod tests {
 	use std::sync::Arc;
 	use std::sync::atomic::AtomicBool;
 	use super::super::stats::*;
+	use bandwidth::BandwidthLimiter;
 	use std::io::{Read, Write, Error, Cursor, ErrorKind};
 	use mio::{Ready};
 	use std::collections::VecDeque;
@@ -553,6 +610,10 @@ mod tests {
 				rec_size: 0,
 				interest: Ready::hup() | Ready::readable(),
 				stats: Arc::<NetworkStats>::new(NetworkStats::new()),
+				limiter: Arc::new(BandwidthLimiter::default()),
+				throttled: false,
+				sent: 0,
+				received: 0,
 				registered: AtomicBool::new(false),
 			}
 		}
@@ -576,6 +637,10 @@ mod tests {
 				rec_size: 0,
 				interest: Ready::hup() | Ready::readable(),
 				stats: Arc::<NetworkStats>::new(NetworkStats::new()),
+				limiter: Arc::new(BandwidthLimiter::default()),
+				throttled: false,
+				sent: 0,
+				received: 0,
 				registered: AtomicBool::new(false),
 			}
 		}
@@ -626,6 +691,28 @@ mod tests {
 		assert_eq!(1024, connection.socket.write_buffer.len());
 	}
 
+	#[test]
+	fn connection_write_is_throttled() {
+		let mut connection = TestConnection::new();
+		connection.limiter = Arc::new(BandwidthLimiter::new(Some(1000), None));
+		connection.send_queue.push_back(Cursor::new(vec![0; 10240]));
+
+		let status = connection.writable(&test_io());
+		assert!(WriteStatus::Ongoing == status.unwrap());
+		assert_eq!(1000, connection.socket.write_buffer.len());
+		assert!(!connection.is_throttled());
+
+		let status = connection.writable(&test_io());
+		assert!(WriteStatus::Ongoing == status.unwrap());
+		assert!(connection.is_throttled());
+		assert!(!connection.is_sending());
+		assert_eq!((1000, 0), connection.traffic());
+
+		connection.unthrottle(&test_io());
+		assert!(!connection.is_throttled());
+		assert!(connection.is_sending());
+	}
+
 	#[test]
 	fn connection_write_to_broken() {
 		let mut connection = TestBrokenConnection::new();
@@ -18,10 +18,12 @@ use util::bytes::Bytes;
 use std::net::SocketAddr;
 use std::collections::{HashSet, HashMap, BTreeMap, VecDeque};
 use std::mem;
+use std::cmp;
 use std::default::Default;
 use mio::*;
 use mio::deprecated::{Handler, EventLoop};
 use mio::udp::*;
+use transport::Datagram;
 use util::sha3::*;
 use time;
 use util::hash::*;
@@ -32,7 +34,6 @@ use io::{StreamToken, IoContext};
 use ethkey::{Secret, KeyPair, sign, recover};
 use AllowIP;
 
-use PROTOCOL_VERSION;
 
 const ADDRESS_BYTES_SIZE: u32 = 32;							// Size of address type in bytes.
 const ADDRESS_BITS: u32 = 8 * ADDRESS_BYTES_SIZE;			// Denoted by n in [Kademlia].
@@ -41,14 +42,29 @@ const DISCOVERY_MAX_STEPS: u16 = 8;							// Max iterations of discovery. (disco
 const BUCKET_SIZE: usize = 16;		// Denoted by k in [Kademlia]. Number of nodes stored in each bucket.
 const ALPHA: usize = 3;				// Denoted by \alpha in [Kademlia]. Number of concurrent FindNode requests.
 const MAX_DATAGRAM_SIZE: usize = 1280;
+const DISCOVERY_VERSION: u32 = 4;
 
 const PACKET_PING: u8 = 1;
 const PACKET_PONG: u8 = 2;
 const PACKET_FIND_NODE: u8 = 3;
 const PACKET_NEIGHBOURS: u8 = 4;
+const PACKET_TOPIC_REGISTER: u8 = 5;
+const PACKET_TOPIC_QUERY: u8 = 6;
+const PACKET_TOPIC_NODES: u8 = 7;
 
 const PING_TIMEOUT_MS: u64 = 300;
+const PACKET_EXPIRY_SEC: u64 = 60; // Time after which a sent packet is considered expired
+const BOND_EXPIRY_SEC: u64 = 12 * 60 * 60; // Time a node which answered our ping is trusted with its address
 const MAX_NODES_PING: usize = 32; // Max nodes to add/ping at once
+const MAX_TOPIC_LEN: usize = 32; // Max size of a topic in bytes
+const MAX_TOPIC_ADS: usize = 256; // Max advertisements stored per topic
+const MAX_TOPICS: usize = 64; // Max distinct topics stored
+const MAX_TOTAL_TOPIC_ADS: usize = 4096; // Max advertisements stored for all topics
+const TOPIC_AD_LIFETIME_SEC: u64 = 600; // Time an advertisement is kept by a registrar
+const TOPIC_NODES: usize = 8; // Number of nodes nearest to a topic to register with or query
+
+/// Discovery topic, such as a capability name.
+pub type Topic = Bytes;
 
 #[derive(Clone, Debug)]
 pub struct NodeEntry {
@@ -80,6 +96,11 @@ impl NodeBucket {
 	}
 }
 
+struct TopicAd {
+	entry: NodeEntry,
+	expires: u64,
+}
+
 struct Datagramm {
 	payload: Bytes,
 	address: SocketAddr,
@@ -90,7 +111,7 @@ pub struct Discovery {
 	id_hash: H256,
 	secret: Secret,
 	public_endpoint: NodeEndpoint,
-	udp_socket: UdpSocket,
+	udp_socket: Box<Datagram>,
 	token: StreamToken,
 	discovery_round: u16,
 	discovery_id: NodeId,
@@ -100,6 +121,13 @@ pub struct Discovery {
 	check_timestamps: bool,
 	adding_nodes: Vec<NodeEntry>,
 	allow_ips: AllowIP,
+	topics: HashSet<Topic>,
+	topic_ads: HashMap<Topic, HashMap<NodeId, TopicAd>>,
+	topic_searches: HashMap<Topic, HashMap<NodeId, NodeEntry>>,
+	// Address of each node which answered our ping, with bond expiry time.
+	bonds: HashMap<NodeId, (SocketAddr, u64)>,
+	// Expiry time of each topic query sent, by queried node and topic.
+	topic_queries: HashMap<(NodeId, Topic), u64>,
 }
 
 pub struct TableUpdates {
@@ -110,6 +138,11 @@ pub struct TableUpdates {
 impl Discovery {
 	pub fn new(key: &KeyPair, listen: SocketAddr, public: NodeEndpoint, token: StreamToken, allow_ips: AllowIP) -> Discovery {
 		let socket = UdpSocket::bind(&listen).expect("Error binding UDP socket");
+		Discovery::with_socket(key, Box::new(socket), public, token, allow_ips)
+	}
+
+	/// Create a new instance over an already bound socket.
+	pub fn with_socket(key: &KeyPair, socket: Box<Datagram>, public: NodeEndpoint, token: StreamToken, allow_ips: AllowIP) -> Discovery {
 		Discovery {
 			id: key.public().clone(),
 			id_hash: key.public().sha3(),
@@ -125,9 +158,54 @@ impl Discovery {
 			check_timestamps: true,
 			adding_nodes: Vec::new(),
 			allow_ips: allow_ips,
+			topics: HashSet::new(),
+			topic_ads: HashMap::new(),
+			topic_searches: HashMap::new(),
+			bonds: HashMap::new(),
+			topic_queries: HashMap::new(),
 		}
 	}
 
+	/// Change the advertised endpoint. Known nodes are pinged, so that they learn the new
+	/// endpoint, and topic registrations are renewed.
+	pub fn set_public_endpoint(&mut self, endpoint: NodeEndpoint) {
+		self.public_endpoint = endpoint;
+		let known: Vec<NodeEndpoint> = self.node_buckets.iter()
+			.flat_map(|bucket| bucket.nodes.iter().map(|n| n.address.endpoint.clone()))
+			.collect();
+		for node in &known {
+			self.ping(node);
+		}
+		let topics: Vec<Topic> = self.topics.iter().cloned().collect();
+		for topic in &topics {
+			self.advertise(topic);
+		}
+	}
+
+	/// Advertise this node under the given topic. Registrations are sent to the nodes
+	/// nearest to the topic hash and renewed on every refresh.
+	pub fn register_topic(&mut self, topic: Topic) {
+		if topic.len() > MAX_TOPIC_LEN || !self.topics.insert(topic.clone()) {
+			return;
+		}
+		self.advertise(&topic);
+	}
+
+	/// Look for nodes advertised under the given topic. Queries are sent to the nodes
+	/// nearest to the topic hash and repeated on every refresh.
+	pub fn search_topic(&mut self, topic: Topic) {
+		if topic.len() > MAX_TOPIC_LEN || self.topic_searches.contains_key(&topic) {
+			return;
+		}
+		self.topic_searches.insert(topic.clone(), HashMap::new());
+		self.query(&topic);
+	}
+
+	/// Nodes found advertising the given topic.
+	pub fn topic_nodes(&self, topic: &[u8]) -> Vec<NodeEntry> {
+		self.topic_searches.get(topic).map_or_else(Vec::new, |nodes| nodes.values().cloned().collect())
+	}
+
 	/// Add a new node to discovery table. Pings the node.
 	pub fn add_node(&mut self, e: NodeEntry) {
 		if self.is_allowed(&e) {
@@ -230,6 +308,39 @@ impl Discovery {
 		self.discovery_round += 1;
 	}
 
+	fn advertise(&mut self, topic: &Topic) {
+		let nearest = Discovery::nearest_hash_entries(&topic.sha3(), &self.node_buckets);
+		for r in nearest.into_iter().take(TOPIC_NODES) {
+			let mut rlp = RlpStream::new_list(2);
+			self.public_endpoint.to_rlp_list(&mut rlp);
+			rlp.append(topic);
+			self.send_packet(PACKET_TOPIC_REGISTER, &r.endpoint.udp_address(), &rlp.drain());
+			trace!(target: "discovery", "Sent TopicRegister to {:?}", &r.endpoint);
+		}
+	}
+
+	fn query(&mut self, topic: &Topic) {
+		let nearest = Discovery::nearest_hash_entries(&topic.sha3(), &self.node_buckets);
+		let expires = time::get_time().sec as u64 + PACKET_EXPIRY_SEC;
+		for r in nearest.into_iter().take(TOPIC_NODES) {
+			let rlp = encode(&(&[topic.clone()][..]));
+			self.send_packet(PACKET_TOPIC_QUERY, &r.endpoint.udp_address(), &rlp);
+			self.topic_queries.insert((r.id.clone(), topic.clone()), expires);
+			trace!(target: "discovery", "Sent TopicQuery to {:?}", &r.endpoint);
+		}
+	}
+
+	// Check if the node answered our ping from the given address recently.
+	fn is_bonded(&self, node: &NodeId, from: &SocketAddr) -> bool {
+		let now = time::get_time().sec as u64;
+		self.bonds.get(node).map_or(false, |&(ref address, expires)| address == from && expires > now)
+	}
+
+	fn in_buckets(&self, id: &NodeId) -> bool {
+		let bucket = &self.node_buckets[Discovery::distance(&self.id_hash, &id.sha3()) as usize];
+		bucket.nodes.iter().any(|n| &n.address.id == id)
+	}
+
 	fn distance(a: &H256, b: &H256) -> u32 {
 		let d = *a ^ *b;
 		let mut ret:u32 = 0;
@@ -245,7 +356,7 @@ impl Discovery {
 
 	fn ping(&mut self, node: &NodeEndpoint) {
 		let mut rlp = RlpStream::new_list(3);
-		rlp.append(&PROTOCOL_VERSION);
+		rlp.append(&DISCOVERY_VERSION);
 		self.public_endpoint.to_rlp_list(&mut rlp);
 		node.to_rlp_list(&mut rlp);
 		trace!(target: "discovery", "Sent Ping to {:?}", &node);
@@ -260,7 +371,7 @@ impl Discovery {
 		for i in 0 .. source.item_count() {
 			rlp.append_raw(source.at(i).as_raw(), 1);
 		}
-		let timestamp = time::get_time().sec as u32 + 60;
+		let timestamp = time::get_time().sec as u32 + PACKET_EXPIRY_SEC as u32;
 		rlp.append(&timestamp);
 
 		let bytes = rlp.drain();
@@ -281,16 +392,19 @@ impl Discovery {
 		self.send_to(packet, address.clone());
 	}
 
-	#[cfg_attr(feature="dev", allow(map_clone))]
 	fn nearest_node_entries(target: &NodeId, buckets: &[NodeBucket]) -> Vec<NodeEntry> {
+		Discovery::nearest_hash_entries(&target.sha3(), buckets)
+	}
+
+	#[cfg_attr(feature="dev", allow(map_clone))]
+	fn nearest_hash_entries(target_hash: &H256, buckets: &[NodeBucket]) -> Vec<NodeEntry> {
 		let mut found: BTreeMap<u32, Vec<&NodeEntry>> = BTreeMap::new();
 		let mut count = 0;
-		let target_hash = target.sha3();
 
 		// Sort nodes by distance to target
 		for bucket in buckets {
 			for node in &bucket.nodes {
-				let distance = Discovery::distance(&target_hash, &node.id_hash);
+				let distance = Discovery::distance(target_hash, &node.id_hash);
 				found.entry(distance).or_insert_with(Vec::new).push(&node.address);
 				if count == BUCKET_SIZE {
 					// delete the most distant element
@@ -384,6 +498,9 @@ impl Discovery {
 			PACKET_PONG => self.on_pong(&rlp, &node_id, &from),
 			PACKET_FIND_NODE => self.on_find_node(&rlp, &node_id, &from),
 			PACKET_NEIGHBOURS => self.on_neighbours(&rlp, &node_id, &from),
+			PACKET_TOPIC_REGISTER => self.on_topic_register(&rlp, &node_id, &from),
+			PACKET_TOPIC_QUERY => self.on_topic_query(&rlp, &node_id, &from),
+			PACKET_TOPIC_NODES => self.on_topic_nodes(&rlp, &node_id, &from),
 			_ => {
 				debug!("Unknown UDP packet: {}", packet_id);
 				Ok(None)
@@ -416,6 +533,10 @@ impl Discovery {
 		} else if !self.is_allowed(&entry) {
 			debug!(target: "discovery", "Address not allowed: {:?}", entry);
 		} else {
+			// ping back unknown nodes, so that they can bond with us.
+			if !self.is_bonded(node, from) {
+				self.ping(&entry.endpoint);
+			}
 			self.update_node(entry.clone());
 			added_map.insert(node.clone(), entry);
 		}
@@ -440,6 +561,10 @@ impl Discovery {
 			entry.endpoint.address = from.clone();
 		}
 		self.clear_ping(node);
+		// only nodes we pinged are bonded, which keeps the bonds bounded by the node table.
+		if self.in_buckets(node) {
+			self.bonds.insert(node.clone(), (from.clone(), time::get_time().sec as u64 + BOND_EXPIRY_SEC));
+		}
 		let mut added_map = HashMap::new();
 		added_map.insert(node.clone(), entry);
 		Ok(None)
@@ -504,6 +629,135 @@ impl Discovery {
 		Ok(Some(TableUpdates { added: added, removed: HashSet::new() }))
 	}
 
+	fn on_topic_register(&mut self, rlp: &UntrustedRlp, node: &NodeId, from: &SocketAddr) -> Result<Option<TableUpdates>, NetworkError> {
+		trace!(target: "discovery", "Got TopicRegister from {:?}", &from);
+		let source = NodeEndpoint::from_rlp(&rlp.at(0)?)?;
+		let topic: Topic = rlp.val_at(1)?;
+		let timestamp: u64 = rlp.val_at(2)?;
+		self.check_timestamp(timestamp)?;
+		if topic.len() > MAX_TOPIC_LEN {
+			return Err(NetworkError::BadProtocol);
+		}
+		if !self.is_bonded(node, from) {
+			debug!(target: "discovery", "Topic registration from unbonded node {:?}", &from);
+			return Ok(None);
+		}
+		// the advertised address must be the one the registration came from.
+		let mut address = from.clone();
+		address.set_port(source.address.port());
+		let entry = NodeEntry { id: node.clone(), endpoint: NodeEndpoint { address: address, udp_port: from.port() } };
+		if !entry.endpoint.is_valid() || !self.is_allowed(&entry) {
+			debug!(target: "discovery", "Rejected topic registration: {:?}", entry);
+			return Ok(None);
+		}
+		let expires = time::get_time().sec as u64 + TOPIC_AD_LIFETIME_SEC;
+		let total_ads: usize = self.topic_ads.values().map(|ads| ads.len()).sum();
+		if !self.topic_ads.contains_key(&topic) && self.topic_ads.len() >= MAX_TOPICS {
+			debug!(target: "discovery", "Too many topics, rejected registration from {:?}", &from);
+			return Ok(None);
+		}
+		let ads = self.topic_ads.entry(topic).or_insert_with(HashMap::new);
+		if ads.contains_key(node) || (ads.len() < MAX_TOPIC_ADS && total_ads < MAX_TOTAL_TOPIC_ADS) {
+			ads.insert(node.clone(), TopicAd { entry: entry, expires: expires });
+		}
+		Ok(None)
+	}
+
+	fn on_topic_query(&mut self, rlp: &UntrustedRlp, node: &NodeId, from: &SocketAddr) -> Result<Option<TableUpdates>, NetworkError> {
+		trace!(target: "discovery", "Got TopicQuery from {:?}", &from);
+		let topic: Topic = rlp.val_at(0)?;
+		let timestamp: u64 = rlp.val_at(1)?;
+		self.check_timestamp(timestamp)?;
+		if !self.is_bonded(node, from) {
+			debug!(target: "discovery", "Topic query from unbonded node {:?}", &from);
+			return Ok(None);
+		}
+		let nodes: Vec<NodeEntry> = match self.topic_ads.get(&topic) {
+			Some(ads) => ads.values().map(|ad| ad.entry.clone()).collect(),
+			None => return Ok(None),
+		};
+		let packet = Discovery::prepare_topic_nodes_packet(&topic, &nodes);
+		self.send_packet(PACKET_TOPIC_NODES, from, &packet);
+		trace!(target: "discovery", "Sent TopicNodes to {:?}", &from);
+		Ok(None)
+	}
+
+	// A single reply with at most `BUCKET_SIZE` nodes, which fits into one datagram.
+	fn prepare_topic_nodes_packet(topic: &[u8], nodes: &[NodeEntry]) -> Bytes {
+		let limit = cmp::min(BUCKET_SIZE, (MAX_DATAGRAM_SIZE - 109 - MAX_TOPIC_LEN) / 90);
+		let nodes = &nodes[..cmp::min(limit, nodes.len())];
+		let mut rlp = RlpStream::new_list(2);
+		rlp.append(&topic);
+		rlp.begin_list(nodes.len());
+		for n in nodes {
+			rlp.begin_list(4);
+			n.endpoint.to_rlp(&mut rlp);
+			rlp.append(&n.id);
+		}
+		rlp.out()
+	}
+
+	fn on_topic_nodes(&mut self, rlp: &UntrustedRlp, node: &NodeId, from: &SocketAddr) -> Result<Option<TableUpdates>, NetworkError> {
+		let topic: Topic = rlp.val_at(0)?;
+		let timestamp: u64 = rlp.val_at(2)?;
+		self.check_timestamp(timestamp)?;
+		let now = time::get_time().sec as u64;
+		let solicited = match self.topic_queries.remove(&(node.clone(), topic.clone())) {
+			Some(expires) => expires > now && self.topic_searches.contains_key(&topic),
+			None => false,
+		};
+		if !solicited {
+			debug!(target: "discovery", "Unexpected TopicNodes from {:?}", &from);
+			return Ok(None);
+		}
+		trace!(target: "discovery", "Got {} TopicNodes from {:?}", rlp.at(1)?.item_count(), &from);
+		let mut added = HashMap::new();
+		for r in rlp.at(1)?.iter().take(BUCKET_SIZE) {
+			let endpoint = NodeEndpoint::from_rlp(&r)?;
+			if !endpoint.is_valid() {
+				debug!(target: "discovery", "Bad address: {:?}", endpoint);
+				continue;
+			}
+			let node_id: NodeId = r.val_at(3)?;
+			let entry = NodeEntry { id: node_id.clone(), endpoint: endpoint };
+			if !self.is_allowed(&entry) {
+				continue;
+			}
+			added.insert(node_id, entry);
+		}
+		for entry in added.values() {
+			self.ping(&entry.endpoint);
+			self.update_node(entry.clone());
+		}
+		if let Some(found) = self.topic_searches.get_mut(&topic) {
+			found.extend(added.iter().map(|(id, entry)| (id.clone(), entry.clone())));
+		}
+		Ok(Some(TableUpdates { added: added, removed: HashSet::new() }))
+	}
+
+	fn expire_topic_ads(&mut self) {
+		let now = time::get_time().sec as u64;
+		let mut empty = Vec::new();
+		for (topic, ads) in &mut self.topic_ads {
+			let expired: Vec<NodeId> = ads.iter().filter(|&(_, ad)| ad.expires <= now).map(|(id, _)| id.clone()).collect();
+			for id in expired {
+				ads.remove(&id);
+			}
+			if ads.is_empty() {
+				empty.push(topic.clone());
+			}
+		}
+		for topic in empty {
+			self.topic_ads.remove(&topic);
+		}
+		self.topic_queries.retain(|_, expires| *expires > now);
+	}
+
+	fn expire_bonds(&mut self) {
+		let now = time::get_time().sec as u64;
+		self.bonds.retain(|_, &mut (_, expires)| expires > now);
+	}
+
 	fn check_expired(&mut self, force: bool) -> HashSet<NodeId> {
 		let now = time::precise_time_ns();
 		let mut removed: HashSet<NodeId> = HashSet::new();
@@ -521,11 +775,16 @@ impl Discovery {
 				} else { true }
 			});
 		}
+		for id in &removed {
+			self.bonds.remove(id);
+		}
 		removed
 	}
 
 	pub fn round(&mut self) -> Option<TableUpdates> {
 		let removed = self.check_expired(false);
+		self.expire_topic_ads();
+		self.expire_bonds();
 		self.discover();
 		if !removed.is_empty() {
 			Some(TableUpdates { added: HashMap::new(), removed: removed })
@@ -534,10 +793,18 @@ impl Discovery {
 
 	pub fn refresh(&mut self) {
 		self.start();
+		let topics: Vec<Topic> = self.topics.iter().cloned().collect();
+		for topic in &topics {
+			self.advertise(topic);
+		}
+		let searches: Vec<Topic> = self.topic_searches.keys().cloned().collect();
+		for topic in &searches {
+			self.query(topic);
+		}
 	}
 
 	pub fn register_socket<Host:Handler>(&self, event_loop: &mut EventLoop<Host>) -> Result<(), NetworkError> {
-		event_loop.register(&self.udp_socket, Token(self.token), Ready::all(), PollOpt::edge()).expect("Error registering UDP socket");
+		event_loop.register(&*self.udp_socket, Token(self.token), Ready::all(), PollOpt::edge()).expect("Error registering UDP socket");
 		Ok(())
 	}
 
@@ -547,7 +814,7 @@ impl Discovery {
 		} else {
 			Ready::readable()
 		};
-		event_loop.reregister(&self.udp_socket, Token(self.token), registration, PollOpt::edge()).expect("Error reregistering UDP socket");
+		event_loop.reregister(&*self.udp_socket, Token(self.token), registration, PollOpt::edge()).expect("Error reregistering UDP socket");
 		Ok(())
 	}
 }
@@ -555,7 +822,10 @@ impl Discovery {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use std::mem;
+	use std::collections::{HashMap, VecDeque};
 	use std::net::{SocketAddr};
+	use rlp::{UntrustedRlp, View};
 	use util::sha3::Hashable;
 	use util::FixedHash;
 	use node_table::{Node, NodeId, NodeEndpoint};
@@ -617,6 +887,112 @@ mod tests {
 		assert_eq!(Discovery::nearest_node_entries(&NodeId::new(), &discovery2.node_buckets).len(), 3)
 	}
 
+	fn deliver(from: &mut Discovery, from_address: &SocketAddr, to: &mut Discovery, to_address: &SocketAddr) -> Vec<TableUpdates> {
+		let mut updates = Vec::new();
+		let mut queue = mem::replace(&mut from.send_queue, VecDeque::new());
+		for datagramm in queue.drain(..) {
+			if datagramm.address == *to_address {
+				if let Ok(Some(update)) = to.on_packet(&datagramm.payload, from_address.clone()) {
+					updates.push(update);
+				}
+			} else {
+				from.send_queue.push_back(datagramm);
+			}
+		}
+		updates
+	}
+
+	fn bond(a: &mut Discovery, a_address: &SocketAddr, b: &mut Discovery, b_address: &SocketAddr) {
+		for _ in 0..2 {
+			deliver(a, a_address, b, b_address);
+			deliver(b, b_address, a, a_address);
+		}
+	}
+
+	#[test]
+	fn topic_nodes_fit_into_one_packet() {
+		let node = Node::from_str("enode://a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c@127.0.0.1:7770").unwrap();
+		let nodes: Vec<_> = (0..MAX_TOPIC_ADS).map(|_| NodeEntry { id: node.id.clone(), endpoint: node.endpoint.clone() }).collect();
+		let packet = Discovery::prepare_topic_nodes_packet(&[0u8; MAX_TOPIC_LEN], &nodes);
+		assert!(packet.len() + 109 <= MAX_DATAGRAM_SIZE);
+		let count = UntrustedRlp::new(&packet).at(1).unwrap().item_count();
+		assert!(count > 0 && count <= BUCKET_SIZE);
+	}
+
+	#[test]
+	fn topics() {
+		let (key_r, key_a, key_s) = (Random.generate().unwrap(), Random.generate().unwrap(), Random.generate().unwrap());
+		let ep_r = NodeEndpoint { address: SocketAddr::from_str("127.0.0.1:40450").unwrap(), udp_port: 40450 };
+		let ep_a = NodeEndpoint { address: SocketAddr::from_str("127.0.0.1:40451").unwrap(), udp_port: 40451 };
+		let ep_s = NodeEndpoint { address: SocketAddr::from_str("127.0.0.1:40452").unwrap(), udp_port: 40452 };
+		let mut registrar = Discovery::new(&key_r, ep_r.address.clone(), ep_r.clone(), 0, AllowIP::All);
+		let mut advertiser = Discovery::new(&key_a, ep_a.address.clone(), ep_a.clone(), 0, AllowIP::All);
+		let mut searcher = Discovery::new(&key_s, ep_s.address.clone(), ep_s.clone(), 0, AllowIP::All);
+
+		advertiser.add_node(NodeEntry { id: key_r.public().clone(), endpoint: ep_r.clone() });
+		searcher.add_node(NodeEntry { id: key_r.public().clone(), endpoint: ep_r.clone() });
+
+		// registrations and queries of nodes which did not answer a ping are ignored.
+		advertiser.register_topic(b"les".to_vec());
+		deliver(&mut advertiser, &ep_a.address, &mut registrar, &ep_r.address);
+		assert!(registrar.topic_ads.is_empty());
+
+		bond(&mut advertiser, &ep_a.address, &mut registrar, &ep_r.address);
+		bond(&mut searcher, &ep_s.address, &mut registrar, &ep_r.address);
+		advertiser.refresh();
+		deliver(&mut advertiser, &ep_a.address, &mut registrar, &ep_r.address);
+		assert_eq!(registrar.topic_ads.get(&b"les".to_vec()).map(|ads| ads.len()), Some(1));
+
+		// topics nobody advertises find nothing.
+		searcher.search_topic(b"pip".to_vec());
+		deliver(&mut searcher, &ep_s.address, &mut registrar, &ep_r.address);
+		deliver(&mut registrar, &ep_r.address, &mut searcher, &ep_s.address);
+		assert!(searcher.topic_nodes(b"pip").is_empty());
+
+		searcher.search_topic(b"les".to_vec());
+		deliver(&mut searcher, &ep_s.address, &mut registrar, &ep_r.address);
+		let updates = deliver(&mut registrar, &ep_r.address, &mut searcher, &ep_s.address);
+		let found = searcher.topic_nodes(b"les");
+		assert_eq!(found.len(), 1);
+		assert_eq!(found[0].id, *key_a.public());
+		assert_eq!(found[0].endpoint.address, ep_a.address);
+		assert!(updates.iter().any(|u| u.added.contains_key(key_a.public())));
+		assert!(searcher.topic_nodes(b"eth").is_empty());
+
+		// replies are accepted once per query.
+		searcher.topic_searches.insert(b"les".to_vec(), HashMap::new());
+		let reply = Discovery::prepare_topic_nodes_packet(b"les", &found);
+		registrar.send_packet(PACKET_TOPIC_NODES, &ep_s.address, &reply);
+		deliver(&mut registrar, &ep_r.address, &mut searcher, &ep_s.address);
+		assert!(searcher.topic_nodes(b"les").is_empty());
+
+		// advertisements expire.
+		for ad in registrar.topic_ads.get_mut(&b"les".to_vec()).unwrap().values_mut() {
+			ad.expires = 0;
+		}
+		registrar.round();
+		assert!(registrar.topic_ads.is_empty());
+	}
+
+	#[test]
+	fn advertises_new_public_endpoint() {
+		let (key1, key2) = (Random.generate().unwrap(), Random.generate().unwrap());
+		let ep1 = NodeEndpoint { address: SocketAddr::from_str("127.0.0.1:40453").unwrap(), udp_port: 40453 };
+		let ep2 = NodeEndpoint { address: SocketAddr::from_str("127.0.0.1:40454").unwrap(), udp_port: 40454 };
+		let mut discovery1 = Discovery::new(&key1, ep1.address.clone(), ep1.clone(), 0, AllowIP::All);
+		let mut discovery2 = Discovery::new(&key2, ep2.address.clone(), ep2.clone(), 0, AllowIP::All);
+		discovery1.add_node(NodeEntry { id: key2.public().clone(), endpoint: ep2.clone() });
+		discovery1.send_queue.clear();
+
+		let mapped = NodeEndpoint { address: SocketAddr::from_str("1.2.3.4:30303").unwrap(), udp_port: 30301 };
+		discovery1.set_public_endpoint(mapped);
+		assert_eq!(discovery1.send_queue.len(), 1);
+		let updates = deliver(&mut discovery1, &ep1.address, &mut discovery2, &ep2.address);
+		let added: Vec<_> = updates.iter().filter_map(|u| u.added.get(key1.public())).collect();
+		assert_eq!(added.len(), 1);
+		assert_eq!(added[0].endpoint.address, SocketAddr::from_str("1.2.3.4:30303").unwrap());
+	}
+
 	#[test]
 	fn removes_expired() {
 		let key = Random.generate().unwrap();
@@ -0,0 +1,302 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Ethereum Node Records (EIP-778).
+//!
+//! A record is the RLP list `[signature, seq, k, v, ...]` with unique keys in ascending
+//! order. The "v4" identity scheme signs the hash of `[seq, k, v, ...]` with the node key
+//! and keeps the compressed public key under `secp256k1`. The text form is `enr:`
+//! followed by the URL-safe base64 encoding of the record without padding.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use ethkey::{KeyPair, Signature, sign, recover};
+use rlp::*;
+use rustc_serialize::base64::{ToBase64, FromBase64, URL_SAFE};
+use util::bytes::Bytes;
+use util::hash::*;
+use util::sha3::Hashable;
+use error::NetworkError;
+use node_table::{NodeId, NodeEndpoint};
+
+/// Prefix of the text form of a record.
+pub const ENR_PREFIX: &'static str = "enr:";
+
+const MAX_RECORD_SIZE: usize = 300;
+const ID_SCHEME: &'static [u8] = b"v4";
+
+/// Signed and versioned node record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enr {
+	seq: u64,
+	// values are kept as raw RLP items.
+	pairs: BTreeMap<Bytes, Bytes>,
+	signature: Bytes,
+	id: NodeId,
+}
+
+impl Enr {
+	/// Create a signed record of the local node. `entries` are additional pairs
+	/// with RLP-encoded values, e.g. the chain id.
+	pub fn new(keys: &KeyPair, seq: u64, endpoint: &NodeEndpoint, entries: &BTreeMap<String, Bytes>) -> Result<Enr, NetworkError> {
+		let mut pairs = BTreeMap::new();
+		for (key, value) in entries {
+			match UntrustedRlp::new(value).payload_info() {
+				Ok(ref info) if info.header_len + info.value_len == value.len() => {},
+				_ => return Err(NetworkError::InvalidNodeRecord),
+			}
+			pairs.insert(key.as_bytes().to_vec(), value.clone());
+		}
+
+		pairs.insert(b"id".to_vec(), encode(&ID_SCHEME).to_vec());
+		pairs.insert(b"secp256k1".to_vec(), encode(&compress(keys.public())).to_vec());
+		match endpoint.address.ip() {
+			IpAddr::V4(ip) => pairs.insert(b"ip".to_vec(), encode(&&ip.octets()[..]).to_vec()),
+			IpAddr::V6(ip) => pairs.insert(b"ip6".to_vec(), encode(&&ip.octets()[..]).to_vec()),
+		};
+		pairs.insert(b"tcp".to_vec(), encode(&endpoint.address.port()).to_vec());
+		pairs.insert(b"udp".to_vec(), encode(&endpoint.udp_port).to_vec());
+
+		let mut enr = Enr {
+			seq: seq,
+			pairs: pairs,
+			signature: Bytes::new(),
+			id: keys.public().clone(),
+		};
+		let signature = sign(keys.secret(), &enr.content().sha3())?;
+		enr.signature = signature[0..64].to_vec();
+		if enr.rlp_bytes().len() > MAX_RECORD_SIZE {
+			return Err(NetworkError::InvalidNodeRecord);
+		}
+		Ok(enr)
+	}
+
+	/// Decode a record and check its signature.
+	pub fn from_rlp(data: &[u8]) -> Result<Enr, NetworkError> {
+		if data.len() > MAX_RECORD_SIZE {
+			return Err(NetworkError::InvalidNodeRecord);
+		}
+		let rlp = UntrustedRlp::new(data);
+		match rlp.payload_info() {
+			Ok(ref info) if info.header_len + info.value_len == data.len() => {},
+			_ => return Err(NetworkError::InvalidNodeRecord),
+		}
+		let enr = Enr::decode_unverified(&rlp).map_err(|_| NetworkError::InvalidNodeRecord)?;
+		enr.verified()
+	}
+
+	fn decode_unverified(rlp: &UntrustedRlp) -> Result<Enr, DecoderError> {
+		let count = rlp.item_count();
+		if count < 2 || count % 2 != 0 {
+			return Err(DecoderError::RlpIncorrectListLen);
+		}
+		let mut pairs = BTreeMap::new();
+		for i in 1..count / 2 {
+			let key: Bytes = rlp.val_at(i * 2)?;
+			if pairs.keys().next_back().map_or(false, |last| *last >= key) {
+				return Err(DecoderError::Custom("Keys are not sorted"));
+			}
+			pairs.insert(key, rlp.at(i * 2 + 1)?.as_raw().to_vec());
+		}
+		Ok(Enr {
+			seq: rlp.val_at(1)?,
+			pairs: pairs,
+			signature: rlp.val_at(0)?,
+			id: NodeId::new(),
+		})
+	}
+
+	// recovers the node id and checks it against the public key of the record.
+	fn verified(mut self) -> Result<Enr, NetworkError> {
+		if self.get(b"id") != Some(encode(&ID_SCHEME).to_vec()) || self.signature.len() != 64 {
+			return Err(NetworkError::InvalidNodeRecord);
+		}
+		let public: Bytes = match self.value(b"secp256k1") {
+			Some(public) => public,
+			None => return Err(NetworkError::InvalidNodeRecord),
+		};
+		let hash = self.content().sha3();
+		let (r, s) = (H256::from_slice(&self.signature[0..32]), H256::from_slice(&self.signature[32..64]));
+		for v in 0..2 {
+			if let Ok(id) = recover(&Signature::from_rsv(&r, &s, v), &hash) {
+				if compress(&id) == public {
+					self.id = id;
+					return Ok(self);
+				}
+			}
+		}
+		Err(NetworkError::InvalidNodeRecord)
+	}
+
+	// the signed part of the record.
+	fn content(&self) -> Bytes {
+		let mut rlp = RlpStream::new_list(1 + self.pairs.len() * 2);
+		rlp.append(&self.seq);
+		for (key, value) in &self.pairs {
+			rlp.append(key);
+			rlp.append_raw(value, 1);
+		}
+		rlp.out()
+	}
+
+	/// Encoded record.
+	pub fn rlp_bytes(&self) -> Bytes {
+		let mut rlp = RlpStream::new_list(2 + self.pairs.len() * 2);
+		rlp.append(&self.signature);
+		rlp.append(&self.seq);
+		for (key, value) in &self.pairs {
+			rlp.append(key);
+			rlp.append_raw(value, 1);
+		}
+		rlp.out()
+	}
+
+	/// Sequence number of the record. Higher numbers replace lower ones.
+	pub fn seq(&self) -> u64 {
+		self.seq
+	}
+
+	/// Id of the node the record belongs to.
+	pub fn id(&self) -> &NodeId {
+		&self.id
+	}
+
+	/// Raw RLP value of the given key.
+	pub fn get(&self, key: &[u8]) -> Option<Bytes> {
+		self.pairs.get(key).cloned()
+	}
+
+	/// Decoded value of the given key. `None` if missing or not decodable.
+	pub fn value<T: RlpDecodable>(&self, key: &[u8]) -> Option<T> {
+		self.pairs.get(key).and_then(|value| UntrustedRlp::new(value).as_val().ok())
+	}
+
+	/// TCP endpoint of the node, with the UDP port defaulting to the TCP one.
+	/// `None` if the record has no address.
+	pub fn endpoint(&self) -> Option<NodeEndpoint> {
+		let ip = match (self.value::<Bytes>(b"ip"), self.value::<Bytes>(b"ip6")) {
+			(Some(ref ip), _) if ip.len() == 4 => IpAddr::V4(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])),
+			(_, Some(ref ip)) if ip.len() == 16 => {
+				let mut octets = [0u8; 16];
+				octets.copy_from_slice(ip);
+				IpAddr::V6(Ipv6Addr::from(octets))
+			},
+			_ => return None,
+		};
+		let tcp: u16 = match self.value(b"tcp") {
+			Some(port) => port,
+			None => return None,
+		};
+		Some(NodeEndpoint {
+			address: SocketAddr::new(ip, tcp),
+			udp_port: self.value(b"udp").unwrap_or(tcp),
+		})
+	}
+}
+
+impl fmt::Display for Enr {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}{}", ENR_PREFIX, self.rlp_bytes().to_base64(URL_SAFE))
+	}
+}
+
+impl FromStr for Enr {
+	type Err = NetworkError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if !s.starts_with(ENR_PREFIX) {
+			return Err(NetworkError::InvalidNodeRecord);
+		}
+		let data = s[ENR_PREFIX.len()..].from_base64().map_err(|_| NetworkError::InvalidNodeRecord)?;
+		Enr::from_rlp(&data)
+	}
+}
+
+// compressed secp256k1 public key.
+fn compress(public: &NodeId) -> Bytes {
+	let mut compressed = Vec::with_capacity(33);
+	compressed.push(2 + (public[63] & 1));
+	compressed.extend_from_slice(&public[0..32]);
+	compressed
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::BTreeMap;
+	use std::net::SocketAddr;
+	use std::str::FromStr;
+	use ethkey::{Random, Generator, Secret};
+	use rlp::encode;
+	use node_table::NodeEndpoint;
+
+	fn endpoint() -> NodeEndpoint {
+		NodeEndpoint { address: SocketAddr::from_str("127.0.0.1:30303").unwrap(), udp_port: 30301 }
+	}
+
+	#[test]
+	fn signs_and_verifies_records() {
+		let keys = Random.generate().unwrap();
+		let mut entries = BTreeMap::new();
+		entries.insert("chain".to_owned(), encode(&1u64).to_vec());
+		let enr = Enr::new(&keys, 7, &endpoint(), &entries).unwrap();
+
+		let parsed: Enr = enr.to_string().parse().unwrap();
+		assert!(enr.to_string().starts_with("enr:"));
+		assert_eq!(parsed, enr);
+		assert_eq!(parsed.id(), keys.public());
+		assert_eq!(parsed.seq(), 7);
+		assert_eq!(parsed.value::<u64>(b"chain"), Some(1));
+		assert_eq!(parsed.endpoint().unwrap().address, endpoint().address);
+		assert_eq!(parsed.endpoint().unwrap().udp_port, 30301);
+	}
+
+	#[test]
+	fn rejects_tampered_records() {
+		let keys = Random.generate().unwrap();
+		let enr = Enr::new(&keys, 1, &endpoint(), &BTreeMap::new()).unwrap();
+
+		let mut tampered = enr.clone();
+		tampered.seq = 2;
+		assert!(Enr::from_rlp(&tampered.rlp_bytes()).is_err());
+
+		let mut data = enr.rlp_bytes();
+		data.push(0);
+		assert!(Enr::from_rlp(&data).is_err());
+
+		assert!(Enr::from_str("enode://1234").is_err());
+		assert!(Enr::from_str("enr:%%%").is_err());
+
+		let mut entries = BTreeMap::new();
+		entries.insert("big".to_owned(), encode(&vec![0u8; 300]).to_vec());
+		assert!(Enr::new(&keys, 1, &endpoint(), &entries).is_err());
+	}
+
+	#[test]
+	fn decodes_eip_example() {
+		// example record of EIP-778.
+		let enr: Enr = "enr:-IS4QHCYrYZbAKWCBRlAy5zzaDZXJBGkcnh4MHcBFZntXNFrdvJjX04jRzjzCBOonrkTfj499SZuOh8R33Ls8RRcy5wBgmlkgnY0gmlwhH8AAAGJc2VjcDI1NmsxoQPKY0yuDUmstAHYpMa2_oxVtw0RW_QAdpzBQA8yWM0xOIN1ZHCCdl8".parse().unwrap();
+		let secret = Secret::from_str("b71c71a67e1177ad4e901695e1b4b9ee17ae16c6668d313eac2f96dbcda3f291").unwrap();
+		let keys = KeyPair::from_secret(secret).unwrap();
+		assert_eq!(enr.id(), keys.public());
+		assert_eq!(enr.seq(), 1);
+		let endpoint = enr.endpoint();
+		assert!(endpoint.is_none());
+		assert_eq!(enr.value::<u16>(b"udp"), Some(30303));
+	}
+}
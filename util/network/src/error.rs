@@ -106,6 +106,12 @@ pub enum NetworkError {
 	AddressResolve(Option<::std::io::Error>),
 	/// Error concerning the Rust standard library's IO subsystem.
 	StdIo(::std::io::Error),
+	/// Malformed or badly signed node record.
+	InvalidNodeRecord,
+	/// Peer group does not exist.
+	UnknownPeerGroup,
+	/// Peer group name is empty or its minimum slots exceed the maximum.
+	InvalidPeerGroup,
 }
 
 impl fmt::Display for NetworkError {
@@ -123,6 +129,9 @@ impl fmt::Display for NetworkError {
 			AddressResolve(Some(ref err)) => format!("{}", err),
 			AddressResolve(_) => "Failed to resolve network address.".into(),
 			StdIo(ref err) => format!("{}", err),
+			InvalidNodeRecord => "Invalid node record".into(),
+			UnknownPeerGroup => "Unknown peer group".into(),
+			InvalidPeerGroup => "Invalid peer group".into(),
 			Util(ref err) => format!("{}", err),
 		};
 
@@ -0,0 +1,316 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Local gateway speaking NAT-PMP, PCP and UPnP IGD control, for port mapping tests.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use parking_lot::Mutex;
+
+const TCP: u8 = 6;
+const UDP: u8 = 17;
+const FIRST_FREE_PORT: u16 = 40000;
+
+struct State {
+	external_ip: Ipv4Addr,
+	pcp: bool,
+	/// Internal ports by protocol and external port. Internal port 0 marks a port taken by another host.
+	mappings: HashMap<(u8, u16), u16>,
+}
+
+impl State {
+	fn is_free(&self, protocol: u8, external: u16, internal: u16) -> bool {
+		self.mappings.get(&(protocol, external)).map_or(true, |i| *i == internal)
+	}
+
+	fn map(&mut self, protocol: u8, internal: u16, suggested: u16) -> u16 {
+		let existing = self.mappings.iter().find(|&(k, v)| k.0 == protocol && *v == internal).map(|(k, _)| k.1);
+		let port = if suggested != 0 && self.is_free(protocol, suggested, internal) {
+			suggested
+		} else if let Some(port) = existing {
+			port
+		} else if self.is_free(protocol, internal, internal) {
+			internal
+		} else {
+			(FIRST_FREE_PORT..).find(|p| self.is_free(protocol, *p, internal)).expect("there are free ports")
+		};
+		self.mappings.insert((protocol, port), internal);
+		port
+	}
+
+	fn unmap_internal(&mut self, protocol: u8, internal: u16) {
+		let ports: Vec<u16> = self.mappings.iter().filter(|&(k, v)| k.0 == protocol && *v == internal).map(|(k, _)| k.1).collect();
+		for port in ports {
+			self.mappings.remove(&(protocol, port));
+		}
+	}
+}
+
+/// Gateway serving NAT-PMP and PCP over UDP and UPnP control requests over HTTP on localhost.
+pub struct FakeGateway {
+	state: Arc<Mutex<State>>,
+	stop: Arc<AtomicBool>,
+	pmp_address: SocketAddrV4,
+	upnp_address: SocketAddrV4,
+	threads: Vec<JoinHandle<()>>,
+}
+
+impl FakeGateway {
+	/// Start a gateway with external address 1.2.3.4. NAT-PMP requests are always served,
+	/// PCP requests only if `pcp` is set.
+	pub fn new(pcp: bool) -> FakeGateway {
+		let state = Arc::new(Mutex::new(State {
+			external_ip: Ipv4Addr::new(1, 2, 3, 4),
+			pcp: pcp,
+			mappings: HashMap::new(),
+		}));
+		let stop = Arc::new(AtomicBool::new(false));
+
+		let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+		udp.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+		let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+		tcp.set_nonblocking(true).unwrap();
+		let pmp_address = v4(udp.local_addr().unwrap());
+		let upnp_address = v4(tcp.local_addr().unwrap());
+
+		let pmp_thread = {
+			let (state, stop) = (state.clone(), stop.clone());
+			thread::spawn(move || {
+				let mut buf = [0u8; 1100];
+				while !stop.load(Ordering::SeqCst) {
+					if let Ok((size, from)) = udp.recv_from(&mut buf) {
+						if let Some(response) = handle_pmp(&mut state.lock(), &buf[..size]) {
+							udp.send_to(&response, from).unwrap();
+						}
+					}
+				}
+			})
+		};
+		let upnp_thread = {
+			let (state, stop) = (state.clone(), stop.clone());
+			thread::spawn(move || {
+				while !stop.load(Ordering::SeqCst) {
+					match tcp.accept() {
+						Ok((stream, _)) => handle_upnp(&state, stream).unwrap(),
+						Err(_) => thread::sleep(Duration::from_millis(10)),
+					}
+				}
+			})
+		};
+
+		FakeGateway {
+			state: state,
+			stop: stop,
+			pmp_address: pmp_address,
+			upnp_address: upnp_address,
+			threads: vec![pmp_thread, upnp_thread],
+		}
+	}
+
+	/// Address of the NAT-PMP and PCP server.
+	pub fn pmp_address(&self) -> SocketAddrV4 {
+		self.pmp_address
+	}
+
+	/// Address of the UPnP control server.
+	pub fn upnp_address(&self) -> SocketAddrV4 {
+		self.upnp_address
+	}
+
+	/// Change the external address, as after reconnecting to the ISP.
+	pub fn set_external_ip(&self, ip: Ipv4Addr) {
+		self.state.lock().external_ip = ip;
+	}
+
+	/// Mark a TCP port as taken by another host.
+	pub fn reserve_port(&self, port: u16) {
+		self.state.lock().mappings.insert((TCP, port), 0);
+	}
+
+	/// Get the current mappings as `(protocol, external port)`.
+	pub fn mappings(&self) -> Vec<(u8, u16)> {
+		self.state.lock().mappings.keys().cloned().collect()
+	}
+}
+
+impl Drop for FakeGateway {
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::SeqCst);
+		for thread in self.threads.drain(..) {
+			thread.join().unwrap();
+		}
+	}
+}
+
+fn v4(address: SocketAddr) -> SocketAddrV4 {
+	match address {
+		SocketAddr::V4(a) => a,
+		SocketAddr::V6(_) => panic!("bound to an IPv4 address"),
+	}
+}
+
+fn get_u16(buf: &[u8]) -> u16 {
+	(buf[0] as u16) << 8 | buf[1] as u16
+}
+
+fn get_u32(buf: &[u8]) -> u32 {
+	(get_u16(buf) as u32) << 16 | get_u16(&buf[2..]) as u32
+}
+
+fn push_u16(buf: &mut Vec<u8>, value: u16) {
+	buf.push((value >> 8) as u8);
+	buf.push(value as u8);
+}
+
+fn push_u32(buf: &mut Vec<u8>, value: u32) {
+	push_u16(buf, (value >> 16) as u16);
+	push_u16(buf, value as u16);
+}
+
+fn handle_pmp(state: &mut State, request: &[u8]) -> Option<Vec<u8>> {
+	if request.len() < 2 {
+		return None;
+	}
+	let (version, opcode) = (request[0], request[1]);
+	let mut response = Vec::new();
+	match (version, opcode) {
+		(0, 0) => {
+			response.extend_from_slice(&[0, 128, 0, 0]);
+			push_u32(&mut response, 0);
+			response.extend_from_slice(&state.external_ip.octets());
+		},
+		(0, 1) | (0, 2) if request.len() >= 12 => {
+			let protocol = if opcode == 1 { UDP } else { TCP };
+			let (internal, suggested, lifetime) = (get_u16(&request[4..]), get_u16(&request[6..]), get_u32(&request[8..]));
+			let external = if lifetime == 0 {
+				state.unmap_internal(protocol, internal);
+				0
+			} else {
+				state.map(protocol, internal, suggested)
+			};
+			response.extend_from_slice(&[0, 128 + opcode, 0, 0]);
+			push_u32(&mut response, 0);
+			push_u16(&mut response, internal);
+			push_u16(&mut response, external);
+			push_u32(&mut response, lifetime);
+		},
+		(2, 1) if state.pcp && request.len() >= 60 => {
+			let lifetime = get_u32(&request[4..]);
+			let protocol = request[36];
+			let (internal, suggested) = (get_u16(&request[40..]), get_u16(&request[42..]));
+			let external = if lifetime == 0 {
+				state.unmap_internal(protocol, internal);
+				0
+			} else {
+				state.map(protocol, internal, suggested)
+			};
+			response.extend_from_slice(&[2, 128 + opcode, 0, 0]);
+			push_u32(&mut response, lifetime);
+			push_u32(&mut response, 0);
+			response.extend_from_slice(&[0u8; 12]);
+			response.extend_from_slice(&request[24..40]);
+			push_u16(&mut response, internal);
+			push_u16(&mut response, external);
+			response.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff]);
+			response.extend_from_slice(&state.external_ip.octets());
+		},
+		_ => {
+			// unsupported version or opcode, answered the NAT-PMP way.
+			response.extend_from_slice(&[0, 128 + opcode, 0, if version == 0 { 5 } else { 1 }]);
+			push_u32(&mut response, 0);
+		},
+	}
+	Some(response)
+}
+
+fn handle_upnp(state: &Mutex<State>, mut stream: TcpStream) -> io::Result<()> {
+	stream.set_nonblocking(false)?;
+	stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+	let mut request = Vec::new();
+	let mut buf = [0u8; 4096];
+	loop {
+		let size = stream.read(&mut buf)?;
+		if size == 0 {
+			break;
+		}
+		request.extend_from_slice(&buf[..size]);
+		let text = String::from_utf8_lossy(&request).into_owned();
+		if let Some(end) = text.find("\r\n\r\n") {
+			if text.len() >= end + 4 + header(&text[..end], "content-length").and_then(|l| l.parse().ok()).unwrap_or(0) {
+				break;
+			}
+		}
+	}
+	let request = String::from_utf8_lossy(&request).into_owned();
+	let action = header(&request, "soapaction")
+		.and_then(|a| a.trim_matches('"').split('#').nth(1).map(|a| a.to_owned()))
+		.unwrap_or_else(String::new);
+	let arg = |name: &str| -> String {
+		let (open, close) = (format!("<{}>", name), format!("</{}>", name));
+		request.find(&open)
+			.and_then(|start| request[start + open.len()..].find(&close).map(|len| request[start + open.len()..start + open.len() + len].to_owned()))
+			.unwrap_or_else(String::new)
+	};
+	let protocol = if arg("NewProtocol") == "UDP" { UDP } else { TCP };
+	let external: u16 = arg("NewExternalPort").parse().unwrap_or(0);
+	let internal: u16 = arg("NewInternalPort").parse().unwrap_or(0);
+
+	let mut state = state.lock();
+	let result = match action.as_str() {
+		"GetExternalIPAddress" => Ok(format!("<NewExternalIPAddress>{}</NewExternalIPAddress>", state.external_ip)),
+		"AddAnyPortMapping" => Ok(format!("<NewReservedPort>{}</NewReservedPort>", state.map(protocol, internal, external))),
+		"AddPortMapping" if state.is_free(protocol, external, internal) => {
+			state.mappings.insert((protocol, external), internal);
+			Ok(String::new())
+		},
+		"AddPortMapping" => Err((718, "ConflictInMappingEntry")),
+		"DeletePortMapping" => match state.mappings.remove(&(protocol, external)) {
+			Some(_) => Ok(String::new()),
+			None => Err((714, "NoSuchEntryInArray")),
+		},
+		_ => Err((401, "Invalid Action")),
+	};
+	let (status, body) = match result {
+		Ok(args) => ("200 OK", format!(
+			"<u:{0}Response xmlns:u=\"urn:schemas-upnp-org:service:WANIPConnection:1\">{1}</u:{0}Response>", action, args)),
+		Err((code, description)) => ("500 Internal Server Error", format!(
+			"<s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail>\
+			<UPnPError xmlns=\"urn:schemas-upnp-org:control-1-0\"><errorCode>{}</errorCode>\
+			<errorDescription>{}</errorDescription></UPnPError></detail></s:Fault>", code, description)),
+	};
+	let body = format!(
+		"<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+		s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body>{}</s:Body></s:Envelope>", body);
+	write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/xml; charset=\"utf-8\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)?;
+	stream.flush()
+}
+
+fn header(headers: &str, name: &str) -> Option<String> {
+	headers.lines()
+		.filter_map(|line| {
+			let mut parts = line.splitn(2, ':');
+			match (parts.next(), parts.next()) {
+				(Some(key), Some(value)) if key.trim().to_lowercase() == name => Some(value.trim().to_owned()),
+				_ => None,
+			}
+		})
+		.next()
+}
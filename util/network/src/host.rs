@@ -15,31 +15,37 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::net::{SocketAddr, SocketAddrV4, Ipv4Addr};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering as AtomicOrdering};
 use std::ops::*;
-use std::cmp::min;
+use std::cmp::{min, max};
 use std::path::{Path, PathBuf};
 use std::io::{Read, Write, ErrorKind};
 use std::fs;
+use std::thread;
 use ethkey::{KeyPair, Secret, Random, Generator};
 use mio::*;
 use mio::deprecated::{EventLoop};
-use mio::tcp::*;
 use util::hash::*;
 use util::Hashable;
 use util::version;
+use util::bytes::Bytes;
 use rlp::*;
 use session::{Session, SessionInfo, SessionData};
 use error::*;
 use io::*;
-use {NetworkProtocolHandler, NonReservedPeerMode, AllowIP, PROTOCOL_VERSION};
+use {NetworkProtocolHandler, NonReservedPeerMode, AllowIP, NatType, PROTOCOL_VERSION};
 use node_table::*;
 use stats::NetworkStats;
+use bandwidth::BandwidthLimiter;
 use discovery::{Discovery, TableUpdates, NodeEntry};
-use ip_utils::{map_external_address, select_public_address};
+use enr::Enr;
+use reputation::{PeerReport, Reputation};
+use peer_group::{Admission, PeerGroups, PeerGroupConfig, PeerGroupInfo};
+use transport::{Transport, TcpTransport, Listener, Stream};
+use ip_utils::{PortMapping, select_public_address};
 use util::path::restrict_permissions_owner;
 use parking_lot::{Mutex, RwLock};
 
@@ -57,6 +63,8 @@ const DISCOVERY: usize = SYS_TIMER + 3;
 const DISCOVERY_REFRESH: usize = SYS_TIMER + 4;
 const DISCOVERY_ROUND: usize = SYS_TIMER + 5;
 const NODE_TABLE: usize = SYS_TIMER + 6;
+const BANDWIDTH: usize = SYS_TIMER + 7;
+const NAT_RENEWAL: usize = SYS_TIMER + 8;
 const FIRST_SESSION: usize = 0;
 const LAST_SESSION: usize = FIRST_SESSION + MAX_SESSIONS - 1;
 const USER_TIMER: usize = LAST_SESSION + 256;
@@ -67,6 +75,7 @@ const MAINTENANCE_TIMEOUT: u64 = 1000;
 const DISCOVERY_REFRESH_TIMEOUT: u64 = 60_000;
 const DISCOVERY_ROUND_TIMEOUT: u64 = 300;
 const NODE_TABLE_TIMEOUT: u64 = 300_000;
+const BANDWIDTH_TIMEOUT: u64 = 100;
 
 #[derive(Debug, PartialEq, Clone)]
 /// Network service configuration
@@ -83,6 +92,8 @@ pub struct NetworkConfiguration {
 	pub udp_port: Option<u16>,
 	/// Enable NAT configuration
 	pub nat_enabled: bool,
+	/// Port mapping method used if NAT configuration is enabled
+	pub nat_type: NatType,
 	/// Enable discovery
 	pub discovery_enabled: bool,
 	/// List of initial node addresses
@@ -101,8 +112,16 @@ pub struct NetworkConfiguration {
 	pub reserved_nodes: Vec<String>,
 	/// The non-reserved peer mode.
 	pub non_reserved_mode: NonReservedPeerMode,
+	/// Static peer groups.
+	pub peer_groups: Vec<PeerGroupConfig>,
 	/// IP filter
 	pub allow_ips: AllowIP,
+	/// Additional entries of the local node record, with RLP-encoded values.
+	pub enr_entries: BTreeMap<String, Bytes>,
+	/// Maximum upload rate in bytes per second. Unlimited if none.
+	pub max_upload_rate: Option<usize>,
+	/// Maximum download rate in bytes per second. Unlimited if none.
+	pub max_download_rate: Option<usize>,
 }
 
 impl Default for NetworkConfiguration {
@@ -121,6 +140,7 @@ impl NetworkConfiguration {
 			public_address: None,
 			udp_port: None,
 			nat_enabled: true,
+			nat_type: NatType::Any,
 			discovery_enabled: true,
 			boot_nodes: Vec::new(),
 			use_secret: None,
@@ -131,6 +151,10 @@ impl NetworkConfiguration {
 			allow_ips: AllowIP::All,
 			reserved_nodes: Vec::new(),
 			non_reserved_mode: NonReservedPeerMode::Accept,
+			peer_groups: Vec::new(),
+			enr_entries: BTreeMap::new(),
+			max_upload_rate: None,
+			max_download_rate: None,
 		}
 	}
 
@@ -186,6 +210,19 @@ pub enum NetworkIoMessage {
 	DisablePeer(PeerId),
 	/// Network has been started with the host as the given enode.
 	NetworkStarted(String),
+	/// Advertise this node under a discovery topic.
+	RegisterTopic(Vec<u8>),
+	/// Look for nodes advertised under a discovery topic.
+	SearchTopic(Vec<u8>),
+	/// Update the reputation of a peer.
+	ReportPeer(PeerId, PeerReport),
+	/// Port mapping renewal finished.
+	PortMappingRenewed {
+		/// New external endpoint, if the gateway changed it.
+		endpoint: Option<NodeEndpoint>,
+		/// Delay until the next renewal in milliseconds.
+		timeout: u64,
+	},
 }
 
 /// Local (temporary) peer session ID.
@@ -281,6 +318,13 @@ impl<'s> NetworkContext<'s> {
 			.unwrap_or_else(|e| warn!("Error sending network IO message: {:?}", e));
 	}
 
+	/// Report the outcome of an exchange with a peer. Peers with a bad reputation
+	/// are disconnected and banned for a while.
+	pub fn report_peer(&self, peer: PeerId, report: PeerReport) {
+		self.io.message(NetworkIoMessage::ReportPeer(peer, report))
+			.unwrap_or_else(|e| warn!("Error sending network IO message: {:?}", e));
+	}
+
 	/// Check if the session is still active.
 	pub fn is_expired(&self) -> bool {
 		self.session.as_ref().map_or(false, |s| s.lock().expired())
@@ -314,6 +358,19 @@ impl<'s> NetworkContext<'s> {
 
 	/// Returns this object's subprotocol name.
 	pub fn subprotocol_name(&self) -> ProtocolId { self.protocol }
+
+	/// Advertise this node under the given topic in node discovery.
+	pub fn register_topic(&self, topic: &[u8]) {
+		self.io.message(NetworkIoMessage::RegisterTopic(topic.to_vec()))
+			.unwrap_or_else(|e| warn!("Error sending network IO message: {:?}", e));
+	}
+
+	/// Look for nodes advertised under the given topic in node discovery.
+	/// Nodes found are added to the node table and preferred when connecting to new peers.
+	pub fn search_topic(&self, topic: &[u8]) {
+		self.io.message(NetworkIoMessage::SearchTopic(topic.to_vec()))
+			.unwrap_or_else(|e| warn!("Error sending network IO message: {:?}", e));
+	}
 }
 
 /// Shared host information
@@ -334,6 +391,8 @@ pub struct HostInfo {
 	pub local_endpoint: NodeEndpoint,
 	/// Public address + discovery port
 	pub public_endpoint: Option<NodeEndpoint>,
+	/// Signed record of the public endpoint
+	pub enr: Option<Enr>,
 }
 
 impl HostInfo {
@@ -362,25 +421,42 @@ struct ProtocolTimer {
 	pub token: TimerToken, // Handler level token
 }
 
+/// Discovery topics requested by protocol handlers.
+#[derive(Default)]
+struct DiscoveryTopics {
+	registered: HashSet<Vec<u8>>,
+	searched: HashSet<Vec<u8>>,
+}
+
 /// Root IO handler. Manages protocol handlers, IO timers and network connections.
 pub struct Host {
 	pub info: RwLock<HostInfo>,
-	tcp_listener: Mutex<TcpListener>,
+	transport: Box<Transport>,
+	tcp_listener: Mutex<Box<Listener>>,
 	sessions: Arc<RwLock<Slab<SharedSession>>>,
 	discovery: Mutex<Option<Discovery>>,
+	port_mapping: Arc<Mutex<Option<PortMapping>>>,
+	topics: RwLock<DiscoveryTopics>,
 	nodes: RwLock<NodeTable>,
 	handlers: RwLock<HashMap<ProtocolId, Arc<NetworkProtocolHandler>>>,
 	timers: RwLock<HashMap<TimerToken, ProtocolTimer>>,
 	timer_counter: RwLock<usize>,
 	stats: Arc<NetworkStats>,
+	limiter: Arc<BandwidthLimiter>,
 	reserved_nodes: RwLock<HashSet<NodeId>>,
+	peer_groups: RwLock<PeerGroups>,
 	num_sessions: AtomicUsize,
 	stopping: AtomicBool,
 }
 
 impl Host {
 	/// Create a new instance
-	pub fn new(mut config: NetworkConfiguration, stats: Arc<NetworkStats>) -> Result<Host, NetworkError> {
+	pub fn new(config: NetworkConfiguration, stats: Arc<NetworkStats>) -> Result<Host, NetworkError> {
+		Host::with_transport(config, stats, Box::new(TcpTransport))
+	}
+
+	/// Create a new instance which opens sockets through the given transport.
+	pub fn with_transport(mut config: NetworkConfiguration, stats: Arc<NetworkStats>, transport: Box<Transport>) -> Result<Host, NetworkError> {
 		let mut listen_address = match config.listen_address {
 			None => SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), DEFAULT_PORT)),
 			Some(addr) => addr,
@@ -401,7 +477,7 @@ impl Host {
 		};
 		let path = config.net_config_path.clone();
 		// Setup the server socket
-		let tcp_listener = TcpListener::bind(&listen_address)?;
+		let tcp_listener = transport.listen(&listen_address)?;
 		listen_address = SocketAddr::new(listen_address.ip(), tcp_listener.local_addr()?.port());
 		debug!(target: "network", "Listening at {:?}", listen_address);
 		let udp_port = config.udp_port.unwrap_or(listen_address.port());
@@ -409,7 +485,9 @@ impl Host {
 
 		let boot_nodes = config.boot_nodes.clone();
 		let reserved_nodes = config.reserved_nodes.clone();
+		let peer_groups = config.peer_groups.clone();
 		config.max_handshakes = min(config.max_handshakes, MAX_HANDSHAKES as u32);
+		let limiter = BandwidthLimiter::new(config.max_upload_rate, config.max_download_rate);
 
 		let mut host = Host {
 			info: RwLock::new(HostInfo {
@@ -420,9 +498,13 @@ impl Host {
 				client_version: version(),
 				capabilities: Vec::new(),
 				public_endpoint: None,
+				enr: None,
 				local_endpoint: local_endpoint,
 			}),
 			discovery: Mutex::new(None),
+			port_mapping: Arc::new(Mutex::new(None)),
+			topics: RwLock::new(DiscoveryTopics::default()),
+			transport: transport,
 			tcp_listener: Mutex::new(tcp_listener),
 			sessions: Arc::new(RwLock::new(Slab::new_starting_at(FIRST_SESSION, MAX_SESSIONS))),
 			nodes: RwLock::new(NodeTable::new(path)),
@@ -430,7 +512,9 @@ impl Host {
 			timers: RwLock::new(HashMap::new()),
 			timer_counter: RwLock::new(USER_TIMER),
 			stats: stats,
+			limiter: Arc::new(limiter),
 			reserved_nodes: RwLock::new(HashSet::new()),
+			peer_groups: RwLock::new(PeerGroups::default()),
 			num_sessions: AtomicUsize::new(0),
 			stopping: AtomicBool::new(false),
 		};
@@ -444,6 +528,18 @@ impl Host {
 				debug!(target: "network", "Error parsing node id: {}: {:?}", n, e);
 			}
 		}
+
+		for group in peer_groups {
+			if let Err(e) = host.set_peer_group(&group.name, group.min_slots, group.max_slots, group.trusted) {
+				warn!(target: "network", "Invalid peer group {}: {}", group.name, e);
+				continue;
+			}
+			for n in group.nodes {
+				if let Err(e) = host.add_peer_group_node(&group.name, &n) {
+					debug!(target: "network", "Error parsing node id: {}: {:?}", n, e);
+				}
+			}
+		}
 		Ok(host)
 	}
 
@@ -463,18 +559,63 @@ impl Host {
 
 	pub fn add_reserved_node(&self, id: &str) -> Result<(), NetworkError> {
 		let n = Node::from_str(id)?;
+		self.reserved_nodes.write().insert(n.id.clone());
+		self.add_static_node(n);
+		Ok(())
+	}
 
+	/// Add a node to the node table and discovery, so that it can be dialed.
+	fn add_static_node(&self, n: Node) {
 		let entry = NodeEntry { endpoint: n.endpoint.clone(), id: n.id.clone() };
-		self.reserved_nodes.write().insert(n.id.clone());
 		self.nodes.write().add_node(Node::new(entry.id.clone(), entry.endpoint.clone()));
 
 		if let Some(ref mut discovery) = *self.discovery.lock() {
 			discovery.add_node(entry);
 		}
+	}
+
+	/// Create a peer group or update the slots of an existing one.
+	pub fn set_peer_group(&self, name: &str, min_slots: u32, max_slots: u32, trusted: bool) -> Result<(), NetworkError> {
+		self.peer_groups.write().set_group(name, min_slots, max_slots, trusted)
+	}
 
+	/// Remove a peer group. Established sessions with its members are kept.
+	pub fn remove_peer_group(&self, name: &str) -> Result<(), NetworkError> {
+		self.peer_groups.write().remove_group(name)
+	}
+
+	/// Add a node given by its url to a peer group.
+	pub fn add_peer_group_node(&self, group: &str, id: &str) -> Result<(), NetworkError> {
+		let n = Node::from_str(id)?;
+		self.peer_groups.write().add_node(group, n.id.clone())?;
+		self.add_static_node(n);
 		Ok(())
 	}
 
+	/// Remove a node given by its url or id from a peer group.
+	pub fn remove_peer_group_node(&self, group: &str, id: &str) -> Result<(), NetworkError> {
+		let id = match NodeId::from_str(id) {
+			Ok(id) => id,
+			Err(_) => Node::from_str(id)?.id,
+		};
+		self.peer_groups.write().remove_node(group, &id)
+	}
+
+	/// Returns the peer groups ordered by name.
+	pub fn peer_groups(&self) -> Vec<PeerGroupInfo> {
+		self.peer_groups.read().info()
+	}
+
+	/// Check if the node is reserved or a member of a trusted group.
+	fn is_trusted(&self, id: &NodeId) -> bool {
+		self.reserved_nodes.read().contains(id) || self.peer_groups.read().is_trusted(id)
+	}
+
+	/// Check if the node is reserved or a member of any group.
+	fn is_static(&self, id: &NodeId) -> bool {
+		self.reserved_nodes.read().contains(id) || self.peer_groups.read().contains(id)
+	}
+
 	pub fn set_non_reserved_mode(&self, mode: NonReservedPeerMode, io: &IoContext<NetworkIoMessage>) {
 		let mut info = self.info.write();
 
@@ -483,7 +624,8 @@ impl Host {
 			drop(info);
 			if let NonReservedPeerMode::Deny = mode {
 				// disconnect all non-reserved peers here.
-				let reserved: HashSet<NodeId> = self.reserved_nodes.read().clone();
+				let mut reserved: HashSet<NodeId> = self.reserved_nodes.read().clone();
+				reserved.extend(self.peer_groups.read().trusted_nodes());
 				let mut to_kill = Vec::new();
 				for e in self.sessions.write().iter_mut() {
 					let mut s = e.lock();
@@ -512,6 +654,37 @@ impl Host {
 		Ok(())
 	}
 
+	/// Ban a node given by its id or url for `duration` seconds and disconnect it.
+	pub fn ban_node(&self, id: &str, duration: u64, io: &IoContext<NetworkIoMessage>) -> Result<(), NetworkError> {
+		let id = match NodeId::from_str(id) {
+			Ok(id) => id,
+			Err(_) => Node::from_str(id)?.id,
+		};
+		self.nodes.write().ban(&id, duration);
+		self.disconnect_node(&id, io);
+		Ok(())
+	}
+
+	/// Reputations of the nodes which have been reported or banned.
+	pub fn node_reputations(&self) -> Vec<(NodeId, Reputation)> {
+		self.nodes.read().reputations()
+	}
+
+	fn disconnect_node(&self, id: &NodeId, io: &IoContext<NetworkIoMessage>) {
+		let mut to_kill = Vec::new();
+		for e in self.sessions.read().iter() {
+			let mut s = e.lock();
+			if s.id() == Some(id) {
+				s.disconnect(io, DisconnectReason::UselessPeer);
+				to_kill.push(s.token());
+			}
+		}
+		for p in to_kill {
+			trace!(target: "network", "Disconnecting banned peer: {}", p);
+			self.kill_connection(p, io, false);
+		}
+	}
+
 	pub fn client_version() -> String {
 		version()
 	}
@@ -520,6 +693,10 @@ impl Host {
 		self.info.read().public_endpoint.as_ref().map(|e| format!("{}", Node::new(self.info.read().id().clone(), e.clone())))
 	}
 
+	pub fn local_enr(&self) -> Option<String> {
+		self.info.read().enr.as_ref().map(|enr| enr.to_string())
+	}
+
 	pub fn local_url(&self) -> String {
 		let r = format!("{}", Node::new(self.info.read().id().clone(), self.info.read().local_endpoint.clone()));
 		println!("{}", r);
@@ -538,10 +715,24 @@ impl Host {
 			trace!(target: "network", "Disconnecting on shutdown: {}", p);
 			self.kill_connection(p, io, true);
 		}
+		if let Some(mut mapping) = self.port_mapping.lock().take() {
+			trace!(target: "network", "Removing port mapping on shutdown");
+			mapping.remove();
+		}
 		io.unregister_handler()?;
 		Ok(())
 	}
 
+	/// Get the ids of nodes with an established session.
+	pub fn connected_nodes(&self) -> Vec<NodeId> {
+		self.sessions.read().iter()
+			.filter_map(|e| {
+				let s = e.lock();
+				if s.is_ready() && !s.expired() { s.id().cloned() } else { None }
+			})
+			.collect()
+	}
+
 	/// Get all connected peers.
 	pub fn connected_peers(&self) -> Vec<PeerId> {
 		let sessions = self.sessions.read();
@@ -563,26 +754,31 @@ impl Host {
 		let local_endpoint = self.info.read().local_endpoint.clone();
 		let public_address = self.info.read().config.public_address.clone();
 		let allow_ips = self.info.read().config.allow_ips;
+		let (nat_enabled, nat_type) = {
+			let info = self.info.read();
+			(info.config.nat_enabled, info.config.nat_type)
+		};
 		let public_endpoint = match public_address {
 			None => {
 				let public_address = select_public_address(local_endpoint.address.port());
 				let public_endpoint = NodeEndpoint { address: public_address, udp_port: local_endpoint.udp_port };
-				if self.info.read().config.nat_enabled {
-					match map_external_address(&local_endpoint) {
-						Some(endpoint) => {
-							info!("NAT mapped to external address {}", endpoint.address);
-							endpoint
-						},
-						None => public_endpoint
-					}
-				} else {
-					public_endpoint
+				let mapping = if nat_enabled { PortMapping::new(&local_endpoint, nat_type) } else { None };
+				match mapping {
+					Some(mapping) => {
+						let endpoint = mapping.external_endpoint().clone();
+						info!("NAT mapped to external address {}", endpoint.address);
+						io.register_timer_once(NAT_RENEWAL, mapping.renewal_timeout())?;
+						*self.port_mapping.lock() = Some(mapping);
+						endpoint
+					},
+					None => public_endpoint
 				}
 			}
 			Some(addr) => NodeEndpoint { address: addr, udp_port: local_endpoint.udp_port }
 		};
 
 		self.info.write().public_endpoint = Some(public_endpoint.clone());
+		self.update_enr(&public_endpoint);
 
 		if let Some(url) = self.external_url() {
 			io.message(NetworkIoMessage::NetworkStarted(url)).unwrap_or_else(|e| warn!("Error sending IO notification: {:?}", e));
@@ -594,13 +790,23 @@ impl Host {
 			if info.config.discovery_enabled && info.config.non_reserved_mode == NonReservedPeerMode::Accept {
 				let mut udp_addr = local_endpoint.address.clone();
 				udp_addr.set_port(local_endpoint.udp_port);
-				Some(Discovery::new(&info.keys, udp_addr, public_endpoint, DISCOVERY, allow_ips))
+				let socket = self.transport.bind(&udp_addr)?;
+				Some(Discovery::with_socket(&info.keys, socket, public_endpoint, DISCOVERY, allow_ips))
 			} else { None }
 		};
 
 		if let Some(mut discovery) = discovery {
 			discovery.init_node_list(self.nodes.read().unordered_entries());
 			discovery.add_node_list(self.nodes.read().unordered_entries());
+			{
+				let topics = self.topics.read();
+				for topic in &topics.registered {
+					discovery.register_topic(topic.clone());
+				}
+				for topic in &topics.searched {
+					discovery.search_topic(topic.clone());
+				}
+			}
 			*self.discovery.lock() = Some(discovery);
 			io.register_stream(DISCOVERY)?;
 			io.register_timer(DISCOVERY_REFRESH, DISCOVERY_REFRESH_TIMEOUT)?;
@@ -611,6 +817,70 @@ impl Host {
 		Ok(())
 	}
 
+	/// Sign a new node record for the public endpoint.
+	fn update_enr(&self, public_endpoint: &NodeEndpoint) {
+		// the sequence number is the creation time, so that records made after a restart replace older ones.
+		let enr = {
+			let info = self.info.read();
+			let seq = max(::time::get_time().sec as u64, info.enr.as_ref().map_or(0, |enr| enr.seq() + 1));
+			Enr::new(&info.keys, seq, public_endpoint, &info.config.enr_entries)
+		};
+		match enr {
+			Ok(enr) => self.info.write().enr = Some(enr),
+			Err(e) => warn!("Error creating node record: {:?}", e),
+		}
+	}
+
+	/// Renew the port mapping on a separate thread, so that slow gateways do not block the IO handler.
+	/// The mapping stays locked until renewal is finished, so it is still removed on shutdown.
+	fn renew_port_mapping(&self, io: &IoContext<NetworkIoMessage>) {
+		let port_mapping = self.port_mapping.clone();
+		let channel = io.channel();
+		let spawned = thread::Builder::new().name("port-mapping".into()).spawn(move || {
+			let renewed = match *port_mapping.lock() {
+				Some(ref mut mapping) => {
+					let changed = mapping.renew().unwrap_or_else(|e| {
+						debug!(target: "network", "Error renewing port mapping: {}", e);
+						false
+					});
+					let endpoint = if changed { Some(mapping.external_endpoint().clone()) } else { None };
+					Some(NetworkIoMessage::PortMappingRenewed { endpoint: endpoint, timeout: mapping.renewal_timeout() })
+				},
+				None => None,
+			};
+			if let Some(message) = renewed {
+				channel.send(message).unwrap_or_else(|e| debug!(target: "network", "Error sending port mapping notification: {:?}", e));
+			}
+		});
+		if let Err(e) = spawned {
+			warn!(target: "network", "Error spawning port mapping thread: {}", e);
+		}
+	}
+
+	/// Schedule the next port mapping renewal and advertise the external endpoint if the gateway changed it.
+	fn on_port_mapping_renewed(&self, io: &IoContext<NetworkIoMessage>, endpoint: Option<NodeEndpoint>, timeout: u64) {
+		io.register_timer_once(NAT_RENEWAL, timeout)
+			.unwrap_or_else(|e| debug!(target: "network", "Error registering port mapping timer: {:?}", e));
+		if let Some(endpoint) = endpoint {
+			info!("NAT mapped to new external address {}", endpoint.address);
+			self.info.write().public_endpoint = Some(endpoint.clone());
+			self.update_enr(&endpoint);
+			let has_discovery = match *self.discovery.lock() {
+				Some(ref mut discovery) => {
+					discovery.set_public_endpoint(endpoint);
+					true
+				},
+				None => false,
+			};
+			if has_discovery {
+				io.update_registration(DISCOVERY).unwrap_or_else(|e| debug!("Error updating discovery registration: {:?}", e));
+			}
+			if let Some(url) = self.external_url() {
+				io.message(NetworkIoMessage::NetworkStarted(url)).unwrap_or_else(|e| warn!("Error sending IO notification: {:?}", e));
+			}
+		}
+	}
+
 	fn maintain_network(&self, io: &IoContext<NetworkIoMessage>) {
 		self.keep_alive(io);
 		self.connect_peers(io);
@@ -661,13 +931,19 @@ impl Host {
 
 		let session_count = self.session_count();
 		let reserved_nodes = self.reserved_nodes.read();
+		// group members missing to fill the group slots. only trusted ones are accepted in reserved-only mode or when banned.
+		let group_nodes: Vec<NodeId> = {
+			let groups = self.peer_groups.read();
+			let nodes = self.nodes.read();
+			groups.wanted().into_iter().filter(|id| groups.is_trusted(id) || (!pin && !nodes.is_banned(id))).collect()
+		};
 		if session_count >= min_peers as usize + reserved_nodes.len() {
 			// check if all pinned nodes are connected.
-			if reserved_nodes.iter().all(|n| self.have_session(n) && self.connecting_to(n)) {
+			if group_nodes.is_empty() && reserved_nodes.iter().all(|n| self.have_session(n) && self.connecting_to(n)) {
 				return;
 			}
 
-			// if not, only attempt connect to reserved peers
+			// if not, only attempt connect to reserved peers and peer groups
 			pin = true;
 		}
 
@@ -677,10 +953,16 @@ impl Host {
 			return;
 		}
 
-		// iterate over all nodes, reserved ones coming first.
+		// iterate over all nodes, reserved ones and peer groups coming first, then the ones found by topic search.
 		// if we are pinned to only reserved nodes, ignore all others.
-		let nodes = reserved_nodes.iter().cloned().chain(if !pin {
-			self.nodes.read().nodes(allow_ips)
+		let nodes = reserved_nodes.iter().cloned().chain(group_nodes).chain(if !pin {
+			let known = self.nodes.read().nodes(allow_ips);
+			let mut nodes: Vec<NodeId> = {
+				let usable: HashSet<&NodeId> = known.iter().collect();
+				self.topic_nodes().into_iter().filter(|id| usable.contains(id)).collect()
+			};
+			nodes.extend(known);
+			nodes
 		} else {
 			Vec::new()
 		});
@@ -695,6 +977,18 @@ impl Host {
 		debug!(target: "network", "Connecting peers: {} sessions, {} pending, {} started", self.session_count(), self.handshake_count(), started);
 	}
 
+	fn topic_nodes(&self) -> Vec<NodeId> {
+		let topics = self.topics.read();
+		let discovery = self.discovery.lock();
+		match *discovery {
+			Some(ref discovery) => topics.searched.iter()
+				.flat_map(|topic| discovery.topic_nodes(topic))
+				.map(|entry| entry.id)
+				.collect(),
+			None => Vec::new(),
+		}
+	}
+
 	#[cfg_attr(feature="dev", allow(single_match))]
 	fn connect_peer(&self, id: &NodeId, io: &IoContext<NetworkIoMessage>) {
 		if self.have_session(id) {
@@ -718,7 +1012,7 @@ impl Host {
 					return;
 				}
 			};
-			match TcpStream::connect(&address) {
+			match self.transport.connect(&address) {
 				Ok(socket) => {
 					trace!(target: "network", "Connecting to {:?}", address);
 					socket
@@ -735,12 +1029,12 @@ impl Host {
 	}
 
 	#[cfg_attr(feature="dev", allow(block_in_if_condition_stmt))]
-	fn create_connection(&self, socket: TcpStream, id: Option<&NodeId>, io: &IoContext<NetworkIoMessage>) -> Result<(), NetworkError> {
+	fn create_connection(&self, socket: Box<Stream>, id: Option<&NodeId>, io: &IoContext<NetworkIoMessage>) -> Result<(), NetworkError> {
 		let nonce = self.info.write().next_nonce();
 		let mut sessions = self.sessions.write();
 
 		let token = sessions.insert_with_opt(|token| {
-			match Session::new(io, socket, token, id, &nonce, self.stats.clone(), &self.info.read()) {
+			match Session::new(io, socket, token, id, &nonce, self.stats.clone(), self.limiter.clone(), &self.info.read()) {
 				Ok(s) => Some(Arc::new(Mutex::new(s))),
 				Err(e) => {
 					debug!(target: "network", "Session create error: {:?}", e);
@@ -812,7 +1106,7 @@ impl Host {
 							trace!(target: "network", "Session read error: {}:{:?} ({:?}) {:?}", token, s.id(), s.remote_addr(), e);
 							if let NetworkError::Disconnect(DisconnectReason::IncompatibleProtocol) = e {
 								if let Some(id) = s.id() {
-									if !self.reserved_nodes.read().contains(id) {
+									if !self.is_static(id) {
 										self.nodes.write().mark_as_useless(id);
 									}
 								}
@@ -836,16 +1130,35 @@ impl Host {
 							};
 
 							let id = s.id().expect("Ready session always has id").clone();
+							let admission = {
+								let mut groups = self.peer_groups.write();
+								let admission = groups.admit(&id);
+								groups.session_started(&id);
+								admission
+							};
+							let reserved = self.reserved_nodes.read().contains(&id);
+							let trusted = self.is_trusted(&id);
+
+							if self.nodes.read().is_banned(&id) && !trusted {
+								trace!(target: "network", "Rejected banned peer: {}", token);
+								s.disconnect(io, DisconnectReason::UselessPeer);
+								return;
+							}
+
+							if admission == Admission::Full && !reserved {
+								trace!(target: "network", "Rejected peer, peer group is full: {}", token);
+								s.disconnect(io, DisconnectReason::TooManyPeers);
+								return;
+							}
 
 							// Check for the session limit. session_counts accounts for the new session.
-							if reserved_only ||
+							// Reserved peers and peers taking a group slot are not limited.
+							let exempt = reserved || (admission == Admission::Slot && (trusted || !reserved_only));
+							if !exempt && (reserved_only ||
 								(s.info.originated && session_count > min_peers) ||
-								(!s.info.originated && session_count > max_peers) {
-								// only proceed if the connecting peer is reserved.
-								if !self.reserved_nodes.read().contains(&id) {
-									s.disconnect(io, DisconnectReason::TooManyPeers);
-									return;
-								}
+								(!s.info.originated && session_count > max_peers)) {
+								s.disconnect(io, DisconnectReason::TooManyPeers);
+								return;
 							}
 							ready_id = Some(id);
 
@@ -923,6 +1236,25 @@ impl Host {
 		self.kill_connection(token, io, true)
 	}
 
+	/// Resume sessions stopped by the bandwidth limits. Edge-triggered polling does not
+	/// report data which was left unread, so pending reads are retried here.
+	fn resume_throttled(&self, io: &IoContext<NetworkIoMessage>) {
+		let throttled: Vec<StreamToken> = self.sessions.read().iter()
+			.filter_map(|e| {
+				let mut s = e.lock();
+				if s.is_throttled() {
+					s.unthrottle(io);
+					Some(s.token())
+				} else {
+					None
+				}
+			})
+			.collect();
+		for token in throttled {
+			self.session_readable(token, io);
+		}
+	}
+
 	fn kill_connection(&self, token: StreamToken, io: &IoContext<NetworkIoMessage>, remote: bool) {
 		let mut to_disconnect: Vec<ProtocolId> = Vec::new();
 		let mut failure_id = None;
@@ -936,6 +1268,9 @@ impl Host {
 				if !s.expired() {
 					if s.is_ready() {
 						self.num_sessions.fetch_sub(1, AtomicOrdering::SeqCst);
+						if let Some(id) = s.id() {
+							self.peer_groups.write().session_ended(id);
+						}
 						for (p, _) in self.handlers.read().iter() {
 							if s.have_capability(*p)  {
 								to_disconnect.push(*p);
@@ -980,7 +1315,9 @@ impl Host {
 		for i in to_remove {
 			trace!(target: "network", "Removed from node table: {}", i);
 		}
-		self.nodes.write().update(node_changes, &*self.reserved_nodes.read());
+		let mut pinned = self.peer_groups.read().nodes();
+		pinned.extend(self.reserved_nodes.read().iter().cloned());
+		self.nodes.write().update(node_changes, &pinned);
 	}
 
 	pub fn with_context<F>(&self, protocol: ProtocolId, io: &IoContext<NetworkIoMessage>, action: F) where F: Fn(&NetworkContext) {
@@ -1002,6 +1339,9 @@ impl IoHandler<NetworkIoMessage> for Host {
 	/// Initialize networking
 	fn initialize(&self, io: &IoContext<NetworkIoMessage>) {
 		io.register_timer(IDLE, MAINTENANCE_TIMEOUT).expect("Error registering Network idle timer");
+		if self.limiter.is_limited() {
+			io.register_timer(BANDWIDTH, BANDWIDTH_TIMEOUT).expect("Error registering bandwidth timer");
+		}
 		io.message(NetworkIoMessage::InitPublicInterface).unwrap_or_else(|e| warn!("Error sending IO notification: {:?}", e));
 		self.maintain_network(io)
 	}
@@ -1065,8 +1405,11 @@ impl IoHandler<NetworkIoMessage> for Host {
 			NODE_TABLE => {
 				trace!(target: "network", "Refreshing node table");
 				self.nodes.write().clear_useless();
+				self.nodes.write().decay_reputations();
 				self.nodes.write().save();
 			},
+			BANDWIDTH => self.resume_throttled(io),
+			NAT_RENEWAL => self.renew_port_mapping(io),
 			_ => match self.timers.read().get(&token).cloned() {
 				Some(timer) => match self.handlers.read().get(&timer.protocol).cloned() {
 					None => { warn!(target: "network", "No handler found for protocol: {:?}", timer.protocol) },
@@ -1128,14 +1471,41 @@ impl IoHandler<NetworkIoMessage> for Host {
 				if let Some(session) = session {
 					session.lock().disconnect(io, DisconnectReason::DisconnectRequested);
 					if let Some(id) = session.lock().id() {
-						self.nodes.write().mark_as_useless(id)
+						let mut nodes = self.nodes.write();
+						nodes.mark_as_useless(id);
+						nodes.report(id, PeerReport::Misbehaviour);
 					}
 				}
 				trace!(target: "network", "Disabling peer {}", peer);
 				self.kill_connection(*peer, io, false);
 			},
+			NetworkIoMessage::ReportPeer(ref peer, ref report) => {
+				let id = { self.sessions.read().get(*peer).and_then(|s| s.lock().id().cloned()) };
+				if let Some(id) = id {
+					let banned = self.nodes.write().report(&id, *report);
+					if banned && !self.is_trusted(&id) {
+						self.disconnect_node(&id, io);
+					}
+				}
+			},
 			NetworkIoMessage::InitPublicInterface =>
 				self.init_public_interface(io).unwrap_or_else(|e| warn!("Error initializing public interface: {:?}", e)),
+			NetworkIoMessage::PortMappingRenewed { ref endpoint, timeout } =>
+				self.on_port_mapping_renewed(io, endpoint.clone(), timeout),
+			NetworkIoMessage::RegisterTopic(ref topic) => {
+				self.topics.write().registered.insert(topic.clone());
+				if let Some(ref mut discovery) = *self.discovery.lock() {
+					discovery.register_topic(topic.clone());
+					io.update_registration(DISCOVERY).unwrap_or_else(|e| debug!("Error updating discovery registration: {:?}", e));
+				}
+			},
+			NetworkIoMessage::SearchTopic(ref topic) => {
+				self.topics.write().searched.insert(topic.clone());
+				if let Some(ref mut discovery) = *self.discovery.lock() {
+					discovery.search_topic(topic.clone());
+					io.update_registration(DISCOVERY).unwrap_or_else(|e| debug!("Error updating discovery registration: {:?}", e));
+				}
+			},
 			_ => {}	// ignore others.
 		}
 	}
@@ -1149,7 +1519,7 @@ impl IoHandler<NetworkIoMessage> for Host {
 				}
 			}
 			DISCOVERY => self.discovery.lock().as_ref().and_then(|d| d.register_socket(event_loop).ok()).expect("Error registering discovery socket"),
-			TCP_ACCEPT => event_loop.register(&*self.tcp_listener.lock(), Token(TCP_ACCEPT), Ready::all(), PollOpt::edge()).expect("Error registering stream"),
+			TCP_ACCEPT => event_loop.register(&**self.tcp_listener.lock(), Token(TCP_ACCEPT), Ready::all(), PollOpt::edge()).expect("Error registering stream"),
 			_ => warn!("Unexpected stream registration")
 		}
 	}
@@ -1177,7 +1547,7 @@ impl IoHandler<NetworkIoMessage> for Host {
 				}
 			}
 			DISCOVERY => self.discovery.lock().as_ref().and_then(|d| d.update_registration(event_loop).ok()).expect("Error reregistering discovery socket"),
-			TCP_ACCEPT => event_loop.reregister(&*self.tcp_listener.lock(), Token(TCP_ACCEPT), Ready::all(), PollOpt::edge()).expect("Error reregistering stream"),
+			TCP_ACCEPT => event_loop.reregister(&**self.tcp_listener.lock(), Token(TCP_ACCEPT), Ready::all(), PollOpt::edge()).expect("Error reregistering stream"),
 			_ => warn!("Unexpected stream update")
 		}
 	}
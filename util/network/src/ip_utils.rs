@@ -16,11 +16,20 @@
 
 // Based on original work by David Levy https://raw.githubusercontent.com/dlevy47/rust-interfaces
 
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
 use std::io;
-use igd::{PortMappingProtocol, search_gateway_from_timeout};
+use std::cmp;
+#[cfg(target_os = "linux")]
+use std::fs::File;
+#[cfg(target_os = "linux")]
+use std::io::Read;
+use igd::{PortMappingProtocol, Gateway as IgdGateway, search_gateway_from_timeout};
+use rand::random;
 use std::time::Duration;
 use node_table::{NodeEndpoint};
+use NatType;
+#[cfg(test)]
+use fake_gateway::FakeGateway;
 
 /// Socket address extension for rustc beta. To be replaces with now unstable API
 pub trait SocketAddrExt {
@@ -179,39 +188,371 @@ pub fn select_public_address(port: u16) -> SocketAddr {
 	SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), port))
 }
 
-pub fn map_external_address(local: &NodeEndpoint) -> Option<NodeEndpoint> {
-	if let SocketAddr::V4(ref local_addr) = local.address {
-		match search_gateway_from_timeout(local_addr.ip().clone(), Duration::new(5, 0)) {
-			Err(ref err) => debug!("Gateway search error: {}", err),
-			Ok(gateway) => {
-				match gateway.get_external_ip() {
-					Err(ref err) => {
-						debug!("IP request error: {}", err);
-					},
-					Ok(external_addr) => {
-						match gateway.add_any_port(PortMappingProtocol::TCP, SocketAddrV4::new(local_addr.ip().clone(), local_addr.port()), 0, "Parity Node/TCP") {
-							Err(ref err) => {
-								debug!("Port mapping error: {}", err);
-							},
-							Ok(tcp_port) => {
-								match gateway.add_any_port(PortMappingProtocol::UDP, SocketAddrV4::new(local_addr.ip().clone(), local.udp_port), 0, "Parity Node/UDP") {
-									Err(ref err) => {
-										debug!("Port mapping error: {}", err);
-									},
-									Ok(udp_port) => {
-										return Some(NodeEndpoint { address: SocketAddr::V4(SocketAddrV4::new(external_addr, tcp_port)), udp_port: udp_port });
-									},
-								}
-							},
-						}
+/// UDP port of NAT-PMP and PCP servers.
+const NAT_PMP_PORT: u16 = 5351;
+const NAT_PMP_VERSION: u8 = 0;
+const PCP_VERSION: u8 = 2;
+const PCP_OPCODE_MAP: u8 = 1;
+/// Requested lifetime of port mappings.
+const NAT_LEASE_SEC: u32 = 20 * 60;
+/// Lower bound of the renewal interval, in case the gateway grants very short leases.
+const MIN_RENEWAL_SEC: u32 = 60;
+/// NAT-PMP and PCP requests are retransmitted with doubling timeouts.
+const PMP_INITIAL_TIMEOUT_MS: u64 = 250;
+const PMP_ATTEMPTS: usize = 4;
+const UPNP_SEARCH_TIMEOUT_SEC: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MappingProtocol {
+	Tcp,
+	Udp,
+}
+
+impl MappingProtocol {
+	fn igd(&self) -> PortMappingProtocol {
+		match *self {
+			MappingProtocol::Tcp => PortMappingProtocol::TCP,
+			MappingProtocol::Udp => PortMappingProtocol::UDP,
+		}
+	}
+
+	fn nat_pmp_opcode(&self) -> u8 {
+		match *self {
+			MappingProtocol::Udp => 1,
+			MappingProtocol::Tcp => 2,
+		}
+	}
+
+	fn iana_number(&self) -> u8 {
+		match *self {
+			MappingProtocol::Tcp => 6,
+			MappingProtocol::Udp => 17,
+		}
+	}
+
+	fn description(&self) -> &'static str {
+		match *self {
+			MappingProtocol::Tcp => "Parity Node/TCP",
+			MappingProtocol::Udp => "Parity Node/UDP",
+		}
+	}
+}
+
+/// Gateway able to map ports.
+enum Gateway {
+	/// UPnP internet gateway device.
+	Upnp(IgdGateway),
+	/// PCP server, or NAT-PMP server if the gateway does not speak PCP.
+	Pmp {
+		address: SocketAddrV4,
+		pcp: bool,
+	},
+}
+
+/// Port mappings of the TCP listener and the UDP discovery socket. Mappings expire
+/// unless renewed before `renewal_timeout` and should be removed on shutdown.
+pub struct PortMapping {
+	gateway: Gateway,
+	local: SocketAddrV4,
+	local_udp_port: u16,
+	external: NodeEndpoint,
+	lifetime: u32,
+	nonce: [u8; 12],
+}
+
+impl PortMapping {
+	/// Find a gateway of the given type and map the local endpoint.
+	pub fn new(local: &NodeEndpoint, nat_type: NatType) -> Option<PortMapping> {
+		let local_addr = match local.address {
+			SocketAddr::V4(a) if !a.ip().is_unspecified_s() => a,
+			SocketAddr::V4(a) => match select_public_address(a.port()) {
+				SocketAddr::V4(a) => a,
+				SocketAddr::V6(_) => return None,
+			},
+			SocketAddr::V6(_) => return None,
+		};
+		let mut gateways = Vec::new();
+		if nat_type != NatType::NatPmp {
+			match search_gateway_from_timeout(local_addr.ip().clone(), Duration::new(UPNP_SEARCH_TIMEOUT_SEC, 0)) {
+				Ok(gateway) => gateways.push(Gateway::Upnp(gateway)),
+				Err(ref err) => debug!(target: "network", "Gateway search error: {}", err),
+			}
+		}
+		if nat_type != NatType::Upnp {
+			match default_gateway() {
+				Some(ip) => gateways.push(Gateway::Pmp { address: SocketAddrV4::new(ip, NAT_PMP_PORT), pcp: true }),
+				None => debug!(target: "network", "No default gateway for NAT-PMP"),
+			}
+		}
+		for gateway in gateways {
+			match PortMapping::with_gateway(gateway, local_addr, local.udp_port) {
+				Ok(mapping) => return Some(mapping),
+				Err(ref err) => debug!(target: "network", "Port mapping error: {}", err),
+			}
+		}
+		None
+	}
+
+	fn with_gateway(gateway: Gateway, local: SocketAddrV4, local_udp_port: u16) -> io::Result<PortMapping> {
+		let mut mapping = PortMapping {
+			gateway: gateway,
+			local: local,
+			local_udp_port: local_udp_port,
+			external: NodeEndpoint { address: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0)), udp_port: 0 },
+			lifetime: 0,
+			nonce: random(),
+		};
+		mapping.map()?;
+		Ok(mapping)
+	}
+
+	/// Get the mapped endpoint as seen from outside.
+	pub fn external_endpoint(&self) -> &NodeEndpoint {
+		&self.external
+	}
+
+	/// Get the delay in milliseconds after which the mappings should be renewed.
+	pub fn renewal_timeout(&self) -> u64 {
+		let lifetime = if self.lifetime == 0 { NAT_LEASE_SEC } else { self.lifetime };
+		cmp::max(lifetime / 2, MIN_RENEWAL_SEC) as u64 * 1000
+	}
+
+	/// Renew the mappings. Returns `true` if the external endpoint has changed.
+	pub fn renew(&mut self) -> io::Result<bool> {
+		let old = self.external.clone();
+		self.map()?;
+		Ok(old.address != self.external.address || old.udp_port != self.external.udp_port)
+	}
+
+	/// Remove the mappings from the gateway.
+	pub fn remove(&mut self) {
+		if self.external.address.port() == 0 {
+			return;
+		}
+		let local_ip = self.local.ip().clone();
+		let (local_tcp, local_udp) = (self.local.port(), self.local_udp_port);
+		let (external_tcp, external_udp) = (self.external.address.port(), self.external.udp_port);
+		let result = match self.gateway {
+			Gateway::Upnp(ref gateway) => gateway.remove_port(PortMappingProtocol::TCP, external_tcp).map_err(other_error)
+				.and_then(|_| gateway.remove_port(PortMappingProtocol::UDP, external_udp).map_err(other_error)),
+			Gateway::Pmp { ref address, pcp: true } => pcp_map(address, &local_ip, &self.nonce, MappingProtocol::Tcp, local_tcp, 0, 0)
+				.and_then(|_| pcp_map(address, &local_ip, &self.nonce, MappingProtocol::Udp, local_udp, 0, 0))
+				.map(|_| ()),
+			Gateway::Pmp { ref address, pcp: false } => nat_pmp_map(address, MappingProtocol::Tcp, local_tcp, 0, 0)
+				.and_then(|_| nat_pmp_map(address, MappingProtocol::Udp, local_udp, 0, 0))
+				.map(|_| ()),
+		};
+		if let Err(ref err) = result {
+			debug!(target: "network", "Error removing port mapping: {}", err);
+		}
+		self.external.address.set_port(0);
+	}
+
+	fn map(&mut self) -> io::Result<()> {
+		let local_ip = self.local.ip().clone();
+		let (local_tcp, local_udp) = (self.local.port(), self.local_udp_port);
+		let (external_tcp, external_udp) = (self.external.address.port(), self.external.udp_port);
+		let (ip, tcp, udp, lifetime) = match self.gateway {
+			Gateway::Upnp(ref gateway) => {
+				let ip = gateway.get_external_ip().map_err(other_error)?;
+				let tcp = upnp_map(gateway, MappingProtocol::Tcp, self.local, external_tcp)?;
+				let udp = upnp_map(gateway, MappingProtocol::Udp, SocketAddrV4::new(local_ip, local_udp), external_udp)?;
+				(ip, tcp, udp, NAT_LEASE_SEC)
+			},
+			Gateway::Pmp { ref address, ref mut pcp } => {
+				let mapped = if *pcp {
+					match pcp_map(address, &local_ip, &self.nonce, MappingProtocol::Tcp, local_tcp, external_tcp, NAT_LEASE_SEC)? {
+						Some((tcp, ip, tcp_lifetime)) => {
+							let (udp, _, udp_lifetime) = pcp_map(address, &local_ip, &self.nonce, MappingProtocol::Udp, local_udp, external_udp, NAT_LEASE_SEC)?
+								.ok_or_else(|| other_error("PCP support withdrawn"))?;
+							Some((ip, tcp, udp, cmp::min(tcp_lifetime, udp_lifetime)))
+						},
+						None => {
+							trace!(target: "network", "Gateway does not support PCP, falling back to NAT-PMP");
+							*pcp = false;
+							None
+						},
+					}
+				} else {
+					None
+				};
+				match mapped {
+					Some(mapped) => mapped,
+					None => {
+						let ip = nat_pmp_external_address(address)?;
+						let (tcp, tcp_lifetime) = nat_pmp_map(address, MappingProtocol::Tcp, local_tcp, external_tcp, NAT_LEASE_SEC)?;
+						let (udp, udp_lifetime) = nat_pmp_map(address, MappingProtocol::Udp, local_udp, external_udp, NAT_LEASE_SEC)?;
+						(ip, tcp, udp, cmp::min(tcp_lifetime, udp_lifetime))
 					},
 				}
 			},
+		};
+		self.external = NodeEndpoint { address: SocketAddr::V4(SocketAddrV4::new(ip, tcp)), udp_port: udp };
+		self.lifetime = lifetime;
+		Ok(())
+	}
+}
+
+impl Drop for PortMapping {
+	fn drop(&mut self) {
+		self.remove();
+	}
+}
+
+fn other_error<E: ::std::fmt::Display>(e: E) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+/// Map a port with UPnP, keeping the external port if one was mapped before.
+fn upnp_map(gateway: &IgdGateway, protocol: MappingProtocol, local: SocketAddrV4, external_port: u16) -> io::Result<u16> {
+	let add = |port: u16, lease: u32| if port != 0 {
+		gateway.add_port(protocol.igd(), port, local, lease, protocol.description()).map(|_| port).map_err(other_error)
+	} else {
+		gateway.add_any_port(protocol.igd(), local, lease, protocol.description()).map_err(other_error)
+	};
+	// some gateways only support permanent leases; those are refreshed and removed all the same.
+	add(external_port, NAT_LEASE_SEC)
+		.or_else(|_| add(external_port, 0))
+		.or_else(|e| if external_port != 0 { add(0, NAT_LEASE_SEC) } else { Err(e) })
+}
+
+/// Send a NAT-PMP or PCP request and wait for the response.
+fn pmp_request(gateway: &SocketAddrV4, request: &[u8]) -> io::Result<Vec<u8>> {
+	let socket = UdpSocket::bind("0.0.0.0:0")?;
+	socket.connect(gateway)?;
+	let mut timeout = PMP_INITIAL_TIMEOUT_MS;
+	let mut buf = [0u8; 1100];
+	for _ in 0..PMP_ATTEMPTS {
+		socket.send(request)?;
+		socket.set_read_timeout(Some(Duration::from_millis(timeout)))?;
+		match socket.recv(&mut buf) {
+			Ok(size) => return Ok(buf[..size].to_vec()),
+			Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => timeout *= 2,
+			Err(e) => return Err(e),
+		}
+	}
+	Err(io::Error::new(io::ErrorKind::TimedOut, "No response from NAT-PMP gateway"))
+}
+
+fn check_nat_pmp_response(response: &[u8], opcode: u8, len: usize) -> io::Result<()> {
+	if response.len() < 4 || response[0] != NAT_PMP_VERSION || response[1] != 128 + opcode {
+		return Err(other_error("Invalid NAT-PMP response"));
+	}
+	match get_u16(&response[2..]) {
+		0 if response.len() >= len => Ok(()),
+		0 => Err(other_error("Invalid NAT-PMP response")),
+		code => Err(other_error(format!("NAT-PMP error code {}", code))),
+	}
+}
+
+/// Ask a NAT-PMP gateway for its external address.
+fn nat_pmp_external_address(gateway: &SocketAddrV4) -> io::Result<Ipv4Addr> {
+	let response = pmp_request(gateway, &[NAT_PMP_VERSION, 0])?;
+	check_nat_pmp_response(&response, 0, 12)?;
+	Ok(Ipv4Addr::new(response[8], response[9], response[10], response[11]))
+}
+
+/// Map a port with NAT-PMP. Returns the external port and the lifetime of the mapping.
+/// A zero lifetime removes the mapping.
+fn nat_pmp_map(gateway: &SocketAddrV4, protocol: MappingProtocol, internal_port: u16, external_port: u16, lifetime: u32) -> io::Result<(u16, u32)> {
+	let mut request = [0u8; 12];
+	request[0] = NAT_PMP_VERSION;
+	request[1] = protocol.nat_pmp_opcode();
+	put_u16(&mut request[4..], internal_port);
+	put_u16(&mut request[6..], if lifetime == 0 { 0 } else { external_port });
+	put_u32(&mut request[8..], lifetime);
+	let response = pmp_request(gateway, &request)?;
+	check_nat_pmp_response(&response, protocol.nat_pmp_opcode(), 16)?;
+	Ok((get_u16(&response[10..]), get_u32(&response[12..])))
+}
+
+/// Map a port with PCP. Returns the external port, address and the lifetime of the mapping,
+/// or `None` if the gateway only speaks NAT-PMP. A zero lifetime removes the mapping.
+fn pcp_map(gateway: &SocketAddrV4, local: &Ipv4Addr, nonce: &[u8; 12], protocol: MappingProtocol, internal_port: u16, external_port: u16, lifetime: u32)
+	-> io::Result<Option<(u16, Ipv4Addr, u32)>>
+{
+	let mut request = [0u8; 60];
+	request[0] = PCP_VERSION;
+	request[1] = PCP_OPCODE_MAP;
+	put_u32(&mut request[4..], lifetime);
+	put_ipv4_mapped(&mut request[8..], local);
+	request[24..36].copy_from_slice(nonce);
+	request[36] = protocol.iana_number();
+	put_u16(&mut request[40..], internal_port);
+	put_u16(&mut request[42..], external_port);
+	put_ipv4_mapped(&mut request[44..], &Ipv4Addr::new(0, 0, 0, 0));
+	let response = pmp_request(gateway, &request)?;
+	if !response.is_empty() && response[0] == NAT_PMP_VERSION {
+		return Ok(None);
+	}
+	if response.len() < 60 || response[0] != PCP_VERSION || response[1] != 128 + PCP_OPCODE_MAP || &response[24..36] != &nonce[..] {
+		return Err(other_error("Invalid PCP response"));
+	}
+	if response[3] != 0 {
+		return Err(other_error(format!("PCP error code {}", response[3])));
+	}
+	let ip = Ipv4Addr::new(response[56], response[57], response[58], response[59]);
+	Ok(Some((get_u16(&response[42..]), ip, get_u32(&response[4..]))))
+}
+
+fn put_u16(buf: &mut [u8], value: u16) {
+	buf[0] = (value >> 8) as u8;
+	buf[1] = value as u8;
+}
+
+fn put_u32(buf: &mut [u8], value: u32) {
+	put_u16(buf, (value >> 16) as u16);
+	put_u16(&mut buf[2..], value as u16);
+}
+
+fn put_ipv4_mapped(buf: &mut [u8], ip: &Ipv4Addr) {
+	buf[10] = 0xff;
+	buf[11] = 0xff;
+	buf[12..16].copy_from_slice(&ip.octets());
+}
+
+fn get_u16(buf: &[u8]) -> u16 {
+	(buf[0] as u16) << 8 | buf[1] as u16
+}
+
+fn get_u32(buf: &[u8]) -> u32 {
+	(get_u16(buf) as u32) << 16 | get_u16(&buf[2..]) as u32
+}
+
+/// Find the default IPv4 gateway.
+#[cfg(target_os = "linux")]
+fn default_gateway() -> Option<Ipv4Addr> {
+	let mut routes = String::new();
+	match File::open("/proc/net/route").and_then(|mut f| f.read_to_string(&mut routes)) {
+		Ok(_) => parse_default_route(&routes),
+		Err(e) => {
+			debug!(target: "network", "Error reading routing table: {:?}", e);
+			None
 		}
 	}
+}
+
+#[cfg(not(target_os = "linux"))]
+fn default_gateway() -> Option<Ipv4Addr> {
 	None
 }
 
+/// Parse the gateway of the default route out of `/proc/net/route`.
+#[cfg(any(test, target_os = "linux"))]
+fn parse_default_route(routes: &str) -> Option<Ipv4Addr> {
+	routes.lines().skip(1)
+		.filter_map(|line| {
+			let fields: Vec<&str> = line.split_whitespace().collect();
+			if fields.len() < 3 || fields[1] != "00000000" {
+				return None;
+			}
+			u32::from_str_radix(fields[2], 16).ok()
+		})
+		.find(|gateway| *gateway != 0)
+		// addresses are in host byte order.
+		.map(|gateway| Ipv4Addr::new(gateway as u8, (gateway >> 8) as u8, (gateway >> 16) as u8, (gateway >> 24) as u8))
+}
+
 #[test]
 fn can_select_public_address() {
 	let pub_address = select_public_address(40477);
@@ -222,7 +563,70 @@ fn can_select_public_address() {
 #[test]
 fn can_map_external_address_or_fail() {
 	let pub_address = select_public_address(40478);
-	let _ = map_external_address(&NodeEndpoint { address: pub_address, udp_port: 40478 });
+	let _ = PortMapping::new(&NodeEndpoint { address: pub_address, udp_port: 40478 }, NatType::Any);
+}
+
+#[test]
+fn parses_default_route() {
+	let routes = "Iface	Destination	Gateway 	Flags	RefCnt	Use	Metric	Mask		MTU	Window	IRTT
+		eth0	0000A8C0	00000000	0001	0	0	0	00FFFFFF	0	0	0
+		eth0	00000000	0100A8C0	0003	0	0	0	00000000	0	0	0
+";
+	assert_eq!(parse_default_route(routes), Some(Ipv4Addr::new(192, 168, 0, 1)));
+	assert_eq!(parse_default_route("Iface	Destination	Gateway
+"), None);
+}
+
+#[test]
+fn maps_ports_with_nat_pmp() {
+	let gateway = FakeGateway::new(false);
+	let local = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 30303);
+	let mut mapping = PortMapping::with_gateway(Gateway::Pmp { address: gateway.pmp_address(), pcp: true }, local, 30301).unwrap();
+	assert_eq!(mapping.external_endpoint().address, "1.2.3.4:30303".parse().unwrap());
+	assert_eq!(mapping.external_endpoint().udp_port, 30301);
+	assert_eq!(gateway.mappings().len(), 2);
+	assert_eq!(mapping.renewal_timeout(), NAT_LEASE_SEC as u64 / 2 * 1000);
+
+	// renewals keep the ports and notice the new external address.
+	assert!(!mapping.renew().unwrap());
+	gateway.set_external_ip(Ipv4Addr::new(5, 6, 7, 8));
+	assert!(mapping.renew().unwrap());
+	assert_eq!(mapping.external_endpoint().address, "5.6.7.8:30303".parse().unwrap());
+
+	mapping.remove();
+	assert!(gateway.mappings().is_empty());
+}
+
+#[test]
+fn maps_ports_with_pcp() {
+	let gateway = FakeGateway::new(true);
+	let local = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 30303);
+	gateway.reserve_port(30303);
+	let mapping = PortMapping::with_gateway(Gateway::Pmp { address: gateway.pmp_address(), pcp: true }, local, 30303).unwrap();
+	assert!(mapping.external_endpoint().address.port() != 30303);
+	assert_eq!(mapping.external_endpoint().udp_port, 30303);
+	assert_eq!(gateway.mappings().len(), 3);
+
+	// mappings are removed when dropped.
+	drop(mapping);
+	assert_eq!(gateway.mappings().len(), 1);
+}
+
+#[test]
+fn maps_ports_with_upnp() {
+	let gateway = FakeGateway::new(false);
+	let local = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 30303);
+	let igd = IgdGateway { addr: gateway.upnp_address(), control_url: "/control".into() };
+	let mut mapping = PortMapping::with_gateway(Gateway::Upnp(igd), local, 30301).unwrap();
+	assert_eq!(mapping.external_endpoint().address.ip(), IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)));
+	assert_eq!(gateway.mappings().len(), 2);
+
+	gateway.set_external_ip(Ipv4Addr::new(5, 6, 7, 8));
+	assert!(mapping.renew().unwrap());
+	assert_eq!(gateway.mappings().len(), 2);
+
+	mapping.remove();
+	assert!(gateway.mappings().is_empty());
 }
 
 #[test]
@@ -83,25 +83,39 @@ mod connection;
 mod handshake;
 mod session;
 mod discovery;
+mod enr;
 mod service;
 mod error;
 mod node_table;
 mod stats;
 mod ip_utils;
+mod reputation;
+mod bandwidth;
+mod peer_group;
+mod transport;
+#[cfg(any(test, feature = "test-helpers"))]
+mod simulation;
 
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+mod fake_gateway;
 
 pub use host::{PeerId, PacketId, ProtocolId, NetworkContext, NetworkIoMessage, NetworkConfiguration};
 pub use service::NetworkService;
 pub use error::NetworkError;
-pub use stats::NetworkStats;
+pub use stats::{NetworkStats, TrafficStats};
 pub use session::SessionInfo;
 
 use io::TimerToken;
 pub use node_table::{is_valid_node_url, NodeId};
+pub use enr::Enr;
+pub use reputation::{PeerReport, Reputation, MAX_BAN_DURATION_SEC};
+pub use peer_group::{PeerGroupConfig, PeerGroupInfo};
+#[cfg(any(test, feature = "test-helpers"))]
+pub use simulation::{Simulation, LinkConfig};
 
-const PROTOCOL_VERSION: u32 = 4;
+const PROTOCOL_VERSION: u32 = 5;
 
 /// Network IO protocol handler. This needs to be implemented for each new subprotocol.
 /// All the handler function are called from within IO event loop.
@@ -139,6 +153,17 @@ impl NonReservedPeerMode {
 	}
 }
 
+/// Port mapping method
+#[derive(Clone, Debug, PartialEq, Eq, Copy)]
+pub enum NatType {
+	/// Try UPnP, then NAT-PMP and PCP
+	Any,
+	/// UPnP IGD only
+	Upnp,
+	/// NAT-PMP or PCP only
+	NatPmp,
+}
+
 /// IP fiter
 #[derive(Clone, Debug, PartialEq, Eq, Copy)]
 pub enum AllowIP {
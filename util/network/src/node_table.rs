@@ -32,7 +32,9 @@ use time::Tm;
 use error::NetworkError;
 use AllowIP;
 use discovery::{TableUpdates, NodeEntry};
+use enr::{Enr, ENR_PREFIX};
 use ip_utils::*;
+use reputation::{PeerReport, Reputation};
 pub use rustc_serialize::json::Json;
 
 /// Node public key
@@ -164,7 +166,12 @@ impl Display for Node {
 impl FromStr for Node {
 	type Err = NetworkError;
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		let (id, endpoint) = if s.len() > 136 && &s[0..8] == "enode://" && &s[136..137] == "@" {
+		let (id, endpoint) = if s.starts_with(ENR_PREFIX) {
+			let enr = Enr::from_str(s)?;
+			let endpoint = enr.endpoint().ok_or(NetworkError::InvalidNodeRecord)?;
+			(enr.id().clone(), endpoint)
+		}
+		else if s.len() > 136 && &s[0..8] == "enode://" && &s[136..137] == "@" {
 			(s[8..136].parse().map_err(UtilError::from)?, NodeEndpoint::from_str(&s[137..])?)
 		}
 		else {
@@ -194,19 +201,29 @@ impl Hash for Node {
 	}
 }
 
+/// Maximum number of reputations kept in the table.
+const MAX_REPUTATIONS: usize = 4096;
+
+fn unix_time() -> u64 {
+	::time::get_time().sec as u64
+}
+
 /// Node table backed by disk file.
 pub struct NodeTable {
 	nodes: HashMap<NodeId, Node>,
 	useless_nodes: HashSet<NodeId>,
+	reputations: HashMap<NodeId, Reputation>,
 	path: Option<String>,
 }
 
 impl NodeTable {
 	pub fn new(path: Option<String>) -> NodeTable {
+		let (nodes, reputations) = NodeTable::load(path.clone());
 		NodeTable {
-			path: path.clone(),
-			nodes: NodeTable::load(path),
+			path: path,
+			nodes: nodes,
 			useless_nodes: HashSet::new(),
+			reputations: reputations,
 		}
 	}
 
@@ -218,10 +235,15 @@ impl NodeTable {
 		self.nodes.insert(node.id.clone(), node);
 	}
 
-	/// Returns node ids sorted by number of failures
+	/// Returns ids of nodes which are not banned, best reputation first, then by number of failures
 	pub fn nodes(&self, filter: AllowIP) -> Vec<NodeId> {
-		let mut refs: Vec<&Node> = self.nodes.values().filter(|n| !self.useless_nodes.contains(&n.id) && n.endpoint.is_allowed(filter)).collect();
-		refs.sort_by(|a, b| a.failures.cmp(&b.failures));
+		let now = unix_time();
+		self.ordered_nodes(|n| n.endpoint.is_allowed(filter) && !self.is_banned_at(&n.id, now))
+	}
+
+	fn ordered_nodes<F>(&self, filter: F) -> Vec<NodeId> where F: Fn(&Node) -> bool {
+		let mut refs: Vec<&Node> = self.nodes.values().filter(|n| !self.useless_nodes.contains(&n.id) && filter(n)).collect();
+		refs.sort_by_key(|n| (-self.reputation(&n.id).score, n.failures));
 		refs.iter().map(|n| n.id.clone()).collect()
 	}
 
@@ -266,6 +288,68 @@ impl NodeTable {
 		self.useless_nodes.clear();
 	}
 
+	// Get the reputation of a node for update. If the table is full, forgotten reputations
+	// are dropped first, then the least notable one: not banned, with a score closest to zero.
+	fn reputation_mut(&mut self, id: &NodeId, now: u64) -> &mut Reputation {
+		if !self.reputations.contains_key(id) && self.reputations.len() >= MAX_REPUTATIONS {
+			self.reputations.retain(|_, r| !r.is_default(now));
+			if self.reputations.len() >= MAX_REPUTATIONS {
+				let evicted = self.reputations.iter()
+					.min_by_key(|&(_, r)| (r.is_banned(now), r.banned_until.unwrap_or(0), r.score.abs()))
+					.map(|(node, _)| node.clone());
+				if let Some(evicted) = evicted {
+					self.reputations.remove(&evicted);
+				}
+			}
+		}
+		self.reputations.entry(id.clone()).or_insert_with(Reputation::default)
+	}
+
+	/// Update the reputation of a node. Returns `true` if the node got banned.
+	pub fn report(&mut self, id: &NodeId, report: PeerReport) -> bool {
+		let now = unix_time();
+		let banned = self.reputation_mut(id, now).report(report, now);
+		if banned {
+			debug!(target: "network", "Banned node {} after {:?}", id, report);
+		}
+		banned
+	}
+
+	/// Ban a node for the given number of seconds.
+	pub fn ban(&mut self, id: &NodeId, duration: u64) {
+		let now = unix_time();
+		self.reputation_mut(id, now).ban(now.saturating_add(duration));
+	}
+
+	/// Decay all scores and drop reputations which have nothing left to keep.
+	pub fn decay_reputations(&mut self) {
+		let now = unix_time();
+		for reputation in self.reputations.values_mut() {
+			reputation.decay();
+		}
+		self.reputations.retain(|_, r| !r.is_default(now));
+	}
+
+	/// Check if a node is currently banned.
+	pub fn is_banned(&self, id: &NodeId) -> bool {
+		self.is_banned_at(id, unix_time())
+	}
+
+	fn is_banned_at(&self, id: &NodeId, now: u64) -> bool {
+		self.reputations.get(id).map_or(false, |r| r.is_banned(now))
+	}
+
+	/// Get the reputation of a node.
+	pub fn reputation(&self, id: &NodeId) -> Reputation {
+		self.reputations.get(id).cloned().unwrap_or_else(Reputation::default)
+	}
+
+	/// Reputations of all nodes which have been reported or banned.
+	pub fn reputations(&self) -> Vec<(NodeId, Reputation)> {
+		let now = unix_time();
+		self.reputations.iter().filter(|&(_, r)| !r.is_default(now)).map(|(id, r)| (id.clone(), *r)).collect()
+	}
+
 	/// Save the nodes.json file.
 	pub fn save(&self) {
 		if let Some(ref path) = self.path {
@@ -278,11 +362,19 @@ impl NodeTable {
 			let mut json = String::new();
 			json.push_str("{\n");
 			json.push_str("\"nodes\": [\n");
-			let node_ids = self.nodes(AllowIP::All);
+			let node_ids = self.ordered_nodes(|_| true);
 			for i in 0 .. node_ids.len() {
-				let node = self.nodes.get(&node_ids[i]).expect("self.nodes() only returns node IDs from self.nodes");
+				let node = self.nodes.get(&node_ids[i]).expect("self.ordered_nodes() only returns node IDs from self.nodes");
 				json.push_str(&format!("\t{{ \"url\": \"{}\", \"failures\": {} }}{}\n", node, node.failures, if i == node_ids.len() - 1 {""} else {","}))
 			}
+			json.push_str("],\n");
+			json.push_str("\"reputation\": [\n");
+			let reputations = self.reputations();
+			for i in 0 .. reputations.len() {
+				let (ref id, ref reputation) = reputations[i];
+				let banned_until = reputation.banned_until.map_or("null".to_owned(), |until| until.to_string());
+				json.push_str(&format!("\t{{ \"id\": \"{}\", \"score\": {}, \"bannedUntil\": {} }}{}\n", id.hex(), reputation.score, banned_until, if i == reputations.len() - 1 {""} else {","}))
+			}
 			json.push_str("]\n");
 			json.push_str("}");
 			let mut file = match fs::File::create(path_buf.as_path()) {
@@ -298,8 +390,9 @@ impl NodeTable {
 		}
 	}
 
-	fn load(path: Option<String>) -> HashMap<NodeId, Node> {
+	fn load(path: Option<String>) -> (HashMap<NodeId, Node>, HashMap<NodeId, Reputation>) {
 		let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+		let mut reputations: HashMap<NodeId, Reputation> = HashMap::new();
 		if let Some(path) = path {
 			let mut path_buf = PathBuf::from(path);
 			path_buf.push("nodes.json");
@@ -307,7 +400,7 @@ impl NodeTable {
 				Ok(file) => file,
 				Err(e) => {
 					debug!("Error opening node table file: {:?}", e);
-					return nodes;
+					return (nodes, reputations);
 				}
 			};
 			let mut buf = String::new();
@@ -315,14 +408,14 @@ impl NodeTable {
 				Ok(_) => {},
 				Err(e) => {
 					warn!("Error reading node table file: {:?}", e);
-					return nodes;
+					return (nodes, reputations);
 				}
 			}
 			let json = match Json::from_str(&buf) {
 				Ok(json) => json,
 				Err(e) => {
 					warn!("Error parsing node table file: {:?}", e);
-					return nodes;
+					return (nodes, reputations);
 				}
 			};
 			if let Some(list) = json.as_object().and_then(|o| o.get("nodes")).and_then(|n| n.as_array()) {
@@ -337,8 +430,19 @@ impl NodeTable {
 					}
 				}
 			}
+			if let Some(list) = json.as_object().and_then(|o| o.get("reputation")).and_then(|n| n.as_array()) {
+				for r in list.iter().filter_map(|r| r.as_object()).take(MAX_REPUTATIONS) {
+					if let Some(Ok(id)) = r.get("id").and_then(|i| i.as_string()).map(NodeId::from_str) {
+						let reputation = Reputation {
+							score: r.get("score").and_then(|s| s.as_i64()).unwrap_or(0) as i32,
+							banned_until: r.get("bannedUntil").and_then(|b| b.as_u64()),
+						};
+						reputations.insert(id, reputation);
+					}
+				}
+			}
 		}
-		nodes
+		(nodes, reputations)
 	}
 }
 
@@ -390,6 +494,25 @@ mod tests {
 			node.id);
 	}
 
+	#[test]
+	fn enr_node_parse() {
+		use ethkey::{Random, Generator};
+		use std::collections::BTreeMap;
+
+		let keys = Random.generate().unwrap();
+		let endpoint = NodeEndpoint { address: SocketAddr::from_str("22.99.55.44:7770").unwrap(), udp_port: 7771 };
+		let url = Enr::new(&keys, 1, &endpoint, &BTreeMap::new()).unwrap().to_string();
+		assert!(is_valid_node_url(&url));
+		let node = Node::from_str(&url).unwrap();
+		assert_eq!(node.id, *keys.public());
+		assert_eq!(node.endpoint.address, endpoint.address);
+		assert_eq!(node.endpoint.udp_port, 7771);
+
+		// a record without a TCP port can't be connected to.
+		assert!(!is_valid_node_url("enr:-IS4QHCYrYZbAKWCBRlAy5zzaDZXJBGkcnh4MHcBFZntXNFrdvJjX04jRzjzCBOonrkTfj499SZuOh8R33Ls8RRcy5wBgmlkgnY0gmlwhH8AAAGJc2VjcDI1NmsxoQPKY0yuDUmstAHYpMa2_oxVtw0RW_QAdpzBQA8yWM0xOIN1ZHCCdl8"));
+		assert!(!is_valid_node_url(&url[..url.len() - 2]));
+	}
+
 	#[test]
 	fn table_failure_order() {
 		let node1 = Node::from_str("enode://a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c@22.99.55.44:7770").unwrap();
@@ -434,4 +557,61 @@ mod tests {
 			assert_eq!(r[1][..], id2[..]);
 		}
 	}
+
+	#[test]
+	fn table_reputation() {
+		use reputation::PeerReport;
+
+		let temp_path = RandomTempPath::create_dir();
+		let id1 = H512::from_str("a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c").unwrap();
+		let id2 = H512::from_str("b979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c").unwrap();
+		let id3 = H512::from_str("c979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c").unwrap();
+		{
+			let mut table = NodeTable::new(Some(temp_path.as_path().to_str().unwrap().to_owned()));
+			for id in &[&id1, &id2, &id3] {
+				table.add_node(Node::from_str(&format!("enode://{}@22.99.55.44:7770", id.hex())).unwrap());
+			}
+
+			// high scores are preferred over fewer failures.
+			table.note_failure(&id2);
+			table.report(&id2, PeerReport::Success);
+			assert!(!table.report(&id1, PeerReport::Misbehaviour));
+			assert!(table.report(&id1, PeerReport::Misbehaviour));
+			table.ban(&id3, 1000);
+			assert!(table.is_banned(&id3));
+
+			assert_eq!(table.nodes(AllowIP::All), vec![id2.clone()]);
+			assert_eq!(table.reputations().len(), 3);
+		}
+
+		{
+			let table = NodeTable::new(Some(temp_path.as_path().to_str().unwrap().to_owned()));
+			assert_eq!(table.nodes(AllowIP::All), vec![id2.clone()]);
+			assert_eq!(table.reputation(&id2).score, 1);
+			assert!(table.is_banned(&id1));
+			assert!(table.is_banned(&id3));
+		}
+	}
+
+	#[test]
+	fn table_reputation_is_bounded() {
+		use reputation::PeerReport;
+
+		let mut table = NodeTable::new(None);
+		let banned = NodeId::from(1);
+		table.ban(&banned, u64::max_value());
+		assert!(table.is_banned(&banned));
+
+		for i in 2..(MAX_REPUTATIONS as u64 + 10) {
+			table.report(&NodeId::from(i), PeerReport::Useless);
+		}
+		assert_eq!(table.reputations().len(), MAX_REPUTATIONS);
+		assert!(table.is_banned(&banned));
+
+		for _ in 0..5 {
+			table.decay_reputations();
+		}
+		assert_eq!(table.reputations().len(), 1);
+		assert!(table.is_banned(&banned));
+	}
 }
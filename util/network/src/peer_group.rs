@@ -0,0 +1,299 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Static peer groups.
+//!
+//! A group is a named set of nodes with its own connection slots. The host keeps at least
+//! `min_slots` sessions with members of each group open, even if the peer limits are reached,
+//! and never more than `max_slots`. Members of trusted groups are dialed up to `max_slots`
+//! and bypass the peer limits, the reserved-only mode and bans.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use node_table::NodeId;
+use error::NetworkError;
+
+/// Static peer group configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerGroupConfig {
+	/// Group name.
+	pub name: String,
+	/// Node urls of the group members.
+	pub nodes: Vec<String>,
+	/// Number of sessions with group members kept open regardless of the peer limits.
+	pub min_slots: u32,
+	/// Maximum number of sessions with group members.
+	pub max_slots: u32,
+	/// Members bypass the peer limits, the reserved-only mode and bans.
+	pub trusted: bool,
+}
+
+/// Peer group state.
+#[derive(Debug, Clone, PartialEq)]
+struct PeerGroup {
+	min_slots: u32,
+	max_slots: u32,
+	trusted: bool,
+	nodes: HashSet<NodeId>,
+}
+
+impl PeerGroup {
+	/// Number of sessions the host tries to keep open.
+	fn target(&self) -> usize {
+		if self.trusted { self.max_slots as usize } else { self.min_slots as usize }
+	}
+}
+
+/// Peer group summary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerGroupInfo {
+	/// Group name.
+	pub name: String,
+	/// Number of sessions with group members kept open regardless of the peer limits.
+	pub min_slots: u32,
+	/// Maximum number of sessions with group members.
+	pub max_slots: u32,
+	/// Members bypass the peer limits, the reserved-only mode and bans.
+	pub trusted: bool,
+	/// Group members.
+	pub nodes: Vec<NodeId>,
+	/// Number of group members with an established session.
+	pub connected: usize,
+}
+
+/// Decision for a new session with a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+	/// The node is not a member of any group.
+	Regular,
+	/// The session takes a group slot and ignores the peer limits.
+	Slot,
+	/// The session is subject to the peer limits.
+	Limited,
+	/// All groups of the node are full.
+	Full,
+}
+
+/// Named peer groups.
+#[derive(Debug, Default)]
+pub struct PeerGroups {
+	groups: BTreeMap<String, PeerGroup>,
+	/// Number of established sessions by node.
+	sessions: HashMap<NodeId, usize>,
+}
+
+impl PeerGroups {
+	/// Create a group or update the slots of an existing one. Members are kept.
+	pub fn set_group(&mut self, name: &str, min_slots: u32, max_slots: u32, trusted: bool) -> Result<(), NetworkError> {
+		if name.is_empty() || min_slots > max_slots {
+			return Err(NetworkError::InvalidPeerGroup);
+		}
+		let group = self.groups.entry(name.to_owned()).or_insert_with(|| PeerGroup {
+			min_slots: 0,
+			max_slots: 0,
+			trusted: false,
+			nodes: HashSet::new(),
+		});
+		group.min_slots = min_slots;
+		group.max_slots = max_slots;
+		group.trusted = trusted;
+		Ok(())
+	}
+
+	/// Remove a group.
+	pub fn remove_group(&mut self, name: &str) -> Result<(), NetworkError> {
+		self.groups.remove(name).map(|_| ()).ok_or(NetworkError::UnknownPeerGroup)
+	}
+
+	/// Add a node to a group.
+	pub fn add_node(&mut self, name: &str, id: NodeId) -> Result<(), NetworkError> {
+		let group = self.groups.get_mut(name).ok_or(NetworkError::UnknownPeerGroup)?;
+		group.nodes.insert(id);
+		Ok(())
+	}
+
+	/// Remove a node from a group.
+	pub fn remove_node(&mut self, name: &str, id: &NodeId) -> Result<(), NetworkError> {
+		let group = self.groups.get_mut(name).ok_or(NetworkError::UnknownPeerGroup)?;
+		group.nodes.remove(id);
+		Ok(())
+	}
+
+	/// Check if the node is a member of any group.
+	pub fn contains(&self, id: &NodeId) -> bool {
+		self.groups.values().any(|g| g.nodes.contains(id))
+	}
+
+	/// Check if the node is a member of a trusted group.
+	pub fn is_trusted(&self, id: &NodeId) -> bool {
+		self.groups.values().any(|g| g.trusted && g.nodes.contains(id))
+	}
+
+	/// Members of all groups.
+	pub fn nodes(&self) -> HashSet<NodeId> {
+		self.groups.values().flat_map(|g| g.nodes.iter().cloned()).collect()
+	}
+
+	/// Members of trusted groups.
+	pub fn trusted_nodes(&self) -> HashSet<NodeId> {
+		self.groups.values().filter(|g| g.trusted).flat_map(|g| g.nodes.iter().cloned()).collect()
+	}
+
+	/// Note an established session.
+	pub fn session_started(&mut self, id: &NodeId) {
+		*self.sessions.entry(id.clone()).or_insert(0) += 1;
+	}
+
+	/// Note a closed session which has been established before.
+	pub fn session_ended(&mut self, id: &NodeId) {
+		let remove = match self.sessions.get_mut(id) {
+			Some(count) => {
+				*count -= 1;
+				*count == 0
+			},
+			None => false,
+		};
+		if remove {
+			self.sessions.remove(id);
+		}
+	}
+
+	fn connected(&self, group: &PeerGroup) -> usize {
+		group.nodes.iter().filter(|id| self.sessions.contains_key(*id)).count()
+	}
+
+	/// Decide if a new session with the node may be kept. Must be called before `session_started`.
+	pub fn admit(&self, id: &NodeId) -> Admission {
+		let mut admission = Admission::Regular;
+		for group in self.groups.values().filter(|g| g.nodes.contains(id)) {
+			let count = self.connected(group);
+			if count >= group.max_slots as usize {
+				if admission == Admission::Regular {
+					admission = Admission::Full;
+				}
+			} else if group.trusted || count < group.min_slots as usize {
+				return Admission::Slot;
+			} else {
+				admission = Admission::Limited;
+			}
+		}
+		admission
+	}
+
+	/// Members which should be dialed to fill the group slots.
+	pub fn wanted(&self) -> Vec<NodeId> {
+		let mut wanted = Vec::new();
+		for group in self.groups.values() {
+			let missing = group.target().saturating_sub(self.connected(group));
+			let members: Vec<NodeId> = group.nodes.iter()
+				.filter(|id| !self.sessions.contains_key(*id) && !wanted.contains(*id))
+				.take(missing)
+				.cloned()
+				.collect();
+			wanted.extend(members);
+		}
+		wanted
+	}
+
+	/// Summary of all groups ordered by name.
+	pub fn info(&self) -> Vec<PeerGroupInfo> {
+		self.groups.iter().map(|(name, group)| PeerGroupInfo {
+			name: name.clone(),
+			min_slots: group.min_slots,
+			max_slots: group.max_slots,
+			trusted: group.trusted,
+			nodes: group.nodes.iter().cloned().collect(),
+			connected: self.connected(group),
+		}).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use util::hash::H512;
+	use super::*;
+
+	fn ids(n: u64) -> Vec<NodeId> {
+		(1..n + 1).map(H512::from).collect()
+	}
+
+	#[test]
+	fn validates_groups() {
+		let id = ids(1).pop().unwrap();
+		let mut groups = PeerGroups::default();
+		assert!(groups.set_group("", 0, 1, false).is_err());
+		assert!(groups.set_group("validators", 2, 1, false).is_err());
+		assert!(groups.set_group("validators", 1, 2, false).is_ok());
+		assert!(groups.add_node("archive", id.clone()).is_err());
+		assert!(groups.add_node("validators", id.clone()).is_ok());
+
+		// updating the slots keeps the members.
+		groups.set_group("validators", 0, 5, true).unwrap();
+		assert!(groups.is_trusted(&id));
+		assert_eq!(groups.info()[0].nodes, vec![id.clone()]);
+		groups.remove_group("validators").unwrap();
+		assert!(!groups.contains(&id));
+		assert!(groups.remove_group("validators").is_err());
+	}
+
+	#[test]
+	fn admits_within_slots() {
+		let nodes = ids(4);
+		let mut groups = PeerGroups::default();
+		groups.set_group("backups", 1, 2, false).unwrap();
+		for id in &nodes[0..3] {
+			groups.add_node("backups", id.clone()).unwrap();
+		}
+
+		assert_eq!(groups.admit(&nodes[3]), Admission::Regular);
+		assert_eq!(groups.admit(&nodes[0]), Admission::Slot);
+		groups.session_started(&nodes[0]);
+		assert_eq!(groups.admit(&nodes[1]), Admission::Limited);
+		groups.session_started(&nodes[1]);
+		assert_eq!(groups.admit(&nodes[2]), Admission::Full);
+		assert_eq!(groups.info()[0].connected, 2);
+
+		// a trusted group with free slots wins.
+		groups.set_group("validators", 0, 1, true).unwrap();
+		groups.add_node("validators", nodes[2].clone()).unwrap();
+		assert_eq!(groups.admit(&nodes[2]), Admission::Slot);
+
+		groups.session_ended(&nodes[1]);
+		groups.remove_group("validators").unwrap();
+		assert_eq!(groups.admit(&nodes[2]), Admission::Limited);
+	}
+
+	#[test]
+	fn dials_missing_members() {
+		let nodes = ids(5);
+		let mut groups = PeerGroups::default();
+		groups.set_group("backups", 1, 3, false).unwrap();
+		groups.set_group("validators", 0, 2, true).unwrap();
+		for id in &nodes[0..3] {
+			groups.add_node("backups", id.clone()).unwrap();
+		}
+		for id in &nodes[3..5] {
+			groups.add_node("validators", id.clone()).unwrap();
+		}
+
+		let wanted = groups.wanted();
+		assert_eq!(wanted.len(), 3);
+		assert!(wanted.contains(&nodes[3]) && wanted.contains(&nodes[4]));
+
+		groups.session_started(&nodes[0]);
+		groups.session_started(&nodes[3]);
+		assert_eq!(groups.wanted(), vec![nodes[4].clone()]);
+	}
+}
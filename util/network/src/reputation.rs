@@ -0,0 +1,172 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Peer reputation.
+//!
+//! Protocol handlers report the outcome of exchanges with peers and every report moves
+//! the score of the peer. A peer whose score drops to `BAN_SCORE` is banned for
+//! `BAN_DURATION_SEC`. Scores decay towards zero over time, so old reports are
+//! forgotten. Scores and bans are kept by the node table and saved with it.
+
+use std::cmp;
+
+/// Highest score a peer can reach.
+pub const MAX_SCORE: i32 = 100;
+/// Score at which a peer gets banned.
+pub const BAN_SCORE: i32 = -100;
+/// Duration of a ban caused by a low score.
+pub const BAN_DURATION_SEC: u64 = 60 * 60;
+/// Longest accepted manual ban.
+pub const MAX_BAN_DURATION_SEC: u64 = 30 * 24 * 60 * 60;
+/// Score a peer loses or regains towards zero on every decay round.
+pub const SCORE_DECAY: i32 = 1;
+
+/// Outcome of an exchange with a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerReport {
+	/// The peer answered with useful data.
+	Success,
+	/// The peer answered with empty or useless data.
+	Useless,
+	/// The peer did not answer in time.
+	Timeout,
+	/// The peer violated the protocol.
+	Misbehaviour,
+}
+
+impl PeerReport {
+	fn score(&self) -> i32 {
+		match *self {
+			PeerReport::Success => 1,
+			PeerReport::Useless => -5,
+			PeerReport::Timeout => -10,
+			PeerReport::Misbehaviour => -50,
+		}
+	}
+}
+
+/// Reputation of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Reputation {
+	/// Score between `BAN_SCORE` and `MAX_SCORE`.
+	pub score: i32,
+	/// Unix time until which the node is banned.
+	pub banned_until: Option<u64>,
+}
+
+impl Reputation {
+	/// Apply a report at the given unix time. Returns `true` if the node got banned.
+	pub fn report(&mut self, report: PeerReport, now: u64) -> bool {
+		self.score = cmp::max(BAN_SCORE, cmp::min(MAX_SCORE, self.score + report.score()));
+		if self.score == BAN_SCORE && !self.is_banned(now) {
+			self.ban(now + BAN_DURATION_SEC);
+			return true;
+		}
+		false
+	}
+
+	/// Ban the node until the given unix time. The score is lowered, so that
+	/// a node misbehaving again after the ban is banned sooner.
+	pub fn ban(&mut self, until: u64) {
+		self.banned_until = Some(cmp::max(until, self.banned_until.unwrap_or(0)));
+		self.score = cmp::min(self.score, BAN_SCORE / 2);
+	}
+
+	/// Move the score `SCORE_DECAY` towards zero.
+	pub fn decay(&mut self) {
+		self.score -= self.score.signum() * cmp::min(SCORE_DECAY, self.score.abs());
+	}
+
+	/// Check if the node is banned at the given unix time.
+	pub fn is_banned(&self, now: u64) -> bool {
+		self.banned_until.map_or(false, |until| until > now)
+	}
+
+	/// Check if there is anything worth keeping at the given unix time.
+	pub fn is_default(&self, now: u64) -> bool {
+		self.score == 0 && !self.is_banned(now)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bans_misbehaving_peers() {
+		let mut reputation = Reputation::default();
+		assert!(!reputation.report(PeerReport::Misbehaviour, 1000));
+		assert_eq!(reputation.score, -50);
+		assert!(reputation.report(PeerReport::Misbehaviour, 1000));
+		assert!(reputation.is_banned(1000));
+		assert!(!reputation.is_banned(1000 + BAN_DURATION_SEC));
+		assert_eq!(reputation.score, BAN_SCORE / 2);
+
+		// banned again sooner after the ban expires.
+		let now = 1000 + BAN_DURATION_SEC;
+		assert!(reputation.report(PeerReport::Misbehaviour, now));
+		assert_eq!(reputation.banned_until, Some(now + BAN_DURATION_SEC));
+	}
+
+	#[test]
+	fn scores_are_bounded() {
+		let mut reputation = Reputation::default();
+		for _ in 0..200 {
+			reputation.report(PeerReport::Success, 0);
+		}
+		assert_eq!(reputation.score, MAX_SCORE);
+		assert!(!reputation.is_default(0));
+
+		for _ in 0..19 {
+			assert!(!reputation.report(PeerReport::Timeout, 0));
+		}
+		assert_eq!(reputation.score, -90);
+		assert!(!reputation.report(PeerReport::Useless, 0));
+		assert!(reputation.report(PeerReport::Useless, 0));
+		assert!(reputation.is_banned(0));
+		assert_eq!(reputation.score, BAN_SCORE / 2);
+	}
+
+	#[test]
+	fn scores_decay_towards_zero() {
+		let mut reputation = Reputation::default();
+		reputation.report(PeerReport::Useless, 0);
+		reputation.report(PeerReport::Success, 0);
+		assert_eq!(reputation.score, -4);
+		for _ in 0..3 {
+			reputation.decay();
+		}
+		assert_eq!(reputation.score, -1);
+		reputation.decay();
+		reputation.decay();
+		assert!(reputation.is_default(0));
+
+		reputation.report(PeerReport::Success, 0);
+		reputation.decay();
+		assert_eq!(reputation.score, 0);
+	}
+
+	#[test]
+	fn manual_bans_never_shorten() {
+		let mut reputation = Reputation::default();
+		reputation.ban(500);
+		reputation.ban(100);
+		assert_eq!(reputation.banned_until, Some(500));
+		assert_eq!(reputation.score, BAN_SCORE / 2);
+		assert!(!reputation.is_default(0));
+		assert!(!reputation.is_default(600));
+	}
+}
@@ -18,6 +18,9 @@ use {NetworkProtocolHandler, NetworkConfiguration, NonReservedPeerMode};
 use error::NetworkError;
 use host::{Host, NetworkContext, NetworkIoMessage, PeerId, ProtocolId};
 use stats::NetworkStats;
+use node_table::NodeId;
+use reputation::Reputation;
+use peer_group::PeerGroupInfo;
 use io::*;
 use parking_lot::RwLock;
 use std::sync::Arc;
@@ -109,6 +112,12 @@ impl NetworkService {
 		host.as_ref().and_then(|h| h.external_url())
 	}
 
+	/// Returns the node record of the external address if available.
+	pub fn local_enr(&self) -> Option<String> {
+		let host = self.host.read();
+		host.as_ref().and_then(|h| h.local_enr())
+	}
+
 	/// Returns external url if available.
 	pub fn local_url(&self) -> Option<String> {
 		let host = self.host.read();
@@ -167,6 +176,69 @@ impl NetworkService {
 		}
 	}
 
+	/// Create a peer group or update the slots of an existing one.
+	pub fn set_peer_group(&self, name: &str, min_slots: u32, max_slots: u32, trusted: bool) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.set_peer_group(name, min_slots, max_slots, trusted)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Remove a peer group.
+	pub fn remove_peer_group(&self, name: &str) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.remove_peer_group(name)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Add a peer to a peer group.
+	pub fn add_peer_group_node(&self, group: &str, peer: &str) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.add_peer_group_node(group, peer)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Remove a peer from a peer group.
+	pub fn remove_peer_group_node(&self, group: &str, peer: &str) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			host.remove_peer_group_node(group, peer)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Returns the peer groups ordered by name.
+	pub fn peer_groups(&self) -> Vec<PeerGroupInfo> {
+		let host = self.host.read();
+		host.as_ref().map_or_else(Vec::new, |h| h.peer_groups())
+	}
+
+	/// Ban a peer given by its node id or url for `duration` seconds.
+	pub fn ban_peer(&self, peer: &str, duration: u64) -> Result<(), NetworkError> {
+		let host = self.host.read();
+		if let Some(ref host) = *host {
+			let io_ctxt = IoContext::new(self.io_service.channel(), 0);
+			host.ban_node(peer, duration, &io_ctxt)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Returns reputations of the peers which have been reported or banned.
+	pub fn peer_reputations(&self) -> Vec<(NodeId, Reputation)> {
+		let host = self.host.read();
+		host.as_ref().map_or_else(Vec::new, |h| h.node_reputations())
+	}
+
 	/// Set the non-reserved peer mode.
 	pub fn set_non_reserved_mode(&self, mode: NonReservedPeerMode) {
 		let host = self.host.read();
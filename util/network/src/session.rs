@@ -22,8 +22,8 @@ use std::collections::HashMap;
 
 use mio::*;
 use mio::deprecated::{Handler, EventLoop};
-use mio::tcp::*;
 use util::hash::*;
+use util::snappy;
 use rlp::*;
 use connection::{EncryptedConnection, Packet, Connection};
 use handshake::Handshake;
@@ -31,13 +31,22 @@ use io::{IoContext, StreamToken};
 use error::{NetworkError, DisconnectReason};
 use host::*;
 use node_table::NodeId;
-use stats::NetworkStats;
+use stats::{NetworkStats, TrafficStats};
+use bandwidth::BandwidthLimiter;
+use transport::Stream;
 use time;
 
 // Timeout must be less than (interval - 1).
 const PING_TIMEOUT_SEC: u64 = 15;
 const PING_INTERVAL_SEC: u64 = 30;
 
+// Lowest p2p protocol version of peers we talk to.
+const MIN_PROTOCOL_VERSION: u32 = 4;
+// Packet payloads are snappy-compressed if both peers support this p2p protocol version.
+const SNAPPY_PROTOCOL_VERSION: u32 = 5;
+// Largest packet payload accepted after decompression.
+const MAX_PAYLOAD_SIZE: usize = (1 << 24) - 1;
+
 #[derive(Debug, Clone)]
 enum ProtocolState {
 	// Packets pending protocol on_connect event return.
@@ -59,6 +68,9 @@ pub struct Session {
 	ping_time_ns: u64,
 	pong_time_ns: Option<u64>,
 	state: State,
+	/// Packet payloads are snappy-compressed. Set after Hello packet exchange with a v5 peer.
+	compression: bool,
+	stats: Arc<NetworkStats>,
 	// Protocol states -- accumulates pending packets until signaled as ready.	
 	protocol_states: HashMap<ProtocolId, ProtocolState>,		
 }
@@ -107,6 +119,10 @@ pub struct SessionInfo {
 	pub remote_address: String,
 	/// Local endpoint address of the session
 	pub local_address: String,
+	/// Bytes sent and received over the connection, including framing
+	pub traffic: TrafficStats,
+	/// Packet payload bytes sent and received per protocol, before compression
+	pub protocol_traffic: HashMap<ProtocolId, TrafficStats>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -174,11 +190,11 @@ const PACKET_LAST: u8 = 0x7f;
 impl Session {
 	/// Create a new session out of comepleted handshake. This clones the handshake connection object
 	/// and leaves the handhsake in limbo to be deregistered from the event loop.
-	pub fn new<Message>(io: &IoContext<Message>, socket: TcpStream, token: StreamToken, id: Option<&NodeId>,
-		nonce: &H256, stats: Arc<NetworkStats>, host: &HostInfo) -> Result<Session, NetworkError>
+	pub fn new<Message>(io: &IoContext<Message>, socket: Box<Stream>, token: StreamToken, id: Option<&NodeId>,
+		nonce: &H256, stats: Arc<NetworkStats>, limiter: Arc<BandwidthLimiter>, host: &HostInfo) -> Result<Session, NetworkError>
 		where Message: Send + Clone + Sync + 'static {
 		let originated = id.is_some();
-		let mut handshake = Handshake::new(token, id, socket, nonce, stats).expect("Can't create handshake");
+		let mut handshake = Handshake::new(token, id, socket, nonce, stats.clone(), limiter).expect("Can't create handshake");
 		let local_addr = handshake.connection.local_addr_str();
 		handshake.start(io, host, originated)?;
 		Ok(Session {
@@ -194,9 +210,13 @@ impl Session {
 				originated: originated,
 				remote_address: "Handshake".to_owned(),
 				local_address: local_addr,
+				traffic: TrafficStats::default(),
+				protocol_traffic: HashMap::new(),
 			},
 			ping_time_ns: 0,
 			pong_time_ns: None,
+			compression: false,
+			stats: stats,
 			expired: false,
 			protocol_states: HashMap::new(),			
 		})
@@ -212,7 +232,10 @@ impl Session {
 		};
 		self.state = State::Session(connection);
 		self.write_hello(io, host)?;
-		self.send_ping(io)?;
+		// the first ping is sent once compression is negotiated; until then the
+		// Hello packet is subject to the ping timeout.
+		self.ping_time_ns = time::precise_time_ns();
+		self.pong_time_ns = None;
 		Ok(())
 	}
 
@@ -223,6 +246,18 @@ impl Session {
 		}
 	}
 
+	fn connection_mut(&mut self) -> &mut Connection {
+		match self.state {
+			State::Handshake(ref mut h) => &mut h.connection,
+			State::Session(ref mut s) => &mut s.connection,
+		}
+	}
+
+	fn update_traffic(&mut self) {
+		let (sent, received) = self.connection().traffic();
+		self.info.traffic = TrafficStats { sent: sent, received: received };
+	}
+
 	/// Get id of the remote peer
 	pub fn id(&self) -> Option<&NodeId> {
 		self.info.id.as_ref()
@@ -256,8 +291,24 @@ impl Session {
 		self.connection().remote_addr()
 	}
 
+	/// Check if IO was stopped by the bandwidth limits.
+	pub fn is_throttled(&self) -> bool {
+		self.connection().is_throttled()
+	}
+
+	/// Resume IO stopped by the bandwidth limits. Should be followed by a `readable` call.
+	pub fn unthrottle<Message>(&mut self, io: &IoContext<Message>) where Message: Send + Sync + Clone {
+		self.connection_mut().unthrottle(io);
+	}
+
 	/// Readable IO handler. Returns packet data if available.
 	pub fn readable<Message>(&mut self, io: &IoContext<Message>, host: &HostInfo) -> Result<SessionData, NetworkError>  where Message: Send + Sync + Clone {
+		let result = self.read(io, host);
+		self.update_traffic();
+		result
+	}
+
+	fn read<Message>(&mut self, io: &IoContext<Message>, host: &HostInfo) -> Result<SessionData, NetworkError>  where Message: Send + Sync + Clone {
 		if self.expired() {
 			return Ok(SessionData::None)
 		}
@@ -289,10 +340,12 @@ impl Session {
 
 	/// Writable IO handler. Sends pending packets.
 	pub fn writable<Message>(&mut self, io: &IoContext<Message>, _host: &HostInfo) -> Result<(), NetworkError> where Message: Send + Sync + Clone {
-		match self.state {
+		let result = match self.state {
 			State::Handshake(ref mut h) => h.writable(io),
 			State::Session(ref mut s) => s.writable(io),
-		}
+		};
+		self.update_traffic();
+		result
 	}
 
 	/// Checks if peer supports given capability
@@ -345,6 +398,7 @@ impl Session {
 			}
 		}
 		let pid = self.info.capabilities[i].id_offset + packet_id;
+		self.info.protocol_traffic.entry(protocol).or_insert_with(TrafficStats::default).sent += data.len();
 		let mut rlp = RlpStream::new();
 		rlp.append(&(pid as u32));
 		rlp.append_raw(data, 1);
@@ -388,11 +442,16 @@ impl Session {
 		}
 	}
 
-	fn read_packet<Message>(&mut self, io: &IoContext<Message>, packet: Packet, host: &HostInfo) -> Result<SessionData, NetworkError>
+	fn read_packet<Message>(&mut self, io: &IoContext<Message>, mut packet: Packet, host: &HostInfo) -> Result<SessionData, NetworkError>
 	where Message: Send + Sync + Clone {
 		if packet.data.len() < 2 {
 			return Err(From::from(NetworkError::BadProtocol));
 		}
+		if self.compression {
+			let compressed_len = packet.data.len();
+			packet.data = decompress_packet(&packet.data)?;
+			self.stats.inc_compressed_recv(packet.data.len() - 1, compressed_len - 1);
+		}
 		let packet_id = packet.data[0];
 		if packet_id != PACKET_HELLO && packet_id != PACKET_DISCONNECT && !self.had_hello {
 			return Err(From::from(NetworkError::BadProtocol));
@@ -436,6 +495,7 @@ impl Session {
 				// map to protocol
 				let protocol = self.info.capabilities[i].protocol;
 				let protocol_packet_id = packet_id - self.info.capabilities[i].id_offset;
+				self.info.protocol_traffic.entry(protocol).or_insert_with(TrafficStats::default).received += packet.data.len() - 1;
 
 				match *self.protocol_states.entry(protocol).or_insert_with(|| ProtocolState::Pending(Vec::new())) {
 					ProtocolState::Connected => {
@@ -520,11 +580,13 @@ impl Session {
 			trace!(target: "network", "No common capabilities with peer.");
 			return Err(From::from(self.disconnect(io, DisconnectReason::UselessPeer)));
 		}
-		if protocol != host.protocol_version {
+		if protocol < MIN_PROTOCOL_VERSION {
 			trace!(target: "network", "Peer protocol version mismatch: {}", protocol);
 			return Err(From::from(self.disconnect(io, DisconnectReason::UselessPeer)));
 		}
 		self.had_hello = true;
+		self.compression = protocol >= SNAPPY_PROTOCOL_VERSION && host.protocol_version >= SNAPPY_PROTOCOL_VERSION;
+		self.send_ping(io)?;
 		Ok(())
 	}
 
@@ -565,10 +627,75 @@ impl Session {
 				warn!(target:"network", "Unexpected send request");
 			},
 			State::Session(ref mut s) => {
-				s.send_packet(io, &rlp.out())?
+				let packet = rlp.out();
+				if self.compression {
+					let compressed = compress_packet(&packet)?;
+					self.stats.inc_compressed_send(packet.len() - 1, compressed.len() - 1);
+					s.send_packet(io, &compressed)?
+				} else {
+					s.send_packet(io, &packet)?
+				}
 			},
 		}
 		Ok(())
 	}
 }
 
+/// Compress the payload of a packet, leaving its single byte packet id as it is.
+fn compress_packet(packet: &[u8]) -> Result<Vec<u8>, NetworkError> {
+	if packet.len() - 1 > MAX_PAYLOAD_SIZE {
+		warn!(target: "network", "Packet payload too large to send: {} bytes", packet.len() - 1);
+		return Err(NetworkError::BadProtocol);
+	}
+	let mut compressed = vec![packet[0]];
+	compressed.extend_from_slice(&snappy::compress(&packet[1..]));
+	Ok(compressed)
+}
+
+/// Decompress the payload of a packet, rejecting payloads which would decompress beyond `MAX_PAYLOAD_SIZE`.
+fn decompress_packet(packet: &[u8]) -> Result<Vec<u8>, NetworkError> {
+	let len = snappy::decompressed_len(&packet[1..]).map_err(|_| NetworkError::BadProtocol)?;
+	if len > MAX_PAYLOAD_SIZE {
+		debug!(target: "network", "Packet payload decompresses to {} bytes, rejecting", len);
+		return Err(NetworkError::BadProtocol);
+	}
+	let mut payload = Vec::with_capacity(len);
+	snappy::decompress_into(&packet[1..], &mut payload).map_err(|_| NetworkError::BadProtocol)?;
+
+	let mut decompressed = Vec::with_capacity(len + 1);
+	decompressed.push(packet[0]);
+	decompressed.extend_from_slice(&payload[..len]);
+	Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{compress_packet, decompress_packet, MAX_PAYLOAD_SIZE};
+	use util::snappy;
+
+	#[test]
+	fn packet_compression_roundtrip() {
+		let mut packet = vec![0x10u8];
+		packet.extend((0..4096).map(|i| (i % 7) as u8));
+
+		let compressed = compress_packet(&packet).unwrap();
+		assert_eq!(compressed[0], 0x10);
+		assert!(compressed.len() < packet.len() / 4);
+		assert_eq!(decompress_packet(&compressed).unwrap(), packet);
+
+		// an empty payload still round-trips.
+		let compressed = compress_packet(&[0x02, 0xc0]).unwrap();
+		assert_eq!(decompress_packet(&compressed).unwrap(), vec![0x02, 0xc0]);
+	}
+
+	#[test]
+	fn rejects_bad_compressed_payloads() {
+		assert!(decompress_packet(&[0x10, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+
+		let bomb = vec![0u8; MAX_PAYLOAD_SIZE + 1];
+		let mut packet = vec![0x10u8];
+		packet.extend_from_slice(&snappy::compress(&bomb));
+		assert!(decompress_packet(&packet).is_err());
+	}
+}
+
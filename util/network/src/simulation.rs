@@ -0,0 +1,1077 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! In-process network simulation.
+//!
+//! `Simulation` runs any number of hosts on the calling thread, connected by virtual sockets.
+//! Time is simulated: IO timers, link latency and bandwidth follow a virtual clock which only
+//! advances while the simulation runs, and packet loss is drawn from a seeded generator. Tests
+//! do not depend on thread scheduling or real sockets, although hosts still use random
+//! handshake keys and hash map ordering. Session pings, bans and discovery packet expiry are
+//! measured with the wall clock and are not affected by the simulated time.
+//!
+//! Streams are reliable and ordered: a lost segment is delivered one round trip later.
+//! Lost datagrams are dropped. A partition holds stream data until it is healed and drops
+//! datagrams and connection attempts.
+
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use mio::{Evented, Poll, Token, Ready, PollOpt};
+use mio::deprecated::EventLoop;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use parking_lot::Mutex;
+use ethkey::Secret;
+use util::Hashable;
+use io::{IoContext, IoChannel, IoHandler, IoManager, IoMessage, IoMessageQueue, TimerToken, StreamToken, TOKENS_PER_HANDLER};
+use host::{Host, NetworkConfiguration, NetworkContext, NetworkIoMessage, ProtocolId};
+use node_table::NodeId;
+use stats::NetworkStats;
+use transport::{Transport, Stream, Listener, Datagram};
+use NetworkProtocolHandler;
+
+const DEFAULT_PORT: u16 = 30303;
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+// Number of dispatch rounds without any time passing after which the simulation is considered stuck.
+const MAX_ROUNDS: usize = 100_000;
+
+/// Properties of the link between two nodes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkConfig {
+	/// One way delay in milliseconds.
+	pub latency: u64,
+	/// Probability of losing a packet, from 0 to 1.
+	pub loss: f64,
+	/// Capacity in bytes per second in each direction. Unlimited if zero.
+	pub bandwidth: usize,
+}
+
+impl Default for LinkConfig {
+	fn default() -> Self {
+		LinkConfig {
+			latency: 10,
+			loss: 0.0,
+			bandwidth: 0,
+		}
+	}
+}
+
+enum Segment {
+	Data(Vec<u8>),
+	Fin,
+}
+
+enum Event {
+	Timer { node: usize, token: TimerToken, generation: u64 },
+	Connect { stream: usize, address: SocketAddr },
+	Accepted { stream: usize, remote: (usize, usize) },
+	Refused { stream: usize },
+	Segment { stream: usize, segment: Segment },
+	Datagram { from: SocketAddr, to: SocketAddr, data: Vec<u8> },
+}
+
+/// Result of processing a scheduled event.
+enum Fired {
+	Timer(usize, TimerToken),
+	Network,
+}
+
+struct StreamState {
+	peer: SocketAddr,
+	/// Socket id and node of the other end.
+	remote: Option<(usize, usize)>,
+	connected: bool,
+	received: VecDeque<u8>,
+	eof: bool,
+	reset: bool,
+	closed: bool,
+	/// Arrival time of the last segment sent, keeps segments in order.
+	last_arrival: u64,
+	/// Segments held back by a partition.
+	held: VecDeque<Segment>,
+}
+
+enum SocketKind {
+	Stream(StreamState),
+	Listener(VecDeque<usize>),
+	Datagram(VecDeque<(SocketAddr, Vec<u8>)>),
+}
+
+struct SocketState {
+	node: usize,
+	local: SocketAddr,
+	handles: usize,
+	token: Option<Token>,
+	interest: Ready,
+	/// Events not reported yet.
+	ready: Ready,
+	kind: SocketKind,
+}
+
+impl SocketState {
+	fn is_writable(&self) -> bool {
+		match self.kind {
+			SocketKind::Stream(ref s) => s.connected && !s.reset,
+			SocketKind::Listener(_) => false,
+			SocketKind::Datagram(_) => true,
+		}
+	}
+
+	fn is_readable(&self) -> bool {
+		match self.kind {
+			SocketKind::Stream(ref s) => !s.received.is_empty(),
+			SocketKind::Listener(ref backlog) => !backlog.is_empty(),
+			SocketKind::Datagram(ref queue) => !queue.is_empty(),
+		}
+	}
+
+	fn stream(&mut self) -> &mut StreamState {
+		match self.kind {
+			SocketKind::Stream(ref mut s) => s,
+			_ => panic!("Socket is not a stream"),
+		}
+	}
+}
+
+struct Timer {
+	delay: u64,
+	once: bool,
+	generation: u64,
+}
+
+/// Shared state of all virtual sockets. Times are in microseconds.
+struct Fabric {
+	now: u64,
+	seq: u64,
+	rng: XorShiftRng,
+	events: BTreeMap<(u64, u64), Event>,
+	sockets: BTreeMap<usize, SocketState>,
+	next_socket: usize,
+	listeners: HashMap<SocketAddr, usize>,
+	datagrams: HashMap<SocketAddr, usize>,
+	ips: Vec<IpAddr>,
+	nodes: HashMap<IpAddr, usize>,
+	next_port: Vec<u16>,
+	default_link: LinkConfig,
+	links: HashMap<(usize, usize), LinkConfig>,
+	busy_until: HashMap<(usize, usize), u64>,
+	blocked: HashSet<(usize, usize)>,
+	timers: HashMap<(usize, TimerToken), Timer>,
+	timer_generation: u64,
+}
+
+impl Fabric {
+	fn new(seed: u64) -> Fabric {
+		let seed = [seed as u32 ^ 0x193a_6754, (seed >> 32) as u32 ^ 0xa8a7_d469, 0x9783_0e05, 0x113b_a7bb];
+		Fabric {
+			now: 0,
+			seq: 0,
+			rng: XorShiftRng::from_seed(seed),
+			events: BTreeMap::new(),
+			sockets: BTreeMap::new(),
+			next_socket: 0,
+			listeners: HashMap::new(),
+			datagrams: HashMap::new(),
+			ips: Vec::new(),
+			nodes: HashMap::new(),
+			next_port: Vec::new(),
+			default_link: LinkConfig::default(),
+			links: HashMap::new(),
+			busy_until: HashMap::new(),
+			blocked: HashSet::new(),
+			timers: HashMap::new(),
+			timer_generation: 0,
+		}
+	}
+
+	fn add_node(&mut self, ip: IpAddr) {
+		self.nodes.insert(ip, self.ips.len());
+		self.ips.push(ip);
+		self.next_port.push(FIRST_EPHEMERAL_PORT);
+	}
+
+	fn schedule(&mut self, at: u64, event: Event) {
+		self.seq += 1;
+		self.events.insert((at, self.seq), event);
+	}
+
+	fn link(&self, from: usize, to: usize) -> LinkConfig {
+		self.links.get(&(cmp::min(from, to), cmp::max(from, to))).cloned().unwrap_or(self.default_link)
+	}
+
+	fn is_lost(&mut self, from: usize, to: usize) -> bool {
+		let loss = self.link(from, to).loss;
+		loss > 0.0 && self.rng.gen::<f64>() < loss
+	}
+
+	/// Arrival time of a packet of the given size sent now.
+	fn transmit(&mut self, from: usize, to: usize, size: usize) -> u64 {
+		let link = self.link(from, to);
+		let mut sent = self.now;
+		if link.bandwidth != 0 {
+			let busy_until = self.busy_until.entry((from, to)).or_insert(0);
+			sent = cmp::max(sent, *busy_until) + size as u64 * 1_000_000 / link.bandwidth as u64;
+			*busy_until = sent;
+		}
+		sent + link.latency * 1000
+	}
+
+	fn allocate_port(&mut self, node: usize) -> u16 {
+		let port = self.next_port[node];
+		self.next_port[node] = port.checked_add(1).unwrap_or(FIRST_EPHEMERAL_PORT);
+		port
+	}
+
+	fn add_socket(&mut self, node: usize, local: SocketAddr, kind: SocketKind) -> usize {
+		let id = self.next_socket;
+		self.next_socket += 1;
+		self.sockets.insert(id, SocketState {
+			node: node,
+			local: local,
+			handles: 1,
+			token: None,
+			interest: Ready::empty(),
+			ready: Ready::empty(),
+			kind: kind,
+		});
+		id
+	}
+
+	fn local_address(&mut self, node: usize, address: &SocketAddr) -> io::Result<SocketAddr> {
+		if address.ip() != self.ips[node] {
+			return Err(io::Error::new(io::ErrorKind::AddrNotAvailable, "address is not local"));
+		}
+		let port = if address.port() == 0 { self.allocate_port(node) } else { address.port() };
+		Ok(SocketAddr::new(address.ip(), port))
+	}
+
+	fn listen(&mut self, node: usize, address: &SocketAddr) -> io::Result<usize> {
+		let address = self.local_address(node, address)?;
+		if self.listeners.contains_key(&address) {
+			return Err(io::Error::new(io::ErrorKind::AddrInUse, "address in use"));
+		}
+		let id = self.add_socket(node, address, SocketKind::Listener(VecDeque::new()));
+		self.listeners.insert(address, id);
+		Ok(id)
+	}
+
+	fn bind(&mut self, node: usize, address: &SocketAddr) -> io::Result<usize> {
+		let address = self.local_address(node, address)?;
+		if self.datagrams.contains_key(&address) {
+			return Err(io::Error::new(io::ErrorKind::AddrInUse, "address in use"));
+		}
+		let id = self.add_socket(node, address, SocketKind::Datagram(VecDeque::new()));
+		self.datagrams.insert(address, id);
+		Ok(id)
+	}
+
+	fn new_stream(&mut self, node: usize, local: SocketAddr, peer: SocketAddr, remote: Option<(usize, usize)>) -> usize {
+		self.add_socket(node, local, SocketKind::Stream(StreamState {
+			peer: peer,
+			remote: remote,
+			connected: remote.is_some(),
+			received: VecDeque::new(),
+			eof: false,
+			reset: false,
+			closed: false,
+			last_arrival: 0,
+			held: VecDeque::new(),
+		}))
+	}
+
+	fn connect(&mut self, node: usize, address: &SocketAddr) -> usize {
+		let port = self.allocate_port(node);
+		let local = SocketAddr::new(self.ips[node], port);
+		let id = self.new_stream(node, local, address.clone(), None);
+		// connection attempts to unknown or partitioned nodes are never answered.
+		if let Some(to) = self.nodes.get(&address.ip()).cloned() {
+			if !self.blocked.contains(&(node, to)) {
+				let mut arrival = self.transmit(node, to, 0);
+				if self.is_lost(node, to) {
+					arrival += 2 * self.link(node, to).latency * 1000;
+				}
+				self.schedule(arrival, Event::Connect { stream: id, address: address.clone() });
+			}
+		}
+		id
+	}
+
+	fn send_segment(&mut self, id: usize, segment: Segment) {
+		let (from, to, remote) = {
+			let socket = self.sockets.get(&id).expect("Segments are sent by open sockets; qed");
+			let node = socket.node;
+			match socket.kind {
+				SocketKind::Stream(StreamState { remote: Some((remote, to)), .. }) => (node, to, remote),
+				_ => return,
+			}
+		};
+		let blocked = self.blocked.contains(&(from, to));
+		{
+			let stream = self.sockets.get_mut(&id).expect("Checked above; qed").stream();
+			if blocked || !stream.held.is_empty() {
+				stream.held.push_back(segment);
+				return;
+			}
+		}
+		let size = match segment {
+			Segment::Data(ref data) => data.len(),
+			Segment::Fin => 0,
+		};
+		let mut arrival = self.transmit(from, to, size);
+		if self.is_lost(from, to) {
+			arrival += 2 * self.link(from, to).latency * 1000;
+		}
+		{
+			let stream = self.sockets.get_mut(&id).expect("Checked above; qed").stream();
+			arrival = cmp::max(arrival, stream.last_arrival);
+			stream.last_arrival = arrival;
+		}
+		self.schedule(arrival, Event::Segment { stream: remote, segment: segment });
+	}
+
+	fn send_datagram(&mut self, id: usize, target: &SocketAddr, data: Vec<u8>) {
+		let (from, local) = {
+			let socket = &self.sockets[&id];
+			(socket.node, socket.local)
+		};
+		let to = match self.nodes.get(&target.ip()) {
+			Some(to) => *to,
+			None => return,
+		};
+		if self.blocked.contains(&(from, to)) || self.is_lost(from, to) {
+			return;
+		}
+		let arrival = self.transmit(from, to, data.len());
+		self.schedule(arrival, Event::Datagram { from: local, to: target.clone(), data: data });
+	}
+
+	fn read(&mut self, id: usize, buf: &mut [u8]) -> io::Result<usize> {
+		let stream = self.sockets.get_mut(&id).expect("Reads only happen on open sockets; qed").stream();
+		if !stream.received.is_empty() {
+			let size = cmp::min(buf.len(), stream.received.len());
+			for (b, r) in buf.iter_mut().zip(stream.received.drain(..size)) {
+				*b = r;
+			}
+			Ok(size)
+		} else if stream.reset {
+			Err(io::Error::new(io::ErrorKind::ConnectionReset, "connection reset"))
+		} else if stream.eof {
+			Ok(0)
+		} else {
+			Err(io::Error::new(io::ErrorKind::WouldBlock, "no data"))
+		}
+	}
+
+	fn write(&mut self, id: usize, buf: &[u8]) -> io::Result<usize> {
+		{
+			let stream = self.sockets.get_mut(&id).expect("Writes only happen on open sockets; qed").stream();
+			if stream.reset {
+				return Err(io::Error::new(io::ErrorKind::ConnectionReset, "connection reset"));
+			}
+			if !stream.connected {
+				return Err(io::Error::new(io::ErrorKind::WouldBlock, "not connected"));
+			}
+		}
+		self.send_segment(id, Segment::Data(buf.to_vec()));
+		Ok(buf.len())
+	}
+
+	fn accept(&mut self, id: usize) -> io::Result<(usize, SocketAddr)> {
+		let stream = match self.sockets.get_mut(&id).map(|s| &mut s.kind) {
+			Some(&mut SocketKind::Listener(ref mut backlog)) => backlog.pop_front(),
+			_ => None,
+		};
+		match stream {
+			Some(stream) => {
+				let socket = self.sockets.get_mut(&stream).expect("Accepted streams are open; qed");
+				let peer = socket.stream().peer;
+				Ok((stream, peer))
+			},
+			None => Err(io::Error::new(io::ErrorKind::WouldBlock, "no pending connections")),
+		}
+	}
+
+	fn recv_from(&mut self, id: usize, buf: &mut [u8]) -> Option<(usize, SocketAddr)> {
+		let socket = self.sockets.get_mut(&id).expect("Reads only happen on open sockets; qed");
+		let received = match socket.kind {
+			SocketKind::Datagram(ref mut queue) => queue.pop_front(),
+			_ => None,
+		};
+		// report the socket again while datagrams are queued.
+		if socket.is_readable() {
+			socket.ready.insert(Ready::readable());
+		}
+		received.map(|(from, data)| {
+			let size = cmp::min(buf.len(), data.len());
+			buf[..size].copy_from_slice(&data[..size]);
+			(size, from)
+		})
+	}
+
+	fn register(&mut self, id: usize, token: Token, interest: Ready) {
+		if let Some(socket) = self.sockets.get_mut(&id) {
+			let new = socket.token.is_none();
+			socket.token = Some(token);
+			socket.interest = interest;
+			if interest.is_writable() && socket.is_writable() {
+				socket.ready.insert(Ready::writable());
+			}
+			if new && socket.is_readable() {
+				socket.ready.insert(Ready::readable());
+			}
+		}
+	}
+
+	fn deregister(&mut self, id: usize) {
+		if let Some(socket) = self.sockets.get_mut(&id) {
+			socket.token = None;
+			socket.ready = Ready::empty();
+		}
+	}
+
+	fn clone_handle(&mut self, id: usize) {
+		self.sockets.get_mut(&id).expect("Cloned sockets are open; qed").handles += 1;
+	}
+
+	/// Drop a socket handle. The socket is closed with the last one.
+	fn release(&mut self, id: usize) {
+		let (closed, local) = {
+			let socket = match self.sockets.get_mut(&id) {
+				Some(socket) => socket,
+				None => return,
+			};
+			socket.handles -= 1;
+			(socket.handles == 0, socket.local)
+		};
+		if !closed {
+			return;
+		}
+		let is_stream = match self.sockets[&id].kind {
+			SocketKind::Stream(_) => true,
+			_ => false,
+		};
+		if is_stream {
+			// closed streams are kept until the pending segments are sent.
+			self.send_segment(id, Segment::Fin);
+			let socket = self.sockets.get_mut(&id).expect("Checked above; qed");
+			socket.token = None;
+			socket.stream().closed = true;
+		} else {
+			self.sockets.remove(&id);
+			if self.listeners.get(&local) == Some(&id) {
+				self.listeners.remove(&local);
+			}
+			if self.datagrams.get(&local) == Some(&id) {
+				self.datagrams.remove(&local);
+			}
+		}
+	}
+
+	fn add_timer(&mut self, node: usize, token: TimerToken, delay: u64, once: bool) {
+		self.timer_generation += 1;
+		let generation = self.timer_generation;
+		// zero delays would fire forever without time passing.
+		let delay = cmp::max(delay, 1);
+		self.timers.insert((node, token), Timer { delay: delay, once: once, generation: generation });
+		let at = self.now + delay * 1000;
+		self.schedule(at, Event::Timer { node: node, token: token, generation: generation });
+	}
+
+	fn remove_timer(&mut self, node: usize, token: TimerToken) {
+		self.timers.remove(&(node, token));
+	}
+
+	/// Make pending segments of all streams move after a partition has been removed.
+	fn release_held(&mut self) {
+		let held: Vec<usize> = self.sockets.iter()
+			.filter(|&(_, s)| match s.kind {
+				SocketKind::Stream(ref s) => !s.held.is_empty(),
+				_ => false,
+			})
+			.map(|(id, _)| *id)
+			.collect();
+		for id in held {
+			let segments: Vec<Segment> = self.sockets.get_mut(&id).expect("Collected above; qed").stream().held.drain(..).collect();
+			for segment in segments {
+				self.send_segment(id, segment);
+			}
+		}
+	}
+
+	/// Process the next event scheduled not later than `until`.
+	fn advance(&mut self, until: u64) -> Option<Fired> {
+		let key = match self.events.keys().next() {
+			Some(key) if key.0 <= until => *key,
+			_ => return None,
+		};
+		let event = self.events.remove(&key).expect("Key is taken from the map; qed");
+		self.now = cmp::max(self.now, key.0);
+		match event {
+			Event::Timer { node, token, generation } => {
+				let (current, once, delay) = match self.timers.get(&(node, token)) {
+					Some(timer) => (timer.generation == generation, timer.once, timer.delay),
+					None => (false, false, 0),
+				};
+				if !current {
+					return Some(Fired::Network);
+				}
+				if once {
+					self.timers.remove(&(node, token));
+				} else {
+					let at = self.now + delay * 1000;
+					self.schedule(at, Event::Timer { node: node, token: token, generation: generation });
+				}
+				return Some(Fired::Timer(node, token));
+			},
+			Event::Connect { stream, address } => {
+				let client = match self.sockets.get(&stream) {
+					Some(socket) => (socket.node, socket.local),
+					None => return Some(Fired::Network),
+				};
+				match self.listeners.get(&address).cloned() {
+					Some(listener) => {
+						let node = self.sockets[&listener].node;
+						let server = self.new_stream(node, address, client.1, Some((stream, client.0)));
+						{
+							let socket = self.sockets.get_mut(&listener).expect("Listeners are removed from the map on close; qed");
+							if let SocketKind::Listener(ref mut backlog) = socket.kind {
+								backlog.push_back(server);
+							}
+							socket.ready.insert(Ready::readable());
+						}
+						let arrival = self.transmit(node, client.0, 0);
+						self.schedule(arrival, Event::Accepted { stream: stream, remote: (server, node) });
+					},
+					None => {
+						let node = self.nodes[&address.ip()];
+						let arrival = self.transmit(node, client.0, 0);
+						self.schedule(arrival, Event::Refused { stream: stream });
+					},
+				}
+			},
+			Event::Accepted { stream, remote } => {
+				let closed = match self.sockets.get_mut(&stream) {
+					Some(socket) => {
+						socket.ready.insert(Ready::writable());
+						let s = socket.stream();
+						s.remote = Some(remote);
+						s.connected = true;
+						s.closed
+					},
+					None => false,
+				};
+				if closed {
+					self.send_segment(stream, Segment::Fin);
+				}
+			},
+			Event::Refused { stream } => {
+				if let Some(socket) = self.sockets.get_mut(&stream) {
+					socket.stream().reset = true;
+					socket.ready.insert(Ready::hup());
+				}
+			},
+			Event::Segment { stream, segment } => {
+				if let Some(socket) = self.sockets.get_mut(&stream) {
+					if socket.stream().closed {
+						return Some(Fired::Network);
+					}
+					match segment {
+						Segment::Data(data) => {
+							socket.stream().received.extend(data);
+							socket.ready.insert(Ready::readable());
+						},
+						Segment::Fin => {
+							socket.stream().eof = true;
+							socket.ready.insert(Ready::hup());
+						},
+					}
+				}
+			},
+			Event::Datagram { from, to, data } => {
+				if let Some(id) = self.datagrams.get(&to).cloned() {
+					let socket = self.sockets.get_mut(&id).expect("Datagram sockets are removed from the map on close; qed");
+					if let SocketKind::Datagram(ref mut queue) = socket.kind {
+						queue.push_back((from, data));
+					}
+					socket.ready.insert(Ready::readable());
+				}
+			},
+		}
+		Some(Fired::Network)
+	}
+
+	/// Collect and clear readiness of all registered sockets.
+	fn take_ready(&mut self) -> Vec<(usize, StreamToken, Ready)> {
+		let mut ready = Vec::new();
+		for socket in self.sockets.values_mut() {
+			if let Some(token) = socket.token {
+				let events = socket.ready & (socket.interest | Ready::hup());
+				if !events.is_empty() {
+					ready.push((socket.node, token.0 % TOKENS_PER_HANDLER, events));
+				}
+			}
+			socket.ready = Ready::empty();
+		}
+		ready
+	}
+}
+
+/// Handle to a virtual socket.
+struct SimSocket {
+	fabric: Arc<Mutex<Fabric>>,
+	id: usize,
+}
+
+impl Drop for SimSocket {
+	fn drop(&mut self) {
+		self.fabric.lock().release(self.id);
+	}
+}
+
+impl Evented for SimSocket {
+	fn register(&self, _poll: &Poll, token: Token, interest: Ready, _opts: PollOpt) -> io::Result<()> {
+		self.fabric.lock().register(self.id, token, interest);
+		Ok(())
+	}
+
+	fn reregister(&self, _poll: &Poll, token: Token, interest: Ready, _opts: PollOpt) -> io::Result<()> {
+		self.fabric.lock().register(self.id, token, interest);
+		Ok(())
+	}
+
+	fn deregister(&self, _poll: &Poll) -> io::Result<()> {
+		self.fabric.lock().deregister(self.id);
+		Ok(())
+	}
+}
+
+impl Read for SimSocket {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.fabric.lock().read(self.id, buf)
+	}
+}
+
+impl Write for SimSocket {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.fabric.lock().write(self.id, buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Stream for SimSocket {
+	fn peer_addr(&self) -> io::Result<SocketAddr> {
+		let mut fabric = self.fabric.lock();
+		Ok(fabric.sockets.get_mut(&self.id).expect("Handles keep sockets open; qed").stream().peer)
+	}
+
+	fn local_addr(&self) -> io::Result<SocketAddr> {
+		Ok(self.fabric.lock().sockets[&self.id].local)
+	}
+
+	fn try_clone(&self) -> io::Result<Box<Stream>> {
+		self.fabric.lock().clone_handle(self.id);
+		Ok(Box::new(SimSocket { fabric: self.fabric.clone(), id: self.id }))
+	}
+}
+
+impl Listener for SimSocket {
+	fn accept(&self) -> io::Result<(Box<Stream>, SocketAddr)> {
+		let (id, address) = self.fabric.lock().accept(self.id)?;
+		let stream: Box<Stream> = Box::new(SimSocket { fabric: self.fabric.clone(), id: id });
+		Ok((stream, address))
+	}
+
+	fn local_addr(&self) -> io::Result<SocketAddr> {
+		Ok(self.fabric.lock().sockets[&self.id].local)
+	}
+}
+
+impl Datagram for SimSocket {
+	fn send_to(&self, buf: &[u8], target: &SocketAddr) -> io::Result<Option<usize>> {
+		self.fabric.lock().send_datagram(self.id, target, buf.to_vec());
+		Ok(Some(buf.len()))
+	}
+
+	fn recv_from(&self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+		Ok(self.fabric.lock().recv_from(self.id, buf))
+	}
+}
+
+/// Virtual sockets of a single node.
+struct SimTransport {
+	fabric: Arc<Mutex<Fabric>>,
+	node: usize,
+}
+
+impl SimTransport {
+	fn socket(&self, id: usize) -> SimSocket {
+		SimSocket { fabric: self.fabric.clone(), id: id }
+	}
+}
+
+impl Transport for SimTransport {
+	fn listen(&self, address: &SocketAddr) -> io::Result<Box<Listener>> {
+		let id = self.fabric.lock().listen(self.node, address)?;
+		Ok(Box::new(self.socket(id)))
+	}
+
+	fn connect(&self, address: &SocketAddr) -> io::Result<Box<Stream>> {
+		let id = self.fabric.lock().connect(self.node, address);
+		Ok(Box::new(self.socket(id)))
+	}
+
+	fn bind(&self, address: &SocketAddr) -> io::Result<Box<Datagram>> {
+		let id = self.fabric.lock().bind(self.node, address)?;
+		Ok(Box::new(self.socket(id)))
+	}
+}
+
+struct SimNode {
+	host: Host,
+	queue: IoMessageQueue<NetworkIoMessage>,
+}
+
+impl SimNode {
+	fn io(&self) -> IoContext<NetworkIoMessage> {
+		IoContext::new(IoChannel::queued(self.queue.clone()), 0)
+	}
+}
+
+/// Simulated network of hosts. Node `n` gets the address `10.0.0.n+1`.
+pub struct Simulation {
+	seed: u64,
+	fabric: Arc<Mutex<Fabric>>,
+	nodes: Vec<SimNode>,
+	event_loop: EventLoop<IoManager<NetworkIoMessage>>,
+}
+
+impl Simulation {
+	/// Create an empty network. Runs with the same seed lose the same packets.
+	pub fn new(seed: u64) -> Simulation {
+		Simulation {
+			seed: seed,
+			fabric: Arc::new(Mutex::new(Fabric::new(seed))),
+			nodes: Vec::new(),
+			event_loop: EventLoop::new().expect("Error creating event loop"),
+		}
+	}
+
+	/// Start a host and return its index. The listen and public addresses are replaced with the
+	/// simulated address, NAT configuration is disabled and the node key is derived from the seed
+	/// unless set.
+	pub fn add_node(&mut self, mut config: NetworkConfiguration) -> usize {
+		let index = self.nodes.len();
+		let n = index + 1;
+		let ip = IpAddr::V4(Ipv4Addr::new(10, (n >> 16) as u8, (n >> 8) as u8, n as u8));
+		let port = match config.listen_address {
+			Some(address) if address.port() != 0 => address.port(),
+			_ => DEFAULT_PORT,
+		};
+		let address = SocketAddr::new(ip, port);
+		config.listen_address = Some(address);
+		config.public_address = Some(address);
+		config.nat_enabled = false;
+		if config.use_secret.is_none() {
+			let seed = format!("simulation {} node {}", self.seed, index).sha3();
+			config.use_secret = Some(Secret::from_slice(&seed).expect("Hash is a valid secret"));
+		}
+
+		self.fabric.lock().add_node(ip);
+		let transport = SimTransport { fabric: self.fabric.clone(), node: index };
+		let host = Host::with_transport(config, Arc::new(NetworkStats::new()), Box::new(transport)).expect("Error creating simulated host");
+		let node = SimNode {
+			host: host,
+			queue: IoMessageQueue::default(),
+		};
+		node.host.initialize(&node.io());
+		self.nodes.push(node);
+		self.settle();
+		index
+	}
+
+	/// Register a protocol handler with a node.
+	pub fn register_protocol(&mut self, node: usize, handler: Arc<NetworkProtocolHandler + Send + Sync>, protocol: ProtocolId, packet_count: u8, versions: &[u8]) {
+		self.nodes[node].queue.lock().push_back(IoMessage::UserMessage(NetworkIoMessage::AddHandler {
+			handler: handler,
+			protocol: protocol,
+			versions: versions.to_vec(),
+			packet_count: packet_count,
+		}));
+		self.settle();
+	}
+
+	/// Execute a closure with the protocol context of a node.
+	pub fn with_context<F>(&mut self, node: usize, protocol: ProtocolId, action: F) where F: Fn(&NetworkContext) {
+		{
+			let node = &self.nodes[node];
+			node.host.with_context(protocol, &node.io(), action);
+		}
+		self.settle();
+	}
+
+	/// Number of nodes.
+	pub fn node_count(&self) -> usize {
+		self.nodes.len()
+	}
+
+	/// Node id.
+	pub fn node_id(&self, node: usize) -> NodeId {
+		self.nodes[node].host.info.read().id().clone()
+	}
+
+	/// Node url, to be used as a boot node or a reserved peer.
+	pub fn node_url(&self, node: usize) -> String {
+		self.nodes[node].host.external_url().expect("Public interface is initialized when the node is added; qed")
+	}
+
+	/// Ids of the nodes with an established session.
+	pub fn connected_nodes(&self, node: usize) -> Vec<NodeId> {
+		self.nodes[node].host.connected_nodes()
+	}
+
+	/// Check if there is an established session between two nodes.
+	pub fn is_connected(&self, a: usize, b: usize) -> bool {
+		let id = self.node_id(b);
+		self.connected_nodes(a).contains(&id)
+	}
+
+	/// Simulated time in milliseconds.
+	pub fn now(&self) -> u64 {
+		self.fabric.lock().now / 1000
+	}
+
+	/// Set the properties of all links without a specific configuration.
+	pub fn set_default_link(&mut self, link: LinkConfig) {
+		self.fabric.lock().default_link = link;
+	}
+
+	/// Set the properties of the link between two nodes.
+	pub fn set_link(&mut self, a: usize, b: usize, link: LinkConfig) {
+		self.fabric.lock().links.insert((cmp::min(a, b), cmp::max(a, b)), link);
+	}
+
+	/// Cut all links between two groups of nodes.
+	pub fn partition(&mut self, a: &[usize], b: &[usize]) {
+		let mut fabric = self.fabric.lock();
+		for x in a {
+			for y in b {
+				fabric.blocked.insert((*x, *y));
+				fabric.blocked.insert((*y, *x));
+			}
+		}
+	}
+
+	/// Remove all partitions. Held stream data is sent.
+	pub fn heal(&mut self) {
+		let mut fabric = self.fabric.lock();
+		fabric.blocked.clear();
+		fabric.release_held();
+	}
+
+	/// Run the simulation for the given number of milliseconds.
+	pub fn run_for(&mut self, ms: u64) {
+		let until = self.fabric.lock().now + ms * 1000;
+		while self.step(until) {}
+		self.fabric.lock().now = until;
+	}
+
+	/// Run the simulation until the condition holds, for at most the given number of milliseconds.
+	/// Returns the final value of the condition.
+	pub fn run_until<F>(&mut self, ms: u64, mut condition: F) -> bool where F: FnMut(&Simulation) -> bool {
+		let until = self.fabric.lock().now + ms * 1000;
+		loop {
+			if condition(self) {
+				return true;
+			}
+			if !self.step(until) {
+				self.fabric.lock().now = until;
+				return condition(self);
+			}
+		}
+	}
+
+	/// Process the next event scheduled not later than `until`. Returns false if there is none.
+	fn step(&mut self, until: u64) -> bool {
+		let fired = self.fabric.lock().advance(until);
+		match fired {
+			None => return false,
+			Some(Fired::Timer(node, token)) => {
+				let node = &self.nodes[node];
+				node.host.timeout(&node.io(), token);
+			},
+			Some(Fired::Network) => {},
+		}
+		self.settle();
+		true
+	}
+
+	/// Handle IO messages and socket events until there are none left at the current time.
+	fn settle(&mut self) {
+		let mut rounds = 0;
+		loop {
+			let mut idle = true;
+			for node in 0..self.nodes.len() {
+				loop {
+					let message = self.nodes[node].queue.lock().pop_front();
+					match message {
+						Some(message) => self.handle_message(node, message),
+						None => break,
+					}
+					idle = false;
+				}
+			}
+			let ready = self.fabric.lock().take_ready();
+			for (node, token, events) in ready {
+				idle = false;
+				let node = &self.nodes[node];
+				let io = node.io();
+				if events.is_hup() {
+					node.host.stream_hup(&io, token);
+					continue;
+				}
+				if events.is_readable() {
+					node.host.stream_readable(&io, token);
+				}
+				if events.is_writable() {
+					node.host.stream_writable(&io, token);
+				}
+			}
+			if idle {
+				break;
+			}
+			rounds += 1;
+			assert!(rounds < MAX_ROUNDS, "Simulated network does not settle");
+		}
+	}
+
+	fn handle_message(&mut self, index: usize, message: IoMessage<NetworkIoMessage>) {
+		let node = &self.nodes[index];
+		match message {
+			IoMessage::AddTimer { token, delay, once, .. } => self.fabric.lock().add_timer(index, token, delay, once),
+			IoMessage::RemoveTimer { token, .. } => self.fabric.lock().remove_timer(index, token),
+			IoMessage::RegisterStream { token, .. } => node.host.register_stream(token, Token(token), &mut self.event_loop),
+			IoMessage::UpdateStreamRegistration { token, .. } => node.host.update_stream(token, Token(token), &mut self.event_loop),
+			IoMessage::DeregisterStream { token, .. } => {
+				node.host.deregister_stream(token, &mut self.event_loop);
+				// a timer with the same token belongs to the stream.
+				self.fabric.lock().remove_timer(index, token);
+			},
+			IoMessage::UserMessage(ref message) => node.host.message(&node.io(), message),
+			_ => {},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use parking_lot::Mutex;
+	use rand::{Rng, SeedableRng, XorShiftRng};
+	use util::Bytes;
+	use host::{NetworkConfiguration, NetworkContext, PeerId};
+	use NetworkProtocolHandler;
+	use super::*;
+
+	#[derive(Default)]
+	struct Recorder {
+		packets: Mutex<Vec<(PeerId, u8, Bytes)>>,
+		peers: Mutex<Vec<PeerId>>,
+	}
+
+	impl NetworkProtocolHandler for Recorder {
+		fn read(&self, _io: &NetworkContext, peer: &PeerId, packet_id: u8, data: &[u8]) {
+			self.packets.lock().push((*peer, packet_id, data.to_vec()));
+		}
+
+		fn connected(&self, _io: &NetworkContext, peer: &PeerId) {
+			self.peers.lock().push(*peer);
+		}
+
+		fn disconnected(&self, _io: &NetworkContext, peer: &PeerId) {
+			self.peers.lock().retain(|p| p != peer);
+		}
+	}
+
+	fn network(sim: &mut Simulation, count: usize) -> Vec<Arc<Recorder>> {
+		let mut handlers = Vec::new();
+		for i in 0..count {
+			let mut config = NetworkConfiguration::new();
+			if i > 0 {
+				config.boot_nodes = vec![sim.node_url(0)];
+			}
+			let node = sim.add_node(config);
+			let handler = Arc::new(Recorder::default());
+			sim.register_protocol(node, handler.clone(), *b"tst", 1, &[1u8]);
+			handlers.push(handler);
+		}
+		handlers
+	}
+
+	fn broadcast(sim: &mut Simulation, node: usize, handler: &Recorder, data: Bytes) {
+		let peers = handler.peers.lock().clone();
+		sim.with_context(node, *b"tst", move |io| {
+			for peer in &peers {
+				io.send(*peer, 0, data.clone()).unwrap();
+			}
+		});
+	}
+
+	#[test]
+	fn discovers_and_connects() {
+		let mut sim = Simulation::new(1);
+		network(&mut sim, 4);
+		// nodes 1 to 3 only know the boot node and find each other with discovery.
+		assert!(sim.run_until(120_000, |sim| (1..4).all(|a| (0..4).all(|b| a == b || sim.is_connected(a, b)))));
+	}
+
+	#[test]
+	fn delivers_over_slow_lossy_links() {
+		let mut sim = Simulation::new(2);
+		sim.set_default_link(LinkConfig { latency: 200, loss: 0.3, bandwidth: 0 });
+		let handlers = network(&mut sim, 2);
+		assert!(sim.run_until(60_000, |sim| sim.is_connected(0, 1) && sim.is_connected(1, 0)));
+
+		sim.set_link(0, 1, LinkConfig { latency: 50, loss: 0.0, bandwidth: 10_000 });
+		// random data does not compress.
+		let data: Bytes = XorShiftRng::from_seed([1, 2, 3, 4]).gen_iter().take(50_000).collect();
+		let start = sim.now();
+		broadcast(&mut sim, 0, &handlers[0], data.clone());
+		assert!(sim.run_until(60_000, |_| !handlers[1].packets.lock().is_empty()));
+		assert!(sim.now() - start >= 5_000);
+		assert_eq!(handlers[1].packets.lock()[0].2, data);
+	}
+
+	#[test]
+	fn partition_holds_traffic() {
+		let mut sim = Simulation::new(3);
+		let handlers = network(&mut sim, 2);
+		assert!(sim.run_until(10_000, |sim| sim.is_connected(0, 1) && sim.is_connected(1, 0)));
+
+		sim.partition(&[0], &[1]);
+		broadcast(&mut sim, 0, &handlers[0], b"hello".to_vec());
+		sim.run_for(10_000);
+		assert!(handlers[1].packets.lock().is_empty());
+
+		sim.heal();
+		assert!(sim.run_until(1_000, |_| !handlers[1].packets.lock().is_empty()));
+		assert_eq!(handlers[1].packets.lock()[0].2, b"hello".to_vec());
+	}
+}
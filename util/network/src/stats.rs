@@ -17,6 +17,15 @@
 //! Network Statistics
 use std::sync::atomic::*;
 
+/// Bytes sent and received.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+	/// Bytes sent
+	pub sent: usize,
+	/// Bytes received
+	pub received: usize,
+}
+
 /// Network statistics structure
 #[derive(Default, Debug)]
 pub struct NetworkStats {
@@ -26,6 +35,14 @@ pub struct NetworkStats {
 	send: AtomicUsize,
 	/// Total number of sessions created
 	sessions: AtomicUsize,
+	/// Payload bytes sent compressed, before compression
+	raw_compressed_send: AtomicUsize,
+	/// Payload bytes sent compressed, after compression
+	compressed_send: AtomicUsize,
+	/// Payload bytes received compressed, after decompression
+	raw_compressed_recv: AtomicUsize,
+	/// Payload bytes received compressed, before decompression
+	compressed_recv: AtomicUsize,
 }
 
 impl NetworkStats {
@@ -47,6 +64,20 @@ impl NetworkStats {
 		self.sessions.fetch_add(1, Ordering::Relaxed);
 	}
 
+	/// Account for a payload sent compressed.
+	#[inline]
+	pub fn inc_compressed_send(&self, raw: usize, compressed: usize) {
+		self.raw_compressed_send.fetch_add(raw, Ordering::Relaxed);
+		self.compressed_send.fetch_add(compressed, Ordering::Relaxed);
+	}
+
+	/// Account for a payload received compressed.
+	#[inline]
+	pub fn inc_compressed_recv(&self, raw: usize, compressed: usize) {
+		self.raw_compressed_recv.fetch_add(raw, Ordering::Relaxed);
+		self.compressed_recv.fetch_add(compressed, Ordering::Relaxed);
+	}
+
 	/// Get bytes sent.
 	#[inline]
 	pub fn send(&self) -> usize {
@@ -65,12 +96,35 @@ impl NetworkStats {
 		self.sessions.load(Ordering::Relaxed)
 	}
 
+	/// Get payload bytes sent compressed as `(before, after)` compression.
+	#[inline]
+	pub fn compressed_send(&self) -> (usize, usize) {
+		(self.raw_compressed_send.load(Ordering::Relaxed), self.compressed_send.load(Ordering::Relaxed))
+	}
+
+	/// Get payload bytes received compressed as `(after, before)` decompression.
+	#[inline]
+	pub fn compressed_recv(&self) -> (usize, usize) {
+		(self.raw_compressed_recv.load(Ordering::Relaxed), self.compressed_recv.load(Ordering::Relaxed))
+	}
+
+	/// Get the number of bytes compression saved in both directions.
+	pub fn compression_savings(&self) -> usize {
+		let (raw_send, compressed_send) = self.compressed_send();
+		let (raw_recv, compressed_recv) = self.compressed_recv();
+		raw_send.saturating_sub(compressed_send) + raw_recv.saturating_sub(compressed_recv)
+	}
+
 	/// Create a new empty instance.
 	pub fn new() -> NetworkStats {
 		NetworkStats {
 			recv: AtomicUsize::new(0),
 			send: AtomicUsize::new(0),
 			sessions: AtomicUsize::new(0),
+			raw_compressed_send: AtomicUsize::new(0),
+			compressed_send: AtomicUsize::new(0),
+			raw_compressed_recv: AtomicUsize::new(0),
+			compressed_recv: AtomicUsize::new(0),
 		}
 	}
 }
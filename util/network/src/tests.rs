@@ -159,3 +159,30 @@ fn net_timeout() {
 		thread::sleep(Duration::from_millis(50));
 	}
 }
+
+#[test]
+fn net_trusted_group_ignores_peer_limit() {
+	let mut service2 = NetworkService::new(NetworkConfiguration::new_local()).unwrap();
+	service2.start().unwrap();
+	let handler2 = TestProtocol::register(&mut service2, false);
+
+	let mut config1 = NetworkConfiguration::new_local();
+	config1.min_peers = 0;
+	config1.max_peers = 0;
+	config1.peer_groups = vec![PeerGroupConfig {
+		name: "validators".into(),
+		nodes: vec![service2.local_url().unwrap()],
+		min_slots: 0,
+		max_slots: 1,
+		trusted: true,
+	}];
+	let mut service1 = NetworkService::new(config1).unwrap();
+	service1.start().unwrap();
+	let handler1 = TestProtocol::register(&mut service1, false);
+	while !(handler1.got_packet() && handler2.got_packet()) {
+		thread::sleep(Duration::from_millis(50));
+	}
+	let groups = service1.peer_groups();
+	assert_eq!(groups.len(), 1);
+	assert_eq!(groups[0].connected, 1);
+}
@@ -0,0 +1,116 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Socket abstraction.
+//!
+//! The host opens all sockets through a `Transport`. `TcpTransport` uses the operating
+//! system sockets; the network simulation provides virtual ones.
+
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use mio::Evented;
+use mio::tcp::{TcpStream, TcpListener};
+use mio::udp::UdpSocket;
+
+/// Non-blocking stream socket.
+pub trait Stream: Read + Write + Evented + Send {
+	/// Remote address.
+	fn peer_addr(&self) -> io::Result<SocketAddr>;
+	/// Local address.
+	fn local_addr(&self) -> io::Result<SocketAddr>;
+	/// Create another handle to the same socket.
+	fn try_clone(&self) -> io::Result<Box<Stream>>;
+}
+
+/// Non-blocking listening socket.
+pub trait Listener: Evented + Send {
+	/// Accept a pending connection. Fails with `WouldBlock` if there is none.
+	fn accept(&self) -> io::Result<(Box<Stream>, SocketAddr)>;
+	/// Local address.
+	fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+/// Non-blocking datagram socket.
+pub trait Datagram: Evented + Send {
+	/// Send a datagram. Returns `None` if the socket is not ready.
+	fn send_to(&self, buf: &[u8], target: &SocketAddr) -> io::Result<Option<usize>>;
+	/// Receive a datagram. Returns `None` if there is nothing to read.
+	fn recv_from(&self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>>;
+}
+
+/// Socket factory.
+pub trait Transport: Send + Sync {
+	/// Open a listening socket.
+	fn listen(&self, address: &SocketAddr) -> io::Result<Box<Listener>>;
+	/// Start connecting to a remote address. The stream becomes writable once connected.
+	fn connect(&self, address: &SocketAddr) -> io::Result<Box<Stream>>;
+	/// Open a datagram socket.
+	fn bind(&self, address: &SocketAddr) -> io::Result<Box<Datagram>>;
+}
+
+/// Operating system sockets.
+#[derive(Debug, Default)]
+pub struct TcpTransport;
+
+impl Transport for TcpTransport {
+	fn listen(&self, address: &SocketAddr) -> io::Result<Box<Listener>> {
+		Ok(Box::new(TcpListener::bind(address)?))
+	}
+
+	fn connect(&self, address: &SocketAddr) -> io::Result<Box<Stream>> {
+		Ok(Box::new(TcpStream::connect(address)?))
+	}
+
+	fn bind(&self, address: &SocketAddr) -> io::Result<Box<Datagram>> {
+		Ok(Box::new(UdpSocket::bind(address)?))
+	}
+}
+
+impl Stream for TcpStream {
+	fn peer_addr(&self) -> io::Result<SocketAddr> {
+		TcpStream::peer_addr(self)
+	}
+
+	fn local_addr(&self) -> io::Result<SocketAddr> {
+		TcpStream::local_addr(self)
+	}
+
+	fn try_clone(&self) -> io::Result<Box<Stream>> {
+		Ok(Box::new(TcpStream::try_clone(self)?))
+	}
+}
+
+impl Listener for TcpListener {
+	fn accept(&self) -> io::Result<(Box<Stream>, SocketAddr)> {
+		let (stream, address) = TcpListener::accept(self)?;
+		let stream: Box<Stream> = Box::new(stream);
+		Ok((stream, address))
+	}
+
+	fn local_addr(&self) -> io::Result<SocketAddr> {
+		TcpListener::local_addr(self)
+	}
+}
+
+impl Datagram for UdpSocket {
+	fn send_to(&self, buf: &[u8], target: &SocketAddr) -> io::Result<Option<usize>> {
+		UdpSocket::send_to(self, buf, target)
+	}
+
+	fn recv_from(&self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+		UdpSocket::recv_from(self, buf)
+	}
+}
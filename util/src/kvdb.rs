@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-//! Key-Value store abstraction with `RocksDB` backend.
+//! Key-Value store abstraction with `RocksDB` and in-memory backends.
 
 use std::io::ErrorKind;
 use common::*;
@@ -59,7 +59,7 @@ enum DBOp {
 
 impl DBTransaction {
 	/// Create new transaction.
-	pub fn new(_db: &Database) -> DBTransaction {
+	pub fn new() -> DBTransaction {
 		DBTransaction {
 			ops: Vec::with_capacity(256),
 		}
@@ -116,6 +116,197 @@ enum KeyState {
 	Delete,
 }
 
+/// Generic key-value database.
+///
+/// Values written with `write_buffered` are readable right away, but are guaranteed
+/// to reach the backing storage only after `flush`. `get_by_prefix` and `iter` may
+/// only see flushed values. Keys are separated into columns; `None` column is always available.
+pub trait KeyValueDB: Sync + Send {
+	/// Creates new transaction for this database.
+	fn transaction(&self) -> DBTransaction {
+		DBTransaction::new()
+	}
+
+	/// Get value by key.
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>, String>;
+
+	/// Get value by partial key. Only searches flushed values.
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>>;
+
+	/// Commit transaction to the write buffer.
+	fn write_buffered(&self, transaction: DBTransaction);
+
+	/// Commit transaction to the backing storage.
+	fn write(&self, transaction: DBTransaction) -> Result<(), String> {
+		self.write_buffered(transaction);
+		self.flush()
+	}
+
+	/// Commit buffered changes to the backing storage.
+	fn flush(&self) -> Result<(), String>;
+
+	/// Iterate over flushed data of given column.
+	fn iter<'a>(&'a self, col: Option<u32>) -> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>;
+
+	/// Iterate over flushed data of given column, starting from a given prefix.
+	/// Only keys starting with the prefix are returned.
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
+		-> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>;
+
+	/// Iterate over flushed data of given column, starting from the first key
+	/// not less than `start`.
+	fn iter_from<'a>(&'a self, col: Option<u32>, start: &'a [u8])
+		-> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>;
+
+	/// Replace the database with a copy at given path.
+	fn restore(&self, new_db: &str) -> Result<(), UtilError>;
+
+	/// Approximate statistics of the database.
+	fn stats(&self) -> DatabaseStats;
+}
+
+/// Approximate statistics of a database column.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ColumnStats {
+	/// Estimated number of keys.
+	pub keys: u64,
+	/// Size of the table files on disk, in bytes.
+	pub disk_size: u64,
+	/// Size of the in-memory write buffers, in bytes.
+	pub memtable_size: u64,
+	/// Number of buffered writes which are not flushed yet.
+	pub pending_writes: u64,
+}
+
+/// Approximate statistics of a database.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DatabaseStats {
+	/// Statistics of every column, starting with the `None` column.
+	pub columns: Vec<(Option<u32>, ColumnStats)>,
+	/// Number of compactions currently running.
+	pub running_compactions: u64,
+	/// Estimated number of bytes compactions have to rewrite to settle all levels.
+	pub pending_compaction_bytes: u64,
+}
+
+/// In-memory key-value database. Intended for tests and ephemeral chains.
+pub struct InMemory {
+	columns: RwLock<HashMap<Option<u32>, BTreeMap<Vec<u8>, DBValue>>>,
+}
+
+/// Creates in-memory database with `None` column and columns `0..num_cols`.
+pub fn in_memory(num_cols: u32) -> InMemory {
+	let mut columns = HashMap::new();
+	columns.insert(None, BTreeMap::new());
+	for col in 0..num_cols {
+		columns.insert(Some(col), BTreeMap::new());
+	}
+
+	InMemory {
+		columns: RwLock::new(columns),
+	}
+}
+
+impl KeyValueDB for InMemory {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>, String> {
+		match self.columns.read().get(&col) {
+			Some(column) => Ok(column.get(key).cloned()),
+			None => Err(format!("No such column: {:?}", col)),
+		}
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
+		self.columns.read().get(&col).and_then(|column| column.iter()
+			.find(|&(key, _)| key.starts_with(prefix))
+			.map(|(_, value)| value.to_vec().into_boxed_slice())
+		)
+	}
+
+	fn write_buffered(&self, transaction: DBTransaction) {
+		let mut columns = self.columns.write();
+		for op in transaction.ops {
+			match op {
+				DBOp::Insert { col, key, value } => {
+					if let Some(column) = columns.get_mut(&col) {
+						column.insert(key.to_vec(), value);
+					}
+				},
+				DBOp::InsertCompressed { col, key, value } => {
+					if let Some(column) = columns.get_mut(&col) {
+						let compressed = UntrustedRlp::new(&value).compress(RlpType::Blocks);
+						column.insert(key.to_vec(), DBValue::from_slice(&compressed));
+					}
+				},
+				DBOp::Delete { col, key } => {
+					if let Some(column) = columns.get_mut(&col) {
+						column.remove(&*key);
+					}
+				},
+			}
+		}
+	}
+
+	fn flush(&self) -> Result<(), String> {
+		Ok(())
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>) -> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
+		let entries: Vec<_> = match self.columns.read().get(&col) {
+			Some(column) => column.iter()
+				.map(|(key, value)| (key.clone().into_boxed_slice(), value.to_vec().into_boxed_slice()))
+				.collect(),
+			None => Vec::new(),
+		};
+		Box::new(entries.into_iter())
+	}
+
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
+		-> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		let entries: Vec<_> = match self.columns.read().get(&col) {
+			Some(column) => column.iter()
+				.skip_while(|&(key, _)| key.as_slice() < prefix)
+				.take_while(|&(key, _)| key.starts_with(prefix))
+				.map(|(key, value)| (key.clone().into_boxed_slice(), value.to_vec().into_boxed_slice()))
+				.collect(),
+			None => Vec::new(),
+		};
+		Box::new(entries.into_iter())
+	}
+
+	fn iter_from<'a>(&'a self, col: Option<u32>, start: &'a [u8])
+		-> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		let entries: Vec<_> = match self.columns.read().get(&col) {
+			Some(column) => column.iter()
+				.skip_while(|&(key, _)| key.as_slice() < start)
+				.map(|(key, value)| (key.clone().into_boxed_slice(), value.to_vec().into_boxed_slice()))
+				.collect(),
+			None => Vec::new(),
+		};
+		Box::new(entries.into_iter())
+	}
+
+	fn restore(&self, _new_db: &str) -> Result<(), UtilError> {
+		Err(UtilError::SimpleString("Attempted to restore in-memory database".into()))
+	}
+
+	fn stats(&self) -> DatabaseStats {
+		let columns = self.columns.read();
+		let mut names: Vec<_> = columns.keys().cloned().collect();
+		names.sort();
+
+		DatabaseStats {
+			columns: names.into_iter().map(|col| (col, ColumnStats {
+				keys: columns[&col].len() as u64,
+				memtable_size: columns[&col].iter().map(|(key, value)| (key.len() + value.len()) as u64).sum(),
+				..Default::default()
+			})).collect(),
+			..Default::default()
+		}
+	}
+}
+
 /// Compaction profile for the database settings
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct CompactionProfile {
@@ -261,6 +452,28 @@ impl<'a> Iterator for DatabaseIterator {
 	}
 }
 
+// default cache size for columns not specified.
+const DEFAULT_CACHE: usize = 2;
+
+fn col_config(col: u32, config: &DatabaseConfig) -> Options {
+	let mut opts = Options::new();
+	opts.set_compaction_style(DBCompactionStyle::DBUniversalCompaction);
+	opts.set_target_file_size_base(config.compaction.initial_file_size);
+	opts.set_target_file_size_multiplier(config.compaction.file_size_multiplier);
+
+	let col_opt = config.columns.map(|_| col);
+
+	{
+		let cache_size = config.cache_sizes.get(&col_opt).cloned().unwrap_or(DEFAULT_CACHE);
+		let mut block_opts = BlockBasedOptions::new();
+		// all goes to read cache.
+		block_opts.set_cache(Cache::new(cache_size * 1024 * 1024));
+		opts.set_block_based_table_factory(&block_opts);
+	}
+
+	opts
+}
+
 struct DBAndColumns {
 	db: DB,
 	cfs: Vec<Column>,
@@ -290,9 +503,6 @@ impl Database {
 
 	/// Open database file. Creates if it does not exist.
 	pub fn open(config: &DatabaseConfig, path: &str) -> Result<Database, String> {
-		// default cache size for columns not specified.
-		const DEFAULT_CACHE: usize = 2;
-
 		let mut opts = Options::new();
 		if let Some(rate_limit) = config.compaction.write_rate_limit {
 			opts.set_parsed_options(&format!("rate_limiter_bytes_per_sec={}", rate_limit))?;
@@ -316,22 +526,7 @@ impl Database {
 		let cfnames: Vec<&str> = cfnames.iter().map(|n| n as &str).collect();
 
 		for col in 0 .. config.columns.unwrap_or(0) {
-			let mut opts = Options::new();
-			opts.set_compaction_style(DBCompactionStyle::DBUniversalCompaction);
-			opts.set_target_file_size_base(config.compaction.initial_file_size);
-			opts.set_target_file_size_multiplier(config.compaction.file_size_multiplier);
-
-			let col_opt = config.columns.map(|_| col);
-
-			{
-				let cache_size = config.cache_sizes.get(&col_opt).cloned().unwrap_or(DEFAULT_CACHE);
-				let mut block_opts = BlockBasedOptions::new();
-				// all goes to read cache.
-				block_opts.set_cache(Cache::new(cache_size * 1024 * 1024));
-				opts.set_block_based_table_factory(&block_opts);
-			}
-
-			cf_options.push(opts);
+			cf_options.push(col_config(col, config));
 		}
 
 		let mut write_opts = WriteOptions::new();
@@ -395,7 +590,7 @@ impl Database {
 
 	/// Creates new transaction for this database.
 	pub fn transaction(&self) -> DBTransaction {
-		DBTransaction::new(self)
+		DBTransaction::new()
 	}
 
 
@@ -570,6 +765,63 @@ impl Database {
 		}
 	}
 
+	/// Get database iterator from prefix for flushed data.
+	pub fn iter_from_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<DatabaseIterator> {
+		match *self.db.read() {
+			Some(DBAndColumns { ref db, ref cfs }) => {
+				let iter = col.map_or_else(|| db.iterator_opt(IteratorMode::From(prefix, Direction::Forward), &self.read_opts),
+					|c| db.iterator_cf_opt(cfs[c as usize], IteratorMode::From(prefix, Direction::Forward), &self.read_opts)
+						.expect("iterator params are valid; qed"));
+
+				Some(DatabaseIterator { iter: iter })
+			},
+			None => None,
+		}
+	}
+
+	/// Approximate statistics from the RocksDB properties of every column.
+	pub fn stats(&self) -> DatabaseStats {
+		match *self.db.read() {
+			Some(DBAndColumns { ref db, ref cfs }) => {
+				// integer properties are read as strings, which is all the binding exposes.
+				let property = |cf: Option<Column>, name: &str| {
+					match cf {
+						Some(cf) => db.property_value_cf(cf, name),
+						None => db.property_value(name),
+					}.ok().and_then(|value| value).and_then(|value| value.trim().parse::<u64>().ok()).unwrap_or(0)
+				};
+				let overlay = self.overlay.read();
+				let flushing = self.flushing.read();
+
+				let columns: Vec<_> = Some(None).into_iter()
+					.chain((0..cfs.len()).map(|c| Some(c as u32)))
+					.map(|col| {
+						let cf = col.map(|c| cfs[c as usize]);
+						let c = Self::to_overlay_column(col);
+						(col, ColumnStats {
+							keys: property(cf, "rocksdb.estimate-num-keys"),
+							disk_size: property(cf, "rocksdb.total-sst-files-size"),
+							memtable_size: property(cf, "rocksdb.cur-size-all-mem-tables"),
+							pending_writes: (overlay[c].len() + flushing[c].len()) as u64,
+						})
+					})
+					.collect();
+
+				let pending_compaction_bytes = Some(None).into_iter()
+					.chain(cfs.iter().map(|cf| Some(*cf)))
+					.map(|cf| property(cf, "rocksdb.estimate-pending-compaction-bytes"))
+					.sum();
+
+				DatabaseStats {
+					columns: columns,
+					running_compactions: property(None, "rocksdb.num-running-compactions"),
+					pending_compaction_bytes: pending_compaction_bytes,
+				}
+			},
+			None => DatabaseStats::default(),
+		}
+	}
+
 	/// Close the database
 	fn close(&self) {
 		*self.db.write() = None;
@@ -579,6 +831,11 @@ impl Database {
 
 	/// Restore the database from a copy at given path.
 	pub fn restore(&self, new_db: &str) -> Result<(), UtilError> {
+		// columns added with `add_column` are not part of the original config.
+		let mut config = self.config.clone();
+		if let Some(DBAndColumns { ref cfs, .. }) = *self.db.read() {
+			config.columns = config.columns.map(|_| cfs.len() as u32);
+		}
 		self.close();
 
 		let mut backup_db = PathBuf::from(&self.path);
@@ -611,12 +868,76 @@ impl Database {
 		}
 
 		// reopen the database and steal handles into self
-		let db = Self::open(&self.config, &self.path)?;
+		let db = Self::open(&config, &self.path)?;
 		*self.db.write() = mem::replace(&mut *db.db.write(), None);
 		*self.overlay.write() = mem::replace(&mut *db.overlay.write(), Vec::new());
 		*self.flushing.write() = mem::replace(&mut *db.flushing.write(), Vec::new());
 		Ok(())
 	}
+
+	/// Appends a new, empty column to the database in place.
+	pub fn add_column(&self) -> Result<(), String> {
+		match *self.db.write() {
+			Some(DBAndColumns { ref mut db, ref mut cfs }) => {
+				let col = cfs.len() as u32;
+				let name = format!("col{}", col);
+				cfs.push(db.create_cf(&name, &col_config(col, &self.config))?);
+				self.overlay.write().push(HashMap::new());
+				self.flushing.write().push(HashMap::new());
+				Ok(())
+			},
+			None => Err("Database is closed".to_owned()),
+		}
+	}
+}
+
+impl KeyValueDB for Database {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>, String> {
+		Database::get(self, col, key)
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
+		Database::get_by_prefix(self, col, prefix)
+	}
+
+	fn write_buffered(&self, transaction: DBTransaction) {
+		Database::write_buffered(self, transaction)
+	}
+
+	fn write(&self, transaction: DBTransaction) -> Result<(), String> {
+		Database::write(self, transaction)
+	}
+
+	fn flush(&self) -> Result<(), String> {
+		Database::flush(self)
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>) -> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
+		Box::new(Database::iter(self, col))
+	}
+
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
+		-> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		let unboxed = Database::iter_from_prefix(self, col, prefix);
+		Box::new(unboxed.into_iter().flat_map(|inner| inner).take_while(move |&(ref key, _)| key.starts_with(prefix)))
+	}
+
+	fn iter_from<'a>(&'a self, col: Option<u32>, start: &'a [u8])
+		-> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		// seeking to a prefix positions the iterator at the first key not less than it.
+		let unboxed = Database::iter_from_prefix(self, col, start);
+		Box::new(unboxed.into_iter().flat_map(|inner| inner))
+	}
+
+	fn restore(&self, new_db: &str) -> Result<(), UtilError> {
+		Database::restore(self, new_db)
+	}
+
+	fn stats(&self) -> DatabaseStats {
+		Database::stats(self)
+	}
 }
 
 impl Drop for Database {
@@ -636,6 +957,10 @@ mod tests {
 	fn test_db(config: &DatabaseConfig) {
 		let path = RandomTempPath::create_dir();
 		let db = Database::open(config, path.as_path().to_str().unwrap()).unwrap();
+		test_kvdb(&db);
+	}
+
+	fn test_kvdb(db: &KeyValueDB) {
 		let key1 = H256::from_str("02c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc").unwrap();
 		let key2 = H256::from_str("03c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc").unwrap();
 		let key3 = H256::from_str("01c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc").unwrap();
@@ -693,6 +1018,38 @@ mod tests {
 		test_db(&DatabaseConfig::default());
 	}
 
+	#[test]
+	fn in_memory_kvdb() {
+		let db = in_memory(1);
+		test_kvdb(&db);
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"key", b"value");
+		db.write(batch).unwrap();
+
+		assert_eq!(&*db.get(Some(0), b"key").unwrap().unwrap(), b"value");
+		assert!(db.get(None, b"key").unwrap().is_none());
+		assert!(db.get(Some(1), b"key").is_err());
+		assert_eq!(db.stats().columns[1], (Some(0), ColumnStats { keys: 1, memtable_size: 8, ..Default::default() }));
+	}
+
+	#[test]
+	fn stats_count_pending_writes() {
+		let path = RandomTempPath::create_dir();
+		let db = Database::open(&DatabaseConfig::with_columns(Some(2)), path.as_path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(1), b"key", b"value");
+		db.write_buffered(batch);
+
+		let stats = db.stats();
+		assert_eq!(stats.columns.iter().map(|&(col, _)| col).collect::<Vec<_>>(), vec![None, Some(0), Some(1)]);
+		assert_eq!(stats.columns[2].1.pending_writes, 1);
+
+		db.flush().unwrap();
+		assert_eq!(db.stats().columns[2].1.pending_writes, 0);
+	}
+
 	#[test]
 	#[cfg(target_os = "linux")]
 	fn df_to_rotational() {
@@ -74,7 +74,7 @@ impl Batch {
 	pub fn commit(&mut self, dest: &mut Database) -> Result<(), Error> {
 		if self.inner.is_empty() { return Ok(()) }
 
-		let mut transaction = DBTransaction::new(dest);
+		let mut transaction = DBTransaction::new();
 
 		for keypair in &self.inner {
 			transaction.put(self.column, &keypair.0, &keypair.1);
@@ -129,6 +129,9 @@ pub trait Migration: 'static {
 	fn columns(&self) -> Option<u32>;
 	/// Version of the database after the migration.
 	fn version(&self) -> u32;
+	/// Whether the migration rewrites existing data. Migrations which only append
+	/// new, empty columns are applied in place without copying the database.
+	fn alters_existing(&self) -> bool { true }
 	/// Migrate a source to a destination.
 	fn migrate(&mut self, source: Arc<Database>, config: &Config, destination: &mut Database, col: Option<u32>) -> Result<(), Error>;
 }
@@ -224,7 +227,9 @@ impl Manager {
 	}
 
 	/// Performs migration in order, starting with a source path, migrating between two temporary databases,
-	/// and producing a path where the final migration lives.
+	/// and producing a path where the final migration lives. Migrations which don't alter existing data
+	/// are applied to the current database in place, so when all of them are in-place the returned path
+	/// is `old_path`.
 	pub fn execute(&mut self, old_path: &Path, version: u32) -> Result<PathBuf, Error> {
 		let config = self.config.clone();
 		let migrations = self.migrations_from(version);
@@ -244,7 +249,7 @@ impl Manager {
 
 		let db_root = database_path(old_path);
 		let mut temp_idx = TempIndex::One;
-		let mut temp_path = temp_idx.path(&db_root);
+		let mut temp_path = old_path.to_owned();
 
 		// start with the old db.
 		let old_path_str = old_path.to_str().ok_or(Error::MigrationImpossible)?;
@@ -256,6 +261,19 @@ impl Manager {
 			let current_columns = db_config.columns;
 			db_config.columns = migration.columns();
 
+			if !migration.alters_existing() {
+				// only new columns are added, so extend the current db in place.
+				let current = current_columns.unwrap_or(0);
+				let target = db_config.columns.unwrap_or(0);
+				if current_columns.is_none() || target < current {
+					return Err(Error::MigrationImpossible);
+				}
+				for _ in current..target {
+					cur_db.add_column().map_err(Error::Custom)?;
+				}
+				continue;
+			}
+
 			// open the target temporary database.
 			temp_path = temp_idx.path(&db_root);
 			let temp_path_str = temp_path.to_str().ok_or(Error::MigrationImpossible)?;
@@ -226,3 +226,45 @@ fn pre_columns() {
 	// short of the one before it.
 	manager.execute(&db_path, 0).unwrap();
 }
+
+struct ColumnsAddedInPlace;
+
+impl Migration for ColumnsAddedInPlace {
+	fn pre_columns(&self) -> Option<u32> { Some(1) }
+
+	fn columns(&self) -> Option<u32> { Some(3) }
+
+	fn version(&self) -> u32 { 1 }
+
+	fn alters_existing(&self) -> bool { false }
+
+	fn migrate(&mut self, _source: Arc<Database>, _config: &Config, _dest: &mut Database, _col: Option<u32>) -> Result<(), Error> {
+		panic!("columns are added in place");
+	}
+}
+
+#[test]
+fn columns_added_in_place() {
+	use kvdb::DatabaseConfig;
+
+	let dir = RandomTempPath::create_dir();
+	let db_path = db_path(dir.as_path());
+	{
+		let db = Database::open(&DatabaseConfig::with_columns(Some(1)), db_path.to_str().unwrap()).unwrap();
+		let mut transaction = db.transaction();
+		transaction.put(Some(0), &[1], &[2]);
+		db.write(transaction).unwrap();
+	}
+
+	let mut manager = Manager::new(Config::default());
+	manager.add_migration(ColumnsAddedInPlace).unwrap();
+	let end_path = manager.execute(&db_path, 0).unwrap();
+	assert_eq!(end_path, db_path);
+
+	let db = Database::open(&DatabaseConfig::with_columns(Some(3)), db_path.to_str().unwrap()).unwrap();
+	assert_eq!(&*db.get(Some(0), &[1]).unwrap().unwrap(), &[2]);
+	let mut transaction = db.transaction();
+	transaction.put(Some(2), &[3], &[4]);
+	db.write(transaction).unwrap();
+	assert_eq!(&*db.get(Some(2), &[3]).unwrap().unwrap(), &[4]);
+}
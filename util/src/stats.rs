@@ -59,11 +59,43 @@ impl Histogram {
 	}
 }
 
+/// Values at given percentiles (0 to 100) of the corpus, where each value carries a weight.
+///
+/// Zero weights are treated as equal weights. Returns zeros for empty corpus.
+pub fn weighted_percentiles(mut corpus: Vec<(U256, U256)>, percentiles: &[f64]) -> Vec<U256> {
+	if corpus.is_empty() {
+		return vec![U256::zero(); percentiles.len()];
+	}
+
+	corpus.sort_by(|a, b| a.0.cmp(&b.0));
+	if corpus.iter().all(|&(_, weight)| weight.is_zero()) {
+		for sample in &mut corpus {
+			sample.1 = U256::one();
+		}
+	}
+
+	let total = corpus.iter().fold(U256::zero(), |acc, &(_, weight)| acc + weight);
+	percentiles.iter().map(|percentile| {
+		// Percentile in basis points to avoid floating point arithmetic on weights.
+		let basis_points = (percentile.max(0.0).min(100.0) * 100.0) as u64;
+		let threshold = total * basis_points.into() / 10_000.into();
+
+		let mut cumulative = U256::zero();
+		for &(value, weight) in &corpus {
+			cumulative = cumulative + weight;
+			if cumulative >= threshold {
+				return value;
+			}
+		}
+		corpus.last().expect("corpus is not empty; qed").0
+	}).collect()
+}
+
 
 #[cfg(test)]
 mod tests {
 	use bigint::prelude::U256;
-	use super::Histogram;
+	use super::{Histogram, weighted_percentiles};
 
 	#[test]
 	fn check_histogram() {
@@ -100,4 +132,23 @@ mod tests {
 	fn none_when_too_few_data() {
 		assert!(Histogram::new(slice_into![], 1).is_none());
 	}
+
+	#[test]
+	fn should_calculate_weighted_percentiles() {
+		let corpus: Vec<(U256, U256)> = vec![(30.into(), 100.into()), (10.into(), 100.into()), (20.into(), 800.into())];
+		let expected: Vec<U256> = vec_into![10, 10, 20, 30, 30];
+		assert_eq!(weighted_percentiles(corpus, &[0.0, 10.0, 50.0, 95.0, 100.0]), expected);
+	}
+
+	#[test]
+	fn should_use_equal_weights_when_all_weights_are_zero() {
+		let corpus: Vec<(U256, U256)> = vec![(10.into(), 0.into()), (20.into(), 0.into()), (30.into(), 0.into()), (40.into(), 0.into())];
+		let expected: Vec<U256> = vec_into![10, 20, 30];
+		assert_eq!(weighted_percentiles(corpus, &[25.0, 50.0, 75.0]), expected);
+	}
+
+	#[test]
+	fn should_return_zeros_for_empty_corpus() {
+		assert_eq!(weighted_percentiles(Vec::new(), &[10.0, 90.0]), vec![U256::zero(); 2]);
+	}
 }
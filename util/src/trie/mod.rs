@@ -34,6 +34,8 @@ pub mod sectriedb;
 pub mod sectriedbmut;
 /// Trie query recording.
 pub mod recorder;
+/// Merkle proofs of trie values.
+pub mod proof;
 
 
 mod fatdb;
@@ -48,6 +50,7 @@ pub use self::sectriedb::SecTrieDB;
 pub use self::fatdb::{FatDB, FatDBIterator};
 pub use self::fatdbmut::FatDBMut;
 pub use self::recorder::Recorder;
+pub use self::proof::{verify_proof, verify_multi_proof};
 
 /// Trie Errors.
 ///
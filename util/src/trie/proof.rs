@@ -0,0 +1,269 @@
+// Copyright 2015, 2016 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Merkle proofs of trie values.
+//!
+//! A proof is the list of nodes visited while looking a key up, starting with the root
+//! node. It proves either the value of the key or its absence. Proofs of several keys
+//! of one trie can be merged into a compact proof which holds every node only once.
+//!
+//! Verification needs no `HashDB`: nodes are found by their hashes and decoded as
+//! untrusted data, so a malformed proof is an error and never a panic.
+
+use std::collections::{HashMap, HashSet};
+
+use bytes::Bytes;
+use hash::H256;
+use hashdb::DBValue;
+use nibbleslice::NibbleSlice;
+use rlp::{DecoderError, Prototype, UntrustedRlp, View};
+use sha3::{Hashable, SHA3_NULL_RLP};
+use super::node::Node;
+use super::recorder::Recorder;
+use super::{Trie, TrieDB, SecTrieDB, TrieError};
+
+impl<'db> TrieDB<'db> {
+	/// Produce a proof of the value of `key`, or of its absence.
+	pub fn get_proof(&self, key: &[u8]) -> super::Result<Vec<Bytes>> {
+		let mut recorder = Recorder::new();
+		self.get_with(key, &mut recorder)?;
+		Ok(recorder.drain().into_iter().map(|record| record.data).collect())
+	}
+
+	/// Produce a compact proof of the values of all `keys`.
+	pub fn get_multi_proof<K: AsRef<[u8]>>(&self, keys: &[K]) -> super::Result<Vec<Bytes>> {
+		let mut recorder = Recorder::new();
+		for key in keys {
+			self.get_with(key.as_ref(), &mut recorder)?;
+		}
+
+		let mut seen = HashSet::new();
+		Ok(recorder.drain().into_iter()
+			.filter(|record| seen.insert(record.hash))
+			.map(|record| record.data)
+			.collect())
+	}
+}
+
+impl<'db> SecTrieDB<'db> {
+	/// Produce a proof of the value of `key`, or of its absence.
+	/// It is checked against the hashed key: `verify_proof(root, &key.sha3(), proof)`.
+	pub fn get_proof(&self, key: &[u8]) -> super::Result<Vec<Bytes>> {
+		self.raw().get_proof(&key.sha3())
+	}
+}
+
+/// Check a proof of `key` in the trie with the given root. Returns the proven value,
+/// or `None` if the proof shows that the key is absent. Fails if a node on the path
+/// to the key is missing from the proof or malformed; unused nodes are ignored.
+pub fn verify_proof(root: &H256, key: &[u8], proof: &[Bytes]) -> super::Result<Option<DBValue>> {
+	look_up(&proof_nodes(proof), root, key)
+}
+
+/// Check a compact proof of all `keys` in the trie with the given root.
+/// Returns the proven values in the order of the keys.
+pub fn verify_multi_proof<K: AsRef<[u8]>>(root: &H256, keys: &[K], proof: &[Bytes]) -> super::Result<Vec<Option<DBValue>>> {
+	let nodes = proof_nodes(proof);
+	keys.iter().map(|key| look_up(&nodes, root, key.as_ref())).collect()
+}
+
+fn proof_nodes(proof: &[Bytes]) -> HashMap<H256, &[u8]> {
+	proof.iter().map(|node| (node.sha3(), &node[..])).collect()
+}
+
+// same walk as `Lookup`, over untrusted nodes.
+fn look_up(nodes: &HashMap<H256, &[u8]>, root: &H256, key: &[u8]) -> super::Result<Option<DBValue>> {
+	if *root == SHA3_NULL_RLP {
+		return Ok(None);
+	}
+
+	let mut key = NibbleSlice::new(key);
+	let mut hash = *root;
+
+	// this loop iterates through non-inline nodes.
+	for depth in 0.. {
+		let missing = |hash| Box::new(match depth {
+			0 => TrieError::InvalidStateRoot(hash),
+			_ => TrieError::IncompleteDatabase(hash),
+		});
+
+		let mut node_data = match nodes.get(&hash) {
+			Some(data) => *data,
+			None => return Err(missing(hash)),
+		};
+
+		// this loop iterates through all inline children without incrementing the depth.
+		loop {
+			let node = match decode_node(node_data) {
+				Ok(node) => node,
+				Err(_) => return Err(missing(hash)),
+			};
+
+			match node {
+				Node::Leaf(slice, value) => {
+					return Ok(match slice == key {
+						true => Some(DBValue::from_slice(value)),
+						false => None,
+					})
+				}
+				Node::Extension(slice, item) => {
+					if key.starts_with(&slice) {
+						node_data = item;
+						key = key.mid(slice.len());
+					} else {
+						return Ok(None)
+					}
+				}
+				Node::Branch(children, value) => match key.is_empty() {
+					true => return Ok(value.map(DBValue::from_slice)),
+					false => {
+						node_data = children[key.at(0) as usize];
+						key = key.mid(1);
+					}
+				},
+				Node::Empty => return Ok(None),
+			}
+
+			// check if new node data is inline or hash.
+			let r = UntrustedRlp::new(node_data);
+			if r.is_data() && r.size() == 32 {
+				hash = match r.as_val() {
+					Ok(hash) => hash,
+					Err(_) => return Err(missing(hash)),
+				};
+				break
+			}
+		}
+	}
+	Ok(None)
+}
+
+// like `Node::decoded`, but failing on invalid data instead of panicking.
+fn decode_node(data: &[u8]) -> Result<Node, DecoderError> {
+	let r = UntrustedRlp::new(data);
+	match r.prototype()? {
+		Prototype::List(2) => {
+			let encoded = r.at(0)?.data()?;
+			if encoded.is_empty() {
+				return Err(DecoderError::RlpIsTooShort);
+			}
+			Ok(match NibbleSlice::from_encoded(encoded) {
+				(slice, true) => Node::Leaf(slice, r.at(1)?.data()?),
+				(slice, false) => Node::Extension(slice, r.at(1)?.as_raw()),
+			})
+		},
+		Prototype::List(17) => {
+			let mut nodes = [&[] as &[u8]; 16];
+			for i in 0..16 {
+				nodes[i] = r.at(i)?.as_raw();
+			}
+			let value = r.at(16)?;
+			Ok(Node::Branch(nodes, if value.is_empty() { None } else { Some(value.data()?) }))
+		},
+		Prototype::Data(0) => Ok(Node::Empty),
+		_ => Err(DecoderError::RlpIncorrectListLen),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use memorydb::MemoryDB;
+	use hash::FixedHash;
+	use super::super::{TrieMut, TrieDBMut, SecTrieDBMut};
+	use super::*;
+
+	fn populate(db: &mut MemoryDB, root: &mut H256) -> Vec<(Vec<u8>, Vec<u8>)> {
+		let items: Vec<_> = (0..200u32)
+			.map(|i| (format!("key{}", i * 7).into_bytes(), format!("value{}", i).into_bytes()))
+			.chain(Some((b"k".to_vec(), b"inline".to_vec())))
+			.collect();
+		let mut trie = TrieDBMut::new(db, root);
+		for &(ref key, ref value) in &items {
+			trie.insert(key, value).unwrap();
+		}
+		items
+	}
+
+	#[test]
+	fn proves_values_and_absence() {
+		let (mut db, mut root) = (MemoryDB::new(), H256::new());
+		let items = populate(&mut db, &mut root);
+		let trie = TrieDB::new(&db, &root).unwrap();
+
+		for &(ref key, ref value) in &items {
+			let proof = trie.get_proof(key).unwrap();
+			assert_eq!(verify_proof(&root, key, &proof).unwrap().map(|v| v.to_vec()), Some(value.clone()));
+		}
+
+		for key in &[&b"key1"[..], b"missing", b""] {
+			let proof = trie.get_proof(key).unwrap();
+			assert_eq!(verify_proof(&root, key, &proof).unwrap(), None);
+		}
+
+		assert_eq!(verify_proof(&SHA3_NULL_RLP, b"key0", &[]).unwrap(), None);
+	}
+
+	#[test]
+	fn rejects_bad_proofs() {
+		let (mut db, mut root) = (MemoryDB::new(), H256::new());
+		populate(&mut db, &mut root);
+		let trie = TrieDB::new(&db, &root).unwrap();
+		let mut proof = trie.get_proof(b"key7").unwrap();
+
+		let wrong_root = H256::random();
+		assert_eq!(*verify_proof(&wrong_root, b"key7", &proof).unwrap_err(), TrieError::InvalidStateRoot(wrong_root));
+
+		let last = proof.pop().unwrap();
+		assert_eq!(*verify_proof(&root, b"key7", &proof).unwrap_err(), TrieError::IncompleteDatabase(last.sha3()));
+
+		let garbage = vec![0xf8, 0xff, 0x00];
+		assert!(verify_proof(&garbage.sha3(), b"key7", &[garbage]).is_err());
+	}
+
+	#[test]
+	fn compact_multi_proofs() {
+		let (mut db, mut root) = (MemoryDB::new(), H256::new());
+		let items = populate(&mut db, &mut root);
+		let trie = TrieDB::new(&db, &root).unwrap();
+
+		let mut keys: Vec<Vec<u8>> = items.iter().take(20).map(|&(ref key, _)| key.clone()).collect();
+		keys.push(b"missing".to_vec());
+
+		let proof = trie.get_multi_proof(&keys).unwrap();
+		let separate: usize = keys.iter().map(|key| trie.get_proof(key).unwrap().len()).sum();
+		assert!(proof.len() < separate);
+
+		let values = verify_multi_proof(&root, &keys, &proof).unwrap();
+		let mut expected: Vec<_> = items.iter().take(20).map(|&(_, ref value)| Some(value.clone())).collect();
+		expected.push(None);
+		assert_eq!(values.into_iter().map(|v| v.map(|v| v.to_vec())).collect::<Vec<_>>(), expected);
+	}
+
+	#[test]
+	fn secure_trie_proofs() {
+		let (mut db, mut root) = (MemoryDB::new(), H256::new());
+		{
+			let mut trie = SecTrieDBMut::new(&mut db, &mut root);
+			for i in 0..50u8 {
+				trie.insert(&[i], &[i; 40]).unwrap();
+			}
+		}
+
+		let trie = SecTrieDB::new(&db, &root).unwrap();
+		let proof = trie.get_proof(&[5]).unwrap();
+		assert_eq!(verify_proof(&root, &[5u8].sha3(), &proof).unwrap().map(|v| v.to_vec()), Some(vec![5; 40]));
+	}
+}